@@ -0,0 +1,195 @@
+//! Encryption at rest for docs in encrypted folders
+//!
+//! Docs are sealed with XChaCha20-Poly1305 under a key derived from the
+//! vault passphrase with Argon2id. The salt and a check value live in
+//! `.vault-key.json` at the contexts root; the passphrase itself is never
+//! written to the vault, so a lost passphrase cannot be recovered.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{CoreError, CoreResult};
+
+pub const VAULT_KEY_FILE: &str = ".vault-key.json";
+
+/// Prefix of every encrypted file, followed by the nonce and ciphertext
+const MAGIC: &[u8] = b"OCENC1\n";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+/// Sealed into the key file so a wrong passphrase is caught on unlock
+/// rather than on the first doc read
+const CHECK_TEXT: &[u8] = b"opencontext vault";
+
+/// Keys of unlocked vaults by contexts root, shared by every context and
+/// indexer in the process
+static UNLOCKED: Mutex<Vec<(PathBuf, [u8; 32])>> = parking_lot::const_mutex(Vec::new());
+
+#[derive(Serialize, Deserialize)]
+struct VaultKeyFile {
+    version: u32,
+    salt: String,
+    check: String,
+}
+
+/// Whether `bytes` are the contents of an encrypted doc
+pub(crate) fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub(crate) fn is_unlocked(contexts_root: &Path) -> bool {
+    UNLOCKED
+        .lock()
+        .iter()
+        .any(|(root, _)| root == contexts_root)
+}
+
+/// Whether a passphrase has been set for the vault
+pub(crate) fn has_passphrase(contexts_root: &Path) -> bool {
+    contexts_root.join(VAULT_KEY_FILE).is_file()
+}
+
+/// Derive the vault key from `passphrase` and keep it for this process.
+/// The first unlock sets the passphrase. Returns whether it was set now.
+pub(crate) fn unlock(contexts_root: &Path, passphrase: &str) -> CoreResult<bool> {
+    if passphrase.is_empty() {
+        return Err(CoreError::Message("Vault passphrase is required.".into()));
+    }
+    let key_path = contexts_root.join(VAULT_KEY_FILE);
+    let (key, created) = match fs::read_to_string(&key_path) {
+        Ok(content) => {
+            let file: VaultKeyFile = serde_json::from_str(&content)
+                .map_err(|e| CoreError::Message(format!("Vault key file is unreadable: {e}")))?;
+            let salt = hex_decode(&file.salt).ok_or_else(|| bad_key_file("salt"))?;
+            let check = hex_decode(&file.check).ok_or_else(|| bad_key_file("check"))?;
+            let key = derive_key(passphrase, &salt)?;
+            match open(&key, &check) {
+                Some(text) if text == CHECK_TEXT => (key, false),
+                _ => return Err(CoreError::WrongPassphrase),
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let key = derive_key(passphrase, &salt)?;
+            let file = VaultKeyFile {
+                version: 1,
+                salt: hex_encode(&salt),
+                check: hex_encode(&seal(&key, CHECK_TEXT)?),
+            };
+            let content = serde_json::to_string_pretty(&file)
+                .map_err(|e| CoreError::Message(format!("Failed to encode vault key: {e}")))?;
+            fs::write(&key_path, content)?;
+            (key, true)
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut unlocked = UNLOCKED.lock();
+    unlocked.retain(|(root, _)| root != contexts_root);
+    unlocked.push((contexts_root.to_path_buf(), key));
+    Ok(created)
+}
+
+/// Forget the vault key. Returns whether the vault was unlocked.
+pub(crate) fn lock(contexts_root: &Path) -> bool {
+    let mut unlocked = UNLOCKED.lock();
+    let before = unlocked.len();
+    unlocked.retain(|(root, _)| root != contexts_root);
+    unlocked.len() != before
+}
+
+/// Encrypt doc contents with the vault key
+pub(crate) fn encrypt(contexts_root: &Path, rel_path: &str, plain: &[u8]) -> CoreResult<Vec<u8>> {
+    let key = vault_key(contexts_root, rel_path)?;
+    seal(&key, plain)
+}
+
+/// Contents of an encrypted doc, or `bytes` as they are when not encrypted
+pub(crate) fn decrypt(contexts_root: &Path, rel_path: &str, bytes: Vec<u8>) -> CoreResult<Vec<u8>> {
+    if !is_encrypted(&bytes) {
+        return Ok(bytes);
+    }
+    let key = vault_key(contexts_root, rel_path)?;
+    open(&key, &bytes).ok_or_else(|| {
+        CoreError::Message(format!(
+            "\"{rel_path}\" could not be decrypted with the vault key. It was encrypted with another passphrase or has been damaged."
+        ))
+    })
+}
+
+/// Read a doc as text, decrypting it when encrypted
+pub(crate) fn read_text(
+    contexts_root: &Path,
+    rel_path: &str,
+    abs_path: &Path,
+) -> CoreResult<String> {
+    let bytes = decrypt(contexts_root, rel_path, fs::read(abs_path)?)?;
+    String::from_utf8(bytes)
+        .map_err(|e| CoreError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+}
+
+fn vault_key(contexts_root: &Path, rel_path: &str) -> CoreResult<[u8; 32]> {
+    UNLOCKED
+        .lock()
+        .iter()
+        .find(|(root, _)| root == contexts_root)
+        .map(|(_, key)| *key)
+        .ok_or_else(|| CoreError::Locked {
+            path: rel_path.to_string(),
+        })
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> CoreResult<[u8; 32]> {
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| CoreError::Message(format!("Failed to derive vault key: {e}")))?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], plain: &[u8]) -> CoreResult<Vec<u8>> {
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain)
+        .map_err(|_| CoreError::Message("Failed to encrypt doc.".into()))?;
+    let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn open(key: &[u8; 32], sealed: &[u8]) -> Option<Vec<u8>> {
+    let body = sealed.strip_prefix(MAGIC)?;
+    if body.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .ok()
+}
+
+fn bad_key_file(field: &str) -> CoreError {
+    CoreError::Message(format!("Vault key file has an invalid {field}."))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,112 @@
+//! File types of the documents in the vault
+
+use std::fmt;
+use std::path::Path;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdx"];
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "text", "log", "csv", "tsv", "json", "jsonl", "yaml", "yml", "toml", "ini", "xml",
+    "html", "htm", "rst", "org", "tex", "rs", "js", "ts", "jsx", "tsx", "py", "go", "java", "c",
+    "h", "cpp", "hpp", "rb", "sh", "sql", "css",
+];
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff", "ico", "heic", "avif", "svg",
+];
+const BINARY_EXTENSIONS: &[&str] = &[
+    "zip", "gz", "tgz", "7z", "rar", "tar", "exe", "dll", "so", "dylib", "bin", "dmg", "iso",
+    "doc", "docx", "xls", "xlsx", "ppt", "pptx", "key", "pages", "numbers", "epub", "mp3", "mp4",
+    "mov", "wav", "flac", "ogg", "webm", "avi", "ttf", "otf", "woff", "woff2", "sqlite", "db",
+];
+
+/// Bytes read to tell text from binary when the extension is unknown
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// What a file in the vault holds, which decides whether it can be opened
+/// as text and how it is indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocKind {
+    Markdown,
+    /// Plain text other than markdown (.txt, .csv, source code, ...)
+    Text,
+    Pdf,
+    Image,
+    /// Anything else that is not text
+    Binary,
+}
+
+impl DocKind {
+    /// Classify by extension alone, so listing docs never opens them.
+    /// Files with no or an unknown extension count as markdown, the app's
+    /// own format, until [`DocKind::sniff`] sees their bytes.
+    pub fn detect(path: &Path) -> Self {
+        Self::by_extension(path).unwrap_or(Self::Markdown)
+    }
+
+    /// Classify a file whose bytes are already loaded: an unknown extension
+    /// is told apart by whether `bytes` look like text.
+    pub fn sniff(path: &Path, bytes: &[u8]) -> Self {
+        Self::by_extension(path).unwrap_or_else(|| {
+            let head = &bytes[..bytes.len().min(SNIFF_BYTES)];
+            if looks_like_text(head) {
+                Self::Markdown
+            } else {
+                Self::Binary
+            }
+        })
+    }
+
+    fn by_extension(path: &Path) -> Option<Self> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)?;
+        match ext.as_str() {
+            ext if MARKDOWN_EXTENSIONS.contains(&ext) => Some(Self::Markdown),
+            ext if TEXT_EXTENSIONS.contains(&ext) => Some(Self::Text),
+            "pdf" => Some(Self::Pdf),
+            ext if IMAGE_EXTENSIONS.contains(&ext) => Some(Self::Image),
+            ext if BINARY_EXTENSIONS.contains(&ext) => Some(Self::Binary),
+            _ => None,
+        }
+    }
+
+    /// Whether the file can be read and edited as text
+    pub fn is_text(self) -> bool {
+        matches!(self, Self::Markdown | Self::Text)
+    }
+
+    /// Why the search index leaves files of this kind out, if it does
+    pub fn skip_reason(self) -> Option<&'static str> {
+        match self {
+            Self::Markdown | Self::Text => None,
+            Self::Pdf if cfg!(feature = "pdf") => None,
+            Self::Pdf => Some("PDF text extraction is not enabled in this build"),
+            Self::Image => Some("images have no text to index"),
+            Self::Binary => Some("binary file"),
+        }
+    }
+}
+
+impl fmt::Display for DocKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Markdown => "markdown",
+            Self::Text => "text",
+            Self::Pdf => "PDF",
+            Self::Image => "image",
+            Self::Binary => "binary",
+        })
+    }
+}
+
+/// UTF-8 without NUL bytes. `head` may end inside a character.
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
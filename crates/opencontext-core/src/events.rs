@@ -48,6 +48,22 @@ pub enum FolderEvent {
     },
 }
 
+impl FolderEvent {
+    /// Old and new path of a renamed or moved folder, the prefix every
+    /// affected doc path changed from and to
+    pub fn renamed_prefix(&self) -> Option<(&str, &str)> {
+        match self {
+            FolderEvent::Renamed {
+                old_path, new_path, ..
+            }
+            | FolderEvent::Moved {
+                old_path, new_path, ..
+            } => Some((old_path, new_path)),
+            _ => None,
+        }
+    }
+}
+
 /// Combined event type
 #[derive(Debug, Clone)]
 pub enum Event {
@@ -0,0 +1,242 @@
+//! Per-folder defaults, stored as `.folder.json` in the folder
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CoreError, CoreResult};
+
+pub const FOLDER_SETTINGS_FILE: &str = ".folder.json";
+
+/// How eagerly edits to a doc are picked up by the index
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexPriority {
+    /// Indexed first in full builds and re-indexed as soon as it changes
+    High,
+    /// Re-indexed with the next batch of changes
+    #[default]
+    Normal,
+    /// Left out of the index
+    Skip,
+}
+
+/// Settings a folder passes down to its docs and subfolders
+///
+/// Unset keys are inherited. Precedence is doc override, then the nearest
+/// folder up to the contexts root, then the app's global settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderSettings {
+    /// System prompt for AI chats about docs here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_ai_prompt: Option<String>,
+    /// Leave docs out of generated manifests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_from_manifest: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_priority: Option<IndexPriority>,
+    /// Prompt template the UI preselects for docs here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_template: Option<String>,
+    /// Keep docs here out of background AI calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private: Option<bool>,
+    /// Keep docs here encrypted at rest. Set with
+    /// `OpenContext::set_folder_encrypted`, which also rewrites the docs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<bool>,
+    /// Let encrypted docs into the search index and manifests while the
+    /// vault is unlocked. The index stores their text unencrypted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_when_unlocked: Option<bool>,
+    /// Refuse creating and saving docs here through the agent tool bridge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+}
+
+impl FolderSettings {
+    /// `self` with unset keys taken from `parent`
+    pub fn inherit(self, parent: &FolderSettings) -> FolderSettings {
+        FolderSettings {
+            default_ai_prompt: self
+                .default_ai_prompt
+                .or_else(|| parent.default_ai_prompt.clone()),
+            exclude_from_manifest: self.exclude_from_manifest.or(parent.exclude_from_manifest),
+            index_priority: self.index_priority.or(parent.index_priority),
+            default_template: self
+                .default_template
+                .or_else(|| parent.default_template.clone()),
+            private: self.private.or(parent.private),
+            encrypted: self.encrypted.or(parent.encrypted),
+            index_when_unlocked: self.index_when_unlocked.or(parent.index_when_unlocked),
+            read_only: self.read_only.or(parent.read_only),
+        }
+    }
+
+    /// What docs under a folder whose `.folder.json` can't be read are held
+    /// to: kept out of AI calls, manifests and the index, saved encrypted
+    /// and closed to agents
+    pub fn fail_closed() -> FolderSettings {
+        FolderSettings {
+            exclude_from_manifest: Some(true),
+            index_priority: Some(IndexPriority::Skip),
+            private: Some(true),
+            encrypted: Some(true),
+            read_only: Some(true),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn excluded_from_manifest(&self) -> bool {
+        self.exclude_from_manifest.unwrap_or(false)
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private.unwrap_or(false)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted.unwrap_or(false)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.unwrap_or(false)
+    }
+
+    /// Whether docs here may be indexed or listed in manifests: always for
+    /// unencrypted folders, and for encrypted ones only while the vault is
+    /// unlocked and the folder opts in
+    pub fn searchable(&self, unlocked: bool) -> bool {
+        !self.is_encrypted() || (unlocked && self.index_when_unlocked.unwrap_or(false))
+    }
+
+    pub fn index_priority(&self) -> IndexPriority {
+        self.index_priority.unwrap_or_default()
+    }
+}
+
+/// Contents of a `.folder.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FolderSettingsFile {
+    #[serde(flatten)]
+    pub folder: FolderSettings,
+    /// Overrides for single docs in the folder, by file name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub docs: BTreeMap<String, FolderSettings>,
+}
+
+impl FolderSettingsFile {
+    /// Settings of the folder at `dir`. A missing file counts as empty; a
+    /// malformed one is an error, so it is never mistaken for "nothing set"
+    /// and saved over.
+    pub fn load(dir: &Path) -> CoreResult<Self> {
+        let path = dir.join(FOLDER_SETTINGS_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&content).map_err(|e| {
+            CoreError::Message(format!(
+                "Failed to read folder settings in \"{}\": {e}. Fix or remove the file.",
+                path.display()
+            ))
+        })
+    }
+
+    /// Write the file through a temp file, or remove it once nothing is set
+    pub fn save(&self, dir: &Path) -> CoreResult<()> {
+        let path = dir.join(FOLDER_SETTINGS_FILE);
+        if self.folder.is_empty() && self.docs.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| CoreError::Message(format!("Failed to encode folder settings: {e}")))?;
+        let tmp = dir.join(format!("{FOLDER_SETTINGS_FILE}.tmp"));
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+struct ResolvedFolder {
+    settings: FolderSettings,
+    docs: BTreeMap<String, FolderSettings>,
+}
+
+/// Resolves inherited settings for many paths, reading each `.folder.json`
+/// once. A folder whose file can't be read, and everything under it,
+/// resolves to that error.
+pub struct SettingsResolver<'a> {
+    contexts_root: &'a Path,
+    folders: HashMap<String, Result<ResolvedFolder, String>>,
+}
+
+impl<'a> SettingsResolver<'a> {
+    pub fn new(contexts_root: &'a Path) -> Self {
+        Self {
+            contexts_root,
+            folders: HashMap::new(),
+        }
+    }
+
+    fn resolve(&mut self, folder_rel_path: &str) -> CoreResult<&ResolvedFolder> {
+        if !self.folders.contains_key(folder_rel_path) {
+            let resolved = self.load(folder_rel_path).map_err(|e| e.to_string());
+            if let Err(e) = &resolved {
+                log::warn!("[Settings] {}", e);
+            }
+            self.folders.insert(folder_rel_path.to_string(), resolved);
+        }
+        self.folders[folder_rel_path]
+            .as_ref()
+            .map_err(|e| CoreError::Message(e.clone()))
+    }
+
+    fn load(&mut self, folder_rel_path: &str) -> CoreResult<ResolvedFolder> {
+        let file = FolderSettingsFile::load(&self.contexts_root.join(folder_rel_path))?;
+        let settings = if folder_rel_path.is_empty() {
+            file.folder
+        } else {
+            let parent = folder_rel_path.rsplit_once('/').map_or("", |(p, _)| p);
+            let inherited = self.resolve(parent)?.settings.clone();
+            file.folder.inherit(&inherited)
+        };
+        Ok(ResolvedFolder {
+            settings,
+            docs: file.docs,
+        })
+    }
+
+    /// Settings of a folder ("" for the root) with inherited keys filled in
+    pub fn folder(&mut self, folder_rel_path: &str) -> CoreResult<FolderSettings> {
+        Ok(self.resolve(folder_rel_path)?.settings.clone())
+    }
+
+    /// Settings of a doc: its override in the folder's file, then the folder
+    pub fn doc(&mut self, doc_rel_path: &str) -> CoreResult<FolderSettings> {
+        let (folder, name) = doc_rel_path.rsplit_once('/').unwrap_or(("", doc_rel_path));
+        let resolved = self.resolve(folder)?;
+        Ok(match resolved.docs.get(name) {
+            Some(doc) => doc.clone().inherit(&resolved.settings),
+            None => resolved.settings.clone(),
+        })
+    }
+
+    /// [`Self::doc`], with [`FolderSettings::fail_closed`] for docs whose
+    /// settings can't be read, for callers that only filter docs out
+    pub fn doc_or_closed(&mut self, doc_rel_path: &str) -> FolderSettings {
+        self.doc(doc_rel_path)
+            .unwrap_or_else(|_| FolderSettings::fail_closed())
+    }
+}
@@ -5,8 +5,12 @@ mod tests;
 
 use chrono::{SecondsFormat, Utc};
 use parking_lot::Mutex;
-use rusqlite::{params, Connection, OptionalExtension};
-use std::{env, fs, path::PathBuf, sync::Arc};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use thiserror::Error;
 
 // Events module (enabled with "search" feature)
@@ -17,13 +21,52 @@ pub mod events;
 #[cfg(feature = "search")]
 pub mod search;
 
+// Retries for HTTP requests (enabled with "search" feature)
+#[cfg(feature = "search")]
+pub mod retry;
+
 #[cfg(feature = "search")]
 use events::{DocEvent, FolderEvent, SharedEventBus};
 
+mod crypto;
+mod doc_kind;
+mod folder_settings;
+mod migrate;
+mod snapshot;
+mod stats;
+mod tags;
+mod vault_path;
+pub use crypto::VAULT_KEY_FILE;
+pub use doc_kind::DocKind;
+pub use folder_settings::{
+    FolderSettings, FolderSettingsFile, IndexPriority, SettingsResolver, FOLDER_SETTINGS_FILE,
+};
+pub use migrate::{CONFIG_VERSION_KEY, VAULT_META_FILE};
+pub use snapshot::{
+    list_snapshots, prune_snapshots, snapshot_text_as_of, SnapshotFile, SnapshotInfo,
+    SnapshotManifest, SnapshotRestore,
+};
+pub use stats::{
+    DocEdits, DocWords, TagCount, VaultStats, VaultStatsOptions, WeekStats, WordStats,
+};
+pub use vault_path::VaultPath;
+
 #[derive(Debug, Error)]
 pub enum CoreError {
     #[error("{0}")]
     Message(String),
+    #[error("Invalid name \"{name}\": {reason}.")]
+    InvalidName { name: String, reason: String },
+    #[error("Invalid path \"{path}\": {reason}.")]
+    InvalidPath { path: String, reason: String },
+    #[error("\"{path}\" is a {kind} file and cannot be opened as text.")]
+    NotText { path: String, kind: DocKind },
+    #[error("\"{path}\" is in an encrypted folder and the vault is locked. Unlock it with the vault passphrase; there is no way to recover the contents without it.")]
+    Locked { path: String },
+    #[error("\"{path}\" is in a read-only folder.")]
+    ReadOnly { path: String },
+    #[error("Wrong vault passphrase. Encrypted docs can only be read with the passphrase that encrypted them, and a lost passphrase cannot be recovered.")]
+    WrongPassphrase,
     #[error("database error: {0}")]
     Db(#[from] rusqlite::Error),
     #[error("io error: {0}")]
@@ -32,11 +75,17 @@ pub enum CoreError {
 
 pub type CoreResult<T> = Result<T, CoreError>;
 
+/// Read-only connections opened next to the writer, so lookups don't queue
+/// behind each other or behind a write.
+const READ_CONNECTIONS: usize = 4;
+
 #[derive(Clone)]
 pub struct OpenContext {
     contexts_root: PathBuf,
     db_path: PathBuf,
     conn: Arc<Mutex<Connection>>,
+    readers: Arc<Vec<Mutex<Connection>>>,
+    text_stats: Arc<stats::TextStatsCache>,
     #[cfg(feature = "search")]
     event_bus: Option<SharedEventBus>,
 }
@@ -77,6 +126,7 @@ pub struct Doc {
     pub stable_id: String,
     pub created_at: String,
     pub updated_at: String,
+    pub kind: DocKind,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -87,6 +137,10 @@ pub struct DocManifestEntry {
     pub stable_id: String,
     pub description: String,
     pub updated_at: String,
+    pub kind: DocKind,
+    /// Why search leaves this file out, e.g. for images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<&'static str>,
 }
 
 impl OpenContext {
@@ -109,9 +163,12 @@ impl OpenContext {
         if let Some(parent) = db_path.parent() {
             fs::create_dir_all(parent)?;
         }
+        migrate::run(&base_root, &contexts_root)?;
 
         let conn = Connection::open(&db_path)?;
         conn.pragma_update(None, "foreign_keys", "ON")?;
+        // WAL lets the read connections run alongside the writer.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
         conn.execute_batch(
             "
             CREATE TABLE IF NOT EXISTS folders (
@@ -141,10 +198,23 @@ impl OpenContext {
 
         ensure_schema_migrations(&conn)?;
 
+        let readers = (0..READ_CONNECTIONS)
+            .map(|_| {
+                let reader = Connection::open_with_flags(
+                    &db_path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                reader.busy_timeout(std::time::Duration::from_secs(5))?;
+                Ok(Mutex::new(reader))
+            })
+            .collect::<CoreResult<Vec<_>>>()?;
+
         Ok(Self {
             contexts_root,
             db_path,
             conn: Arc::new(Mutex::new(conn)),
+            readers: Arc::new(readers),
+            text_stats: Arc::default(),
             #[cfg(feature = "search")]
             event_bus: None,
         })
@@ -184,7 +254,7 @@ impl OpenContext {
         if cleaned.is_empty() {
             return Err(CoreError::Message("stable_id is required.".into()));
         }
-        self.with_conn(|conn| {
+        self.with_read_conn(|conn| {
             let mut stmt = conn.prepare(
                 "SELECT id, folder_id, name, rel_path, abs_path, description, stable_id, created_at, updated_at
                  FROM docs WHERE stable_id = ?1",
@@ -227,7 +297,7 @@ impl OpenContext {
     }
 
     pub fn list_folders(&self, all: bool) -> CoreResult<Vec<Folder>> {
-        self.with_conn(|conn| {
+        self.with_read_conn(|conn| {
             let query = if all {
                 "SELECT id, parent_id, name, rel_path, abs_path, description, created_at, updated_at FROM folders ORDER BY rel_path"
             } else {
@@ -252,6 +322,11 @@ impl OpenContext {
                 "Cannot create root folder. Provide a sub-path like \"project-a\".".into(),
             ));
         }
+        let rel_path = rel_path
+            .split('/')
+            .map(normalize_name)
+            .collect::<CoreResult<Vec<_>>>()?
+            .join("/");
         let parent_path = parent_rel_path(&rel_path);
         if let Some(parent) = parent_path.as_deref() {
             self.ensure_folder_record(parent)?;
@@ -304,11 +379,8 @@ impl OpenContext {
                 "Cannot rename the root contexts directory.".into(),
             ));
         }
-        if new_name.is_empty() || new_name.contains('/') {
-            return Err(CoreError::Message(
-                "New name must be a single path segment.".into(),
-            ));
-        }
+        let new_name = normalize_name(new_name)?;
+        let new_name = new_name.as_str();
         let folder = self
             .find_folder(&rel_path)?
             .ok_or_else(|| folder_not_found(&rel_path))?;
@@ -332,6 +404,10 @@ impl OpenContext {
             fs::create_dir_all(parent)?;
         }
         fs::rename(&folder.abs_path, &new_abs_path)?;
+        // Text stats are cached by file path; moved files are read anew
+        self.text_stats
+            .lock()
+            .retain(|path, _| !path.starts_with(&folder.abs_path));
         let ts = now_iso();
 
         // Collect affected doc paths before the transaction (for event emission)
@@ -455,6 +531,10 @@ impl OpenContext {
             fs::create_dir_all(parent)?;
         }
         fs::rename(&folder.abs_path, &new_abs_path)?;
+        // Text stats are cached by file path; moved files are read anew
+        self.text_stats
+            .lock()
+            .retain(|path, _| !path.starts_with(&folder.abs_path));
 
         let ts = now_iso();
 
@@ -624,7 +704,7 @@ impl OpenContext {
         let folder = self
             .find_folder(&rel_folder_path)?
             .ok_or_else(|| folder_not_found(&rel_folder_path))?;
-        self.with_conn(|conn| {
+        self.with_read_conn(|conn| {
             if recursive {
                 let pattern = if folder.rel_path.is_empty() {
                     "%".to_string()
@@ -661,6 +741,49 @@ impl OpenContext {
         })
     }
 
+    /// Docs most recently modified first, going by the file's modified time
+    /// so edits made outside OpenContext count too. Only docs modified at or
+    /// after `since` (RFC 3339) when given, and at most `limit`.
+    pub fn list_recent_docs(
+        &self,
+        since: Option<&str>,
+        limit: Option<usize>,
+    ) -> CoreResult<Vec<Doc>> {
+        let since = since
+            .map(|since| {
+                chrono::DateTime::parse_from_rfc3339(since)
+                    .map(|since| since.with_timezone(&Utc))
+                    .map_err(|e| CoreError::Message(format!("Invalid time \"{since}\": {e}")))
+            })
+            .transpose()?;
+        let mut docs = self.with_read_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, folder_id, name, rel_path, abs_path, description, stable_id, created_at, updated_at
+                 FROM docs",
+            )?;
+            let rows = stmt
+                .query_map([], row_to_doc)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })?;
+        for doc in &mut docs {
+            if let Ok(updated) = sync_updated_at_from_fs(doc) {
+                doc.updated_at = updated;
+            }
+        }
+        if let Some(since) = since {
+            docs.retain(|doc| {
+                chrono::DateTime::parse_from_rfc3339(&doc.updated_at)
+                    .is_ok_and(|updated| updated >= since)
+            });
+        }
+        docs.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        if let Some(limit) = limit {
+            docs.truncate(limit);
+        }
+        Ok(docs)
+    }
+
     pub fn create_doc(
         &self,
         folder_path: &str,
@@ -670,11 +793,8 @@ impl OpenContext {
         if name.is_empty() {
             return Err(CoreError::Message("Document name is required.".into()));
         }
-        if name.contains('/') {
-            return Err(CoreError::Message(
-                "Document name must not contain \"/\".".into(),
-            ));
-        }
+        let name = normalize_name(name)?;
+        let name = name.as_str();
         let rel_folder_path = normalize_folder_path(Some(folder_path))?;
         let folder = self
             .find_folder(&rel_folder_path)?
@@ -698,8 +818,8 @@ impl OpenContext {
         let stable_id = self.with_conn(|conn| {
             let sid = generate_stable_id(conn)?;
             conn.execute(
-                "INSERT INTO docs (folder_id, name, rel_path, abs_path, description, stable_id, created_at, updated_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                "INSERT INTO docs (folder_id, name, rel_path, abs_path, description, stable_id, created_at, updated_at, description_updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7, ?8)",
                 params![
                     folder.id,
                     name,
@@ -707,7 +827,8 @@ impl OpenContext {
                     abs_path.to_string_lossy(),
                     description.unwrap_or(""),
                     sid,
-                    ts
+                    ts,
+                    description.filter(|d| !d.is_empty()).map(|_| &ts)
                 ],
             )?;
             Ok(sid)
@@ -746,6 +867,15 @@ impl OpenContext {
                 "Document \"{new_rel_path}\" already exists."
             )));
         }
+        // The doc takes on the destination's encryption, which needs the key
+        let mut resolver = SettingsResolver::new(&self.contexts_root);
+        let encrypt = resolver.doc(&new_rel_path)?.is_encrypted();
+        if doc.kind.is_text()
+            && encrypt != resolver.doc(&rel_doc_path)?.is_encrypted()
+            && !crypto::is_unlocked(&self.contexts_root)
+        {
+            return Err(CoreError::Locked { path: new_rel_path });
+        }
         let new_abs_path = self.contexts_root.join(&new_rel_path);
         if let Some(parent) = new_abs_path.parent() {
             fs::create_dir_all(parent)?;
@@ -765,6 +895,10 @@ impl OpenContext {
             )?;
             Ok(())
         })?;
+        self.carry_doc_settings(&rel_doc_path, Some(&new_rel_path));
+        if doc.kind.is_text() {
+            self.apply_doc_encryption(&new_rel_path, &new_abs_path, encrypt)?;
+        }
 
         // Emit event
         #[cfg(feature = "search")]
@@ -780,11 +914,8 @@ impl OpenContext {
     }
 
     pub fn rename_doc(&self, doc_path: &str, new_name: &str) -> CoreResult<RenameResult> {
-        if new_name.is_empty() || new_name.contains('/') {
-            return Err(CoreError::Message(
-                "New name must be a single file name without \"/\".".into(),
-            ));
-        }
+        let new_name = normalize_name(new_name)?;
+        let new_name = new_name.as_str();
         let rel_doc_path = normalize_doc_path(Some(doc_path))?;
         let doc = self
             .find_doc(&rel_doc_path)?
@@ -812,6 +943,7 @@ impl OpenContext {
             )?;
             Ok(())
         })?;
+        self.carry_doc_settings(&rel_doc_path, Some(&new_rel_path));
 
         // Emit event
         #[cfg(feature = "search")]
@@ -838,6 +970,7 @@ impl OpenContext {
             conn.execute("DELETE FROM docs WHERE id = ?1", params![doc.id])?;
             Ok(())
         })?;
+        self.carry_doc_settings(&rel_doc_path, None);
 
         // Emit event
         #[cfg(feature = "search")]
@@ -857,7 +990,7 @@ impl OpenContext {
         let ts = now_iso();
         self.with_conn(|conn| {
             conn.execute(
-                "UPDATE docs SET description = ?1, updated_at = ?2 WHERE id = ?3",
+                "UPDATE docs SET description = ?1, updated_at = ?2, description_updated_at = ?2 WHERE id = ?3",
                 params![description, ts, doc.id],
             )?;
             Ok(())
@@ -868,6 +1001,22 @@ impl OpenContext {
         })
     }
 
+    /// When the description was last set (RFC 3339). `None` if it never was,
+    /// or only before this was tracked.
+    pub fn get_doc_description_updated_at(&self, doc_path: &str) -> CoreResult<Option<String>> {
+        let rel_doc_path = normalize_doc_path(Some(doc_path))?;
+        let doc = self
+            .find_doc(&rel_doc_path)?
+            .ok_or_else(|| doc_not_found(&rel_doc_path))?;
+        self.with_read_conn(|conn| {
+            Ok(conn.query_row(
+                "SELECT description_updated_at FROM docs WHERE id = ?1",
+                params![doc.id],
+                |row| row.get(0),
+            )?)
+        })
+    }
+
     pub fn get_doc_content(&self, doc_path: &str) -> CoreResult<String> {
         let rel_doc_path = normalize_doc_path(Some(doc_path))?;
         let doc = self
@@ -886,7 +1035,20 @@ impl OpenContext {
                 })?;
             }
         }
-        let content = fs::read_to_string(&doc.abs_path)?;
+        if !doc.kind.is_text() {
+            return Err(CoreError::NotText {
+                path: rel_doc_path,
+                kind: doc.kind,
+            });
+        }
+        let content = crypto::read_text(&self.contexts_root, &rel_doc_path, &doc.abs_path)?;
+        let kind = DocKind::sniff(&doc.abs_path, content.as_bytes());
+        if !kind.is_text() {
+            return Err(CoreError::NotText {
+                path: rel_doc_path,
+                kind,
+            });
+        }
         Ok(content)
     }
 
@@ -900,17 +1062,25 @@ impl OpenContext {
         let doc = self
             .find_doc(&rel_doc_path)?
             .ok_or_else(|| doc_not_found(&rel_doc_path))?;
-        fs::write(&doc.abs_path, content)?;
+        if SettingsResolver::new(&self.contexts_root)
+            .doc(&rel_doc_path)?
+            .is_encrypted()
+        {
+            let sealed = crypto::encrypt(&self.contexts_root, &rel_doc_path, content.as_bytes())?;
+            fs::write(&doc.abs_path, sealed)?;
+        } else {
+            fs::write(&doc.abs_path, content)?;
+        }
         let ts = now_iso();
         self.with_conn(|conn| {
             if let Some(desc) = description {
                 conn.execute(
-                    "UPDATE docs SET description = ?1, updated_at = ?2 WHERE id = ?3",
+                    "UPDATE docs SET description = ?1, updated_at = ?2, description_updated_at = ?2, edit_count = edit_count + 1 WHERE id = ?3",
                     params![desc, ts, doc.id],
                 )?;
             } else {
                 conn.execute(
-                    "UPDATE docs SET updated_at = ?1 WHERE id = ?2",
+                    "UPDATE docs SET updated_at = ?1, edit_count = edit_count + 1 WHERE id = ?2",
                     params![ts, doc.id],
                 )?;
             }
@@ -945,34 +1115,394 @@ impl OpenContext {
         let folder = self
             .find_folder(&rel_path)?
             .ok_or_else(|| folder_not_found(&rel_path))?;
-        self.with_conn(|conn| {
-            let sql = if limit.is_some() {
-                "SELECT name, rel_path, abs_path, stable_id, description, updated_at FROM docs WHERE rel_path LIKE ?1 ORDER BY rel_path LIMIT ?2"
-            } else {
-                "SELECT name, rel_path, abs_path, stable_id, description, updated_at FROM docs WHERE rel_path LIKE ?1 ORDER BY rel_path"
-            };
+        let mut rows = self.with_read_conn(|conn| {
             let pattern = if folder.rel_path.is_empty() {
                 "%".to_string()
             } else {
                 format!("{}/%", folder.rel_path)
             };
-            let mut stmt = conn.prepare(sql)?;
-            if let Some(limit) = limit {
-                let rows = stmt
-                    .query_map(params![pattern, limit as i64], manifest_row)?
-                    .collect::<Result<Vec<_>, _>>()?;
-                Ok(rows)
-            } else {
+            let mut stmt = conn.prepare(
+                "SELECT name, rel_path, abs_path, stable_id, description, updated_at FROM docs WHERE rel_path LIKE ?1 ORDER BY rel_path",
+            )?;
+            let rows = stmt
+                .query_map([pattern], manifest_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })?;
+        // Applied before the limit, so excluded docs don't use up slots
+        let mut resolver = SettingsResolver::new(&self.contexts_root);
+        let unlocked = crypto::is_unlocked(&self.contexts_root);
+        rows.retain(|entry| {
+            let settings = resolver.doc_or_closed(&entry.rel_path);
+            !settings.excluded_from_manifest() && settings.searchable(unlocked)
+        });
+        if let Some(limit) = limit {
+            rows.truncate(limit);
+        }
+        Ok(rows)
+    }
+
+    /// Tags of a text doc, from its front matter and `#hashtags`
+    pub fn get_doc_tags(&self, doc_path: &str) -> CoreResult<Vec<String>> {
+        let content = self.get_doc_content(doc_path)?;
+        let (front_matter, body) = tags::split_front_matter(&content);
+        Ok(tags::extract_tags(front_matter, body))
+    }
+
+    /// Add tags to a doc's front matter, returning all of its tags. Tags
+    /// are lowercased, with spaces turned into hyphens.
+    pub fn add_doc_tags(&self, doc_path: &str, new_tags: &[String]) -> CoreResult<Vec<String>> {
+        let content = self.get_doc_content(doc_path)?;
+        let (front_matter, body) = tags::split_front_matter(&content);
+        let current = tags::extract_tags(front_matter, body);
+        let new_tags = tags::normalize_tags(new_tags.iter().cloned());
+        if new_tags.iter().all(|tag| current.contains(tag)) {
+            return Ok(current);
+        }
+        let updated = tags::add_front_matter_tags(&content, &new_tags);
+        self.save_doc_content(doc_path, &updated, None)?;
+        let (front_matter, body) = tags::split_front_matter(&updated);
+        Ok(tags::extract_tags(front_matter, body))
+    }
+
+    /// Doc counts over time, most edited docs and, when asked for, word
+    /// counts and tags across the whole vault
+    pub fn vault_statistics(&self, options: &VaultStatsOptions) -> CoreResult<VaultStats> {
+        let (docs, total_folders) = self.with_read_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT rel_path, abs_path, created_at, updated_at, edit_count FROM docs",
+            )?;
+            let docs = stmt
+                .query_map([], |row| {
+                    Ok(stats::DocRow {
+                        rel_path: row.get(0)?,
+                        abs_path: PathBuf::from(row.get::<_, String>(1)?),
+                        created_at: row.get(2)?,
+                        updated_at: row.get(3)?,
+                        edit_count: row.get::<_, i64>(4)? as u64,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let total_folders: i64 =
+                conn.query_row("SELECT COUNT(*) FROM folders", [], |row| row.get(0))?;
+            Ok((docs, total_folders as usize))
+        })?;
+        Ok(stats::compute(
+            docs,
+            total_folders,
+            options,
+            &self.text_stats,
+        ))
+    }
+
+    /// Copy every vault file and the database into a new snapshot under
+    /// `snapshots_dir`. `on_progress` gets the files copied and the total.
+    pub fn create_snapshot(
+        &self,
+        snapshots_dir: &Path,
+        label: Option<&str>,
+        on_progress: impl FnMut(usize, usize),
+    ) -> CoreResult<SnapshotInfo> {
+        fs::create_dir_all(snapshots_dir)?;
+        self.with_conn(|conn| {
+            snapshot::create(&self.contexts_root, conn, snapshots_dir, label, on_progress)
+        })
+    }
+
+    /// Roll the vault files and database back to snapshot `id`, after taking
+    /// a "pre-restore" snapshot of the current state to undo it with.
+    /// `on_progress` gets the files done and the total for each of the two.
+    /// Restored and removed docs are announced as updated and deleted.
+    pub fn restore_snapshot(
+        &self,
+        snapshots_dir: &Path,
+        id: &str,
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> CoreResult<SnapshotRestore> {
+        let pre_restore =
+            self.create_snapshot(snapshots_dir, Some("pre-restore"), &mut on_progress)?;
+        let (snapshot, restored, removed) = self.with_conn(|conn| {
+            snapshot::restore(&self.contexts_root, conn, snapshots_dir, id, on_progress)
+        })?;
+
+        #[cfg(feature = "search")]
+        {
+            for rel_path in &restored {
+                self.emit_doc_event(DocEvent::Updated {
+                    rel_path: rel_path.clone(),
+                });
+            }
+            for rel_path in &removed {
+                self.emit_doc_event(DocEvent::Deleted {
+                    rel_path: rel_path.clone(),
+                });
+            }
+        }
+        Ok(SnapshotRestore {
+            snapshot,
+            pre_restore,
+            restored,
+            removed,
+        })
+    }
+
+    /// Settings stored in a folder's `.folder.json`, doc overrides included
+    pub fn get_folder_settings(&self, folder_path: &str) -> CoreResult<FolderSettingsFile> {
+        let rel_path = normalize_folder_path(Some(folder_path))?;
+        if !rel_path.is_empty() && self.find_folder(&rel_path)?.is_none() {
+            return Err(folder_not_found(&rel_path));
+        }
+        FolderSettingsFile::load(&self.contexts_root.join(&rel_path))
+    }
+
+    /// Replace a folder's own settings, or with `doc_name` the overrides of
+    /// one doc in it. Empty settings remove them.
+    pub fn set_folder_settings(
+        &self,
+        folder_path: &str,
+        doc_name: Option<&str>,
+        settings: FolderSettings,
+    ) -> CoreResult<FolderSettingsFile> {
+        let rel_path = normalize_folder_path(Some(folder_path))?;
+        let mut file = self.get_folder_settings(&rel_path)?;
+        match doc_name {
+            Some(name) => {
+                let doc_rel_path = if rel_path.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{rel_path}/{name}")
+                };
+                if self.find_doc(&doc_rel_path)?.is_none() {
+                    return Err(doc_not_found(&doc_rel_path));
+                }
+                // Encryption is set per folder, through `set_folder_encrypted`
+                let settings = FolderSettings {
+                    encrypted: None,
+                    ..settings
+                };
+                if settings.is_empty() {
+                    file.docs.remove(name);
+                } else {
+                    file.docs.insert(name.to_string(), settings);
+                }
+            }
+            None => {
+                file.folder = FolderSettings {
+                    encrypted: file.folder.encrypted,
+                    ..settings
+                }
+            }
+        }
+        file.save(&self.contexts_root.join(&rel_path))?;
+        Ok(file)
+    }
+
+    /// A folder's settings with inherited keys filled in
+    pub fn resolve_folder_settings(&self, folder_path: &str) -> CoreResult<FolderSettings> {
+        let rel_path = normalize_folder_path(Some(folder_path))?;
+        SettingsResolver::new(&self.contexts_root).folder(&rel_path)
+    }
+
+    /// A doc's settings: its own overrides, then its folders'
+    pub fn resolve_doc_settings(&self, doc_path: &str) -> CoreResult<FolderSettings> {
+        let rel_doc_path = normalize_doc_path(Some(doc_path))?;
+        SettingsResolver::new(&self.contexts_root).doc(&rel_doc_path)
+    }
+
+    /// Refuse an agent's write to `doc_path` when the doc or a folder above
+    /// it is read-only. The doc and its folders need not exist yet.
+    pub fn ensure_agent_writable(&self, doc_path: &str) -> CoreResult<()> {
+        if self.resolve_doc_settings(doc_path)?.is_read_only() {
+            return Err(CoreError::ReadOnly {
+                path: doc_path.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn vault_status(&self) -> VaultStatus {
+        VaultStatus {
+            has_passphrase: crypto::has_passphrase(&self.contexts_root),
+            unlocked: crypto::is_unlocked(&self.contexts_root),
+        }
+    }
+
+    /// Unlock encrypted folders for this process. The first unlock sets the
+    /// vault passphrase; it is not stored in the vault and cannot be
+    /// recovered, so losing it loses every encrypted doc.
+    pub fn unlock_vault(&self, passphrase: &str) -> CoreResult<VaultStatus> {
+        crypto::unlock(&self.contexts_root, passphrase)?;
+        self.reindex_encrypted_docs();
+        Ok(self.vault_status())
+    }
+
+    /// Forget the vault key, so encrypted docs can't be read until the next
+    /// unlock
+    pub fn lock_vault(&self) -> VaultStatus {
+        if crypto::lock(&self.contexts_root) {
+            self.reindex_encrypted_docs();
+        }
+        self.vault_status()
+    }
+
+    /// Encrypt the text docs in a folder and its subfolders and keep them
+    /// encrypted as they are saved, or decrypt them again. Needs the vault
+    /// unlocked. Other files, such as images, are left as they are.
+    pub fn set_folder_encrypted(
+        &self,
+        folder_path: &str,
+        encrypted: bool,
+    ) -> CoreResult<FolderEncryption> {
+        let rel_path = normalize_folder_path(Some(folder_path))?;
+        if rel_path.is_empty() {
+            return Err(CoreError::Message(
+                "Only folders can be encrypted, not the whole vault.".into(),
+            ));
+        }
+        if self.find_folder(&rel_path)?.is_none() {
+            return Err(folder_not_found(&rel_path));
+        }
+        if !crypto::is_unlocked(&self.contexts_root) {
+            return Err(CoreError::Locked { path: rel_path });
+        }
+        // Settings first, so saves during the pass are already encrypted
+        let dir = self.contexts_root.join(&rel_path);
+        let mut file = FolderSettingsFile::load(&dir)?;
+        file.folder.encrypted = encrypted.then_some(true);
+        file.save(&dir)?;
+
+        let mut resolver = SettingsResolver::new(&self.contexts_root);
+        let mut docs_changed = 0;
+        for doc in self.list_docs(&rel_path, true)? {
+            if !doc.kind.is_text() {
+                continue;
+            }
+            // Subfolders encrypted on their own stay encrypted
+            let encrypt = resolver.doc(&doc.rel_path)?.is_encrypted();
+            if self.apply_doc_encryption(&doc.rel_path, &doc.abs_path, encrypt)? {
+                docs_changed += 1;
+            }
+        }
+        Ok(FolderEncryption {
+            folder_path: rel_path.clone(),
+            encrypted: resolver.folder(&rel_path)?.is_encrypted(),
+            docs_changed,
+        })
+    }
+
+    /// Text docs in a folder and its subfolders that encryption keeps out
+    /// of the index as things stand, e.g. to purge them from it once
+    /// encrypted or locked
+    pub fn unsearchable_docs(&self, folder_path: &str) -> CoreResult<Vec<String>> {
+        let unlocked = crypto::is_unlocked(&self.contexts_root);
+        let mut resolver = SettingsResolver::new(&self.contexts_root);
+        Ok(self
+            .list_docs(folder_path, true)?
+            .into_iter()
+            .filter(|doc| {
+                doc.kind.is_text() && !resolver.doc_or_closed(&doc.rel_path).searchable(unlocked)
+            })
+            .map(|doc| doc.rel_path)
+            .collect())
+    }
+
+    /// Encrypt or decrypt a doc file in place. Returns whether it changed.
+    fn apply_doc_encryption(
+        &self,
+        rel_path: &str,
+        abs_path: &std::path::Path,
+        encrypt: bool,
+    ) -> CoreResult<bool> {
+        let bytes = fs::read(abs_path)?;
+        if crypto::is_encrypted(&bytes) == encrypt {
+            return Ok(false);
+        }
+        let rewritten = if encrypt {
+            crypto::encrypt(&self.contexts_root, rel_path, &bytes)?
+        } else {
+            crypto::decrypt(&self.contexts_root, rel_path, bytes)?
+        };
+        // Write beside the doc and swap it in, so a crash never leaves a
+        // half-written doc
+        let name = abs_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let temp_path = abs_path.with_file_name(format!(".{name}.oc-crypt"));
+        fs::write(&temp_path, rewritten)?;
+        fs::rename(&temp_path, abs_path)?;
+
+        #[cfg(feature = "search")]
+        self.emit_doc_event(DocEvent::Updated {
+            rel_path: rel_path.to_string(),
+        });
+        Ok(true)
+    }
+
+    /// Queue encrypted docs that opt into search for re-indexing, which adds
+    /// them while unlocked and drops them from the index once locked
+    fn reindex_encrypted_docs(&self) {
+        #[cfg(feature = "search")]
+        {
+            let Ok(rel_paths) = self.with_read_conn(|conn| {
+                let mut stmt = conn.prepare("SELECT rel_path FROM docs")?;
                 let rows = stmt
-                    .query_map([pattern], manifest_row)?
+                    .query_map([], |row| row.get::<_, String>(0))?
                     .collect::<Result<Vec<_>, _>>()?;
                 Ok(rows)
+            }) else {
+                return;
+            };
+            let mut resolver = SettingsResolver::new(&self.contexts_root);
+            for rel_path in rel_paths {
+                let settings = resolver.doc_or_closed(&rel_path);
+                if settings.is_encrypted() && settings.searchable(true) {
+                    self.emit_doc_event(DocEvent::Updated { rel_path });
+                }
             }
-        })
+        }
+    }
+
+    /// Move a doc's overrides along with it, or drop them when `new_rel_path`
+    /// is `None`. The doc itself has already moved, so a failure is logged
+    /// rather than returned.
+    fn carry_doc_settings(&self, old_rel_path: &str, new_rel_path: Option<&str>) {
+        if let Err(e) = self.move_doc_settings(old_rel_path, new_rel_path) {
+            log::warn!(
+                "[Settings] Failed to carry the settings of {} to {}: {}",
+                old_rel_path,
+                new_rel_path.unwrap_or("nowhere"),
+                e
+            );
+        }
+    }
+
+    fn move_doc_settings(&self, old_rel_path: &str, new_rel_path: Option<&str>) -> CoreResult<()> {
+        let split = |rel: &str| -> (String, String) {
+            match rel.rsplit_once('/') {
+                Some((folder, name)) => (folder.to_string(), name.to_string()),
+                None => (String::new(), rel.to_string()),
+            }
+        };
+        let (old_folder, old_name) = split(old_rel_path);
+        let old_dir = self.contexts_root.join(&old_folder);
+        let mut file = FolderSettingsFile::load(&old_dir)?;
+        let Some(settings) = file.docs.remove(&old_name) else {
+            return Ok(());
+        };
+        file.save(&old_dir)?;
+        if let Some(new_rel_path) = new_rel_path {
+            let (new_folder, new_name) = split(new_rel_path);
+            let new_dir = self.contexts_root.join(&new_folder);
+            let mut file = FolderSettingsFile::load(&new_dir)?;
+            file.docs.insert(new_name, settings);
+            file.save(&new_dir)?;
+        }
+        Ok(())
     }
 
     fn find_folder(&self, rel_path: &str) -> CoreResult<Option<Folder>> {
-        self.with_conn(|conn| {
+        self.with_read_conn(|conn| {
             let mut stmt = conn.prepare(
                 "SELECT id, parent_id, name, rel_path, abs_path, description, created_at, updated_at
                  FROM folders WHERE rel_path = ?1",
@@ -982,7 +1512,7 @@ impl OpenContext {
     }
 
     fn find_doc(&self, rel_path: &str) -> CoreResult<Option<Doc>> {
-        self.with_conn(|conn| {
+        self.with_read_conn(|conn| {
             let mut stmt = conn.prepare(
                 "SELECT id, folder_id, name, rel_path, abs_path, description, stable_id, created_at, updated_at
                  FROM docs WHERE rel_path = ?1",
@@ -1040,6 +1570,20 @@ impl OpenContext {
         let conn = self.conn.lock();
         action(&conn)
     }
+
+    /// Run a read-only query on an idle read connection, falling back to
+    /// the writer when every reader is busy.
+    fn with_read_conn<F, T>(&self, action: F) -> CoreResult<T>
+    where
+        F: FnOnce(&Connection) -> CoreResult<T>,
+    {
+        for reader in self.readers.iter() {
+            if let Some(conn) = reader.try_lock() {
+                return action(&conn);
+            }
+        }
+        self.with_conn(action)
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -1074,48 +1618,112 @@ pub struct DocSummary {
     pub description: String,
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStatus {
+    /// Whether a vault passphrase has been set
+    pub has_passphrase: bool,
+    pub unlocked: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderEncryption {
+    pub folder_path: String,
+    /// Whether the folder is now encrypted, by its own setting or a parent's
+    pub encrypted: bool,
+    /// Docs encrypted or decrypted by the change
+    pub docs_changed: usize,
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct DocSaved {
     pub rel_path: String,
     pub abs_path: PathBuf,
 }
 
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Fold `bytes` into the FNV-1a `hash`, which starts at `FNV_OFFSET`.
+/// Snapshots and the search index hash content with it.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
 fn now_iso() -> String {
     Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
 }
 
+/// Every folder path a public API accepts goes through `VaultPath`, so
+/// none can point outside the contexts root. Empty means the root.
 fn normalize_folder_path(input: Option<&str>) -> CoreResult<String> {
     let Some(value) = input else {
         return Err(CoreError::Message("Folder path is required".into()));
     };
-    let trimmed = value.trim();
-    if trimmed.is_empty() || trimmed == "." || trimmed == "/" {
-        return Ok(String::new());
-    }
-    let normalized = trimmed
-        .replace('\\', "/")
-        .split('/')
-        .filter(|segment| !segment.is_empty())
-        .collect::<Vec<_>>()
-        .join("/");
-    Ok(normalized)
+    Ok(VaultPath::parse(value)?.into_string())
 }
 
+/// Like `normalize_folder_path`, but a document path cannot be the root.
 fn normalize_doc_path(input: Option<&str>) -> CoreResult<String> {
     let Some(value) = input else {
         return Err(CoreError::Message("Document path is required".into()));
     };
-    let cleaned = value
-        .trim()
-        .replace('\\', "/")
-        .split('/')
-        .filter(|segment| !segment.is_empty())
-        .collect::<Vec<_>>()
-        .join("/");
-    if cleaned.is_empty() {
+    let path = VaultPath::parse(value)?;
+    if path.is_root() {
         return Err(CoreError::Message("Document path cannot be root".into()));
     }
-    Ok(cleaned)
+    Ok(path.into_string())
+}
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED_WINDOWS_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Validate a single folder or document name before it touches disk.
+///
+/// Trailing spaces and dots are stripped (Windows drops them silently, which
+/// would desync the path in the database from the one on disk). Names must be
+/// valid on every platform so a synced contexts directory opens anywhere.
+fn normalize_name(name: &str) -> CoreResult<String> {
+    let invalid = |reason: &str| CoreError::InvalidName {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    };
+    if name.contains(['/', '\\']) {
+        return Err(invalid("names cannot contain path separators"));
+    }
+    if name.trim() == "." || name.trim() == ".." {
+        return Err(invalid("\".\" and \"..\" are reserved"));
+    }
+    let trimmed = name.trim_start().trim_end_matches([' ', '.']);
+    if trimmed.trim().is_empty() {
+        return Err(invalid("name is empty"));
+    }
+    if trimmed.chars().any(char::is_control) {
+        return Err(invalid("names cannot contain control characters"));
+    }
+    if let Some(c) = trimmed
+        .chars()
+        .find(|c| matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*'))
+    {
+        return Err(invalid(&format!("names cannot contain '{c}'")));
+    }
+    let stem = trimmed.split('.').next().unwrap_or(trimmed).trim_end();
+    if RESERVED_WINDOWS_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Err(invalid("name is reserved on Windows"));
+    }
+    if trimmed.len() > 255 {
+        return Err(invalid("name is longer than 255 bytes"));
+    }
+    Ok(trimmed.to_string())
 }
 
 fn parent_rel_path(rel_path: &str) -> Option<String> {
@@ -1159,12 +1767,14 @@ fn row_to_folder(row: &rusqlite::Row<'_>) -> rusqlite::Result<Folder> {
 }
 
 fn row_to_doc(row: &rusqlite::Row<'_>) -> rusqlite::Result<Doc> {
+    let abs_path = PathBuf::from(row.get::<_, String>(4)?);
     Ok(Doc {
         id: row.get(0)?,
         folder_id: row.get(1)?,
         name: row.get(2)?,
         rel_path: row.get(3)?,
-        abs_path: PathBuf::from(row.get::<_, String>(4)?),
+        kind: DocKind::detect(&abs_path),
+        abs_path,
         description: row.get(5)?,
         stable_id: row.get(6)?,
         created_at: row.get(7)?,
@@ -1173,13 +1783,17 @@ fn row_to_doc(row: &rusqlite::Row<'_>) -> rusqlite::Result<Doc> {
 }
 
 fn manifest_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DocManifestEntry> {
+    let abs_path = PathBuf::from(row.get::<_, String>(2)?);
+    let kind = DocKind::detect(&abs_path);
     Ok(DocManifestEntry {
         doc_name: row.get(0)?,
         rel_path: row.get(1)?,
-        abs_path: PathBuf::from(row.get::<_, String>(2)?),
+        abs_path,
         stable_id: row.get(3)?,
         description: row.get(4)?,
         updated_at: row.get(5)?,
+        kind,
+        skip_reason: kind.skip_reason(),
     })
 }
 
@@ -1197,6 +1811,20 @@ fn ensure_schema_migrations(conn: &Connection) -> CoreResult<()> {
         "CREATE UNIQUE INDEX IF NOT EXISTS idx_docs_stable_id ON docs(stable_id)",
         [],
     )?;
+    // Add docs.description_updated_at if missing.
+    if !cols.iter().any(|c| c == "description_updated_at") {
+        conn.execute(
+            "ALTER TABLE docs ADD COLUMN description_updated_at TEXT",
+            [],
+        )?;
+    }
+    // Add docs.edit_count (saves from the app) if missing.
+    if !cols.iter().any(|c| c == "edit_count") {
+        conn.execute(
+            "ALTER TABLE docs ADD COLUMN edit_count INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
 
     // Backfill missing stable_id.
     let mut stmt = conn.prepare("SELECT id FROM docs WHERE stable_id IS NULL OR stable_id = ''")?;
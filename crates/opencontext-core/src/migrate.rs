@@ -0,0 +1,207 @@
+//! Versioned migrations of config.json and the vault layout
+//!
+//! Run once by `OpenContext::initialize`. config.json records the last
+//! migration applied to it in `CONFIG_VERSION`, the contexts root in
+//! `.vault.json`, and each migration runs only when its version is newer.
+//! A missing version counts as 0, the layout from before migrations.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{now_iso, CoreError, CoreResult};
+
+pub const CONFIG_VERSION_KEY: &str = "CONFIG_VERSION";
+pub const VAULT_META_FILE: &str = ".vault.json";
+
+type Config = Map<String, Value>;
+
+struct Migration<F> {
+    version: u32,
+    name: &'static str,
+    up: F,
+}
+
+/// In version order. Never renumber or remove one that has shipped.
+const CONFIG_MIGRATIONS: &[Migration<fn(&mut Config) -> CoreResult<()>>] = &[Migration {
+    version: 1,
+    name: "move OPENAI_API_KEY and OPENAI_BASE_URL to EMBEDDING_*",
+    up: legacy_embedding_keys,
+}];
+
+/// In version order, each given the contexts root
+const VAULT_MIGRATIONS: &[Migration<fn(&Path) -> CoreResult<()>>] = &[];
+
+/// A migration `run` applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AppliedMigration {
+    /// "config" or "vault"
+    pub(crate) target: &'static str,
+    pub(crate) version: u32,
+    pub(crate) name: &'static str,
+}
+
+/// Contents of `.vault.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultMeta {
+    #[serde(default)]
+    schema_version: u32,
+    /// Every migration applied to the vault, oldest first
+    #[serde(default)]
+    migrations: Vec<MigrationRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MigrationRecord {
+    version: u32,
+    name: String,
+    applied_at: String,
+}
+
+/// Bring config.json in `base_root` and the vault at `contexts_root` up to
+/// date
+pub(crate) fn run(base_root: &Path, contexts_root: &Path) -> CoreResult<Vec<AppliedMigration>> {
+    let mut applied = migrate_config(&base_root.join("config.json"))?;
+    applied.extend(migrate_vault(contexts_root)?);
+    Ok(applied)
+}
+
+/// Apply pending config migrations. The file is backed up first, to
+/// `config.json.v<old version>.bak`, and rewritten only once all of them
+/// succeeded. A missing file needs nothing; an invalid one is left for the
+/// user to fix, as the config loader reports it.
+pub(crate) fn migrate_config(path: &Path) -> CoreResult<Vec<AppliedMigration>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let Ok(Value::Object(mut config)) = serde_json::from_str(&content) else {
+        log::warn!(
+            "[Migrate] {} is not a valid JSON object; not migrating it",
+            path.display()
+        );
+        return Ok(Vec::new());
+    };
+    let from = config
+        .get(CONFIG_VERSION_KEY)
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    let pending: Vec<_> = CONFIG_MIGRATIONS
+        .iter()
+        .filter(|migration| u64::from(migration.version) > from)
+        .collect();
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    fs::copy(path, with_suffix(path, &format!(".v{from}.bak")))?;
+    let mut applied = Vec::new();
+    for migration in pending {
+        (migration.up)(&mut config).map_err(|e| {
+            CoreError::Message(format!(
+                "Config migration {} ({}) failed: {e}",
+                migration.version, migration.name
+            ))
+        })?;
+        config.insert(CONFIG_VERSION_KEY.to_string(), migration.version.into());
+        log::info!(
+            "[Migrate] config.json: applied {} ({})",
+            migration.version,
+            migration.name
+        );
+        applied.push(AppliedMigration {
+            target: "config",
+            version: migration.version,
+            name: migration.name,
+        });
+    }
+    let content = serde_json::to_string_pretty(&Value::Object(config))
+        .map_err(|e| CoreError::Message(format!("Failed to encode config: {e}")))?;
+    write_replacing(path, &content)?;
+    Ok(applied)
+}
+
+/// Apply pending vault migrations, recording each in `.vault.json` as soon
+/// as it is done, since layout changes can't be rolled back together
+pub(crate) fn migrate_vault(contexts_root: &Path) -> CoreResult<Vec<AppliedMigration>> {
+    let meta_path = contexts_root.join(VAULT_META_FILE);
+    let mut meta: VaultMeta = match fs::read_to_string(&meta_path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| {
+            CoreError::Message(format!("{} is unreadable: {e}", meta_path.display()))
+        })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => VaultMeta::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut applied = Vec::new();
+    for migration in VAULT_MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > meta.schema_version)
+    {
+        (migration.up)(contexts_root).map_err(|e| {
+            CoreError::Message(format!(
+                "Vault migration {} ({}) failed: {e}",
+                migration.version, migration.name
+            ))
+        })?;
+        meta.schema_version = migration.version;
+        meta.migrations.push(MigrationRecord {
+            version: migration.version,
+            name: migration.name.to_string(),
+            applied_at: now_iso(),
+        });
+        let content = serde_json::to_string_pretty(&meta)
+            .map_err(|e| CoreError::Message(format!("Failed to encode vault metadata: {e}")))?;
+        write_replacing(&meta_path, &content)?;
+        log::info!(
+            "[Migrate] vault: applied {} ({})",
+            migration.version,
+            migration.name
+        );
+        applied.push(AppliedMigration {
+            target: "vault",
+            version: migration.version,
+            name: migration.name,
+        });
+    }
+    Ok(applied)
+}
+
+/// Config version 1: the embedding settings were renamed from their
+/// OpenAI-specific keys. A new key that is already set wins, as it did
+/// when loading, and the legacy key is dropped either way.
+fn legacy_embedding_keys(config: &mut Config) -> CoreResult<()> {
+    for (legacy, current) in [
+        ("OPENAI_API_KEY", "EMBEDDING_API_KEY"),
+        ("OPENAI_BASE_URL", "EMBEDDING_API_BASE"),
+    ] {
+        let Some(value) = config.remove(legacy) else {
+            continue;
+        };
+        let current_set = config.get(current).is_some_and(|v| !v.is_null());
+        if !current_set && !value.is_null() {
+            config.insert(current.to_string(), value);
+        }
+    }
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Write through a temp file so an interrupted write can't leave `path`
+/// half-written
+fn write_replacing(path: &Path, content: &str) -> CoreResult<()> {
+    let tmp = with_suffix(path, ".tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
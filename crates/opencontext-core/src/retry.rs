@@ -0,0 +1,102 @@
+//! Retries for transient HTTP failures
+//!
+//! Rate limits (429) and overloaded servers (503) usually clear up within
+//! seconds, so idempotent requests are retried with exponential backoff and
+//! jitter, waiting at least as long as a `Retry-After` header asks.
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// Longest wait between attempts, whatever the server asks for
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How often and how patiently to retry a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first; 1 never retries
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each retry after it
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+        }
+    }
+
+    /// Wait before retry number `retry` (0 for the first): the backoff for
+    /// that retry with jitter in its upper half, or `retry_after` if longer
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(1 << retry.min(16))
+            .min(MAX_RETRY_DELAY);
+        let jittered = backoff / 2 + backoff.mul_f64(rand::random::<f64>() / 2.0);
+        retry_after
+            .map_or(jittered, |after| after.max(jittered))
+            .min(MAX_RETRY_DELAY)
+    }
+
+    /// Send the request built by `request`, building and sending it again
+    /// after a transient status or a failed connection. The last attempt's
+    /// response or error is returned as is.
+    pub async fn send(
+        &self,
+        mut request: impl FnMut() -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        let mut retry = 0;
+        loop {
+            let last = retry + 1 >= self.max_attempts;
+            let delay = match request().send().await {
+                Ok(response) if !last && is_transient(response.status()) => {
+                    let delay = self.delay(retry, retry_after(response.headers()));
+                    log::warn!(
+                        "Request to {} failed with {}, retrying in {:?}",
+                        response.url().path(),
+                        response.status(),
+                        delay
+                    );
+                    delay
+                }
+                Err(e) if !last && e.is_connect() => {
+                    let delay = self.delay(retry, None);
+                    log::warn!("Request failed to connect, retrying in {:?}", delay);
+                    delay
+                }
+                result => return result,
+            };
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// Statuses worth retrying: timeouts, rate limits and server overload
+pub fn is_transient(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504)
+}
+
+/// The wait a `Retry-After` header asks for, given in seconds or as an
+/// HTTP date
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
@@ -1,64 +1,113 @@
 //! Markdown document chunking with proper Unicode support
+//!
+//! Chunk sizes are in estimated tokens of the embedding model: about four
+//! characters per token, with each CJK character a token of its own. Where
+//! chunks are cut depends on the [`ChunkStrategy`]; the fixed and sentence
+//! strategies read markdown as plain text.
+//!
+//! Docs within the overlap of a single chunk are cut without one, so a doc
+//! just over the chunk size doesn't come out as two near-copies.
+//!
+//! Each chunk records the headings it sits under. Headings quoted in a
+//! blockquote are the quote's text, not sections of the doc.
+//!
+//! Code blocks are kept whole: a chunk never ends inside one, and a block too
+//! long for one chunk is cut at line boundaries, each piece fenced again with
+//! the block's fence and language.
 
-use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::ops::Range;
 
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Parser, Tag, TagEnd};
+
+use super::config::{ChunkStrategy, SearchConfig};
 use super::types::TextChunk;
 
+/// Bumped when the same settings start chunking docs differently, so the
+/// next build re-chunks docs it would otherwise keep
+pub(crate) const CHUNKER_VERSION: u32 = 3;
+
 /// Markdown chunker that splits documents into semantic chunks
-/// All size calculations are based on **character count**, not byte count,
-/// ensuring proper handling of Unicode (CJK, emoji, etc.)
+/// All splitting happens on character boundaries, so Unicode text (CJK,
+/// emoji, etc.) is never cut inside a character
 pub struct Chunker {
-    /// Maximum chunk size in characters (not bytes)
-    max_chunk_chars: usize,
-    /// Overlap between chunks in characters
-    overlap_chars: usize,
+    /// Maximum chunk size in estimated tokens
+    max_chunk_tokens: usize,
+    /// Estimated tokens a chunk repeats from the end of the one before
+    overlap_tokens: usize,
+    strategy: ChunkStrategy,
 }
 
 impl Default for Chunker {
     fn default() -> Self {
-        Self {
-            max_chunk_chars: 1500,
-            overlap_chars: 200,
-        }
+        Self::new(400, 50)
     }
 }
 
 impl Chunker {
-    /// Create a new chunker with custom settings (in characters)
-    pub fn new(max_chunk_chars: usize, overlap_chars: usize) -> Self {
+    /// Create a chunker aiming for chunks of at most `max_chunk_tokens`,
+    /// each starting with about `overlap_tokens` of the one before. The
+    /// overlap is capped at half a chunk.
+    pub fn new(max_chunk_tokens: usize, overlap_tokens: usize) -> Self {
+        let max_chunk_tokens = max_chunk_tokens.max(1);
         Self {
-            max_chunk_chars,
-            overlap_chars,
+            max_chunk_tokens,
+            overlap_tokens: overlap_tokens.min(max_chunk_tokens / 2),
+            strategy: ChunkStrategy::default(),
         }
     }
 
-    /// Chunk a markdown document into semantic pieces
+    /// Cut chunks with `strategy` instead of by headings
+    pub fn with_strategy(mut self, strategy: ChunkStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// The chunker `config` asks for
+    pub fn for_config(config: &SearchConfig) -> Self {
+        Self::new(config.chunk_max_tokens(), config.chunking.overlap_tokens)
+            .with_strategy(config.chunking.strategy)
+    }
+
+    /// Chunk a markdown document into semantic pieces, or as plain text
+    /// unless the strategy is by headings
     pub fn chunk(&self, content: &str, _file_path: &str) -> Vec<TextChunk> {
+        if self.strategy != ChunkStrategy::Heading {
+            return self.chunk_plain(content);
+        }
+
+        let overlap_tokens = self.overlap_for(content);
         let mut chunks = Vec::new();
         let mut current_heading_path: Vec<(HeadingLevel, String)> = Vec::new();
         let mut current_text = String::new();
-        let mut current_start_line = 1;
-        let mut line_number = 1;
+        let mut source_map = SourceMap::new(content);
+        // Where code blocks sit in `current_text`, with their language
+        let mut code_spans: Vec<CodeSpan> = Vec::new();
+        let mut code_block: Option<CodeBlock> = None;
 
-        let parser = Parser::new(content);
+        let parser = Parser::new(content).into_offset_iter();
         let mut in_heading = false;
         let mut heading_level: Option<HeadingLevel> = None;
         let mut heading_text = String::new();
+        let mut quote_depth = 0usize;
 
-        for event in parser {
+        for (event, range) in parser {
             match event {
-                Event::Start(Tag::Heading { level, .. }) => {
+                Event::Start(Tag::BlockQuote(_)) => quote_depth += 1,
+                Event::End(TagEnd::BlockQuote(_)) => quote_depth = quote_depth.saturating_sub(1),
+                Event::End(TagEnd::Heading(_)) if quote_depth > 0 => {
+                    source_map.mark(current_text.len(), range.end);
+                    current_text.push_str("\n\n");
+                }
+                Event::Start(Tag::Heading { level, .. }) if quote_depth == 0 => {
                     // Save current chunk before starting new heading section
-                    if !current_text.trim().is_empty() {
-                        let heading_path = Self::build_heading_path(&current_heading_path);
-                        chunks.push(TextChunk {
-                            content: current_text.trim().to_string(),
-                            heading_path,
-                            start_line: current_start_line,
-                            end_line: line_number,
-                        });
-                        current_text.clear();
-                    }
+                    let headings = Self::heading_titles(&current_heading_path);
+                    Self::flush(
+                        &mut chunks,
+                        &mut current_text,
+                        &mut source_map,
+                        &mut code_spans,
+                        headings,
+                    );
 
                     in_heading = true;
                     heading_level = Some(level);
@@ -80,20 +129,39 @@ impl Chunker {
                     in_heading = false;
                     heading_level = None;
                     heading_text.clear();
-                    current_start_line = line_number + 1;
+                }
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    code_block = Some(CodeBlock::new(content, kind, range.start));
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some(mut block) = code_block.take() {
+                        block.end = range.end;
+                        let headings = Self::heading_titles(&current_heading_path);
+                        self.add_code_block(
+                            &block,
+                            &mut chunks,
+                            &mut current_text,
+                            &mut source_map,
+                            &mut code_spans,
+                            headings,
+                        );
+                    }
                 }
                 Event::Text(text) => {
                     if in_heading {
                         heading_text.push_str(&text);
+                    } else if let Some(block) = code_block.as_mut() {
+                        block.push(&text, range.start);
                     } else {
+                        source_map.mark(current_text.len(), range.start);
                         current_text.push_str(&text);
                     }
-                    line_number += text.matches('\n').count();
                 }
                 Event::Code(code) => {
                     if in_heading {
                         heading_text.push_str(&code);
                     } else {
+                        source_map.mark(current_text.len(), range.start);
                         current_text.push('`');
                         current_text.push_str(&code);
                         current_text.push('`');
@@ -103,139 +171,240 @@ impl Chunker {
                     if in_heading {
                         heading_text.push(' ');
                     } else {
+                        source_map.mark(current_text.len(), range.start);
                         current_text.push('\n');
                     }
-                    line_number += 1;
                 }
                 Event::End(TagEnd::Paragraph) => {
+                    source_map.mark(current_text.len(), range.end);
                     current_text.push_str("\n\n");
                 }
                 Event::End(TagEnd::Item) => {
+                    source_map.mark(current_text.len(), range.end);
                     current_text.push('\n');
                 }
                 _ => {}
             }
 
-            // Check if we need to split the chunk (using char count, not byte count)
-            if current_text.chars().count() > self.max_chunk_chars {
-                let heading_path = Self::build_heading_path(&current_heading_path);
-                let (chunk, remainder) = self.split_chunk(&current_text);
+            while estimate_tokens(current_text.trim_end()) > self.max_chunk_tokens {
+                let headings = Self::heading_titles(&current_heading_path);
+                let (chunk_end, remainder_start) = self.split_chunk(&current_text, overlap_tokens);
+                let (chunk_end, remainder_start) =
+                    keep_code_whole(&current_text, &code_spans, chunk_end, remainder_start);
 
-                chunks.push(TextChunk {
-                    content: chunk,
-                    heading_path: heading_path.clone(),
-                    start_line: current_start_line,
-                    end_line: line_number,
-                });
+                let mut chunk = source_map.text_chunk(&current_text, 0..chunk_end, headings);
+                chunk.code_language = language_in(&code_spans, 0..chunk_end);
+                chunks.push(chunk);
 
-                current_text = remainder;
-                current_start_line = line_number;
+                let rest = &current_text[remainder_start..];
+                let remainder_start = remainder_start + (rest.len() - rest.trim_start().len());
+                if remainder_start == 0 {
+                    break;
+                }
+                current_text.drain(..remainder_start);
+                source_map.drop_before(remainder_start);
+                code_spans.retain(|span| span.range.end > remainder_start);
+                for span in &mut code_spans {
+                    span.range.start = span.range.start.saturating_sub(remainder_start);
+                    span.range.end -= remainder_start;
+                }
             }
         }
 
         // Don't forget the last chunk
+        let headings = Self::heading_titles(&current_heading_path);
+        Self::flush(
+            &mut chunks,
+            &mut current_text,
+            &mut source_map,
+            &mut code_spans,
+            headings,
+        );
+
+        // Filter out very small chunks and merge if needed
+        self.post_process_chunks(chunks)
+    }
+
+    /// Chunk plain text (.txt, text extracted from a PDF, ...) by size
+    /// alone, without reading it as markdown
+    pub fn chunk_plain(&self, content: &str) -> Vec<TextChunk> {
+        let overlap_tokens = self.overlap_for(content);
+        let mut chunks = Vec::new();
+        let mut current_text = content.to_string();
+        let mut source_map = SourceMap::new(content);
+        source_map.mark(0, 0);
+
+        while estimate_tokens(&current_text) > self.max_chunk_tokens {
+            let (chunk_end, remainder_start) = self.split_chunk(&current_text, overlap_tokens);
+            chunks.push(source_map.text_chunk(&current_text, 0..chunk_end, Vec::new()));
+
+            let rest = &current_text[remainder_start..];
+            let remainder_start = remainder_start + (rest.len() - rest.trim_start().len());
+            if remainder_start == 0 {
+                break;
+            }
+            current_text.drain(..remainder_start);
+            source_map.drop_before(remainder_start);
+        }
         if !current_text.trim().is_empty() {
-            let heading_path = Self::build_heading_path(&current_heading_path);
-            chunks.push(TextChunk {
-                content: current_text.trim().to_string(),
-                heading_path,
-                start_line: current_start_line,
-                end_line: line_number,
-            });
+            chunks.push(source_map.text_chunk(&current_text, 0..current_text.len(), Vec::new()));
         }
 
-        // Filter out very small chunks and merge if needed
         self.post_process_chunks(chunks)
     }
 
-    fn build_heading_path(headings: &[(HeadingLevel, String)]) -> String {
-        headings
-            .iter()
-            .map(|(_, text)| text.as_str())
-            .collect::<Vec<_>>()
-            .join(" > ")
+    /// Overlap between the chunks of `content`: none when the overlap alone
+    /// would hold what's left of it after the first chunk
+    fn overlap_for(&self, content: &str) -> usize {
+        if estimate_tokens(content.trim()) <= self.max_chunk_tokens + self.overlap_tokens {
+            0
+        } else {
+            self.overlap_tokens
+        }
+    }
+
+    /// Close the chunk being collected, if it holds anything
+    fn flush(
+        chunks: &mut Vec<TextChunk>,
+        text: &mut String,
+        source_map: &mut SourceMap,
+        code_spans: &mut Vec<CodeSpan>,
+        headings: Vec<String>,
+    ) {
+        if !text.trim().is_empty() {
+            let mut chunk = source_map.text_chunk(text, 0..text.len(), headings);
+            chunk.code_language = language_in(code_spans, 0..text.len());
+            chunks.push(chunk);
+        }
+        text.clear();
+        source_map.clear();
+        code_spans.clear();
+    }
+
+    /// Add a finished code block to the chunk being collected, closing that
+    /// chunk first if the block doesn't fit. A block longer than a chunk
+    /// becomes chunks of its own, one per piece.
+    fn add_code_block(
+        &self,
+        block: &CodeBlock<'_>,
+        chunks: &mut Vec<TextChunk>,
+        text: &mut String,
+        source_map: &mut SourceMap,
+        code_spans: &mut Vec<CodeSpan>,
+        headings: Vec<String>,
+    ) {
+        if estimate_tokens(text) + block.fenced_tokens(0..block.code.len()) > self.max_chunk_tokens
+        {
+            Self::flush(chunks, text, source_map, code_spans, headings.clone());
+        }
+
+        let pieces = block.pieces(self.max_chunk_tokens);
+        let last = pieces.len() - 1;
+        for (i, piece) in pieces.into_iter().enumerate() {
+            if i > 0 {
+                Self::flush(chunks, text, source_map, code_spans, headings.clone());
+            }
+            let start = text.len();
+            let source_start = if i == 0 {
+                block.start
+            } else {
+                block.source_offset(piece.start)
+            };
+            source_map.mark(start, source_start);
+            text.push_str(&block.header());
+            let code_start = text.len();
+            source_map.mark(code_start, block.source_offset(piece.start));
+            for &(code_pos, source_pos) in &block.anchors {
+                if code_pos > piece.start && code_pos < piece.end {
+                    source_map.mark(code_start + code_pos - piece.start, source_pos);
+                }
+            }
+            text.push_str(&block.code[piece.clone()]);
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            // The closing fence maps onto the source's closing fence, or
+            // onto the end of this piece's last line
+            let source_end = if i == last {
+                block.start + block.source[block.start..block.end].trim_end().len()
+            } else {
+                block.source_offset(piece.end - 1)
+            };
+            source_map.mark(text.len(), source_end.saturating_sub(block.fence.len()));
+            text.push_str(&block.fence);
+            code_spans.push(CodeSpan {
+                range: start..text.len(),
+                language: block.language.clone(),
+            });
+        }
+        source_map.mark(text.len(), block.end);
+        text.push_str("\n\n");
+    }
+
+    fn heading_titles(headings: &[(HeadingLevel, String)]) -> Vec<String> {
+        headings.iter().map(|(_, text)| text.clone()).collect()
     }
 
-    /// Split text into (chunk, remainder) at a natural boundary
+    /// Split text at a natural boundary. Returns the byte offsets where the
+    /// chunk ends and where the remainder starts (overlapping the chunk).
     /// All calculations use character indices for Unicode safety
-    fn split_chunk(&self, text: &str) -> (String, String) {
+    fn split_chunk(&self, text: &str, overlap_tokens: usize) -> (usize, usize) {
         let chars: Vec<char> = text.chars().collect();
-        let char_count = chars.len();
+        let window = chars_within(chars.iter().copied(), self.max_chunk_tokens).max(1);
 
-        if char_count <= self.max_chunk_chars {
-            return (text.to_string(), String::new());
+        if window >= chars.len() {
+            return (text.len(), text.len());
         }
 
         // Helper: convert char index to byte index
         let char_to_byte =
             |char_idx: usize| -> usize { chars.iter().take(char_idx).map(|c| c.len_utf8()).sum() };
+        let split_at = |char_pos: usize| {
+            let overlap = chars_within(chars[..char_pos].iter().rev().copied(), overlap_tokens);
+            let remainder_char_start = match overlap_start(&chars, char_pos - overlap, char_pos) {
+                // Never repeat the whole chunk
+                0 => char_pos,
+                start => start,
+            };
+            (char_to_byte(char_pos), char_to_byte(remainder_char_start))
+        };
+
+        // Search window: look for split points within max_chunk_tokens
+        let search_text: String = chars[..window].iter().collect();
 
-        // Search window: look for split points within max_chunk_chars
-        let search_text: String = chars[..self.max_chunk_chars].iter().collect();
-
-        // Try to split at paragraph boundary
-        if let Some(pos) = search_text.rfind("\n\n") {
-            let char_pos = search_text[..pos].chars().count();
-            let byte_pos = char_to_byte(char_pos);
-            let chunk = text[..byte_pos].trim().to_string();
-
-            let remainder_char_start = char_pos.saturating_sub(self.overlap_chars);
-            let remainder_byte_start = char_to_byte(remainder_char_start);
-            let remainder = text[remainder_byte_start..].trim().to_string();
-            return (chunk, remainder);
-        }
-
-        // Try to split at sentence boundary (supports Chinese and English)
-        let sentence_ends = ["。", "！", "？", ".\n", "!\n", "?\n", ". ", "! ", "? "];
-        for end in &sentence_ends {
-            if let Some(pos) = search_text.rfind(end) {
-                let split_text = &search_text[..pos + end.len()];
-                let char_pos = split_text.chars().count();
-                let byte_pos = char_to_byte(char_pos);
-                let chunk = text[..byte_pos].trim().to_string();
-
-                let remainder_char_start = char_pos.saturating_sub(self.overlap_chars);
-                let remainder_byte_start = char_to_byte(remainder_char_start);
-                let remainder = text[remainder_byte_start..].trim().to_string();
-                return (chunk, remainder);
+        // Fixed windows go straight to the last word boundary
+        if self.strategy != ChunkStrategy::Fixed {
+            // Try to split at paragraph boundary
+            if let Some(pos) = search_text.rfind("\n\n") {
+                return split_at(search_text[..pos].chars().count());
+            }
+
+            // Try to split at sentence boundary (supports Chinese and English)
+            let sentence_ends = ["。", "！", "？", ".\n", "!\n", "?\n", ". ", "! ", "? "];
+            for end in &sentence_ends {
+                if let Some(pos) = search_text.rfind(end) {
+                    return split_at(search_text[..pos + end.len()].chars().count());
+                }
             }
         }
 
         // Try to split at clause boundary (Chinese comma, semicolon, etc.)
-        let clause_ends = ['，', '；', '、', ',', ';'];
-        for end in &clause_ends {
-            if let Some(pos) = search_text.rfind(*end) {
-                let char_pos = search_text[..=pos].chars().count();
-                let byte_pos = char_to_byte(char_pos);
-                let chunk = text[..byte_pos].trim().to_string();
-
-                let remainder_char_start = char_pos.saturating_sub(self.overlap_chars);
-                let remainder_byte_start = char_to_byte(remainder_char_start);
-                let remainder = text[remainder_byte_start..].trim().to_string();
-                return (chunk, remainder);
+        if self.strategy == ChunkStrategy::Heading {
+            let clause_ends = ['，', '；', '、', ',', ';'];
+            for end in &clause_ends {
+                if let Some(pos) = search_text.rfind(*end) {
+                    return split_at(search_text[..pos + end.len_utf8()].chars().count());
+                }
             }
         }
 
         // Fall back to whitespace boundary
         if let Some(pos) = search_text.rfind(char::is_whitespace) {
-            let char_pos = search_text[..pos].chars().count();
-            let byte_pos = char_to_byte(char_pos);
-            let chunk = text[..byte_pos].trim().to_string();
-
-            let remainder_char_start = char_pos.saturating_sub(self.overlap_chars);
-            let remainder_byte_start = char_to_byte(remainder_char_start);
-            let remainder = text[remainder_byte_start..].trim().to_string();
-            return (chunk, remainder);
+            return split_at(search_text[..pos].chars().count());
         }
 
-        // Last resort: hard split at max_chunk_chars (safe because we use char index)
-        let byte_pos = char_to_byte(self.max_chunk_chars);
-        let chunk = text[..byte_pos].to_string();
-
-        let remainder_char_start = self.max_chunk_chars.saturating_sub(self.overlap_chars);
-        let remainder_byte_start = char_to_byte(remainder_char_start);
-        let remainder = text[remainder_byte_start..].to_string();
-        (chunk, remainder)
+        // Last resort: hard split at the window's end (safe because we use char index)
+        split_at(window)
     }
 
     fn post_process_chunks(&self, chunks: Vec<TextChunk>) -> Vec<TextChunk> {
@@ -244,11 +413,16 @@ impl Chunker {
 
         for chunk in chunks {
             if chunk.content.chars().count() < min_chunk_chars {
-                // Try to merge with previous chunk
-                if let Some(last) = result.last_mut() {
+                // Try to merge with previous chunk, if the two still fit in one
+                if let Some(last) = result.last_mut().filter(|last| {
+                    estimate_tokens(&last.content) + estimate_tokens(&chunk.content)
+                        < self.max_chunk_tokens
+                }) {
                     last.content.push_str("\n\n");
                     last.content.push_str(&chunk.content);
                     last.end_line = chunk.end_line;
+                    last.end_byte = chunk.end_byte;
+                    last.code_language = last.code_language.take().or(chunk.code_language);
                     continue;
                 }
             }
@@ -259,6 +433,312 @@ impl Chunker {
     }
 }
 
+/// A code block as the chunker collects it
+struct CodeBlock<'a> {
+    source: &'a str,
+    /// Opening fence as written (``` for indented blocks)
+    fence: String,
+    /// First word of the fence's info string
+    language: Option<String>,
+    /// Byte range of the whole block in the source
+    start: usize,
+    end: usize,
+    /// The code, without fences
+    code: String,
+    /// (offset in code, offset in source) where each piece of code starts
+    anchors: Vec<(usize, usize)>,
+}
+
+impl<'a> CodeBlock<'a> {
+    fn new(source: &'a str, kind: CodeBlockKind, start: usize) -> Self {
+        let (fence, language) = match kind {
+            CodeBlockKind::Fenced(info) => {
+                let line = source[start..].trim_start();
+                let fence_char = if line.starts_with('~') { '~' } else { '`' };
+                let len = line.chars().take_while(|c| *c == fence_char).count();
+                let language = info
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .next()
+                    .filter(|word| !word.is_empty())
+                    .map(str::to_string);
+                (fence_char.to_string().repeat(len.max(3)), language)
+            }
+            CodeBlockKind::Indented => ("```".to_string(), None),
+        };
+        Self {
+            source,
+            fence,
+            language,
+            start,
+            end: start,
+            code: String::new(),
+            anchors: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, text: &str, source_pos: usize) {
+        self.anchors.push((self.code.len(), source_pos));
+        self.code.push_str(text);
+    }
+
+    /// The opening fence line, language included
+    fn header(&self) -> String {
+        format!("{}{}\n", self.fence, self.language.as_deref().unwrap_or(""))
+    }
+
+    /// Estimated tokens of `code[range]` once fenced
+    fn fenced_tokens(&self, range: Range<usize>) -> usize {
+        let code = &self.code[range];
+        let newline = if code.ends_with('\n') { "" } else { "\n" };
+        estimate_tokens(&format!(
+            "{}{}{}{}",
+            self.header(),
+            code,
+            newline,
+            self.fence
+        ))
+    }
+
+    /// Ranges of `code`, cut at line boundaries, that fit in `max_tokens`
+    /// once fenced. A single line longer than that is a piece of its own.
+    fn pieces(&self, max_tokens: usize) -> Vec<Range<usize>> {
+        let budget = max_tokens.saturating_sub(self.fenced_tokens(0..0));
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        let mut pos = 0;
+        let mut count = TokenCount::default();
+        for line in self.code.split_inclusive('\n') {
+            let mut with_line = count;
+            line.chars().for_each(|c| with_line.add(c));
+            if pos > start && with_line.tokens() > budget {
+                pieces.push(start..pos);
+                start = pos;
+                with_line = TokenCount::default();
+                line.chars().for_each(|c| with_line.add(c));
+            }
+            count = with_line;
+            pos += line.len();
+        }
+        pieces.push(start..self.code.len());
+        pieces
+    }
+
+    /// Source offset of the code at `code_pos`
+    fn source_offset(&self, code_pos: usize) -> usize {
+        let i = self.anchors.partition_point(|(code, _)| *code <= code_pos);
+        match i.checked_sub(1).map(|i| self.anchors[i]) {
+            Some((code, source)) => (source + (code_pos - code)).min(self.end),
+            None => self.start,
+        }
+    }
+}
+
+/// Estimated tokens of text, counted as it grows
+#[derive(Debug, Default, Clone, Copy)]
+struct TokenCount {
+    /// CJK characters, a token each
+    wide: usize,
+    /// Other characters, about four to a token
+    narrow: usize,
+}
+
+impl TokenCount {
+    fn add(&mut self, c: char) {
+        if matches!(c, '\u{2E80}'..='\u{9FFF}' | '\u{AC00}'..='\u{D7AF}' | '\u{F900}'..='\u{FAFF}')
+        {
+            self.wide += 1;
+        } else {
+            self.narrow += 1;
+        }
+    }
+
+    fn tokens(&self) -> usize {
+        self.wide + self.narrow.div_ceil(4)
+    }
+}
+
+/// Rough token count of `text` for the usual embedding tokenizers: about
+/// four characters per token, with each CJK character a token of its own
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    let mut count = TokenCount::default();
+    text.chars().for_each(|c| count.add(c));
+    count.tokens()
+}
+
+/// How many of `chars`, from the first, fit in `max_tokens`
+fn chars_within(chars: impl Iterator<Item = char>, max_tokens: usize) -> usize {
+    let mut count = TokenCount::default();
+    let mut fitting = 0;
+    for c in chars {
+        count.add(c);
+        if count.tokens() > max_tokens {
+            break;
+        }
+        fitting += 1;
+    }
+    fitting
+}
+
+/// Where an overlap that could start at char `from` should start so it
+/// doesn't begin mid-sentence: at the first sentence starting in `from..to`,
+/// else after the first whitespace, else at `from`
+fn overlap_start(chars: &[char], from: usize, to: usize) -> usize {
+    let sentence_end = |c: char| matches!(c, '.' | '!' | '?' | '。' | '！' | '？');
+    let starts_sentence = |i: usize| {
+        if i == 0 {
+            return true;
+        }
+        if chars[i].is_whitespace() {
+            return false;
+        }
+        chars[..i]
+            .iter()
+            .rev()
+            .find(|c| !c.is_whitespace())
+            .is_some_and(|c| sentence_end(*c))
+            && (chars[i - 1].is_whitespace() || !chars[i - 1].is_ascii())
+    };
+    (from..to)
+        .find(|&i| starts_sentence(i))
+        .or_else(|| (from..to).find(|&i| chars[i - 1].is_whitespace() && !chars[i].is_whitespace()))
+        .unwrap_or(from)
+}
+
+/// Where a code block sits in the text collected for a chunk
+struct CodeSpan {
+    range: Range<usize>,
+    language: Option<String>,
+}
+
+/// Move the split points `split_chunk` chose out of code blocks: the chunk
+/// ends before the block it would cut (after it, if nothing comes before),
+/// and the remainder starts after the block it would start in
+fn keep_code_whole(
+    text: &str,
+    spans: &[CodeSpan],
+    chunk_end: usize,
+    remainder_start: usize,
+) -> (usize, usize) {
+    let inside = |pos: usize| {
+        spans
+            .iter()
+            .find(|span| span.range.start < pos && pos < span.range.end)
+    };
+    let mut chunk_end = chunk_end;
+    if let Some(span) = inside(chunk_end) {
+        chunk_end = if text[..span.range.start].trim().is_empty() {
+            span.range.end
+        } else {
+            span.range.start
+        };
+    }
+    let mut remainder_start = remainder_start.min(chunk_end);
+    if let Some(span) = inside(remainder_start) {
+        remainder_start = span.range.end;
+    }
+    if remainder_start == 0 {
+        remainder_start = chunk_end;
+    }
+    (chunk_end, remainder_start)
+}
+
+/// Language of the first code block in `range` that has one
+fn language_in(spans: &[CodeSpan], range: Range<usize>) -> Option<String> {
+    spans
+        .iter()
+        .filter(|span| span.range.start < range.end && range.start < span.range.end)
+        .find_map(|span| span.language.clone())
+}
+
+/// Maps the text collected for a chunk back to the source document. Text
+/// events mostly copy the source verbatim, so each piece of text is anchored
+/// at the source offset it came from and positions inside it are counted
+/// from there.
+struct SourceMap<'a> {
+    source: &'a str,
+    /// Byte offset where each line of the source starts
+    line_starts: Vec<usize>,
+    /// (offset in collected text, offset in source) where each piece starts
+    anchors: Vec<(usize, usize)>,
+}
+
+impl<'a> SourceMap<'a> {
+    fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+            anchors: Vec::new(),
+        }
+    }
+
+    fn mark(&mut self, text_pos: usize, source_pos: usize) {
+        self.anchors.push((text_pos, source_pos));
+    }
+
+    fn clear(&mut self) {
+        self.anchors.clear();
+    }
+
+    /// Forget the text before `text_pos`, which becomes offset 0
+    fn drop_before(&mut self, text_pos: usize) {
+        let start = self.source_offset(text_pos);
+        self.anchors.retain(|(text, _)| *text > text_pos);
+        for (text, _) in &mut self.anchors {
+            *text -= text_pos;
+        }
+        if let Some(start) = start {
+            self.anchors.insert(0, (0, start));
+        }
+    }
+
+    fn source_offset(&self, text_pos: usize) -> Option<usize> {
+        let i = self.anchors.partition_point(|(text, _)| *text <= text_pos);
+        let (text, source) = self.anchors.get(i.checked_sub(1)?)?;
+        Some((source + (text_pos - text)).min(self.source.len()))
+    }
+
+    /// 1-based line holding the byte at `offset`
+    fn line_at(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+
+    /// A chunk of `text[range]`, trimmed, located in the source
+    fn text_chunk(&self, text: &str, range: Range<usize>, headings: Vec<String>) -> TextChunk {
+        let piece = &text[range.clone()];
+        let start = range.start + (piece.len() - piece.trim_start().len());
+        let end = (range.start + piece.trim_end().len()).max(start);
+
+        let mut start_byte = self.source_offset(start).unwrap_or(0);
+        while !self.source.is_char_boundary(start_byte) {
+            start_byte -= 1;
+        }
+        let mut end_byte = match end.checked_sub(1).filter(|last| *last >= start) {
+            Some(last) => self
+                .source_offset(last)
+                .map_or(start_byte, |offset| offset + 1),
+            None => start_byte,
+        };
+        end_byte = end_byte.clamp(start_byte, self.source.len());
+        while !self.source.is_char_boundary(end_byte) {
+            end_byte += 1;
+        }
+
+        TextChunk {
+            content: text[start..end].to_string(),
+            heading_path: headings.join(" > "),
+            headings,
+            start_line: self.line_at(start_byte),
+            end_line: self.line_at(end_byte.saturating_sub(1).max(start_byte)),
+            start_byte,
+            end_byte,
+            code_language: None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -272,9 +752,115 @@ mod tests {
         assert!(!chunks.is_empty());
     }
 
+    #[test]
+    fn test_tiny_doc_is_one_chunk() {
+        let content = "# Note\n\nJust a line.\n";
+        let chunks = Chunker::new(50, 10).chunk(content, "test.md");
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, "Just a line.");
+        assert_eq!(chunks[0].heading_path, "Note");
+    }
+
+    #[test]
+    fn test_doc_of_exactly_max_tokens_is_one_chunk() {
+        let content: String = (1..=4)
+            .map(|i| format!("Sentence number {} of the test doc ends here. ", i))
+            .collect();
+        let content = content.trim_end();
+        let tokens = estimate_tokens(content);
+
+        let chunker = Chunker::new(tokens, 0);
+        assert_eq!(chunker.chunk(content, "test.md").len(), 1);
+        assert_eq!(chunker.chunk_plain(content).len(), 1);
+
+        let chunker = Chunker::new(tokens - 1, 0);
+        for chunks in [
+            chunker.chunk(content, "test.md"),
+            chunker.chunk_plain(content),
+        ] {
+            assert_eq!(chunks.len(), 2);
+            assert!(chunks[0]
+                .content
+                .ends_with("number 3 of the test doc ends here."));
+            assert_eq!(
+                chunks[1].content,
+                "Sentence number 4 of the test doc ends here."
+            );
+        }
+    }
+
+    #[test]
+    fn test_overlap_repeats_trailing_sentence() {
+        let content: String = (1..=4)
+            .map(|i| format!("Sentence number {} of the test doc ends here. ", i))
+            .collect();
+        let chunks = Chunker::new(30, 12).chunk(&content, "test.md");
+
+        assert_eq!(chunks.len(), 3);
+        for pair in chunks.windows(2) {
+            let (previous, next) = (&pair[0].content, &pair[1].content);
+            let trailing = &previous[previous.rfind("Sentence").unwrap()..];
+            assert!(next.starts_with(trailing));
+            assert!(estimate_tokens(previous) <= 30);
+        }
+        assert!(chunks[2]
+            .content
+            .ends_with("number 4 of the test doc ends here."));
+    }
+
+    #[test]
+    fn test_small_doc_is_cut_without_overlap() {
+        let content =
+            "A short note that runs a little over the size of a chunk. It ends with one more line.";
+        let chunks = Chunker::new(20, 10).chunk(content, "test.md");
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks[0].content,
+            "A short note that runs a little over the size of a chunk."
+        );
+        assert_eq!(chunks[1].content, "It ends with one more line.");
+    }
+
+    #[test]
+    fn test_strategies_choose_where_chunks_end() {
+        let first_chunk = |strategy, content: &str| {
+            Chunker::new(10, 0)
+                .with_strategy(strategy)
+                .chunk(content, "test.md")
+                .remove(0)
+                .content
+        };
+
+        let sentence = "One two, three. Four five six seven eight nine ten eleven twelve.";
+        assert_eq!(
+            first_chunk(ChunkStrategy::Heading, sentence),
+            "One two, three."
+        );
+        assert_eq!(
+            first_chunk(ChunkStrategy::Sentence, sentence),
+            "One two, three."
+        );
+        assert_eq!(
+            first_chunk(ChunkStrategy::Fixed, sentence),
+            "One two, three. Four five six seven"
+        );
+
+        let clause = "Alpha beta gamma, delta epsilon zeta eta theta iota kappa lambda.";
+        assert_eq!(
+            first_chunk(ChunkStrategy::Heading, clause),
+            "Alpha beta gamma,"
+        );
+        assert_eq!(
+            first_chunk(ChunkStrategy::Sentence, clause),
+            "Alpha beta gamma, delta epsilon zeta"
+        );
+    }
+
     #[test]
     fn test_chinese_content() {
-        let chunker = Chunker::new(100, 20);
+        let chunker = Chunker::new(20, 5);
         let content = "# 标题\n\n这是一段很长的中文内容。我们需要确保在切分时不会切到汉字中间。这对于处理多语言内容非常重要。";
         let chunks = chunker.chunk(content, "test.md");
 
@@ -301,9 +887,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_headings_cover_setext_and_skip_blockquotes() {
+        let content = "Guide\n=====\n\nSetup\n-----\n\n### Install\n\nRun the installer.\n\n> ## Quoted\n> Said elsewhere.\n\n## Usage\n\nOpen the app.\n";
+        let chunks = Chunker::new(12, 0).chunk(content, "test.md");
+
+        let install = chunks
+            .iter()
+            .find(|c| c.content.starts_with("Run the installer."))
+            .unwrap();
+        assert_eq!(install.headings, ["Guide", "Setup", "Install"]);
+        assert_eq!(install.heading_path, "Guide > Setup > Install");
+        assert!(install.content.contains("Quoted\n\nSaid elsewhere."));
+        let usage = chunks.last().unwrap();
+        assert_eq!(usage.headings, ["Guide", "Usage"]);
+        assert_eq!(usage.content, "Open the app.");
+    }
+
     #[test]
     fn test_mixed_language() {
-        let chunker = Chunker::new(50, 10);
+        let chunker = Chunker::new(12, 3);
         let content = "Hello 世界！This is a test. 这是测试。Mixed content here 混合内容。";
         let chunks = chunker.chunk(content, "test.md");
 
@@ -313,4 +916,122 @@ mod tests {
             println!("Mixed chunk: {}", chunk.content);
         }
     }
+
+    #[test]
+    fn test_chunk_positions_point_at_source() {
+        let chunker = Chunker::new(25, 5);
+        let content = "# Intro\n\nFirst paragraph of the intro, with `code` in it.\n\n## 详情\n\n第二段内容在这里。Second paragraph spans\ntwo lines of text here.\n";
+        let chunks = chunker.chunk(content, "test.md");
+
+        assert!(chunks.len() >= 2);
+        let first = &chunks[0];
+        assert_eq!(first.start_line, 3);
+        assert_eq!(first.end_line, 3);
+        assert_eq!(
+            &content[first.start_byte..first.end_byte],
+            "First paragraph of the intro, with `code` in it."
+        );
+        let last = chunks.last().unwrap();
+        assert!(content[last.start_byte..last.end_byte].ends_with("two lines of text here."));
+        assert_eq!(last.end_line, 8);
+        for chunk in &chunks {
+            assert!(chunk.start_line <= chunk.end_line);
+            assert!(chunk.start_byte < chunk.end_byte);
+        }
+    }
+
+    #[test]
+    fn test_code_block_is_never_split() {
+        let chunker = Chunker::new(30, 5);
+        let block = "```rust\nfn main() {\n\n    let x = 1;\n\n    println!(\"{}\", x);\n}\n```";
+        let content = format!(
+            "Intro text before the code, long enough to matter here.\n\n{}\n\nText after the code block, also long enough to be kept.\n",
+            block
+        );
+        let chunks = chunker.chunk(&content, "test.md");
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert_eq!(chunk.content.matches("```").count() % 2, 0);
+        }
+        let with_code = chunks.iter().find(|c| c.content.contains(block)).unwrap();
+        assert_eq!(with_code.code_language.as_deref(), Some("rust"));
+    }
+
+    #[test]
+    fn test_long_code_block_splits_at_lines_with_fence_header() {
+        let chunker = Chunker::new(20, 3);
+        let code: String = (0..20)
+            .map(|i| format!("let value_{} = {};\n", i, i))
+            .collect();
+        let content = format!("```rust\n{}```\n", code);
+        let chunks = chunker.chunk(&content, "test.md");
+
+        assert!(chunks.len() > 1);
+        let mut pieces = String::new();
+        for chunk in &chunks {
+            assert!(estimate_tokens(&chunk.content) <= 20);
+            assert_eq!(chunk.code_language.as_deref(), Some("rust"));
+            let piece = chunk.content.strip_prefix("```rust\n").unwrap();
+            pieces.push_str(piece.strip_suffix("```").unwrap());
+        }
+        assert_eq!(pieces, code);
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(chunks[1].start_line, chunks[0].end_line + 1);
+        assert_eq!(chunks.last().unwrap().end_line, 22);
+    }
+
+    #[test]
+    fn test_unterminated_fence_runs_to_end_of_doc() {
+        let content = "Some intro paragraph that is long enough to stand alone.\n\n```python\ndef main():\n    return 1\n";
+        let chunks = Chunker::default().chunk(content, "test.md");
+
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0]
+            .content
+            .ends_with("```python\ndef main():\n    return 1\n```"));
+        assert_eq!(chunks[0].code_language.as_deref(), Some("python"));
+        assert_eq!(chunks[0].end_byte, content.trim_end().len());
+        assert_eq!(chunks[0].end_line, 4);
+
+        let long = format!("```\n{}", "echo one two three four five\n".repeat(6));
+        let chunks = Chunker::new(18, 3).chunk(&long, "test.md");
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.content.starts_with("```\n"));
+            assert!(chunk.content.ends_with("\n```"));
+            assert_eq!(chunk.code_language, None);
+        }
+    }
+
+    #[test]
+    fn test_nested_fences_keep_their_own_fence() {
+        let content = "~~~~markdown\nExample:\n```js\nlet a = 1;\n```\n~~~~\n";
+        let chunks = Chunker::default().chunk(content, "test.md");
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, content.trim_end());
+        assert_eq!(chunks[0].code_language.as_deref(), Some("markdown"));
+
+        let content = "- item\n\n  ```sh\n  echo hi\n  ```\n";
+        let chunks = Chunker::default().chunk(content, "test.md");
+        assert!(chunks[0].content.contains("```sh\necho hi\n```"));
+        assert_eq!(chunks[0].code_language.as_deref(), Some("sh"));
+    }
+
+    #[test]
+    fn test_chunk_plain_ignores_markdown_syntax() {
+        let chunker = Chunker::new(20, 3);
+        let line = "# not a heading, just a line of a plain text file.\n";
+        let content = line.repeat(4);
+        let chunks = chunker.chunk_plain(&content);
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.heading_path.is_empty()));
+        assert!(chunks[0].content.starts_with("# not a heading"));
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(
+            &content[chunks[0].start_byte..chunks[0].end_byte],
+            chunks[0].content
+        );
+    }
 }
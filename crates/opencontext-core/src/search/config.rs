@@ -1,17 +1,26 @@
 //! Search configuration
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use super::error::{SearchError, SearchResult};
+use crate::retry::RetryPolicy;
 
 /// Main search configuration
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct SearchConfig {
     /// Embedding API configuration
     #[serde(default)]
     pub embedding: EmbeddingConfig,
 
+    /// How docs are cut into chunks
+    #[serde(default)]
+    pub chunking: ChunkingConfig,
+
     /// Search behavior configuration
     #[serde(default)]
     pub search: SearchBehaviorConfig,
@@ -19,11 +28,78 @@ pub struct SearchConfig {
     /// Paths configuration
     #[serde(default)]
     pub paths: PathsConfig,
+
+    /// Named embedding profiles, each with its own index
+    #[serde(default)]
+    pub profiles: BTreeMap<String, EmbeddingProfile>,
+
+    /// Folder path -> embedding profile name. Docs under an assigned folder
+    /// are indexed with that profile; the rest use the main embedding config.
+    #[serde(default)]
+    pub folder_profiles: BTreeMap<String, String>,
+
+    /// Embedding model -> USD per million tokens, for usage cost estimates.
+    /// Known OpenAI models are priced without an entry.
+    #[serde(default)]
+    pub prices: BTreeMap<String, f64>,
 }
 
+/// Service that computes embeddings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingProvider {
+    /// OpenAI or any server implementing its `/embeddings` endpoint
+    #[default]
+    OpenAI,
+    /// A local Ollama server, via its `/api/embed` endpoint; needs no key
+    Ollama,
+    /// A model run in-process with fastembed (ONNX). It is downloaded once,
+    /// after which embedding works offline. Needs the `local-embeddings`
+    /// feature.
+    Local,
+}
+
+impl EmbeddingProvider {
+    /// Parse a config value such as `"ollama"`, ignoring case
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "openai" => Some(Self::OpenAI),
+            "ollama" => Some(Self::Ollama),
+            "local" | "fastembed" => Some(Self::Local),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OpenAI => "openai",
+            Self::Ollama => "ollama",
+            Self::Local => "local",
+        }
+    }
+
+    /// Whether this build can embed with the provider
+    pub fn is_available(&self) -> bool {
+        *self != Self::Local || cfg!(feature = "local-embeddings")
+    }
+}
+
+/// Ollama's default address, used when no API base is configured
+pub const OLLAMA_DEFAULT_API_BASE: &str = "http://localhost:11434";
+
+/// Embedding model used with Ollama when none is configured
+pub const OLLAMA_DEFAULT_MODEL: &str = "nomic-embed-text";
+
+/// fastembed model used by the local provider when none is configured
+pub const LOCAL_DEFAULT_MODEL: &str = "BAAI/bge-small-en-v1.5";
+
 /// Embedding API configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EmbeddingConfig {
+    /// Embedding service
+    #[serde(default)]
+    pub provider: EmbeddingProvider,
+
     /// OpenAI API key (can also use OPENAI_API_KEY env var)
     #[serde(default)]
     pub api_key: Option<String>,
@@ -36,40 +112,163 @@ pub struct EmbeddingConfig {
     #[serde(default = "default_model")]
     pub model: String,
 
-    /// Embedding dimensions
+    /// Embedding dimensions. Ollama models are never sent this; their
+    /// dimensions are detected from the first response.
     #[serde(default = "default_dimensions")]
     pub dimensions: usize,
 
     /// Batch size for embedding requests
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
+
+    /// Batches the indexer keeps in flight at once
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
+    /// Attempts per request, including the first, when the API answers
+    /// 429/5xx or can't be reached
+    #[serde(default = "default_retry_max_attempts")]
+    pub retry_max_attempts: u32,
+
+    /// Wait before the first retry in milliseconds, doubled for each retry
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+
+    /// Embed each chunk with its heading path in front, so chunks deep in
+    /// a doc carry the sections they belong to
+    #[serde(default)]
+    pub include_headings: bool,
+
+    /// Proxy embedding requests go through, e.g. `http://proxy.corp:8080`.
+    /// Without one the usual `HTTPS_PROXY`/`HTTP_PROXY` variables apply.
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+
+    /// Headers sent with every embedding request, e.g. a gateway's own auth
+    /// header. The API key's `Authorization` header takes precedence.
+    #[serde(default)]
+    pub extra_headers: BTreeMap<String, String>,
 }
 
 impl Default for EmbeddingConfig {
     fn default() -> Self {
         Self {
+            provider: EmbeddingProvider::default(),
             api_key: None,
             api_base: default_api_base(),
             model: default_model(),
             dimensions: default_dimensions(),
             batch_size: default_batch_size(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            retry_max_attempts: default_retry_max_attempts(),
+            retry_base_delay_ms: default_retry_base_delay_ms(),
+            include_headings: false,
+            proxy_url: None,
+            extra_headers: BTreeMap::new(),
         }
     }
 }
 
 impl EmbeddingConfig {
     /// Get API key from config or environment
+    ///
+    /// Ollama and local models run without one, so they get the configured
+    /// key or an empty string and never `ApiKeyMissing`.
     pub fn get_api_key(&self) -> SearchResult<String> {
         if let Some(ref key) = self.api_key {
             if !key.is_empty() {
                 return Ok(key.clone());
             }
         }
+        if self.provider != EmbeddingProvider::OpenAI {
+            return Ok(String::new());
+        }
 
         std::env::var("OPENAI_API_KEY")
             .or_else(|_| std::env::var("OPENAI_KEY"))
             .map_err(|_| SearchError::ApiKeyMissing)
     }
+
+    /// How embedding requests are retried
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(
+            self.retry_max_attempts,
+            Duration::from_millis(self.retry_base_delay_ms),
+        )
+    }
+
+    /// Whether embeddings are computed on this machine: in-process, or by a
+    /// server on a loopback address
+    pub fn is_local(&self) -> bool {
+        if self.provider == EmbeddingProvider::Local {
+            return true;
+        }
+        let Ok(url) = reqwest::Url::parse(&self.api_base) else {
+            return false;
+        };
+        match url.host_str() {
+            Some("localhost") => true,
+            Some(host) => host
+                .trim_matches(['[', ']'])
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback()),
+            None => false,
+        }
+    }
+
+    /// Most tokens the model embeds of one input, for the models whose
+    /// limit is known
+    pub fn context_tokens(&self) -> Option<usize> {
+        match self.provider {
+            // fastembed's models are BERT-sized
+            EmbeddingProvider::Local => Some(512),
+            EmbeddingProvider::OpenAI if self.model.starts_with("text-embedding-") => Some(8191),
+            _ => None,
+        }
+    }
+
+    /// Replace the OpenAI defaults of an unset API base and model by the
+    /// provider's own. The local provider has no API base.
+    fn apply_provider_defaults(&mut self) {
+        let (api_base, model) = match self.provider {
+            EmbeddingProvider::OpenAI => return,
+            EmbeddingProvider::Ollama => (Some(OLLAMA_DEFAULT_API_BASE), OLLAMA_DEFAULT_MODEL),
+            EmbeddingProvider::Local => (None, LOCAL_DEFAULT_MODEL),
+        };
+        if let Some(api_base) = api_base.filter(|_| self.api_base == default_api_base()) {
+            self.api_base = api_base.to_string();
+        }
+        if self.model == default_model() {
+            self.model = model.to_string();
+        }
+    }
+}
+
+/// Named embedding profile
+///
+/// Unset fields fall back to the main embedding config. Each profile keeps its
+/// own LanceDB index so indexes built with different models can coexist.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct EmbeddingProfile {
+    /// API key override
+    #[serde(default, alias = "EMBEDDING_API_KEY")]
+    pub api_key: Option<String>,
+
+    /// API base URL override
+    #[serde(default, alias = "EMBEDDING_API_BASE")]
+    pub api_base: Option<String>,
+
+    /// Model name override
+    #[serde(default, alias = "EMBEDDING_MODEL")]
+    pub model: Option<String>,
+
+    /// Embedding dimensions override
+    #[serde(default, alias = "EMBEDDING_DIMENSIONS")]
+    pub dimensions: Option<usize>,
+
+    /// LanceDB path override (default: ~/.opencontext/profiles/<name>/lancedb)
+    #[serde(default, alias = "LANCEDB_PATH")]
+    pub lancedb_path: Option<PathBuf>,
 }
 
 fn default_api_base() -> String {
@@ -93,28 +292,106 @@ fn default_batch_size() -> usize {
     10 // DashScope and some other APIs limit batch size to 10
 }
 
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
+fn default_retry_max_attempts() -> u32 {
+    RetryPolicy::default().max_attempts
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    RetryPolicy::default().base_delay.as_millis() as u64
+}
+
+/// Where chunk boundaries go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    /// Markdown-aware: a chunk never spans two sections, and splits prefer
+    /// paragraphs, then sentences
+    #[default]
+    Heading,
+    /// Fixed-size windows of the raw text, cut between words
+    Fixed,
+    /// As many whole sentences of the raw text as fit
+    Sentence,
+}
+
+impl ChunkStrategy {
+    /// Parse a config value such as `"sentence"`, ignoring case
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "heading" => Some(Self::Heading),
+            "fixed" => Some(Self::Fixed),
+            "sentence" => Some(Self::Sentence),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Heading => "heading",
+            Self::Fixed => "fixed",
+            Self::Sentence => "sentence",
+        }
+    }
+}
+
+/// Chunking configuration. Changing it only reaches docs indexed since;
+/// the index reports when a full rebuild is needed to re-chunk the rest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkingConfig {
+    /// Largest chunk docs are cut into, in estimated tokens. Capped at the
+    /// embedding model's input limit where that is known.
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: usize,
+
+    /// Estimated tokens each chunk repeats from the end of the one before
+    #[serde(default = "default_overlap_tokens")]
+    pub overlap_tokens: usize,
+
+    /// Where chunk boundaries go
+    #[serde(default)]
+    pub strategy: ChunkStrategy,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: default_max_tokens(),
+            overlap_tokens: default_overlap_tokens(),
+            strategy: ChunkStrategy::default(),
+        }
+    }
+}
+
+fn default_max_tokens() -> usize {
+    400
+}
+
+fn default_overlap_tokens() -> usize {
+    50
+}
+
 /// Search behavior configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SearchBehaviorConfig {
     /// Default result limit
     #[serde(default = "default_limit")]
     pub default_limit: usize,
 
-    /// Maximum chunk size in characters
-    #[serde(default = "default_chunk_size")]
-    pub chunk_size: usize,
-
-    /// Overlap between chunks in characters
-    #[serde(default = "default_chunk_overlap")]
-    pub chunk_overlap: usize,
+    /// Weight of the vector ranking in hybrid search (0-1); the keyword
+    /// ranking gets the rest
+    #[serde(default = "default_hybrid_alpha")]
+    pub hybrid_alpha: f32,
 }
 
 impl Default for SearchBehaviorConfig {
     fn default() -> Self {
         Self {
             default_limit: default_limit(),
-            chunk_size: default_chunk_size(),
-            chunk_overlap: default_chunk_overlap(),
+            hybrid_alpha: default_hybrid_alpha(),
         }
     }
 }
@@ -123,16 +400,12 @@ fn default_limit() -> usize {
     10
 }
 
-fn default_chunk_size() -> usize {
-    1500
-}
-
-fn default_chunk_overlap() -> usize {
-    200
+fn default_hybrid_alpha() -> f32 {
+    0.7
 }
 
 /// Paths configuration
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct PathsConfig {
     /// LanceDB database path
     #[serde(default)]
@@ -141,6 +414,14 @@ pub struct PathsConfig {
     /// Index metadata path
     #[serde(default)]
     pub index_metadata_path: Option<PathBuf>,
+
+    /// Embedding usage ledger path
+    #[serde(default)]
+    pub usage_ledger_path: Option<PathBuf>,
+
+    /// Where the local provider keeps downloaded models
+    #[serde(default)]
+    pub models_path: Option<PathBuf>,
 }
 
 impl PathsConfig {
@@ -173,6 +454,36 @@ impl PathsConfig {
             .map(|h| h.join(".opencontext").join("index-metadata.json"))
             .unwrap_or_else(|| PathBuf::from(".opencontext/index-metadata.json"))
     }
+
+    /// Get embedding usage ledger path
+    pub fn get_usage_ledger_path(&self) -> PathBuf {
+        if let Some(ref path) = self.usage_ledger_path {
+            return path.clone();
+        }
+
+        if let Ok(root) = std::env::var("OPENCONTEXT_ROOT") {
+            return PathBuf::from(root).join("embedding-usage.json");
+        }
+
+        dirs::home_dir()
+            .map(|h| h.join(".opencontext").join("embedding-usage.json"))
+            .unwrap_or_else(|| PathBuf::from(".opencontext/embedding-usage.json"))
+    }
+
+    /// Get the local embedding model cache path
+    pub fn get_models_path(&self) -> PathBuf {
+        if let Some(ref path) = self.models_path {
+            return path.clone();
+        }
+
+        if let Ok(root) = std::env::var("OPENCONTEXT_ROOT") {
+            return PathBuf::from(root).join("models");
+        }
+
+        dirs::home_dir()
+            .map(|h| h.join(".opencontext").join("models"))
+            .unwrap_or_else(|| PathBuf::from(".opencontext/models"))
+    }
 }
 
 /// Node.js compatible config format (config.json)
@@ -180,63 +491,718 @@ impl PathsConfig {
 #[derive(Debug, Clone, Default, Deserialize)]
 struct NodeJsConfig {
     // New naming convention
+    #[serde(rename = "EMBEDDING_PROVIDER")]
+    embedding_provider: Option<String>,
     #[serde(rename = "EMBEDDING_API_KEY")]
     embedding_api_key: Option<String>,
     #[serde(rename = "EMBEDDING_API_BASE")]
     embedding_api_base: Option<String>,
     #[serde(rename = "EMBEDDING_MODEL")]
     embedding_model: Option<String>,
+    #[serde(rename = "EMBEDDING_BATCH_SIZE")]
+    embedding_batch_size: Option<usize>,
+    #[serde(rename = "EMBEDDING_MAX_CONCURRENT_REQUESTS")]
+    embedding_max_concurrent_requests: Option<usize>,
+    #[serde(rename = "EMBEDDING_RETRY_MAX_ATTEMPTS")]
+    embedding_retry_max_attempts: Option<u32>,
+    #[serde(rename = "EMBEDDING_RETRY_BASE_DELAY_MS")]
+    embedding_retry_base_delay_ms: Option<u64>,
+    #[serde(rename = "EMBEDDING_INCLUDE_HEADINGS")]
+    embedding_include_headings: Option<bool>,
+    #[serde(rename = "EMBEDDING_PROXY_URL")]
+    embedding_proxy_url: Option<String>,
+    #[serde(rename = "EMBEDDING_EXTRA_HEADERS")]
+    embedding_extra_headers: Option<BTreeMap<String, String>>,
+    #[serde(rename = "CHUNKING_MAX_TOKENS", alias = "EMBEDDING_MAX_CHUNK_TOKENS")]
+    chunking_max_tokens: Option<usize>,
+    #[serde(rename = "CHUNKING_OVERLAP_TOKENS", alias = "EMBEDDING_CHUNK_OVERLAP")]
+    chunking_overlap_tokens: Option<usize>,
+    #[serde(rename = "CHUNKING_STRATEGY")]
+    chunking_strategy: Option<String>,
+    #[serde(rename = "SEARCH_HYBRID_ALPHA")]
+    search_hybrid_alpha: Option<f32>,
 
     // Legacy naming (backward compatibility)
     #[serde(rename = "OPENAI_API_KEY")]
     openai_api_key: Option<String>,
     #[serde(rename = "OPENAI_BASE_URL")]
     openai_base_url: Option<String>,
+
+    #[serde(rename = "EMBEDDING_PROFILES")]
+    embedding_profiles: Option<BTreeMap<String, EmbeddingProfile>>,
+    #[serde(rename = "EMBEDDING_FOLDER_PROFILES")]
+    embedding_folder_profiles: Option<BTreeMap<String, String>>,
+    #[serde(rename = "EMBEDDING_PRICES")]
+    embedding_prices: Option<BTreeMap<String, f64>>,
+}
+
+/// How serious a config problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigIssueSeverity {
+    /// The value (or whole file) could not be used and a default applies
+    Error,
+    /// The value is used but probably not what the user meant
+    Warning,
+}
+
+/// A problem found while loading the config files
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigIssue {
+    pub severity: ConfigIssueSeverity,
+    /// Config file the problem was found in
+    pub file: PathBuf,
+    /// Offending key; nested keys are dotted (`EMBEDDING_PROFILES.work.model`)
+    pub key: Option<String>,
+    pub message: String,
+    /// 1-based position, for syntax errors
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl ConfigIssue {
+    fn error(file: &Path, key: Option<String>, message: impl Into<String>) -> Self {
+        Self::new(ConfigIssueSeverity::Error, file, key, message)
+    }
+
+    fn warning(file: &Path, key: Option<String>, message: impl Into<String>) -> Self {
+        Self::new(ConfigIssueSeverity::Warning, file, key, message)
+    }
+
+    fn new(
+        severity: ConfigIssueSeverity,
+        file: &Path,
+        key: Option<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            severity,
+            file: file.to_path_buf(),
+            key,
+            message: message.into(),
+            line: None,
+            column: None,
+        }
+    }
+
+    fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        if let Some(ref key) = self.key {
+            write!(f, " [{}]", key)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Expected shape of a known config value
+#[derive(Clone, Copy)]
+enum FieldKind {
+    Text,
+    Url,
+    Count,
+    /// A whole number, zero included
+    Amount,
+    Flag,
+    /// A number from 0 to 1
+    Fraction,
+}
+
+/// Scalar keys read from config.json
+const JSON_FIELDS: &[(&str, FieldKind)] = &[
+    ("EMBEDDING_PROVIDER", FieldKind::Text),
+    ("EMBEDDING_API_KEY", FieldKind::Text),
+    ("EMBEDDING_API_BASE", FieldKind::Url),
+    ("EMBEDDING_MODEL", FieldKind::Text),
+    ("EMBEDDING_BATCH_SIZE", FieldKind::Count),
+    ("EMBEDDING_MAX_CONCURRENT_REQUESTS", FieldKind::Count),
+    ("EMBEDDING_RETRY_MAX_ATTEMPTS", FieldKind::Count),
+    ("EMBEDDING_RETRY_BASE_DELAY_MS", FieldKind::Count),
+    ("EMBEDDING_INCLUDE_HEADINGS", FieldKind::Flag),
+    ("EMBEDDING_PROXY_URL", FieldKind::Url),
+    ("CHUNKING_MAX_TOKENS", FieldKind::Count),
+    ("CHUNKING_OVERLAP_TOKENS", FieldKind::Amount),
+    ("CHUNKING_STRATEGY", FieldKind::Text),
+    ("SEARCH_HYBRID_ALPHA", FieldKind::Fraction),
+    ("EMBEDDING_MAX_CHUNK_TOKENS", FieldKind::Count),
+    ("EMBEDDING_CHUNK_OVERLAP", FieldKind::Amount),
+    ("OPENAI_API_KEY", FieldKind::Text),
+    ("OPENAI_BASE_URL", FieldKind::Url),
+];
+
+/// Keys renamed since, as (old, new). The old key is still read as an alias
+/// of the new one.
+const RENAMED_FIELDS: &[(&str, &str)] = &[
+    ("EMBEDDING_MAX_CHUNK_TOKENS", "CHUNKING_MAX_TOKENS"),
+    ("EMBEDDING_CHUNK_OVERLAP", "CHUNKING_OVERLAP_TOKENS"),
+];
+
+/// config.toml keys since replaced, as (table, old key, new key). They were
+/// in characters and the new ones are in tokens, so they are not aliases.
+const REPLACED_TOML_FIELDS: &[(&str, &str, &str)] = &[
+    ("search", "chunk_size", "chunking.max_tokens"),
+    ("search", "chunk_overlap", "chunking.overlap_tokens"),
+];
+
+/// Keys of an `EMBEDDING_PROFILES` entry, including serde aliases
+const PROFILE_FIELDS: &[(&str, FieldKind)] = &[
+    ("api_key", FieldKind::Text),
+    ("EMBEDDING_API_KEY", FieldKind::Text),
+    ("api_base", FieldKind::Url),
+    ("EMBEDDING_API_BASE", FieldKind::Url),
+    ("model", FieldKind::Text),
+    ("EMBEDDING_MODEL", FieldKind::Text),
+    ("dimensions", FieldKind::Count),
+    ("EMBEDDING_DIMENSIONS", FieldKind::Count),
+    ("lancedb_path", FieldKind::Text),
+    ("LANCEDB_PATH", FieldKind::Text),
+];
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Check that a base URL is an absolute http(s) URL. Empty means "default".
+fn check_api_base(value: &str) -> Option<String> {
+    if value.is_empty() {
+        return None;
+    }
+    match reqwest::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => None,
+        Ok(_) => Some(format!("'{}' must be an http:// or https:// URL", value)),
+        Err(e) => Some(format!("'{}' is not a valid URL ({})", value, e)),
+    }
+}
+
+/// Check a value against its expected kind. `Err` means the value is unusable
+/// and gets dropped; `Ok(Some(_))` reports a problem with a value that is kept.
+fn check_field(kind: FieldKind, value: &Value) -> Result<Option<String>, String> {
+    match (kind, value) {
+        (_, Value::Null) => Ok(None),
+        (FieldKind::Text, Value::String(_)) => Ok(None),
+        // An invalid URL is kept: falling back to the default base would send
+        // the API key to a different host than the user configured.
+        (FieldKind::Url, Value::String(url)) => Ok(check_api_base(url)),
+        (FieldKind::Count, Value::Number(n)) if n.as_u64().is_some_and(|n| n > 0) => Ok(None),
+        (FieldKind::Count, _) => Err(format!(
+            "expected a positive whole number, found {}",
+            json_type_name(value)
+        )),
+        (FieldKind::Amount, Value::Number(n)) if n.as_u64().is_some() => Ok(None),
+        (FieldKind::Amount, _) => Err(format!(
+            "expected a whole number, found {}",
+            json_type_name(value)
+        )),
+        (FieldKind::Flag, Value::Bool(_)) => Ok(None),
+        (FieldKind::Flag, _) => Err(format!(
+            "expected true or false, found {}",
+            json_type_name(value)
+        )),
+        (FieldKind::Fraction, Value::Number(n))
+            if n.as_f64().is_some_and(|n| (0.0..=1.0).contains(&n)) =>
+        {
+            Ok(None)
+        }
+        (FieldKind::Fraction, Value::Number(n)) => {
+            Err(format!("expected a number from 0 to 1, found {}", n))
+        }
+        (FieldKind::Fraction, _) => Err(format!(
+            "expected a number from 0 to 1, found {}",
+            json_type_name(value)
+        )),
+        _ => Err(format!(
+            "expected a string, found {}",
+            json_type_name(value)
+        )),
+    }
+}
+
+/// Check the known fields of a JSON object in place, dropping unusable values
+fn check_fields(
+    map: &mut Map<String, Value>,
+    fields: &[(&str, FieldKind)],
+    prefix: &str,
+    file: &Path,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    for (key, kind) in fields {
+        let Some(value) = map.get(*key) else {
+            continue;
+        };
+        let dotted = Some(format!("{}{}", prefix, key));
+        match check_field(*kind, value) {
+            Ok(None) => {}
+            Ok(Some(message)) => issues.push(ConfigIssue::error(file, dotted, message)),
+            Err(message) => {
+                issues.push(ConfigIssue::error(
+                    file,
+                    dotted,
+                    format!("{}; ignored", message),
+                ));
+                map.remove(*key);
+            }
+        }
+    }
+}
+
+/// Validate config.json content
+///
+/// Returns the document with unusable values removed, or `None` when the
+/// file can't be used at all. Unrelated keys (AI settings etc.) share this
+/// file and are left alone.
+pub(crate) fn check_json_config(
+    file: &Path,
+    content: &str,
+    issues: &mut Vec<ConfigIssue>,
+) -> Option<Value> {
+    let mut value: Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(e) => {
+            issues.push(
+                ConfigIssue::error(
+                    file,
+                    None,
+                    format!("Invalid JSON, using default settings: {}", e),
+                )
+                .at(e.line(), e.column()),
+            );
+            return None;
+        }
+    };
+    let Some(map) = value.as_object_mut() else {
+        issues.push(ConfigIssue::error(
+            file,
+            None,
+            format!(
+                "Expected a JSON object, found {}; using default settings",
+                json_type_name(&value)
+            ),
+        ));
+        return None;
+    };
+
+    check_fields(map, JSON_FIELDS, "", file, issues);
+
+    // Serde rejects a key and its alias together; the new key wins
+    for (old, new) in RENAMED_FIELDS {
+        if map.contains_key(*new) && map.remove(*old).is_some() {
+            issues.push(ConfigIssue::warning(
+                file,
+                Some(old.to_string()),
+                format!("Replaced by {}; ignored", new),
+            ));
+        }
+    }
+
+    let known_key = |key: &str| {
+        JSON_FIELDS.iter().any(|(known, _)| *known == key)
+            || key == "EMBEDDING_PROFILES"
+            || key == "EMBEDDING_FOLDER_PROFILES"
+            || key == "EMBEDDING_PRICES"
+            || key == "EMBEDDING_EXTRA_HEADERS"
+    };
+    for key in map.keys() {
+        if key.starts_with("EMBEDDING_") && !known_key(key) {
+            issues.push(ConfigIssue::warning(
+                file,
+                Some(key.clone()),
+                "Unknown embedding setting; ignored",
+            ));
+        }
+    }
+
+    if let Some(profiles) = map.get_mut("EMBEDDING_PROFILES") {
+        match profiles.as_object_mut() {
+            Some(profiles) => {
+                profiles.retain(|name, profile| {
+                    let prefix = format!("EMBEDDING_PROFILES.{}.", name);
+                    let Some(fields) = profile.as_object_mut() else {
+                        issues.push(ConfigIssue::error(
+                            file,
+                            Some(format!("EMBEDDING_PROFILES.{}", name)),
+                            format!(
+                                "expected an object, found {}; profile ignored",
+                                json_type_name(profile)
+                            ),
+                        ));
+                        return false;
+                    };
+                    check_fields(fields, PROFILE_FIELDS, &prefix, file, issues);
+                    for key in fields.keys() {
+                        if !PROFILE_FIELDS.iter().any(|(known, _)| known == key) {
+                            issues.push(ConfigIssue::warning(
+                                file,
+                                Some(format!("{}{}", prefix, key)),
+                                "Unknown profile setting; ignored",
+                            ));
+                        }
+                    }
+                    true
+                });
+            }
+            None => {
+                issues.push(ConfigIssue::error(
+                    file,
+                    Some("EMBEDDING_PROFILES".to_string()),
+                    format!(
+                        "expected an object, found {}; ignored",
+                        json_type_name(profiles)
+                    ),
+                ));
+                map.remove("EMBEDDING_PROFILES");
+            }
+        }
+    }
+
+    if let Some(assignments) = map.get_mut("EMBEDDING_FOLDER_PROFILES") {
+        match assignments.as_object_mut() {
+            Some(assignments) => {
+                assignments.retain(|folder, profile| {
+                    if profile.is_string() {
+                        return true;
+                    }
+                    issues.push(ConfigIssue::error(
+                        file,
+                        Some(format!("EMBEDDING_FOLDER_PROFILES.{}", folder)),
+                        format!(
+                            "expected a profile name, found {}; ignored",
+                            json_type_name(profile)
+                        ),
+                    ));
+                    false
+                });
+            }
+            None => {
+                issues.push(ConfigIssue::error(
+                    file,
+                    Some("EMBEDDING_FOLDER_PROFILES".to_string()),
+                    format!(
+                        "expected an object, found {}; ignored",
+                        json_type_name(assignments)
+                    ),
+                ));
+                map.remove("EMBEDDING_FOLDER_PROFILES");
+            }
+        }
+    }
+
+    if let Some(prices) = map.get_mut("EMBEDDING_PRICES") {
+        match prices.as_object_mut() {
+            Some(prices) => {
+                prices.retain(|model, price| {
+                    if price.as_f64().is_some_and(|price| price >= 0.0) {
+                        return true;
+                    }
+                    let found = if price.is_number() {
+                        price.to_string()
+                    } else {
+                        json_type_name(price).to_string()
+                    };
+                    issues.push(ConfigIssue::error(
+                        file,
+                        Some(format!("EMBEDDING_PRICES.{}", model)),
+                        format!(
+                            "expected a non-negative price in USD per million tokens, found {}; \
+                             ignored",
+                            found
+                        ),
+                    ));
+                    false
+                });
+            }
+            None => {
+                issues.push(ConfigIssue::error(
+                    file,
+                    Some("EMBEDDING_PRICES".to_string()),
+                    format!(
+                        "expected an object, found {}; ignored",
+                        json_type_name(prices)
+                    ),
+                ));
+                map.remove("EMBEDDING_PRICES");
+            }
+        }
+    }
+
+    if let Some(headers) = map.get_mut("EMBEDDING_EXTRA_HEADERS") {
+        match headers.as_object_mut() {
+            Some(headers) => {
+                headers.retain(|name, header| {
+                    if header.is_string() {
+                        return true;
+                    }
+                    issues.push(ConfigIssue::error(
+                        file,
+                        Some(format!("EMBEDDING_EXTRA_HEADERS.{}", name)),
+                        format!(
+                            "expected a header value, found {}; ignored",
+                            json_type_name(header)
+                        ),
+                    ));
+                    false
+                });
+            }
+            None => {
+                issues.push(ConfigIssue::error(
+                    file,
+                    Some("EMBEDDING_EXTRA_HEADERS".to_string()),
+                    format!(
+                        "expected an object, found {}; ignored",
+                        json_type_name(headers)
+                    ),
+                ));
+                map.remove("EMBEDDING_EXTRA_HEADERS");
+            }
+        }
+    }
+
+    Some(value)
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|l| l.chars().count())
+        .unwrap_or(0)
+        + 1;
+    (line, column)
+}
+
+/// Validate config.toml content, returning the parsed config if usable
+pub(crate) fn check_toml_config(
+    file: &Path,
+    content: &str,
+    issues: &mut Vec<ConfigIssue>,
+) -> Option<SearchConfig> {
+    let config = match toml::from_str::<SearchConfig>(content) {
+        Ok(config) => config,
+        Err(e) => {
+            let mut issue = ConfigIssue::error(
+                file,
+                None,
+                format!("Invalid TOML, file ignored: {}", e.message()),
+            );
+            if let Some(span) = e.span() {
+                let (line, column) = line_column(content, span.start);
+                issue = issue.at(line, column);
+            }
+            issues.push(issue);
+            return None;
+        }
+    };
+    // Serde drops unknown keys, so old ones would go unnoticed
+    if let Ok(table) = toml::from_str::<toml::Table>(content) {
+        for (section, old, new) in REPLACED_TOML_FIELDS {
+            if table.get(*section).and_then(|t| t.get(*old)).is_some() {
+                issues.push(ConfigIssue::warning(
+                    file,
+                    Some(format!("{}.{}", section, old)),
+                    format!("Renamed to {}, now in tokens; ignored", new),
+                ));
+            }
+        }
+    }
+    if let Some(message) = check_api_base(&config.embedding.api_base) {
+        issues.push(ConfigIssue::error(
+            file,
+            Some("embedding.api_base".to_string()),
+            message,
+        ));
+    }
+    for (name, profile) in &config.profiles {
+        if let Some(message) = profile.api_base.as_deref().and_then(check_api_base) {
+            issues.push(ConfigIssue::error(
+                file,
+                Some(format!("profiles.{}.api_base", name)),
+                message,
+            ));
+        }
+    }
+    Some(config)
 }
 
 impl SearchConfig {
     /// Load configuration from file and environment
     /// Priority: environment variables > config.json (Node.js) > config.toml (Rust) > defaults
+    ///
+    /// Problems in the config files are logged; see [`Self::load_with_issues`].
     pub fn load() -> SearchResult<Self> {
+        let (config, issues) = Self::load_with_issues();
+        for issue in &issues {
+            log::warn!("[SearchConfig] {}", issue);
+        }
+        Ok(config)
+    }
+
+    /// Load configuration, also returning the problems found in the config files
+    ///
+    /// Unreadable files and invalid values fall back to defaults, and every
+    /// fallback is reported.
+    pub fn load_with_issues() -> (Self, Vec<ConfigIssue>) {
         let mut config = Self::default();
+        let mut issues = Vec::new();
 
         // 1. Try loading from config.toml (Rust format)
         let toml_path = Self::toml_config_path();
         if toml_path.exists() {
-            if let Ok(content) = std::fs::read_to_string(&toml_path) {
-                if let Ok(toml_config) = toml::from_str::<SearchConfig>(&content) {
-                    config = toml_config;
+            match std::fs::read_to_string(&toml_path) {
+                Ok(content) => {
+                    if let Some(toml_config) = check_toml_config(&toml_path, &content, &mut issues)
+                    {
+                        config = toml_config;
+                    }
                 }
+                Err(e) => issues.push(ConfigIssue::error(
+                    &toml_path,
+                    None,
+                    format!("Failed to read file: {}", e),
+                )),
             }
         }
 
         // 2. Try loading from config.json (Node.js format) - this takes precedence
         let json_path = Self::json_config_path();
         if json_path.exists() {
-            if let Ok(content) = std::fs::read_to_string(&json_path) {
-                if let Ok(node_config) = serde_json::from_str::<NodeJsConfig>(&content) {
-                    // Merge Node.js config into our config
-                    // New naming takes precedence over legacy naming
-                    let api_key = node_config.embedding_api_key.or(node_config.openai_api_key);
-                    if let Some(key) = api_key {
-                        if !key.is_empty() {
-                            config.embedding.api_key = Some(key);
+            let node_config = match std::fs::read_to_string(&json_path) {
+                Ok(content) => {
+                    check_json_config(&json_path, &content, &mut issues).and_then(|value| {
+                        match serde_json::from_value::<NodeJsConfig>(value) {
+                            Ok(node_config) => Some(node_config),
+                            Err(e) => {
+                                issues.push(ConfigIssue::error(
+                                    &json_path,
+                                    None,
+                                    format!("Invalid embedding settings, using defaults: {}", e),
+                                ));
+                                None
+                            }
                         }
+                    })
+                }
+                Err(e) => {
+                    issues.push(ConfigIssue::error(
+                        &json_path,
+                        None,
+                        format!("Failed to read file: {}", e),
+                    ));
+                    None
+                }
+            };
+            if let Some(node_config) = node_config {
+                // Merge Node.js config into our config
+                // New naming takes precedence over legacy naming
+                if let Some(provider) = node_config.embedding_provider {
+                    match EmbeddingProvider::parse(&provider) {
+                        Some(provider) => config.embedding.provider = provider,
+                        None if provider.trim().is_empty() => {}
+                        None => issues.push(ConfigIssue::error(
+                            &json_path,
+                            Some("EMBEDDING_PROVIDER".to_string()),
+                            format!(
+                                "Unknown provider '{}', expected 'openai', 'ollama' or 'local'; using '{}'",
+                                provider,
+                                config.embedding.provider.as_str()
+                            ),
+                        )),
                     }
-                    let api_base = node_config
-                        .embedding_api_base
-                        .or(node_config.openai_base_url);
-                    if let Some(base_url) = api_base {
-                        if !base_url.is_empty() {
-                            config.embedding.api_base = base_url;
-                        }
+                }
+                let api_key = node_config.embedding_api_key.or(node_config.openai_api_key);
+                if let Some(key) = api_key {
+                    if !key.is_empty() {
+                        config.embedding.api_key = Some(key);
                     }
-                    if let Some(model) = node_config.embedding_model {
-                        if !model.is_empty() {
-                            config.embedding.model = model;
-                        }
+                }
+                let api_base = node_config
+                    .embedding_api_base
+                    .or(node_config.openai_base_url);
+                if let Some(base_url) = api_base {
+                    if !base_url.is_empty() {
+                        config.embedding.api_base = base_url;
+                    }
+                }
+                if let Some(model) = node_config.embedding_model {
+                    if !model.is_empty() {
+                        config.embedding.model = model;
+                    }
+                }
+                if let Some(batch_size) = node_config.embedding_batch_size {
+                    config.embedding.batch_size = batch_size;
+                }
+                if let Some(max) = node_config.embedding_max_concurrent_requests {
+                    config.embedding.max_concurrent_requests = max;
+                }
+                if let Some(attempts) = node_config.embedding_retry_max_attempts {
+                    config.embedding.retry_max_attempts = attempts;
+                }
+                if let Some(delay_ms) = node_config.embedding_retry_base_delay_ms {
+                    config.embedding.retry_base_delay_ms = delay_ms;
+                }
+                if let Some(include) = node_config.embedding_include_headings {
+                    config.embedding.include_headings = include;
+                }
+                if let Some(proxy_url) = node_config.embedding_proxy_url {
+                    if !proxy_url.is_empty() {
+                        config.embedding.proxy_url = Some(proxy_url);
+                    }
+                }
+                if let Some(headers) = node_config.embedding_extra_headers {
+                    config.embedding.extra_headers.extend(headers);
+                }
+                if let Some(max_tokens) = node_config.chunking_max_tokens {
+                    config.chunking.max_tokens = max_tokens;
+                }
+                if let Some(overlap) = node_config.chunking_overlap_tokens {
+                    config.chunking.overlap_tokens = overlap;
+                }
+                if let Some(strategy) = node_config.chunking_strategy {
+                    match ChunkStrategy::parse(&strategy) {
+                        Some(strategy) => config.chunking.strategy = strategy,
+                        None if strategy.trim().is_empty() => {}
+                        None => issues.push(ConfigIssue::error(
+                            &json_path,
+                            Some("CHUNKING_STRATEGY".to_string()),
+                            format!(
+                                "Unknown strategy '{}', expected 'heading', 'fixed' or 'sentence'; using '{}'",
+                                strategy,
+                                config.chunking.strategy.as_str()
+                            ),
+                        )),
                     }
                 }
+                if let Some(alpha) = node_config.search_hybrid_alpha {
+                    config.search.hybrid_alpha = alpha;
+                }
+                if let Some(profiles) = node_config.embedding_profiles {
+                    config.profiles.extend(profiles);
+                }
+                if let Some(folder_profiles) = node_config.embedding_folder_profiles {
+                    config.folder_profiles.extend(folder_profiles);
+                }
+                if let Some(prices) = node_config.embedding_prices {
+                    config.prices.extend(prices);
+                }
             }
         }
 
@@ -255,10 +1221,129 @@ impl SearchConfig {
         if let Ok(model) = std::env::var("EMBEDDING_MODEL") {
             config.embedding.model = model;
         }
+        if let Some(provider) = std::env::var("EMBEDDING_PROVIDER")
+            .ok()
+            .and_then(|value| EmbeddingProvider::parse(&value))
+        {
+            config.embedding.provider = provider;
+        }
+        config.embedding.apply_provider_defaults();
+
+        for (folder, profile) in &config.folder_profiles {
+            let profile = profile.trim();
+            if !profile.is_empty() && !config.profiles.contains_key(profile) {
+                issues.push(ConfigIssue::warning(
+                    &json_path,
+                    Some(format!("EMBEDDING_FOLDER_PROFILES.{}", folder)),
+                    format!("Folder is assigned to unknown profile '{}'", profile),
+                ));
+            }
+        }
+
+        (config, issues)
+    }
+
+    /// Problems in the config files, without keeping the loaded config
+    pub fn validate() -> Vec<ConfigIssue> {
+        Self::load_with_issues().1
+    }
+
+    /// Resolve the config for a named embedding profile
+    ///
+    /// The profile's overrides are applied on top of the main embedding config,
+    /// and index paths point at the profile's own directory.
+    pub fn with_profile(&self, name: &str) -> SearchResult<SearchConfig> {
+        let name = name.trim();
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(SearchError::Config(format!(
+                "Invalid embedding profile name '{}'",
+                name
+            )));
+        }
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| SearchError::Config(format!("Unknown embedding profile '{}'", name)))?;
 
+        let mut config = self.clone();
+        if let Some(ref key) = profile.api_key {
+            if !key.is_empty() {
+                config.embedding.api_key = Some(key.clone());
+            }
+        }
+        if let Some(ref base) = profile.api_base {
+            if !base.is_empty() {
+                config.embedding.api_base = base.clone();
+            }
+        }
+        if let Some(ref model) = profile.model {
+            if !model.is_empty() {
+                config.embedding.model = model.clone();
+            }
+        }
+        if let Some(dimensions) = profile.dimensions {
+            config.embedding.dimensions = dimensions;
+        }
+
+        let profile_dir = Self::config_dir().join("profiles").join(name);
+        config.paths.lancedb_path = Some(
+            profile
+                .lancedb_path
+                .clone()
+                .unwrap_or_else(|| profile_dir.join("lancedb")),
+        );
+        config.paths.index_metadata_path = Some(profile_dir.join("index-metadata.json"));
+        // A profile config only ever indexes its own docs.
+        config.folder_profiles.clear();
         Ok(config)
     }
 
+    /// Largest chunk to cut, in estimated tokens: the configured size,
+    /// capped at what the embedding model takes in one input
+    pub fn chunk_max_tokens(&self) -> usize {
+        let max_tokens = self.chunking.max_tokens;
+        self.embedding
+            .context_tokens()
+            .map_or(max_tokens, |context| max_tokens.min(context))
+    }
+
+    /// Embedding profile assigned to the folder containing `rel_path`
+    ///
+    /// The most specific (longest) assigned folder wins. Returns `None` for
+    /// unassigned paths, which use the main embedding config.
+    pub fn profile_for_path(&self, rel_path: &str) -> Option<&str> {
+        let path = rel_path.trim_matches('/');
+        self.folder_profiles
+            .iter()
+            .filter_map(|(folder, profile)| {
+                let folder = folder.trim_matches('/');
+                let profile = profile.trim();
+                if folder.is_empty() || profile.is_empty() {
+                    return None;
+                }
+                let matches = path == folder
+                    || path
+                        .strip_prefix(folder)
+                        .is_some_and(|rest| rest.starts_with('/'));
+                matches.then_some((folder.len(), profile))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, profile)| profile)
+    }
+
+    /// Profile names referenced by folder assignments, deduplicated
+    pub fn assigned_profiles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .folder_profiles
+            .values()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
     /// Get base config directory
     fn config_dir() -> PathBuf {
         if let Ok(root) = std::env::var("OPENCONTEXT_ROOT") {
@@ -0,0 +1,166 @@
+//! Outcome of the last indexing attempt for each doc
+//!
+//! Kept in `index-doc-status.json` beside the index metadata, so a doc with
+//! no chunks can be told apart as skipped, failed or never indexed.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+const DOC_STATUS_FILE: &str = "index-doc-status.json";
+
+/// Held from reading a status file to writing it back, so indexers updating
+/// records at once don't drop each other's changes
+static WRITE_LOCK: Mutex<()> = parking_lot::const_mutex(());
+
+/// What happened the last time a doc was indexed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum DocIndexState {
+    /// Stored as this many chunks
+    Indexed { chunks: usize },
+    /// Read fine but had no text to chunk
+    Empty,
+    /// Left out on purpose, by folder settings or because it has no text
+    Skipped { reason: String },
+    /// Reading, embedding or storing it failed
+    Failed { error: String },
+}
+
+impl DocIndexState {
+    pub fn chunks(&self) -> usize {
+        match self {
+            DocIndexState::Indexed { chunks } => *chunks,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocIndexStatus {
+    #[serde(flatten)]
+    pub state: DocIndexState,
+    /// When the attempt was made (ms since epoch)
+    pub at: u64,
+}
+
+/// The status file of one index
+pub(crate) struct DocStatusFile {
+    path: PathBuf,
+}
+
+impl DocStatusFile {
+    /// The file beside `metadata_path`
+    pub(crate) fn beside(metadata_path: PathBuf) -> Self {
+        Self {
+            path: metadata_path.with_file_name(DOC_STATUS_FILE),
+        }
+    }
+
+    /// Every recorded doc. A missing or malformed file reads as empty.
+    pub(crate) fn load(&self) -> BTreeMap<String, DocIndexStatus> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn get(&self, rel_path: &str) -> Option<DocIndexStatus> {
+        self.load().remove(rel_path)
+    }
+
+    /// Record outcomes, keeping the other docs' records
+    pub(crate) fn record(&self, states: Vec<(String, DocIndexState)>) {
+        self.update(|docs| {
+            for (rel_path, state) in states {
+                docs.insert(rel_path, status(state));
+            }
+        })
+    }
+
+    /// Replace every record, as after a full build
+    pub(crate) fn replace(&self, states: Vec<(String, DocIndexState)>) {
+        let _guard = WRITE_LOCK.lock();
+        self.write(
+            &states
+                .into_iter()
+                .map(|(rel_path, state)| (rel_path, status(state)))
+                .collect(),
+        )
+    }
+
+    /// Move a renamed doc's record to its new path
+    pub(crate) fn rename(&self, old_path: &str, new_path: &str) {
+        self.update(|docs| {
+            if let Some(record) = docs.remove(old_path) {
+                docs.insert(new_path.to_string(), record);
+            }
+        })
+    }
+
+    /// Move the records of every doc under folder `old_prefix` to
+    /// `new_prefix`
+    pub(crate) fn rename_prefix(&self, old_prefix: &str, new_prefix: &str) {
+        let old_prefix = format!("{}/", old_prefix);
+        self.update(|docs| {
+            let moved: Vec<String> = docs
+                .keys()
+                .filter(|rel_path| rel_path.starts_with(&old_prefix))
+                .cloned()
+                .collect();
+            for old_path in moved {
+                if let Some(record) = docs.remove(&old_path) {
+                    let rest = &old_path[old_prefix.len()..];
+                    docs.insert(format!("{}/{}", new_prefix, rest), record);
+                }
+            }
+        })
+    }
+
+    pub(crate) fn remove(&self, rel_path: &str) {
+        self.update(|docs| {
+            docs.remove(rel_path);
+        })
+    }
+
+    /// Read, change and write back the records. The file is shared by every
+    /// indexer on the same index, so nothing is cached between calls.
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<String, DocIndexStatus>)) {
+        let _guard = WRITE_LOCK.lock();
+        let mut docs = self.load();
+        change(&mut docs);
+        self.write(&docs)
+    }
+
+    /// Written through a temp file, so a reader never sees half a file.
+    /// Losing the records only loses diagnostics, so a failed write is
+    /// logged rather than failing the indexing that caused it.
+    fn write(&self, docs: &BTreeMap<String, DocIndexStatus>) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let content = serde_json::to_string(docs).map_err(std::io::Error::other)?;
+                let tmp = self.path.with_extension("json.tmp");
+                std::fs::write(&tmp, content)?;
+                std::fs::rename(&tmp, &self.path)
+            });
+        if let Err(e) = result {
+            log::warn!("[Indexer] Failed to write {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn status(state: DocIndexState) -> DocIndexStatus {
+    DocIndexStatus {
+        state,
+        at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+    }
+}
@@ -1,18 +1,34 @@
-//! OpenAI Embedding API client
+//! Embedding client for OpenAI-compatible servers, Ollama and local models
 
-use reqwest::Client;
+use futures::stream::{self, StreamExt};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Proxy};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "local-embeddings")]
+use std::sync::Arc;
 
-use super::config::EmbeddingConfig;
+use super::config::{EmbeddingConfig, EmbeddingProvider, PathsConfig};
 use super::error::{SearchError, SearchResult};
+#[cfg(feature = "local-embeddings")]
+use super::local_embedding::LocalEmbedder;
+use super::usage::UsageLedger;
 
-/// OpenAI Embedding API client
+/// Embedding API client
 pub struct EmbeddingClient {
     config: EmbeddingConfig,
     client: Client,
     /// Actual dimensions detected from API response (0 = not yet detected)
     actual_dimensions: AtomicUsize,
+    /// Where the tokens each request used are recorded
+    usage_ledger: Option<UsageLedger>,
+    /// Where the local provider downloads its model
+    #[cfg_attr(not(feature = "local-embeddings"), allow(dead_code))]
+    models_path: PathBuf,
+    /// The local provider's model, loaded on first use
+    #[cfg(feature = "local-embeddings")]
+    local: tokio::sync::OnceCell<Arc<LocalEmbedder>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -27,7 +43,6 @@ struct EmbeddingRequest {
 #[derive(Debug, Deserialize)]
 struct EmbeddingResponse {
     data: Vec<EmbeddingData>,
-    #[allow(dead_code)]
     usage: Option<Usage>,
 }
 
@@ -42,8 +57,21 @@ struct EmbeddingData {
 struct Usage {
     #[allow(dead_code)]
     prompt_tokens: usize,
-    #[allow(dead_code)]
-    total_tokens: usize,
+    total_tokens: u64,
+}
+
+/// Request to Ollama's batch endpoint, `/api/embed`
+#[derive(Debug, Serialize)]
+struct OllamaEmbedRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+    /// Input tokens, when the server reports them
+    prompt_eval_count: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,6 +79,12 @@ struct ErrorResponse {
     error: ErrorDetail,
 }
 
+/// Ollama reports errors as a bare string
+#[derive(Debug, Deserialize)]
+struct OllamaErrorResponse {
+    error: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct ErrorDetail {
     message: String,
@@ -61,19 +95,47 @@ impl EmbeddingClient {
     pub fn new(config: EmbeddingConfig) -> SearchResult<Self> {
         // Validate API key is available
         config.get_api_key()?;
+        if !config.provider.is_available() {
+            return Err(SearchError::Config(
+                "The local embedding provider needs a build with the `local-embeddings` feature"
+                    .to_string(),
+            ));
+        }
 
-        let client = Client::builder()
+        let mut builder = Client::builder()
             .timeout(std::time::Duration::from_secs(60))
-            .build()
-            .map_err(SearchError::Http)?;
+            .default_headers(extra_headers(&config)?);
+        if let Some(proxy_url) = config.proxy_url.as_deref().filter(|url| !url.is_empty()) {
+            let proxy = Proxy::all(proxy_url).map_err(|e| {
+                SearchError::Config(format!("Invalid embedding proxy '{}': {}", proxy_url, e))
+            })?;
+            builder = builder.proxy(proxy);
+        }
+        let client = builder.build().map_err(SearchError::Http)?;
 
         Ok(Self {
             config,
             client,
             actual_dimensions: AtomicUsize::new(0),
+            usage_ledger: None,
+            models_path: PathsConfig::default().get_models_path(),
+            #[cfg(feature = "local-embeddings")]
+            local: tokio::sync::OnceCell::new(),
         })
     }
 
+    /// Record the tokens of every request in `ledger`
+    pub fn with_usage_ledger(mut self, ledger: UsageLedger) -> Self {
+        self.usage_ledger = Some(ledger);
+        self
+    }
+
+    /// Keep the local provider's model in `path`
+    pub fn with_models_path(mut self, path: PathBuf) -> Self {
+        self.models_path = path;
+        self
+    }
+
     /// Get embedding dimensions (returns actual detected dimensions if available)
     pub fn dimensions(&self) -> usize {
         let actual = self.actual_dimensions.load(Ordering::Relaxed);
@@ -91,22 +153,50 @@ impl EmbeddingClient {
 
     /// Generate embeddings for multiple texts
     pub async fn embed(&self, texts: Vec<String>) -> SearchResult<Vec<Vec<f32>>> {
+        self.embed_with_progress(texts, |_| {}).await
+    }
+
+    /// Generate embeddings for multiple texts, calling `on_batch` with the
+    /// number of texts of each batch as it completes
+    ///
+    /// Texts are sent `batch_size` at a time with up to
+    /// `max_concurrent_requests` requests in flight. The embeddings come back
+    /// in the order of `texts`; any failed batch fails the whole call.
+    pub async fn embed_with_progress(
+        &self,
+        texts: Vec<String>,
+        mut on_batch: impl FnMut(usize),
+    ) -> SearchResult<Vec<Vec<f32>>> {
         if texts.is_empty() {
             return Ok(vec![]);
         }
 
         let api_key = self.config.get_api_key()?;
-        let url = format!("{}/embeddings", self.config.api_base);
+        let url = match self.config.provider {
+            EmbeddingProvider::OpenAI => format!("{}/embeddings", self.config.api_base),
+            EmbeddingProvider::Ollama => ollama_embed_url(&self.config.api_base),
+            EmbeddingProvider::Local => String::new(),
+        };
 
-        // Process in batches
-        let mut all_embeddings = Vec::with_capacity(texts.len());
+        let batches: Vec<Vec<String>> = texts
+            .chunks(self.config.batch_size.max(1))
+            .map(<[String]>::to_vec)
+            .collect();
+        let mut results: Vec<Option<Vec<Vec<f32>>>> = vec![None; batches.len()];
 
-        for batch in texts.chunks(self.config.batch_size) {
-            let batch_embeddings = self.embed_batch(batch.to_vec(), &api_key, &url).await?;
-            all_embeddings.extend(batch_embeddings);
+        let mut in_flight = stream::iter(batches.into_iter().enumerate())
+            .map(|(index, batch)| {
+                let (api_key, url) = (&api_key, &url);
+                async move { (index, self.embed_batch(batch, api_key, url).await) }
+            })
+            .buffer_unordered(self.config.max_concurrent_requests.max(1));
+        while let Some((index, result)) = in_flight.next().await {
+            let embeddings = result?;
+            on_batch(embeddings.len());
+            results[index] = Some(embeddings);
         }
 
-        Ok(all_embeddings)
+        Ok(results.into_iter().flatten().flatten().collect())
     }
 
     /// Generate embedding for a single text
@@ -142,6 +232,53 @@ impl EmbeddingClient {
             })
             .collect();
 
+        let (embeddings, total_tokens) = match self.config.provider {
+            EmbeddingProvider::OpenAI => self.request_openai(texts, api_key, url).await?,
+            EmbeddingProvider::Ollama => self.request_ollama(texts, api_key, url).await?,
+            EmbeddingProvider::Local => self.request_local(texts).await?,
+        };
+
+        // Verify we got embeddings for all inputs
+        if embeddings.len() != input_count {
+            return Err(SearchError::Embedding(format!(
+                "Embedding count mismatch: sent {} texts, got {} embeddings",
+                input_count,
+                embeddings.len()
+            )));
+        }
+
+        // Not every API reports usage; those requests go unrecorded
+        if let (Some(ledger), Some(total_tokens)) = (&self.usage_ledger, total_tokens) {
+            ledger.record(&self.config.model, total_tokens);
+        }
+
+        // Auto-detect actual dimensions from first embedding
+        if let Some(first) = embeddings.first() {
+            let detected_dim = first.len();
+            let current = self.actual_dimensions.load(Ordering::Relaxed);
+            if current == 0 {
+                self.actual_dimensions
+                    .store(detected_dim, Ordering::Relaxed);
+                log::info!("Auto-detected embedding dimensions: {}", detected_dim);
+            } else if current != detected_dim {
+                log::warn!(
+                    "Embedding dimension mismatch: expected {}, got {}",
+                    current,
+                    detected_dim
+                );
+            }
+        }
+
+        Ok(embeddings)
+    }
+
+    /// Embeddings in input order, and the tokens used if reported
+    async fn request_openai(
+        &self,
+        texts: Vec<String>,
+        api_key: &str,
+        url: &str,
+    ) -> SearchResult<(Vec<Vec<f32>>, Option<u64>)> {
         // Only send dimensions for OpenAI text-embedding-3 models
         // Other APIs (like DashScope) may not support this parameter
         let dimensions = if self.config.model.starts_with("text-embedding-3") {
@@ -155,14 +292,97 @@ impl EmbeddingClient {
             input: texts,
             dimensions,
         };
+        let body = self.post(url, api_key, &request).await?;
+        let response: EmbeddingResponse = serde_json::from_str(&body).map_err(SearchError::Json)?;
 
+        // Sort by index to ensure correct order
+        let mut data = response.data;
+        data.sort_by_key(|d| d.index);
+        Ok((
+            data.into_iter().map(|d| d.embedding).collect(),
+            response.usage.map(|usage| usage.total_tokens),
+        ))
+    }
+
+    /// Embeddings in input order, and the tokens used if reported
+    async fn request_ollama(
+        &self,
+        texts: Vec<String>,
+        api_key: &str,
+        url: &str,
+    ) -> SearchResult<(Vec<Vec<f32>>, Option<u64>)> {
+        let request = OllamaEmbedRequest {
+            model: self.config.model.clone(),
+            input: texts,
+        };
+        let body = self.post(url, api_key, &request).await?;
+        let response: OllamaEmbedResponse =
+            serde_json::from_str(&body).map_err(SearchError::Json)?;
+        Ok((response.embeddings, response.prompt_eval_count))
+    }
+
+    /// Embeddings from the in-process model, loaded on first use. Local
+    /// models report no token usage.
+    #[cfg(feature = "local-embeddings")]
+    async fn request_local(
+        &self,
+        texts: Vec<String>,
+    ) -> SearchResult<(Vec<Vec<f32>>, Option<u64>)> {
+        let embedder = self
+            .local
+            .get_or_try_init(|| {
+                let model = self.config.model.clone();
+                let cache_dir = self.models_path.clone();
+                async move {
+                    tokio::task::spawn_blocking(move || LocalEmbedder::load(&model, cache_dir))
+                        .await
+                        .map_err(|e| SearchError::Embedding(e.to_string()))?
+                        .map(Arc::new)
+                }
+            })
+            .await?
+            .clone();
+        let embeddings = tokio::task::spawn_blocking(move || embedder.embed(texts))
+            .await
+            .map_err(|e| SearchError::Embedding(e.to_string()))??;
+        Ok((embeddings, None))
+    }
+
+    /// Never called: `new` refuses the local provider in this build
+    #[cfg(not(feature = "local-embeddings"))]
+    async fn request_local(
+        &self,
+        _texts: Vec<String>,
+    ) -> SearchResult<(Vec<Vec<f32>>, Option<u64>)> {
+        Err(SearchError::Config(
+            "Local embeddings are not available in this build".to_string(),
+        ))
+    }
+
+    /// POST `request` as JSON and return the body of a successful response,
+    /// retrying rate limits and server errors per the config's retry policy.
+    /// No `Authorization` header is sent without a key (local Ollama).
+    async fn post<T: Serialize>(
+        &self,
+        url: &str,
+        api_key: &str,
+        request: &T,
+    ) -> SearchResult<String> {
         let response = self
-            .client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
+            .config
+            .retry_policy()
+            .send(|| {
+                let builder = self
+                    .client
+                    .post(url)
+                    .header("Content-Type", "application/json")
+                    .json(request);
+                if api_key.is_empty() {
+                    builder
+                } else {
+                    builder.header("Authorization", format!("Bearer {}", api_key))
+                }
+            })
             .await
             .map_err(SearchError::Http)?;
 
@@ -174,46 +394,38 @@ impl EmbeddingClient {
             if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&body) {
                 return Err(SearchError::Embedding(error_response.error.message));
             }
+            if let Ok(error_response) = serde_json::from_str::<OllamaErrorResponse>(&body) {
+                return Err(SearchError::Embedding(error_response.error));
+            }
             return Err(SearchError::Embedding(format!(
                 "API error ({}): {}",
                 status, body
             )));
         }
+        Ok(body)
+    }
+}
 
-        let response: EmbeddingResponse = serde_json::from_str(&body).map_err(SearchError::Json)?;
-
-        // Verify we got embeddings for all inputs
-        if response.data.len() != input_count {
-            return Err(SearchError::Embedding(format!(
-                "Embedding count mismatch: sent {} texts, got {} embeddings",
-                input_count,
-                response.data.len()
-            )));
-        }
-
-        // Sort by index to ensure correct order
-        let mut data = response.data;
-        data.sort_by_key(|d| d.index);
-
-        // Auto-detect actual dimensions from first embedding
-        if let Some(first) = data.first() {
-            let detected_dim = first.embedding.len();
-            let current = self.actual_dimensions.load(Ordering::Relaxed);
-            if current == 0 {
-                self.actual_dimensions
-                    .store(detected_dim, Ordering::Relaxed);
-                log::info!("Auto-detected embedding dimensions: {}", detected_dim);
-            } else if current != detected_dim {
-                log::warn!(
-                    "Embedding dimension mismatch: expected {}, got {}",
-                    current,
-                    detected_dim
-                );
-            }
-        }
-
-        Ok(data.into_iter().map(|d| d.embedding).collect())
+/// The config's `extra_headers`, sent with every request; requests add
+/// their own `Content-Type` and `Authorization` over them
+fn extra_headers(config: &EmbeddingConfig) -> SearchResult<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.extra_headers {
+        let invalid = || SearchError::Config(format!("Invalid embedding header '{}'", name));
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?,
+            HeaderValue::from_str(value).map_err(|_| invalid())?,
+        );
     }
+    Ok(headers)
+}
+
+/// Ollama's batch embedding endpoint under `api_base`, which may be given
+/// with or without the `/api` suffix
+fn ollama_embed_url(api_base: &str) -> String {
+    let base = api_base.trim_end_matches('/');
+    let base = base.strip_suffix("/api").unwrap_or(base);
+    format!("{}/api/embed", base)
 }
 
 #[cfg(test)]
@@ -226,4 +438,64 @@ mod tests {
         assert_eq!(config.model, "text-embedding-3-small");
         assert_eq!(config.dimensions, 1536);
     }
+
+    #[test]
+    fn test_ollama_embed_url() {
+        assert_eq!(
+            ollama_embed_url("http://localhost:11434"),
+            "http://localhost:11434/api/embed"
+        );
+        assert_eq!(
+            ollama_embed_url("http://localhost:11434/api/"),
+            "http://localhost:11434/api/embed"
+        );
+    }
+
+    #[test]
+    fn test_local_provider_needs_the_feature() {
+        let config = EmbeddingConfig {
+            provider: EmbeddingProvider::Local,
+            ..EmbeddingConfig::default()
+        };
+        assert_eq!(
+            EmbeddingClient::new(config).is_ok(),
+            cfg!(feature = "local-embeddings")
+        );
+    }
+
+    #[test]
+    fn test_proxy_and_headers_are_checked_up_front() {
+        let config = |proxy_url: &str, header: (&str, &str)| EmbeddingConfig {
+            api_key: Some("test".to_string()),
+            proxy_url: Some(proxy_url.to_string()),
+            extra_headers: [(header.0.to_string(), header.1.to_string())].into(),
+            ..EmbeddingConfig::default()
+        };
+        assert!(
+            EmbeddingClient::new(config("http://proxy.corp:8080", ("X-Gateway-Key", "k"))).is_ok()
+        );
+        assert!(EmbeddingClient::new(config("", ("X-Gateway-Key", "k"))).is_ok());
+        assert!(matches!(
+            EmbeddingClient::new(config("http://proxy.corp:8080", ("Bad Header", "k"))),
+            Err(SearchError::Config(_))
+        ));
+        assert!(matches!(
+            EmbeddingClient::new(config("http://proxy.corp:8080", ("X-Gateway-Key", "a\nb"))),
+            Err(SearchError::Config(_))
+        ));
+        assert!(matches!(
+            EmbeddingClient::new(config("not a proxy", ("X-Gateway-Key", "k"))),
+            Err(SearchError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_ollama_needs_no_api_key() {
+        let config = EmbeddingConfig {
+            provider: EmbeddingProvider::Ollama,
+            ..EmbeddingConfig::default()
+        };
+        assert_eq!(config.get_api_key().unwrap(), "");
+        assert!(EmbeddingClient::new(config).is_ok());
+    }
 }
@@ -0,0 +1,195 @@
+//! Vectors of chunks embedded before, by content hash
+//!
+//! Kept in `embedding-cache.db` beside the LanceDB directory, so a rebuild,
+//! a chunk that moved to another doc, or a doc changed back to an earlier
+//! version doesn't pay for the same embedding twice. The cache remembers the
+//! provider, model and dimensions its vectors came from and empties itself
+//! when any of them changes.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use super::config::SearchConfig;
+use super::error::{SearchError, SearchResult};
+
+const CACHE_FILE: &str = "embedding-cache.db";
+
+/// `meta` key for the embedding settings the cached vectors came from
+const SETTINGS_KEY: &str = "settings";
+
+/// Vectors kept; the least recently used go first past this
+pub(crate) const MAX_ENTRIES: usize = 200_000;
+
+/// Cache key of a chunk: SHA-256 of its text with line endings and
+/// surrounding whitespace normalized
+pub(crate) fn chunk_key(text: &str) -> String {
+    let normalized = text.replace("\r\n", "\n");
+    format!("{:x}", Sha256::digest(normalized.trim().as_bytes()))
+}
+
+fn cache_err(e: rusqlite::Error) -> SearchError {
+    SearchError::Index(format!("Embedding cache: {}", e))
+}
+
+fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// The embedding cache of one index
+pub(crate) struct EmbeddingCache {
+    conn: Mutex<Connection>,
+}
+
+impl EmbeddingCache {
+    /// The cache beside the LanceDB directory of `config`, for vectors of
+    /// its embedding provider, model and dimensions
+    pub(crate) fn for_config(config: &SearchConfig) -> SearchResult<Self> {
+        let embedding = &config.embedding;
+        let settings = format!(
+            "{}:{}:{}",
+            embedding.provider.as_str(),
+            embedding.model,
+            embedding.dimensions
+        );
+        let path = config.paths.get_lancedb_path().with_file_name(CACHE_FILE);
+        Self::open(&path, &settings)
+    }
+
+    /// Open the cache at `path` for vectors made with `settings`, emptying
+    /// it if they were made with anything else
+    pub(crate) fn open(path: &Path, settings: &str) -> SearchResult<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path).map_err(cache_err)?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS embeddings (
+                hash TEXT PRIMARY KEY,
+                vector BLOB NOT NULL,
+                used_at INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_embeddings_used_at ON embeddings(used_at);
+        ",
+        )
+        .map_err(cache_err)?;
+
+        let stored: Option<String> = conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                params![SETTINGS_KEY],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(cache_err)?;
+        if stored.as_deref() != Some(settings) {
+            if stored.is_some() {
+                log::info!("[EmbeddingCache] Embedding settings changed, clearing the cache");
+            }
+            conn.execute("DELETE FROM embeddings", [])
+                .map_err(cache_err)?;
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+                params![SETTINGS_KEY, settings],
+            )
+            .map_err(cache_err)?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Cached vectors of the chunks keyed `keys`, by key. Marks them used.
+    pub(crate) fn get_many(&self, keys: &[String]) -> SearchResult<HashMap<String, Vec<f32>>> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(cache_err)?;
+        let mut found = HashMap::new();
+        {
+            let mut select = tx
+                .prepare_cached("SELECT vector FROM embeddings WHERE hash = ?1")
+                .map_err(cache_err)?;
+            let mut touch = tx
+                .prepare_cached("UPDATE embeddings SET used_at = ?1 WHERE hash = ?2")
+                .map_err(cache_err)?;
+            let now = now_ms();
+            for key in keys {
+                if found.contains_key(key) {
+                    continue;
+                }
+                let blob: Option<Vec<u8>> = select
+                    .query_row(params![key], |row| row.get(0))
+                    .optional()
+                    .map_err(cache_err)?;
+                if let Some(blob) = blob {
+                    touch.execute(params![now, key]).map_err(cache_err)?;
+                    found.insert(key.clone(), decode_vector(&blob));
+                }
+            }
+        }
+        tx.commit().map_err(cache_err)?;
+        Ok(found)
+    }
+
+    /// Remember `vector` for each chunk key
+    pub(crate) fn put_many<'a>(
+        &self,
+        entries: impl IntoIterator<Item = (&'a str, &'a [f32])>,
+    ) -> SearchResult<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(cache_err)?;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO embeddings (hash, vector, used_at) VALUES (?1, ?2, ?3)",
+                )
+                .map_err(cache_err)?;
+            let now = now_ms();
+            for (key, vector) in entries {
+                insert
+                    .execute(params![key, encode_vector(vector), now])
+                    .map_err(cache_err)?;
+            }
+        }
+        tx.commit().map_err(cache_err)
+    }
+
+    /// Drop the least recently used vectors beyond `keep`; returns how many
+    /// were dropped
+    pub(crate) fn prune(&self, keep: usize) -> SearchResult<usize> {
+        self.conn
+            .lock()
+            .execute(
+                "DELETE FROM embeddings WHERE hash IN (
+                    SELECT hash FROM embeddings ORDER BY used_at DESC LIMIT -1 OFFSET ?1
+                )",
+                params![keep as i64],
+            )
+            .map_err(cache_err)
+    }
+}
+
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn decode_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
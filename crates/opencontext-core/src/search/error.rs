@@ -1,5 +1,6 @@
 //! Search error types
 
+use std::path::PathBuf;
 use thiserror::Error;
 
 /// Search-specific error type
@@ -32,6 +33,24 @@ pub enum SearchError {
     #[error("LanceDB error: {0}")]
     Lance(#[from] lancedb::Error),
 
+    /// The index files can't be opened, e.g. after a crash mid-write
+    #[error("Search index at {} is corrupted ({reason}). Reset it and rebuild the index.", path.display())]
+    CorruptIndex { path: PathBuf, reason: String },
+
+    /// The embedding model changed since the index was built
+    #[error("The search index holds {index}-dimension vectors but the embedding model returns {embedding}. Rebuild the index.")]
+    DimensionMismatch { index: usize, embedding: usize },
+
+    /// The index was built for another embedding model than the one
+    /// configured, so its vectors can't be compared with the query's
+    #[error("The search index was built with {index_model} ({index_dimensions} dimensions) but search is set up for {model} ({dimensions} dimensions). Clear it (clean_search_index) and rebuild the index.")]
+    IndexModelMismatch {
+        index_model: String,
+        index_dimensions: usize,
+        model: String,
+        dimensions: usize,
+    },
+
     #[error("Index not built. Run 'oc index build' first.")]
     IndexNotBuilt,
 
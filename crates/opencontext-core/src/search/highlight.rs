@@ -0,0 +1,221 @@
+//! Where a search hit matches its query
+//!
+//! Highlights are byte ranges of query terms in a hit's content, found by the
+//! rules keyword search tokenizes with: ASCII words of two or more characters,
+//! ignoring case, and runs of Chinese characters by their 2-grams (a run of
+//! one character by that character). In hybrid search these are the matches
+//! that gave the keyword side its score. A vector hit gets them wherever the
+//! query's words happen to appear, and none if they don't.
+//!
+//! A snippet is a short window of the hit centered on the sentence with the
+//! most highlights, the highlights wrapped in `<mark>` and `</mark>` for the
+//! frontend to style.
+
+use std::collections::HashSet;
+
+use super::searcher::Searcher;
+
+/// Characters in a snippet, not counting the ellipses
+const SNIPPET_CHARS: usize = 200;
+/// Characters kept before the first highlight of a sentence longer than a
+/// snippet
+const SNIPPET_LEAD_CHARS: usize = 40;
+/// Opens a query term in a snippet
+pub const SNIPPET_MARK_START: &str = "<mark>";
+/// Closes a query term in a snippet
+pub const SNIPPET_MARK_END: &str = "</mark>";
+
+/// The terms of a query, as highlighting matches them
+#[derive(Default)]
+struct QueryTerms {
+    words: HashSet<String>,
+    bigrams: HashSet<(char, char)>,
+    chars: HashSet<char>,
+}
+
+impl QueryTerms {
+    fn new(query: &str) -> Self {
+        let mut terms = Self::default();
+        for_each_run(query, |run| match run {
+            Run::Word(_, word) => {
+                if word.len() >= 2 {
+                    terms.words.insert(word.to_ascii_lowercase());
+                }
+            }
+            Run::Chinese(chars) => {
+                if let [(_, c)] = chars {
+                    terms.chars.insert(*c);
+                }
+                for pair in chars.windows(2) {
+                    terms.bigrams.insert((pair[0].1, pair[1].1));
+                }
+            }
+        });
+        terms
+    }
+}
+
+/// A token run: an ASCII word with its byte offset, or Chinese characters
+/// with theirs
+enum Run<'a> {
+    Word(usize, &'a str),
+    Chinese(&'a [(usize, char)]),
+}
+
+/// Call `f` with each run of `text`, in order
+fn for_each_run(text: &str, mut f: impl FnMut(Run<'_>)) {
+    let mut word_start = None;
+    let mut chinese: Vec<(usize, char)> = Vec::new();
+    for (i, c) in text.char_indices() {
+        if c.is_ascii_alphanumeric() {
+            if !chinese.is_empty() {
+                f(Run::Chinese(&chinese));
+                chinese.clear();
+            }
+            word_start.get_or_insert(i);
+        } else {
+            if let Some(start) = word_start.take() {
+                f(Run::Word(start, &text[start..i]));
+            }
+            if Searcher::is_chinese_char(c) {
+                chinese.push((i, c));
+            } else if !chinese.is_empty() {
+                f(Run::Chinese(&chinese));
+                chinese.clear();
+            }
+        }
+    }
+    if let Some(start) = word_start {
+        f(Run::Word(start, &text[start..]));
+    }
+    if !chinese.is_empty() {
+        f(Run::Chinese(&chinese));
+    }
+}
+
+/// Byte ranges of `query`'s terms in `content`, in order and merged where
+/// they overlap or touch. Every range starts and ends on a char boundary.
+pub(crate) fn highlights(content: &str, query: &str) -> Vec<(usize, usize)> {
+    let terms = QueryTerms::new(query);
+    let mut spans = Vec::new();
+    for_each_run(content, |run| match run {
+        Run::Word(start, word) => {
+            if terms.words.contains(&word.to_ascii_lowercase()) {
+                spans.push((start, start + word.len()));
+            }
+        }
+        Run::Chinese(chars) => {
+            for (k, &(start, c)) in chars.iter().enumerate() {
+                if terms.chars.contains(&c) {
+                    spans.push((start, start + c.len_utf8()));
+                }
+                if let Some(&(next_start, next)) = chars.get(k + 1) {
+                    if terms.bigrams.contains(&(c, next)) {
+                        spans.push((start, next_start + next.len_utf8()));
+                    }
+                }
+            }
+        }
+    });
+
+    spans.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Up to `SNIPPET_CHARS` characters of `content` around its sentence with
+/// the most `highlights`, with each highlight wrapped in `SNIPPET_MARK_START`
+/// and `SNIPPET_MARK_END` and an ellipsis wherever it was cut. Starts at the
+/// beginning when there are no highlights.
+pub(crate) fn snippet(content: &str, highlights: &[(usize, usize)]) -> String {
+    let boundaries: Vec<usize> = content
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(content.len()))
+        .collect();
+    let total_chars = boundaries.len() - 1;
+    let char_at = |byte: usize| boundaries.partition_point(|&b| b < byte);
+
+    let (start, end) = if total_chars <= SNIPPET_CHARS {
+        (0, total_chars)
+    } else {
+        let start = best_sentence(content, highlights)
+            .map_or(0, |(sentence, first_highlight)| {
+                let (sentence_start, sentence_end) = (char_at(sentence.0), char_at(sentence.1));
+                if sentence_end - sentence_start <= SNIPPET_CHARS {
+                    (sentence_start + sentence_end).saturating_sub(SNIPPET_CHARS) / 2
+                } else {
+                    char_at(first_highlight).saturating_sub(SNIPPET_LEAD_CHARS)
+                }
+            })
+            .min(total_chars - SNIPPET_CHARS);
+        (start, start + SNIPPET_CHARS)
+    };
+
+    let window = &content[boundaries[start]..boundaries[end]];
+    let text_start = boundaries[start] + (window.len() - window.trim_start().len());
+    let text_end = boundaries[start] + window.trim_end().len();
+    let mut text = String::new();
+    let mut pos = text_start;
+    for &(mark_start, mark_end) in highlights {
+        let (mark_start, mark_end) = (mark_start.max(pos), mark_end.min(text_end));
+        if mark_start >= mark_end {
+            continue;
+        }
+        text.push_str(&content[pos..mark_start]);
+        text.push_str(SNIPPET_MARK_START);
+        text.push_str(&content[mark_start..mark_end]);
+        text.push_str(SNIPPET_MARK_END);
+        pos = mark_end;
+    }
+    text.push_str(&content[pos..text_end.max(pos)]);
+
+    format!(
+        "{}{}{}",
+        if start > 0 { "…" } else { "" },
+        text,
+        if end < total_chars { "…" } else { "" }
+    )
+}
+
+/// Byte range of the sentence of `content` holding the most `highlights`,
+/// with where its first highlight starts. Earlier sentences win ties; `None`
+/// when there are no highlights.
+fn best_sentence(content: &str, highlights: &[(usize, usize)]) -> Option<((usize, usize), usize)> {
+    let mut sentences = Vec::new();
+    let mut sentence_start = 0;
+    let mut chars = content.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let ends = match c {
+            '。' | '！' | '？' | '\n' => true,
+            '.' | '!' | '?' => next.map_or(true, char::is_whitespace),
+            _ => false,
+        };
+        if ends {
+            sentences.push((sentence_start, i + c.len_utf8()));
+            sentence_start = i + c.len_utf8();
+        }
+    }
+    sentences.push((sentence_start, content.len()));
+
+    sentences
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let held: Vec<usize> = highlights
+                .iter()
+                .map(|&(mark_start, _)| mark_start)
+                .filter(|mark_start| (start..end).contains(mark_start))
+                .collect();
+            let first = *held.first()?;
+            Some((held.len(), std::cmp::Reverse(start), (start, end), first))
+        })
+        .max_by_key(|&(held, earlier, ..)| (held, earlier))
+        .map(|(_, _, sentence, first)| (sentence, first))
+}
@@ -2,19 +2,24 @@
 //!
 //! Listens to document events and batches index updates.
 //! Uses interval-based checking (default: 5 minutes) instead of real-time updates.
+//! Docs whose folder settings give them high index priority are re-indexed
+//! as soon as they change, and so are docs the app asks to have indexed
+//! right away, such as one the user just saved. Deleted docs leave the
+//! index right away, so they never turn up in search results.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, Mutex, MutexGuard};
 use tokio::time::{interval_at, Instant};
 
 use super::config::SearchConfig;
 use super::error::SearchResult;
 use super::indexer::Indexer;
 use crate::events::{DocEvent, Event, FolderEvent, SharedEventBus};
+use crate::{IndexPriority, SettingsResolver};
 
 /// Update action for the index
 #[derive(Debug, Clone)]
@@ -27,18 +32,33 @@ enum IndexAction {
     Rename { old_path: String, new_path: String },
 }
 
+/// Aborts a spawned task when dropped
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
 /// Index synchronization service
 ///
 /// Collects file change events and processes them in batches at regular intervals.
 pub struct IndexSyncService {
-    config: SearchConfig,
+    config: std::sync::RwLock<SearchConfig>,
     contexts_root: PathBuf,
     indexer: Arc<Mutex<Option<Indexer>>>,
     enabled: Arc<std::sync::atomic::AtomicBool>,
+    /// While paused, events are still collected but not processed
+    paused: Arc<std::sync::atomic::AtomicBool>,
     /// Pending actions waiting to be processed
     pending_actions: Arc<Mutex<HashMap<String, IndexAction>>>,
+    /// Files to index as soon as their update comes in, see `prioritize`
+    prioritized: Mutex<HashSet<String>>,
     /// Interval in seconds for checking pending updates (default: 300 = 5 minutes)
     check_interval_secs: u64,
+    /// Paths of docs re-indexed, see `subscribe_indexed`
+    indexed: broadcast::Sender<String>,
 }
 
 impl IndexSyncService {
@@ -46,12 +66,15 @@ impl IndexSyncService {
     /// Default check interval is 5 minutes (300 seconds)
     pub fn new(config: SearchConfig, contexts_root: PathBuf) -> Self {
         Self {
-            config,
+            config: std::sync::RwLock::new(config),
             contexts_root,
             indexer: Arc::new(Mutex::new(None)),
             enabled: Arc::new(std::sync::atomic::AtomicBool::new(true)),
+            paused: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             pending_actions: Arc::new(Mutex::new(HashMap::new())),
+            prioritized: Mutex::new(HashSet::new()),
             check_interval_secs: 300, // 5 minutes
+            indexed: broadcast::channel(64).0,
         }
     }
 
@@ -72,6 +95,50 @@ impl IndexSyncService {
         self.enabled.load(std::sync::atomic::Ordering::SeqCst)
     }
 
+    /// Pause or resume processing of pending updates
+    ///
+    /// Unlike disabling, pausing keeps collecting events so nothing is lost;
+    /// the backlog is processed on the first interval after resuming.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused
+            .store(paused, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Check if processing is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Current search config
+    fn config(&self) -> SearchConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Replace the search config, e.g. after the config file changed
+    ///
+    /// The indexer is rebuilt with the new settings; pending updates are kept
+    /// and processed with it on the next interval.
+    pub async fn set_config(&self, config: SearchConfig) -> SearchResult<()> {
+        *self
+            .config
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = config.clone();
+
+        let mut indexer_guard = self.indexer.lock().await;
+        *indexer_guard = None;
+        *indexer_guard = Some(Indexer::new(config, self.contexts_root.clone()).await?);
+        Ok(())
+    }
+
+    /// Receive the path of each doc the service re-indexes, e.g. to
+    /// refresh what is shown about its index status
+    pub fn subscribe_indexed(&self) -> broadcast::Receiver<String> {
+        self.indexed.subscribe()
+    }
+
     /// Get count of pending updates
     pub async fn pending_count(&self) -> usize {
         self.pending_actions.lock().await.len()
@@ -79,28 +146,34 @@ impl IndexSyncService {
 
     /// Start the sync service, listening to events from the event bus
     ///
-    /// Events are collected and processed in batches at regular intervals (default: 5 minutes)
+    /// Events are collected and processed in batches at regular intervals (default: 5 minutes).
+    /// Runs until the event bus closes; dropping the future stops the service,
+    /// interval processing included. Pending updates are kept for the next start.
     pub async fn start(&self, event_bus: SharedEventBus) -> SearchResult<()> {
         let mut receiver = event_bus.subscribe();
 
         // Initialize indexer
-        {
-            let mut indexer_guard = self.indexer.lock().await;
-            if indexer_guard.is_none() {
-                let indexer = Indexer::new(self.config.clone(), self.contexts_root.clone()).await?;
-                *indexer_guard = Some(indexer);
-            }
-        }
+        drop(self.lock_indexer().await?);
 
         // Spawn interval processor (every N seconds)
         let indexer = self.indexer.clone();
         let enabled = self.enabled.clone();
+        let paused = self.paused.clone();
         let pending = self.pending_actions.clone();
+        let indexed = self.indexed.clone();
         let interval_secs = self.check_interval_secs;
 
-        tokio::spawn(async move {
-            Self::process_pending_interval(pending, indexer, enabled, interval_secs).await;
-        });
+        let _processor = AbortOnDrop(tokio::spawn(async move {
+            Self::process_pending_interval(
+                pending,
+                indexer,
+                enabled,
+                paused,
+                indexed,
+                interval_secs,
+            )
+            .await;
+        }));
 
         log::info!(
             "[IndexSync] Started with {} second interval",
@@ -115,28 +188,136 @@ impl IndexSyncService {
                         continue;
                     }
 
-                    let actions = Self::event_to_actions(event);
-                    let mut pending_guard = self.pending_actions.lock().await;
-                    for action in actions {
-                        match &action {
-                            IndexAction::Update { rel_path } => {
-                                pending_guard.insert(rel_path.clone(), action);
-                            }
-                            IndexAction::Remove { rel_path } => {
-                                pending_guard.insert(rel_path.clone(), action);
+                    if let Event::Folder(ref folder_event) = event {
+                        if let Some((old_prefix, new_prefix)) = folder_event.renamed_prefix() {
+                            if self.apply_folder_rename(old_prefix, new_prefix).await {
+                                continue;
                             }
-                            IndexAction::Rename { old_path, new_path } => {
-                                // Remove any pending action for the old path
-                                pending_guard.remove(old_path);
-                                // Insert rename action with new_path as key
-                                pending_guard.insert(new_path.clone(), action);
+                        }
+                    }
+
+                    let actions = Self::event_to_actions(event);
+                    let mut renames = Vec::new();
+                    let mut removals = Vec::new();
+                    let mut updates = Vec::new();
+                    {
+                        let mut pending_guard = self.pending_actions.lock().await;
+                        let mut prioritized = self.prioritized.lock().await;
+                        for action in actions {
+                            match action {
+                                IndexAction::Update { rel_path }
+                                    if !self.is_paused()
+                                        && (prioritized.remove(&rel_path)
+                                            || self.is_high_priority(&rel_path)) =>
+                                {
+                                    pending_guard.remove(&rel_path);
+                                    updates.push(rel_path);
+                                }
+                                IndexAction::Update { ref rel_path } => {
+                                    pending_guard.insert(rel_path.clone(), action);
+                                }
+                                IndexAction::Remove { rel_path } => {
+                                    // A rename into this path that is still pending
+                                    // left the chunks at the path it came from
+                                    let mut paths = vec![rel_path];
+                                    if let Some(IndexAction::Rename { old_path, .. }) =
+                                        pending_guard.remove(&paths[0])
+                                    {
+                                        paths.push(old_path);
+                                    }
+                                    for rel_path in paths {
+                                        if self.is_paused() {
+                                            pending_guard.insert(
+                                                rel_path.clone(),
+                                                IndexAction::Remove { rel_path },
+                                            );
+                                        } else {
+                                            pending_guard.remove(&rel_path);
+                                            removals.push(rel_path);
+                                        }
+                                    }
+                                }
+                                IndexAction::Rename { old_path, new_path } => {
+                                    match pending_guard.remove(&old_path) {
+                                        // Content changed since it was indexed: re-index
+                                        // under the new path.
+                                        Some(IndexAction::Update { .. }) => {
+                                            pending_guard.insert(
+                                                new_path.clone(),
+                                                IndexAction::Update { rel_path: new_path },
+                                            );
+                                            pending_guard.insert(
+                                                old_path.clone(),
+                                                IndexAction::Remove { rel_path: old_path },
+                                            );
+                                        }
+                                        // Collapse a chain of pending renames into one
+                                        Some(IndexAction::Rename {
+                                            old_path: original, ..
+                                        }) => {
+                                            pending_guard.insert(
+                                                new_path.clone(),
+                                                IndexAction::Rename {
+                                                    old_path: original,
+                                                    new_path,
+                                                },
+                                            );
+                                        }
+                                        _ if !self.is_paused() => {
+                                            renames.push((old_path, new_path))
+                                        }
+                                        _ => {
+                                            pending_guard.insert(
+                                                new_path.clone(),
+                                                IndexAction::Rename { old_path, new_path },
+                                            );
+                                        }
+                                    }
+                                }
                             }
                         }
+
+                        let count = pending_guard.len();
+                        if count > 0 {
+                            log::debug!("[IndexSync] {} pending updates", count);
+                        }
+                    }
+
+                    // Renames only rewrite stored paths, so apply them right away
+                    // and keep search links valid; failures are retried in a batch.
+                    for (old_path, new_path) in renames {
+                        if let Err(e) = self.apply_rename(&old_path, &new_path).await {
+                            log::warn!(
+                                "[IndexSync] Rename {} -> {} failed, queued: {}",
+                                old_path,
+                                new_path,
+                                e
+                            );
+                            self.pending_actions.lock().await.insert(
+                                new_path.clone(),
+                                IndexAction::Rename { old_path, new_path },
+                            );
+                        }
+                    }
+
+                    for rel_path in removals {
+                        if let Err(e) = self.apply_remove(&rel_path).await {
+                            log::warn!("[IndexSync] Remove {} failed, queued: {}", rel_path, e);
+                            self.pending_actions
+                                .lock()
+                                .await
+                                .insert(rel_path.clone(), IndexAction::Remove { rel_path });
+                        }
                     }
 
-                    let count = pending_guard.len();
-                    if count > 0 {
-                        log::debug!("[IndexSync] {} pending updates", count);
+                    for rel_path in updates {
+                        if let Err(e) = self.apply_update(&rel_path).await {
+                            log::warn!("[IndexSync] Update {} failed, queued: {}", rel_path, e);
+                            self.pending_actions
+                                .lock()
+                                .await
+                                .insert(rel_path.clone(), IndexAction::Update { rel_path });
+                        }
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
@@ -152,6 +333,211 @@ impl IndexSyncService {
         Ok(())
     }
 
+    /// Lock the indexer, creating it if the service hasn't yet
+    async fn lock_indexer(&self) -> SearchResult<MutexGuard<'_, Option<Indexer>>> {
+        let mut indexer_guard = self.indexer.lock().await;
+        if indexer_guard.is_none() {
+            let indexer = Indexer::new(self.config(), self.contexts_root.clone()).await?;
+            *indexer_guard = Some(indexer);
+        }
+        Ok(indexer_guard)
+    }
+
+    /// Close the index, as before its files are swapped for another's. Pause
+    /// the service first, and `reopen_index` once the swap is done.
+    pub async fn close_index(&self) {
+        *self.indexer.lock().await = None;
+    }
+
+    /// Open the index again after `close_index`, with the current config
+    pub async fn reopen_index(&self) -> SearchResult<()> {
+        drop(self.lock_indexer().await?);
+        Ok(())
+    }
+
+    /// Index `rel_path` as soon as its update comes in instead of with the
+    /// next batch, as for a doc the user just saved, so the edit shows up in
+    /// search right away. An update already queued for it is applied now.
+    /// Nothing happens while paused or until an index is built.
+    pub async fn prioritize(&self, rel_path: &str) {
+        if !self.is_enabled() || self.is_paused() {
+            return;
+        }
+        {
+            let mut pending_guard = self.pending_actions.lock().await;
+            if !matches!(
+                pending_guard.get(rel_path),
+                Some(IndexAction::Update { .. })
+            ) {
+                // The update is still on its way
+                self.prioritized.lock().await.insert(rel_path.to_string());
+                return;
+            }
+            pending_guard.remove(rel_path);
+        }
+        if let Err(e) = self.apply_update(rel_path).await {
+            log::warn!("[IndexSync] Update {} failed, queued: {}", rel_path, e);
+            self.pending_actions.lock().await.insert(
+                rel_path.to_string(),
+                IndexAction::Update {
+                    rel_path: rel_path.to_string(),
+                },
+            );
+        }
+    }
+
+    /// Re-index one doc now, in place of an update queued for it, building
+    /// the index if there is none yet. Returns its chunk count.
+    pub async fn index_doc(&self, doc: &crate::Doc) -> SearchResult<usize> {
+        {
+            let mut pending_guard = self.pending_actions.lock().await;
+            if matches!(
+                pending_guard.get(&doc.rel_path),
+                Some(IndexAction::Update { .. })
+            ) {
+                pending_guard.remove(&doc.rel_path);
+            }
+        }
+        let mut indexer_guard = self.lock_indexer().await?;
+        let Some(indexer) = indexer_guard.as_mut() else {
+            return Ok(0);
+        };
+        let count = indexer.index_doc(doc).await?;
+        let _ = self.indexed.send(doc.rel_path.clone());
+        Ok(count)
+    }
+
+    /// Drop files from the index and purge their chunks from the index
+    /// files right away, as for docs that were just encrypted, if an index
+    /// is built
+    pub async fn purge_files(&self, rel_paths: &[String]) -> SearchResult<()> {
+        if rel_paths.is_empty() {
+            return Ok(());
+        }
+        // Purged even when the service isn't running, as the text must not
+        // stay readable until it is
+        let mut indexer_guard = self.lock_indexer().await?;
+        let Some(indexer) = indexer_guard.as_mut() else {
+            return Ok(());
+        };
+        if !indexer.index_exists().await {
+            return Ok(());
+        }
+        indexer.purge_files(rel_paths).await?;
+        indexer.update_metadata()?;
+        log::debug!("[IndexSync] Purged {} files", rel_paths.len());
+        Ok(())
+    }
+
+    /// Repoint a renamed file's chunks in the index, if one is built
+    async fn apply_rename(&self, old_path: &str, new_path: &str) -> SearchResult<()> {
+        let mut indexer_guard = self.indexer.lock().await;
+        let Some(indexer) = indexer_guard.as_mut() else {
+            return Ok(());
+        };
+        if !indexer.index_exists().await {
+            return Ok(());
+        }
+        indexer.rename_doc_path(old_path, new_path).await?;
+        log::debug!("[IndexSync] Renamed: {} -> {}", old_path, new_path);
+        Ok(())
+    }
+
+    /// Drop a deleted file's chunks from the index, if one is built
+    async fn apply_remove(&self, rel_path: &str) -> SearchResult<()> {
+        let mut indexer_guard = self.indexer.lock().await;
+        let Some(indexer) = indexer_guard.as_mut() else {
+            return Ok(());
+        };
+        if !indexer.index_exists().await {
+            return Ok(());
+        }
+        indexer.remove_file(rel_path).await?;
+        indexer.update_metadata()?;
+        log::debug!("[IndexSync] Removed: {}", rel_path);
+        Ok(())
+    }
+
+    /// Repoint every chunk under a renamed or moved folder in one update,
+    /// if an index is built. Returns false when the folder's docs have to be
+    /// renamed one by one instead: while paused, when updates under the
+    /// folder are pending, when the move changes embedding profiles, or when
+    /// the update failed.
+    async fn apply_folder_rename(&self, old_prefix: &str, new_prefix: &str) -> bool {
+        if self.is_paused() {
+            return false;
+        }
+        let under = |path: &str| {
+            path.strip_prefix(old_prefix)
+                .is_some_and(|rest| rest.starts_with('/'))
+        };
+        let pending_inside = self
+            .pending_actions
+            .lock()
+            .await
+            .iter()
+            .any(|(rel_path, action)| {
+                under(rel_path)
+                    || matches!(action, IndexAction::Rename { old_path, .. } if under(old_path))
+            });
+        if pending_inside {
+            return false;
+        }
+
+        let mut indexer_guard = self.indexer.lock().await;
+        let Some(indexer) = indexer_guard.as_mut() else {
+            return true;
+        };
+        if !indexer.index_exists().await {
+            return true;
+        }
+        match indexer.rename_folder_path(old_prefix, new_prefix).await {
+            Ok(renamed) => {
+                if renamed {
+                    log::debug!(
+                        "[IndexSync] Renamed folder: {} -> {}",
+                        old_prefix,
+                        new_prefix
+                    );
+                }
+                renamed
+            }
+            Err(e) => {
+                log::warn!(
+                    "[IndexSync] Folder rename {} -> {} failed, renaming docs one by one: {}",
+                    old_prefix,
+                    new_prefix,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    fn is_high_priority(&self, rel_path: &str) -> bool {
+        SettingsResolver::new(&self.contexts_root)
+            .doc_or_closed(rel_path)
+            .index_priority()
+            == IndexPriority::High
+    }
+
+    /// Re-index a high-priority or prioritized file right away, if an index
+    /// is built
+    async fn apply_update(&self, rel_path: &str) -> SearchResult<()> {
+        let mut indexer_guard = self.indexer.lock().await;
+        let Some(indexer) = indexer_guard.as_mut() else {
+            return Ok(());
+        };
+        if !indexer.index_exists().await {
+            return Ok(());
+        }
+        let count = indexer.index_file(rel_path).await?;
+        indexer.update_metadata()?;
+        log::debug!("[IndexSync] Updated: {} ({} chunks)", rel_path, count);
+        let _ = self.indexed.send(rel_path.to_string());
+        Ok(())
+    }
+
     /// Convert an event to index actions
     fn event_to_actions(event: Event) -> Vec<IndexAction> {
         match event {
@@ -187,6 +573,8 @@ impl IndexSyncService {
         pending: Arc<Mutex<HashMap<String, IndexAction>>>,
         indexer: Arc<Mutex<Option<Indexer>>>,
         enabled: Arc<std::sync::atomic::AtomicBool>,
+        paused: Arc<std::sync::atomic::AtomicBool>,
+        indexed: broadcast::Sender<String>,
         interval_secs: u64,
     ) {
         // Start first tick after interval_secs (not immediately)
@@ -196,7 +584,9 @@ impl IndexSyncService {
         loop {
             ticker.tick().await;
 
-            if !enabled.load(std::sync::atomic::Ordering::SeqCst) {
+            if !enabled.load(std::sync::atomic::Ordering::SeqCst)
+                || paused.load(std::sync::atomic::Ordering::SeqCst)
+            {
                 continue;
             }
 
@@ -233,6 +623,7 @@ impl IndexSyncService {
                                         rel_path,
                                         count
                                     );
+                                    let _ = indexed.send(rel_path);
                                     Ok(())
                                 }
                                 Err(e) => Err(e),
@@ -248,7 +639,7 @@ impl IndexSyncService {
                             }
                         }
                         IndexAction::Rename { old_path, new_path } => {
-                            match indexer.update_file_path(&old_path, &new_path).await {
+                            match indexer.rename_doc_path(&old_path, &new_path).await {
                                 Ok(()) => {
                                     log::debug!(
                                         "[IndexSync] Renamed: {} -> {}",
@@ -1,13 +1,19 @@
 //! Document indexer
 
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use super::chunker::Chunker;
+use super::chunker::{Chunker, CHUNKER_VERSION};
 use super::config::SearchConfig;
+use super::doc_status::{DocIndexState, DocIndexStatus, DocStatusFile};
 use super::embedding::EmbeddingClient;
+use super::embedding_cache::{chunk_key, EmbeddingCache, MAX_ENTRIES};
 use super::error::{SearchError, SearchResult};
-use super::types::Chunk;
-use super::vector_store::VectorStore;
+use super::types::{Chunk, ChunkPreview};
+use super::usage::UsageLedger;
+use super::vector_store::{content_hash, IndexedDoc, VectorStore, SEARCH_DISTANCE};
+use crate::{DocKind, IndexPriority, SettingsResolver};
 
 #[derive(Clone)]
 struct IdeaEntry {
@@ -18,6 +24,21 @@ struct IdeaEntry {
 
 const DEFAULT_IDEA_BOX: &str = "inbox";
 
+/// Send a chunking progress update every this many docs
+const CHUNKING_PROGRESS_EVERY: usize = 10;
+
+/// Share of a build's progress percentage given to chunking; embedding and
+/// storing take the rest
+const CHUNKING_PERCENT: usize = 10;
+
+/// Index metadata key for the embedding and chunking settings the index was
+/// built with; a build with other settings can't reuse its chunks
+const BUILD_SETTINGS_KEY: &str = "buildSettings";
+/// Index metadata keys for the embedding model and the dimensions it was
+/// configured for when the index was built; searches check them
+pub(super) const EMBEDDING_MODEL_KEY: &str = "embeddingModel";
+pub(super) const EMBEDDING_DIMENSIONS_KEY: &str = "embeddingDimensions";
+
 fn parse_idea_marker(line: &str) -> Option<(String, String)> {
     let trimmed = line.trim();
     if !trimmed.starts_with("[//]: # (") || !trimmed.ends_with(')') {
@@ -95,12 +116,88 @@ fn extract_idea_box(rel_path: &str) -> Option<String> {
     }
 }
 
+/// Text of a doc for indexing, or why the doc is left out
+enum DocText {
+    Text { content: String, markdown: bool },
+    Skipped(String),
+}
+
+fn read_doc_text(contexts_root: &Path, rel_path: &str, abs_path: &Path) -> SearchResult<DocText> {
+    let kind = DocKind::detect(abs_path);
+    if let Some(reason) = kind.skip_reason() {
+        return Ok(DocText::Skipped(reason.to_string()));
+    }
+    #[cfg(feature = "pdf")]
+    if kind == DocKind::Pdf {
+        return Ok(match pdf_extract::extract_text(abs_path) {
+            Ok(content) => DocText::Text {
+                content,
+                markdown: false,
+            },
+            Err(e) => DocText::Skipped(format!("PDF text extraction failed: {}", e)),
+        });
+    }
+    match crate::crypto::read_text(contexts_root, rel_path, abs_path) {
+        Ok(content) => {
+            let kind = DocKind::sniff(abs_path, content.as_bytes());
+            Ok(match kind.skip_reason() {
+                Some(reason) => DocText::Skipped(reason.to_string()),
+                None => DocText::Text {
+                    content,
+                    markdown: kind == DocKind::Markdown,
+                },
+            })
+        }
+        Err(crate::CoreError::Locked { .. }) => Ok(DocText::Skipped(
+            "encrypted and the vault is locked".to_string(),
+        )),
+        Err(crate::CoreError::Io(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+            Ok(DocText::Skipped("not valid UTF-8 text".to_string()))
+        }
+        Err(crate::CoreError::Io(e)) => Err(e.into()),
+        Err(e) => Ok(DocText::Skipped(e.to_string())),
+    }
+}
+
+/// A file's modified time in ms since epoch, if the platform reports one
+fn modified_ms(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_millis() as u64)
+}
+
+/// Build percentage once chunking is done, from the chunks embedded
+fn embedding_percent(embedded: usize, total: usize) -> u8 {
+    (CHUNKING_PERCENT + embedded * (100 - CHUNKING_PERCENT) / total.max(1)) as u8
+}
+
+/// The embedding cache of the index at `config`, or `None` if it can't be
+/// opened
+fn open_embedding_cache(config: &SearchConfig) -> Option<EmbeddingCache> {
+    match EmbeddingCache::for_config(config) {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            log::warn!("Embedding cache disabled: {}", e);
+            None
+        }
+    }
+}
+
 /// Index build statistics
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexStats {
     /// Total documents indexed
     pub total_docs: usize,
+    /// Docs chunked and embedded by this build
+    pub indexed_docs: usize,
+    /// Docs whose text hadn't changed since they were last indexed, kept
+    /// without embedding them again
+    pub skipped_docs: usize,
+    /// Chunks whose vectors came from the embedding cache
+    pub cache_hits: usize,
+    /// Chunks sent to the embedding provider
+    pub cache_misses: usize,
     /// Total chunks created
     pub total_chunks: usize,
     /// Total tokens used (if available)
@@ -109,22 +206,91 @@ pub struct IndexStats {
     pub elapsed_ms: u64,
     /// Last updated timestamp (ms since epoch)
     pub last_updated: Option<u64>,
+    /// Docs left out because they have no indexable text
+    pub skipped: Vec<SkippedDoc>,
+    /// Stopped early by the cancel flag; the index holds only the batches
+    /// stored before it was set
+    pub cancelled: bool,
+}
+
+/// A doc the index leaves out, such as an image
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedDoc {
+    pub path: String,
+    pub reason: String,
+}
+
+/// How much of the vault the index covers, by doc
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexCoverage {
+    /// Indexed and unchanged since
+    pub indexed: usize,
+    /// Changed since they were indexed
+    pub stale: usize,
+    /// Indexable but not in the index
+    pub missing: usize,
+    /// Left out by folder settings
+    pub excluded: usize,
+    /// No text to index, such as images
+    pub unsearchable: usize,
+}
+
+/// Where one doc stands in the index, for marking docs that changed since
+/// they were indexed
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocIndexSummary {
+    pub path: String,
+    /// Has chunks in the index
+    pub indexed: bool,
+    pub chunks: usize,
+    /// Modified since it was indexed
+    pub stale: bool,
+    /// The doc's modified time when indexed (ms since epoch)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexed_modified_at: Option<u64>,
+}
+
+/// How a doc is held in the index, for finding out why search misses it
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocIndexInspection {
+    pub path: String,
+    /// Embedding profile whose index holds the doc, if not the default
+    pub profile: Option<String>,
+    pub chunks: Vec<ChunkPreview>,
+    /// Last attempt to index the doc; `None` when it was never indexed, or
+    /// not since the index started recording attempts
+    pub status: Option<DocIndexStatus>,
 }
 
 /// Index build progress
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexProgress {
-    /// Current phase: "chunking", "embedding", "storing"
+    /// Current phase: "chunking", "embedding", "storing", "cancelled", "done"
     pub phase: String,
-    /// Current item being processed
+    /// Items done: docs while chunking, chunks embedded after that
     pub current: usize,
-    /// Total items to process
+    /// Total items: docs while chunking, chunks after that
     pub total: usize,
     /// Percentage complete (0-100)
     pub percent: u8,
-    /// Optional message
-    pub message: Option<String>,
+    /// Message catalog key for the phase, e.g. `index.progress.embedding`.
+    /// The app fills in the counts below by field name.
+    pub message_key: &'static str,
+    /// Docs read and chunked so far
+    pub docs_processed: usize,
+    /// Docs in the build
+    pub docs_total: usize,
+    /// Chunks written to the index so far
+    pub chunks_stored: usize,
+    /// Chunks embedded so far
+    pub chunks_embedded: usize,
+    /// Chunks to embed, known once every doc is chunked (0 until then)
+    pub chunks_total: usize,
 }
 
 /// Document indexer for building search index
@@ -133,9 +299,14 @@ pub struct Indexer {
     contexts_root: PathBuf,
     vector_store: VectorStore,
     embedding_client: EmbeddingClient,
+    /// Vectors of chunks embedded before; `None` if the cache can't be
+    /// opened, and every chunk is embedded
+    embedding_cache: Option<EmbeddingCache>,
     chunker: Chunker,
     /// Whether vector_store has been re-initialized with actual dimensions
     dimensions_verified: bool,
+    /// Indexers for embedding profiles assigned to folders, created on first use
+    profile_indexers: HashMap<String, Indexer>,
 }
 
 impl Indexer {
@@ -147,27 +318,46 @@ impl Indexer {
         let mut vector_store = VectorStore::new(lancedb_path, dimensions);
         vector_store.initialize().await?;
 
-        let embedding_client = EmbeddingClient::new(config.embedding.clone())?;
+        let embedding_client = EmbeddingClient::new(config.embedding.clone())?
+            .with_usage_ledger(UsageLedger::new(config.paths.get_usage_ledger_path()))
+            .with_models_path(config.paths.get_models_path());
+        let embedding_cache = open_embedding_cache(&config);
 
-        let chunker = Chunker::new(config.search.chunk_size, config.search.chunk_overlap);
+        let chunker = Chunker::for_config(&config);
 
         Ok(Self {
             config,
             contexts_root,
             vector_store,
             embedding_client,
+            embedding_cache,
             chunker,
             dimensions_verified: false,
+            profile_indexers: HashMap::new(),
         })
     }
 
-    /// Verify and update vector store dimensions based on actual embedding dimensions
-    async fn verify_dimensions(&mut self) -> SearchResult<()> {
+    /// Get the indexer for a folder-assigned embedding profile
+    async fn profile_indexer(&mut self, name: &str) -> SearchResult<&mut Indexer> {
+        if !self.profile_indexers.contains_key(name) {
+            let config = self.config.with_profile(name)?;
+            let indexer = Indexer::new(config, self.contexts_root.clone()).await?;
+            self.profile_indexers.insert(name.to_string(), indexer);
+        }
+        Ok(self
+            .profile_indexers
+            .get_mut(name)
+            .expect("profile indexer inserted above"))
+    }
+
+    /// Verify and update vector store dimensions based on actual embedding
+    /// dimensions: `actual_dim` is the size of the vectors about to be stored,
+    /// which may all have come from the embedding cache
+    async fn verify_dimensions(&mut self, actual_dim: usize) -> SearchResult<()> {
         if self.dimensions_verified {
             return Ok(());
         }
 
-        let actual_dim = self.embedding_client.actual_dimensions();
         if actual_dim > 0 && actual_dim != self.config.embedding.dimensions {
             log::info!(
                 "Re-initializing vector store with actual dimensions: {} (was {})",
@@ -184,179 +374,584 @@ impl Indexer {
         Ok(())
     }
 
-    /// Build index for all documents
+    /// Rebuild the index from scratch for all documents. Vectors still come
+    /// from the embedding cache where it has them.
     pub async fn build_all(&mut self, docs: Vec<crate::Doc>) -> SearchResult<IndexStats> {
-        self.build_all_with_progress(docs, |_| {}).await
+        self.clean().await?;
+        self.build_all_with_progress(docs, |_| {}, &AtomicBool::new(false), false)
+            .await
     }
 
     /// Build index for all documents with progress callback
+    ///
+    /// Docs in folders assigned to an embedding profile are built into that
+    /// profile's index; every assigned profile's index is rebuilt.
+    ///
+    /// Chunks are embedded `embedding.batch_size` per request with up to
+    /// `embedding.max_concurrent_requests` requests in flight, and progress
+    /// counts chunks embedded.
+    ///
+    /// Unless `force` is set, docs whose text hasn't changed since they were
+    /// indexed keep their chunks and aren't embedded again, and chunks of
+    /// docs no longer listed are dropped. An index built with another
+    /// embedding model or chunk size is always rebuilt from scratch. Chunks
+    /// whose text is in the embedding cache then take their vectors from it
+    /// instead of the embedding provider; a forced build embeds every chunk
+    /// again and refreshes the cache with the new vectors.
+    ///
+    /// `cancel` is checked between docs while chunking and between embedding
+    /// rounds. Once it is set the build stops after the round in flight, keeps
+    /// what it has stored, and returns stats marked `cancelled` with a final
+    /// `cancelled` progress.
     pub async fn build_all_with_progress<F>(
         &mut self,
         docs: Vec<crate::Doc>,
         mut on_progress: F,
+        cancel: &AtomicBool,
+        force: bool,
+    ) -> SearchResult<IndexStats>
+    where
+        F: FnMut(IndexProgress),
+    {
+        let start = std::time::Instant::now();
+        let (docs, excluded) = self.prioritize(docs);
+        let profiles = self.config.assigned_profiles();
+        if profiles.is_empty() {
+            let mut stats = self
+                .build_local_with_progress(docs, on_progress, cancel, force)
+                .await?;
+            self.record_excluded(&excluded);
+            stats.skipped.extend(excluded);
+            return Ok(stats);
+        }
+
+        let mut by_profile: BTreeMap<String, Vec<crate::Doc>> = profiles
+            .into_iter()
+            .map(|name| (name, Vec::new()))
+            .collect();
+        let mut default_docs = Vec::new();
+        for doc in docs {
+            match self.config.profile_for_path(&doc.rel_path) {
+                Some(name) => by_profile.entry(name.to_string()).or_default().push(doc),
+                None => default_docs.push(doc),
+            }
+        }
+
+        let mut stats = self
+            .build_local_with_progress(default_docs, &mut on_progress, cancel, force)
+            .await?;
+        for (name, docs) in by_profile {
+            if stats.cancelled {
+                break;
+            }
+            let indexer = self.profile_indexer(&name).await?;
+            let profile_stats = indexer
+                .build_local_with_progress(docs, &mut on_progress, cancel, force)
+                .await?;
+            indexer.update_metadata()?;
+            stats.total_docs += profile_stats.total_docs;
+            stats.indexed_docs += profile_stats.indexed_docs;
+            stats.skipped_docs += profile_stats.skipped_docs;
+            stats.cache_hits += profile_stats.cache_hits;
+            stats.cache_misses += profile_stats.cache_misses;
+            stats.total_chunks += profile_stats.total_chunks;
+            stats.skipped.extend(profile_stats.skipped);
+            stats.cancelled = profile_stats.cancelled;
+        }
+        self.record_excluded(&excluded);
+        stats.skipped.extend(excluded);
+        stats.elapsed_ms = start.elapsed().as_millis() as u64;
+        Ok(stats)
+    }
+
+    /// Record docs left out by folder settings in the status of the index
+    /// each would otherwise be in
+    fn record_excluded(&self, excluded: &[SkippedDoc]) {
+        let mut by_index: BTreeMap<PathBuf, Vec<(String, DocIndexState)>> = BTreeMap::new();
+        for doc in excluded {
+            by_index
+                .entry(self.metadata_path_for(&doc.path))
+                .or_default()
+                .push((
+                    doc.path.clone(),
+                    DocIndexState::Skipped {
+                        reason: doc.reason.clone(),
+                    },
+                ));
+        }
+        for (metadata_path, states) in by_index {
+            DocStatusFile::beside(metadata_path).record(states);
+        }
+    }
+
+    /// Metadata path of the index `rel_path` belongs in
+    fn metadata_path_for(&self, rel_path: &str) -> PathBuf {
+        self.config
+            .profile_for_path(rel_path)
+            .and_then(|name| self.config.with_profile(name).ok())
+            .unwrap_or_else(|| self.config.clone())
+            .paths
+            .get_index_metadata_path()
+    }
+
+    fn doc_status(&self) -> DocStatusFile {
+        DocStatusFile::beside(self.config.paths.get_index_metadata_path())
+    }
+
+    /// `docs` in build order, those in high-priority folders first, and the
+    /// docs that folder settings leave out of the index
+    fn prioritize(&self, docs: Vec<crate::Doc>) -> (Vec<crate::Doc>, Vec<SkippedDoc>) {
+        let mut resolver = SettingsResolver::new(&self.contexts_root);
+        let unlocked = crate::crypto::is_unlocked(&self.contexts_root);
+        let mut excluded = Vec::new();
+        let mut kept = Vec::with_capacity(docs.len());
+        for doc in docs {
+            let settings = resolver.doc_or_closed(&doc.rel_path);
+            if !settings.searchable(unlocked) {
+                excluded.push(SkippedDoc {
+                    path: doc.rel_path,
+                    reason: "in an encrypted folder".to_string(),
+                });
+                continue;
+            }
+            match settings.index_priority() {
+                IndexPriority::Skip => excluded.push(SkippedDoc {
+                    path: doc.rel_path,
+                    reason: "excluded by folder settings".to_string(),
+                }),
+                priority => kept.push((priority != IndexPriority::High, doc)),
+            }
+        }
+        // Stable, so the original order holds within each priority
+        kept.sort_by_key(|(normal, _)| *normal);
+        (kept.into_iter().map(|(_, doc)| doc).collect(), excluded)
+    }
+
+    /// Embedding and chunking settings that decide what a doc's chunks
+    /// and vectors look like, and the distance they are searched by: indexes
+    /// built before search switched from L2 to cosine are rebuilt
+    fn build_settings(&self) -> String {
+        let embedding = &self.config.embedding;
+        let chunking = &self.config.chunking;
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}:{}:{:?}",
+            embedding.provider.as_str(),
+            embedding.model,
+            embedding.dimensions,
+            self.config.chunk_max_tokens(),
+            chunking.overlap_tokens,
+            chunking.strategy.as_str(),
+            embedding.include_headings,
+            CHUNKER_VERSION,
+            SEARCH_DISTANCE
+        )
+    }
+
+    /// Settings the existing index was built with, if recorded
+    fn stored_build_settings(&self) -> Option<String> {
+        let metadata = std::fs::read_to_string(self.config.paths.get_index_metadata_path()).ok()?;
+        serde_json::from_str::<serde_json::Value>(&metadata)
+            .ok()?
+            .get(BUILD_SETTINGS_KEY)?
+            .as_str()
+            .map(str::to_string)
+    }
+
+    /// Build this indexer's own index from `docs`, reusing the chunks of
+    /// unchanged docs unless `force` is set
+    async fn build_local_with_progress<F>(
+        &mut self,
+        docs: Vec<crate::Doc>,
+        mut on_progress: F,
+        cancel: &AtomicBool,
+        force: bool,
     ) -> SearchResult<IndexStats>
     where
         F: FnMut(IndexProgress),
     {
         let start = std::time::Instant::now();
         let total_docs = docs.len();
+        let mut cancelled = false;
         let mut total_chunks = 0;
         let mut processed_docs = 0;
+        let mut skipped_docs = 0;
+        let mut skipped = Vec::new();
+        let mut states = Vec::with_capacity(total_docs);
+
+        // Start over unless the index was built the same way; otherwise what
+        // it holds for each doc decides whether the doc is embedded again
+        let build_settings = self.build_settings();
+        let reuse = !force && self.stored_build_settings().as_ref() == Some(&build_settings);
+        let mut indexed = if reuse {
+            self.vector_store.indexed_docs().await?
+        } else {
+            self.vector_store.reset().await?;
+            HashMap::new()
+        };
+        // Docs the index keeps, unchanged or about to be replaced; chunks of
+        // any other doc are stale
+        let mut kept = HashSet::new();
+
+        // Phase 1: Chunking. Every doc is chunked up front so progress can
+        // count chunks; each entry holds one doc's chunks.
+        let mut chunked: Vec<(String, Vec<Chunk>)> = Vec::new();
+        for (doc_idx, doc) in docs.iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                cancelled = true;
+                break;
+            }
+            if doc_idx % CHUNKING_PROGRESS_EVERY == 0 {
+                on_progress(IndexProgress {
+                    phase: "chunking".to_string(),
+                    current: processed_docs,
+                    total: total_docs,
+                    percent: ((processed_docs * CHUNKING_PERCENT) / total_docs.max(1)) as u8,
+                    message_key: "index.progress.chunking",
+                    docs_processed: processed_docs,
+                    docs_total: total_docs,
+                    chunks_stored: total_chunks,
+                    chunks_embedded: 0,
+                    chunks_total: 0,
+                });
+            }
 
-        // Reset existing index
-        self.vector_store.reset().await?;
-
-        // Process documents in batches
-        let batch_size = 10;
-        let total_batches = docs.len().div_ceil(batch_size);
-
-        for (batch_idx, batch) in docs.chunks(batch_size).enumerate() {
-            let mut all_chunks = Vec::new();
+            // Skip files that no longer exist on disk (orphaned DB records)
+            if !std::path::Path::new(&doc.abs_path).exists() {
+                log::warn!(
+                    "Skipping missing file during indexing: {} (orphaned DB record?)",
+                    doc.rel_path
+                );
+                processed_docs += 1;
+                continue;
+            }
 
-            // Phase 1: Chunking
-            on_progress(IndexProgress {
-                phase: "chunking".to_string(),
-                current: batch_idx + 1,
-                total: total_batches,
-                percent: ((batch_idx * 100) / total_batches.max(1)) as u8,
-                message: Some(format!(
-                    "正在分块处理文档 ({}/{})",
-                    processed_docs, total_docs
-                )),
-            });
+            // A file untouched since it was indexed needn't even be read
+            let doc_modified_at = modified_ms(Path::new(&doc.abs_path));
+            let previous = indexed.get(&doc.rel_path);
+            if let Some(previous) = previous.filter(|previous| {
+                previous.doc_hash.is_some()
+                    && previous.doc_modified_at.is_some()
+                    && previous.doc_modified_at == doc_modified_at
+            }) {
+                states.push((
+                    doc.rel_path.clone(),
+                    DocIndexState::Indexed {
+                        chunks: previous.chunks,
+                    },
+                ));
+                total_chunks += previous.chunks;
+                skipped_docs += 1;
+                processed_docs += 1;
+                kept.insert(doc.rel_path.clone());
+                continue;
+            }
 
-            for doc in batch {
-                // Skip files that no longer exist on disk (orphaned DB records)
-                if !std::path::Path::new(&doc.abs_path).exists() {
-                    log::warn!(
-                        "Skipping missing file during indexing: {} (orphaned DB record?)",
-                        doc.rel_path
-                    );
+            let (content, markdown) = match read_doc_text(
+                &self.contexts_root,
+                &doc.rel_path,
+                Path::new(&doc.abs_path),
+            )? {
+                DocText::Text { content, markdown } => (content, markdown),
+                DocText::Skipped(reason) => {
+                    log::info!("Skipping {} during indexing: {}", doc.rel_path, reason);
+                    states.push((
+                        doc.rel_path.clone(),
+                        DocIndexState::Skipped {
+                            reason: reason.clone(),
+                        },
+                    ));
+                    skipped.push(SkippedDoc {
+                        path: doc.rel_path.clone(),
+                        reason,
+                    });
                     processed_docs += 1;
                     continue;
                 }
-
-                let content = std::fs::read_to_string(&doc.abs_path)?;
-                if content.trim().is_empty() {
-                    processed_docs += 1;
-                    continue;
+            };
+            if content.trim().is_empty() {
+                states.push((doc.rel_path.clone(), DocIndexState::Empty));
+                processed_docs += 1;
+                continue;
+            }
+            // Touched but not changed
+            let doc_hash = content_hash(&content);
+            if let Some(previous) =
+                previous.filter(|previous| previous.doc_hash.as_ref() == Some(&doc_hash))
+            {
+                states.push((
+                    doc.rel_path.clone(),
+                    DocIndexState::Indexed {
+                        chunks: previous.chunks,
+                    },
+                ));
+                total_chunks += previous.chunks;
+                skipped_docs += 1;
+                processed_docs += 1;
+                kept.insert(doc.rel_path.clone());
+                continue;
+            }
+            let mut doc_chunks = Vec::new();
+
+            if doc.rel_path.starts_with(".ideas/") {
+                let entries = parse_idea_entries(&content);
+                let idea_box = extract_idea_box(&doc.rel_path);
+                for (i, entry) in entries.into_iter().enumerate() {
+                    let entry_date = entry.created_at.get(0..10).unwrap_or("").to_string();
+                    let title_line = entry
+                        .content
+                        .split('\n')
+                        .next()
+                        .unwrap_or("")
+                        .trim()
+                        .to_string();
+                    let id = format!("{}#{}", doc.rel_path, entry.id);
+                    doc_chunks.push(Chunk {
+                        id,
+                        file_path: doc.rel_path.clone(),
+                        content: entry.content,
+                        heading_path: String::new(),
+                        headings: Vec::new(),
+                        section_title: if title_line.is_empty() {
+                            None
+                        } else {
+                            Some(title_line)
+                        },
+                        doc_type: Some("idea".to_string()),
+                        entry_id: Some(entry.id),
+                        entry_date: if entry_date.is_empty() {
+                            None
+                        } else {
+                            Some(entry_date)
+                        },
+                        entry_created_at: Some(entry.created_at),
+                        idea_box: idea_box.clone(),
+                        doc_modified_at,
+                        doc_hash: Some(doc_hash.clone()),
+                        chunk_index: i,
+                        line_start: None,
+                        line_end: None,
+                        byte_start: None,
+                        byte_end: None,
+                        code_language: None,
+                        vector: vec![], // Will be filled below
+                    });
                 }
-
-                if doc.rel_path.starts_with(".ideas/") {
-                    let entries = parse_idea_entries(&content);
-                    let idea_box = extract_idea_box(&doc.rel_path);
-                    for (i, entry) in entries.into_iter().enumerate() {
-                        let entry_date = entry.created_at.get(0..10).unwrap_or("").to_string();
-                        let title_line = entry
-                            .content
-                            .split('\n')
-                            .next()
-                            .unwrap_or("")
-                            .trim()
-                            .to_string();
-                        let id = format!("{}#{}", doc.rel_path, entry.id);
-                        all_chunks.push(Chunk {
-                            id,
-                            file_path: doc.rel_path.clone(),
-                            content: entry.content,
-                            heading_path: String::new(),
-                            section_title: if title_line.is_empty() {
-                                None
-                            } else {
-                                Some(title_line)
-                            },
-                            doc_type: Some("idea".to_string()),
-                            entry_id: Some(entry.id),
-                            entry_date: if entry_date.is_empty() {
-                                None
-                            } else {
-                                Some(entry_date)
-                            },
-                            entry_created_at: Some(entry.created_at),
-                            idea_box: idea_box.clone(),
-                            chunk_index: i,
-                            vector: vec![], // Will be filled below
-                        });
-                    }
+            } else {
+                let text_chunks = if markdown {
+                    self.chunker.chunk(&content, &doc.rel_path)
                 } else {
-                    let text_chunks = self.chunker.chunk(&content, &doc.rel_path);
-
-                    for (i, text_chunk) in text_chunks.into_iter().enumerate() {
-                        let id = format!("{}#{}", doc.rel_path, i);
-                        all_chunks.push(Chunk {
-                            id,
-                            file_path: doc.rel_path.clone(),
-                            content: text_chunk.content,
-                            heading_path: text_chunk.heading_path,
-                            section_title: None,
-                            doc_type: Some("doc".to_string()),
-                            entry_id: None,
-                            entry_date: None,
-                            entry_created_at: None,
-                            idea_box: None,
-                            chunk_index: i,
-                            vector: vec![], // Will be filled below
-                        });
-                    }
+                    self.chunker.chunk_plain(&content)
+                };
+
+                for (i, text_chunk) in text_chunks.into_iter().enumerate() {
+                    let id = format!("{}#{}", doc.rel_path, i);
+                    doc_chunks.push(Chunk {
+                        id,
+                        file_path: doc.rel_path.clone(),
+                        content: text_chunk.content,
+                        heading_path: text_chunk.heading_path,
+                        headings: text_chunk.headings,
+                        section_title: None,
+                        doc_type: Some("doc".to_string()),
+                        entry_id: None,
+                        entry_date: None,
+                        entry_created_at: None,
+                        idea_box: None,
+                        doc_modified_at,
+                        doc_hash: Some(doc_hash.clone()),
+                        chunk_index: i,
+                        line_start: Some(text_chunk.start_line),
+                        line_end: Some(text_chunk.end_line),
+                        byte_start: Some(text_chunk.start_byte),
+                        byte_end: Some(text_chunk.end_byte),
+                        code_language: text_chunk.code_language,
+                        vector: vec![], // Will be filled below
+                    });
                 }
-                processed_docs += 1;
             }
-
-            if all_chunks.is_empty() {
-                continue;
+            if doc_chunks.is_empty() {
+                states.push((doc.rel_path.clone(), DocIndexState::Empty));
+            } else {
+                kept.insert(doc.rel_path.clone());
+                chunked.push((doc.rel_path.clone(), doc_chunks));
+            }
+            processed_docs += 1;
+        }
+        let indexed_docs = chunked.len();
+
+        // Phases 2 and 3: Embedding and storing, in rounds of whole docs that
+        // fill every concurrent request. A round is only stored once all its
+        // batches are embedded, so a failed batch never leaves half a doc.
+        let chunks_total: usize = chunked.iter().map(|(_, chunks)| chunks.len()).sum();
+        let round_size = self.config.embedding.batch_size.max(1)
+            * self.config.embedding.max_concurrent_requests.max(1);
+        let mut chunks_embedded = 0;
+        let mut cache_hits = 0;
+        let mut cache_misses = 0;
+        let mut chunked = chunked.into_iter().peekable();
+        while !cancelled && chunked.peek().is_some() {
+            if cancel.load(Ordering::SeqCst) {
+                cancelled = true;
+                break;
+            }
+            let mut all_chunks = Vec::new();
+            // Docs in this round, and how many chunks each has
+            let mut round_docs = Vec::new();
+            while all_chunks.len() < round_size {
+                let Some((rel_path, chunks)) = chunked.next() else {
+                    break;
+                };
+                round_docs.push((rel_path, chunks.len()));
+                all_chunks.extend(chunks);
             }
 
-            // Phase 2: Embedding
-            on_progress(IndexProgress {
-                phase: "embedding".to_string(),
-                current: batch_idx + 1,
-                total: total_batches,
-                percent: ((batch_idx * 100 + 33) / total_batches.max(1)) as u8,
-                message: Some(format!("正在生成向量 ({} 个文本块)", all_chunks.len())),
-            });
+            // Only chunks the cache doesn't have go to the provider
+            let misses = if force {
+                (0..all_chunks.len()).collect()
+            } else {
+                self.fill_from_cache(&mut all_chunks)
+            };
+            cache_hits += all_chunks.len() - misses.len();
+            cache_misses += misses.len();
+            chunks_embedded += all_chunks.len() - misses.len();
+            let texts: Vec<String> = misses
+                .iter()
+                .map(|&i| self.embedding_text(&all_chunks[i]))
+                .collect();
+            let embedded = self
+                .embedding_client
+                .embed_with_progress(texts, |count| {
+                    chunks_embedded += count;
+                    on_progress(IndexProgress {
+                        phase: "embedding".to_string(),
+                        current: chunks_embedded,
+                        total: chunks_total,
+                        percent: embedding_percent(chunks_embedded, chunks_total),
+                        message_key: "index.progress.embedding",
+                        docs_processed: processed_docs,
+                        docs_total: total_docs,
+                        chunks_stored: total_chunks,
+                        chunks_embedded,
+                        chunks_total,
+                    });
+                })
+                .await;
+            let embeddings = match embedded {
+                Ok(embeddings) => embeddings,
+                Err(e) => return Err(self.record_build_failure(states, round_docs, e)),
+            };
 
-            let texts: Vec<String> = all_chunks.iter().map(|c| c.content.clone()).collect();
-            let embeddings = self.embedding_client.embed(texts).await?;
+            // Attach embeddings to chunks
+            for (&i, embedding) in misses.iter().zip(embeddings.into_iter()) {
+                all_chunks[i].vector = embedding;
+            }
+            self.cache_vectors(misses.iter().map(|&i| &all_chunks[i]));
 
             // After first embedding batch, verify dimensions match and re-init vector store if needed
             if !self.dimensions_verified {
-                self.verify_dimensions().await?;
+                let actual_dim = all_chunks.first().map_or(0, |c| c.vector.len());
+                self.verify_dimensions(actual_dim).await?;
             }
 
-            // Attach embeddings to chunks
-            for (chunk, embedding) in all_chunks.iter_mut().zip(embeddings.into_iter()) {
-                chunk.vector = embedding;
+            // Replace the chunks the round's docs had before they changed
+            let replaced: Vec<String> = round_docs
+                .iter()
+                .filter_map(|(rel_path, _)| indexed.remove_entry(rel_path).map(|(path, _)| path))
+                .collect();
+            if let Err(e) = self.vector_store.delete_by_files(&replaced).await {
+                return Err(self.record_build_failure(states, round_docs, e));
             }
 
-            // Phase 3: Storing
             on_progress(IndexProgress {
                 phase: "storing".to_string(),
-                current: batch_idx + 1,
-                total: total_batches,
-                percent: ((batch_idx * 100 + 66) / total_batches.max(1)) as u8,
-                message: Some("正在写入索引...".to_string()),
+                current: chunks_embedded,
+                total: chunks_total,
+                percent: embedding_percent(chunks_embedded, chunks_total),
+                message_key: "index.progress.storing",
+                docs_processed: processed_docs,
+                docs_total: total_docs,
+                chunks_stored: total_chunks,
+                chunks_embedded,
+                chunks_total,
             });
 
-            let count = self.vector_store.upsert(all_chunks).await?;
+            let count = match self.vector_store.upsert(all_chunks).await {
+                Ok(count) => count,
+                Err(e) => return Err(self.record_build_failure(states, round_docs, e)),
+            };
             total_chunks += count;
+            states.extend(
+                round_docs
+                    .into_iter()
+                    .map(|(rel_path, chunks)| (rel_path, DocIndexState::Indexed { chunks })),
+            );
+        }
+        // Drop docs that are gone, now empty or left out. A cancelled build
+        // hasn't looked at every doc, so it leaves the rest as they were.
+        if !cancelled {
+            let stale: Vec<String> = indexed
+                .into_keys()
+                .filter(|rel_path| !kept.contains(rel_path))
+                .collect();
+            if !stale.is_empty() {
+                log::info!("Dropping {} stale docs from the index", stale.len());
+                self.vector_store.delete_by_files(&stale).await?;
+            }
+            self.prune_embedding_cache();
         }
+        self.doc_status().replace(states);
+        let mut values = serde_json::Map::new();
+        values.insert(
+            BUILD_SETTINGS_KEY.to_string(),
+            serde_json::Value::String(build_settings),
+        );
+        values.insert(
+            EMBEDDING_MODEL_KEY.to_string(),
+            serde_json::json!(self.config.embedding.model),
+        );
+        values.insert(
+            EMBEDDING_DIMENSIONS_KEY.to_string(),
+            serde_json::json!(self.config.embedding.dimensions),
+        );
+        self.write_metadata(values)?;
 
         // Final progress
-        on_progress(IndexProgress {
-            phase: "done".to_string(),
-            current: total_batches,
-            total: total_batches,
-            percent: 100,
-            message: Some(format!(
-                "索引构建完成！共 {} 个文档，{} 个文本块",
-                total_docs, total_chunks
-            )),
-        });
+        if cancelled {
+            on_progress(IndexProgress {
+                phase: "cancelled".to_string(),
+                current: chunks_embedded,
+                total: chunks_total,
+                percent: ((processed_docs * 100) / total_docs.max(1)) as u8,
+                message_key: "index.progress.cancelled",
+                docs_processed: processed_docs,
+                docs_total: total_docs,
+                chunks_stored: total_chunks,
+                chunks_embedded,
+                chunks_total,
+            });
+        } else {
+            on_progress(IndexProgress {
+                phase: "done".to_string(),
+                current: chunks_total,
+                total: chunks_total,
+                percent: 100,
+                message_key: "index.progress.done",
+                docs_processed: processed_docs,
+                docs_total: total_docs,
+                chunks_stored: total_chunks,
+                chunks_embedded,
+                chunks_total,
+            });
+        }
 
         let elapsed_ms = start.elapsed().as_millis() as u64;
 
         Ok(IndexStats {
             total_docs,
+            indexed_docs,
+            skipped_docs,
+            cache_hits,
+            cache_misses,
             total_chunks,
             total_tokens: None,
             elapsed_ms,
@@ -366,25 +961,112 @@ impl Indexer {
                     .unwrap_or_default()
                     .as_millis() as u64,
             ),
+            skipped,
+            cancelled,
         })
     }
 
     /// Index a single file
+    ///
+    /// Uses the embedding profile assigned to the file's folder, if any.
     pub async fn index_file(&mut self, rel_path: &str) -> SearchResult<usize> {
+        match self.config.profile_for_path(rel_path).map(str::to_string) {
+            Some(name) => {
+                self.profile_indexer(&name)
+                    .await?
+                    .index_file_local(rel_path)
+                    .await
+            }
+            None => self.index_file_local(rel_path).await,
+        }
+    }
+
+    /// Re-index one doc in place: drop its chunks, re-chunk and re-embed just
+    /// that file, and refresh `totalChunks` in the index metadata
+    ///
+    /// A doc deleted since it was listed only has its stale chunks removed.
+    pub async fn index_doc(&mut self, doc: &crate::Doc) -> SearchResult<usize> {
+        let rel_path = doc.rel_path.as_str();
+        let count = if self.contexts_root.join(rel_path).exists() {
+            match self.index_file(rel_path).await {
+                Ok(count) => Some(count),
+                // Deleted while it was being read
+                Err(_) if !self.contexts_root.join(rel_path).exists() => None,
+                Err(e) => return Err(e),
+            }
+        } else {
+            None
+        };
+        let count = match count {
+            Some(count) => count,
+            None => {
+                self.remove_file(rel_path).await?;
+                0
+            }
+        };
+
+        let stats = self.get_stats().await?;
+        let mut values = serde_json::Map::new();
+        values.insert(
+            "totalChunks".to_string(),
+            serde_json::json!(stats.total_chunks),
+        );
+        self.write_metadata(values)?;
+        Ok(count)
+    }
+
+    /// Index a single file into this indexer's own index
+    async fn index_file_local(&mut self, rel_path: &str) -> SearchResult<usize> {
         let abs_path = self.contexts_root.join(rel_path);
 
         if !abs_path.exists() {
             return Err(SearchError::Index(format!("File not found: {}", rel_path)));
         }
 
+        let result = self.index_existing_file(rel_path, &abs_path).await;
+        let state = match &result {
+            Ok(state) => state.clone(),
+            Err(e) => DocIndexState::Failed {
+                error: e.to_string(),
+            },
+        };
+        self.doc_status()
+            .record(vec![(rel_path.to_string(), state)]);
+        result.map(|state| state.chunks())
+    }
+
+    async fn index_existing_file(
+        &mut self,
+        rel_path: &str,
+        abs_path: &Path,
+    ) -> SearchResult<DocIndexState> {
         // Remove existing chunks for this file
-        self.vector_store.delete_by_file(rel_path).await?;
+        self.vector_store.delete_by_doc(rel_path).await?;
+        let settings = SettingsResolver::new(&self.contexts_root).doc_or_closed(rel_path);
+        if settings.index_priority() == IndexPriority::Skip {
+            return Ok(DocIndexState::Skipped {
+                reason: "excluded by folder settings".to_string(),
+            });
+        }
+        if !settings.searchable(crate::crypto::is_unlocked(&self.contexts_root)) {
+            return Ok(DocIndexState::Skipped {
+                reason: "in an encrypted folder".to_string(),
+            });
+        }
 
         // Read and chunk the document
-        let content = std::fs::read_to_string(&abs_path)?;
+        let (content, markdown) = match read_doc_text(&self.contexts_root, rel_path, abs_path)? {
+            DocText::Text { content, markdown } => (content, markdown),
+            DocText::Skipped(reason) => {
+                log::info!("Not indexing {}: {}", rel_path, reason);
+                return Ok(DocIndexState::Skipped { reason });
+            }
+        };
         if content.trim().is_empty() {
-            return Ok(0);
+            return Ok(DocIndexState::Empty);
         }
+        let doc_modified_at = modified_ms(abs_path);
+        let doc_hash = content_hash(&content);
 
         let mut chunks = Vec::new();
 
@@ -406,6 +1088,7 @@ impl Indexer {
                     file_path: rel_path.to_string(),
                     content: entry.content,
                     heading_path: String::new(),
+                    headings: Vec::new(),
                     section_title: if title_line.is_empty() {
                         None
                     } else {
@@ -420,12 +1103,23 @@ impl Indexer {
                     },
                     entry_created_at: Some(entry.created_at),
                     idea_box: idea_box.clone(),
+                    doc_modified_at,
+                    doc_hash: Some(doc_hash.clone()),
                     chunk_index: i,
+                    line_start: None,
+                    line_end: None,
+                    byte_start: None,
+                    byte_end: None,
+                    code_language: None,
                     vector: vec![],
                 });
             }
         } else {
-            let text_chunks = self.chunker.chunk(&content, rel_path);
+            let text_chunks = if markdown {
+                self.chunker.chunk(&content, rel_path)
+            } else {
+                self.chunker.chunk_plain(&content)
+            };
             for (i, text_chunk) in text_chunks.into_iter().enumerate() {
                 let id = format!("{}#{}", rel_path, i);
                 chunks.push(Chunk {
@@ -433,49 +1127,185 @@ impl Indexer {
                     file_path: rel_path.to_string(),
                     content: text_chunk.content,
                     heading_path: text_chunk.heading_path,
+                    headings: text_chunk.headings,
                     section_title: None,
                     doc_type: Some("doc".to_string()),
                     entry_id: None,
                     entry_date: None,
                     entry_created_at: None,
                     idea_box: None,
+                    doc_modified_at,
+                    doc_hash: Some(doc_hash.clone()),
                     chunk_index: i,
+                    line_start: Some(text_chunk.start_line),
+                    line_end: Some(text_chunk.end_line),
+                    byte_start: Some(text_chunk.start_byte),
+                    byte_end: Some(text_chunk.end_byte),
+                    code_language: text_chunk.code_language,
                     vector: vec![],
                 });
             }
         }
 
         if chunks.is_empty() {
-            return Ok(0);
+            return Ok(DocIndexState::Empty);
         }
 
-        // Generate embeddings
-        let texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+        // Generate embeddings for chunks the cache doesn't have
+        let misses = self.fill_from_cache(&mut chunks);
+        let texts: Vec<String> = misses
+            .iter()
+            .map(|&i| self.embedding_text(&chunks[i]))
+            .collect();
         let embeddings = self.embedding_client.embed(texts).await?;
+        for (&i, embedding) in misses.iter().zip(embeddings.into_iter()) {
+            chunks[i].vector = embedding;
+        }
+        self.cache_vectors(misses.iter().map(|&i| &chunks[i]));
 
         // Verify dimensions after getting embeddings
         if !self.dimensions_verified {
-            self.verify_dimensions().await?;
-        }
-
-        for (chunk, embedding) in chunks.iter_mut().zip(embeddings.into_iter()) {
-            chunk.vector = embedding;
+            self.verify_dimensions(chunks[0].vector.len()).await?;
         }
 
         // Store
         let count = self.vector_store.upsert(chunks).await?;
-        Ok(count)
+        Ok(DocIndexState::Indexed { chunks: count })
     }
 
-    /// Remove a file from the index
+    /// The text embedded for `chunk`: its content, after its heading path
+    /// when `include_headings` is set
+    fn embedding_text(&self, chunk: &Chunk) -> String {
+        if self.config.embedding.include_headings && !chunk.headings.is_empty() {
+            format!("{}\n\n{}", chunk.headings.join(" > "), chunk.content)
+        } else {
+            chunk.content.clone()
+        }
+    }
+
+    /// Fill in the vectors of `chunks` the embedding cache has; returns the
+    /// indices of the chunks still to embed
+    fn fill_from_cache(&self, chunks: &mut [Chunk]) -> Vec<usize> {
+        let Some(cache) = &self.embedding_cache else {
+            return (0..chunks.len()).collect();
+        };
+        let keys: Vec<String> = chunks
+            .iter()
+            .map(|c| chunk_key(&self.embedding_text(c)))
+            .collect();
+        let cached = match cache.get_many(&keys) {
+            Ok(cached) => cached,
+            Err(e) => {
+                log::warn!("{}", e);
+                HashMap::new()
+            }
+        };
+        let mut misses = Vec::new();
+        for (i, (chunk, key)) in chunks.iter_mut().zip(&keys).enumerate() {
+            // A chunk repeated in the batch takes the vector each time
+            match cached.get(key) {
+                Some(vector) => chunk.vector = vector.clone(),
+                None => misses.push(i),
+            }
+        }
+        misses
+    }
+
+    /// Remember the vectors of freshly embedded `chunks`
+    fn cache_vectors<'a>(&self, chunks: impl Iterator<Item = &'a Chunk>) {
+        let Some(cache) = &self.embedding_cache else {
+            return;
+        };
+        let entries: Vec<(String, &[f32])> = chunks
+            .map(|c| (chunk_key(&self.embedding_text(c)), c.vector.as_slice()))
+            .collect();
+        let stored = cache.put_many(entries.iter().map(|(key, vector)| (key.as_str(), *vector)));
+        if let Err(e) = stored {
+            log::warn!("{}", e);
+        }
+    }
+
+    /// Keep the embedding cache to `MAX_ENTRIES` vectors
+    fn prune_embedding_cache(&self) {
+        let Some(cache) = &self.embedding_cache else {
+            return;
+        };
+        match cache.prune(MAX_ENTRIES) {
+            Ok(0) => {}
+            Ok(pruned) => log::info!("Dropped {} old vectors from the embedding cache", pruned),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+
+    /// Record how far a failed build got: `states` for the docs before the
+    /// failing round, and the error for the round's docs. Returns `error`.
+    fn record_build_failure(
+        &self,
+        mut states: Vec<(String, DocIndexState)>,
+        round_docs: Vec<(String, usize)>,
+        error: SearchError,
+    ) -> SearchError {
+        states.extend(round_docs.into_iter().map(|(rel_path, _)| {
+            (
+                rel_path,
+                DocIndexState::Failed {
+                    error: error.to_string(),
+                },
+            )
+        }));
+        self.doc_status().replace(states);
+        error
+    }
+
+    /// Remove a file from the index it was built into
     pub async fn remove_file(&mut self, rel_path: &str) -> SearchResult<()> {
-        self.vector_store.delete_by_file(rel_path).await?;
+        let indexer = match self.config.profile_for_path(rel_path).map(str::to_string) {
+            Some(name) => self.profile_indexer(&name).await?,
+            None => self,
+        };
+        indexer.vector_store.delete_by_doc(rel_path).await?;
+        indexer.doc_status().remove(rel_path);
         Ok(())
     }
 
-    /// Update file path (for rename/move operations)
-    pub async fn update_file_path(&mut self, old_path: &str, new_path: &str) -> SearchResult<()> {
-        // For now, we simply remove old and re-index new
+    /// Remove files from the indexes and purge their chunks from the index
+    /// files, so no older version of the table still holds their text
+    pub async fn purge_files(&mut self, rel_paths: &[String]) -> SearchResult<()> {
+        for rel_path in rel_paths {
+            self.remove_file(rel_path).await?;
+        }
+        for name in self.config.assigned_profiles() {
+            let indexer = self.profile_indexer(&name).await?;
+            indexer.vector_store.purge_deleted().await?;
+        }
+        self.vector_store.purge_deleted().await
+    }
+
+    /// Follow a doc renamed or moved from `old_path` to `new_path`
+    ///
+    /// Stored chunks are repointed in place, so no re-embedding is needed.
+    /// Moves that change what the chunks derive from the path (the idea box,
+    /// or the embedding profile of the target folder) are re-indexed instead.
+    pub async fn rename_doc_path(&mut self, old_path: &str, new_path: &str) -> SearchResult<()> {
+        let old_profile = self.config.profile_for_path(old_path).map(str::to_string);
+        let new_profile = self.config.profile_for_path(new_path).map(str::to_string);
+        let is_idea = old_path.starts_with(".ideas/") || new_path.starts_with(".ideas/");
+        if old_profile == new_profile && !is_idea {
+            let indexer = match old_profile {
+                Some(name) => self.profile_indexer(&name).await?,
+                None => self,
+            };
+            // Chunks a doc deleted from the new path left behind would
+            // otherwise turn up beside the moved ones
+            indexer.vector_store.delete_by_doc(new_path).await?;
+            indexer
+                .vector_store
+                .update_file_path(old_path, new_path)
+                .await?;
+            indexer.doc_status().rename(old_path, new_path);
+            return Ok(());
+        }
+
         self.remove_file(old_path).await?;
 
         let abs_path = self.contexts_root.join(new_path);
@@ -486,42 +1316,243 @@ impl Indexer {
         Ok(())
     }
 
+    /// Repoint the chunks of every doc under folder `old_prefix` at
+    /// `new_prefix` in one update, without re-embedding
+    ///
+    /// Returns false, changing nothing, when the move could change the
+    /// embedding profile of docs inside; rename those one by one with
+    /// `rename_doc_path`, which re-indexes them where needed.
+    pub async fn rename_folder_path(
+        &mut self,
+        old_prefix: &str,
+        new_prefix: &str,
+    ) -> SearchResult<bool> {
+        let within = |path: &str, folder: &str| {
+            path == folder
+                || path
+                    .strip_prefix(folder)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+        let assigned_inside = self.config.folder_profiles.keys().any(|folder| {
+            let folder = folder.trim_matches('/');
+            within(folder, old_prefix) || within(folder, new_prefix)
+        });
+        let is_idea = within(old_prefix, ".ideas") || within(new_prefix, ".ideas");
+        let old_profile = self.config.profile_for_path(old_prefix).map(str::to_string);
+        if assigned_inside
+            || is_idea
+            || old_profile.as_deref() != self.config.profile_for_path(new_prefix)
+        {
+            return Ok(false);
+        }
+
+        let indexer = match old_profile {
+            Some(name) => self.profile_indexer(&name).await?,
+            None => self,
+        };
+        indexer
+            .vector_store
+            .rename_path_prefix(old_prefix, new_prefix)
+            .await?;
+        indexer.doc_status().rename_prefix(old_prefix, new_prefix);
+        Ok(true)
+    }
+
+    /// The chunks stored for a doc and the outcome of the last attempt to
+    /// index it, from the index its folder's embedding profile uses
+    pub async fn inspect_doc(&mut self, rel_path: &str) -> SearchResult<DocIndexInspection> {
+        let profile = self.config.profile_for_path(rel_path).map(str::to_string);
+        let indexer = match &profile {
+            Some(name) => self.profile_indexer(name).await?,
+            None => self,
+        };
+        Ok(DocIndexInspection {
+            path: rel_path.to_string(),
+            chunks: indexer.vector_store.chunks_for_file(rel_path).await?,
+            status: indexer.doc_status().get(rel_path),
+            profile,
+        })
+    }
+
     /// Check if index exists
     pub async fn index_exists(&self) -> bool {
         self.vector_store.exists().await
     }
 
+    /// Whether the existing index was built with other chunking or
+    /// embedding settings than the current ones. Until a full rebuild
+    /// re-chunks every doc, its hits come from the old settings.
+    pub async fn needs_rebuild(&self) -> bool {
+        self.index_exists().await
+            && self
+                .stored_build_settings()
+                .is_some_and(|stored| stored != self.build_settings())
+    }
+
     /// Get index statistics
     pub async fn get_stats(&self) -> SearchResult<IndexStats> {
         let count = self.vector_store.count().await?;
 
         // Read lastUpdated from metadata file
         let metadata_path = self.config.paths.get_index_metadata_path();
-        let last_updated = if metadata_path.exists() {
+        let metadata = if metadata_path.exists() {
             std::fs::read_to_string(&metadata_path)
                 .ok()
                 .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
-                .and_then(|v| v.get("lastUpdated").and_then(|v| v.as_u64()))
         } else {
             None
         };
+        let last_updated = metadata
+            .as_ref()
+            .and_then(|v| v.get("lastUpdated").and_then(|v| v.as_u64()));
+        // Recorded by the app after a full build
+        let skipped = metadata
+            .and_then(|v| v.get("skipped").cloned())
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
 
         Ok(IndexStats {
             total_docs: 0, // We don't track this separately
+            indexed_docs: 0,
+            skipped_docs: 0,
+            cache_hits: 0,
+            cache_misses: 0,
             total_chunks: count,
             total_tokens: None,
             elapsed_ms: 0,
             last_updated,
+            skipped,
+            cancelled: false,
         })
     }
 
-    /// Clean the index
+    /// Where each of `rel_paths` stands in the index. Each index involved
+    /// is scanned once, however many docs are asked about.
+    pub async fn doc_summaries(
+        &mut self,
+        rel_paths: &[String],
+    ) -> SearchResult<Vec<DocIndexSummary>> {
+        let mut indexes: HashMap<Option<String>, HashMap<String, IndexedDoc>> = HashMap::new();
+        for rel_path in rel_paths {
+            let profile = self.config.profile_for_path(rel_path).map(str::to_string);
+            if indexes.contains_key(&profile) {
+                continue;
+            }
+            let indexed = match &profile {
+                Some(name) => {
+                    self.profile_indexer(name)
+                        .await?
+                        .vector_store
+                        .indexed_docs()
+                        .await?
+                }
+                None => self.vector_store.indexed_docs().await?,
+            };
+            indexes.insert(profile, indexed);
+        }
+
+        Ok(rel_paths
+            .iter()
+            .map(|rel_path| {
+                let profile = self.config.profile_for_path(rel_path).map(str::to_string);
+                let doc = indexes.get(&profile).and_then(|docs| docs.get(rel_path));
+                let indexed_modified_at = doc.and_then(|doc| doc.doc_modified_at);
+                let stale = doc.is_some_and(|doc| self.changed_since_indexed(rel_path, doc));
+                DocIndexSummary {
+                    path: rel_path.clone(),
+                    indexed: doc.is_some(),
+                    chunks: doc.map_or(0, |doc| doc.chunks),
+                    stale,
+                    indexed_modified_at,
+                }
+            })
+            .collect())
+    }
+
+    /// Whether the doc at `rel_path` changed since it was indexed as
+    /// `indexed`: touched since, and its text no longer hashes the same
+    fn changed_since_indexed(&self, rel_path: &str, indexed: &IndexedDoc) -> bool {
+        let Some(indexed_at) = indexed.doc_modified_at else {
+            return false;
+        };
+        let abs_path = self.contexts_root.join(rel_path);
+        if !modified_ms(&abs_path).is_some_and(|modified| modified > indexed_at) {
+            return false;
+        }
+        match (
+            &indexed.doc_hash,
+            read_doc_text(&self.contexts_root, rel_path, &abs_path),
+        ) {
+            (Some(hash), Ok(DocText::Text { content, .. })) => content_hash(&content) != *hash,
+            _ => true,
+        }
+    }
+
+    /// Oldest and newest doc modified times in the default index, as
+    /// recorded when each doc was last indexed (ms since epoch)
+    pub async fn doc_modified_range(&self) -> SearchResult<Option<(u64, u64)>> {
+        self.vector_store.doc_modified_range().await
+    }
+
+    /// Compare `docs` with what the default and profile indexes hold. A
+    /// doc counts as stale by the same test as in [`Self::doc_summaries`].
+    pub async fn coverage(&mut self, docs: &[crate::Doc]) -> SearchResult<IndexCoverage> {
+        let mut indexed = self.vector_store.indexed_docs().await?;
+        for name in self.config.assigned_profiles() {
+            let profile = self.profile_indexer(&name).await?;
+            indexed.extend(profile.vector_store.indexed_docs().await?);
+        }
+
+        let mut resolver = SettingsResolver::new(&self.contexts_root);
+        let unlocked = crate::crypto::is_unlocked(&self.contexts_root);
+        let mut coverage = IndexCoverage::default();
+        for doc in docs {
+            let settings = resolver.doc_or_closed(&doc.rel_path);
+            if doc.kind.skip_reason().is_some() {
+                coverage.unsearchable += 1;
+            } else if settings.index_priority() == IndexPriority::Skip
+                || !settings.searchable(unlocked)
+            {
+                coverage.excluded += 1;
+            } else {
+                match indexed.get(&doc.rel_path) {
+                    None => coverage.missing += 1,
+                    Some(indexed) if self.changed_since_indexed(&doc.rel_path, indexed) => {
+                        coverage.stale += 1
+                    }
+                    Some(_) => coverage.indexed += 1,
+                }
+            }
+        }
+        Ok(coverage)
+    }
+
+    /// Clean the index, including folder-assigned profile indexes
     pub async fn clean(&mut self) -> SearchResult<()> {
+        for name in self.config.assigned_profiles() {
+            let indexer = self.profile_indexer(&name).await?;
+            indexer.vector_store.reset().await?;
+            indexer.doc_status().replace(vec![]);
+        }
+        self.doc_status().replace(vec![]);
         self.vector_store.reset().await
     }
 
     /// Update index metadata with current timestamp
+    ///
+    /// Profile indexes touched by this indexer are updated as well.
     pub fn update_metadata(&self) -> SearchResult<()> {
+        self.write_metadata(serde_json::Map::new())
+    }
+
+    /// Update the metadata timestamp and set `values` alongside it
+    fn write_metadata(
+        &self,
+        values: serde_json::Map<String, serde_json::Value>,
+    ) -> SearchResult<()> {
+        for indexer in self.profile_indexers.values() {
+            indexer.update_metadata()?;
+        }
         let metadata_path = self.config.paths.get_index_metadata_path();
 
         // Read existing metadata or create new
@@ -541,6 +1572,9 @@ impl Indexer {
             .as_millis() as u64;
 
         metadata["lastUpdated"] = serde_json::json!(now);
+        for (key, value) in values {
+            metadata[key.as_str()] = value;
+        }
 
         // Ensure directory exists
         if let Some(parent) = metadata_path.parent() {
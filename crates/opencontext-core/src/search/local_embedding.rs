@@ -0,0 +1,56 @@
+//! In-process embeddings with fastembed, for search without an embedding API
+
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use std::path::PathBuf;
+
+use super::error::{SearchError, SearchResult};
+
+/// A fastembed model loaded into memory
+pub struct LocalEmbedder {
+    model: TextEmbedding,
+}
+
+impl LocalEmbedder {
+    /// Load `model`, given by its Hugging Face name such as
+    /// `BAAI/bge-small-en-v1.5`, downloading it into `cache_dir` the first
+    /// time. Blocks; call it off the async runtime.
+    pub fn load(model: &str, cache_dir: PathBuf) -> SearchResult<Self> {
+        let model = find_model(model)?;
+        log::info!("[LocalEmbedding] Loading {:?}", model);
+        let model = TextEmbedding::try_new(
+            InitOptions::new(model)
+                .with_cache_dir(cache_dir)
+                .with_show_download_progress(false),
+        )
+        .map_err(|e| SearchError::Embedding(format!("Failed to load local model: {}", e)))?;
+        Ok(Self { model })
+    }
+
+    /// Embeddings of `texts`, in order. Blocks; call it off the async
+    /// runtime.
+    pub fn embed(&self, texts: Vec<String>) -> SearchResult<Vec<Vec<f32>>> {
+        self.model
+            .embed(texts, None)
+            .map_err(|e| SearchError::Embedding(e.to_string()))
+    }
+}
+
+/// The supported model named `name`, ignoring case
+fn find_model(name: &str) -> SearchResult<EmbeddingModel> {
+    let supported = TextEmbedding::list_supported_models();
+    supported
+        .iter()
+        .find(|info| info.model_code.eq_ignore_ascii_case(name.trim()))
+        .map(|info| info.model.clone())
+        .ok_or_else(|| {
+            let names: Vec<&str> = supported
+                .iter()
+                .map(|info| info.model_code.as_str())
+                .collect();
+            SearchError::Config(format!(
+                "Unknown local embedding model '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            ))
+        })
+}
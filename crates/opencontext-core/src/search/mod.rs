@@ -25,23 +25,38 @@
 
 mod chunker;
 mod config;
+mod doc_status;
 mod embedding;
+mod embedding_cache;
 mod error;
+mod highlight;
 mod index_sync;
 mod indexer;
+#[cfg(feature = "local-embeddings")]
+mod local_embedding;
 mod searcher;
 mod types;
+mod usage;
 mod vector_store;
 
 #[cfg(test)]
 mod tests;
 
 pub use chunker::Chunker;
-pub use config::{EmbeddingConfig, SearchConfig};
+pub use config::{
+    ChunkStrategy, ChunkingConfig, ConfigIssue, ConfigIssueSeverity, EmbeddingConfig,
+    EmbeddingProfile, EmbeddingProvider, SearchConfig,
+};
+pub use doc_status::{DocIndexState, DocIndexStatus};
 pub use embedding::EmbeddingClient;
 pub use error::{SearchError, SearchResult};
+pub use highlight::{SNIPPET_MARK_END, SNIPPET_MARK_START};
 pub use index_sync::IndexSyncService;
-pub use indexer::{IndexProgress, IndexStats, Indexer};
+pub use indexer::{
+    DocIndexInspection, DocIndexSummary, IndexCoverage, IndexProgress, IndexStats, Indexer,
+    SkippedDoc,
+};
 pub use searcher::Searcher;
 pub use types::*;
-pub use vector_store::VectorStore;
+pub use usage::{EmbeddingUsage, ModelUsage, UsageLedger, UsagePeriod, UsageTotals};
+pub use vector_store::{IndexedDoc, VectorStore};
@@ -2,30 +2,41 @@
 //! Aligned with Node.js searcher.js implementation
 
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use super::config::SearchConfig;
+use tokio::sync::Mutex;
+
+use super::config::{EmbeddingProvider, SearchConfig};
 use super::embedding::EmbeddingClient;
-use super::error::SearchResult;
-use super::types::{AggregateBy, MatchType, SearchHit, SearchMode, SearchOptions, SearchResults};
+use super::error::{SearchError, SearchResult};
+use super::highlight;
+use super::indexer::{EMBEDDING_DIMENSIONS_KEY, EMBEDDING_MODEL_KEY};
+use super::types::{
+    AggregateBy, DocGroup, MatchType, SearchHit, SearchMode, SearchOptions, SearchResults,
+};
+use super::usage::UsageLedger;
 use super::vector_store::VectorStore;
 
 /// RRF constant, typically 60
 const RRF_K: f32 = 60.0;
 
-/// Vector search weight in hybrid mode
-const VECTOR_WEIGHT: f32 = 0.7;
-
-/// Keyword search weight in hybrid mode  
-const KEYWORD_WEIGHT: f32 = 0.3;
+/// Most query variants searched alongside the query
+const MAX_QUERY_VARIANTS: usize = 3;
 
 /// Search executor
 pub struct Searcher {
-    #[allow(dead_code)]
     config: SearchConfig,
     vector_store: VectorStore,
-    embedding_client: EmbeddingClient,
+    /// `None` without an embedding API key; every search is then a keyword
+    /// search over the stored chunks
+    embedding_client: Option<EmbeddingClient>,
     /// All chunks for keyword search (loaded on init)
     all_chunks: Vec<SearchHit>,
+    /// Searchers for named embedding profiles, created on first use
+    profile_searchers: Mutex<HashMap<String, Arc<Searcher>>>,
+    /// Where docs live; when set, hits on docs missing from disk are dropped
+    contexts_root: Option<PathBuf>,
 }
 
 impl Searcher {
@@ -37,7 +48,22 @@ impl Searcher {
         let mut vector_store = VectorStore::new(lancedb_path, dimensions);
         vector_store.initialize().await?;
 
-        let embedding_client = EmbeddingClient::new(config.embedding.clone())?;
+        let embedding_client = match EmbeddingClient::new(config.embedding.clone()) {
+            Ok(client) => Some(
+                client
+                    .with_usage_ledger(UsageLedger::new(config.paths.get_usage_ledger_path()))
+                    .with_models_path(config.paths.get_models_path()),
+            ),
+            Err(SearchError::ApiKeyMissing) => {
+                log::info!("[Search] No embedding API key, searching by keyword only");
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        // Keyword-only search doesn't compare vectors
+        if embedding_client.is_some() {
+            Self::check_index_model(&config)?;
+        }
 
         // Load all chunks for keyword search
         let all_chunks = vector_store.get_all_chunks().await.unwrap_or_default();
@@ -47,11 +73,133 @@ impl Searcher {
             vector_store,
             embedding_client,
             all_chunks,
+            profile_searchers: Mutex::new(HashMap::new()),
+            contexts_root: None,
         })
     }
 
+    /// Fail if the index was built for another embedding model, or another
+    /// vector size of an OpenAI model, than `config` queries it with
+    ///
+    /// Ollama and local models aren't sent a size, so only their model is
+    /// compared. Indexes built before the model was recorded aren't checked.
+    fn check_index_model(config: &SearchConfig) -> SearchResult<()> {
+        let Some(metadata) = std::fs::read_to_string(config.paths.get_index_metadata_path())
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        else {
+            return Ok(());
+        };
+        let Some(index_model) = metadata[EMBEDDING_MODEL_KEY].as_str() else {
+            return Ok(());
+        };
+        let embedding = &config.embedding;
+        let index_dimensions = metadata[EMBEDDING_DIMENSIONS_KEY]
+            .as_u64()
+            .map_or(embedding.dimensions, |dimensions| dimensions as usize);
+        let dimensions_differ = embedding.provider == EmbeddingProvider::OpenAI
+            && index_dimensions != embedding.dimensions;
+        if index_model != embedding.model || dimensions_differ {
+            return Err(SearchError::IndexModelMismatch {
+                index_model: index_model.to_string(),
+                index_dimensions,
+                model: embedding.model.clone(),
+                dimensions: embedding.dimensions,
+            });
+        }
+        Ok(())
+    }
+
+    /// Drop hits on docs no longer under `contexts_root`, in case the index
+    /// still holds chunks of a doc deleted or moved outside the app
+    pub fn with_contexts_root(mut self, contexts_root: PathBuf) -> Self {
+        self.contexts_root = Some(contexts_root);
+        self
+    }
+
+    /// Point the keyword snapshot at the new paths of docs under a renamed
+    /// or moved folder. Profile searchers are dropped and reload on next use.
+    pub fn rename_path_prefix(&mut self, old_prefix: &str, new_prefix: &str) {
+        let rebase = |path: &mut String| {
+            let rest = match path.strip_prefix(old_prefix) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+                _ => return,
+            };
+            *path = format!("{}{}", new_prefix, rest);
+        };
+        for hit in &mut self.all_chunks {
+            rebase(&mut hit.file_path);
+            if let Some(folder_path) = hit.folder_path.as_mut() {
+                rebase(folder_path);
+            }
+        }
+        self.profile_searchers.get_mut().clear();
+    }
+
+    /// Get the searcher for a named embedding profile
+    ///
+    /// Fails if the profile is unknown or its index has not been built. With
+    /// `no_cache` a fresh searcher is built and not kept.
+    async fn profile_searcher(&self, name: &str, no_cache: bool) -> SearchResult<Arc<Searcher>> {
+        let mut searchers = self.profile_searchers.lock().await;
+        if !no_cache {
+            if let Some(searcher) = searchers.get(name) {
+                return Ok(searcher.clone());
+            }
+        }
+
+        let config = self.config.with_profile(name)?;
+        let mut searcher = Searcher::new(config).await?;
+        searcher.contexts_root = self.contexts_root.clone();
+        if !searcher.vector_store.exists().await {
+            return Err(SearchError::Index(format!(
+                "No index built for embedding profile '{}'",
+                name
+            )));
+        }
+
+        let searcher = Arc::new(searcher);
+        if !no_cache {
+            searchers.insert(name.to_string(), searcher.clone());
+        }
+        Ok(searcher)
+    }
+
     /// Execute a search
+    ///
+    /// Queries the named embedding profile's index when `embedding_profile` is set,
+    /// otherwise the profile assigned to `folder_prefix` (if any). Without
+    /// either, the main index and the indexes of all assigned profiles are
+    /// searched and their hits ranked together.
     pub async fn search(&self, options: SearchOptions) -> SearchResult<SearchResults> {
+        let profile = options
+            .embedding_profile
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .or_else(|| {
+                options
+                    .folder_prefix
+                    .as_deref()
+                    .and_then(|folder| self.config.profile_for_path(folder))
+            })
+            .map(str::to_string);
+        match profile {
+            Some(name) => {
+                let searcher = self.profile_searcher(&name, options.no_cache).await?;
+                searcher.search_index(options, false).await
+            }
+            None => self.search_index(options, true).await,
+        }
+    }
+
+    /// Execute a search against this searcher's own index, and with
+    /// `with_profiles` also the built indexes of the assigned profiles
+    async fn search_index(
+        &self,
+        options: SearchOptions,
+        with_profiles: bool,
+    ) -> SearchResult<SearchResults> {
         let query = options.query.trim();
 
         if query.is_empty() {
@@ -64,22 +212,69 @@ impl Searcher {
         }
 
         let limit = options.limit();
-        let mode = options.mode();
+        let mode = self.mode_for(&options);
         let aggregate_by = options.aggregate_by();
+        let variants = Self::query_variants(query, &options.query_variants);
+        let folder = options
+            .folder_prefix
+            .as_deref()
+            .map(|f| f.trim_matches('/').to_lowercase())
+            .filter(|f| !f.is_empty());
+        let folder = folder.as_deref();
+
+        // Everything up to the end of the requested page, with a page to
+        // spare in case results moved since the cursor was handed out
+        let cursor = options.cursor.as_deref().and_then(PageCursor::decode);
+        let fetch_limit = cursor
+            .as_ref()
+            .map_or(0, |cursor| cursor.offset)
+            .saturating_add(limit.saturating_mul(2));
 
         // For aggregation, get more candidates
-        let search_limit = if aggregate_by == AggregateBy::Content {
-            limit
+        let search_limit = if aggregate_by == AggregateBy::Content && !options.group_by_doc {
+            fetch_limit
         } else {
-            limit * 5
+            fetch_limit.saturating_mul(5)
         };
 
-        // Execute search based on mode
-        let mut hits = match mode {
-            SearchMode::Vector => self.vector_search(query, search_limit).await?,
-            SearchMode::Keyword => self.keyword_search(query, search_limit),
-            SearchMode::Hybrid => self.hybrid_search(query, search_limit).await?,
-        };
+        let mut hits = self
+            .ranked_hits(query, &variants, search_limit, folder, &options)
+            .await?;
+        if with_profiles {
+            for name in self.config.assigned_profiles() {
+                // A profile whose index isn't built yet has nothing to add
+                let searcher = match self.profile_searcher(&name, options.no_cache).await {
+                    Ok(searcher) => searcher,
+                    Err(e) => {
+                        log::debug!("[Search] Skipping profile '{}': {}", name, e);
+                        continue;
+                    }
+                };
+                hits.extend(
+                    searcher
+                        .ranked_hits(query, &variants, search_limit, folder, &options)
+                        .await?,
+                );
+            }
+            hits.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+
+        // The index may lag behind the disk; hits on docs that are gone
+        // are dropped and counted
+        let mut stale_hits_dropped = None;
+        if let Some(root) = &self.contexts_root {
+            let before = hits.len();
+            hits.retain(|hit| root.join(&hit.file_path).exists());
+            let dropped = before - hits.len();
+            if dropped > 0 {
+                log::warn!("[Search] Dropped {} hits on missing docs", dropped);
+                stale_hits_dropped = Some(dropped);
+            }
+        }
 
         if let Some(filter_type) = options.doc_type.as_deref() {
             hits.retain(|hit| match filter_type {
@@ -89,13 +284,58 @@ impl Searcher {
             });
         }
 
-        // Aggregate results
-        let results = match aggregate_by {
-            AggregateBy::Content => hits.into_iter().take(limit).collect(),
-            AggregateBy::Doc => self.aggregate_by_doc(hits, limit),
-            AggregateBy::Folder => self.aggregate_by_folder(hits, limit),
+        // Before the limit, so a strict threshold may leave fewer hits
+        let no_confident_match = match options.min_score {
+            Some(min_score) if mode != SearchMode::Keyword => {
+                Self::drop_weak_matches(&mut hits, min_score)
+            }
+            _ => None,
         };
 
+        // Aggregate or group before cutting the page, so a doc's chunks are
+        // never split across pages
+        let (mut results, mut groups, next_cursor, total_estimate) = if options.group_by_doc {
+            let ranked = self.group_by_doc(hits, options.max_chunks_per_doc(), fetch_limit);
+            let (groups, next_cursor, total_estimate) = page(
+                ranked,
+                cursor,
+                limit,
+                |group: &DocGroup| group.file_path.clone(),
+                |_, _| false,
+            );
+            (Vec::new(), Some(groups), next_cursor, total_estimate)
+        } else {
+            let ranked: Vec<SearchHit> = match aggregate_by {
+                AggregateBy::Content => {
+                    cluster_by_doc(hits).into_iter().take(fetch_limit).collect()
+                }
+                AggregateBy::Doc => self.aggregate_by_doc(hits, fetch_limit),
+                AggregateBy::Folder => self.aggregate_by_folder(hits, fetch_limit),
+            };
+            let same_doc = |a: &SearchHit, b: &SearchHit| {
+                aggregate_by == AggregateBy::Content && a.file_path == b.file_path
+            };
+            let (results, next_cursor, total_estimate) =
+                page(ranked, cursor, limit, page_key, same_doc);
+            (results, None, next_cursor, total_estimate)
+        };
+
+        // Only the hits returned are highlighted, once ranking is done
+        let grouped_hits = groups
+            .iter_mut()
+            .flatten()
+            .flat_map(|group| group.hits.iter_mut());
+        for hit in results.iter_mut().chain(grouped_hits) {
+            hit.highlights = highlight::highlights(&hit.content, query);
+            hit.snippet = Some(highlight::snippet(&hit.content, &hit.highlights));
+        }
+        if let Some(groups) = &groups {
+            results = groups
+                .iter()
+                .flat_map(|group| group.hits.iter().cloned())
+                .collect();
+        }
+
         // Convert mode and aggregate_by to strings for response
         let mode_str = match mode {
             SearchMode::Vector => "vector",
@@ -103,6 +343,7 @@ impl Searcher {
             SearchMode::Hybrid => "hybrid",
         };
         let aggregate_str = match aggregate_by {
+            _ if options.group_by_doc => "content",
             AggregateBy::Content => "content",
             AggregateBy::Doc => "doc",
             AggregateBy::Folder => "folder",
@@ -116,16 +357,113 @@ impl Searcher {
             aggregate_by: Some(aggregate_str.to_string()),
             index_missing: None,
             error: None,
+            rerank_warning: None,
+            expanded_queries: if mode == SearchMode::Keyword {
+                Vec::new()
+            } else {
+                variants
+            },
+            no_confident_match,
+            stale_hits_dropped,
+            groups,
+            next_cursor,
+            total_estimate: Some(total_estimate),
         })
     }
 
-    /// Perform vector search
-    async fn vector_search(&self, query: &str, limit: usize) -> SearchResult<Vec<SearchHit>> {
+    /// The search mode `options` asks for, falling back to keyword search
+    /// without an embedding client
+    fn mode_for(&self, options: &SearchOptions) -> SearchMode {
+        if self.embedding_client.is_some() {
+            options.mode()
+        } else {
+            SearchMode::Keyword
+        }
+    }
+
+    /// Hits of this searcher's index for `query`, best first
+    async fn ranked_hits(
+        &self,
+        query: &str,
+        variants: &[String],
+        search_limit: usize,
+        folder: Option<&str>,
+        options: &SearchOptions,
+    ) -> SearchResult<Vec<SearchHit>> {
+        let mode = self.mode_for(options);
+
+        // Keyword matching normally uses the chunk snapshot loaded at startup;
+        // `no_cache` reads the store instead, without replacing the snapshot.
+        let fresh_chunks;
+        let chunks: &[SearchHit] = if options.no_cache && mode != SearchMode::Vector {
+            fresh_chunks = self.vector_store.get_all_chunks().await?;
+            &fresh_chunks
+        } else {
+            &self.all_chunks
+        };
+        // Keyword matching only sees the folder's chunks, as the vector
+        // scan does
+        let folder_chunks: Vec<SearchHit>;
+        let chunks = match folder {
+            Some(folder) if mode != SearchMode::Vector => {
+                folder_chunks = chunks
+                    .iter()
+                    .filter(|chunk| in_folder(&chunk.file_path, folder))
+                    .cloned()
+                    .collect();
+                &folder_chunks
+            }
+            _ => chunks,
+        };
+
+        // Execute search based on mode
+        let hits = match mode {
+            SearchMode::Vector => {
+                self.expanded_vector_search(query, variants, search_limit, folder)
+                    .await?
+            }
+            SearchMode::Keyword => self.keyword_search(query, search_limit, chunks),
+            SearchMode::Hybrid => {
+                let weights = self.hybrid_weights(options);
+                self.hybrid_search(query, variants, search_limit, chunks, folder, weights)
+                    .await?
+            }
+        };
+        Ok(hits)
+    }
+
+    /// Drop vector hits whose similarity (0-1) is below `min_score`. A
+    /// keyword match counts as confident whatever its similarity. Returns
+    /// `Some(true)` when there were hits and none were kept.
+    pub(super) fn drop_weak_matches(hits: &mut Vec<SearchHit>, min_score: f32) -> Option<bool> {
+        let had_hits = !hits.is_empty();
+        hits.retain(|hit| {
+            hit.matched_by != MatchType::Vector || hit.similarity.is_some_and(|s| s >= min_score)
+        });
+        (had_hits && hits.is_empty()).then_some(true)
+    }
+
+    fn embedding_client(&self) -> SearchResult<&EmbeddingClient> {
+        self.embedding_client
+            .as_ref()
+            .ok_or(SearchError::ApiKeyMissing)
+    }
+
+    /// Perform vector search, among the chunks of `folder` when given
+    async fn vector_search(
+        &self,
+        query: &str,
+        limit: usize,
+        folder: Option<&str>,
+    ) -> SearchResult<Vec<SearchHit>> {
         // Generate query embedding
-        let query_vector = self.embedding_client.embed_one(query).await?;
+        let query_vector = self.embedding_client()?.embed_one(query).await?;
 
         // Search vector store
-        let mut results = self.vector_store.search(&query_vector, limit).await?;
+        let mut results = self
+            .vector_store
+            .search_in_folder(&query_vector, limit, folder)
+            .await?;
 
         // Mark as vector match
         for hit in &mut results {
@@ -135,25 +473,120 @@ impl Searcher {
         Ok(results)
     }
 
+    /// Usable variants of `query`: trimmed, distinct from the query and each
+    /// other (ignoring case), and capped at `MAX_QUERY_VARIANTS`
+    fn query_variants(query: &str, variants: &[String]) -> Vec<String> {
+        let mut seen = HashSet::from([query.to_lowercase()]);
+        variants
+            .iter()
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty() && seen.insert(v.to_lowercase()))
+            .take(MAX_QUERY_VARIANTS)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Vector search for the query and each variant, merged by reciprocal
+    /// rank fusion
+    ///
+    /// All phrasings are embedded in one request and searched concurrently.
+    /// Hits are ordered by fused rank but keep their best similarity as the
+    /// score, and record which phrasings retrieved them.
+    async fn expanded_vector_search(
+        &self,
+        query: &str,
+        variants: &[String],
+        limit: usize,
+        folder: Option<&str>,
+    ) -> SearchResult<Vec<SearchHit>> {
+        if variants.is_empty() {
+            return self.vector_search(query, limit, folder).await;
+        }
+
+        let phrasings: Vec<String> = std::iter::once(query.to_string())
+            .chain(variants.iter().cloned())
+            .collect();
+        let vectors = self.embedding_client()?.embed(phrasings.clone()).await?;
+        let lists = futures::future::try_join_all(
+            vectors
+                .iter()
+                .map(|vector| self.vector_store.search_in_folder(vector, limit, folder)),
+        )
+        .await?;
+
+        struct FusedHit {
+            rrf: f32,
+            best_rank: usize,
+            hit: SearchHit,
+        }
+        let mut fused: HashMap<(String, String), FusedHit> = HashMap::new();
+        for (phrasing, hits) in phrasings.iter().zip(lists) {
+            for (rank, mut hit) in hits.into_iter().enumerate() {
+                let rrf = 1.0 / (RRF_K + rank as f32 + 1.0);
+                let key = (hit.file_path.clone(), hit.content.clone());
+                match fused.get_mut(&key) {
+                    Some(entry) => {
+                        entry.rrf += rrf;
+                        entry.hit.score = entry.hit.score.max(hit.score);
+                        entry.hit.similarity = max_similarity(entry.hit.similarity, hit.similarity);
+                        if rank < entry.best_rank {
+                            entry.best_rank = rank;
+                            entry.hit.matched_queries.insert(0, phrasing.clone());
+                        } else {
+                            entry.hit.matched_queries.push(phrasing.clone());
+                        }
+                    }
+                    None => {
+                        hit.matched_by = MatchType::Vector;
+                        hit.matched_queries = vec![phrasing.clone()];
+                        fused.insert(
+                            key,
+                            FusedHit {
+                                rrf,
+                                best_rank: rank,
+                                hit,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut entries: Vec<FusedHit> = fused.into_values().collect();
+        // Ties go by place, so repeated searches (and pages) agree on order
+        entries.sort_by(|a, b| {
+            b.rrf
+                .partial_cmp(&a.rrf)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| by_place(&a.hit, &b.hit))
+        });
+        Ok(entries
+            .into_iter()
+            .take(limit)
+            .map(|entry| entry.hit)
+            .collect())
+    }
+
     /// Perform keyword search using BM25 algorithm
-    /// Matches Node.js KeywordSearcher implementation
-    fn keyword_search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+    /// Matches Node.js KeywordSearcher implementation. Only chunks holding
+    /// every phrase the query wraps in double quotes match.
+    fn keyword_search(&self, query: &str, limit: usize, chunks: &[SearchHit]) -> Vec<SearchHit> {
         // BM25 parameters (same as Node.js)
         const K1: f32 = 1.2; // Term frequency saturation parameter
         const B: f32 = 0.75; // Document length normalization parameter
 
         let query_tokens = Self::tokenize(query);
+        let phrases = quoted_phrases(query);
 
-        if query_tokens.is_empty() || self.all_chunks.is_empty() {
+        if query_tokens.is_empty() || chunks.is_empty() {
             return vec![];
         }
 
         // Pre-compute document statistics
-        let total_docs = self.all_chunks.len();
+        let total_docs = chunks.len();
 
         // Tokenize all documents and compute stats
-        let doc_data: Vec<(Vec<String>, HashMap<String, usize>, usize)> = self
-            .all_chunks
+        let doc_data: Vec<(bool, HashMap<String, usize>, usize)> = chunks
             .iter()
             .map(|chunk| {
                 let combined = format!(
@@ -161,10 +594,14 @@ impl Searcher {
                     chunk.content,
                     chunk.heading_path.as_deref().unwrap_or("")
                 );
+                let has_phrases = phrases.is_empty() || {
+                    let text = normalize_phrase(&combined);
+                    phrases.iter().all(|phrase| text.contains(phrase.as_str()))
+                };
                 let tokens = Self::tokenize(&combined);
                 let token_freq = Self::count_tokens(&tokens);
                 let length = tokens.len();
-                (tokens, token_freq, length)
+                (has_phrases, token_freq, length)
             })
             .collect();
 
@@ -187,10 +624,10 @@ impl Searcher {
         }
 
         // Score each document using BM25
-        let mut scored_hits: Vec<(f32, SearchHit)> = self
-            .all_chunks
+        let mut scored_hits: Vec<(f32, SearchHit)> = chunks
             .iter()
             .zip(doc_data.iter())
+            .filter(|(_, (has_phrases, _, _))| *has_phrases)
             .filter_map(|(chunk, (_, token_freq, doc_length))| {
                 let mut score = 0.0f32;
 
@@ -304,7 +741,7 @@ impl Searcher {
     }
 
     /// Check if character is Chinese
-    fn is_chinese_char(c: char) -> bool {
+    pub(super) fn is_chinese_char(c: char) -> bool {
         // Common Chinese Unicode range
         ('\u{4e00}'..='\u{9fff}').contains(&c)
     }
@@ -331,98 +768,107 @@ impl Searcher {
         freq
     }
 
-    /// Perform hybrid search using RRF (Reciprocal Rank Fusion)
-    async fn hybrid_search(&self, query: &str, limit: usize) -> SearchResult<Vec<SearchHit>> {
+    /// Perform hybrid search using RRF (Reciprocal Rank Fusion). The keyword
+    /// side runs while the query is being embedded.
+    async fn hybrid_search(
+        &self,
+        query: &str,
+        variants: &[String],
+        limit: usize,
+        chunks: &[SearchHit],
+        folder: Option<&str>,
+        weights: (f32, f32),
+    ) -> SearchResult<Vec<SearchHit>> {
         let candidate_limit = limit * 3;
 
         // Execute both searches
-        let vector_results = self.vector_search(query, candidate_limit).await?;
-        let keyword_results = self.keyword_search(query, candidate_limit);
+        let (vector_results, keyword_results) = tokio::join!(
+            self.expanded_vector_search(query, variants, candidate_limit, folder),
+            async { self.keyword_search(query, candidate_limit, chunks) }
+        );
+        let vector_results = vector_results?;
 
         // Use RRF to fuse results
-        let fused = self.rrf_fusion(vector_results, keyword_results, limit);
+        let fused = Self::rrf_fusion(vector_results, keyword_results, limit, weights);
 
         Ok(fused)
     }
 
+    /// Weights of the vector and keyword rankings in hybrid search, summing
+    /// to 1: the query's own where set, else from `hybrid_alpha`
+    fn hybrid_weights(&self, options: &SearchOptions) -> (f32, f32) {
+        let alpha = self.config.search.hybrid_alpha.clamp(0.0, 1.0);
+        let vector = options.vector_weight.unwrap_or(alpha).max(0.0);
+        let keyword = options.keyword_weight.unwrap_or(1.0 - alpha).max(0.0);
+        let total = vector + keyword;
+        if total > 0.0 && total.is_finite() {
+            (vector / total, keyword / total)
+        } else {
+            (alpha, 1.0 - alpha)
+        }
+    }
+
     /// Reciprocal Rank Fusion (RRF) algorithm
-    /// RRF(d) = Σ weight / (k + rank(d))
-    fn rrf_fusion(
-        &self,
+    /// RRF(d) = Σ weight / (k + rank(d)), with `weights` for the vector and
+    /// keyword rankings. Each hit keeps what each ranking added to its score,
+    /// and `matched_by` tells which rankings found it.
+    pub(super) fn rrf_fusion(
         vector_results: Vec<SearchHit>,
         keyword_results: Vec<SearchHit>,
         limit: usize,
+        (vector_weight, keyword_weight): (f32, f32),
     ) -> Vec<SearchHit> {
-        // Key: file_path:line_start (or file_path:0 if no line_start)
-        struct FusedEntry {
-            score: f32,
-            hit: SearchHit,
-            sources: Vec<&'static str>,
-        }
-
-        let mut scores: HashMap<String, FusedEntry> = HashMap::new();
+        // Key: file_path:line_start (or file_path:0 if no line_start). The
+        // RRF total ranks; each side keeps the score it found the hit with.
+        let mut fused: HashMap<String, (SearchHit, f32)> = HashMap::new();
 
-        // Process vector search results
         for (index, hit) in vector_results.into_iter().enumerate() {
             let key = format!("{}:{}", hit.file_path, hit.line_start.unwrap_or(0));
-            let rrf_score = VECTOR_WEIGHT / (RRF_K + index as f32 + 1.0);
-
-            if let Some(entry) = scores.get_mut(&key) {
-                entry.score += rrf_score;
-                entry.sources.push("vector");
-            } else {
-                scores.insert(
-                    key,
-                    FusedEntry {
-                        score: rrf_score,
-                        hit: SearchHit {
-                            matched_by: MatchType::Hybrid,
-                            ..hit
-                        },
-                        sources: vec!["vector"],
-                    },
-                );
-            }
+            let rrf_score = vector_weight / (RRF_K + index as f32 + 1.0);
+            let similarity = hit.score;
+            let (entry, total) = fused.entry(key).or_insert((
+                SearchHit {
+                    score: 0.0,
+                    vector_score: None,
+                    keyword_score: None,
+                    ..hit
+                },
+                0.0,
+            ));
+            entry.vector_score.get_or_insert(similarity);
+            *total += rrf_score;
         }
 
-        // Process keyword search results
         for (index, hit) in keyword_results.into_iter().enumerate() {
             let key = format!("{}:{}", hit.file_path, hit.line_start.unwrap_or(0));
-            let rrf_score = KEYWORD_WEIGHT / (RRF_K + index as f32 + 1.0);
-
-            if let Some(entry) = scores.get_mut(&key) {
-                entry.score += rrf_score;
-                entry.sources.push("keyword");
-            } else {
-                scores.insert(
-                    key,
-                    FusedEntry {
-                        score: rrf_score,
-                        hit: SearchHit {
-                            matched_by: MatchType::Hybrid,
-                            ..hit
-                        },
-                        sources: vec!["keyword"],
-                    },
-                );
-            }
+            let rrf_score = keyword_weight / (RRF_K + index as f32 + 1.0);
+            let bm25 = hit.score;
+            let (entry, total) = fused.entry(key).or_insert((
+                SearchHit {
+                    score: 0.0,
+                    vector_score: None,
+                    keyword_score: None,
+                    ..hit
+                },
+                0.0,
+            ));
+            entry.keyword_score.get_or_insert(bm25);
+            *total += rrf_score;
         }
 
         // Convert to results and sort
-        let mut results: Vec<SearchHit> = scores
+        let mut results: Vec<SearchHit> = fused
             .into_values()
-            .map(|entry| {
-                let matched_by = if entry.sources.len() > 1 {
-                    MatchType::Hybrid // vector+keyword
-                } else if entry.sources.contains(&"vector") {
-                    MatchType::Vector
-                } else {
-                    MatchType::Keyword
+            .map(|(hit, total)| {
+                let matched_by = match (hit.vector_score, hit.keyword_score) {
+                    (Some(_), Some(_)) => MatchType::Hybrid,
+                    (Some(_), None) => MatchType::Vector,
+                    _ => MatchType::Keyword,
                 };
                 SearchHit {
-                    score: entry.score,
+                    score: total,
                     matched_by,
-                    ..entry.hit
+                    ..hit
                 }
             })
             .collect();
@@ -431,11 +877,51 @@ impl Searcher {
             b.score
                 .partial_cmp(&a.score)
                 .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| by_place(a, b))
         });
         results.truncate(limit);
         results
     }
 
+    /// Collect chunk hits by document, keeping each document's best
+    /// `max_chunks` and ordering documents by their best chunk, then by path
+    fn group_by_doc(&self, hits: Vec<SearchHit>, max_chunks: usize, limit: usize) -> Vec<DocGroup> {
+        let mut by_doc: HashMap<String, Vec<SearchHit>> = HashMap::new();
+        for hit in hits {
+            by_doc.entry(hit.file_path.clone()).or_default().push(hit);
+        }
+
+        let mut groups: Vec<DocGroup> = by_doc
+            .into_iter()
+            .map(|(file_path, mut hits)| {
+                hits.sort_by(|a, b| {
+                    b.score
+                        .partial_cmp(&a.score)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then_with(|| by_place(a, b))
+                });
+                let hit_count = hits.len();
+                hits.truncate(max_chunks);
+                DocGroup {
+                    display_name: hits[0].display_name.clone(),
+                    score: hits[0].score,
+                    file_path,
+                    hit_count,
+                    hits,
+                }
+            })
+            .collect();
+
+        groups.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.file_path.cmp(&b.file_path))
+        });
+        groups.truncate(limit);
+        groups
+    }
+
     /// Aggregate results by document
     /// Uses weighted score formula matching Node.js:
     /// score = topScore * 0.6 + min(hitCount/5, 1) * topScore * 0.4
@@ -444,6 +930,7 @@ impl Searcher {
             file_path: String,
             display_name: String,
             top_score: f32,
+            similarity: Option<f32>,
             hit_count: usize,
             top_chunk: SearchHit,
         }
@@ -465,11 +952,13 @@ impl Searcher {
                     file_path: hit.file_path.clone(),
                     display_name: display_name.clone(),
                     top_score: 0.0,
+                    similarity: None,
                     hit_count: 0,
                     top_chunk: hit.clone(),
                 });
 
             entry.hit_count += 1;
+            entry.similarity = max_similarity(entry.similarity, hit.similarity);
 
             // Update with best chunk
             if hit.score > entry.top_score {
@@ -492,11 +981,18 @@ impl Searcher {
                     display_name: doc.display_name,
                     content: doc.top_chunk.content,
                     heading_path: doc.top_chunk.heading_path,
+                    headings: doc.top_chunk.headings,
                     section_title: doc.top_chunk.section_title,
                     line_start: doc.top_chunk.line_start,
                     line_end: doc.top_chunk.line_end,
+                    byte_start: doc.top_chunk.byte_start,
+                    byte_end: doc.top_chunk.byte_end,
+                    code_language: doc.top_chunk.code_language,
                     score: aggregated_score,
+                    similarity: doc.similarity,
                     matched_by: doc.top_chunk.matched_by,
+                    vector_score: doc.top_chunk.vector_score,
+                    keyword_score: doc.top_chunk.keyword_score,
                     hit_count: Some(doc.hit_count),
                     doc_count: None,
                     folder_path: None,
@@ -506,6 +1002,11 @@ impl Searcher {
                     entry_date: None,
                     entry_created_at: None,
                     idea_box: doc.top_chunk.idea_box,
+                    doc_modified_at: doc.top_chunk.doc_modified_at,
+                    rerank_score: None,
+                    matched_queries: doc.top_chunk.matched_queries,
+                    highlights: Vec::new(),
+                    snippet: None,
                 }
             })
             .collect();
@@ -514,6 +1015,7 @@ impl Searcher {
             b.score
                 .partial_cmp(&a.score)
                 .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.file_path.cmp(&b.file_path))
         });
         results.truncate(limit);
         results
@@ -527,8 +1029,11 @@ impl Searcher {
             folder_path: String,
             display_name: String,
             top_score: f32,
+            similarity: Option<f32>,
             hit_count: usize,
             docs: HashSet<String>,
+            /// Most recently modified doc among the hits
+            doc_modified_at: Option<u64>,
             top_chunk: SearchHit,
         }
 
@@ -557,13 +1062,17 @@ impl Searcher {
                     folder_path: folder_path.clone(),
                     display_name,
                     top_score: 0.0,
+                    similarity: None,
                     hit_count: 0,
                     docs: HashSet::new(),
+                    doc_modified_at: None,
                     top_chunk: hit.clone(),
                 });
 
             entry.hit_count += 1;
             entry.docs.insert(hit.file_path.clone());
+            entry.similarity = max_similarity(entry.similarity, hit.similarity);
+            entry.doc_modified_at = entry.doc_modified_at.max(hit.doc_modified_at);
 
             if hit.score > entry.top_score {
                 entry.top_score = hit.score;
@@ -588,11 +1097,18 @@ impl Searcher {
                     display_name: folder.display_name,
                     content: folder.top_chunk.content,
                     heading_path: folder.top_chunk.heading_path,
+                    headings: folder.top_chunk.headings,
                     section_title: folder.top_chunk.section_title,
                     line_start: folder.top_chunk.line_start,
                     line_end: folder.top_chunk.line_end,
+                    byte_start: folder.top_chunk.byte_start,
+                    byte_end: folder.top_chunk.byte_end,
+                    code_language: folder.top_chunk.code_language,
                     score: aggregated_score,
+                    similarity: folder.similarity,
                     matched_by: folder.top_chunk.matched_by,
+                    vector_score: folder.top_chunk.vector_score,
+                    keyword_score: folder.top_chunk.keyword_score,
                     hit_count: Some(folder.hit_count),
                     doc_count: Some(folder.docs.len()),
                     folder_path: Some(folder.folder_path),
@@ -602,6 +1118,11 @@ impl Searcher {
                     entry_date: None,
                     entry_created_at: None,
                     idea_box: folder.top_chunk.idea_box,
+                    doc_modified_at: folder.doc_modified_at,
+                    rerank_score: None,
+                    matched_queries: folder.top_chunk.matched_queries,
+                    highlights: Vec::new(),
+                    snippet: None,
                 }
             })
             .collect();
@@ -610,6 +1131,7 @@ impl Searcher {
             b.score
                 .partial_cmp(&a.score)
                 .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.folder_path.cmp(&b.folder_path))
         });
         results.truncate(limit);
         results
@@ -620,3 +1142,147 @@ impl Searcher {
         self.vector_store.exists().await
     }
 }
+
+/// The higher of two similarities, either of which may be unknown
+fn max_similarity(a: Option<f32>, b: Option<f32>) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => a.or(b),
+    }
+}
+
+/// Order of two chunks by doc and then position in it, to break score ties
+fn by_place(a: &SearchHit, b: &SearchHit) -> std::cmp::Ordering {
+    (&a.file_path, a.byte_start, a.line_start).cmp(&(&b.file_path, b.byte_start, b.line_start))
+}
+
+/// Whether `path` is the lowercased `folder` or lies below it, ignoring case
+fn in_folder(path: &str, folder: &str) -> bool {
+    let path = path.to_lowercase();
+    path == folder
+        || path
+            .strip_prefix(folder)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Phrases `query` wraps in double quotes, as `normalize_phrase` leaves them
+fn quoted_phrases(query: &str) -> Vec<String> {
+    let query = query.replace(['“', '”'], "\"");
+    let parts: Vec<&str> = query.split('"').collect();
+    // Odd parts are quoted; an unclosed quote's last part is not
+    parts
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % 2 == 1 && i + 1 < parts.len())
+        .map(|(_, phrase)| normalize_phrase(phrase))
+        .filter(|phrase| !phrase.is_empty())
+        .collect()
+}
+
+/// `text` lowercased, with each run of whitespace as one space
+fn normalize_phrase(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Furthest a cursor may point into the results; a larger offset is taken
+/// as this one rather than fetching everything up to it
+const MAX_CURSOR_OFFSET: usize = 10_000;
+
+/// Where a page of results ended: the position after it and the key of its
+/// last result. Encoded as `<offset>:<key>`, which callers treat as opaque.
+struct PageCursor {
+    offset: usize,
+    key: String,
+}
+
+impl PageCursor {
+    fn encode(&self) -> String {
+        format!("{}:{}", self.offset, self.key)
+    }
+
+    fn decode(cursor: &str) -> Option<Self> {
+        let (offset, key) = cursor.split_once(':')?;
+        Some(Self {
+            offset: offset.parse::<usize>().ok()?.min(MAX_CURSOR_OFFSET),
+            key: key.to_string(),
+        })
+    }
+
+    /// Where the next page starts in `ranked`: after the previous page's
+    /// last result wherever it ranks now, or at the same position if it's
+    /// gone from the results
+    fn start_in<T>(&self, ranked: &[T], key: impl Fn(&T) -> String) -> usize {
+        ranked
+            .iter()
+            .position(|result| key(result) == self.key)
+            .map_or(self.offset, |i| i + 1)
+            .min(ranked.len())
+    }
+}
+
+/// The page of `ranked` that `cursor` asks for, the cursor of the page
+/// after it if there is one, and how many results were ranked. A page runs
+/// past `limit` rather than end between two results `keep_together`.
+fn page<T>(
+    ranked: Vec<T>,
+    cursor: Option<PageCursor>,
+    limit: usize,
+    key: impl Fn(&T) -> String,
+    keep_together: impl Fn(&T, &T) -> bool,
+) -> (Vec<T>, Option<String>, usize) {
+    let start = cursor.map_or(0, |cursor| cursor.start_in(&ranked, &key));
+    let total = ranked.len();
+    let mut end = start.saturating_add(limit).min(total);
+    while end > start && end < total && keep_together(&ranked[end - 1], &ranked[end]) {
+        end += 1;
+    }
+    let next_cursor = (end > start && end < total).then(|| {
+        PageCursor {
+            offset: end,
+            key: key(&ranked[end - 1]),
+        }
+        .encode()
+    });
+    let page = ranked.into_iter().skip(start).take(end - start).collect();
+    (page, next_cursor, total)
+}
+
+/// `hits` with each doc's chunks moved up behind its best-ranked one, so
+/// pages of chunks can end between docs
+fn cluster_by_doc(hits: Vec<SearchHit>) -> Vec<SearchHit> {
+    let mut order = Vec::new();
+    let mut by_doc: HashMap<String, Vec<SearchHit>> = HashMap::new();
+    for hit in hits {
+        by_doc
+            .entry(hit.file_path.clone())
+            .or_insert_with(|| {
+                order.push(hit.file_path.clone());
+                Vec::new()
+            })
+            .push(hit);
+    }
+    order
+        .into_iter()
+        .flat_map(|path| by_doc.remove(&path).unwrap_or_default())
+        .collect()
+}
+
+/// What tells a result apart from the others on its level: the folder or doc
+/// it aggregates, or the chunk's place in its doc
+fn page_key(hit: &SearchHit) -> String {
+    match hit.aggregate_type.as_deref() {
+        Some("folder") => hit.folder_path.clone().unwrap_or_default(),
+        Some("doc") => hit.file_path.clone(),
+        _ => {
+            let place = hit
+                .entry_id
+                .clone()
+                .or_else(|| hit.byte_start.or(hit.line_start).map(|n| n.to_string()))
+                .unwrap_or_default();
+            format!("{}#{}", hit.file_path, place)
+        }
+    }
+}
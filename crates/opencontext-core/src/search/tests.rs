@@ -46,7 +46,7 @@ mod tests {
             // Most chunks should be reasonably sized (allow some overflow for edge cases)
             let reasonable_chunks = chunks
                 .iter()
-                .filter(|c| c.content.chars().count() <= max_size * 3)
+                .filter(|c| super::super::chunker::estimate_tokens(&c.content) <= max_size)
                 .count();
             assert!(
                 reasonable_chunks >= chunks.len() / 2,
@@ -136,6 +136,22 @@ mod tests {
             let results = SearchResults::index_not_built("query".to_string());
             assert!(results.index_missing.unwrap_or(false));
         }
+
+        #[test]
+        fn test_chunk_without_doc_modified_at_deserializes() {
+            let chunk: Chunk = serde_json::from_str(
+                r#"{"id":"a.md#0","file_path":"a.md","content":"x","heading_path":"","chunk_index":0}"#,
+            )
+            .unwrap();
+            assert_eq!(chunk.doc_modified_at, None);
+
+            let json = serde_json::to_value(Chunk {
+                doc_modified_at: Some(1_700_000_000_000),
+                ..chunk
+            })
+            .unwrap();
+            assert_eq!(json["doc_modified_at"], 1_700_000_000_000u64);
+        }
     }
 
     mod config_tests {
@@ -146,7 +162,24 @@ mod tests {
             // Should not panic
             let config = SearchConfig::default();
             assert!(config.embedding.dimensions > 0);
-            assert!(config.search.chunk_size > 0);
+            assert!(config.chunking.max_tokens > 0);
+        }
+
+        #[test]
+        fn test_chunk_size_is_capped_at_model_context() {
+            let mut config = SearchConfig::default();
+            config.chunking.max_tokens = 1000;
+            config.embedding.provider = EmbeddingProvider::Local;
+            assert_eq!(config.chunk_max_tokens(), 512);
+
+            config.embedding.provider = EmbeddingProvider::Ollama;
+            assert_eq!(config.chunk_max_tokens(), 1000);
+
+            assert_eq!(
+                ChunkStrategy::parse(" Sentence "),
+                Some(ChunkStrategy::Sentence)
+            );
+            assert_eq!(ChunkStrategy::parse("paragraph"), None);
         }
 
         #[test]
@@ -155,6 +188,233 @@ mod tests {
             assert!(!config.model.is_empty());
             assert!(config.dimensions > 0);
             assert!(config.batch_size > 0);
+            assert!(config.max_concurrent_requests > 0);
+        }
+
+        #[test]
+        fn test_embedding_provider_parses_config_values() {
+            assert_eq!(
+                EmbeddingProvider::parse(" Ollama "),
+                Some(EmbeddingProvider::Ollama)
+            );
+            assert_eq!(
+                EmbeddingProvider::parse("openai"),
+                Some(EmbeddingProvider::OpenAI)
+            );
+            assert_eq!(
+                EmbeddingProvider::parse("fastembed"),
+                Some(EmbeddingProvider::Local)
+            );
+            assert_eq!(EmbeddingProvider::parse("cohere"), None);
+            let config: EmbeddingConfig = toml::from_str("provider = \"ollama\"").unwrap();
+            assert_eq!(config.provider, EmbeddingProvider::Ollama);
+        }
+
+        #[test]
+        fn test_embedding_config_is_local_for_loopback_servers() {
+            let config = |provider, api_base: &str| EmbeddingConfig {
+                provider,
+                api_base: api_base.to_string(),
+                ..EmbeddingConfig::default()
+            };
+            assert!(config(EmbeddingProvider::Local, "https://api.openai.com/v1").is_local());
+            assert!(config(EmbeddingProvider::Ollama, "http://localhost:11434").is_local());
+            assert!(config(EmbeddingProvider::OpenAI, "http://127.0.0.1:8080/v1").is_local());
+            assert!(config(EmbeddingProvider::OpenAI, "http://[::1]:8080/v1").is_local());
+            assert!(!config(EmbeddingProvider::Ollama, "http://gpu-box:11434").is_local());
+            assert!(!config(EmbeddingProvider::OpenAI, "https://api.openai.com/v1").is_local());
+        }
+
+        #[test]
+        fn test_with_profile_overrides_model_and_index_path() {
+            let mut config = SearchConfig::default();
+            config.profiles.insert(
+                "large".to_string(),
+                EmbeddingProfile {
+                    model: Some("text-embedding-3-large".to_string()),
+                    dimensions: Some(3072),
+                    ..Default::default()
+                },
+            );
+
+            let resolved = config.with_profile("large").unwrap();
+            assert_eq!(resolved.embedding.model, "text-embedding-3-large");
+            assert_eq!(resolved.embedding.dimensions, 3072);
+            assert_eq!(resolved.embedding.api_base, config.embedding.api_base);
+            assert_ne!(
+                resolved.paths.get_lancedb_path(),
+                config.paths.get_lancedb_path()
+            );
+        }
+
+        #[test]
+        fn test_with_profile_rejects_unknown_or_invalid_names() {
+            let mut config = SearchConfig::default();
+            config
+                .profiles
+                .insert("../escape".to_string(), EmbeddingProfile::default());
+
+            assert!(matches!(
+                config.with_profile("missing"),
+                Err(SearchError::Config(_))
+            ));
+            assert!(matches!(
+                config.with_profile("../escape"),
+                Err(SearchError::Config(_))
+            ));
+        }
+
+        #[test]
+        fn test_profile_for_path_prefers_most_specific_folder() {
+            let mut config = SearchConfig::default();
+            config
+                .folder_profiles
+                .insert("code".to_string(), "code".to_string());
+            config
+                .folder_profiles
+                .insert("code/prose/".to_string(), "prose".to_string());
+
+            assert_eq!(config.profile_for_path("code/main.md"), Some("code"));
+            assert_eq!(
+                config.profile_for_path("code/prose/intro.md"),
+                Some("prose")
+            );
+            assert_eq!(config.profile_for_path("codex/notes.md"), None);
+            assert_eq!(config.profile_for_path("notes.md"), None);
+            assert_eq!(config.assigned_profiles(), vec!["code", "prose"]);
+        }
+
+        #[test]
+        fn test_check_json_config_reports_syntax_error_position() {
+            let path = std::path::Path::new("config.json");
+            let mut issues = Vec::new();
+            let content = "{\n  \"EMBEDDING_MODEL\": \"m\",\n}";
+
+            assert!(config::check_json_config(path, content, &mut issues).is_none());
+            assert_eq!(issues.len(), 1);
+            assert_eq!(issues[0].severity, ConfigIssueSeverity::Error);
+            assert_eq!(issues[0].line, Some(3));
+        }
+
+        #[test]
+        fn test_check_json_config_drops_mistyped_values() {
+            let path = std::path::Path::new("config.json");
+            let mut issues = Vec::new();
+            let content = r#"{
+                "EMBEDDING_MODEL": 3,
+                "EMBEDDING_API_BASE": "localhost:11434",
+                "EMBEDDING_MODLE": "typo",
+                "AI_PROVIDER": "ollama",
+                "EMBEDDING_PROFILES": {
+                    "large": { "dimensions": "3072", "colour": "blue" }
+                }
+            }"#;
+
+            let value = config::check_json_config(path, content, &mut issues).unwrap();
+            assert!(value.get("EMBEDDING_MODEL").is_none());
+            assert!(value.get("EMBEDDING_API_BASE").is_some());
+            assert!(value["EMBEDDING_PROFILES"]["large"]
+                .get("dimensions")
+                .is_none());
+
+            let keys: Vec<_> = issues.iter().filter_map(|i| i.key.as_deref()).collect();
+            assert_eq!(
+                keys,
+                vec![
+                    "EMBEDDING_API_BASE",
+                    "EMBEDDING_MODEL",
+                    "EMBEDDING_MODLE",
+                    "EMBEDDING_PROFILES.large.dimensions",
+                    "EMBEDDING_PROFILES.large.colour",
+                ]
+            );
+        }
+
+        #[test]
+        fn test_check_json_config_keeps_positive_embedding_limits() {
+            let path = std::path::Path::new("config.json");
+            let mut issues = Vec::new();
+            let content = r#"{
+                "EMBEDDING_BATCH_SIZE": 64,
+                "EMBEDDING_MAX_CONCURRENT_REQUESTS": 0
+            }"#;
+
+            let value = config::check_json_config(path, content, &mut issues).unwrap();
+            assert_eq!(value["EMBEDDING_BATCH_SIZE"], 64);
+            assert!(value.get("EMBEDDING_MAX_CONCURRENT_REQUESTS").is_none());
+            assert_eq!(issues.len(), 1);
+            assert_eq!(
+                issues[0].key.as_deref(),
+                Some("EMBEDDING_MAX_CONCURRENT_REQUESTS")
+            );
+        }
+
+        #[test]
+        fn test_check_json_config_keeps_string_extra_headers() {
+            let path = std::path::Path::new("config.json");
+            let mut issues = Vec::new();
+            let content = r#"{
+                "EMBEDDING_PROXY_URL": "http://proxy.corp:8080",
+                "EMBEDDING_EXTRA_HEADERS": { "X-Gateway-Key": "k", "X-Retries": 3 }
+            }"#;
+
+            let value = config::check_json_config(path, content, &mut issues).unwrap();
+            assert_eq!(value["EMBEDDING_PROXY_URL"], "http://proxy.corp:8080");
+            assert_eq!(value["EMBEDDING_EXTRA_HEADERS"]["X-Gateway-Key"], "k");
+            assert!(value["EMBEDDING_EXTRA_HEADERS"].get("X-Retries").is_none());
+            let keys: Vec<_> = issues.iter().filter_map(|i| i.key.as_deref()).collect();
+            assert_eq!(keys, vec!["EMBEDDING_EXTRA_HEADERS.X-Retries"]);
+        }
+
+        #[test]
+        fn test_check_json_config_prefers_renamed_chunking_keys() {
+            let path = std::path::Path::new("config.json");
+            let mut issues = Vec::new();
+            let content = r#"{
+                "EMBEDDING_MAX_CHUNK_TOKENS": 300,
+                "CHUNKING_MAX_TOKENS": 500,
+                "EMBEDDING_CHUNK_OVERLAP": 40
+            }"#;
+
+            let value = config::check_json_config(path, content, &mut issues).unwrap();
+            assert!(value.get("EMBEDDING_MAX_CHUNK_TOKENS").is_none());
+            assert_eq!(value["CHUNKING_MAX_TOKENS"], 500);
+            // Only set under its old name: kept, and read as the new one
+            assert_eq!(value["EMBEDDING_CHUNK_OVERLAP"], 40);
+            let keys: Vec<_> = issues.iter().filter_map(|i| i.key.as_deref()).collect();
+            assert_eq!(keys, vec!["EMBEDDING_MAX_CHUNK_TOKENS"]);
+        }
+
+        #[test]
+        fn test_check_toml_config_reports_character_chunk_keys() {
+            let path = std::path::Path::new("config.toml");
+            let mut issues = Vec::new();
+            let content = "[search]\nchunk_size = 1500\nchunk_overlap = 200\n";
+
+            let config = config::check_toml_config(path, content, &mut issues).unwrap();
+            assert_eq!(config.chunking, ChunkingConfig::default());
+            let keys: Vec<_> = issues.iter().filter_map(|i| i.key.as_deref()).collect();
+            assert_eq!(keys, vec!["search.chunk_size", "search.chunk_overlap"]);
+            assert!(issues
+                .iter()
+                .all(|i| i.severity == ConfigIssueSeverity::Warning));
+        }
+
+        #[test]
+        fn test_check_json_config_keeps_hybrid_alpha_within_range() {
+            let path = std::path::Path::new("config.json");
+            for (alpha, kept) in [("0", true), ("0.35", true), ("1", true), ("1.5", false)] {
+                let mut issues = Vec::new();
+                let content = format!(r#"{{ "SEARCH_HYBRID_ALPHA": {} }}"#, alpha);
+                let value = config::check_json_config(path, &content, &mut issues).unwrap();
+                assert_eq!(
+                    value.get("SEARCH_HYBRID_ALPHA").is_some(),
+                    kept,
+                    "{}",
+                    alpha
+                );
+                assert_eq!(issues.is_empty(), kept, "{}", alpha);
+            }
         }
     }
 
@@ -176,4 +436,1360 @@ mod tests {
             assert!(display.contains("file not found") || display.contains("IO"));
         }
     }
+
+    mod index_tests {
+        use super::*;
+
+        fn chunk(file_path: &str, vector: Vec<f32>) -> Chunk {
+            Chunk {
+                id: format!("{}#0", file_path),
+                file_path: file_path.to_string(),
+                content: "Quarterly roadmap".to_string(),
+                heading_path: String::new(),
+                headings: Vec::new(),
+                section_title: None,
+                doc_type: Some("doc".to_string()),
+                entry_id: None,
+                entry_date: None,
+                entry_created_at: None,
+                idea_box: None,
+                doc_modified_at: None,
+                doc_hash: None,
+                chunk_index: 0,
+                line_start: Some(1),
+                line_end: Some(1),
+                byte_start: Some(0),
+                byte_end: Some(17),
+                code_language: None,
+                vector,
+            }
+        }
+
+        /// A temp dir with an empty 4-dimension index at `lancedb`, and a
+        /// config that searches it with no API key, so by keyword only
+        async fn test_index() -> (tempfile::TempDir, VectorStore, SearchConfig) {
+            let dir = tempfile::tempdir().unwrap();
+            let lancedb_path = dir.path().join("lancedb");
+            let mut store = VectorStore::new(lancedb_path.clone(), 4);
+            store.initialize().await.unwrap();
+            let mut config = SearchConfig::default();
+            config.embedding.api_key = None;
+            config.embedding.dimensions = 4;
+            config.paths.lancedb_path = Some(lancedb_path);
+            config.paths.index_metadata_path = Some(dir.path().join("index-metadata.json"));
+            (dir, store, config)
+        }
+
+        /// Point `config` at an embedding API where nothing listens, so any
+        /// embedding request fails
+        fn unreachable_embedding(config: &mut SearchConfig) {
+            config.embedding.api_key = Some("test".to_string());
+            config.embedding.api_base = "http://127.0.0.1:9".to_string();
+        }
+
+        /// The Markdown doc at `rel_path` under `dir`, as the context lists it
+        fn doc(dir: &std::path::Path, rel_path: &str) -> crate::Doc {
+            let name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+            crate::Doc {
+                id: 1,
+                folder_id: 1,
+                name: name.to_string(),
+                rel_path: rel_path.to_string(),
+                abs_path: dir.join(rel_path),
+                description: String::new(),
+                stable_id: name.trim_end_matches(".md").to_string(),
+                created_at: String::new(),
+                updated_at: String::new(),
+                kind: crate::DocKind::Markdown,
+            }
+        }
+
+        #[tokio::test]
+        async fn test_folder_prefix_filters_before_ranking() {
+            let (_dir, mut store, config) = test_index().await;
+            store
+                .upsert(vec![
+                    chunk("Work/plan.md", vec![0.0, 1.0, 0.0, 0.0]),
+                    chunk("work-archive/plan.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("personal/plan.md", vec![1.0, 0.1, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            // The folder's only chunk is the farthest from the query
+            let query = [1.0, 0.0, 0.0, 0.0];
+            let hits = store
+                .search_in_folder(&query, 1, Some("work"))
+                .await
+                .unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].file_path, "Work/plan.md");
+            assert_eq!(store.search(&query, 3).await.unwrap().len(), 3);
+
+            let searcher = Searcher::new(config).await.unwrap();
+            let search = |folder_prefix: Option<&str>| SearchOptions {
+                query: "roadmap".to_string(),
+                mode: Some(SearchMode::Keyword),
+                aggregate_by: Some(AggregateBy::Content),
+                folder_prefix: folder_prefix.map(str::to_string),
+                ..Default::default()
+            };
+            let results = searcher.search(search(Some("/WORK/"))).await.unwrap();
+            let paths: Vec<&str> = results
+                .results
+                .iter()
+                .map(|hit| hit.file_path.as_str())
+                .collect();
+            assert_eq!(paths, ["Work/plan.md"]);
+            for folder_prefix in [None, Some("")] {
+                let results = searcher.search(search(folder_prefix)).await.unwrap();
+                assert_eq!(results.count, 3);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_rename_doc_path_repoints_chunks_without_embedding() {
+            let (dir, mut store, mut config) = test_index().await;
+            let lancedb_path = dir.path().join("lancedb");
+            store
+                .upsert(vec![chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0])])
+                .await
+                .unwrap();
+
+            unreachable_embedding(&mut config);
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+            indexer
+                .rename_doc_path("plans/roadmap.md", "archive/roadmap-2024.md")
+                .await
+                .unwrap();
+
+            let mut store = VectorStore::new(lancedb_path, 4);
+            store.initialize().await.unwrap();
+            let hits = store.search(&[1.0, 0.0, 0.0, 0.0], 10).await.unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].file_path, "archive/roadmap-2024.md");
+            assert_eq!(store.count().await.unwrap(), 1);
+        }
+
+        /// Wait for the only chunk in the index at `lancedb_path` to be filed
+        /// under `expected`
+        async fn wait_for_indexed_path(lancedb_path: &std::path::Path, expected: &str) {
+            let mut paths = Vec::new();
+            for _ in 0..200 {
+                let mut store = VectorStore::new(lancedb_path.to_path_buf(), 4);
+                store.initialize().await.unwrap();
+                let hits = store.search(&[1.0, 0.0, 0.0, 0.0], 10).await.unwrap();
+                paths = hits.into_iter().map(|hit| hit.file_path).collect();
+                if paths == [expected] {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+            }
+            assert_eq!(paths, [expected]);
+        }
+
+        #[tokio::test]
+        async fn test_renaming_and_moving_through_context_embeds_nothing() {
+            use crate::events::create_event_bus;
+            use crate::{EnvOverrides, OpenContext};
+            use std::sync::atomic::{AtomicUsize, Ordering};
+            use std::sync::Arc;
+
+            // A stand-in embedding API that counts requests and answers none
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let api_base = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(AtomicUsize::new(0));
+            std::thread::spawn({
+                let requests = requests.clone();
+                move || {
+                    for _connection in listener.incoming() {
+                        requests.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            });
+
+            let (dir, mut store, mut config) = test_index().await;
+            let lancedb_path = dir.path().join("lancedb");
+            let contexts_root = dir.path().join("contexts");
+            let bus = create_event_bus();
+            let ctx = OpenContext::initialize(EnvOverrides {
+                base_root: Some(dir.path().to_path_buf()),
+                contexts_root: Some(contexts_root.clone()),
+                db_path: Some(dir.path().join("test.db")),
+            })
+            .unwrap()
+            .with_event_bus(bus.clone());
+            ctx.create_folder("plans", None).unwrap();
+            ctx.create_folder("archive", None).unwrap();
+            ctx.create_doc("plans", "roadmap.md", None).unwrap();
+            ctx.save_doc_content("plans/roadmap.md", "Quarterly roadmap", None)
+                .unwrap();
+
+            store
+                .upsert(vec![chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0])])
+                .await
+                .unwrap();
+
+            config.embedding.api_key = Some("test".to_string());
+            config.embedding.api_base = api_base;
+            let service =
+                Arc::new(IndexSyncService::new(config, contexts_root).with_interval(3600));
+            let running = tokio::spawn({
+                let service = service.clone();
+                let bus = bus.clone();
+                async move { service.start(bus).await }
+            });
+            while bus.subscriber_count() == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+
+            ctx.rename_doc("plans/roadmap.md", "roadmap-2024.md")
+                .unwrap();
+            wait_for_indexed_path(&lancedb_path, "plans/roadmap-2024.md").await;
+            ctx.move_folder("plans", "archive").unwrap();
+            wait_for_indexed_path(&lancedb_path, "archive/plans/roadmap-2024.md").await;
+
+            assert_eq!(requests.load(Ordering::SeqCst), 0);
+            assert_eq!(service.pending_count().await, 0);
+            running.abort();
+        }
+
+        #[tokio::test]
+        async fn test_rename_folder_path_repoints_nested_chunks_without_embedding() {
+            let (dir, mut store, mut config) = test_index().await;
+            let lancedb_path = dir.path().join("lancedb");
+            store
+                .upsert(vec![
+                    chunk("work/plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("work/plans/2024/q1.md", vec![0.0, 1.0, 0.0, 0.0]),
+                    chunk("work/plans-old/notes.md", vec![0.0, 0.0, 1.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            unreachable_embedding(&mut config);
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+            assert!(indexer
+                .rename_folder_path("work/plans", "archive/plans")
+                .await
+                .unwrap());
+
+            let mut store = VectorStore::new(lancedb_path, 4);
+            store.initialize().await.unwrap();
+            let top_path = |hits: Vec<SearchHit>| hits[0].file_path.clone();
+            assert_eq!(
+                top_path(store.search(&[1.0, 0.0, 0.0, 0.0], 1).await.unwrap()),
+                "archive/plans/roadmap.md"
+            );
+            assert_eq!(
+                top_path(store.search(&[0.0, 1.0, 0.0, 0.0], 1).await.unwrap()),
+                "archive/plans/2024/q1.md"
+            );
+            // A sibling sharing the name as a prefix stays put
+            assert_eq!(
+                top_path(store.search(&[0.0, 0.0, 1.0, 0.0], 1).await.unwrap()),
+                "work/plans-old/notes.md"
+            );
+            let previews = store
+                .chunks_for_file("archive/plans/2024/q1.md")
+                .await
+                .unwrap();
+            assert_eq!(previews[0].id, "archive/plans/2024/q1.md#0");
+            assert_eq!(store.count().await.unwrap(), 3);
+        }
+
+        #[tokio::test]
+        async fn test_rename_folder_path_defers_when_profiles_change() {
+            let (dir, _, mut config) = test_index().await;
+            unreachable_embedding(&mut config);
+            config
+                .folder_profiles
+                .insert("code".to_string(), "code".to_string());
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+
+            assert!(!indexer
+                .rename_folder_path("notes/snippets", "code/snippets")
+                .await
+                .unwrap());
+        }
+
+        #[tokio::test]
+        async fn test_index_doc_deleted_since_listing_removes_its_chunks() {
+            let (dir, mut store, mut config) = test_index().await;
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("plans/other.md", vec![0.0, 1.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let metadata_path = dir.path().join("index-metadata.json");
+            unreachable_embedding(&mut config);
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+            let doc = doc(dir.path(), "plans/roadmap.md");
+            assert_eq!(indexer.index_doc(&doc).await.unwrap(), 0);
+
+            assert_eq!(indexer.get_stats().await.unwrap().total_chunks, 1);
+            let metadata: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(metadata_path).unwrap()).unwrap();
+            assert_eq!(metadata["totalChunks"], 1);
+        }
+
+        #[tokio::test]
+        async fn test_deleted_doc_leaves_search_results_right_away() {
+            use crate::events::{create_event_bus, DocEvent};
+            use std::sync::Arc;
+
+            let (dir, mut store, config) = test_index().await;
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("plans/roadmap-2023.md", vec![0.0, 1.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let search = |config: SearchConfig| async move {
+                let results = Searcher::new(config)
+                    .await
+                    .unwrap()
+                    .search(SearchOptions {
+                        query: "roadmap".to_string(),
+                        mode: Some(SearchMode::Keyword),
+                        aggregate_by: Some(AggregateBy::Content),
+                        ..Default::default()
+                    })
+                    .await
+                    .unwrap();
+                let mut paths: Vec<String> = results
+                    .results
+                    .into_iter()
+                    .map(|hit| hit.file_path)
+                    .collect();
+                paths.sort();
+                paths
+            };
+            assert_eq!(
+                search(config.clone()).await,
+                ["plans/roadmap-2023.md", "plans/roadmap.md"]
+            );
+
+            // Hours between batches: only an immediate removal passes
+            let service = Arc::new(
+                IndexSyncService::new(config.clone(), dir.path().to_path_buf()).with_interval(3600),
+            );
+            let bus = create_event_bus();
+            let running = tokio::spawn({
+                let service = service.clone();
+                let bus = bus.clone();
+                async move { service.start(bus).await }
+            });
+            while bus.subscriber_count() == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            bus.emit_doc(DocEvent::Deleted {
+                rel_path: "plans/roadmap.md".to_string(),
+            });
+
+            let mut paths = Vec::new();
+            for _ in 0..200 {
+                paths = search(config.clone()).await;
+                if paths.len() == 1 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+            }
+            assert_eq!(paths, ["plans/roadmap-2023.md"]);
+            assert_eq!(service.pending_count().await, 0);
+            running.abort();
+        }
+
+        #[tokio::test]
+        async fn test_unscoped_search_includes_profile_indexes() {
+            let (dir, mut store, mut config) = test_index().await;
+            let profile_path = dir.path().join("code-lancedb");
+            let mut profile_store = VectorStore::new(profile_path.clone(), 4);
+            profile_store.initialize().await.unwrap();
+            for (store, file) in [
+                (&mut store, "notes/roadmap.md"),
+                (&mut profile_store, "code/roadmap.md"),
+            ] {
+                store
+                    .upsert(vec![chunk(file, vec![1.0, 0.0, 0.0, 0.0])])
+                    .await
+                    .unwrap();
+            }
+
+            config.profiles.insert(
+                "code".to_string(),
+                EmbeddingProfile {
+                    lancedb_path: Some(profile_path),
+                    ..Default::default()
+                },
+            );
+            config
+                .folder_profiles
+                .insert("code".to_string(), "code".to_string());
+            let searcher = Searcher::new(config).await.unwrap();
+            let search = |folder_prefix: Option<&str>| SearchOptions {
+                query: "roadmap".to_string(),
+                mode: Some(SearchMode::Keyword),
+                aggregate_by: Some(AggregateBy::Content),
+                folder_prefix: folder_prefix.map(str::to_string),
+                no_cache: true,
+                ..Default::default()
+            };
+
+            let results = searcher.search(search(None)).await.unwrap();
+            let mut paths: Vec<String> = results
+                .results
+                .into_iter()
+                .map(|hit| hit.file_path)
+                .collect();
+            paths.sort();
+            assert_eq!(paths, ["code/roadmap.md", "notes/roadmap.md"]);
+
+            // A folder with its own profile only searches that profile
+            let results = searcher.search(search(Some("code"))).await.unwrap();
+            assert_eq!(results.count, 1);
+            assert_eq!(results.results[0].file_path, "code/roadmap.md");
+        }
+
+        #[tokio::test]
+        async fn test_prioritized_update_skips_the_batch() {
+            use crate::events::{create_event_bus, DocEvent};
+            use crate::{FolderSettingsFile, IndexPriority};
+            use std::sync::Arc;
+
+            let (dir, mut store, config) = test_index().await;
+            let lancedb_path = dir.path().join("lancedb");
+            let plans = dir.path().join("plans");
+            std::fs::create_dir_all(&plans).unwrap();
+            std::fs::write(plans.join("roadmap.md"), "Quarterly roadmap").unwrap();
+            std::fs::write(plans.join("roadmap-2023.md"), "Quarterly roadmap").unwrap();
+            // Re-indexing a skipped doc only drops its chunks, so no
+            // embedding API is needed
+            let mut settings = FolderSettingsFile::load(&plans).unwrap();
+            settings.folder.index_priority = Some(IndexPriority::Skip);
+            settings.save(&plans).unwrap();
+
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("plans/roadmap-2023.md", vec![0.0, 1.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let service = Arc::new(
+                IndexSyncService::new(config, dir.path().to_path_buf()).with_interval(3600),
+            );
+            let bus = create_event_bus();
+            let running = tokio::spawn({
+                let service = service.clone();
+                let bus = bus.clone();
+                async move { service.start(bus).await }
+            });
+            while bus.subscriber_count() == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+
+            // Asked for before its update comes in, as after a save
+            service.prioritize("plans/roadmap.md").await;
+            for rel_path in ["plans/roadmap-2023.md", "plans/roadmap.md"] {
+                bus.emit_doc(DocEvent::Updated {
+                    rel_path: rel_path.to_string(),
+                });
+            }
+
+            wait_for_indexed_path(&lancedb_path, "plans/roadmap-2023.md").await;
+            // The other update waits for the batch
+            assert_eq!(service.pending_count().await, 1);
+            running.abort();
+        }
+
+        #[tokio::test]
+        async fn test_build_cancelled_before_first_batch_embeds_nothing() {
+            let (dir, _, mut config) = test_index().await;
+            std::fs::write(dir.path().join("roadmap.md"), "# Roadmap\n\nShip it.\n").unwrap();
+
+            unreachable_embedding(&mut config);
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+            let doc = doc(dir.path(), "roadmap.md");
+            let mut phases = Vec::new();
+            let stats = indexer
+                .build_all_with_progress(
+                    vec![doc],
+                    |progress| phases.push(progress.phase),
+                    &std::sync::atomic::AtomicBool::new(true),
+                    false,
+                )
+                .await
+                .unwrap();
+            assert!(stats.cancelled);
+            assert_eq!(stats.total_chunks, 0);
+            assert_eq!(phases, vec!["cancelled".to_string()]);
+        }
+
+        #[tokio::test]
+        async fn test_build_skips_unchanged_docs_and_drops_stale_ones() {
+            let (dir, _, mut config) = test_index().await;
+            let content = "# Roadmap\n\nShip it.\n";
+            std::fs::write(dir.path().join("roadmap.md"), content).unwrap();
+
+            unreachable_embedding(&mut config);
+            let cancel = std::sync::atomic::AtomicBool::new(false);
+
+            // An empty build records the settings the index is built with
+            Indexer::new(config.clone(), dir.path().to_path_buf())
+                .await
+                .unwrap()
+                .build_all_with_progress(vec![], |_| {}, &cancel, false)
+                .await
+                .unwrap();
+            let mut store = VectorStore::new(dir.path().join("lancedb"), 4);
+            store.initialize().await.unwrap();
+            store
+                .upsert(vec![
+                    Chunk {
+                        doc_hash: Some(super::super::vector_store::content_hash(content)),
+                        ..chunk("roadmap.md", vec![1.0, 0.0, 0.0, 0.0])
+                    },
+                    chunk("deleted.md", vec![0.0, 1.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let doc = doc(dir.path(), "roadmap.md");
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+            let stats = indexer
+                .build_all_with_progress(vec![doc], |_| {}, &cancel, false)
+                .await
+                .unwrap();
+            assert_eq!((stats.indexed_docs, stats.skipped_docs), (0, 1));
+            assert_eq!(stats.total_chunks, 1);
+
+            let mut store = VectorStore::new(dir.path().join("lancedb"), 4);
+            store.initialize().await.unwrap();
+            let docs = store.indexed_docs().await.unwrap();
+            assert_eq!(docs.keys().collect::<Vec<_>>(), ["roadmap.md"]);
+        }
+
+        #[tokio::test]
+        async fn test_doc_summaries_mark_docs_changed_since_indexing() {
+            let (dir, mut store, mut config) = test_index().await;
+            for name in ["old.md", "fresh.md", "new.md", "touched.md"] {
+                std::fs::write(dir.path().join(name), "Quarterly roadmap").unwrap();
+            }
+            let mut old = chunk("old.md", vec![1.0, 0.0, 0.0, 0.0]);
+            old.doc_modified_at = Some(1);
+            let mut old_second = chunk("old.md", vec![0.0, 1.0, 0.0, 0.0]);
+            old_second.id = "old.md#1".to_string();
+            old_second.chunk_index = 1;
+            old_second.doc_modified_at = Some(1);
+            let mut fresh = chunk("fresh.md", vec![1.0, 0.0, 0.0, 0.0]);
+            // 2100-01-01
+            fresh.doc_modified_at = Some(4_102_444_800_000);
+            // Saved since it was indexed, but with the same text
+            let mut touched = chunk("touched.md", vec![1.0, 0.0, 0.0, 0.0]);
+            touched.doc_modified_at = Some(1);
+            touched.doc_hash = Some(crate::search::vector_store::content_hash(
+                "Quarterly roadmap",
+            ));
+            store
+                .upsert(vec![old, old_second, fresh, touched])
+                .await
+                .unwrap();
+
+            unreachable_embedding(&mut config);
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+            let paths = ["old.md", "fresh.md", "new.md", "touched.md"].map(str::to_string);
+            let summaries = indexer.doc_summaries(&paths).await.unwrap();
+
+            let summary = |path: &str| summaries.iter().find(|s| s.path == path).unwrap();
+            assert_eq!(summaries.len(), 4);
+            assert!(summary("old.md").indexed && summary("old.md").stale);
+            assert_eq!(summary("old.md").chunks, 2);
+            assert!(summary("fresh.md").indexed && !summary("fresh.md").stale);
+            assert!(!summary("new.md").indexed && !summary("new.md").stale);
+            assert_eq!(summary("new.md").chunks, 0);
+            assert!(summary("touched.md").indexed && !summary("touched.md").stale);
+
+            // The vault statistics count the same docs as stale
+            let docs: Vec<crate::Doc> = paths.iter().map(|path| doc(dir.path(), path)).collect();
+            let coverage = indexer.coverage(&docs).await.unwrap();
+            assert_eq!(
+                (coverage.indexed, coverage.stale, coverage.missing),
+                (2, 1, 1)
+            );
+        }
+
+        #[tokio::test]
+        async fn test_build_takes_vectors_from_the_embedding_cache() {
+            let (dir, _, mut config) = test_index().await;
+            let content = "# Roadmap\n\nShip it.\n";
+            std::fs::write(dir.path().join("roadmap.md"), content).unwrap();
+
+            unreachable_embedding(&mut config);
+            config.embedding.retry_max_attempts = 1;
+
+            let chunks = Chunker::for_config(&config).chunk(content, "roadmap.md");
+            let vector = [1.0, 0.0, 0.0, 0.0];
+            let keys: Vec<String> = chunks
+                .iter()
+                .map(|c| super::super::embedding_cache::chunk_key(&c.content))
+                .collect();
+            super::super::embedding_cache::EmbeddingCache::for_config(&config)
+                .unwrap()
+                .put_many(keys.iter().map(|key| (key.as_str(), &vector[..])))
+                .unwrap();
+
+            let doc = doc(dir.path(), "roadmap.md");
+            let mut indexer = Indexer::new(config, dir.path().to_path_buf())
+                .await
+                .unwrap();
+            let stats = indexer.build_all(vec![doc.clone()]).await.unwrap();
+            assert_eq!((stats.cache_hits, stats.cache_misses), (chunks.len(), 0));
+            assert_eq!(stats.total_chunks, chunks.len());
+
+            // A forced build asks the provider for every vector again
+            let forced = indexer
+                .build_all_with_progress(
+                    vec![doc],
+                    |_| {},
+                    &std::sync::atomic::AtomicBool::new(false),
+                    true,
+                )
+                .await;
+            assert!(forced.is_err());
+        }
+
+        #[test]
+        fn test_embedding_cache_empties_when_the_model_changes() {
+            use super::super::embedding_cache::{chunk_key, EmbeddingCache};
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("embedding-cache.db");
+            // Line endings and surrounding whitespace don't change the key
+            let key = chunk_key("Ship it.\r\n");
+            assert_eq!(key, chunk_key("Ship it.\n"));
+
+            let cache = EmbeddingCache::open(&path, "openai:small:4").unwrap();
+            cache.put_many([(key.as_str(), &[1.0, 0.5][..])]).unwrap();
+            let found = cache.get_many(&[key.clone(), "other".to_string()]).unwrap();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[&key], vec![1.0, 0.5]);
+            drop(cache);
+
+            let cache = EmbeddingCache::open(&path, "openai:small:4").unwrap();
+            assert_eq!(cache.get_many(&[key.clone()]).unwrap().len(), 1);
+            drop(cache);
+            let cache = EmbeddingCache::open(&path, "openai:large:4").unwrap();
+            assert!(cache.get_many(&[key]).unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_store_rejects_vectors_of_another_size() {
+            let (dir, mut store, _) = test_index().await;
+            let lancedb_path = dir.path().join("lancedb");
+            store
+                .upsert(vec![chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0])])
+                .await
+                .unwrap();
+
+            // As after switching to a model with smaller vectors
+            let mut store = VectorStore::new(lancedb_path, 2);
+            store.initialize().await.unwrap();
+            let upserted = store
+                .upsert(vec![chunk("plans/other.md", vec![1.0, 0.0])])
+                .await;
+            assert!(matches!(
+                upserted,
+                Err(SearchError::DimensionMismatch {
+                    index: 4,
+                    embedding: 2
+                })
+            ));
+            assert!(matches!(
+                store.search(&[1.0, 0.0], 10).await,
+                Err(SearchError::DimensionMismatch { .. })
+            ));
+
+            store.reset().await.unwrap();
+            store
+                .upsert(vec![chunk("plans/other.md", vec![1.0, 0.0])])
+                .await
+                .unwrap();
+            assert_eq!(store.search(&[1.0, 0.0], 10).await.unwrap().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_searcher_refuses_index_built_for_another_model() {
+            let (dir, mut store, _) = test_index().await;
+            let lancedb_path = dir.path().join("lancedb");
+            let metadata_path = dir.path().join("index-metadata.json");
+            store
+                .upsert(vec![chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0])])
+                .await
+                .unwrap();
+            // As a build records it
+            std::fs::write(
+                &metadata_path,
+                r#"{ "embeddingModel": "text-embedding-3-small", "embeddingDimensions": 4 }"#,
+            )
+            .unwrap();
+
+            let config = |model: &str, dimensions| {
+                let mut config = SearchConfig::default();
+                config.embedding.api_key = Some("test".to_string());
+                config.embedding.model = model.to_string();
+                config.embedding.dimensions = dimensions;
+                config.paths.lancedb_path = Some(lancedb_path.clone());
+                config.paths.index_metadata_path = Some(metadata_path.clone());
+                config
+            };
+            assert!(Searcher::new(config("text-embedding-3-small", 4))
+                .await
+                .is_ok());
+
+            let error = Searcher::new(config("text-embedding-3-small", 8))
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(
+                error,
+                SearchError::IndexModelMismatch {
+                    index_dimensions: 4,
+                    dimensions: 8,
+                    ..
+                }
+            ));
+            assert!(error.to_string().contains("clean_search_index"));
+            assert!(matches!(
+                Searcher::new(config("text-embedding-3-large", 4)).await,
+                Err(SearchError::IndexModelMismatch { .. })
+            ));
+
+            // Keyword-only search doesn't compare vectors
+            let mut keyword_only = config("text-embedding-3-large", 8);
+            keyword_only.embedding.api_key = None;
+            assert!(Searcher::new(keyword_only).await.is_ok());
+        }
+
+        #[tokio::test]
+        async fn test_chunks_for_file_previews_stored_chunks() {
+            let (_dir, mut store, _) = test_index().await;
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("plans/other.md", vec![0.0, 1.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let previews = store.chunks_for_file("plans/roadmap.md").await.unwrap();
+            assert_eq!(previews.len(), 1);
+            let preview = &previews[0];
+            assert_eq!(preview.id, "plans/roadmap.md#0");
+            assert_eq!(preview.content, "Quarterly roadmap");
+            assert_eq!(preview.tokens, 5);
+            assert_eq!(preview.content_hash.len(), 16);
+            assert!(preview.indexed_at.is_some());
+            assert!(store
+                .chunks_for_file("missing.md")
+                .await
+                .unwrap()
+                .is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_search_without_api_key_falls_back_to_keyword() {
+            let (_dir, mut store, config) = test_index().await;
+            let mut other = chunk("notes/groceries.md", vec![0.0, 1.0, 0.0, 0.0]);
+            other.content = "Milk and eggs".to_string();
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    other,
+                ])
+                .await
+                .unwrap();
+
+            let searcher = Searcher::new(config).await.unwrap();
+            let results = searcher
+                .search(SearchOptions {
+                    query: "roadmap".to_string(),
+                    mode: Some(SearchMode::Hybrid),
+                    aggregate_by: Some(AggregateBy::Content),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(results.mode.as_deref(), Some("keyword"));
+            assert_eq!(results.count, 1);
+            assert_eq!(results.results[0].file_path, "plans/roadmap.md");
+        }
+
+        #[tokio::test]
+        async fn test_vector_hits_carry_similarity_regardless_of_length() {
+            let (_dir, mut store, _) = test_index().await;
+            store
+                .upsert(vec![
+                    chunk("same.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("orthogonal.md", vec![0.0, 1.0, 0.0, 0.0]),
+                    chunk("opposite.md", vec![-1.0, 0.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let hits = store.search(&[3.0, 0.0, 0.0, 0.0], 10).await.unwrap();
+            let similarity = |path: &str| {
+                let hit = hits.iter().find(|hit| hit.file_path == path).unwrap();
+                assert_eq!(hit.similarity, Some(hit.score));
+                hit.score
+            };
+            assert!((similarity("same.md") - 1.0).abs() < 1e-4);
+            assert!((similarity("orthogonal.md") - 0.5).abs() < 1e-4);
+            assert!(similarity("opposite.md").abs() < 1e-4);
+        }
+
+        #[test]
+        fn test_similarity_is_the_same_under_every_metric() {
+            use super::super::vector_store::similarity;
+            use lancedb::DistanceType;
+
+            // Unit vectors at 60 degrees: cos = 0.5
+            assert!((similarity(0.5, DistanceType::Cosine) - 0.75).abs() < 1e-6);
+            assert!((similarity(1.0, DistanceType::L2) - 0.75).abs() < 1e-6);
+            assert!((similarity(0.5, DistanceType::Dot) - 0.75).abs() < 1e-6);
+            assert_eq!(similarity(-0.1, DistanceType::Cosine), 1.0);
+            assert_eq!(similarity(f32::NAN, DistanceType::Cosine), 0.0);
+        }
+
+        #[tokio::test]
+        async fn test_quoted_phrase_is_required_by_keyword_search() {
+            let (_dir, mut store, config) = test_index().await;
+            let mut exact = chunk("ops/deploy.md", vec![1.0, 0.0, 0.0, 0.0]);
+            exact.content = "Set OPENAI_API_KEY before the deploy".to_string();
+            let mut scattered = chunk("ops/keys.md", vec![0.0, 1.0, 0.0, 0.0]);
+            scattered.content = "The API key for OpenAI lives in the vault".to_string();
+            store.upsert(vec![exact, scattered]).await.unwrap();
+
+            let searcher = Searcher::new(config).await.unwrap();
+            let search = |query: &str| SearchOptions {
+                query: query.to_string(),
+                mode: Some(SearchMode::Keyword),
+                aggregate_by: Some(AggregateBy::Content),
+                ..Default::default()
+            };
+            let paths = |results: SearchResults| -> Vec<String> {
+                results
+                    .results
+                    .into_iter()
+                    .map(|hit| hit.file_path)
+                    .collect()
+            };
+
+            let results = searcher.search(search("openai api key")).await.unwrap();
+            assert_eq!(results.count, 2);
+            let results = searcher
+                .search(search("\"openai_api_key\" deploy"))
+                .await
+                .unwrap();
+            assert_eq!(paths(results), vec!["ops/deploy.md"]);
+            let results = searcher
+                .search(search("\"key for  OpenAI\""))
+                .await
+                .unwrap();
+            assert_eq!(paths(results), vec!["ops/keys.md"]);
+        }
+
+        #[tokio::test]
+        async fn test_hybrid_fusion_reports_each_side_score() {
+            let (_dir, mut store, _) = test_index().await;
+            store
+                .upsert(vec![
+                    chunk("a.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("b.md", vec![0.0, 1.0, 0.0, 0.0]),
+                    chunk("c.md", vec![0.0, 0.0, 1.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+            let hits = store.get_all_chunks().await.unwrap();
+            let hit = |path: &str, score: f32| SearchHit {
+                score,
+                ..hits.iter().find(|h| h.file_path == path).unwrap().clone()
+            };
+            let fuse = |weights| {
+                Searcher::rrf_fusion(
+                    vec![hit("a.md", 0.8), hit("b.md", 0.6)],
+                    vec![hit("c.md", 1.0), hit("a.md", 0.4)],
+                    10,
+                    weights,
+                )
+            };
+            let paths = |fused: &[SearchHit]| -> Vec<String> {
+                fused.iter().map(|h| h.file_path.clone()).collect()
+            };
+
+            // Found by both first; the heavier side's other hit next
+            let fused = fuse((0.1, 0.9));
+            assert_eq!(paths(&fused), vec!["a.md", "c.md", "b.md"]);
+            assert_eq!(fused[0].matched_by, MatchType::Hybrid);
+            assert!((fused[0].score - (0.1 / 61.0 + 0.9 / 62.0)).abs() < 1e-6);
+            assert_eq!(fused[0].vector_score, Some(0.8));
+            assert_eq!(fused[0].keyword_score, Some(0.4));
+            assert_eq!(fused[1].matched_by, MatchType::Keyword);
+            assert_eq!(fused[1].vector_score, None);
+
+            let fused = fuse((0.9, 0.1));
+            assert_eq!(paths(&fused), vec!["a.md", "b.md", "c.md"]);
+            assert_eq!(fused[1].matched_by, MatchType::Vector);
+        }
+
+        #[tokio::test]
+        async fn test_deleted_doc_leaves_no_hits() {
+            let (dir, mut store, config) = test_index().await;
+            std::fs::create_dir_all(dir.path().join("plans")).unwrap();
+            std::fs::write(dir.path().join("plans/roadmap.md"), "Quarterly roadmap").unwrap();
+            std::fs::write(dir.path().join("plans/goals.md"), "Quarterly roadmap").unwrap();
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("plans/goals.md", vec![0.0, 1.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let search = |config: SearchConfig| {
+                let root = dir.path().to_path_buf();
+                async move {
+                    Searcher::new(config)
+                        .await
+                        .unwrap()
+                        .with_contexts_root(root)
+                        .search(SearchOptions {
+                            query: "roadmap".to_string(),
+                            mode: Some(SearchMode::Keyword),
+                            aggregate_by: Some(AggregateBy::Content),
+                            ..Default::default()
+                        })
+                        .await
+                        .unwrap()
+                }
+            };
+            assert_eq!(search(config.clone()).await.count, 2);
+
+            // Deleted in the app: its chunks leave the index
+            std::fs::remove_file(dir.path().join("plans/roadmap.md")).unwrap();
+            let mut with_key = config.clone();
+            unreachable_embedding(&mut with_key);
+            Indexer::new(with_key, dir.path().to_path_buf())
+                .await
+                .unwrap()
+                .remove_file("plans/roadmap.md")
+                .await
+                .unwrap();
+            let results = search(config.clone()).await;
+            assert_eq!(results.count, 1);
+            assert_eq!(results.results[0].file_path, "plans/goals.md");
+            assert_eq!(results.stale_hits_dropped, None);
+
+            // Deleted behind the app's back: its hits are dropped at search
+            std::fs::remove_file(dir.path().join("plans/goals.md")).unwrap();
+            let results = search(config).await;
+            assert_eq!(results.count, 0);
+            assert_eq!(results.stale_hits_dropped, Some(1));
+        }
+
+        #[tokio::test]
+        async fn test_min_score_drops_unrelated_vector_hits() {
+            let (_dir, mut store, _) = test_index().await;
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    chunk("plans/goals.md", vec![0.9, 0.1, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            // A query pointing nowhere near either doc
+            let unrelated = |mut hits: Vec<SearchHit>| {
+                for hit in &mut hits {
+                    hit.matched_by = MatchType::Vector;
+                }
+                hits
+            };
+            let mut hits = unrelated(store.search(&[0.0, 0.0, 1.0, 0.0], 10).await.unwrap());
+            assert_eq!(hits.len(), 2);
+            assert_eq!(Searcher::drop_weak_matches(&mut hits, 0.9), Some(true));
+            assert!(hits.is_empty());
+
+            // A related one keeps what clears the threshold, fewer than the limit
+            let mut hits = unrelated(store.search(&[1.0, 0.0, 0.0, 0.0], 10).await.unwrap());
+            assert_eq!(Searcher::drop_weak_matches(&mut hits, 0.999), None);
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].file_path, "plans/roadmap.md");
+        }
+
+        #[tokio::test]
+        async fn test_cursor_pages_show_each_result_once() {
+            let (_dir, mut store, config) = test_index().await;
+            let second_chunk = Chunk {
+                id: "plans/roadmap.md#1".to_string(),
+                chunk_index: 1,
+                line_start: Some(3),
+                line_end: Some(3),
+                byte_start: Some(19),
+                byte_end: Some(36),
+                ..chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0])
+            };
+            store
+                .upsert(vec![
+                    chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0]),
+                    second_chunk,
+                    chunk("plans/goals.md", vec![0.0, 1.0, 0.0, 0.0]),
+                    chunk("notes/ideas.md", vec![0.0, 0.0, 1.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let searcher = Searcher::new(config).await.unwrap();
+            let searcher = &searcher;
+            let pages = move |aggregate_by| async move {
+                let mut seen = Vec::new();
+                let mut cursor = None;
+                loop {
+                    let results = searcher
+                        .search(SearchOptions {
+                            query: "roadmap".to_string(),
+                            mode: Some(SearchMode::Keyword),
+                            aggregate_by: Some(aggregate_by),
+                            limit: Some(1),
+                            cursor,
+                            ..Default::default()
+                        })
+                        .await
+                        .unwrap();
+                    let page: Vec<(String, Option<usize>)> = results
+                        .results
+                        .iter()
+                        .map(|hit| (hit.file_path.clone(), hit.line_start))
+                        .collect();
+                    seen.push(page);
+                    cursor = results.next_cursor;
+                    if cursor.is_none() {
+                        return (seen, results.total_estimate);
+                    }
+                }
+            };
+
+            // The doc with two matching chunks turns up on one page only
+            let (doc_pages, total) = pages(AggregateBy::Doc).await;
+            assert_eq!(doc_pages.len(), 3);
+            let mut docs: Vec<String> = doc_pages
+                .concat()
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect();
+            docs.sort();
+            assert_eq!(
+                docs,
+                ["notes/ideas.md", "plans/goals.md", "plans/roadmap.md"]
+            );
+            assert_eq!(total, Some(3));
+
+            // Its two chunks share a page, which runs past the limit for them
+            let (chunk_pages, total) = pages(AggregateBy::Content).await;
+            assert_eq!(chunk_pages.len(), 3);
+            assert!(chunk_pages.iter().any(|page| {
+                page.len() == 2 && page.iter().all(|(path, _)| path == "plans/roadmap.md")
+            }));
+            let mut chunks = chunk_pages.concat();
+            assert_eq!(chunks.len(), 4);
+            chunks.sort();
+            chunks.dedup();
+            assert_eq!(chunks.len(), 4);
+            assert_eq!(total, Some(4));
+
+            // An offset past any result list doesn't fetch up to it
+            let results = searcher
+                .search(SearchOptions {
+                    query: "roadmap".to_string(),
+                    mode: Some(SearchMode::Keyword),
+                    cursor: Some(format!("{}:gone", usize::MAX)),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert!(results.results.is_empty());
+            assert_eq!(results.next_cursor, None);
+        }
+
+        #[tokio::test]
+        async fn test_group_by_doc_caps_chunks_per_doc() {
+            let (_dir, mut store, config) = test_index().await;
+            let roadmap_chunk = |index: usize| Chunk {
+                id: format!("plans/roadmap.md#{}", index),
+                chunk_index: index,
+                line_start: Some(1 + index * 2),
+                line_end: Some(1 + index * 2),
+                byte_start: Some(index * 19),
+                byte_end: Some(index * 19 + 17),
+                ..chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0])
+            };
+            store
+                .upsert(vec![
+                    roadmap_chunk(0),
+                    roadmap_chunk(1),
+                    roadmap_chunk(2),
+                    chunk("plans/goals.md", vec![0.0, 1.0, 0.0, 0.0]),
+                ])
+                .await
+                .unwrap();
+
+            let searcher = Searcher::new(config).await.unwrap();
+            let options = SearchOptions {
+                query: "roadmap".to_string(),
+                mode: Some(SearchMode::Keyword),
+                aggregate_by: Some(AggregateBy::Content),
+                ..Default::default()
+            };
+
+            // Flat unless asked
+            let flat = searcher.search(options.clone()).await.unwrap();
+            assert_eq!(flat.count, 4);
+            assert!(flat.groups.is_none());
+
+            let grouped = searcher
+                .search(SearchOptions {
+                    group_by_doc: true,
+                    max_chunks_per_doc: Some(2),
+                    ..options
+                })
+                .await
+                .unwrap();
+            let groups = grouped.groups.unwrap();
+            // Equal best scores fall back to path order
+            let docs: Vec<(&str, usize, usize)> = groups
+                .iter()
+                .map(|group| (group.file_path.as_str(), group.hit_count, group.hits.len()))
+                .collect();
+            assert_eq!(docs, [("plans/goals.md", 1, 1), ("plans/roadmap.md", 3, 2)]);
+            let lines: Vec<Option<usize>> =
+                groups[1].hits.iter().map(|hit| hit.line_start).collect();
+            assert_eq!(lines, [Some(1), Some(3)]);
+            assert_eq!(grouped.count, 3);
+            assert_eq!(grouped.results[0].file_path, "plans/goals.md");
+        }
+
+        #[tokio::test]
+        async fn test_keyword_search_ignores_min_score() {
+            let (_dir, mut store, config) = test_index().await;
+            store
+                .upsert(vec![chunk("plans/roadmap.md", vec![1.0, 0.0, 0.0, 0.0])])
+                .await
+                .unwrap();
+
+            let searcher = Searcher::new(config).await.unwrap();
+            let results = searcher
+                .search(SearchOptions {
+                    query: "roadmap".to_string(),
+                    mode: Some(SearchMode::Keyword),
+                    min_score: Some(0.99),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(results.count, 1);
+            assert_eq!(results.results[0].similarity, None);
+            assert_eq!(results.no_confident_match, None);
+        }
+    }
+
+    mod highlight_tests {
+        use super::super::super::highlight::{highlights, snippet};
+
+        #[test]
+        fn test_highlights_match_whole_words_ignoring_case() {
+            let content = "Roadmap: ship the roadmap. A plan, roadmaps later.";
+            assert_eq!(highlights(content, "ROADMAP a"), vec![(0, 7), (18, 25)]);
+            assert!(highlights(content, "nothing here").is_empty());
+        }
+
+        #[test]
+        fn test_highlights_keep_to_char_boundaries() {
+            let content = "我们的搜索功能很快，搜索 index 也快";
+            let spans = highlights(content, "搜索 Index");
+            assert_eq!(spans.len(), 3);
+            for (start, end) in &spans {
+                assert!(content.is_char_boundary(*start) && content.is_char_boundary(*end));
+            }
+            assert_eq!(&content[spans[0].0..spans[0].1], "搜索");
+            assert_eq!(&content[spans[2].0..spans[2].1], "index");
+
+            // Overlapping 2-grams merge into one range
+            assert_eq!(
+                highlights(content, "搜索功能")
+                    .iter()
+                    .map(|&(start, end)| &content[start..end])
+                    .collect::<Vec<_>>(),
+                vec!["搜索功能", "搜索"]
+            );
+        }
+
+        #[test]
+        fn test_snippet_centers_on_highlights() {
+            let content = format!(
+                "{}deadline moved{}",
+                "前言。".repeat(100),
+                "后记。".repeat(100)
+            );
+            let spans = highlights(&content, "deadline");
+            let text = snippet(&content, &spans);
+            assert!(text.starts_with('…') && text.ends_with('…'));
+            assert!(text.contains("<mark>deadline</mark> moved"));
+            assert!(
+                text.replace("<mark>", "")
+                    .replace("</mark>", "")
+                    .chars()
+                    .count()
+                    <= 202
+            );
+
+            // Short content is returned whole
+            assert_eq!(snippet(" Ship it. ", &[]), "Ship it.");
+        }
+
+        #[test]
+        fn test_snippet_picks_sentence_with_most_terms() {
+            let content = format!(
+                "The launch date is set. {}Launch review: the launch date moved to May. {}",
+                "Filler text goes here. ".repeat(10),
+                "Closing words come last. ".repeat(10)
+            );
+            let spans = highlights(&content, "launch date");
+            let text = snippet(&content, &spans);
+            assert!(text.starts_with('…') && text.ends_with('…'));
+            assert!(text.contains(
+                "<mark>Launch</mark> review: the <mark>launch</mark> <mark>date</mark> moved to May."
+            ));
+
+            // No terms: the start of the content, unmarked
+            let text = snippet(&content, &[]);
+            assert!(text.starts_with("The launch date is set.") && text.ends_with('…'));
+            assert!(!text.contains("<mark>"));
+        }
+    }
+
+    mod usage_tests {
+        use super::*;
+        use std::collections::BTreeMap;
+
+        #[test]
+        fn test_usage_ledger_aggregates_days_and_months() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("embedding-usage.json");
+            std::fs::write(
+                &path,
+                r#"{
+                    "2026-01-30": { "text-embedding-3-small": { "tokens": 1000000, "requests": 2 } },
+                    "2026-01-31": { "nomic-embed-text": { "tokens": 500, "requests": 1 } },
+                    "2026-02-01": { "text-embedding-3-small": { "tokens": 500000, "requests": 1 } }
+                }"#,
+            )
+            .unwrap();
+            let ledger = UsageLedger::new(path);
+
+            let usage = ledger.summary(&BTreeMap::new());
+            assert_eq!(usage.daily.len(), 3);
+            let months: Vec<_> = usage.monthly.iter().map(|m| m.period.as_str()).collect();
+            assert_eq!(months, vec!["2026-01", "2026-02"]);
+            assert_eq!(usage.monthly[0].totals.tokens, 1_000_500);
+            assert_eq!(usage.monthly[0].totals.requests, 3);
+            assert!((usage.cost_usd - 0.03).abs() < 1e-9);
+            assert_eq!(usage.unpriced_models, vec!["nomic-embed-text"]);
+
+            let prices = BTreeMap::from([("nomic-embed-text".to_string(), 1000.0)]);
+            let usage = ledger.summary(&prices);
+            assert!((usage.cost_usd - 0.53).abs() < 1e-9);
+            assert!(usage.unpriced_models.is_empty());
+        }
+
+        #[test]
+        fn test_usage_ledger_records_and_resets() {
+            let dir = tempfile::tempdir().unwrap();
+            let ledger = UsageLedger::new(dir.path().join("usage").join("ledger.json"));
+            ledger.record("m", 10);
+            ledger.record("m", 5);
+
+            let usage = ledger.summary(&BTreeMap::new());
+            assert_eq!(usage.daily.len(), 1);
+            assert_eq!(usage.daily[0].models[0].model, "m");
+            assert_eq!(
+                usage.totals,
+                UsageTotals {
+                    tokens: 15,
+                    requests: 2
+                }
+            );
+
+            ledger.reset().unwrap();
+            ledger.reset().unwrap();
+            assert_eq!(
+                ledger.summary(&BTreeMap::new()).totals,
+                UsageTotals::default()
+            );
+        }
+
+        #[test]
+        fn test_usage_ledger_moves_a_malformed_file_aside() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("ledger.json");
+            std::fs::write(&path, "{\"2024-01-01\": {\"m\": ").unwrap();
+            let ledger = UsageLedger::new(path.clone());
+            ledger.record("m", 10);
+
+            assert_eq!(ledger.summary(&BTreeMap::new()).totals.tokens, 10);
+            let aside: Vec<String> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("ledger.json.corrupt-"))
+                .collect();
+            assert_eq!(aside.len(), 1);
+            assert_eq!(
+                std::fs::read_to_string(dir.path().join(&aside[0])).unwrap(),
+                "{\"2024-01-01\": {\"m\": "
+            );
+        }
+
+        #[test]
+        fn test_usage_ledger_prices_unknown_models_with_recorded_estimates() {
+            let dir = tempfile::tempdir().unwrap();
+            let ledger = UsageLedger::new(dir.path().join("chat-usage.json"));
+            ledger.record_with_cost("gpt-4o", 1000, Some(0.25));
+            ledger.record_with_cost("gpt-4o", 1000, Some(0.5));
+            ledger.record_with_cost("llama3", 1000, None);
+
+            let usage = ledger.summary(&BTreeMap::new());
+            assert!((usage.cost_usd - 0.75).abs() < 1e-9);
+            assert_eq!(usage.unpriced_models, vec!["llama3"]);
+            assert_eq!(usage.totals.requests, 3);
+        }
+    }
 }
@@ -13,6 +13,9 @@ pub struct Chunk {
     pub content: String,
     /// Heading path (e.g., "## Background > ### Goals")
     pub heading_path: String,
+    /// Headings the chunk sits under, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub headings: Vec<String>,
     /// Optional section title (for ideas entry title)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub section_title: Option<String>,
@@ -31,13 +34,61 @@ pub struct Chunk {
     /// Ideas box name (e.g. "inbox")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub idea_box: Option<String>,
+    /// Source document's modified time when indexed (ms since epoch)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub doc_modified_at: Option<u64>,
+    /// Hash of the whole source document's text when indexed, so rebuilds
+    /// can skip docs that haven't changed
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub doc_hash: Option<String>,
     /// Index of this chunk within the document
     pub chunk_index: usize,
+    /// First and last line of the chunk in the source (1-indexed)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub line_start: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub line_end: Option<usize>,
+    /// Byte range of the chunk in the source (end exclusive)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub byte_start: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub byte_end: Option<usize>,
+    /// Language of the first code block in the chunk, from its fence
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub code_language: Option<String>,
     /// Embedding vector
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub vector: Vec<f32>,
 }
 
+/// A stored chunk as shown when inspecting how a doc was indexed, without
+/// its vector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkPreview {
+    pub id: String,
+    pub chunk_index: usize,
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_start: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_end: Option<usize>,
+    /// Estimated tokens the embedding model saw
+    pub tokens: usize,
+    /// Hash of the content, to compare chunks across builds
+    pub content_hash: String,
+    /// Source doc's modified time when indexed (ms since epoch)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc_modified_at: Option<u64>,
+    /// When the chunk was written; unknown for older indexes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexed_at: Option<u64>,
+}
+
 /// A text chunk before embedding is generated
 #[derive(Debug, Clone)]
 pub struct TextChunk {
@@ -45,10 +96,18 @@ pub struct TextChunk {
     pub content: String,
     /// Heading path
     pub heading_path: String,
+    /// Headings the chunk sits under, outermost first
+    pub headings: Vec<String>,
     /// Start line number (1-indexed)
     pub start_line: usize,
     /// End line number (1-indexed)
     pub end_line: usize,
+    /// Byte offset in the source where the chunk starts
+    pub start_byte: usize,
+    /// Byte offset in the source just past the chunk's end
+    pub end_byte: usize,
+    /// Language of the first code block in the chunk, from its fence
+    pub code_language: Option<String>,
 }
 
 /// Search mode
@@ -83,6 +142,7 @@ pub enum AggregateBy {
 pub enum MatchType {
     Vector,
     Keyword,
+    /// Found by both sides of a hybrid search
     #[serde(rename = "vector+keyword")]
     Hybrid,
 }
@@ -101,6 +161,51 @@ pub struct SearchOptions {
     pub aggregate_by: Option<AggregateBy>,
     /// Filter by document type: "doc" | "idea"
     pub doc_type: Option<String>,
+    /// Named embedding profile whose index to query (default: main index)
+    pub embedding_profile: Option<String>,
+    /// Restrict results to docs under this folder, ignoring case. Applied
+    /// before ranking, so `limit` results can all come from the folder.
+    /// Also selects the embedding profile assigned to the folder when none
+    /// is given.
+    pub folder_prefix: Option<String>,
+    /// Bypass in-memory caches for this query (debugging live retrieval).
+    /// Fresh data is used for this query only and never written back.
+    #[serde(default, alias = "no_cache")]
+    pub no_cache: bool,
+    /// Re-order the top results with the configured chat model. Applied by
+    /// the app after retrieval; `Searcher` itself ignores it.
+    #[serde(default)]
+    pub rerank: bool,
+    /// Ask the app to generate paraphrases of the query into
+    /// `query_variants`. Skipped when no AI provider is configured.
+    #[serde(default)]
+    pub expand_query: bool,
+    /// Extra phrasings of the query. Each is searched by vector alongside
+    /// the query and the lists are merged with reciprocal-rank fusion.
+    #[serde(default)]
+    pub query_variants: Vec<String>,
+    /// Drop vector hits whose `similarity` is below this (0-1). Hits that
+    /// matched by keyword are kept, and keyword mode ignores it.
+    #[serde(default)]
+    pub min_score: Option<f32>,
+    /// How much the vector ranking counts in hybrid search, relative to
+    /// `keyword_weight`. Unset weights follow the configured `hybrid_alpha`.
+    #[serde(default)]
+    pub vector_weight: Option<f32>,
+    /// How much the keyword ranking counts in hybrid search, relative to
+    /// `vector_weight`
+    #[serde(default)]
+    pub keyword_weight: Option<f32>,
+    /// Continue from a previous page: its `next_cursor`, passed back as is
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Collect chunk hits into `groups` by document, best document first;
+    /// `limit` then counts documents. Takes the place of `aggregate_by`.
+    #[serde(default)]
+    pub group_by_doc: bool,
+    /// Most chunks kept per document when grouping (default 3)
+    #[serde(default)]
+    pub max_chunks_per_doc: Option<usize>,
 }
 
 impl SearchOptions {
@@ -115,6 +220,10 @@ impl SearchOptions {
     pub fn aggregate_by(&self) -> AggregateBy {
         self.aggregate_by.unwrap_or_default()
     }
+
+    pub fn max_chunks_per_doc(&self) -> usize {
+        self.max_chunks_per_doc.unwrap_or(3).max(1)
+    }
 }
 
 /// A single search result
@@ -125,11 +234,14 @@ pub struct SearchHit {
     pub file_path: String,
     /// Display name for the document
     pub display_name: String,
-    /// Matched content snippet
+    /// Full text of the matched chunk
     pub content: String,
     /// Heading path within the document
     #[serde(skip_serializing_if = "Option::is_none")]
     pub heading_path: Option<String>,
+    /// Headings the matched chunk sits under, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub headings: Vec<String>,
     /// Section title
     #[serde(skip_serializing_if = "Option::is_none")]
     pub section_title: Option<String>,
@@ -139,10 +251,33 @@ pub struct SearchHit {
     /// End line number
     #[serde(skip_serializing_if = "Option::is_none")]
     pub line_end: Option<usize>,
+    /// Byte offset in the source where the matched chunk starts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub byte_start: Option<usize>,
+    /// Byte offset in the source just past the matched chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub byte_end: Option<usize>,
+    /// Language of the first code block in the matched chunk, for syntax
+    /// highlighting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_language: Option<String>,
     /// Relevance score (0-1)
     pub score: f32,
+    /// How close the query and the best matching chunk are by vector, from
+    /// 0 (opposite) to 1 (same direction); unset for keyword-only matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
     /// How this result was matched
     pub matched_by: MatchType,
+    /// Vector similarity (0-1) the vector side found a hybrid hit with;
+    /// unset when it didn't find the hit. Aggregated results carry their
+    /// top chunk's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_score: Option<f32>,
+    /// BM25 score, normalized to the best keyword hit (0-1), the keyword
+    /// side found a hybrid hit with; unset when it didn't find the hit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword_score: Option<f32>,
     /// Number of hits in this document (for aggregated results)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hit_count: Option<usize>,
@@ -170,6 +305,40 @@ pub struct SearchHit {
     /// Ideas box name (e.g. "inbox")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub idea_box: Option<String>,
+    /// Source document's modified time when indexed (ms since epoch)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc_modified_at: Option<u64>,
+    /// Relevance assigned by the rerank model (0-1), when reranked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
+    /// Which of the query and its variants retrieved this hit by vector,
+    /// the one that ranked it highest first (only set for expanded queries)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matched_queries: Vec<String>,
+    /// Byte ranges `[start, end)` in `content` where the query's keywords
+    /// appear, on char boundaries (set on returned results only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<(usize, usize)>,
+    /// About 200 characters of `content` around its sentence with the most
+    /// highlights, each wrapped in `<mark>`…`</mark>` (set on returned
+    /// results only). `content` stays whole.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// A document's best chunk hits, for searches grouped by document
+#[derive(Debug, Clone, Serialize)]
+pub struct DocGroup {
+    /// File path of the document
+    pub file_path: String,
+    /// Display name for the document
+    pub display_name: String,
+    /// Score of the document's best chunk
+    pub score: f32,
+    /// Chunks of the document that matched, including any left out of `hits`
+    pub hit_count: usize,
+    /// The best `max_chunks_per_doc` chunks, best first
+    pub hits: Vec<SearchHit>,
 }
 
 /// Search results response
@@ -194,6 +363,29 @@ pub struct SearchResults {
     /// Error message if any
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Set when a requested rerank failed and the original order was kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_warning: Option<String>,
+    /// Query variants searched alongside the query
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expanded_queries: Vec<String>,
+    /// Set when there were hits but `min_score` dropped all of them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_confident_match: Option<bool>,
+    /// How many hits were dropped because their doc is no longer on disk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_hits_dropped: Option<usize>,
+    /// Results by document when `group_by_doc` was set; `results` then lists
+    /// the same hits in group order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<DocGroup>>,
+    /// Pass as `cursor` for the page after this one; unset on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Results found through this page and a little past it, so a lower
+    /// bound while there is a next page and exact on the last one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_estimate: Option<usize>,
 }
 
 impl SearchResults {
@@ -206,6 +398,13 @@ impl SearchResults {
             aggregate_by: None,
             index_missing: None,
             error: None,
+            rerank_warning: None,
+            expanded_queries: Vec::new(),
+            no_confident_match: None,
+            stale_hits_dropped: None,
+            groups: None,
+            next_cursor: None,
+            total_estimate: None,
         }
     }
 
@@ -218,6 +417,13 @@ impl SearchResults {
             aggregate_by: None,
             index_missing: None,
             error: Some(error),
+            rerank_warning: None,
+            expanded_queries: Vec::new(),
+            no_confident_match: None,
+            stale_hits_dropped: None,
+            groups: None,
+            next_cursor: None,
+            total_estimate: None,
         }
     }
 
@@ -230,6 +436,13 @@ impl SearchResults {
             aggregate_by: None,
             index_missing: Some(true),
             error: None,
+            rerank_warning: None,
+            expanded_queries: Vec::new(),
+            no_confident_match: None,
+            stale_hits_dropped: None,
+            groups: None,
+            next_cursor: None,
+            total_estimate: None,
         }
     }
 }
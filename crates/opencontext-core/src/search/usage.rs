@@ -0,0 +1,287 @@
+//! Ledger of tokens spent on embedding requests
+//!
+//! Every successful request adds the token count the API reported to the
+//! day's total for its model, in `embedding-usage.json` beside config.json.
+//! Only totals are kept, so the file stays small however much is indexed.
+//! The app keeps a second ledger in the same format for chat requests.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// USD per million tokens for models whose price is known. Prices in the
+/// `EMBEDDING_PRICES` config setting replace these.
+const DEFAULT_PRICES: &[(&str, f64)] = &[
+    ("text-embedding-3-small", 0.02),
+    ("text-embedding-3-large", 0.13),
+    ("text-embedding-ada-002", 0.10),
+];
+
+/// Serializes ledger updates within the process; the indexer and the sync
+/// service record from separate tasks
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Tokens and requests counted for one model or period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageTotals {
+    pub tokens: u64,
+    pub requests: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, other: UsageTotals) {
+        self.tokens += other.tokens;
+        self.requests += other.requests;
+    }
+}
+
+/// What the ledger keeps for a model on a day
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LedgerEntry {
+    #[serde(flatten)]
+    totals: UsageTotals,
+    /// Costs estimated by the caller when recording, for models the ledger
+    /// has no price for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    estimated_cost_usd: Option<f64>,
+}
+
+impl LedgerEntry {
+    fn add(&mut self, other: LedgerEntry) {
+        self.totals.add(other.totals);
+        self.estimated_cost_usd = match (self.estimated_cost_usd, other.estimated_cost_usd) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+        };
+    }
+}
+
+/// Date (YYYY-MM-DD, local time) -> model -> totals
+type Ledger = BTreeMap<String, BTreeMap<String, LedgerEntry>>;
+
+/// Usage of one model in a period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelUsage {
+    pub model: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    /// None if the model's price is unknown
+    pub cost_usd: Option<f64>,
+}
+
+/// Usage in a day (YYYY-MM-DD) or month (YYYY-MM)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsagePeriod {
+    pub period: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    /// Cost of the models with a known price
+    pub cost_usd: f64,
+    pub models: Vec<ModelUsage>,
+}
+
+/// Aggregated ledger, oldest period first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddingUsage {
+    pub daily: Vec<UsagePeriod>,
+    pub monthly: Vec<UsagePeriod>,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    pub cost_usd: f64,
+    /// Models used without a known price, left out of `cost_usd`
+    pub unpriced_models: Vec<String>,
+}
+
+/// The usage ledger file
+#[derive(Debug, Clone)]
+pub struct UsageLedger {
+    path: PathBuf,
+}
+
+impl UsageLedger {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Add a request of `tokens` tokens to today's total for `model`
+    pub fn record(&self, model: &str, tokens: u64) {
+        self.record_with_cost(model, tokens, None);
+    }
+
+    /// Like `record`, also adding `estimated_cost_usd` to the model's cost.
+    /// The estimate is used for models `summary` has no price for.
+    pub fn record_with_cost(&self, model: &str, tokens: u64, estimated_cost_usd: Option<f64>) {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ledger = match self.read() {
+            Ok(ledger) => ledger,
+            // A file that can't be moved aside is left for the user to fix
+            Err(e) if !self.set_aside(&e) => return,
+            Err(_) => Ledger::default(),
+        };
+        ledger
+            .entry(today)
+            .or_default()
+            .entry(model.to_string())
+            .or_default()
+            .add(LedgerEntry {
+                totals: UsageTotals {
+                    tokens,
+                    requests: 1,
+                },
+                estimated_cost_usd,
+            });
+        self.write(&ledger);
+    }
+
+    /// Daily and monthly totals, priced with `prices` (model -> USD per
+    /// million tokens) over the defaults, then with recorded estimates
+    pub fn summary(&self, prices: &BTreeMap<String, f64>) -> EmbeddingUsage {
+        let ledger = self.load();
+        let price = |model: &str| {
+            prices.get(model).copied().or_else(|| {
+                DEFAULT_PRICES
+                    .iter()
+                    .find(|(known, _)| *known == model)
+                    .map(|(_, price)| *price)
+            })
+        };
+
+        let mut months: Ledger = BTreeMap::new();
+        for (date, models) in &ledger {
+            let month = months
+                .entry(date.get(..7).unwrap_or(date).to_string())
+                .or_default();
+            for (model, entry) in models {
+                month.entry(model.clone()).or_default().add(*entry);
+            }
+        }
+        let periods = |ledger: &Ledger| -> Vec<UsagePeriod> {
+            ledger
+                .iter()
+                .map(|(period, models)| {
+                    let models: Vec<ModelUsage> = models
+                        .iter()
+                        .map(|(model, entry)| ModelUsage {
+                            model: model.clone(),
+                            totals: entry.totals,
+                            cost_usd: price(model)
+                                .map(|price| entry.totals.tokens as f64 / 1_000_000.0 * price)
+                                .or(entry.estimated_cost_usd),
+                        })
+                        .collect();
+                    let mut totals = UsageTotals::default();
+                    models.iter().for_each(|usage| totals.add(usage.totals));
+                    UsagePeriod {
+                        period: period.clone(),
+                        totals,
+                        cost_usd: models.iter().filter_map(|usage| usage.cost_usd).sum(),
+                        models,
+                    }
+                })
+                .collect()
+        };
+
+        let monthly = periods(&months);
+        let mut totals = UsageTotals::default();
+        monthly.iter().for_each(|month| totals.add(month.totals));
+        let mut unpriced_models: Vec<String> = monthly
+            .iter()
+            .flat_map(|month| &month.models)
+            .filter(|usage| usage.cost_usd.is_none())
+            .map(|usage| usage.model.clone())
+            .collect();
+        unpriced_models.sort();
+        unpriced_models.dedup();
+        EmbeddingUsage {
+            daily: periods(&ledger),
+            cost_usd: monthly.iter().map(|month| month.cost_usd).sum(),
+            monthly,
+            totals,
+            unpriced_models,
+        }
+    }
+
+    /// Forget all recorded usage
+    pub fn reset(&self) -> std::io::Result<()> {
+        let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// A missing or malformed file reads as empty
+    fn load(&self) -> Ledger {
+        self.read().unwrap_or_default()
+    }
+
+    /// The recorded usage; a missing file reads as empty
+    fn read(&self) -> serde_json::Result<Ledger> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content),
+            Err(_) => Ok(Ledger::default()),
+        }
+    }
+
+    /// Move a malformed ledger to `<name>.corrupt-<time>` so a new one can
+    /// start without losing it. Returns false if it couldn't be moved.
+    fn set_aside(&self, error: &serde_json::Error) -> bool {
+        let aside = self.with_suffix(&format!(
+            ".corrupt-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        match std::fs::rename(&self.path, &aside) {
+            Ok(()) => {
+                log::warn!(
+                    "[Embedding] {} is malformed ({}); moved it to {} and started a new ledger",
+                    self.path.display(),
+                    error,
+                    aside.display()
+                );
+                true
+            }
+            Err(e) => {
+                log::warn!(
+                    "[Embedding] {} is malformed ({}) and could not be moved aside: {}",
+                    self.path.display(),
+                    error,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    fn with_suffix(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    /// Written through a temp file, so a crash mid-write can't leave a
+    /// truncated ledger. Usage is bookkeeping, so a failed write is logged
+    /// rather than failing the request that was already paid for.
+    fn write(&self, ledger: &Ledger) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let content = serde_json::to_string(ledger).map_err(std::io::Error::other)?;
+                let tmp = self.with_suffix(".tmp");
+                std::fs::write(&tmp, content)?;
+                std::fs::rename(&tmp, &self.path)
+            });
+        if let Err(e) = result {
+            log::warn!("[Embedding] Failed to write {}: {}", self.path.display(), e);
+        }
+    }
+}
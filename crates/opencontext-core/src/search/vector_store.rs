@@ -1,28 +1,80 @@
 //! LanceDB vector store
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use arrow_array::{
-    types::Float32Type, FixedSizeListArray, RecordBatch, RecordBatchIterator, StringArray,
-    UInt32Array,
+    types::Float32Type, Array, FixedSizeListArray, Int64Array, RecordBatch, RecordBatchIterator,
+    StringArray, UInt32Array,
 };
 use arrow_schema::{DataType, Field, Schema};
 use futures::TryStreamExt;
-use lancedb::query::{ExecutableQuery, QueryBase};
-use lancedb::{connect, Connection, Table};
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use lancedb::table::{CompactionOptions, NewColumnTransform, OptimizeAction};
+use lancedb::{connect, Connection, DistanceType, Table};
 
+use super::chunker::estimate_tokens;
 use super::error::{SearchError, SearchResult};
-use super::types::{Chunk, MatchType, SearchHit};
+use super::types::{Chunk, ChunkPreview, MatchType, SearchHit};
 
 const TABLE_NAME: &str = "chunks";
 
+/// Column holding the source doc's modified time; absent in indexes built
+/// before it was tracked.
+const DOC_MODIFIED_AT: &str = "doc_modified_at";
+/// Where each chunk sits in its source doc; absent in older indexes too.
+const LINE_START: &str = "line_start";
+const LINE_END: &str = "line_end";
+const BYTE_START: &str = "byte_start";
+const BYTE_END: &str = "byte_end";
+/// When each chunk was written (ms since epoch); absent in older indexes.
+const INDEXED_AT: &str = "indexed_at";
+/// Hash of the source doc's text; absent in older indexes.
+const DOC_HASH: &str = "doc_hash";
+/// Language of the chunk's first code block; absent in older indexes.
+const CODE_LANGUAGE: &str = "code_language";
+/// The chunk's headings as a JSON array; absent in older indexes.
+const HEADINGS: &str = "headings";
+
+/// Metric vector search ranks chunks by. Embedding models are trained for
+/// cosine similarity, and unlike L2 it doesn't depend on vector length.
+/// Part of the indexer's build settings, so changing it rebuilds indexes.
+pub(crate) const SEARCH_DISTANCE: DistanceType = DistanceType::Cosine;
+
+/// Nullable columns added after the first release of the schema, with
+/// their SQL type
+const ADDED_COLUMNS: [(&str, &str); 9] = [
+    (DOC_MODIFIED_AT, "BIGINT"),
+    (LINE_START, "BIGINT"),
+    (LINE_END, "BIGINT"),
+    (BYTE_START, "BIGINT"),
+    (BYTE_END, "BIGINT"),
+    (INDEXED_AT, "BIGINT"),
+    (DOC_HASH, "STRING"),
+    (CODE_LANGUAGE, "STRING"),
+    (HEADINGS, "STRING"),
+];
+
+/// What the index holds for one doc
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexedDoc {
+    /// Hash of the doc's text when indexed; `None` in older indexes
+    pub doc_hash: Option<String>,
+    /// The doc's modified time when indexed (ms since epoch)
+    pub doc_modified_at: Option<u64>,
+    pub chunks: usize,
+}
+
 /// LanceDB vector store for semantic search
 pub struct VectorStore {
     db_path: PathBuf,
     dimensions: usize,
     db: Option<Connection>,
     table: Option<Table>,
+    /// Vector size of the existing table, which may differ from
+    /// `dimensions` when the embedding model changed since it was built
+    table_dimensions: Option<usize>,
 }
 
 impl VectorStore {
@@ -33,6 +85,7 @@ impl VectorStore {
             dimensions,
             db: None,
             table: None,
+            table_dimensions: None,
         }
     }
 
@@ -50,19 +103,28 @@ impl VectorStore {
 
         self.db = Some(db);
 
-        // Try to open existing table
+        // Try to open existing table. Failures past this point mean the
+        // files on disk are damaged, not that the index is missing.
         if let Some(ref db) = self.db {
             let table_names = db
                 .table_names()
                 .execute()
                 .await
-                .map_err(SearchError::Lance)?;
+                .map_err(|e| self.corrupt(e))?;
             if table_names.contains(&TABLE_NAME.to_string()) {
                 let table = db
                     .open_table(TABLE_NAME)
                     .execute()
                     .await
-                    .map_err(SearchError::Lance)?;
+                    .map_err(|e| self.corrupt(e))?;
+                // Reads the latest manifest, which a torn write leaves broken
+                table.count_rows(None).await.map_err(|e| self.corrupt(e))?;
+                Self::ensure_added_columns(&table).await;
+                self.table_dimensions = table
+                    .schema()
+                    .await
+                    .ok()
+                    .and_then(|schema| vector_dimensions(&schema));
                 self.table = Some(table);
             }
         }
@@ -70,6 +132,78 @@ impl VectorStore {
         Ok(())
     }
 
+    fn corrupt(&self, e: lancedb::Error) -> SearchError {
+        SearchError::CorruptIndex {
+            path: self.db_path.clone(),
+            reason: e.to_string(),
+        }
+    }
+
+    /// Move a damaged index directory aside, next to where it was, so a new
+    /// one can be built in its place. Returns where it went, or `None` when
+    /// there was nothing at `db_path`.
+    pub fn archive(db_path: &Path) -> SearchResult<Option<PathBuf>> {
+        if !db_path.exists() {
+            return Ok(None);
+        }
+        let name = db_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "lancedb".to_string());
+        let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+        let archived = db_path.with_file_name(format!("{}.corrupt-{}", name, stamp));
+        std::fs::rename(db_path, &archived)?;
+        log::warn!(
+            "[VectorStore] Moved damaged index to {}",
+            archived.display()
+        );
+        Ok(Some(archived))
+    }
+
+    /// Add columns introduced since an older table was created so new rows
+    /// match the current schema. Existing rows keep nulls until reindexed.
+    async fn ensure_added_columns(table: &Table) {
+        let missing: Vec<(&str, &str)> = match table.schema().await {
+            Ok(schema) => ADDED_COLUMNS
+                .into_iter()
+                .filter(|(name, _)| schema.field_with_name(name).is_err())
+                .collect(),
+            Err(e) => {
+                log::warn!("[VectorStore] Failed to read table schema: {}", e);
+                return;
+            }
+        };
+        if missing.is_empty() {
+            return;
+        }
+        let transform = NewColumnTransform::SqlExpressions(
+            missing
+                .iter()
+                .map(|(name, sql_type)| (name.to_string(), format!("CAST(NULL AS {})", sql_type)))
+                .collect(),
+        );
+        if let Err(e) = table.add_columns(transform, None).await {
+            log::warn!(
+                "[VectorStore] Failed to add {} columns: {}",
+                missing.join(", "),
+                e
+            );
+        }
+    }
+
+    /// Fail unless vectors of `dimensions` fit the existing table
+    fn check_dimensions(&self, dimensions: usize) -> SearchResult<()> {
+        match self.table_dimensions {
+            Some(index) if self.table.is_some() && index != dimensions => {
+                Err(SearchError::DimensionMismatch {
+                    index,
+                    embedding: dimensions,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Check if index exists
     pub async fn exists(&self) -> bool {
         self.table.is_some()
@@ -89,6 +223,15 @@ impl VectorStore {
             Field::new("entry_created_at", DataType::Utf8, true),
             Field::new("idea_box", DataType::Utf8, true),
             Field::new("chunk_index", DataType::UInt32, false),
+            Field::new(DOC_MODIFIED_AT, DataType::Int64, true),
+            Field::new(LINE_START, DataType::Int64, true),
+            Field::new(LINE_END, DataType::Int64, true),
+            Field::new(BYTE_START, DataType::Int64, true),
+            Field::new(BYTE_END, DataType::Int64, true),
+            Field::new(INDEXED_AT, DataType::Int64, true),
+            Field::new(DOC_HASH, DataType::Utf8, true),
+            Field::new(CODE_LANGUAGE, DataType::Utf8, true),
+            Field::new(HEADINGS, DataType::Utf8, true),
             Field::new(
                 "vector",
                 DataType::FixedSizeList(
@@ -111,6 +254,7 @@ impl VectorStore {
             .as_ref()
             .ok_or_else(|| SearchError::VectorStore("Database not initialized".to_string()))?;
 
+        self.check_dimensions(self.dimensions)?;
         let schema = self.create_schema();
         let batch = self.chunks_to_batch(&chunks, schema.clone())?;
         let count = batch.num_rows();
@@ -133,6 +277,7 @@ impl VectorStore {
                 .await
                 .map_err(SearchError::Lance)?;
             self.table = Some(table);
+            self.table_dimensions = Some(self.dimensions);
         }
 
         Ok(count)
@@ -169,6 +314,24 @@ impl VectorStore {
             .map(|c| c.idea_box.as_deref().unwrap_or(""))
             .collect();
         let chunk_indices: Vec<u32> = chunks.iter().map(|c| c.chunk_index as u32).collect();
+        let doc_modified_ats: Vec<Option<i64>> = chunks
+            .iter()
+            .map(|c| c.doc_modified_at.map(|t| t as i64))
+            .collect();
+        let position = |get: fn(&Chunk) -> Option<usize>| -> Int64Array {
+            chunks.iter().map(|c| get(c).map(|v| v as i64)).collect()
+        };
+        let doc_hashes: Vec<Option<&str>> = chunks.iter().map(|c| c.doc_hash.as_deref()).collect();
+        let code_languages: Vec<Option<&str>> =
+            chunks.iter().map(|c| c.code_language.as_deref()).collect();
+        let headings: Vec<Option<String>> = chunks
+            .iter()
+            .map(|c| serde_json::to_string(&c.headings).ok())
+            .collect();
+        let indexed_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
 
         let vectors_array = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
             chunks
@@ -191,6 +354,15 @@ impl VectorStore {
                 Arc::new(StringArray::from(entry_created_ats)),
                 Arc::new(StringArray::from(idea_boxes)),
                 Arc::new(UInt32Array::from(chunk_indices)),
+                Arc::new(Int64Array::from(doc_modified_ats)),
+                Arc::new(position(|c| c.line_start)),
+                Arc::new(position(|c| c.line_end)),
+                Arc::new(position(|c| c.byte_start)),
+                Arc::new(position(|c| c.byte_end)),
+                Arc::new(Int64Array::from(vec![indexed_at; chunks.len()])),
+                Arc::new(StringArray::from(doc_hashes)),
+                Arc::new(StringArray::from(code_languages)),
+                Arc::new(StringArray::from(headings)),
                 Arc::new(vectors_array),
             ],
         )
@@ -201,12 +373,31 @@ impl VectorStore {
 
     /// Search for similar vectors
     pub async fn search(&self, query_vector: &[f32], limit: usize) -> SearchResult<Vec<SearchHit>> {
+        self.search_in_folder(query_vector, limit, None).await
+    }
+
+    /// Search for similar vectors among the chunks of docs in `folder` or
+    /// below it, ignoring case, or among all chunks without a folder. The
+    /// folder filters chunks before the vector scan, so up to `limit` hits
+    /// come from it however few of the closest chunks it holds.
+    pub async fn search_in_folder(
+        &self,
+        query_vector: &[f32],
+        limit: usize,
+        folder: Option<&str>,
+    ) -> SearchResult<Vec<SearchHit>> {
         let table = self.table.as_ref().ok_or(SearchError::IndexNotBuilt)?;
+        self.check_dimensions(query_vector.len())?;
 
-        let results = table
+        let mut query = table
             .vector_search(query_vector.to_vec())
             .map_err(SearchError::Lance)?
-            .limit(limit)
+            .distance_type(SEARCH_DISTANCE)
+            .limit(limit);
+        if let Some(folder) = folder {
+            query = query.only_if(folder_predicate(folder));
+        }
+        let results = query
             .execute()
             .await
             .map_err(SearchError::Lance)?
@@ -259,13 +450,25 @@ impl VectorStore {
                 .column_by_name("idea_box")
                 .and_then(|c| c.as_any().downcast_ref::<StringArray>());
 
-            let line_starts = batch
-                .column_by_name("line_start")
-                .and_then(|c| c.as_any().downcast_ref::<arrow_array::Int64Array>());
-
-            let line_ends = batch
-                .column_by_name("line_end")
-                .and_then(|c| c.as_any().downcast_ref::<arrow_array::Int64Array>());
+            let int_column = |name: &str| {
+                batch
+                    .column_by_name(name)
+                    .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+            };
+            let line_starts = int_column(LINE_START);
+            let line_ends = int_column(LINE_END);
+            let byte_starts = int_column(BYTE_START);
+            let byte_ends = int_column(BYTE_END);
+
+            let doc_modified_ats = batch
+                .column_by_name(DOC_MODIFIED_AT)
+                .and_then(|c| c.as_any().downcast_ref::<Int64Array>());
+            let code_languages = batch
+                .column_by_name(CODE_LANGUAGE)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+            let headings_column = batch
+                .column_by_name(HEADINGS)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>());
 
             // LanceDB returns _distance column for vector search
             let distances = batch
@@ -335,8 +538,26 @@ impl VectorStore {
                     }
                 });
 
-                let line_start = line_starts.map(|arr| arr.value(i) as usize);
-                let line_end = line_ends.map(|arr| arr.value(i) as usize);
+                let position = |arr: Option<&Int64Array>| {
+                    arr.filter(|arr| arr.is_valid(i))
+                        .map(|arr| arr.value(i) as usize)
+                };
+                let line_start = position(line_starts);
+                let line_end = position(line_ends);
+                let byte_start = position(byte_starts);
+                let byte_end = position(byte_ends);
+                let doc_modified_at = doc_modified_ats
+                    .filter(|arr| arr.is_valid(i))
+                    .map(|arr| arr.value(i) as u64);
+                let code_language = code_languages
+                    .filter(|arr| arr.is_valid(i))
+                    .map(|arr| arr.value(i).to_string());
+                let headings = read_headings(
+                    headings_column
+                        .filter(|arr| arr.is_valid(i))
+                        .map(|arr| arr.value(i)),
+                    heading_path.as_deref(),
+                );
 
                 let display_name = if doc_type.as_deref() == Some("idea") {
                     section_title
@@ -359,11 +580,8 @@ impl VectorStore {
                         .to_string()
                 };
 
-                // Convert distance to similarity score
-                // Use same formula as Node.js: score = 1 / (1 + distance)
-                // This ensures score is always in (0, 1] range
                 let score = distances
-                    .map(|d| 1.0 / (1.0 + d.value(i).max(0.0)))
+                    .map(|d| similarity(d.value(i), SEARCH_DISTANCE))
                     .unwrap_or(0.5);
 
                 hits.push(SearchHit {
@@ -371,11 +589,18 @@ impl VectorStore {
                     display_name,
                     content: contents.value(i).to_string(),
                     heading_path,
+                    headings,
                     section_title,
                     line_start,
                     line_end,
+                    byte_start,
+                    byte_end,
+                    code_language,
                     score,
+                    similarity: Some(score),
                     matched_by: MatchType::Vector,
+                    vector_score: None,
+                    keyword_score: None,
                     hit_count: None,
                     doc_count: None,
                     folder_path: None,
@@ -385,6 +610,11 @@ impl VectorStore {
                     entry_date,
                     entry_created_at,
                     idea_box,
+                    doc_modified_at,
+                    rerank_score: None,
+                    matched_queries: Vec::new(),
+                    highlights: Vec::new(),
+                    snippet: None,
                 });
             }
         }
@@ -392,21 +622,138 @@ impl VectorStore {
         Ok(hits)
     }
 
-    /// Delete chunks by file path
-    pub async fn delete_by_file(&self, file_path: &str) -> SearchResult<usize> {
+    /// Delete every chunk of the doc at `doc_path`
+    ///
+    /// Docs are keyed by path in the index, so this is what drops a deleted
+    /// doc, or whatever a moved doc left behind at its old path.
+    pub async fn delete_by_doc(&self, doc_path: &str) -> SearchResult<()> {
         let table = match self.table.as_ref() {
             Some(t) => t,
-            None => return Ok(0),
+            None => return Ok(()),
         };
 
-        // Use delete with filter
         table
-            .delete(&format!("file_path = '{}'", file_path.replace('\'', "''")))
+            .delete(&format!("file_path = '{}'", doc_path.replace('\'', "''")))
+            .await
+            .map_err(SearchError::Lance)?;
+
+        Ok(())
+    }
+
+    /// Delete the chunks of every file in `file_paths` at once
+    pub async fn delete_by_files(&self, file_paths: &[String]) -> SearchResult<()> {
+        let table = match self.table.as_ref() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        if file_paths.is_empty() {
+            return Ok(());
+        }
+
+        let literals: Vec<String> = file_paths
+            .iter()
+            .map(|path| format!("'{}'", path.replace('\'', "''")))
+            .collect();
+        table
+            .delete(&format!("file_path IN ({})", literals.join(", ")))
+            .await
+            .map_err(SearchError::Lance)?;
+
+        Ok(())
+    }
+
+    /// Rewrite the table without deleted chunks and drop its older versions
+    ///
+    /// LanceDB deletes only mark rows and keeps every past version on disk,
+    /// so text dropped from the index can still be read from the files
+    /// until this runs.
+    pub async fn purge_deleted(&self) -> SearchResult<()> {
+        let table = match self.table.as_ref() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        table
+            .optimize(OptimizeAction::Compact {
+                options: CompactionOptions {
+                    materialize_deletions_threshold: 0.0,
+                    ..Default::default()
+                },
+                remap_options: None,
+            })
+            .await
+            .map_err(SearchError::Lance)?;
+        table
+            .optimize(OptimizeAction::Prune {
+                older_than: Some(chrono::Duration::zero()),
+                delete_unverified: Some(true),
+                error_if_tagged_old_versions: Some(false),
+            })
+            .await
+            .map_err(SearchError::Lance)?;
+
+        Ok(())
+    }
+
+    /// Point a file's chunks at a new path without re-embedding
+    ///
+    /// Chunk ids are `<path>#<suffix>`, so the id prefix is rewritten too.
+    pub async fn update_file_path(&self, old_path: &str, new_path: &str) -> SearchResult<()> {
+        let table = match self.table.as_ref() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        let old_literal = old_path.replace('\'', "''");
+        let new_literal = new_path.replace('\'', "''");
+        // substr is 1-based and counts characters; keep "#<suffix>".
+        let suffix_start = old_path.chars().count() + 1;
+        table
+            .update()
+            .only_if(format!("file_path = '{}'", old_literal))
+            .column("file_path", format!("'{}'", new_literal))
+            .column(
+                "id",
+                format!("concat('{}', substr(id, {}))", new_literal, suffix_start),
+            )
+            .execute()
+            .await
+            .map_err(SearchError::Lance)?;
+
+        Ok(())
+    }
+
+    /// Point the chunks of every file under folder `old_prefix` at the
+    /// same file under `new_prefix`, as after the folder moved
+    pub async fn rename_path_prefix(&self, old_prefix: &str, new_prefix: &str) -> SearchResult<()> {
+        let table = match self.table.as_ref() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        let old_literal = format!("{}/", old_prefix).replace('\'', "''");
+        let new_literal = format!("{}/", new_prefix).replace('\'', "''");
+        // substr is 1-based and counts characters; keep what follows the prefix.
+        let rest_start = old_prefix.chars().count() + 2;
+        table
+            .update()
+            .only_if(format!("starts_with(file_path, '{}')", old_literal))
+            .column(
+                "file_path",
+                format!(
+                    "concat('{}', substr(file_path, {}))",
+                    new_literal, rest_start
+                ),
+            )
+            .column(
+                "id",
+                format!("concat('{}', substr(id, {}))", new_literal, rest_start),
+            )
+            .execute()
             .await
             .map_err(SearchError::Lance)?;
 
-        // LanceDB delete doesn't return count, so we return 0
-        Ok(0)
+        Ok(())
     }
 
     /// Reset the index (delete all data)
@@ -422,6 +769,7 @@ impl VectorStore {
                 .await
                 .map_err(SearchError::Lance)?;
             self.table = None;
+            self.table_dimensions = None;
         }
 
         Ok(())
@@ -438,6 +786,180 @@ impl VectorStore {
         Ok(count)
     }
 
+    /// Oldest and newest source-doc modified times across indexed chunks
+    ///
+    /// Chunks indexed before mtimes were tracked are ignored; `None` when no
+    /// chunk has one.
+    pub async fn doc_modified_range(&self) -> SearchResult<Option<(u64, u64)>> {
+        let table = match self.table.as_ref() {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+
+        let results = table
+            .query()
+            .select(Select::columns(&[DOC_MODIFIED_AT]))
+            .execute()
+            .await
+            .map_err(SearchError::Lance)?
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(SearchError::Lance)?;
+
+        let mut range: Option<(u64, u64)> = None;
+        for batch in results {
+            let Some(values) = batch
+                .column_by_name(DOC_MODIFIED_AT)
+                .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+            else {
+                continue;
+            };
+            for value in values.iter().flatten() {
+                let value = value as u64;
+                range = Some(match range {
+                    Some((min, max)) => (min.min(value), max.max(value)),
+                    None => (value, value),
+                });
+            }
+        }
+
+        Ok(range)
+    }
+
+    /// Each indexed doc with the hash and modified time it was indexed at
+    /// and its chunk count
+    pub async fn indexed_docs(&self) -> SearchResult<HashMap<String, IndexedDoc>> {
+        let table = match self.table.as_ref() {
+            Some(t) => t,
+            None => return Ok(HashMap::new()),
+        };
+
+        let results = table
+            .query()
+            .select(Select::columns(&["file_path", DOC_MODIFIED_AT, DOC_HASH]))
+            .execute()
+            .await
+            .map_err(SearchError::Lance)?
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(SearchError::Lance)?;
+
+        let mut docs: HashMap<String, IndexedDoc> = HashMap::new();
+        for batch in results {
+            let Some(paths) = batch
+                .column_by_name("file_path")
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            else {
+                continue;
+            };
+            let modified = batch
+                .column_by_name(DOC_MODIFIED_AT)
+                .and_then(|c| c.as_any().downcast_ref::<Int64Array>());
+            let hashes = batch
+                .column_by_name(DOC_HASH)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+            for i in 0..batch.num_rows() {
+                let doc = docs.entry(paths.value(i).to_string()).or_default();
+                // Every chunk of a doc is written together, so any one
+                // chunk's values stand for the doc
+                if doc.chunks == 0 {
+                    doc.doc_modified_at = modified
+                        .filter(|values| values.is_valid(i))
+                        .map(|values| values.value(i) as u64);
+                    doc.doc_hash = hashes
+                        .filter(|values| values.is_valid(i))
+                        .map(|values| values.value(i).to_string());
+                }
+                doc.chunks += 1;
+            }
+        }
+
+        Ok(docs)
+    }
+
+    /// A file's chunks in order, without their vectors
+    pub async fn chunks_for_file(&self, file_path: &str) -> SearchResult<Vec<ChunkPreview>> {
+        let table = match self.table.as_ref() {
+            Some(t) => t,
+            None => return Ok(vec![]),
+        };
+
+        let results = table
+            .query()
+            .only_if(format!("file_path = '{}'", file_path.replace('\'', "''")))
+            .select(Select::columns(&[
+                "id",
+                "content",
+                "heading_path",
+                "section_title",
+                "chunk_index",
+                DOC_MODIFIED_AT,
+                LINE_START,
+                LINE_END,
+                INDEXED_AT,
+            ]))
+            .execute()
+            .await
+            .map_err(SearchError::Lance)?
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(SearchError::Lance)?;
+
+        let mut previews = Vec::new();
+        for batch in results {
+            let text_column = |name: &str| {
+                batch
+                    .column_by_name(name)
+                    .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            };
+            let int_column = |name: &str| {
+                batch
+                    .column_by_name(name)
+                    .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+            };
+            let (Some(ids), Some(contents)) = (text_column("id"), text_column("content")) else {
+                continue;
+            };
+            let heading_paths = text_column("heading_path");
+            let section_titles = text_column("section_title");
+            let chunk_indices = batch
+                .column_by_name("chunk_index")
+                .and_then(|c| c.as_any().downcast_ref::<UInt32Array>());
+            let doc_modified_ats = int_column(DOC_MODIFIED_AT);
+            let line_starts = int_column(LINE_START);
+            let line_ends = int_column(LINE_END);
+            let indexed_ats = int_column(INDEXED_AT);
+
+            for i in 0..batch.num_rows() {
+                let text = |arr: Option<&StringArray>| {
+                    arr.map(|arr| arr.value(i))
+                        .filter(|val| !val.is_empty())
+                        .map(str::to_string)
+                };
+                let int = |arr: Option<&Int64Array>| {
+                    arr.filter(|arr| arr.is_valid(i)).map(|arr| arr.value(i))
+                };
+                let content = contents.value(i).to_string();
+                previews.push(ChunkPreview {
+                    id: ids.value(i).to_string(),
+                    chunk_index: chunk_indices.map_or(0, |arr| arr.value(i) as usize),
+                    heading_path: text(heading_paths),
+                    section_title: text(section_titles),
+                    line_start: int(line_starts).map(|v| v as usize),
+                    line_end: int(line_ends).map(|v| v as usize),
+                    tokens: estimate_tokens(&content),
+                    content_hash: content_hash(&content),
+                    content,
+                    doc_modified_at: int(doc_modified_ats).map(|v| v as u64),
+                    indexed_at: int(indexed_ats).map(|v| v as u64),
+                });
+            }
+        }
+        previews.sort_by_key(|preview| preview.chunk_index);
+
+        Ok(previews)
+    }
+
     /// Get all chunks (for keyword search)
     pub async fn get_all_chunks(&self) -> SearchResult<Vec<SearchHit>> {
         let table = match self.table.as_ref() {
@@ -501,13 +1023,25 @@ impl VectorStore {
                 .column_by_name("idea_box")
                 .and_then(|c| c.as_any().downcast_ref::<StringArray>());
 
-            let line_starts = batch
-                .column_by_name("line_start")
-                .and_then(|c| c.as_any().downcast_ref::<arrow_array::Int64Array>());
-
-            let line_ends = batch
-                .column_by_name("line_end")
-                .and_then(|c| c.as_any().downcast_ref::<arrow_array::Int64Array>());
+            let int_column = |name: &str| {
+                batch
+                    .column_by_name(name)
+                    .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+            };
+            let line_starts = int_column(LINE_START);
+            let line_ends = int_column(LINE_END);
+            let byte_starts = int_column(BYTE_START);
+            let byte_ends = int_column(BYTE_END);
+
+            let doc_modified_ats = batch
+                .column_by_name(DOC_MODIFIED_AT)
+                .and_then(|c| c.as_any().downcast_ref::<Int64Array>());
+            let code_languages = batch
+                .column_by_name(CODE_LANGUAGE)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+            let headings_column = batch
+                .column_by_name(HEADINGS)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>());
 
             for i in 0..batch.num_rows() {
                 let file_path = file_paths.value(i).to_string();
@@ -574,8 +1108,26 @@ impl VectorStore {
                     }
                 });
 
-                let line_start = line_starts.map(|arr| arr.value(i) as usize);
-                let line_end = line_ends.map(|arr| arr.value(i) as usize);
+                let position = |arr: Option<&Int64Array>| {
+                    arr.filter(|arr| arr.is_valid(i))
+                        .map(|arr| arr.value(i) as usize)
+                };
+                let line_start = position(line_starts);
+                let line_end = position(line_ends);
+                let byte_start = position(byte_starts);
+                let byte_end = position(byte_ends);
+                let doc_modified_at = doc_modified_ats
+                    .filter(|arr| arr.is_valid(i))
+                    .map(|arr| arr.value(i) as u64);
+                let code_language = code_languages
+                    .filter(|arr| arr.is_valid(i))
+                    .map(|arr| arr.value(i).to_string());
+                let headings = read_headings(
+                    headings_column
+                        .filter(|arr| arr.is_valid(i))
+                        .map(|arr| arr.value(i)),
+                    heading_path.as_deref(),
+                );
 
                 let display_name = if doc_type.as_deref() == Some("idea") {
                     section_title
@@ -603,11 +1155,18 @@ impl VectorStore {
                     display_name,
                     content: contents.value(i).to_string(),
                     heading_path,
+                    headings,
                     section_title,
                     line_start,
                     line_end,
+                    byte_start,
+                    byte_end,
+                    code_language,
                     score: 0.0,
+                    similarity: None,
                     matched_by: MatchType::Keyword,
+                    vector_score: None,
+                    keyword_score: None,
                     hit_count: None,
                     doc_count: None,
                     folder_path: None,
@@ -617,6 +1176,11 @@ impl VectorStore {
                     entry_date,
                     entry_created_at,
                     idea_box,
+                    doc_modified_at,
+                    rerank_score: None,
+                    matched_queries: Vec::new(),
+                    highlights: Vec::new(),
+                    snippet: None,
                 });
             }
         }
@@ -624,3 +1188,57 @@ impl VectorStore {
         Ok(hits)
     }
 }
+
+/// A LanceDB `distance` under `distance_type` as a similarity from 0 (the
+/// opposite direction) to 1 (the same direction), so one threshold works
+/// whichever metric ranked the hits. L2 and dot distances are read as for
+/// the unit-length vectors embedding models return.
+pub(crate) fn similarity(distance: f32, distance_type: DistanceType) -> f32 {
+    let similarity = match distance_type {
+        // 1 - cos, from 0 to 2
+        DistanceType::Cosine | DistanceType::Dot => 1.0 - distance / 2.0,
+        // Squared L2, 2 - 2cos for unit vectors, from 0 to 4
+        DistanceType::L2 => 1.0 - distance / 4.0,
+        _ => 1.0 / (1.0 + distance.max(0.0)),
+    };
+    if similarity.is_nan() {
+        0.0
+    } else {
+        similarity.clamp(0.0, 1.0)
+    }
+}
+
+/// Filter for the chunks of docs in `folder` or below it, ignoring case
+fn folder_predicate(folder: &str) -> String {
+    let folder = folder.to_lowercase().replace('\'', "''");
+    format!(
+        "lower(file_path) = '{0}' OR starts_with(lower(file_path), '{0}/')",
+        folder
+    )
+}
+
+/// Headings stored as a JSON array, or for chunks written before they
+/// were, split out of the heading path
+fn read_headings(stored: Option<&str>, heading_path: Option<&str>) -> Vec<String> {
+    stored
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_else(|| {
+            heading_path
+                .map(|path| path.split(" > ").map(str::to_string).collect())
+                .unwrap_or_default()
+        })
+}
+
+/// Size of the `vector` column of a chunks table schema
+fn vector_dimensions(schema: &Schema) -> Option<usize> {
+    match schema.field_with_name("vector").ok()?.data_type() {
+        DataType::FixedSizeList(_, size) => usize::try_from(*size).ok(),
+        _ => None,
+    }
+}
+
+/// FNV-1a hash of a chunk's or doc's text, to spot text that changed
+/// between builds
+pub(crate) fn content_hash(text: &str) -> String {
+    format!("{:016x}", crate::fnv1a(crate::FNV_OFFSET, text.as_bytes()))
+}
@@ -0,0 +1,464 @@
+//! Whole-vault snapshots
+//!
+//! A snapshot is a directory holding a copy of every file under the contexts
+//! root, a copy of the database, and `manifest.json` listing each file with
+//! its size and hash. Files unchanged since the previous snapshot are
+//! hard-linked to its copy instead of copied again; snapshots are never
+//! written to after they are made, so the links can't change under them.
+//! Vault files are always copied, never linked, since docs are saved in place.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{fnv1a, now_iso, CoreError, CoreResult, VaultPath, FNV_OFFSET};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const FILES_DIR: &str = "files";
+const DB_FILE: &str = "opencontext.db";
+/// Suffix of a snapshot still being written
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Entries of the contexts root a snapshot leaves out and a restore leaves
+/// alone: git history and search index data
+const EXCLUDED_NAMES: &[&str] = &[".git", "lancedb"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotFile {
+    /// Path relative to the contexts root, with `/` separators
+    pub path: String,
+    pub size: u64,
+    /// FNV-1a hash of the content
+    pub hash: String,
+}
+
+/// Contents of a snapshot's `manifest.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotManifest {
+    pub id: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Every directory, so empty folders come back too
+    #[serde(default)]
+    pub dirs: Vec<String>,
+    pub files: Vec<SnapshotFile>,
+}
+
+/// A snapshot as listed to the user
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub created_at: String,
+    pub label: Option<String>,
+    pub file_count: usize,
+    pub total_bytes: u64,
+}
+
+impl From<&SnapshotManifest> for SnapshotInfo {
+    fn from(manifest: &SnapshotManifest) -> Self {
+        Self {
+            id: manifest.id.clone(),
+            created_at: manifest.created_at.clone(),
+            label: manifest.label.clone(),
+            file_count: manifest.files.len(),
+            total_bytes: manifest.files.iter().map(|file| file.size).sum(),
+        }
+    }
+}
+
+/// What a restore changed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotRestore {
+    pub snapshot: SnapshotInfo,
+    /// Taken just before the restore, to undo it with
+    pub pre_restore: SnapshotInfo,
+    /// Files written back from the snapshot
+    pub restored: Vec<String>,
+    /// Files deleted because the snapshot doesn't have them
+    pub removed: Vec<String>,
+}
+
+/// Snapshots in `snapshots_dir`, newest first. Unreadable ones are skipped.
+pub fn list_snapshots(snapshots_dir: &Path) -> CoreResult<Vec<SnapshotInfo>> {
+    Ok(read_manifests(snapshots_dir)?
+        .iter()
+        .map(SnapshotInfo::from)
+        .collect())
+}
+
+/// Delete snapshots beyond the newest `keep` and those older than
+/// `max_age`. The newest snapshot is always kept. Returns the deleted ids.
+pub fn prune_snapshots(
+    snapshots_dir: &Path,
+    keep: Option<usize>,
+    max_age: Option<chrono::Duration>,
+) -> CoreResult<Vec<String>> {
+    let now = Utc::now();
+    let mut pruned = Vec::new();
+    for (index, manifest) in read_manifests(snapshots_dir)?.iter().enumerate() {
+        if index == 0 {
+            continue;
+        }
+        let too_many = keep.is_some_and(|keep| index >= keep);
+        let too_old = max_age.is_some_and(|max_age| {
+            DateTime::parse_from_rfc3339(&manifest.created_at)
+                .is_ok_and(|created| now - created.with_timezone(&Utc) > max_age)
+        });
+        if too_many || too_old {
+            fs::remove_dir_all(snapshots_dir.join(&manifest.id))?;
+            pruned.push(manifest.id.clone());
+        }
+    }
+    Ok(pruned)
+}
+
+/// Text of the file at `path` (relative to the contexts root) in the newest
+/// snapshot taken at or before `at`. `None` when there is no such snapshot,
+/// it doesn't have the file, or the file isn't UTF-8 text.
+pub fn snapshot_text_as_of(
+    snapshots_dir: &Path,
+    path: &str,
+    at: DateTime<Utc>,
+) -> CoreResult<Option<String>> {
+    let manifests = read_manifests(snapshots_dir)?;
+    let Some(manifest) = manifests.iter().find(|manifest| {
+        DateTime::parse_from_rfc3339(&manifest.created_at)
+            .is_ok_and(|created| created.with_timezone(&Utc) <= at)
+    }) else {
+        return Ok(None);
+    };
+    if !manifest.files.iter().any(|file| file.path == path) {
+        return Ok(None);
+    }
+    let file = snapshots_dir.join(&manifest.id).join(FILES_DIR).join(path);
+    match fs::read_to_string(file) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Manifests of the finished snapshots, newest first
+fn read_manifests(snapshots_dir: &Path) -> CoreResult<Vec<SnapshotManifest>> {
+    let entries = match fs::read_dir(snapshots_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut manifests = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.ends_with(PARTIAL_SUFFIX) {
+            continue;
+        }
+        match read_manifest(&entry.path()) {
+            Ok(manifest) if manifest.id == name => manifests.push(manifest),
+            Ok(_) => log::warn!("Snapshot {} has another id in its manifest", name),
+            Err(e) => log::warn!("Skipping unreadable snapshot {}: {}", name, e),
+        }
+    }
+    // Ids are timestamps, so they sort by age
+    manifests.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(manifests)
+}
+
+fn read_manifest(snapshot_dir: &Path) -> CoreResult<SnapshotManifest> {
+    let content = fs::read_to_string(snapshot_dir.join(MANIFEST_FILE))?;
+    serde_json::from_str(&content).map_err(|e| CoreError::Message(e.to_string()))
+}
+
+/// Copy the vault into a new snapshot in `snapshots_dir`, calling
+/// `on_progress` with the files done and the total as it goes
+pub(crate) fn create(
+    contexts_root: &Path,
+    conn: &Connection,
+    snapshots_dir: &Path,
+    label: Option<&str>,
+    mut on_progress: impl FnMut(usize, usize),
+) -> CoreResult<SnapshotInfo> {
+    let previous = read_manifests(snapshots_dir)?.into_iter().next();
+    let id = new_snapshot_id(snapshots_dir);
+    let partial = snapshots_dir.join(format!("{}{}", id, PARTIAL_SUFFIX));
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    let files_dir = partial.join(FILES_DIR);
+    fs::create_dir_all(&files_dir)?;
+
+    let (dirs, paths) = walk(contexts_root, snapshots_dir)?;
+    for dir in &dirs {
+        fs::create_dir_all(files_dir.join(dir))?;
+    }
+
+    // Files of the previous snapshot to link to, by path
+    let linkable: HashMap<&str, &SnapshotFile> = previous
+        .iter()
+        .flat_map(|manifest| &manifest.files)
+        .map(|file| (file.path.as_str(), file))
+        .collect();
+    let previous_files = previous
+        .as_ref()
+        .map(|manifest| snapshots_dir.join(&manifest.id).join(FILES_DIR));
+
+    let mut files = Vec::with_capacity(paths.len());
+    for (done, path) in paths.iter().enumerate() {
+        let source = contexts_root.join(path);
+        let (size, hash) = hash_file(&source)?;
+        let dest = files_dir.join(path);
+        let unchanged = linkable
+            .get(path.as_str())
+            .is_some_and(|file| file.size == size && file.hash == hash);
+        let linked = match (&previous_files, unchanged) {
+            (Some(previous_files), true) => fs::hard_link(previous_files.join(path), &dest).is_ok(),
+            _ => false,
+        };
+        if !linked {
+            fs::copy(&source, &dest)?;
+        }
+        files.push(SnapshotFile {
+            path: path.clone(),
+            size,
+            hash,
+        });
+        on_progress(done + 1, paths.len());
+    }
+
+    // A consistent copy even while other connections read
+    conn.execute(
+        "VACUUM INTO ?1",
+        [partial.join(DB_FILE).to_string_lossy().to_string()],
+    )?;
+
+    let manifest = SnapshotManifest {
+        id: id.clone(),
+        created_at: now_iso(),
+        label: label.map(str::to_string),
+        dirs,
+        files,
+    };
+    let json =
+        serde_json::to_string_pretty(&manifest).map_err(|e| CoreError::Message(e.to_string()))?;
+    fs::write(partial.join(MANIFEST_FILE), json)?;
+    fs::rename(&partial, snapshots_dir.join(&id))?;
+    Ok(SnapshotInfo::from(&manifest))
+}
+
+/// Bring the vault files and database back to snapshot `id`. The caller
+/// takes the pre-restore snapshot first.
+pub(crate) fn restore(
+    contexts_root: &Path,
+    conn: &Connection,
+    snapshots_dir: &Path,
+    id: &str,
+    mut on_progress: impl FnMut(usize, usize),
+) -> CoreResult<(SnapshotInfo, Vec<String>, Vec<String>)> {
+    let snapshot_dir = snapshot_dir(snapshots_dir, id)?;
+    // Checked before anything is touched: the manifest is only a file, and
+    // a path in it must not reach outside the contexts root
+    let manifest = checked_paths(read_manifest(&snapshot_dir)?)?;
+    let files_dir = snapshot_dir.join(FILES_DIR);
+
+    let (current_dirs, current_paths) = walk(contexts_root, snapshots_dir)?;
+    let wanted: BTreeSet<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    let mut removed = Vec::new();
+    for path in current_paths {
+        if !wanted.contains(path.as_str()) {
+            fs::remove_file(contexts_root.join(&path))?;
+            removed.push(path);
+        }
+    }
+
+    for dir in &manifest.dirs {
+        fs::create_dir_all(contexts_root.join(dir))?;
+    }
+    let mut restored = Vec::new();
+    for (done, file) in manifest.files.iter().enumerate() {
+        let dest = contexts_root.join(&file.path);
+        let current = hash_file(&dest).ok();
+        if current.as_ref() != Some(&(file.size, file.hash.clone())) {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Copied, not linked: the vault copy gets saved over in place
+            fs::copy(files_dir.join(&file.path), &dest)?;
+            restored.push(file.path.clone());
+        }
+        on_progress(done + 1, manifest.files.len());
+    }
+
+    // Deepest first, so emptied parents go too. Ones still holding
+    // excluded entries are not empty and stay.
+    let kept_dirs: BTreeSet<&str> = manifest.dirs.iter().map(String::as_str).collect();
+    for dir in current_dirs.iter().rev() {
+        if !kept_dirs.contains(dir.as_str()) {
+            let _ = fs::remove_dir(contexts_root.join(dir));
+        }
+    }
+
+    restore_database(conn, &snapshot_dir.join(DB_FILE))?;
+    Ok((SnapshotInfo::from(&manifest), restored, removed))
+}
+
+/// `manifest` with its paths normalized, or the error for the first one
+/// that is not a path inside the contexts root
+fn checked_paths(mut manifest: SnapshotManifest) -> CoreResult<SnapshotManifest> {
+    for dir in &mut manifest.dirs {
+        *dir = VaultPath::parse(dir)?.into_string();
+    }
+    for file in &mut manifest.files {
+        let path = VaultPath::parse(&file.path)?;
+        if path.is_root() {
+            return Err(CoreError::InvalidPath {
+                path: file.path.clone(),
+                reason: "a snapshot file cannot be the contexts root".to_string(),
+            });
+        }
+        file.path = path.into_string();
+    }
+    Ok(manifest)
+}
+
+/// Replace the folders and docs tables with the snapshot's
+fn restore_database(conn: &Connection, snapshot_db: &Path) -> CoreResult<()> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS snapshot",
+        [snapshot_db.to_string_lossy().to_string()],
+    )?;
+    let copied = copy_snapshot_tables(conn);
+    let detached = conn.execute_batch("DETACH DATABASE snapshot;");
+    copied?;
+    detached?;
+    Ok(())
+}
+
+/// Copy the attached snapshot's tables over the vault's in one transaction,
+/// so a failure leaves the vault's as they were
+fn copy_snapshot_tables(conn: &Connection) -> CoreResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "PRAGMA defer_foreign_keys = ON;
+         DELETE FROM main.docs;
+         DELETE FROM main.folders;",
+    )?;
+    for table in ["folders", "docs"] {
+        // By name, as columns added since the snapshot was taken, or added
+        // in another order, would shift a `SELECT *`
+        let columns = shared_columns(&tx, table)?.join(", ");
+        tx.execute(
+            &format!("INSERT INTO main.{table} ({columns}) SELECT {columns} FROM snapshot.{table}"),
+            [],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Columns of `table` in both the vault's and the snapshot's database,
+/// quoted for SQL. Ones only the vault has keep their defaults.
+fn shared_columns(conn: &Connection, table: &str) -> CoreResult<Vec<String>> {
+    let column_names = |schema: &str| -> CoreResult<Vec<String>> {
+        let mut stmt = conn.prepare(&format!("PRAGMA {schema}.table_info({table})"))?;
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(names)
+    };
+    let in_snapshot = column_names("snapshot")?;
+    let columns: Vec<String> = column_names("main")?
+        .into_iter()
+        .filter(|name| in_snapshot.contains(name))
+        .map(|name| format!("\"{}\"", name.replace('"', "\"\"")))
+        .collect();
+    if columns.is_empty() {
+        return Err(CoreError::Message(format!(
+            "The snapshot's database has no {table} table"
+        )));
+    }
+    Ok(columns)
+}
+
+/// The directory of snapshot `id`, which must be a plain name
+fn snapshot_dir(snapshots_dir: &Path, id: &str) -> CoreResult<PathBuf> {
+    let dir = snapshots_dir.join(id);
+    if id.is_empty()
+        || id.contains(['/', '\\'])
+        || id.starts_with('.')
+        || id.ends_with(PARTIAL_SUFFIX)
+        || !dir.join(MANIFEST_FILE).is_file()
+    {
+        return Err(CoreError::Message(format!("Snapshot \"{}\" not found", id)));
+    }
+    Ok(dir)
+}
+
+/// A timestamp id not taken yet in `snapshots_dir`
+fn new_snapshot_id(snapshots_dir: &Path) -> String {
+    let base = Utc::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+    let mut id = base.clone();
+    let mut n = 1;
+    while snapshots_dir.join(&id).exists() {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    id
+}
+
+/// Directories and files under `root` relative to it, sorted, leaving out
+/// excluded names, symlinks, and `skip` when it lies inside `root`
+fn walk(root: &Path, skip: &Path) -> CoreResult<(Vec<String>, Vec<String>)> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name();
+            if EXCLUDED_NAMES.iter().any(|excluded| name == *excluded) || path == skip {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            let Ok(rel) = path.strip_prefix(root) else {
+                continue;
+            };
+            let rel = rel.to_string_lossy().replace('\\', "/");
+            if file_type.is_dir() {
+                dirs.push(rel);
+                pending.push(path);
+            } else if file_type.is_file() {
+                files.push(rel);
+            }
+        }
+    }
+    dirs.sort();
+    files.sort();
+    Ok((dirs, files))
+}
+
+/// Size and FNV-1a hash of a file's content
+fn hash_file(path: &Path) -> CoreResult<(u64, String)> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = [0u8; 64 * 1024];
+    let mut hash = FNV_OFFSET;
+    let mut size = 0u64;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hash = fnv1a(hash, &buffer[..read]);
+        size += read as u64;
+    }
+    Ok((size, format!("{:016x}", hash)))
+}
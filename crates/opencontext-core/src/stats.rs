@@ -0,0 +1,344 @@
+//! Vault-wide statistics for the stats page
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::tags::{extract_tags, split_front_matter};
+use crate::DocKind;
+
+/// Most threads used to read docs for word counts and tags
+const MAX_SCAN_THREADS: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct VaultStatsOptions {
+    /// Weekly buckets in the series, ending with the current week
+    pub weeks: usize,
+    /// Length of each top-N list
+    pub top: usize,
+    /// Read every text doc to count its words
+    pub word_counts: bool,
+    /// Read every text doc to collect its tags
+    pub tags: bool,
+}
+
+impl Default for VaultStatsOptions {
+    fn default() -> Self {
+        Self {
+            weeks: 26,
+            top: 10,
+            word_counts: false,
+            tags: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStats {
+    /// When the stats were computed (RFC 3339)
+    pub as_of: String,
+    pub total_docs: usize,
+    pub total_folders: usize,
+    pub docs_by_kind: BTreeMap<DocKind, usize>,
+    /// Oldest week first
+    pub weeks: Vec<WeekStats>,
+    /// Docs saved most often from the app
+    pub most_edited: Vec<DocEdits>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub words: Option<WordStats>,
+    /// Most used tags first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<TagCount>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeekStats {
+    /// Monday the week starts on (UTC), as YYYY-MM-DD
+    pub week_start: String,
+    pub docs_created: usize,
+    /// Docs whose last change falls in this week
+    pub docs_updated: usize,
+    /// Words in the docs created by the end of the week, counted as they
+    /// read today, since past versions are not kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_words: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocEdits {
+    pub rel_path: String,
+    pub edits: u64,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WordStats {
+    pub total: usize,
+    /// Longest docs first
+    pub longest: Vec<DocWords>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocWords {
+    pub rel_path: String,
+    pub words: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    /// Docs using the tag
+    pub docs: usize,
+}
+
+/// A doc as read from the database for the stats
+pub(crate) struct DocRow {
+    pub rel_path: String,
+    pub abs_path: PathBuf,
+    pub created_at: String,
+    pub updated_at: String,
+    pub edit_count: u64,
+}
+
+/// Word count and tags of a file as of its modified time and size
+#[derive(Clone)]
+pub(crate) struct TextStats {
+    modified: SystemTime,
+    len: u64,
+    words: usize,
+    tags: Vec<String>,
+}
+
+/// Text stats by absolute path, kept for the life of the context so
+/// unchanged docs are not read again
+pub(crate) type TextStatsCache = Mutex<HashMap<PathBuf, TextStats>>;
+
+pub(crate) fn compute(
+    docs: Vec<DocRow>,
+    total_folders: usize,
+    options: &VaultStatsOptions,
+    cache: &TextStatsCache,
+) -> VaultStats {
+    let now = Utc::now();
+    let kinds: Vec<DocKind> = docs.iter().map(|d| DocKind::detect(&d.abs_path)).collect();
+    let mut docs_by_kind = BTreeMap::new();
+    for kind in &kinds {
+        *docs_by_kind.entry(*kind).or_insert(0) += 1;
+    }
+
+    let texts = if options.word_counts || options.tags {
+        let paths: Vec<Option<&Path>> = docs
+            .iter()
+            .zip(&kinds)
+            .map(|(doc, kind)| kind.is_text().then_some(doc.abs_path.as_path()))
+            .collect();
+        scan_texts(&paths, cache)
+    } else {
+        vec![None; docs.len()]
+    };
+    let words_of = |i: usize| texts[i].as_ref().map_or(0, |t| t.words);
+
+    // Weekly series, with docs created before the first week counted as a
+    // base for the running word total
+    let current_week = week_start(now.date_naive());
+    let first_week = current_week
+        .checked_sub_days(Days::new(7 * options.weeks.saturating_sub(1) as u64))
+        .unwrap_or(current_week);
+    let mut weeks: Vec<WeekStats> = (0..options.weeks)
+        .filter_map(|i| first_week.checked_add_days(Days::new(7 * i as u64)))
+        .map(|start| WeekStats {
+            week_start: start.format("%Y-%m-%d").to_string(),
+            docs_created: 0,
+            docs_updated: 0,
+            total_words: None,
+        })
+        .collect();
+    let week_count = weeks.len();
+    let bucket = |timestamp: &str| {
+        let week = week_start(parse_date(timestamp)?);
+        let index = usize::try_from((week - first_week).num_days() / 7).ok()?;
+        (index < week_count).then_some(index)
+    };
+    let mut base_words = 0;
+    let mut words_created = vec![0; week_count];
+    for (i, doc) in docs.iter().enumerate() {
+        match bucket(&doc.created_at) {
+            Some(week) => {
+                weeks[week].docs_created += 1;
+                words_created[week] += words_of(i);
+            }
+            None if parse_date(&doc.created_at).is_some_and(|d| week_start(d) < first_week) => {
+                base_words += words_of(i)
+            }
+            None => {}
+        }
+        if let Some(week) = bucket(&doc.updated_at) {
+            weeks[week].docs_updated += 1;
+        }
+    }
+    if options.word_counts {
+        let mut running = base_words;
+        for (week, created) in weeks.iter_mut().zip(words_created) {
+            running += created;
+            week.total_words = Some(running);
+        }
+    }
+
+    let mut most_edited: Vec<DocEdits> = docs
+        .iter()
+        .filter(|doc| doc.edit_count > 0)
+        .map(|doc| DocEdits {
+            rel_path: doc.rel_path.clone(),
+            edits: doc.edit_count,
+            updated_at: doc.updated_at.clone(),
+        })
+        .collect();
+    most_edited.sort_by(|a, b| {
+        b.edits
+            .cmp(&a.edits)
+            .then_with(|| b.updated_at.cmp(&a.updated_at))
+    });
+    most_edited.truncate(options.top);
+
+    let words = options.word_counts.then(|| {
+        let mut longest: Vec<DocWords> = docs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| words_of(*i) > 0)
+            .map(|(i, doc)| DocWords {
+                rel_path: doc.rel_path.clone(),
+                words: words_of(i),
+            })
+            .collect();
+        let total = longest.iter().map(|d| d.words).sum();
+        longest.sort_by(|a, b| b.words.cmp(&a.words).then(a.rel_path.cmp(&b.rel_path)));
+        longest.truncate(options.top);
+        WordStats { total, longest }
+    });
+
+    let tags = options.tags.then(|| {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for text in texts.iter().flatten() {
+            for tag in &text.tags {
+                *counts.entry(tag.as_str()).or_insert(0) += 1;
+            }
+        }
+        let mut tags: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(tag, docs)| TagCount {
+                tag: tag.to_string(),
+                docs,
+            })
+            .collect();
+        tags.sort_by(|a, b| b.docs.cmp(&a.docs).then(a.tag.cmp(&b.tag)));
+        tags.truncate(options.top);
+        tags
+    });
+
+    VaultStats {
+        as_of: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        total_docs: docs.len(),
+        total_folders,
+        docs_by_kind,
+        weeks,
+        most_edited,
+        words,
+        tags,
+    }
+}
+
+fn parse_date(timestamp: &str) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&Utc).date_naive())
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Days::new(u64::from(date.weekday().num_days_from_monday()))
+}
+
+/// Text stats for each path, `None` for paths that are `None` or can't be
+/// read. Files are read in parallel; cached stats are reused while a file's
+/// modified time and size are unchanged.
+fn scan_texts(paths: &[Option<&Path>], cache: &TextStatsCache) -> Vec<Option<TextStats>> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_SCAN_THREADS);
+    let chunk_size = paths.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|path| path.and_then(|path| text_stats(path, cache)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    })
+}
+
+fn text_stats(path: &Path, cache: &TextStatsCache) -> Option<TextStats> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?;
+    if let Some(cached) = cache.lock().get(path) {
+        if cached.modified == modified && cached.len == meta.len() {
+            return Some(cached.clone());
+        }
+    }
+    let bytes = fs::read(path).ok()?;
+    // Encrypted docs stay out of the stats, locked or not
+    if crate::crypto::is_encrypted(&bytes) {
+        return None;
+    }
+    let content = String::from_utf8(bytes).ok()?;
+    let (front_matter, body) = split_front_matter(&content);
+    let stats = TextStats {
+        modified,
+        len: meta.len(),
+        words: count_words(body),
+        tags: extract_tags(front_matter, body),
+    };
+    cache.lock().insert(path.to_path_buf(), stats.clone());
+    Some(stats)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // kana
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}' // hangul
+        | '\u{f900}'..='\u{faff}')
+}
+
+/// Whitespace-separated words, with each CJK character counted as a word
+/// since those scripts don't separate words with spaces
+pub(crate) fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|token| {
+            let cjk = token.chars().filter(|c| is_cjk(*c)).count();
+            let rest = token
+                .split(is_cjk)
+                .any(|part| part.chars().any(char::is_alphanumeric));
+            cjk + usize::from(rest)
+        })
+        .sum()
+}
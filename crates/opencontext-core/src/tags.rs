@@ -0,0 +1,131 @@
+//! Doc tags, read from YAML front matter and `#hashtags` in the body
+
+/// YAML front matter without its fences, and the body after it
+pub(crate) fn split_front_matter(text: &str) -> (&str, &str) {
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return ("", text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed == "---" || trimmed == "..." {
+            return (&rest[..offset], &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    ("", text)
+}
+
+/// Whether a front matter line starts the `tags` key
+fn tags_key(line: &str) -> Option<&str> {
+    line.strip_prefix("tags:")
+        .or_else(|| line.strip_prefix("tag:"))
+}
+
+/// Tags listed under the front matter `tags` key, inline or as a block list
+fn front_matter_tags(front_matter: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut in_tags = false;
+    for line in front_matter.lines() {
+        let trimmed = line.trim();
+        if let Some(value) = tags_key(trimmed) {
+            let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+            tags.extend(value.split(',').map(str::to_string));
+            in_tags = true;
+        } else if let Some(item) = trimmed.strip_prefix("- ").filter(|_| in_tags) {
+            tags.push(item.to_string());
+        } else {
+            in_tags = false;
+        }
+    }
+    normalize_tags(tags)
+}
+
+/// `#tags` in the body, outside code
+fn hashtags(body: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut in_fence = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let mut in_code = false;
+        let mut prev = ' ';
+        for (i, c) in line.char_indices() {
+            if c == '`' {
+                in_code = !in_code;
+            } else if c == '#' && !in_code && prev.is_whitespace() {
+                let tag: String = line[i + 1..]
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                    .collect();
+                // `#1` is an issue number, not a tag
+                if tag.chars().any(|c| !c.is_ascii_digit()) {
+                    tags.push(tag);
+                }
+            }
+            prev = c;
+        }
+    }
+    tags
+}
+
+/// Trimmed, unquoted, lowercased and hyphenated, sorted and deduplicated
+pub(crate) fn normalize_tags(tags: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .into_iter()
+        .map(|tag| {
+            tag.trim()
+                .trim_matches(['"', '\''])
+                .trim_start_matches('#')
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join("-")
+                .replace([',', '[', ']'], "")
+                .to_lowercase()
+        })
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+    tags
+}
+
+/// Tags from the front matter and the body, sorted and deduplicated
+pub(crate) fn extract_tags(front_matter: &str, body: &str) -> Vec<String> {
+    let mut tags = front_matter_tags(front_matter);
+    tags.extend(hashtags(body));
+    normalize_tags(tags)
+}
+
+/// `content` with `tags` added to its front matter `tags` key, creating
+/// the front matter when there is none. Other keys are left as they are.
+pub(crate) fn add_front_matter_tags(content: &str, tags: &[String]) -> String {
+    let (front_matter, body) = split_front_matter(content);
+    let mut merged = front_matter_tags(front_matter);
+    merged.extend(tags.iter().cloned());
+    let merged = normalize_tags(merged);
+
+    let mut lines = Vec::new();
+    let mut in_tags = false;
+    for line in front_matter.lines() {
+        let trimmed = line.trim();
+        if tags_key(trimmed).is_some() {
+            in_tags = true;
+        } else if !(in_tags && trimmed.starts_with("- ")) {
+            in_tags = false;
+            lines.push(line);
+        }
+    }
+    let tags_line = format!("tags: [{}]", merged.join(", "));
+    lines.push(&tags_line);
+    format!("---\n{}\n---\n{}", lines.join("\n"), body)
+}
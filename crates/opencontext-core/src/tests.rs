@@ -296,7 +296,7 @@ mod folder_tests {
 
 #[cfg(test)]
 mod doc_tests {
-    use crate::{EnvOverrides, OpenContext};
+    use crate::{CoreError, DocKind, EnvOverrides, OpenContext};
     use tempfile::TempDir;
 
     fn create_test_context() -> (OpenContext, TempDir) {
@@ -423,6 +423,38 @@ mod doc_tests {
         assert!(docs.is_empty());
     }
 
+    #[test]
+    fn test_list_recent_docs_goes_by_file_modified_time() {
+        let (ctx, _temp) = create_test_context();
+
+        let old = ctx.create_doc("test-folder", "old.md", None).unwrap();
+        ctx.create_doc("test-folder", "new.md", None).unwrap();
+        let long_ago = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_577_836_800);
+        std::fs::File::options()
+            .write(true)
+            .open(&old.abs_path)
+            .unwrap()
+            .set_modified(long_ago)
+            .unwrap();
+
+        let paths = |docs: Vec<crate::Doc>| -> Vec<String> {
+            docs.into_iter().map(|doc| doc.rel_path).collect()
+        };
+        assert_eq!(
+            paths(ctx.list_recent_docs(None, None).unwrap()),
+            ["test-folder/new.md", "test-folder/old.md"]
+        );
+        assert_eq!(
+            paths(
+                ctx.list_recent_docs(Some("2021-01-01T00:00:00Z"), None)
+                    .unwrap()
+            ),
+            ["test-folder/new.md"]
+        );
+        assert_eq!(ctx.list_recent_docs(None, Some(1)).unwrap().len(), 1);
+        assert!(ctx.list_recent_docs(Some("last week"), None).is_err());
+    }
+
     #[test]
     fn test_move_doc_basic() {
         let (ctx, _temp) = create_test_context();
@@ -534,6 +566,25 @@ mod doc_tests {
         assert_eq!(doc.description, "New description");
     }
 
+    #[test]
+    fn test_set_doc_description_records_when() {
+        let (ctx, _temp) = create_test_context();
+
+        ctx.create_doc("test-folder", "doc.md", None).unwrap();
+        assert_eq!(
+            ctx.get_doc_description_updated_at("test-folder/doc.md")
+                .unwrap(),
+            None
+        );
+
+        ctx.set_doc_description("test-folder/doc.md", "New description")
+            .unwrap();
+        assert!(ctx
+            .get_doc_description_updated_at("test-folder/doc.md")
+            .unwrap()
+            .is_some());
+    }
+
     #[test]
     fn test_set_doc_description_not_found() {
         let (ctx, _temp) = create_test_context();
@@ -619,11 +670,50 @@ mod doc_tests {
         let doc = ctx.get_doc_meta("test-folder/doc.md").unwrap();
         assert_eq!(doc.description, "New desc");
     }
+
+    #[test]
+    fn test_get_doc_content_refuses_binary_files() {
+        let (ctx, _temp) = create_test_context();
+        let created = ctx.create_doc("test-folder", "photo.png", None).unwrap();
+        std::fs::write(&created.abs_path, [0x89, b'P', b'N', b'G', 0, 0]).unwrap();
+        let notes = ctx.create_doc("test-folder", "notes", None).unwrap();
+        std::fs::write(&notes.abs_path, "plain notes").unwrap();
+
+        let doc = ctx.get_doc_meta("test-folder/photo.png").unwrap();
+        assert_eq!(doc.kind, DocKind::Image);
+        let err = ctx.get_doc_content("test-folder/photo.png").unwrap_err();
+        assert!(matches!(
+            err,
+            CoreError::NotText {
+                kind: DocKind::Image,
+                ..
+            }
+        ));
+
+        // No extension: sniffed once read, and text counts as markdown
+        assert_eq!(
+            ctx.get_doc_meta("test-folder/notes").unwrap().kind,
+            DocKind::Markdown
+        );
+        assert_eq!(
+            ctx.get_doc_content("test-folder/notes").unwrap(),
+            "plain notes"
+        );
+        let blob = ctx.create_doc("test-folder", "blob", None).unwrap();
+        std::fs::write(&blob.abs_path, "a\0b").unwrap();
+        assert!(matches!(
+            ctx.get_doc_content("test-folder/blob").unwrap_err(),
+            CoreError::NotText {
+                kind: DocKind::Binary,
+                ..
+            }
+        ));
+    }
 }
 
 #[cfg(test)]
 mod manifest_tests {
-    use crate::{EnvOverrides, OpenContext};
+    use crate::{DocKind, EnvOverrides, OpenContext};
     use tempfile::TempDir;
 
     fn create_test_context() -> (OpenContext, TempDir) {
@@ -729,4 +819,1112 @@ mod manifest_tests {
         assert!(!entry.updated_at.is_empty());
         assert!(entry.abs_path.to_string_lossy().contains("folder/doc.md"));
     }
+
+    #[test]
+    fn test_generate_manifest_reports_unsearchable_files() {
+        let (ctx, _temp) = create_test_context();
+
+        ctx.create_folder("mixed", None).unwrap();
+        ctx.create_doc("mixed", "notes.md", None).unwrap();
+        ctx.create_doc("mixed", "log.txt", None).unwrap();
+        ctx.create_doc("mixed", "archive.zip", None).unwrap();
+
+        let manifest = ctx.generate_manifest("mixed", None).unwrap();
+        let entry = |name: &str| manifest.iter().find(|e| e.doc_name == name).unwrap();
+        assert_eq!(entry("notes.md").kind, DocKind::Markdown);
+        assert_eq!(entry("log.txt").kind, DocKind::Text);
+        assert_eq!(entry("log.txt").skip_reason, None);
+        assert_eq!(entry("archive.zip").kind, DocKind::Binary);
+        assert_eq!(entry("archive.zip").skip_reason, Some("binary file"));
+    }
+}
+
+#[cfg(test)]
+mod name_tests {
+    use crate::{CoreError, EnvOverrides, OpenContext};
+    use tempfile::TempDir;
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+        ctx.create_folder("test-folder", None).unwrap();
+
+        (ctx, temp_dir)
+    }
+
+    #[test]
+    fn test_name_with_path_separator_is_invalid() {
+        let (ctx, _temp) = create_test_context();
+
+        let result = ctx.create_doc("test-folder", "a\\b.md", None);
+        assert!(matches!(result, Err(CoreError::InvalidName { .. })));
+
+        ctx.create_doc("test-folder", "doc.md", None).unwrap();
+        let result = ctx.rename_doc("test-folder/doc.md", "other/doc.md");
+        assert!(matches!(result, Err(CoreError::InvalidName { .. })));
+    }
+
+    #[test]
+    fn test_reserved_windows_name_is_invalid() {
+        let (ctx, _temp) = create_test_context();
+
+        let result = ctx.create_folder("test-folder/con", None);
+        assert!(matches!(result, Err(CoreError::InvalidName { .. })));
+
+        let result = ctx.create_doc("test-folder", "NUL.md", None);
+        assert!(matches!(result, Err(CoreError::InvalidName { .. })));
+    }
+
+    #[test]
+    fn test_whitespace_only_name_is_invalid() {
+        let (ctx, _temp) = create_test_context();
+
+        let result = ctx.create_doc("test-folder", "   ", None);
+        match result {
+            Err(CoreError::InvalidName { reason, .. }) => assert_eq!(reason, "name is empty"),
+            other => panic!("expected InvalidName, got {:?}", other.map(|d| d.rel_path)),
+        }
+
+        let result = ctx.rename_folder("test-folder", " ");
+        assert!(matches!(result, Err(CoreError::InvalidName { .. })));
+    }
+
+    #[test]
+    fn test_trailing_spaces_and_dots_are_stripped() {
+        let (ctx, _temp) = create_test_context();
+
+        let created = ctx.create_doc("test-folder", "notes.md. ", None).unwrap();
+        assert_eq!(created.rel_path, "test-folder/notes.md");
+
+        let folder = ctx.create_folder("drafts..", None).unwrap();
+        assert_eq!(folder.rel_path, "drafts");
+    }
+}
+
+#[cfg(test)]
+mod vault_path_tests {
+    use crate::{CoreError, EnvOverrides, OpenContext, VaultPath};
+    use tempfile::TempDir;
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+        ctx.create_folder("test-folder", None).unwrap();
+
+        (ctx, temp_dir)
+    }
+
+    fn parse(input: &str) -> Result<String, String> {
+        VaultPath::parse(input)
+            .map(VaultPath::into_string)
+            .map_err(|e| match e {
+                CoreError::InvalidPath { reason, .. } => reason,
+                other => panic!("expected InvalidPath, got {other}"),
+            })
+    }
+
+    #[test]
+    fn test_separators_and_redundant_segments_are_normalized() {
+        for (input, expected) in [
+            ("notes/a.md", "notes/a.md"),
+            ("  notes/a.md  ", "notes/a.md"),
+            ("notes\\a.md", "notes/a.md"),
+            ("notes\\sub/a.md", "notes/sub/a.md"),
+            ("./notes//a.md/", "notes/a.md"),
+            ("/notes/a.md", "notes/a.md"),
+            ("notes/./sub/../a.md", "notes/a.md"),
+            ("", ""),
+            (".", ""),
+            ("/", ""),
+        ] {
+            assert_eq!(parse(input).as_deref(), Ok(expected), "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn test_traversal_outside_root_is_rejected() {
+        for input in [
+            "..",
+            "../secret.md",
+            "notes/../../secret.md",
+            "..\\secret.md",
+            "/../etc",
+        ] {
+            assert_eq!(
+                parse(input),
+                Err("path leads outside the contexts root".to_string()),
+                "input {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_absolute_and_unc_paths_are_rejected() {
+        for input in ["C:\\Windows\\win.ini", "c:/notes/a.md", "D:"] {
+            assert_eq!(
+                parse(input),
+                Err("absolute paths are not allowed".to_string()),
+                "input {input:?}"
+            );
+        }
+        for input in ["\\\\server\\share\\a.md", "//server/share/a.md", "\\/mixed"] {
+            assert_eq!(
+                parse(input),
+                Err("UNC paths are not allowed".to_string()),
+                "input {input:?}"
+            );
+        }
+        assert!(parse("notes/a\0.md").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_reports_invalid_path() {
+        let path: VaultPath = serde_json::from_str("\"notes\\\\a.md\"").unwrap();
+        assert_eq!(path.as_str(), "notes/a.md");
+
+        let err = serde_json::from_str::<VaultPath>("\"../a.md\"").unwrap_err();
+        assert!(err.to_string().contains("outside the contexts root"));
+    }
+
+    #[test]
+    fn test_doc_apis_reject_escaping_paths() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_doc("test-folder", "doc.md", None).unwrap();
+
+        let content = ctx.get_doc_content("test-folder\\doc.md");
+        assert!(content.is_ok());
+
+        let result = ctx.get_doc_content("test-folder/../../test.db");
+        assert!(matches!(result, Err(CoreError::InvalidPath { .. })));
+
+        let result = ctx.move_doc("test-folder/doc.md", "../outside");
+        assert!(matches!(result, Err(CoreError::InvalidPath { .. })));
+
+        let result = ctx.list_docs("//server/share", true);
+        assert!(matches!(result, Err(CoreError::InvalidPath { .. })));
+    }
+}
+
+#[cfg(test)]
+mod concurrency_tests {
+    use crate::{EnvOverrides, OpenContext};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// How long a test waits for a step that should happen at once before
+    /// failing, rather than hanging
+    const GIVE_UP: Duration = Duration::from_secs(10);
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+
+        ctx.create_folder("notes/deep", None).unwrap();
+        for i in 0..50 {
+            ctx.create_doc("notes/deep", &format!("doc-{i}.md"), None)
+                .unwrap();
+        }
+
+        (ctx, temp_dir)
+    }
+
+    #[test]
+    fn test_reads_do_not_wait_for_the_writer() {
+        let (ctx, _temp) = create_test_context();
+        let ctx = Arc::new(ctx);
+
+        // The writer keeps the lock until the reads are done. The timeout
+        // only stops a regression from hanging the test.
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let writer = {
+            let ctx = Arc::clone(&ctx);
+            thread::spawn(move || {
+                ctx.with_conn(|_| {
+                    locked_tx.send(()).unwrap();
+                    Ok(release_rx.recv_timeout(GIVE_UP).is_ok())
+                })
+                .unwrap()
+            })
+        };
+        locked_rx.recv().unwrap();
+
+        assert_eq!(ctx.list_docs("notes", true).unwrap().len(), 50);
+        assert!(ctx.get_doc_meta("notes/deep/doc-0.md").is_ok());
+        release_tx.send(()).unwrap();
+        assert!(writer.join().unwrap(), "reads queued behind the writer");
+    }
+
+    #[test]
+    fn test_concurrent_reads_run_in_parallel() {
+        let (ctx, _temp) = create_test_context();
+        let ctx = Arc::new(ctx);
+        let readers = 3;
+
+        // Each reader holds its connection until every reader has one;
+        // serialized, the first would never see the others arrive
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_txs, handles): (Vec<_>, Vec<_>) = (0..readers)
+            .map(|_| {
+                let ctx = Arc::clone(&ctx);
+                let entered_tx = entered_tx.clone();
+                let (release_tx, release_rx) = mpsc::channel::<()>();
+                let handle = thread::spawn(move || {
+                    ctx.with_read_conn(|conn| {
+                        entered_tx.send(()).unwrap();
+                        let _ = release_rx.recv_timeout(GIVE_UP);
+                        let count: i64 =
+                            conn.query_row("SELECT COUNT(*) FROM docs", [], |row| row.get(0))?;
+                        Ok(count)
+                    })
+                    .unwrap()
+                });
+                (release_tx, handle)
+            })
+            .unzip();
+        let entered = (0..readers)
+            .take_while(|_| entered_rx.recv_timeout(GIVE_UP).is_ok())
+            .count();
+        for release_tx in release_txs {
+            let _ = release_tx.send(());
+        }
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 50);
+        }
+        assert_eq!(entered, readers, "reads were serialized");
+    }
+}
+
+#[cfg(test)]
+mod folder_settings_tests {
+    use crate::{EnvOverrides, FolderSettings, IndexPriority, OpenContext};
+    use tempfile::TempDir;
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+
+        (ctx, temp_dir)
+    }
+
+    #[test]
+    fn test_settings_inherit_down_the_tree() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("journal/2024", None).unwrap();
+        ctx.create_doc("journal/2024", "may.md", None).unwrap();
+
+        ctx.set_folder_settings(
+            "",
+            None,
+            FolderSettings {
+                index_priority: Some(IndexPriority::High),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        ctx.set_folder_settings(
+            "journal",
+            None,
+            FolderSettings {
+                default_ai_prompt: Some("Reflect.".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        ctx.set_folder_settings(
+            "journal/2024",
+            Some("may.md"),
+            FolderSettings {
+                default_ai_prompt: Some("Summarize May.".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let folder = ctx.resolve_folder_settings("journal/2024").unwrap();
+        assert_eq!(folder.default_ai_prompt.as_deref(), Some("Reflect."));
+        assert_eq!(folder.index_priority(), IndexPriority::High);
+        let doc = ctx.resolve_doc_settings("journal/2024/may.md").unwrap();
+        assert_eq!(doc.default_ai_prompt.as_deref(), Some("Summarize May."));
+
+        // Overrides follow the doc when it is renamed
+        ctx.rename_doc("journal/2024/may.md", "june.md").unwrap();
+        let file = ctx.get_folder_settings("journal/2024").unwrap();
+        assert!(file.docs.contains_key("june.md"));
+        assert!(!file.docs.contains_key("may.md"));
+    }
+
+    #[test]
+    fn test_read_only_is_inherited() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("archive/2023", None).unwrap();
+        ctx.create_doc("archive/2023", "notes.md", None).unwrap();
+        ctx.set_folder_settings(
+            "archive",
+            None,
+            FolderSettings {
+                read_only: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(ctx
+            .resolve_doc_settings("archive/2023/notes.md")
+            .unwrap()
+            .is_read_only());
+        assert!(!ctx.resolve_folder_settings("").unwrap().is_read_only());
+    }
+
+    #[test]
+    fn test_agent_writes_are_refused_in_read_only_folders() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("archive", None).unwrap();
+        ctx.create_doc("archive", "notes.md", None).unwrap();
+        ctx.create_folder("drafts", None).unwrap();
+        ctx.set_folder_settings(
+            "archive",
+            None,
+            FolderSettings {
+                read_only: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for path in ["archive/notes.md", "archive/new.md", "archive/2024/new.md"] {
+            assert!(matches!(
+                ctx.ensure_agent_writable(path),
+                Err(crate::CoreError::ReadOnly { .. })
+            ));
+        }
+        assert!(ctx.ensure_agent_writable("drafts/new.md").is_ok());
+    }
+
+    #[test]
+    fn test_malformed_settings_fail_closed() {
+        let (ctx, temp) = create_test_context();
+        ctx.create_folder("private/notes", None).unwrap();
+        ctx.create_doc("private/notes", "plan.md", None).unwrap();
+        let settings_path = temp
+            .path()
+            .join("contexts/private")
+            .join(crate::FOLDER_SETTINGS_FILE);
+        std::fs::write(&settings_path, "{\"encrypted\": true,").unwrap();
+
+        assert!(ctx.get_folder_settings("private").is_err());
+        assert!(ctx
+            .set_folder_settings("private", None, FolderSettings::default())
+            .is_err());
+        assert_eq!(
+            std::fs::read_to_string(&settings_path).unwrap(),
+            "{\"encrypted\": true,"
+        );
+
+        assert!(ctx.resolve_doc_settings("private/notes/plan.md").is_err());
+        assert!(ctx
+            .save_doc_content("private/notes/plan.md", "secret", None)
+            .is_err());
+        assert!(ctx.ensure_agent_writable("private/notes/plan.md").is_err());
+        assert!(ctx.generate_manifest("private", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_settings_are_saved_through_a_temp_file() {
+        let (ctx, temp) = create_test_context();
+        ctx.create_folder("notes", None).unwrap();
+        ctx.set_folder_settings(
+            "notes",
+            None,
+            FolderSettings {
+                private: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let dir = temp.path().join("contexts/notes");
+        assert!(dir.join(crate::FOLDER_SETTINGS_FILE).exists());
+        assert!(!dir
+            .join(format!("{}.tmp", crate::FOLDER_SETTINGS_FILE))
+            .exists());
+        assert!(ctx.resolve_folder_settings("notes").unwrap().is_private());
+    }
+
+    #[test]
+    fn test_manifest_skips_excluded_folders() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("work/journal", None).unwrap();
+        ctx.create_folder("work/projects", None).unwrap();
+        ctx.create_doc("work/journal", "today.md", None).unwrap();
+        ctx.create_doc("work/projects", "plan.md", None).unwrap();
+        ctx.create_doc("work/projects", "secret.md", None).unwrap();
+
+        ctx.set_folder_settings(
+            "work/journal",
+            None,
+            FolderSettings {
+                exclude_from_manifest: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        ctx.set_folder_settings(
+            "work/projects",
+            Some("secret.md"),
+            FolderSettings {
+                exclude_from_manifest: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let manifest = ctx.generate_manifest("work", Some(1)).unwrap();
+        let paths: Vec<&str> = manifest.iter().map(|e| e.rel_path.as_str()).collect();
+        assert_eq!(paths, ["work/projects/plan.md"]);
+
+        // Clearing the settings removes the file
+        ctx.set_folder_settings("work/journal", None, FolderSettings::default())
+            .unwrap();
+        assert!(!ctx
+            .env_info()
+            .contexts_root
+            .join("work/journal/.folder.json")
+            .exists());
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use crate::stats::count_words;
+    use crate::tags::{extract_tags, split_front_matter};
+    use crate::{EnvOverrides, OpenContext, VaultStatsOptions};
+    use tempfile::TempDir;
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+
+        (ctx, temp_dir)
+    }
+
+    #[test]
+    fn test_tags_and_words() {
+        let text = "---\ntags: [Rust, \"ideas\"]\n---\n# Title\nHello #Rust, see #42 and `#code`\n```\n#not-a-tag\n```\n你好";
+        let (front_matter, body) = split_front_matter(text);
+        assert_eq!(front_matter, "tags: [Rust, \"ideas\"]\n");
+        assert_eq!(extract_tags(front_matter, body), ["ideas", "rust"]);
+        assert_eq!(count_words("Hello world, 你好"), 4);
+    }
+
+    #[test]
+    fn test_vault_statistics() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("notes", None).unwrap();
+        ctx.create_doc("notes", "a.md", None).unwrap();
+        ctx.create_doc("notes", "b.md", None).unwrap();
+        ctx.save_doc_content("notes/a.md", "draft", None).unwrap();
+        ctx.save_doc_content(
+            "notes/a.md",
+            "---\ntags:\n  - journal\n---\nOne two three",
+            None,
+        )
+        .unwrap();
+        ctx.save_doc_content("notes/b.md", "Four #journal #work", None)
+            .unwrap();
+
+        let options = VaultStatsOptions {
+            weeks: 4,
+            word_counts: true,
+            tags: true,
+            ..Default::default()
+        };
+        let stats = ctx.vault_statistics(&options).unwrap();
+        assert_eq!(stats.total_docs, 2);
+        assert_eq!(stats.most_edited[0].rel_path, "notes/a.md");
+        assert_eq!(stats.most_edited[0].edits, 2);
+
+        assert_eq!(stats.weeks.len(), 4);
+        let this_week = stats.weeks.last().unwrap();
+        assert_eq!(this_week.docs_created, 2);
+        assert_eq!(this_week.total_words, Some(6));
+        let words = stats.words.unwrap();
+        assert_eq!(words.total, 6);
+        assert_eq!(words.longest[0].rel_path, "notes/a.md");
+
+        let tags = stats.tags.unwrap();
+        assert_eq!(tags[0].tag, "journal");
+        assert_eq!(tags[0].docs, 2);
+        assert_eq!(tags[1].tag, "work");
+
+        // Unchanged stats are served from the cache
+        let again = ctx.vault_statistics(&options).unwrap();
+        assert_eq!(again.words.unwrap().total, 6);
+    }
+}
+
+#[cfg(test)]
+mod tags_tests {
+    use crate::{EnvOverrides, OpenContext};
+    use tempfile::TempDir;
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+
+        (ctx, temp_dir)
+    }
+
+    #[test]
+    fn test_add_doc_tags_merges_into_front_matter() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("notes", None).unwrap();
+        ctx.create_doc("notes", "a.md", None).unwrap();
+        ctx.save_doc_content(
+            "notes/a.md",
+            "---\ntitle: Plan\ntags:\n  - work\n---\nBody #draft",
+            None,
+        )
+        .unwrap();
+
+        let tags = ctx
+            .add_doc_tags("notes/a.md", &["Road Map".to_string(), "work".to_string()])
+            .unwrap();
+        assert_eq!(tags, ["draft", "road-map", "work"]);
+        assert_eq!(
+            ctx.get_doc_content("notes/a.md").unwrap(),
+            "---\ntitle: Plan\ntags: [road-map, work]\n---\nBody #draft"
+        );
+
+        // A doc without front matter gets one
+        ctx.create_doc("notes", "b.md", None).unwrap();
+        ctx.save_doc_content("notes/b.md", "Hello", None).unwrap();
+        ctx.add_doc_tags("notes/b.md", &["idea".to_string()])
+            .unwrap();
+        assert_eq!(
+            ctx.get_doc_content("notes/b.md").unwrap(),
+            "---\ntags: [idea]\n---\nHello"
+        );
+    }
+}
+
+#[cfg(test)]
+mod crypto_tests {
+    use crate::{CoreError, EnvOverrides, OpenContext};
+    use tempfile::TempDir;
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+
+        (ctx, temp_dir)
+    }
+
+    #[test]
+    fn test_encrypted_folder_round_trip() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("private", None).unwrap();
+        ctx.create_folder("notes", None).unwrap();
+        let doc = ctx.create_doc("private", "a.md", None).unwrap();
+        ctx.save_doc_content("private/a.md", "therapy notes", None)
+            .unwrap();
+
+        // Encrypting needs the vault unlocked
+        assert!(matches!(
+            ctx.set_folder_encrypted("private", true),
+            Err(CoreError::Locked { .. })
+        ));
+        let status = ctx.unlock_vault("correct horse").unwrap();
+        assert!(status.has_passphrase && status.unlocked);
+        let result = ctx.set_folder_encrypted("private", true).unwrap();
+        assert!(result.encrypted);
+        assert_eq!(result.docs_changed, 1);
+
+        let on_disk = std::fs::read(&doc.abs_path).unwrap();
+        assert!(!String::from_utf8_lossy(&on_disk).contains("therapy"));
+        assert_eq!(
+            ctx.get_doc_content("private/a.md").unwrap(),
+            "therapy notes"
+        );
+        ctx.save_doc_content("private/a.md", "updated", None)
+            .unwrap();
+        assert!(
+            !String::from_utf8_lossy(&std::fs::read(&doc.abs_path).unwrap()).contains("updated")
+        );
+
+        // Locked: names still list, content is refused
+        assert!(!ctx.lock_vault().unlocked);
+        assert_eq!(ctx.list_docs("private", false).unwrap().len(), 1);
+        assert!(matches!(
+            ctx.get_doc_content("private/a.md"),
+            Err(CoreError::Locked { .. })
+        ));
+        assert!(ctx.generate_manifest("private", None).unwrap().is_empty());
+
+        assert!(matches!(
+            ctx.unlock_vault("wrong"),
+            Err(CoreError::WrongPassphrase)
+        ));
+        ctx.unlock_vault("correct horse").unwrap();
+        let result = ctx.set_folder_encrypted("private", false).unwrap();
+        assert!(!result.encrypted);
+        assert_eq!(std::fs::read_to_string(&doc.abs_path).unwrap(), "updated");
+    }
+
+    #[test]
+    fn test_move_into_encrypted_folder_needs_unlock() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("private", None).unwrap();
+        ctx.create_folder("notes", None).unwrap();
+        let doc = ctx.create_doc("notes", "a.md", None).unwrap();
+        ctx.save_doc_content("notes/a.md", "therapy notes", None)
+            .unwrap();
+        ctx.unlock_vault("passphrase").unwrap();
+        ctx.set_folder_encrypted("private", true).unwrap();
+        assert!(ctx.unsearchable_docs("notes").unwrap().is_empty());
+
+        // Locked, the doc can't be encrypted on the way in, so it stays put
+        ctx.lock_vault();
+        assert!(matches!(
+            ctx.move_doc("notes/a.md", "private"),
+            Err(CoreError::Locked { .. })
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&doc.abs_path).unwrap(),
+            "therapy notes"
+        );
+
+        ctx.unlock_vault("passphrase").unwrap();
+        ctx.move_doc("notes/a.md", "private").unwrap();
+        let moved = ctx.get_doc_meta("private/a.md").unwrap();
+        assert!(
+            !String::from_utf8_lossy(&std::fs::read(&moved.abs_path).unwrap()).contains("therapy")
+        );
+        assert_eq!(
+            ctx.unsearchable_docs("").unwrap(),
+            vec!["private/a.md".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_encrypted_folder_manifest_opt_in() {
+        let (ctx, _temp) = create_test_context();
+        ctx.create_folder("private", None).unwrap();
+        ctx.create_doc("private", "a.md", None).unwrap();
+        ctx.unlock_vault("passphrase").unwrap();
+        ctx.set_folder_encrypted("private", true).unwrap();
+        assert!(ctx.generate_manifest("private", None).unwrap().is_empty());
+
+        let mut settings = ctx.get_folder_settings("private").unwrap().folder;
+        settings.encrypted = None;
+        settings.index_when_unlocked = Some(true);
+        ctx.set_folder_settings("private", None, settings).unwrap();
+        // `encrypted` is kept: only set_folder_encrypted changes it
+        assert!(ctx
+            .resolve_folder_settings("private")
+            .unwrap()
+            .is_encrypted());
+        assert_eq!(ctx.generate_manifest("private", None).unwrap().len(), 1);
+
+        ctx.lock_vault();
+        assert!(ctx.generate_manifest("private", None).unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod migrate_tests {
+    use crate::migrate::{migrate_config, migrate_vault};
+    use crate::{EnvOverrides, OpenContext, CONFIG_VERSION_KEY};
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn write_config(dir: &Path, config: Value) -> std::path::PathBuf {
+        let path = dir.join("config.json");
+        fs::write(&path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+        path
+    }
+
+    fn read_config(path: &Path) -> Value {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_config_from_version_0_moves_legacy_keys() {
+        let temp = TempDir::new().unwrap();
+        let path = write_config(
+            temp.path(),
+            json!({
+                "OPENAI_API_KEY": "sk-legacy",
+                "OPENAI_BASE_URL": "https://legacy.example/v1",
+                "EMBEDDING_MODEL": "text-embedding-3-small",
+            }),
+        );
+
+        let applied = migrate_config(&path).unwrap();
+        assert_eq!(applied.len(), 1);
+        assert_eq!((applied[0].target, applied[0].version), ("config", 1));
+        assert_eq!(
+            read_config(&path),
+            json!({
+                "EMBEDDING_API_KEY": "sk-legacy",
+                "EMBEDDING_API_BASE": "https://legacy.example/v1",
+                "EMBEDDING_MODEL": "text-embedding-3-small",
+                CONFIG_VERSION_KEY: 1,
+            })
+        );
+        // The original is kept as a backup
+        let backup = read_config(&temp.path().join("config.json.v0.bak"));
+        assert_eq!(backup["OPENAI_API_KEY"], "sk-legacy");
+
+        // Applied exactly once
+        assert!(migrate_config(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_config_from_version_0_keeps_new_keys_over_legacy() {
+        let temp = TempDir::new().unwrap();
+        let path = write_config(
+            temp.path(),
+            json!({ "OPENAI_API_KEY": "sk-legacy", "EMBEDDING_API_KEY": "sk-new" }),
+        );
+
+        migrate_config(&path).unwrap();
+        assert_eq!(
+            read_config(&path),
+            json!({ "EMBEDDING_API_KEY": "sk-new", CONFIG_VERSION_KEY: 1 })
+        );
+    }
+
+    #[test]
+    fn test_config_at_latest_version_is_untouched() {
+        let temp = TempDir::new().unwrap();
+        let config = json!({ "OPENAI_API_KEY": "sk-manual", CONFIG_VERSION_KEY: 1 });
+        let path = write_config(temp.path(), config.clone());
+
+        assert!(migrate_config(&path).unwrap().is_empty());
+        assert_eq!(read_config(&path), config);
+        assert!(!temp.path().join("config.json.v1.bak").exists());
+    }
+
+    #[test]
+    fn test_missing_or_invalid_config_is_left_alone() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.json");
+        assert!(migrate_config(&path).unwrap().is_empty());
+        assert!(!path.exists());
+
+        fs::write(&path, "{ not json").unwrap();
+        assert!(migrate_config(&path).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ not json");
+    }
+
+    #[test]
+    fn test_initialize_runs_migrations() {
+        let temp = TempDir::new().unwrap();
+        let base = temp.path();
+        let path = write_config(base, json!({ "OPENAI_API_KEY": "sk-legacy" }));
+
+        OpenContext::initialize(EnvOverrides {
+            base_root: Some(base.to_path_buf()),
+            contexts_root: Some(base.join("contexts")),
+            db_path: Some(base.join("test.db")),
+        })
+        .unwrap();
+
+        assert_eq!(read_config(&path)["EMBEDDING_API_KEY"], "sk-legacy");
+        assert!(migrate_vault(&base.join("contexts")).unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use crate::{list_snapshots, prune_snapshots, snapshot_text_as_of, EnvOverrides, OpenContext};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_context() -> (OpenContext, TempDir) {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let base_path = temp_dir.path().to_path_buf();
+
+        let ctx = OpenContext::initialize(EnvOverrides {
+            base_root: Some(base_path.clone()),
+            contexts_root: Some(base_path.join("contexts")),
+            db_path: Some(base_path.join("test.db")),
+        })
+        .expect("Failed to initialize context");
+        ctx.create_folder("notes", None).unwrap();
+        ctx.create_doc("notes", "plan.md", Some("The plan"))
+            .unwrap();
+        ctx.save_doc_content("notes/plan.md", "# Plan\n\nv1\n", None)
+            .unwrap();
+        ctx.create_doc("notes", "keep.md", None).unwrap();
+
+        (ctx, temp_dir)
+    }
+
+    #[test]
+    fn test_restore_brings_back_files_and_metadata() {
+        let (ctx, temp) = create_test_context();
+        let snapshots = temp.path().join("snapshots");
+        let contexts = temp.path().join("contexts");
+        let mut progress = Vec::new();
+        let snapshot = ctx
+            .create_snapshot(&snapshots, Some("before agent"), |done, total| {
+                progress.push((done, total))
+            })
+            .unwrap();
+        assert_eq!(
+            progress.last(),
+            Some(&(snapshot.file_count, snapshot.file_count))
+        );
+
+        // What an agent might do
+        ctx.save_doc_content("notes/plan.md", "rewritten", None)
+            .unwrap();
+        ctx.set_doc_description("notes/plan.md", "Changed").unwrap();
+        ctx.remove_doc("notes/keep.md").unwrap();
+        ctx.create_folder("scratch", None).unwrap();
+        ctx.create_doc("scratch", "new.md", None).unwrap();
+
+        let restore = ctx
+            .restore_snapshot(&snapshots, &snapshot.id, |_, _| {})
+            .unwrap();
+        assert_eq!(restore.snapshot.id, snapshot.id);
+        assert!(restore.removed.contains(&"scratch/new.md".to_string()));
+        assert!(restore.restored.contains(&"notes/plan.md".to_string()));
+        assert!(restore.restored.contains(&"notes/keep.md".to_string()));
+
+        assert_eq!(
+            fs::read_to_string(contexts.join("notes/plan.md")).unwrap(),
+            "# Plan\n\nv1\n"
+        );
+        assert!(!contexts.join("scratch").exists());
+        assert_eq!(
+            ctx.get_doc_meta("notes/plan.md").unwrap().description,
+            "The plan"
+        );
+        assert!(ctx.get_doc_meta("notes/keep.md").is_ok());
+        assert!(ctx.get_doc_meta("scratch/new.md").is_err());
+
+        // The pre-restore snapshot holds the state the restore replaced
+        let listed = list_snapshots(&snapshots).unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].id, restore.pre_restore.id);
+        assert_eq!(listed[0].label.as_deref(), Some("pre-restore"));
+    }
+
+    #[test]
+    fn test_restore_takes_snapshots_from_an_older_schema() {
+        let (ctx, temp) = create_test_context();
+        let snapshots = temp.path().join("snapshots");
+        let snapshot = ctx.create_snapshot(&snapshots, None, |_, _| {}).unwrap();
+        // As taken before docs had an edit count
+        let conn = rusqlite::Connection::open(snapshots.join(&snapshot.id).join("opencontext.db"))
+            .unwrap();
+        conn.execute_batch("ALTER TABLE docs DROP COLUMN edit_count;")
+            .unwrap();
+        drop(conn);
+
+        ctx.remove_doc("notes/keep.md").unwrap();
+        ctx.restore_snapshot(&snapshots, &snapshot.id, |_, _| {})
+            .unwrap();
+        assert_eq!(
+            ctx.get_doc_meta("notes/plan.md").unwrap().description,
+            "The plan"
+        );
+        assert!(ctx.get_doc_meta("notes/keep.md").is_ok());
+    }
+
+    #[test]
+    fn test_snapshot_text_as_of_reads_the_last_snapshot_before() {
+        let (ctx, temp) = create_test_context();
+        let snapshots = temp.path().join("snapshots");
+        let before = chrono::Utc::now() - chrono::Duration::seconds(1);
+        ctx.create_snapshot(&snapshots, None, |_, _| {}).unwrap();
+        ctx.save_doc_content("notes/plan.md", "# Plan\n\nv2\n", None)
+            .unwrap();
+
+        let now = chrono::Utc::now();
+        assert_eq!(
+            snapshot_text_as_of(&snapshots, "notes/plan.md", now)
+                .unwrap()
+                .as_deref(),
+            Some("# Plan\n\nv1\n")
+        );
+        assert_eq!(
+            snapshot_text_as_of(&snapshots, "notes/plan.md", before).unwrap(),
+            None
+        );
+        assert_eq!(
+            snapshot_text_as_of(&snapshots, "notes/missing.md", now).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_restore_rejects_unknown_snapshot() {
+        let (ctx, temp) = create_test_context();
+        let snapshots = temp.path().join("snapshots");
+        assert!(ctx
+            .restore_snapshot(&snapshots, "../contexts", |_, _| {})
+            .is_err());
+        assert!(ctx
+            .restore_snapshot(&snapshots, "missing", |_, _| {})
+            .is_err());
+    }
+
+    #[test]
+    fn test_restore_rejects_manifest_paths_outside_the_vault() {
+        let (ctx, temp) = create_test_context();
+        let snapshots = temp.path().join("snapshots");
+        let snapshot = ctx.create_snapshot(&snapshots, None, |_, _| {}).unwrap();
+        let manifest_path = snapshots.join(&snapshot.id).join("manifest.json");
+        let manifest = fs::read_to_string(&manifest_path)
+            .unwrap()
+            .replace("\"notes/plan.md\"", "\"../escape.md\"");
+        fs::write(&manifest_path, manifest).unwrap();
+        ctx.save_doc_content("notes/plan.md", "edited", None)
+            .unwrap();
+
+        assert!(ctx
+            .restore_snapshot(&snapshots, &snapshot.id, |_, _| {})
+            .is_err());
+        assert!(!temp.path().join("escape.md").exists());
+        assert_eq!(
+            fs::read_to_string(temp.path().join("contexts/notes/plan.md")).unwrap(),
+            "edited"
+        );
+    }
+
+    #[test]
+    fn test_prune_keeps_the_newest() {
+        let (ctx, temp) = create_test_context();
+        let snapshots = temp.path().join("snapshots");
+        for _ in 0..3 {
+            ctx.create_snapshot(&snapshots, None, |_, _| {}).unwrap();
+        }
+        let newest = list_snapshots(&snapshots).unwrap()[0].id.clone();
+
+        let pruned = prune_snapshots(&snapshots, Some(2), None).unwrap();
+        assert_eq!(pruned.len(), 1);
+        let pruned = prune_snapshots(&snapshots, None, Some(chrono::Duration::zero())).unwrap();
+        assert_eq!(pruned.len(), 1);
+
+        let left = list_snapshots(&snapshots).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].id, newest);
+    }
+}
+
+#[cfg(all(test, feature = "search"))]
+mod retry_tests {
+    use crate::retry::{is_transient, retry_after, RetryPolicy, MAX_RETRY_DELAY};
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn test_delay_doubles_with_jitter_and_caps() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100));
+        for retry in 0..3 {
+            let backoff = Duration::from_millis(100 << retry);
+            let delay = policy.delay(retry, None);
+            assert!(delay >= backoff / 2 && delay <= backoff, "{:?}", delay);
+        }
+        assert!(policy.delay(30, None) <= MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_delay_waits_at_least_retry_after() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(3600))),
+            MAX_RETRY_DELAY
+        );
+    }
+
+    #[test]
+    fn test_retry_after_reads_seconds_and_dates() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(" 12 "));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(12)));
+
+        // A date in the past means "now"
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_only_transient_statuses_are_retried() {
+        assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_transient(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_transient(StatusCode::UNAUTHORIZED));
+        assert!(!is_transient(StatusCode::BAD_REQUEST));
+        assert_eq!(RetryPolicy::new(0, Duration::ZERO).max_attempts, 1);
+    }
 }
@@ -0,0 +1,100 @@
+//! Paths relative to the contexts root
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::{CoreError, CoreResult};
+
+/// A path inside the contexts root, normalized for storage and lookups
+///
+/// Separators are `/`, and there are no empty, `.` or `..` segments. A
+/// leading `/` means the contexts root, as the webview sends it. `..` may
+/// step back within the path but never above the root. Drive-letter and
+/// UNC paths are rejected. The empty path is the root itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VaultPath(String);
+
+impl VaultPath {
+    /// The contexts root
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Normalize and validate a path from user input
+    pub fn parse(input: &str) -> CoreResult<Self> {
+        let invalid = |reason: &str| CoreError::InvalidPath {
+            path: input.to_string(),
+            reason: reason.to_string(),
+        };
+        let unified = input.trim().replace('\\', "/");
+        if unified.starts_with("//") {
+            return Err(invalid("UNC paths are not allowed"));
+        }
+        let bytes = unified.as_bytes();
+        if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+            return Err(invalid("absolute paths are not allowed"));
+        }
+        if unified.chars().any(char::is_control) {
+            return Err(invalid("paths cannot contain control characters"));
+        }
+
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in unified.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    if segments.pop().is_none() {
+                        return Err(invalid("path leads outside the contexts root"));
+                    }
+                }
+                _ => segments.push(segment),
+            }
+        }
+        Ok(Self(segments.join("/")))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// This path under `contexts_root`
+    pub fn to_abs(&self, contexts_root: &Path) -> PathBuf {
+        if self.is_root() {
+            return contexts_root.to_path_buf();
+        }
+        contexts_root.join(&self.0)
+    }
+}
+
+impl fmt::Display for VaultPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for VaultPath {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl serde::Serialize for VaultPath {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for VaultPath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::parse(&raw).map_err(serde::de::Error::custom)
+    }
+}
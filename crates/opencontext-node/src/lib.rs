@@ -266,6 +266,15 @@ pub struct SearchOptions {
     pub mode: Option<String>,
     pub aggregate_by: Option<String>,
     pub doc_type: Option<String>,
+    pub embedding_profile: Option<String>,
+    pub folder_prefix: Option<String>,
+    pub no_cache: Option<bool>,
+    pub min_score: Option<f64>,
+    pub vector_weight: Option<f64>,
+    pub keyword_weight: Option<f64>,
+    pub cursor: Option<String>,
+    pub group_by_doc: Option<bool>,
+    pub max_chunks_per_doc: Option<u32>,
 }
 
 impl From<SearchOptions> for RustSearchOptions {
@@ -290,6 +299,18 @@ impl From<SearchOptions> for RustSearchOptions {
             mode,
             aggregate_by,
             doc_type: opts.doc_type,
+            embedding_profile: opts.embedding_profile,
+            folder_prefix: opts.folder_prefix,
+            no_cache: opts.no_cache.unwrap_or(false),
+            rerank: false,
+            expand_query: false,
+            query_variants: Vec::new(),
+            min_score: opts.min_score.map(|v| v as f32),
+            vector_weight: opts.vector_weight.map(|v| v as f32),
+            keyword_weight: opts.keyword_weight.map(|v| v as f32),
+            cursor: opts.cursor,
+            group_by_doc: opts.group_by_doc.unwrap_or(false),
+            max_chunks_per_doc: opts.max_chunks_per_doc.map(|v| v as usize),
         }
     }
 }
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use crate::commands::pricing::TokenCounts;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -8,6 +9,16 @@ pub(crate) enum AgentRpcKind {
     OpenCodeAcp,
 }
 
+impl AgentRpcKind {
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            AgentRpcKind::CodexMcp => "Codex",
+            AgentRpcKind::ClaudeAcp => "Claude",
+            AgentRpcKind::OpenCodeAcp => "OpenCode",
+        }
+    }
+}
+
 pub(crate) struct AgentRpcSession {
     pub(crate) kind: AgentRpcKind,
     #[allow(dead_code)]
@@ -33,6 +44,12 @@ pub(crate) struct AgentRpcState {
     pub(crate) codex_received_delta: bool,
     pub(crate) codex_elicitation_map: HashMap<String, u64>,
     pub(crate) codex_patch_changes: HashMap<String, serde_json::Value>,
+    /// Tokens the current Codex turn has used so far
+    pub(crate) codex_turn_tokens: Option<TokenCounts>,
     pub(crate) acp_permission_map: HashMap<String, u64>,
+    /// RPC ids of stopped requests whose cancel has not been acknowledged yet
+    pub(crate) cancelled_rpc_ids: HashSet<u64>,
+    /// Model ids advertised by the agent in its `session/new` response
+    pub(crate) discovered_models: Vec<String>,
     pub(crate) startup_error: Option<String>,
 }
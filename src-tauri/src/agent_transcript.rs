@@ -0,0 +1,451 @@
+//! Server-side copy of each agent session's transcript
+//!
+//! Every `agent-stream-*` event worth keeping is appended to a JSONL file per
+//! session in the app data dir, so a session can be rebuilt after the
+//! webview reloads or crashes mid-stream. Streamed text is coalesced into
+//! one entry per second or so; concatenate the `content` (and `reasoning`)
+//! of a request's entries, in `seq` order, to get its full reply.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+const TRANSCRIPTS_DIR: &str = "agent-transcripts";
+/// Entries kept per session; the oldest are dropped past this
+const MAX_ENTRIES: usize = 5000;
+/// How far a file may grow past the cap before it is rewritten, so appends
+/// don't rewrite the file every time
+const COMPACT_SLACK: usize = 500;
+/// Streamed text is buffered for at most this long before it is written
+const FLUSH_AFTER: Duration = Duration::from_secs(1);
+const FLUSH_BYTES: usize = 4096;
+const MAX_TOOL_TITLE_CHARS: usize = 200;
+/// Statuses worth keeping; the connection steps are not
+const KEPT_STATUSES: [&str; 3] = ["context_truncated", "stopped", "error"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TranscriptEntry {
+    pub(crate) seq: u64,
+    /// ms since epoch
+    pub(crate) ts: u64,
+    pub(crate) request_id: String,
+    /// "user" for the prompt, "agent" for what came back
+    pub(crate) role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reasoning: Option<String>,
+    /// `{ type, callId, title, status }`, without the tool's full output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tool: Option<serde_json::Value>,
+    /// `{ type, callId, prompt, title }` of a permission request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) permission: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) done: Option<bool>,
+}
+
+/// Text streamed for a request and not yet written
+struct PendingText {
+    content: String,
+    reasoning: String,
+    since: Instant,
+}
+
+struct ActiveRequest {
+    session_id: String,
+    pending: Option<PendingText>,
+}
+
+struct SessionLog {
+    next_seq: u64,
+    entries: usize,
+}
+
+/// Transcripts of agent sessions, kept in `AppState`
+#[derive(Default)]
+pub(crate) struct TranscriptStore {
+    /// Streaming requests by id
+    requests: Mutex<HashMap<String, ActiveRequest>>,
+    sessions: Mutex<HashMap<String, SessionLog>>,
+}
+
+impl TranscriptStore {
+    /// Start recording a request's events under `session_id`, with the
+    /// user's prompt as its first entry
+    pub(crate) fn begin(
+        &self,
+        app: &tauri::AppHandle,
+        session_id: &str,
+        request_id: &str,
+        prompt: Option<String>,
+    ) {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                request_id.to_string(),
+                ActiveRequest {
+                    session_id: session_id.to_string(),
+                    pending: None,
+                },
+            );
+        if let Some(prompt) = prompt.filter(|p| !p.trim().is_empty()) {
+            self.append(
+                app,
+                session_id,
+                TranscriptEntry {
+                    request_id: request_id.to_string(),
+                    role: "user".to_string(),
+                    content: Some(prompt),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// Record a streamed event for a request started with `begin`
+    pub(crate) fn record(
+        &self,
+        app: &tauri::AppHandle,
+        request_id: &str,
+        event: &serde_json::Value,
+    ) {
+        let text = |key: &str| event.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let content = text("content").filter(|s| !s.is_empty());
+        let reasoning = text("reasoning").filter(|s| !s.is_empty());
+        let status = text("status").filter(|s| KEPT_STATUSES.contains(&s.as_str()));
+        let error = text("error");
+        let done = event.get("done").and_then(|v| v.as_bool());
+        let tool = event.get("tool").map(summarize_tool);
+        let permission = event.get("permission").map(summarize_permission);
+
+        let mut to_write = Vec::new();
+        let session_id = {
+            let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(request) = requests.get_mut(request_id) else {
+                return;
+            };
+            let session_id = request.session_id.clone();
+            let only_text = tool.is_none()
+                && permission.is_none()
+                && status.is_none()
+                && error.is_none()
+                && done.is_none();
+            if content.is_some() || reasoning.is_some() {
+                let pending = request.pending.get_or_insert_with(|| PendingText {
+                    content: String::new(),
+                    reasoning: String::new(),
+                    since: Instant::now(),
+                });
+                pending
+                    .content
+                    .push_str(content.as_deref().unwrap_or_default());
+                pending
+                    .reasoning
+                    .push_str(reasoning.as_deref().unwrap_or_default());
+            }
+            let flush = !only_text
+                || request.pending.as_ref().is_some_and(|p| {
+                    p.since.elapsed() >= FLUSH_AFTER
+                        || p.content.len() + p.reasoning.len() >= FLUSH_BYTES
+                });
+            if flush {
+                if let Some(pending) = request.pending.take() {
+                    to_write.push(TranscriptEntry {
+                        content: Some(pending.content).filter(|s| !s.is_empty()),
+                        reasoning: Some(pending.reasoning).filter(|s| !s.is_empty()),
+                        ..Default::default()
+                    });
+                }
+            }
+            if !only_text {
+                to_write.push(TranscriptEntry {
+                    tool,
+                    permission,
+                    status,
+                    error,
+                    done,
+                    ..Default::default()
+                });
+            }
+            if done == Some(true) {
+                requests.remove(request_id);
+            }
+            session_id
+        };
+        for entry in to_write {
+            self.append(
+                app,
+                &session_id,
+                TranscriptEntry {
+                    request_id: request_id.to_string(),
+                    role: "agent".to_string(),
+                    ..entry
+                },
+            );
+        }
+    }
+
+    fn append(&self, app: &tauri::AppHandle, session_id: &str, mut entry: TranscriptEntry) {
+        let Some(path) = transcript_path(app, session_id) else {
+            return;
+        };
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let session = sessions.entry(session_id.to_string()).or_insert_with(|| {
+            let entries = read_entries(&path);
+            SessionLog {
+                next_seq: entries.last().map_or(1, |e| e.seq + 1),
+                entries: entries.len(),
+            }
+        });
+        entry.seq = session.next_seq;
+        entry.ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let written = (|| -> std::io::Result<()> {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let line = serde_json::to_string(&entry)?;
+            writeln!(file, "{}", line)
+        })();
+        if let Err(e) = written {
+            log::warn!(
+                "[AgentTranscript] Failed to write {}: {}",
+                path.display(),
+                e
+            );
+            return;
+        }
+        session.next_seq += 1;
+        session.entries += 1;
+        if session.entries > MAX_ENTRIES + COMPACT_SLACK {
+            match compact(&path) {
+                Ok(kept) => session.entries = kept,
+                Err(e) => log::warn!(
+                    "[AgentTranscript] Failed to compact {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Entries of a session from `from_seq` on, oldest first
+    pub(crate) fn read(
+        &self,
+        app: &tauri::AppHandle,
+        session_id: &str,
+        from_seq: u64,
+    ) -> Vec<TranscriptEntry> {
+        // Hold the lock so a compaction can't swap the file mid-read
+        let _sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        transcript_path(app, session_id)
+            .map(|path| read_entries(&path))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| entry.seq >= from_seq)
+            .collect()
+    }
+
+    /// Delete a session's transcript. Returns whether there was one.
+    pub(crate) fn delete(&self, app: &tauri::AppHandle, session_id: &str) -> std::io::Result<bool> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.remove(session_id);
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, request| request.session_id != session_id);
+        match transcript_path(app, session_id) {
+            Some(path) if path.exists() => {
+                fs::remove_file(path)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+fn transcript_path(app: &tauri::AppHandle, session_id: &str) -> Option<PathBuf> {
+    let safe: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if safe.is_empty() {
+        return None;
+    }
+    let dir = app.path().app_data_dir().ok()?.join(TRANSCRIPTS_DIR);
+    Some(dir.join(format!("{}.jsonl", safe)))
+}
+
+/// Entries in a transcript file, skipping lines that don't parse (such as
+/// one cut short by a crash)
+fn read_entries(path: &std::path::Path) -> Vec<TranscriptEntry> {
+    let Ok(file) = fs::File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+/// Keep the newest `MAX_ENTRIES` entries. Returns how many are left.
+fn compact(path: &std::path::Path) -> std::io::Result<usize> {
+    let entries = read_entries(path);
+    let keep = &entries[entries.len().saturating_sub(MAX_ENTRIES)..];
+    let mut content = String::new();
+    for entry in keep {
+        content.push_str(&serde_json::to_string(entry)?);
+        content.push('\n');
+    }
+    let temp = path.with_extension("jsonl.tmp");
+    fs::write(&temp, content)?;
+    fs::rename(&temp, path)?;
+    Ok(keep.len())
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// A short title for a tool call: its title, command or name
+fn tool_title(data: &serde_json::Value) -> Option<String> {
+    ["title", "command", "name", "kind"]
+        .iter()
+        .find_map(|key| match data.get(*key)? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Array(parts) => Some(
+                parts
+                    .iter()
+                    .filter_map(|p| p.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            _ => None,
+        })
+        .filter(|title| !title.is_empty())
+        .map(|title| truncate_chars(&title, MAX_TOOL_TITLE_CHARS))
+}
+
+fn summarize_tool(tool: &serde_json::Value) -> serde_json::Value {
+    let data = tool.get("data").unwrap_or(&serde_json::Value::Null);
+    serde_json::json!({
+        "type": tool.get("type"),
+        "callId": tool.get("callId"),
+        "title": tool_title(data),
+        "status": data.get("status"),
+    })
+}
+
+fn summarize_permission(permission: &serde_json::Value) -> serde_json::Value {
+    let data = permission
+        .get("toolCall")
+        .filter(|v| !v.is_null())
+        .or_else(|| permission.get("data"))
+        .unwrap_or(&serde_json::Value::Null);
+    serde_json::json!({
+        "type": permission.get("type").or_else(|| permission.get("source")),
+        "callId": permission.get("callId"),
+        "prompt": permission.get("prompt"),
+        "title": tool_title(data),
+    })
+}
+
+/// The transcript as markdown, for saving as a doc
+pub(crate) fn to_markdown(title: &str, entries: &[TranscriptEntry]) -> String {
+    let mut out = format!("# {}\n", title);
+    let mut last_role = "";
+    for entry in entries {
+        if entry.role == "user" {
+            out.push_str("\n## You\n\n");
+            out.push_str(entry.content.as_deref().unwrap_or_default().trim());
+            out.push('\n');
+            last_role = "user";
+            continue;
+        }
+        if last_role != "agent" {
+            out.push_str("\n## Agent\n\n");
+            last_role = "agent";
+        }
+        if let Some(content) = &entry.content {
+            out.push_str(content);
+        }
+        if let Some(title) = entry
+            .tool
+            .as_ref()
+            .and_then(|tool| tool.get("title"))
+            .and_then(|t| t.as_str())
+        {
+            out.push_str(&format!("\n\n> Tool: `{}`\n\n", title.replace('`', "'")));
+        }
+        if let Some(error) = &entry.error {
+            out.push_str(&format!("\n\n> Error: {}\n\n", error));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_markdown_joins_streamed_text_per_turn() {
+        let entry = |role: &str, content: &str| TranscriptEntry {
+            role: role.to_string(),
+            content: Some(content.to_string()),
+            ..Default::default()
+        };
+        let entries = vec![
+            entry("user", "Summarize plan.md"),
+            entry("agent", "The plan "),
+            TranscriptEntry {
+                role: "agent".to_string(),
+                tool: Some(serde_json::json!({ "title": "cat plan.md" })),
+                ..Default::default()
+            },
+            entry("agent", "has three steps."),
+        ];
+        assert_eq!(
+            to_markdown("Session", &entries),
+            "# Session\n\n## You\n\nSummarize plan.md\n\n## Agent\n\nThe plan \n\n> Tool: `cat plan.md`\n\nhas three steps.\n"
+        );
+    }
+
+    #[test]
+    fn summarize_tool_keeps_title_not_output() {
+        let tool = serde_json::json!({
+            "type": "exec_command_begin",
+            "callId": "c1",
+            "data": { "command": ["ls", "-la"], "output": "x".repeat(10_000) },
+        });
+        let summary = summarize_tool(&tool);
+        assert_eq!(summary["title"], "ls -la");
+        assert!(summary.get("output").is_none());
+    }
+}
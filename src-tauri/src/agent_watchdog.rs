@@ -0,0 +1,243 @@
+//! Watchdog for agent requests that go quiet
+//!
+//! An agent can stop talking in the middle of a turn, e.g. a Codex turn that
+//! never sends `task_complete` after the CLI lost its network, and the
+//! request would then never end. Every event a request emits resets its
+//! watchdog. After `AGENT_STALL_MINUTES` without one the request gets a
+//! `stalled` status, and after `AGENT_STALL_GRACE_MINUTES` more it ends with
+//! an error. A request waiting on the user to answer a permission prompt is
+//! not timed until its next event.
+
+use crate::utils::get_config_value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minutes without an event before a request is reported stalled
+pub(crate) const DEFAULT_STALL_MINUTES: u64 = 10;
+/// Minutes a stalled request gets before it is ended
+pub(crate) const DEFAULT_GRACE_MINUTES: u64 = 5;
+
+/// How long a request may go quiet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct StallLimits {
+    /// Quiet time before the request is reported stalled
+    pub(crate) stall: Duration,
+    /// Further quiet time before it is ended
+    pub(crate) grace: Duration,
+}
+
+impl StallLimits {
+    /// Read `AGENT_STALL_MINUTES` and `AGENT_STALL_GRACE_MINUTES` from
+    /// config. `None` when `AGENT_STALL_MINUTES` is 0, which turns the
+    /// watchdog off.
+    pub(crate) fn from_config() -> Option<Self> {
+        let minutes = |key: &str, default: u64| {
+            get_config_value(key)
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let stall = minutes("AGENT_STALL_MINUTES", DEFAULT_STALL_MINUTES);
+        if stall == 0 {
+            return None;
+        }
+        Some(Self {
+            stall: Duration::from_secs(stall * 60),
+            grace: Duration::from_secs(
+                minutes("AGENT_STALL_GRACE_MINUTES", DEFAULT_GRACE_MINUTES) * 60,
+            ),
+        })
+    }
+}
+
+/// config.json `AGENT_STALL_INTERRUPT`: whether the agent is asked to cancel
+/// a request the watchdog ended. On unless set to `false`.
+pub(crate) fn interrupt_on_expiry() -> bool {
+    get_config_value("AGENT_STALL_INTERRUPT").map_or(true, |v| v.trim() != "false")
+}
+
+/// An event of a watched request, as the watchdog counts it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Activity {
+    /// Content, reasoning, a tool call or a status: the agent is alive
+    Event,
+    /// A permission prompt: the agent waits on the user, not the other way
+    AwaitingUser,
+    /// The request ended
+    Done,
+}
+
+/// What a watched request's quiet time calls for
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    /// Nothing yet
+    Quiet,
+    /// Report the request stalled; returned once per quiet spell
+    Stalled,
+    /// End the request; it is no longer watched
+    Expired,
+    /// The request is not watched, having ended or never been started
+    Unwatched,
+}
+
+struct Watch {
+    last_event: Instant,
+    awaiting_user: bool,
+    stalled: bool,
+}
+
+/// Watchdogs of running agent requests, by request id
+#[derive(Default)]
+pub(crate) struct Watchdogs {
+    watches: Mutex<HashMap<String, Watch>>,
+}
+
+impl Watchdogs {
+    /// Start timing `request_id` from `now`
+    pub(crate) fn start(&self, request_id: &str, now: Instant) {
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        watches.insert(
+            request_id.to_string(),
+            Watch {
+                last_event: now,
+                awaiting_user: false,
+                stalled: false,
+            },
+        );
+    }
+
+    /// Note `activity` of `request_id` at `now`. Events of requests not
+    /// being watched are ignored.
+    pub(crate) fn record(&self, request_id: &str, activity: Activity, now: Instant) {
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        if activity == Activity::Done {
+            watches.remove(request_id);
+        } else if let Some(watch) = watches.get_mut(request_id) {
+            watch.last_event = now;
+            watch.awaiting_user = activity == Activity::AwaitingUser;
+            watch.stalled = false;
+        }
+    }
+
+    /// What `request_id` having been quiet until `now` calls for
+    pub(crate) fn check(&self, request_id: &str, now: Instant, limits: StallLimits) -> Verdict {
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(watch) = watches.get_mut(request_id) else {
+            return Verdict::Unwatched;
+        };
+        if watch.awaiting_user {
+            return Verdict::Quiet;
+        }
+        let quiet = now.saturating_duration_since(watch.last_event);
+        if quiet >= limits.stall + limits.grace {
+            watches.remove(request_id);
+            Verdict::Expired
+        } else if quiet >= limits.stall && !watch.stalled {
+            watch.stalled = true;
+            Verdict::Stalled
+        } else {
+            Verdict::Quiet
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: StallLimits = StallLimits {
+        stall: Duration::from_secs(600),
+        grace: Duration::from_secs(300),
+    };
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    #[test]
+    fn quiet_request_stalls_once_then_expires() {
+        let watchdogs = Watchdogs::default();
+        let start = Instant::now();
+        watchdogs.start("req", start);
+
+        assert_eq!(
+            watchdogs.check("req", start + minutes(9), LIMITS),
+            Verdict::Quiet
+        );
+        assert_eq!(
+            watchdogs.check("req", start + minutes(10), LIMITS),
+            Verdict::Stalled
+        );
+        assert_eq!(
+            watchdogs.check("req", start + minutes(12), LIMITS),
+            Verdict::Quiet
+        );
+        assert_eq!(
+            watchdogs.check("req", start + minutes(15), LIMITS),
+            Verdict::Expired
+        );
+        assert_eq!(
+            watchdogs.check("req", start + minutes(16), LIMITS),
+            Verdict::Unwatched
+        );
+    }
+
+    #[test]
+    fn events_reset_the_timers() {
+        let watchdogs = Watchdogs::default();
+        let start = Instant::now();
+        watchdogs.start("req", start);
+        assert_eq!(
+            watchdogs.check("req", start + minutes(11), LIMITS),
+            Verdict::Stalled
+        );
+
+        // The agent came back; a later quiet spell is reported again
+        watchdogs.record("req", Activity::Event, start + minutes(12));
+        assert_eq!(
+            watchdogs.check("req", start + minutes(16), LIMITS),
+            Verdict::Quiet
+        );
+        assert_eq!(
+            watchdogs.check("req", start + minutes(22), LIMITS),
+            Verdict::Stalled
+        );
+        assert_eq!(
+            watchdogs.check("req", start + minutes(27), LIMITS),
+            Verdict::Expired
+        );
+    }
+
+    #[test]
+    fn done_and_permission_prompts_stop_the_clock() {
+        let watchdogs = Watchdogs::default();
+        let start = Instant::now();
+        watchdogs.start("asking", start);
+        watchdogs.start("finished", start);
+
+        watchdogs.record("asking", Activity::AwaitingUser, start + minutes(1));
+        watchdogs.record("finished", Activity::Done, start + minutes(1));
+        assert_eq!(
+            watchdogs.check("asking", start + minutes(60), LIMITS),
+            Verdict::Quiet
+        );
+        assert_eq!(
+            watchdogs.check("finished", start + minutes(60), LIMITS),
+            Verdict::Unwatched
+        );
+
+        // Answering the prompt restarts the timing
+        watchdogs.record("asking", Activity::Event, start + minutes(60));
+        assert_eq!(
+            watchdogs.check("asking", start + minutes(70), LIMITS),
+            Verdict::Stalled
+        );
+
+        // Events of requests never watched don't start a watch
+        watchdogs.record("other", Activity::Event, start);
+        assert_eq!(
+            watchdogs.check("other", start + minutes(60), LIMITS),
+            Verdict::Unwatched
+        );
+    }
+}
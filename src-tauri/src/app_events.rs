@@ -0,0 +1,121 @@
+//! Event names scoped to this app instance
+//!
+//! Every event the app emits is named `<instance>:<event>`, e.g.
+//! `com-opencontext-app-1a2b3c4d:index-progress`, so a dev build and a
+//! release build running side by side on different contexts roots never act
+//! on each other's events. The instance id comes from the app identifier and
+//! the contexts root, or from `OPENCONTEXT_INSTANCE_ID` when that is set.
+//! `get_env_info` hands it to the frontend, whose `listenAppEvent` subscribes
+//! to the scoped name. Map payloads also carry it as `instanceId`.
+
+use serde::Serialize;
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::{Emitter, Runtime};
+
+/// Overrides the derived instance id
+const INSTANCE_ENV: &str = "OPENCONTEXT_INSTANCE_ID";
+
+static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+
+/// Characters Tauri accepts in event names, other than the `:` separator
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// FNV-1a, stable across builds so an instance keeps its id between runs
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Instance id of the app `identifier` running on `contexts_root`
+fn derive_instance_id(identifier: &str, contexts_root: &Path) -> String {
+    format!(
+        "{}-{:08x}",
+        sanitize(identifier),
+        fnv1a(contexts_root.to_string_lossy().as_bytes())
+    )
+}
+
+/// Settle the instance id. Call once at startup, before anything is emitted.
+pub(crate) fn init(identifier: &str, contexts_root: &Path) {
+    let id = std::env::var(INSTANCE_ENV)
+        .ok()
+        .map(|id| sanitize(id.trim()))
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| derive_instance_id(identifier, contexts_root));
+    log::info!("[Events] Instance id {}", id);
+    let _ = INSTANCE_ID.set(id);
+}
+
+/// This app instance's id; `opencontext` until `init` runs
+pub(crate) fn instance_id() -> &'static str {
+    INSTANCE_ID.get().map_or("opencontext", String::as_str)
+}
+
+/// `event` as this instance emits it
+pub(crate) fn scoped(event: &str) -> String {
+    format!("{}:{}", instance_id(), event)
+}
+
+/// `payload` with `instanceId` added if it serializes to a map; other
+/// payloads are sent as they are
+fn with_instance(payload: impl Serialize, instance: &str) -> serde_json::Result<serde_json::Value> {
+    let mut value = serde_json::to_value(payload)?;
+    if let serde_json::Value::Object(fields) = &mut value {
+        fields.insert(
+            "instanceId".to_string(),
+            serde_json::Value::String(instance.to_string()),
+        );
+    }
+    Ok(value)
+}
+
+/// Emitting under this instance's event names
+pub(crate) trait EmitScoped<R: Runtime>: Emitter<R> {
+    /// Emit `event` to every target as `<instance>:<event>`
+    fn emit_scoped(&self, event: &str, payload: impl Serialize) -> tauri::Result<()> {
+        self.emit(&scoped(event), with_instance(payload, instance_id())?)
+    }
+}
+
+impl<R: Runtime, T: Emitter<R>> EmitScoped<R> for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn instance_id_tells_contexts_roots_apart() {
+        let dev = derive_instance_id("com.opencontext.app", Path::new("/tmp/dev/contexts"));
+        let release = derive_instance_id("com.opencontext.app", Path::new("/home/me/contexts"));
+        assert!(dev.starts_with("com-opencontext-app-"));
+        assert_ne!(dev, release);
+        assert_eq!(
+            dev,
+            derive_instance_id("com.opencontext.app", Path::new("/tmp/dev/contexts"))
+        );
+    }
+
+    #[test]
+    fn instance_id_goes_into_map_payloads_only() {
+        let payload = with_instance(json!({ "id": "t1", "data": "ls\n" }), "dev").unwrap();
+        assert_eq!(
+            payload,
+            json!({ "id": "t1", "data": "ls\n", "instanceId": "dev" })
+        );
+        assert_eq!(with_instance(true, "dev").unwrap(), json!(true));
+        assert_eq!(with_instance((), "dev").unwrap(), json!(null));
+    }
+}
@@ -1,5 +1,13 @@
+use crate::utils::get_config_value;
 use serde::{Deserialize, Serialize};
 
+/// Rough chars-per-token ratio used when the limit is configured in tokens.
+const CHARS_PER_TOKEN: usize = 4;
+pub(crate) const DEFAULT_KEEP_LAST: usize = 6;
+/// Per-message excerpt length in a summarize-middle note.
+const SUMMARY_EXCERPT_CHARS: usize = 120;
+const TRUNCATED_MARKER: &str = "\n[truncated]";
+
 #[derive(Deserialize, Serialize, Clone)]
 pub(crate) struct ChatMessage {
     pub(crate) role: String,
@@ -72,6 +80,205 @@ pub(crate) fn build_cli_prompt(messages: &[ChatMessage]) -> String {
     lines.join("\n\n")
 }
 
+/// How to shrink a conversation that exceeds the prompt budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TruncationStrategy {
+    /// Drop the oldest non-system messages until the prompt fits.
+    DropOldest,
+    /// Keep system messages and the last N other messages.
+    KeepSystemLastN(usize),
+    /// Keep the first exchange and the most recent messages; replace the
+    /// middle with a short extractive note.
+    SummarizeMiddle,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PromptLimit {
+    pub(crate) max_chars: usize,
+    pub(crate) strategy: TruncationStrategy,
+}
+
+impl PromptLimit {
+    /// Read `AI_MAX_PROMPT_CHARS` (or `AI_MAX_PROMPT_TOKENS`) and
+    /// `AI_PROMPT_TRUNCATION` from config. `None` when no limit is set.
+    pub(crate) fn from_config() -> Option<Self> {
+        let max_chars = get_config_value("AI_MAX_PROMPT_CHARS")
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .or_else(|| {
+                get_config_value("AI_MAX_PROMPT_TOKENS")
+                    .and_then(|v| v.trim().parse::<usize>().ok())
+                    .map(|tokens| tokens.saturating_mul(CHARS_PER_TOKEN))
+            })
+            .filter(|max| *max > 0)?;
+        let keep_last = get_config_value("AI_PROMPT_KEEP_LAST")
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_KEEP_LAST);
+        let strategy = match get_config_value("AI_PROMPT_TRUNCATION").as_deref() {
+            Some("keep-system-last-n") => TruncationStrategy::KeepSystemLastN(keep_last),
+            Some("summarize-middle") => TruncationStrategy::SummarizeMiddle,
+            _ => TruncationStrategy::DropOldest,
+        };
+        Some(Self {
+            max_chars,
+            strategy,
+        })
+    }
+
+    /// Shrink `messages` to fit the budget. Returns the messages to send and
+    /// whether anything was dropped or cut.
+    pub(crate) fn apply(&self, messages: &[ChatMessage]) -> (Vec<ChatMessage>, bool) {
+        if prompt_size(messages) <= self.max_chars {
+            return (messages.to_vec(), false);
+        }
+        let mut kept = match self.strategy {
+            TruncationStrategy::DropOldest => drop_oldest(messages.to_vec(), self.max_chars),
+            TruncationStrategy::KeepSystemLastN(n) => {
+                drop_oldest(keep_system_last_n(messages, n), self.max_chars)
+            }
+            TruncationStrategy::SummarizeMiddle => summarize_middle(messages, self.max_chars),
+        };
+        clip_latest(&mut kept, self.max_chars);
+        (kept, true)
+    }
+}
+
+/// Apply the configured prompt limit, if any.
+pub(crate) fn fit_prompt_messages(messages: &[ChatMessage]) -> (Vec<ChatMessage>, bool) {
+    match PromptLimit::from_config() {
+        Some(limit) => limit.apply(messages),
+        None => (messages.to_vec(), false),
+    }
+}
+
+/// Size of a message as rendered by `build_cli_prompt` ("ROLE: text\n\n").
+fn message_size(msg: &ChatMessage) -> usize {
+    let content = flatten_message_content(&msg.content);
+    let content = content.trim();
+    if content.is_empty() {
+        return 0;
+    }
+    msg.role.chars().count() + content.chars().count() + 4
+}
+
+fn prompt_size(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(message_size).sum()
+}
+
+fn is_system(msg: &ChatMessage) -> bool {
+    msg.role.eq_ignore_ascii_case("system")
+}
+
+/// Remove the oldest non-system messages, always keeping the latest message.
+fn drop_oldest(mut messages: Vec<ChatMessage>, max_chars: usize) -> Vec<ChatMessage> {
+    while prompt_size(&messages) > max_chars {
+        let last = messages.len().saturating_sub(1);
+        match messages.iter().position(|m| !is_system(m)) {
+            Some(index) if index < last => {
+                messages.remove(index);
+            }
+            _ => break,
+        }
+    }
+    messages
+}
+
+fn keep_system_last_n(messages: &[ChatMessage], n: usize) -> Vec<ChatMessage> {
+    let others = messages.iter().filter(|m| !is_system(m)).count();
+    let skip = others.saturating_sub(n.max(1));
+    let mut seen = 0;
+    messages
+        .iter()
+        .filter(|m| {
+            if is_system(m) {
+                return true;
+            }
+            seen += 1;
+            seen > skip
+        })
+        .cloned()
+        .collect()
+}
+
+fn excerpt(text: &str, max_chars: usize) -> String {
+    let line = text.trim().lines().next().unwrap_or("").trim();
+    if line.chars().count() <= max_chars {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(max_chars).collect();
+    format!("{}…", cut)
+}
+
+fn summarize_middle(messages: &[ChatMessage], max_chars: usize) -> Vec<ChatMessage> {
+    let (system, others): (Vec<&ChatMessage>, Vec<&ChatMessage>) =
+        messages.iter().partition(|m| is_system(m));
+    if others.len() < 3 {
+        return drop_oldest(messages.to_vec(), max_chars);
+    }
+
+    let head = others[0];
+    let fixed = system.iter().map(|m| message_size(m)).sum::<usize>() + message_size(head);
+    // Walk back from the newest message, keeping what fits in half the
+    // remaining budget; the rest is left for the summary note.
+    let tail_budget = max_chars.saturating_sub(fixed) / 2;
+    let mut tail_start = others.len() - 1;
+    let mut tail_size = message_size(others[tail_start]);
+    while tail_start > 1 && tail_size + message_size(others[tail_start - 1]) <= tail_budget {
+        tail_start -= 1;
+        tail_size += message_size(others[tail_start]);
+    }
+
+    let middle = &others[1..tail_start];
+    let mut kept: Vec<ChatMessage> = system.into_iter().cloned().collect();
+    kept.push(head.clone());
+    if !middle.is_empty() {
+        let note_budget = max_chars.saturating_sub(fixed + tail_size);
+        let mut note = format!("[{} earlier messages omitted]", middle.len());
+        for msg in middle {
+            let line = format!(
+                "\n- {}: {}",
+                msg.role,
+                excerpt(
+                    &flatten_message_content(&msg.content),
+                    SUMMARY_EXCERPT_CHARS
+                )
+            );
+            if note.chars().count() + line.chars().count() + "system".len() + 4 > note_budget {
+                break;
+            }
+            note.push_str(&line);
+        }
+        kept.push(ChatMessage {
+            role: "system".to_string(),
+            content: serde_json::Value::String(note),
+        });
+    }
+    kept.extend(others[tail_start..].iter().map(|m| (*m).clone()));
+    drop_oldest(kept, max_chars)
+}
+
+/// Last resort when the latest message alone overflows: cut its text.
+/// Only plain-string content is cut so attachments are never mangled.
+fn clip_latest(messages: &mut [ChatMessage], max_chars: usize) {
+    let total = prompt_size(messages);
+    if total <= max_chars {
+        return;
+    }
+    let Some(last) = messages.last_mut() else {
+        return;
+    };
+    let Some(text) = last.content.as_str() else {
+        return;
+    };
+    let text = text.trim();
+    let keep = text
+        .chars()
+        .count()
+        .saturating_sub(total - max_chars)
+        .saturating_sub(TRUNCATED_MARKER.chars().count());
+    let clipped: String = text.chars().take(keep).collect();
+    last.content = serde_json::Value::String(format!("{}{}", clipped, TRUNCATED_MARKER));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,4 +308,103 @@ mod tests {
         ];
         assert_eq!(build_cli_prompt(&messages), "USER: Hi\n\nASSISTANT: Ok");
     }
+
+    fn msg(role: &str, text: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: json!(text),
+        }
+    }
+
+    fn long_chat() -> Vec<ChatMessage> {
+        let mut messages = vec![msg("system", "Be brief.")];
+        for i in 0..10 {
+            messages.push(msg("user", &format!("question {} {}", i, "x".repeat(40))));
+            messages.push(msg(
+                "assistant",
+                &format!("answer {} {}", i, "y".repeat(40)),
+            ));
+        }
+        messages.push(msg("user", "final question"));
+        messages
+    }
+
+    fn texts(messages: &[ChatMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|m| flatten_message_content(&m.content))
+            .collect()
+    }
+
+    #[test]
+    fn prompt_limit_leaves_short_chats_untouched() {
+        let limit = PromptLimit {
+            max_chars: 10_000,
+            strategy: TruncationStrategy::DropOldest,
+        };
+        let messages = long_chat();
+        let (kept, truncated) = limit.apply(&messages);
+        assert!(!truncated);
+        assert_eq!(kept.len(), messages.len());
+    }
+
+    #[test]
+    fn drop_oldest_keeps_system_and_latest() {
+        let limit = PromptLimit {
+            max_chars: 300,
+            strategy: TruncationStrategy::DropOldest,
+        };
+        let (kept, truncated) = limit.apply(&long_chat());
+        assert!(truncated);
+        assert!(build_cli_prompt(&kept).len() <= 300);
+        assert_eq!(kept[0].role, "system");
+        assert_eq!(texts(&kept).last().unwrap(), "final question");
+        assert!(!texts(&kept).iter().any(|t| t.starts_with("question 0")));
+    }
+
+    #[test]
+    fn keep_system_last_n_keeps_exactly_n_recent() {
+        let limit = PromptLimit {
+            max_chars: 600,
+            strategy: TruncationStrategy::KeepSystemLastN(3),
+        };
+        let (kept, truncated) = limit.apply(&long_chat());
+        assert!(truncated);
+        assert_eq!(kept.len(), 4);
+        assert_eq!(kept[0].role, "system");
+        let kept_texts = texts(&kept);
+        assert!(kept_texts[1].starts_with("question 9"));
+        assert!(kept_texts[2].starts_with("answer 9"));
+        assert_eq!(kept_texts[3], "final question");
+    }
+
+    #[test]
+    fn summarize_middle_keeps_head_and_tail_with_note() {
+        let limit = PromptLimit {
+            max_chars: 600,
+            strategy: TruncationStrategy::SummarizeMiddle,
+        };
+        let (kept, truncated) = limit.apply(&long_chat());
+        assert!(truncated);
+        assert!(build_cli_prompt(&kept).len() <= 600);
+        let kept_texts = texts(&kept);
+        assert_eq!(kept_texts[0], "Be brief.");
+        assert!(kept_texts[1].starts_with("question 0"));
+        assert!(kept_texts[2].contains("earlier messages omitted"));
+        assert_eq!(kept_texts.last().unwrap(), "final question");
+    }
+
+    #[test]
+    fn oversized_latest_message_is_clipped() {
+        let limit = PromptLimit {
+            max_chars: 100,
+            strategy: TruncationStrategy::DropOldest,
+        };
+        let messages = vec![msg("user", "old"), msg("user", &"z".repeat(500))];
+        let (kept, truncated) = limit.apply(&messages);
+        assert!(truncated);
+        assert_eq!(kept.len(), 1);
+        assert!(build_cli_prompt(&kept).chars().count() <= 100);
+        assert!(texts(&kept)[0].ends_with("[truncated]"));
+    }
 }
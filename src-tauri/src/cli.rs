@@ -0,0 +1,397 @@
+//! Headless subcommands on the desktop binary, for scripts and cron:
+//!
+//! ```text
+//! opencontext index --all | <doc-path>
+//! opencontext search "<query>" [--limit N] [--mode hybrid|vector|keyword] [--min-score 0-1] [--no-cache] [--json]
+//! opencontext doc get <doc-path> | --id <stable-id> [--json]
+//! opencontext doc create <folder> <name> [--description <text>]
+//! opencontext manifest <folder> [--limit N]
+//! ```
+//!
+//! Unrecognized or empty argv falls through to the GUI. Exit codes: 0 success,
+//! 1 runtime failure, 2 usage error, 3 file read/write failure. On Windows
+//! release builds the binary uses the GUI subsystem, so output is only visible
+//! when redirected to a file/pipe.
+
+use opencontext_core::search::{
+    Indexer, SearchConfig, SearchError, SearchMode, SearchOptions, Searcher,
+};
+use opencontext_core::{CoreError, EnvOverrides, OpenContext};
+use std::collections::HashMap;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_IO: i32 = 3;
+
+const SUBCOMMANDS: &[&str] = &["index", "search", "doc", "manifest", "help"];
+
+/// Flags that take a value; everything else starting with `--` is boolean.
+const VALUE_FLAGS: &[&str] = &["--limit", "--mode", "--min-score", "--description", "--id"];
+
+const USAGE: &str = "Usage:
+  opencontext index --all | <doc-path>
+  opencontext search \"<query>\" [--limit N] [--mode hybrid|vector|keyword] [--min-score 0-1] [--no-cache] [--json]
+  opencontext doc get <doc-path> | --id <stable-id> [--json]
+  opencontext doc create <folder> <name> [--description <text>]
+  opencontext manifest <folder> [--limit N]";
+
+enum CliError {
+    Usage(String),
+    Runtime(String),
+    /// Reading or writing a file failed, whatever the arguments were
+    Io(String),
+}
+
+type CliResult<T> = Result<T, CliError>;
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Runtime(_) => EXIT_FAILURE,
+            CliError::Io(_) => EXIT_IO,
+        }
+    }
+}
+
+fn runtime<E: std::fmt::Display>(e: E) -> CliError {
+    CliError::Runtime(e.to_string())
+}
+
+/// A core error from looking up what the arguments name: a usage error,
+/// unless the vault itself could not be read
+fn input_error(e: CoreError) -> CliError {
+    match e {
+        CoreError::Io(_) | CoreError::Db(_) => e.into(),
+        e => CliError::Usage(e.to_string()),
+    }
+}
+
+impl From<CoreError> for CliError {
+    fn from(e: CoreError) -> Self {
+        match e {
+            CoreError::Io(e) => CliError::Io(e.to_string()),
+            e => runtime(e),
+        }
+    }
+}
+
+impl From<SearchError> for CliError {
+    fn from(e: SearchError) -> Self {
+        match e {
+            SearchError::Io(e) => CliError::Io(e.to_string()),
+            e => runtime(e),
+        }
+    }
+}
+
+struct ParsedArgs {
+    positional: Vec<String>,
+    flags: HashMap<String, Option<String>>,
+}
+
+impl ParsedArgs {
+    fn parse(args: &[String]) -> CliResult<Self> {
+        let mut positional = Vec::new();
+        let mut flags = HashMap::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if VALUE_FLAGS.contains(&arg.as_str()) {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| CliError::Usage(format!("{} requires a value", arg)))?;
+                flags.insert(arg.clone(), Some(value.clone()));
+            } else if arg.starts_with("--") {
+                flags.insert(arg.clone(), None);
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        Ok(Self { positional, flags })
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.contains_key(flag)
+    }
+
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.flags.get(flag).and_then(|v| v.as_deref())
+    }
+
+    fn usize_value(&self, flag: &str) -> CliResult<Option<usize>> {
+        self.value(flag)
+            .map(|v| {
+                v.parse::<usize>()
+                    .map_err(|_| CliError::Usage(format!("{} expects a number, got '{}'", flag, v)))
+            })
+            .transpose()
+    }
+}
+
+/// Run a CLI subcommand if argv names one. Returns the process exit code, or
+/// `None` when the GUI should start instead.
+pub(crate) fn run_from_env() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first()?;
+    if !SUBCOMMANDS.contains(&command.as_str()) {
+        return None;
+    }
+
+    let result = ParsedArgs::parse(&args[1..]).and_then(|parsed| run(command, parsed));
+    Some(match result {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            match &e {
+                CliError::Usage(message) => eprintln!("error: {}\n\n{}", message, USAGE),
+                CliError::Runtime(message) | CliError::Io(message) => {
+                    eprintln!("error: {}", message)
+                }
+            }
+            e.exit_code()
+        }
+    })
+}
+
+fn run(command: &str, args: ParsedArgs) -> CliResult<()> {
+    match command {
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        "index" => run_index(args),
+        "search" => run_search(args),
+        "doc" => run_doc(args),
+        "manifest" => run_manifest(args),
+        other => Err(CliError::Usage(format!("unknown command '{}'", other))),
+    }
+}
+
+fn open_context() -> CliResult<OpenContext> {
+    Ok(OpenContext::initialize(EnvOverrides::default())?)
+}
+
+fn block_on<F: std::future::Future>(future: F) -> CliResult<F::Output> {
+    let runtime = tokio::runtime::Runtime::new().map_err(runtime)?;
+    Ok(runtime.block_on(future))
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> CliResult<()> {
+    println!("{}", serde_json::to_string_pretty(value).map_err(runtime)?);
+    Ok(())
+}
+
+fn run_index(args: ParsedArgs) -> CliResult<()> {
+    let ctx = open_context()?;
+    let contexts_root = ctx.env_info().contexts_root;
+    let config = SearchConfig::load()?;
+
+    if args.has("--all") {
+        let folders = ctx.list_folders(true)?;
+        let mut docs = Vec::new();
+        for folder in folders {
+            if let Ok(folder_docs) = ctx.list_docs(&folder.rel_path, false) {
+                docs.extend(folder_docs);
+            }
+        }
+        let stats = block_on(async move {
+            let mut indexer = Indexer::new(config, contexts_root).await?;
+            let stats = indexer.build_all(docs).await?;
+            indexer.update_metadata()?;
+            Ok::<_, SearchError>(stats)
+        })??;
+        return print_json(&stats);
+    }
+
+    let Some(rel_path) = args.positional.first().cloned() else {
+        return Err(CliError::Usage(
+            "index expects --all or a document path".to_string(),
+        ));
+    };
+    ctx.get_doc_meta(&rel_path).map_err(input_error)?;
+    let chunks = block_on(async move {
+        let mut indexer = Indexer::new(config, contexts_root).await?;
+        let chunks = indexer.index_file(&rel_path).await?;
+        indexer.update_metadata()?;
+        Ok::<_, SearchError>(chunks)
+    })??;
+    println!("Indexed {} chunks", chunks);
+    Ok(())
+}
+
+fn run_search(args: ParsedArgs) -> CliResult<()> {
+    let query = args.positional.join(" ");
+    if query.trim().is_empty() {
+        return Err(CliError::Usage("search expects a query".to_string()));
+    }
+    let mode = match args.value("--mode") {
+        None => None,
+        Some("hybrid") => Some(SearchMode::Hybrid),
+        Some("vector") => Some(SearchMode::Vector),
+        Some("keyword") => Some(SearchMode::Keyword),
+        Some(other) => return Err(CliError::Usage(format!("unknown search mode '{}'", other))),
+    };
+    let min_score = args
+        .value("--min-score")
+        .map(|v| match v.parse::<f32>() {
+            Ok(score) if (0.0..=1.0).contains(&score) => Ok(score),
+            _ => Err(CliError::Usage(format!(
+                "--min-score expects a number from 0 to 1, got '{}'",
+                v
+            ))),
+        })
+        .transpose()?;
+    let options = SearchOptions {
+        query,
+        limit: args.usize_value("--limit")?,
+        mode,
+        no_cache: args.has("--no-cache"),
+        min_score,
+        ..Default::default()
+    };
+
+    let config = SearchConfig::load()?;
+    let results = block_on(async move {
+        let searcher = Searcher::new(config).await?;
+        searcher.search(options).await
+    })??;
+
+    // Checked before the JSON too, so scripts see the same exit code
+    if results.index_missing == Some(true) {
+        return Err(CliError::Runtime(
+            "Index not built. Run `opencontext index --all` first.".to_string(),
+        ));
+    }
+    if args.has("--json") {
+        return print_json(&results);
+    }
+    if results.no_confident_match == Some(true) {
+        println!("No results above --min-score");
+        return Ok(());
+    }
+    for (i, hit) in results.results.iter().enumerate() {
+        println!(
+            "{}. {} [{}] ({:.3})",
+            i + 1,
+            hit.display_name,
+            hit.file_path,
+            hit.score
+        );
+        let snippet: String = hit.content.chars().take(200).collect();
+        println!("   {}", snippet.replace('\n', " "));
+    }
+    Ok(())
+}
+
+fn run_doc(args: ParsedArgs) -> CliResult<()> {
+    let Some(action) = args.positional.first().map(String::as_str) else {
+        return Err(CliError::Usage("doc expects 'get' or 'create'".to_string()));
+    };
+    let ctx = open_context()?;
+    match action {
+        "get" => {
+            let doc = match (args.value("--id"), args.positional.get(1)) {
+                (Some(id), _) => ctx.get_doc_by_stable_id(id),
+                (None, Some(path)) => ctx.get_doc_meta(path),
+                (None, None) => {
+                    return Err(CliError::Usage(
+                        "doc get expects a document path or --id".to_string(),
+                    ))
+                }
+            }
+            .map_err(input_error)?;
+            let content = ctx.get_doc_content(&doc.rel_path)?;
+            if args.has("--json") {
+                let mut value = serde_json::to_value(&doc).map_err(runtime)?;
+                value["content"] = serde_json::Value::String(content);
+                return print_json(&value);
+            }
+            print!("{}", content);
+            Ok(())
+        }
+        "create" => {
+            let (Some(folder), Some(name)) = (args.positional.get(1), args.positional.get(2))
+            else {
+                return Err(CliError::Usage(
+                    "doc create expects <folder> <name>".to_string(),
+                ));
+            };
+            let created = ctx
+                .create_doc(folder, name, args.value("--description"))
+                .map_err(input_error)?;
+            print_json(&created)
+        }
+        other => Err(CliError::Usage(format!("unknown doc action '{}'", other))),
+    }
+}
+
+fn run_manifest(args: ParsedArgs) -> CliResult<()> {
+    let Some(folder) = args.positional.first() else {
+        return Err(CliError::Usage(
+            "manifest expects a folder path".to_string(),
+        ));
+    };
+    let ctx = open_context()?;
+    let manifest = ctx
+        .generate_manifest(folder, args.usize_value("--limit")?)
+        .map_err(input_error)?;
+    print_json(&manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_splits_positional_arguments_and_flags() {
+        let parsed =
+            ParsedArgs::parse(&args(&["release", "notes", "--limit", "5", "--json"])).unwrap();
+        assert_eq!(parsed.positional, ["release", "notes"]);
+        assert_eq!(parsed.usize_value("--limit").ok(), Some(Some(5)));
+        assert!(parsed.has("--json"));
+        assert_eq!(parsed.value("--json"), None);
+        assert!(!parsed.has("--no-cache"));
+    }
+
+    #[test]
+    fn parse_rejects_missing_and_malformed_values() {
+        let missing = ParsedArgs::parse(&args(&["query", "--limit"])).err();
+        assert_eq!(missing.map(|e| e.exit_code()), Some(EXIT_USAGE));
+
+        let parsed = ParsedArgs::parse(&args(&["--limit", "many"])).unwrap();
+        let malformed = parsed.usize_value("--limit").err();
+        assert_eq!(malformed.map(|e| e.exit_code()), Some(EXIT_USAGE));
+    }
+
+    #[test]
+    fn errors_map_to_distinct_exit_codes() {
+        let io = || std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let invalid = CoreError::InvalidPath {
+            path: "../x".to_string(),
+            reason: "outside".to_string(),
+        };
+
+        assert_eq!(input_error(invalid).exit_code(), EXIT_USAGE);
+        assert_eq!(input_error(CoreError::Io(io())).exit_code(), EXIT_IO);
+        assert_eq!(CliError::from(CoreError::Io(io())).exit_code(), EXIT_IO);
+        assert_eq!(CliError::from(SearchError::Io(io())).exit_code(), EXIT_IO);
+        assert_eq!(
+            CliError::from(CoreError::Message("failed".to_string())).exit_code(),
+            EXIT_FAILURE
+        );
+        assert_eq!(
+            CliError::from(SearchError::Index("failed".to_string())).exit_code(),
+            EXIT_FAILURE
+        );
+        assert_eq!(
+            run("unknown", ParsedArgs::parse(&[]).unwrap())
+                .err()
+                .map(|e| e.exit_code()),
+            Some(EXIT_USAGE)
+        );
+    }
+}
@@ -1,20 +1,33 @@
 use crate::agent_rpc::{AgentRpcKind, AgentRpcSession, AgentRpcState};
-use crate::chat::build_cli_prompt;
-use crate::utils::{map_err, CmdResult};
+use crate::agent_transcript::{self, TranscriptEntry};
+use crate::agent_watchdog::{self, Activity, StallLimits, Verdict};
+use crate::app_events::EmitScoped;
+use crate::chat::{build_cli_prompt, fit_prompt_messages, flatten_message_content, ChatMessage};
+use crate::commands::pricing::{record_chat_usage, ChatUsage, TokenCounts};
+use crate::commands::prompts::TemplatedPrompt;
+use crate::commands::scratch::{
+    is_scratch_dir, prune_scratch_dirs, scratch_cwd_default, scratch_dir,
+};
+use crate::i18n;
+use crate::utils::{get_config_value, map_err, redact, CmdResult, CommandError, ErrorCode};
 use crate::AppState;
 use opencontext_core::search::SearchConfig;
+use opencontext_core::{DocCreated, OpenContext, VaultPath};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{BufRead, BufReader, ErrorKind, Write};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{mpsc, Arc, Mutex};
 use std::time::{Duration, Instant};
-use tauri::{Emitter, Manager, State};
+use tauri::{Manager, State};
 
 static AGENT_COUNTER: AtomicU64 = AtomicU64::new(1);
 const AGENT_SESSIONS_FILE: &str = "agent-sessions.json";
+const SOFT_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often a request's watchdog checks how long it has been quiet
+const WATCHDOG_POLL_INTERVAL: Duration = Duration::from_secs(5);
 const DEFAULT_CODEX_MODELS: [&str; 4] = [
     "gpt-5.2-codex",
     "gpt-5.1-codex-max",
@@ -26,12 +39,74 @@ const DEFAULT_AGENTS_MD: &str = r#"# OpenContext Agent
 You are the OpenContext dedicated coding agent.
 
 Guidelines:
-- Prefer using `oc` CLI commands to create/search/iterate OpenContext content.
+- Prefer the `opencontext` MCP tools (create_doc, save_doc_content,
+  semantic_search, generate_manifest) to create/search/iterate OpenContext
+  content; fall back to `oc` CLI commands when they are unavailable.
 - Avoid editing user files directly unless explicitly requested.
 - When reading or writing files, ask for permission if required.
 - Be concise, actionable, and follow OpenContext workflows.
 "#;
 
+/// `status` of an `AgentStreamEvent`.
+///
+/// A request moves through these in order, skipping steps that don't apply:
+///
+/// 1. `Connecting` - starting or reusing the agent process
+/// 2. `Connected` - the ACP handshake finished (ACP agents only)
+/// 3. `Authenticating` -> `Authenticated` - credentials checked (ACP agents
+///    send only `Authenticated`)
+/// 4. `SessionActive` - ready for prompts; also sent when an existing
+///    session is reused, in which case steps 2-3 are skipped
+/// 5. `ContextTruncated` - the prompt was cut to fit the context limit
+/// 6. `TaskStarted` - the agent began working on the prompt (Codex only)
+/// 7. `Stopped` - the user stopped the request; sent with `done`
+///
+/// `Error` can replace any step after `Connecting`; the error itself is
+/// sent in a separate event with `done`. `Stalled` can come at any point
+/// when the agent has sent nothing for a while; see `agent_watchdog`.
+///
+/// Serializes to the snake_case strings the frontend has always received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum AgentStatus {
+    Connecting,
+    Connected,
+    Authenticating,
+    Authenticated,
+    SessionActive,
+    ContextTruncated,
+    TaskStarted,
+    Stopped,
+    Stalled,
+    Error,
+    /// A status not modelled above, passed through verbatim
+    #[allow(dead_code)]
+    Other(String),
+}
+
+impl AgentStatus {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            AgentStatus::Connecting => "connecting",
+            AgentStatus::Connected => "connected",
+            AgentStatus::Authenticating => "authenticating",
+            AgentStatus::Authenticated => "authenticated",
+            AgentStatus::SessionActive => "session_active",
+            AgentStatus::ContextTruncated => "context_truncated",
+            AgentStatus::TaskStarted => "task_started",
+            AgentStatus::Stopped => "stopped",
+            AgentStatus::Stalled => "stalled",
+            AgentStatus::Error => "error",
+            AgentStatus::Other(status) => status,
+        }
+    }
+}
+
+impl Serialize for AgentStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 #[derive(Serialize, Clone, Default)]
 pub(crate) struct AgentStreamEvent {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -41,7 +116,10 @@ pub(crate) struct AgentStreamEvent {
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    status: Option<String>,
+    status: Option<AgentStatus>,
+    /// `status` in the backend locale; `status` itself stays a stable code
+    #[serde(rename = "statusText", skip_serializing_if = "Option::is_none")]
+    status_text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     reasoning: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -50,16 +128,64 @@ pub(crate) struct AgentStreamEvent {
     tool: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     models: Option<serde_json::Value>,
+    /// On the `done` event, when the agent reported token counts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<ChatUsage>,
 }
 
-fn agent_sessions_path(app: &tauri::AppHandle) -> CmdResult<PathBuf> {
+/// Tokens of the model call a Codex `token_count` event reports. Newer
+/// versions nest them under `info.last_token_usage`, older ones send them
+/// flat.
+fn codex_token_counts(msg: &serde_json::Value) -> Option<TokenCounts> {
+    let usage = msg
+        .get("info")
+        .and_then(|info| info.get("last_token_usage"))
+        .unwrap_or(msg);
+    let count = |key: &str| usage.get(key).and_then(|v| v.as_u64());
+    if count("input_tokens").is_none() && count("output_tokens").is_none() {
+        return None;
+    }
+    Some(TokenCounts {
+        input: count("input_tokens").unwrap_or(0),
+        output: count("output_tokens").unwrap_or(0),
+    })
+}
+
+fn agent_sessions_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
     let base_dir = app.path().app_data_dir().map_err(map_err)?;
     Ok(base_dir.join(AGENT_SESSIONS_FILE))
 }
 
+/// How `payload` counts toward its request's watchdog; the watchdog's own
+/// `stalled` status doesn't count
+fn watchdog_activity(payload: &AgentStreamEvent) -> Option<Activity> {
+    if payload.done == Some(true) {
+        Some(Activity::Done)
+    } else if payload.permission.is_some() {
+        Some(Activity::AwaitingUser)
+    } else if payload.status == Some(AgentStatus::Stalled) {
+        None
+    } else {
+        Some(Activity::Event)
+    }
+}
+
 fn emit_agent_event(app: &tauri::AppHandle, request_id: &str, payload: AgentStreamEvent) {
+    let state = app.state::<AppState>();
+    if let Some(activity) = watchdog_activity(&payload) {
+        state
+            .agent_watchdogs
+            .record(request_id, activity, Instant::now());
+    }
+    if let Ok(event) = serde_json::to_value(&payload) {
+        state.agent_transcripts.record(app, request_id, &event);
+    }
+    let last = payload.done == Some(true);
     let event_name = format!("agent-stream-{}", request_id);
-    let _ = app.emit(&event_name, payload);
+    let _ = app.emit_scoped(
+        &event_name,
+        state.agent_stream_seqs.wrap(request_id, payload, last),
+    );
 }
 
 fn emit_agent_error(app: &tauri::AppHandle, request_id: &str, message: String) {
@@ -75,15 +201,25 @@ fn emit_agent_error(app: &tauri::AppHandle, request_id: &str, message: String) {
     );
 }
 
-fn emit_agent_status(app: &tauri::AppHandle, request_id: &str, status: &str) {
-    emit_agent_event(
-        app,
-        request_id,
-        AgentStreamEvent {
-            status: Some(status.to_string()),
-            ..Default::default()
-        },
-    );
+fn status_event(status: AgentStatus) -> AgentStreamEvent {
+    AgentStreamEvent {
+        status_text: i18n::lookup(&format!("agent.status.{}", status.as_str()), &[]),
+        status: Some(status),
+        ..Default::default()
+    }
+}
+
+fn emit_agent_status(app: &tauri::AppHandle, request_id: &str, status: AgentStatus) {
+    emit_agent_event(app, request_id, status_event(status));
+}
+
+/// What the user typed for this request, for the transcript
+fn last_user_prompt(messages: &[ChatMessage]) -> Option<String> {
+    messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(|message| flatten_message_content(&message.content))
 }
 
 fn detect_codex_mcp_args() -> Vec<String> {
@@ -163,7 +299,10 @@ fn classify_http_error(message: &str, label: &str) -> Option<String> {
         || lower.contains("quota")
     {
         let detail = extract_error_detail(message);
-        return Some(format!("{} request failed: {}", label, detail));
+        return Some(i18n::t(
+            "agent.error.request_failed",
+            &[("label", label), ("detail", &detail)],
+        ));
     }
     None
 }
@@ -172,24 +311,28 @@ fn classify_codex_error(message: &str) -> Option<String> {
     let cleaned = strip_ansi(message);
     let lower = cleaned.to_lowercase();
     if lower.contains("command not found") || lower.contains("not recognized") {
-        return Some(
-            "Codex CLI not found. Please ensure 'codex' is installed and in PATH.".to_string(),
-        );
+        return Some(i18n::t("agent.error.codex_not_found", &[]));
     }
     if lower.contains("permission denied") {
-        return Some(format!("Permission denied when starting Codex: {}", cleaned));
+        return Some(i18n::t(
+            "agent.error.permission_denied_detail",
+            &[("label", "Codex"), ("detail", &cleaned)],
+        ));
     }
     if lower.contains("authentication") || lower.contains("login") {
-        return Some("Codex authentication required. Please run 'codex auth' first.".to_string());
+        return Some(i18n::t("agent.error.codex_auth_required", &[]));
     }
     if lower.contains("unknown flag")
         || lower.contains("invalid option")
         || lower.contains("unrecognized")
     {
-        return Some(format!("Invalid Codex CLI arguments: {}", cleaned));
+        return Some(i18n::t(
+            "agent.error.invalid_args",
+            &[("label", "Codex"), ("detail", &cleaned)],
+        ));
     }
     if lower.contains("timed out") || lower.contains("timeout") {
-        return Some("Codex initialization timed out. Please check Codex auth status and network.".to_string());
+        return Some(i18n::t("agent.error.codex_timeout", &[]));
     }
     if let Some(error) = classify_http_error(&cleaned, "Codex") {
         return Some(error);
@@ -206,25 +349,28 @@ fn classify_acp_error(message: &str, kind: AgentRpcKind) -> Option<String> {
     let cleaned = strip_ansi(message);
     let lower = cleaned.to_lowercase();
     if lower.contains("command not found") || lower.contains("not recognized") {
-        return Some(format!(
-            "{} CLI not found. Please ensure the CLI is installed and in PATH.",
-            label
-        ));
+        return Some(i18n::t("agent.error.cli_not_found", &[("label", label)]));
     }
     if lower.contains("permission denied") {
-        return Some(format!("Permission denied when starting {}.", label));
+        return Some(i18n::t(
+            "agent.error.permission_denied",
+            &[("label", label)],
+        ));
     }
     if lower.contains("authentication") || lower.contains("unauthorized") || lower.contains("login") {
         let hint = match kind {
-            AgentRpcKind::ClaudeAcp => "Please run `claude /login`.",
-        AgentRpcKind::OpenCodeAcp => "Please run `opencode auth login`.",
-            _ => "",
+            AgentRpcKind::ClaudeAcp => i18n::lookup("agent.error.auth_hint_claude", &[]),
+            AgentRpcKind::OpenCodeAcp => i18n::lookup("agent.error.auth_hint_opencode", &[]),
+            _ => None,
         };
-        let suffix = if hint.is_empty() { "".to_string() } else { format!(" {}", hint) };
-        return Some(format!("{} authentication required.{}", label, suffix));
+        let message = i18n::t("agent.error.auth_required", &[("label", label)]);
+        return Some(match hint {
+            Some(hint) => format!("{} {}", message, hint),
+            None => message,
+        });
     }
     if lower.contains("timed out") || lower.contains("timeout") {
-        return Some(format!("{} request timed out. Please check network and auth.", label));
+        return Some(i18n::t("agent.error.timeout", &[("label", label)]));
     }
     if let Some(error) = classify_http_error(&cleaned, label) {
         return Some(error);
@@ -232,15 +378,15 @@ fn classify_acp_error(message: &str, kind: AgentRpcKind) -> Option<String> {
     None
 }
 
-fn codex_preflight(app: &tauri::AppHandle, session: &AgentRpcSession, request_id: &str) -> CmdResult<()> {
-    emit_agent_status(app, request_id, "connecting");
+fn codex_preflight(app: &tauri::AppHandle, session: &AgentRpcSession, request_id: &str) -> Result<(), String> {
+    emit_agent_status(app, request_id, AgentStatus::Connecting);
     if let Some(err) = session
         .state
         .lock()
         .ok()
         .and_then(|state| state.startup_error.clone())
     {
-        emit_agent_status(app, request_id, "error");
+        emit_agent_status(app, request_id, AgentStatus::Error);
         return Err(err);
     }
 
@@ -252,11 +398,11 @@ fn codex_preflight(app: &tauri::AppHandle, session: &AgentRpcSession, request_id
         .map(|state| state.initialized)
         .unwrap_or(false);
     if already_initialized {
-        emit_agent_status(app, request_id, "session_active");
+        emit_agent_status(app, request_id, AgentStatus::SessionActive);
         return Ok(());
     }
 
-    emit_agent_status(app, request_id, "authenticating");
+    emit_agent_status(app, request_id, AgentStatus::Authenticating);
     let client_name = app.package_info().name.clone();
     let client_version = app.package_info().version.to_string();
     let init_params = serde_json::json!({
@@ -274,7 +420,7 @@ fn codex_preflight(app: &tauri::AppHandle, session: &AgentRpcSession, request_id
         let tools_result = send_rpc_request(session, "tools/list", serde_json::json!({}), None, true, 10);
         if let Err(err) = tools_result {
             let message = classify_codex_error(&err).unwrap_or(err);
-            emit_agent_status(app, request_id, "error");
+            emit_agent_status(app, request_id, AgentStatus::Error);
             return Err(message);
         }
     }
@@ -282,12 +428,12 @@ fn codex_preflight(app: &tauri::AppHandle, session: &AgentRpcSession, request_id
     if let Ok(mut state) = session.state.lock() {
         state.initialized = true;
     }
-    emit_agent_status(app, request_id, "authenticated");
-    emit_agent_status(app, request_id, "session_active");
+    emit_agent_status(app, request_id, AgentStatus::Authenticated);
+    emit_agent_status(app, request_id, AgentStatus::SessionActive);
     Ok(())
 }
 
-fn run_cli_login(command: &str, args: &[&str]) -> CmdResult<()> {
+fn run_cli_login(command: &str, args: &[&str]) -> Result<(), String> {
     let mut child = Command::new(command)
         .args(args)
         .stdin(Stdio::null())
@@ -312,7 +458,7 @@ fn run_cli_login(command: &str, args: &[&str]) -> CmdResult<()> {
     }
 }
 
-fn attempt_acp_login(kind: AgentRpcKind) -> CmdResult<()> {
+fn attempt_acp_login(kind: AgentRpcKind) -> Result<(), String> {
     match kind {
         AgentRpcKind::ClaudeAcp => {
             if run_cli_login("claude", &["/login"]).is_ok() {
@@ -328,7 +474,7 @@ fn attempt_acp_login(kind: AgentRpcKind) -> CmdResult<()> {
     Ok(())
 }
 
-fn probe_acp_auth(session: &AgentRpcSession, session_id: &str, kind: AgentRpcKind) -> CmdResult<()> {
+fn probe_acp_auth(session: &AgentRpcSession, session_id: &str, kind: AgentRpcKind) -> Result<(), String> {
     let params = serde_json::json!({
         "sessionId": session_id,
         "prompt": [{ "type": "text", "text": "ping" }]
@@ -352,15 +498,15 @@ fn acp_preflight(
     request_id: &str,
     kind: AgentRpcKind,
     cwd: Option<String>,
-) -> CmdResult<String> {
-    emit_agent_status(app, request_id, "connecting");
+) -> Result<String, String> {
+    emit_agent_status(app, request_id, AgentStatus::Connecting);
     if let Some(err) = session
         .state
         .lock()
         .ok()
         .and_then(|state| state.startup_error.clone())
     {
-        emit_agent_status(app, request_id, "error");
+        emit_agent_status(app, request_id, AgentStatus::Error);
         return Err(err);
     }
 
@@ -375,7 +521,7 @@ fn acp_preflight(
                 state.cwd = Some(resolved);
             }
         }
-        emit_agent_status(app, request_id, "session_active");
+        emit_agent_status(app, request_id, AgentStatus::SessionActive);
         return Ok(session_id);
     }
 
@@ -394,11 +540,11 @@ fn acp_preflight(
         Ok(value) => value,
         Err(err) => {
             let message = classify_acp_error(&err, kind).unwrap_or(err);
-            emit_agent_status(app, request_id, "error");
+            emit_agent_status(app, request_id, AgentStatus::Error);
             return Err(message);
         }
     };
-    emit_agent_status(app, request_id, "connected");
+    emit_agent_status(app, request_id, AgentStatus::Connected);
 
     let has_auth_methods = init_value
         .as_ref()
@@ -437,7 +583,7 @@ fn acp_preflight(
                 let retry = send_rpc_request(session, "authenticate", auth_params, None, true, 60);
                 if let Err(err) = retry {
                     let message = classify_acp_error(&err, kind).unwrap_or(err);
-                    emit_agent_status(app, request_id, "error");
+                    emit_agent_status(app, request_id, AgentStatus::Error);
                     return Err(message);
                 }
             }
@@ -445,27 +591,36 @@ fn acp_preflight(
     }
 
     let cwd_value = resolve_agent_cwd(cwd).unwrap_or_else(|| ".".to_string());
-    let session_params = serde_json::json!({ "cwd": cwd_value, "mcpServers": [] });
+    // Agents without the bridge can still fall back to the `oc` CLI
+    let bridge = app.state::<AppState>().tool_bridge.server_entry(app);
+    let mcp_servers: Vec<serde_json::Value> = match bridge {
+        Ok(entry) => vec![entry],
+        Err(err) => {
+            log::warn!("[Agent] OpenContext tool bridge unavailable: {}", err);
+            Vec::new()
+        }
+    };
+    let session_params = serde_json::json!({ "cwd": cwd_value, "mcpServers": mcp_servers });
     let session_result = send_rpc_request(session, "session/new", session_params, None, true, 60);
     let session_value = match session_result {
         Ok(value) => value,
         Err(err) => {
             if has_auth_methods {
                 let _ = attempt_acp_login(kind);
-                let retry_params = serde_json::json!({ "cwd": cwd_value, "mcpServers": [] });
+                let retry_params = serde_json::json!({ "cwd": cwd_value, "mcpServers": mcp_servers });
                 let retry_result =
                     send_rpc_request(session, "session/new", retry_params, None, true, 60);
                 match retry_result {
                     Ok(value) => value,
                     Err(err) => {
                         let message = classify_acp_error(&err, kind).unwrap_or(err);
-                        emit_agent_status(app, request_id, "error");
+                        emit_agent_status(app, request_id, AgentStatus::Error);
                         return Err(message);
                     }
                 }
             } else {
                 let message = classify_acp_error(&err, kind).unwrap_or(err);
-                emit_agent_status(app, request_id, "error");
+                emit_agent_status(app, request_id, AgentStatus::Error);
                 return Err(message);
             }
         }
@@ -476,6 +631,9 @@ fn acp_preflight(
         .and_then(|val| val.get("models"))
         .cloned()
     {
+        if let Ok(mut state) = session.state.lock() {
+            state.discovered_models = parse_acp_model_ids(&models);
+        }
         emit_agent_event(
             app,
             request_id,
@@ -490,7 +648,7 @@ fn acp_preflight(
         .as_ref()
         .and_then(|val| val.get("sessionId").and_then(|v| v.as_str()).map(|s| s.to_string()))
     else {
-        emit_agent_status(app, request_id, "error");
+        emit_agent_status(app, request_id, AgentStatus::Error);
         return Err("ACP session did not return a sessionId".to_string());
     };
 
@@ -502,7 +660,7 @@ fn acp_preflight(
 
     if matches!(kind, AgentRpcKind::ClaudeAcp | AgentRpcKind::OpenCodeAcp) {
         if let Err(err) = probe_acp_auth(session, &session_id, kind) {
-            emit_agent_status(app, request_id, "error");
+            emit_agent_status(app, request_id, AgentStatus::Error);
             if let Ok(mut state) = session.state.lock() {
                 state.session_id = None;
                 state.initialized = false;
@@ -511,8 +669,8 @@ fn acp_preflight(
         }
     }
 
-    emit_agent_status(app, request_id, "authenticated");
-    emit_agent_status(app, request_id, "session_active");
+    emit_agent_status(app, request_id, AgentStatus::Authenticated);
+    emit_agent_status(app, request_id, AgentStatus::SessionActive);
     Ok(session_id)
 }
 
@@ -539,7 +697,7 @@ fn spawn_agent_rpc_session(
     kind: AgentRpcKind,
     cwd: Option<String>,
     model: Option<String>,
-) -> CmdResult<Arc<AgentRpcSession>> {
+) -> Result<Arc<AgentRpcSession>, String> {
     let mut cmd = match kind {
         AgentRpcKind::CodexMcp => {
             let mut cmd = Command::new("codex");
@@ -582,31 +740,31 @@ fn spawn_agent_rpc_session(
         match kind {
             AgentRpcKind::CodexMcp => match err.kind() {
                 ErrorKind::NotFound => {
-                    return "Codex CLI not found. Please ensure 'codex' is installed and in PATH."
-                        .to_string();
+                    return i18n::t("agent.error.codex_not_found", &[]);
                 }
                 ErrorKind::PermissionDenied => {
-                    return "Permission denied when starting Codex.".to_string();
+                    return i18n::t("agent.error.permission_denied", &[("label", "Codex")]);
                 }
                 _ => {}
             },
             AgentRpcKind::ClaudeAcp => match err.kind() {
                 ErrorKind::NotFound => {
-                    return "npx not found. Please install Node.js/npm to run Claude ACP."
-                        .to_string();
+                    return i18n::t("agent.error.npx_not_found", &[]);
                 }
                 ErrorKind::PermissionDenied => {
-                    return "Permission denied when starting Claude ACP.".to_string();
+                    return i18n::t("agent.error.permission_denied", &[("label", "Claude ACP")]);
                 }
                 _ => {}
             },
             AgentRpcKind::OpenCodeAcp => match err.kind() {
                 ErrorKind::NotFound => {
-                    return "OpenCode CLI not found. Please ensure 'opencode' is installed and in PATH."
-                        .to_string();
+                    return i18n::t("agent.error.opencode_not_found", &[]);
                 }
                 ErrorKind::PermissionDenied => {
-                    return "Permission denied when starting OpenCode ACP.".to_string();
+                    return i18n::t(
+                        "agent.error.permission_denied",
+                        &[("label", "OpenCode ACP")],
+                    );
                 }
                 _ => {}
             },
@@ -640,7 +798,10 @@ fn spawn_agent_rpc_session(
             codex_received_delta: false,
             codex_elicitation_map: HashMap::new(),
             codex_patch_changes: HashMap::new(),
+            codex_turn_tokens: None,
             acp_permission_map: HashMap::new(),
+            cancelled_rpc_ids: HashSet::new(),
+            discovered_models: Vec::new(),
             startup_error: None,
         })),
         next_id: AtomicU64::new(1),
@@ -659,7 +820,7 @@ fn spawn_agent_rpc_session(
             for line in reader.lines().flatten() {
                 match kind_for_stdout {
                     AgentRpcKind::CodexMcp => {
-                        eprintln!("[codex mcp] {}", line);
+                        log::info!("[codex mcp] {}", redact(&line));
                         if let Some(message) = classify_codex_error(&line) {
                             let active_request = state_for_stderr
                                 .lock()
@@ -676,7 +837,7 @@ fn spawn_agent_rpc_session(
                         }
                     }
                     AgentRpcKind::ClaudeAcp => {
-                        eprintln!("[claude acp] {}", line);
+                        log::info!("[claude acp] {}", redact(&line));
                         if let Some(message) = classify_acp_error(&line, AgentRpcKind::ClaudeAcp) {
                             let active_request = state_for_stderr
                                 .lock()
@@ -693,7 +854,7 @@ fn spawn_agent_rpc_session(
                         }
                     }
                     AgentRpcKind::OpenCodeAcp => {
-                        eprintln!("[opencode acp] {}", line);
+                        log::info!("[opencode acp] {}", redact(&line));
                         if let Some(message) = classify_acp_error(&line, AgentRpcKind::OpenCodeAcp) {
                             let active_request = state_for_stderr
                                 .lock()
@@ -773,7 +934,10 @@ fn spawn_agent_rpc_session(
                     let request_id = state_for_stdout
                         .lock()
                         .ok()
-                        .and_then(|mut state| state.request_map.remove(&id));
+                        .and_then(|mut state| {
+                            state.cancelled_rpc_ids.remove(&id);
+                            state.request_map.remove(&id)
+                        });
                     if let Some(request_id) = request_id {
                         // 对于所有 Agent 类型，如果收到错误响应，都需要发送错误事件
                         let has_error = value.get("error").is_some();
@@ -843,6 +1007,7 @@ fn spawn_agent_rpc_session(
                                 AgentStreamEvent {
                                     permission: Some(serde_json::json!({
                                         "source": "acp",
+                                        "prompt": i18n::t("permission.tool_call", &[]),
                                         "callId": call_id,
                                         "toolCall": params.and_then(|p| p.get("toolCall")).cloned(),
                                         "options": params.and_then(|p| p.get("options")).cloned(),
@@ -858,7 +1023,8 @@ fn spawn_agent_rpc_session(
                                 .lock()
                                 .ok()
                                 .and_then(|state| state.cwd.clone());
-                            let result = handle_fs_read(value.get("params"), cwd);
+                            let roots = fs_roots(&app_for_stdout, cwd);
+                            let result = handle_fs_read(value.get("params"), &roots);
                             let _ = send_rpc_response(&stdin_for_stdout, request_id, result);
                         }
                     }
@@ -868,7 +1034,9 @@ fn spawn_agent_rpc_session(
                                 .lock()
                                 .ok()
                                 .and_then(|state| state.cwd.clone());
-                            let result = handle_fs_write(value.get("params"), cwd);
+                            let roots = fs_roots(&app_for_stdout, cwd);
+                            let result =
+                                handle_fs_write(&app_for_stdout, value.get("params"), &roots);
                             let _ = send_rpc_response(&stdin_for_stdout, request_id, result);
                         }
                     }
@@ -920,15 +1088,15 @@ fn spawn_agent_rpc_session(
                                     if let Some(request_id) = state_for_stdout
                                         .lock()
                                         .ok()
-                                        .and_then(|state| state.active_request.clone())
+                                        .and_then(|mut state| {
+                                            state.codex_turn_tokens = None;
+                                            state.active_request.clone()
+                                        })
                                     {
-                                        emit_agent_event(
+                                        emit_agent_status(
                                             &app_for_stdout,
                                             &request_id,
-                                            AgentStreamEvent {
-                                                status: Some("task_started".to_string()),
-                                                ..Default::default()
-                                            },
+                                            AgentStatus::TaskStarted,
                                         );
                                     }
                                 }
@@ -949,6 +1117,7 @@ fn spawn_agent_rpc_session(
                                             }
                                         }
 
+                                        let mut preview_available = false;
                                         if msg_type == "apply_patch_approval_request" {
                                             if let Some(changes) = msg
                                                 .get("changes")
@@ -957,6 +1126,7 @@ fn spawn_agent_rpc_session(
                                             {
                                                 if let Ok(mut state) = state_for_stdout.lock() {
                                                     state.codex_patch_changes.insert(call_id.clone(), changes);
+                                                    preview_available = true;
                                                 }
                                             }
                                         }
@@ -972,7 +1142,9 @@ fn spawn_agent_rpc_session(
                                                 AgentStreamEvent {
                                                     permission: Some(serde_json::json!({
                                                         "type": msg_type,
+                                                        "prompt": i18n::lookup(&format!("permission.{}", msg_type), &[]),
                                                         "callId": call_id,
+                                                        "previewAvailable": preview_available,
                                                         "data": msg,
                                                     })),
                                                     ..Default::default()
@@ -1041,20 +1213,41 @@ fn spawn_agent_rpc_session(
                                     }
                                 }
 
+                                if msg_type == "token_count" {
+                                    if let Some(counts) = codex_token_counts(msg) {
+                                        if let Ok(mut state) = state_for_stdout.lock() {
+                                            let turn = state
+                                                .codex_turn_tokens
+                                                .get_or_insert_with(TokenCounts::default);
+                                            turn.input += counts.input;
+                                            turn.output += counts.output;
+                                        }
+                                    }
+                                }
+
                                 if msg_type == "task_complete" {
-                                    if let Some(request_id) = state_for_stdout
+                                    if let Some((request_id, tokens, model)) = state_for_stdout
                                         .lock()
                                         .ok()
                                         .and_then(|mut state| {
                                             state.codex_received_delta = false;
-                                            state.active_request.clone()
+                                            let tokens = state.codex_turn_tokens.take();
+                                            let model = state.model.clone();
+                                            let request_id = state.active_request.clone()?;
+                                            Some((request_id, tokens, model))
                                         })
                                     {
+                                        let usage = tokens.map(|tokens| {
+                                            let model = model.as_deref().unwrap_or("codex");
+                                            let state = app_for_stdout.state::<AppState>();
+                                            record_chat_usage(&state, model, tokens)
+                                        });
                                         emit_agent_event(
                                             &app_for_stdout,
                                             &request_id,
                                             AgentStreamEvent {
                                                 done: Some(true),
+                                                usage,
                                                 ..Default::default()
                                             },
                                         );
@@ -1192,7 +1385,7 @@ fn send_rpc_request(
     request_id: Option<String>,
     wait_response: bool,
     timeout_secs: u64,
-) -> CmdResult<Option<serde_json::Value>> {
+) -> Result<Option<serde_json::Value>, String> {
     let id = session.next_id.fetch_add(1, Ordering::Relaxed);
     let payload = serde_json::json!({
         "jsonrpc": "2.0",
@@ -1239,7 +1432,7 @@ fn send_rpc_response(
     stdin: &Arc<Mutex<std::process::ChildStdin>>,
     id: u64,
     result: Result<serde_json::Value, String>,
-) -> CmdResult<()> {
+) -> Result<(), String> {
     let payload = match result {
         Ok(value) => serde_json::json!({
             "jsonrpc": "2.0",
@@ -1264,7 +1457,7 @@ fn send_rpc_response(
 
 fn handle_fs_read(
     params: Option<&serde_json::Value>,
-    cwd: Option<String>,
+    roots: &[PathBuf],
 ) -> Result<serde_json::Value, String> {
     let params = params.ok_or_else(|| "Missing params".to_string())?;
     let path = params
@@ -1273,7 +1466,7 @@ fn handle_fs_read(
         .ok_or_else(|| "Missing path".to_string())?;
     let line = params.get("line").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
     let limit = params.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize);
-    let resolved = resolve_fs_path(cwd, path);
+    let resolved = resolve_fs_path(roots, path)?;
     let content = std::fs::read_to_string(&resolved).map_err(map_err)?;
     if line <= 1 && limit.is_none() {
         return Ok(serde_json::json!({ "content": content }));
@@ -1289,9 +1482,13 @@ fn handle_fs_read(
     Ok(serde_json::json!({ "content": slice }))
 }
 
+/// Docs under the contexts root are saved through the context, so editors
+/// with the doc open get `doc-changed` and the index hears of the write, as
+/// for a save from the editor
 fn handle_fs_write(
+    app: &tauri::AppHandle,
     params: Option<&serde_json::Value>,
-    cwd: Option<String>,
+    roots: &[PathBuf],
 ) -> Result<serde_json::Value, String> {
     let params = params.ok_or_else(|| "Missing params".to_string())?;
     let path = params
@@ -1302,7 +1499,16 @@ fn handle_fs_write(
         .get("content")
         .and_then(|v| v.as_str())
         .ok_or_else(|| "Missing content".to_string())?;
-    let resolved = resolve_fs_path(cwd, path);
+    let resolved = resolve_fs_path(roots, path)?;
+    {
+        let state = app.state::<AppState>();
+        let ctx = state.ctx.write().map_err(map_err)?;
+        if let Ok(rel_path) = resolved.strip_prefix(&ctx.env_info().contexts_root) {
+            let doc_path = VaultPath::parse(&rel_path.to_string_lossy()).map_err(map_err)?;
+            save_agent_doc(&ctx, &doc_path, content)?;
+            return Ok(serde_json::json!({}));
+        }
+    }
     if let Some(parent) = resolved.parent() {
         std::fs::create_dir_all(parent).map_err(map_err)?;
     }
@@ -1310,17 +1516,71 @@ fn handle_fs_write(
     Ok(serde_json::json!({}))
 }
 
-fn resolve_fs_path(cwd: Option<String>, path: &str) -> PathBuf {
-    let input = PathBuf::from(path);
-    if input.is_absolute() {
-        return input;
+/// Save `content` an agent wrote to `doc_path`, creating the doc and its
+/// folder if they don't exist yet. Read-only folders are refused, as in the
+/// tool bridge.
+fn save_agent_doc(ctx: &OpenContext, doc_path: &VaultPath, content: &str) -> Result<(), String> {
+    ctx.ensure_agent_writable(doc_path.as_str())
+        .map_err(map_err)?;
+    if ctx.get_doc_meta(doc_path.as_str()).is_err() {
+        let (folder, name) = doc_path
+            .as_str()
+            .rsplit_once('/')
+            .unwrap_or(("", doc_path.as_str()));
+        let folder_exists = folder.is_empty()
+            || ctx
+                .list_folders(true)
+                .map_err(map_err)?
+                .iter()
+                .any(|existing| existing.rel_path == folder);
+        if !folder_exists {
+            ctx.create_folder(folder, None).map_err(map_err)?;
+        }
+        ctx.create_doc(folder, name, None).map_err(map_err)?;
     }
-    if let Some(cwd) = cwd {
-        return PathBuf::from(cwd).join(input);
+    ctx.save_doc_content(doc_path.as_str(), content, None)
+        .map_err(map_err)?;
+    Ok(())
+}
+
+/// Directories an ACP agent's `fs/*` calls may reach: its working directory
+/// first, then the contexts root. A scratch session is held to its scratch
+/// directory.
+fn fs_roots(app: &tauri::AppHandle, cwd: Option<String>) -> Vec<PathBuf> {
+    let scratch = cwd.as_deref().is_some_and(|cwd| is_scratch_dir(app, cwd));
+    let mut roots = vec![match cwd {
+        Some(cwd) => PathBuf::from(cwd),
+        None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+    }];
+    if !scratch {
+        if let Ok(ctx) = app.state::<AppState>().ctx.read() {
+            roots.push(ctx.env_info().contexts_root.clone());
+        }
     }
-    std::env::current_dir()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join(input)
+    roots
+}
+
+/// Relative paths resolve against the first of `roots` and must stay inside
+/// it. Absolute paths must lie inside one of `roots`; anything else is
+/// refused.
+fn resolve_fs_path(roots: &[PathBuf], path: &str) -> Result<PathBuf, String> {
+    let input = PathBuf::from(path);
+    let (root, relative) = if !input.is_absolute() {
+        let root = roots
+            .first()
+            .ok_or_else(|| format!("{} is outside the agent's directories", path))?;
+        (root, path.to_string())
+    } else {
+        roots
+            .iter()
+            .find_map(|root| {
+                let rest = input.strip_prefix(root).ok()?;
+                Some((root, rest.to_string_lossy().to_string()))
+            })
+            .ok_or_else(|| format!("{} is outside the agent's directories", path))?
+    };
+    let relative = VaultPath::parse(&relative).map_err(map_err)?;
+    Ok(relative.to_abs(root))
 }
 
 fn default_agent_cwd() -> Option<String> {
@@ -1344,11 +1604,26 @@ fn resolve_agent_cwd(cwd: Option<String>) -> Option<String> {
     default_agent_cwd()
 }
 
+/// Working directory for an exec call: the session's scratch directory when
+/// `use_scratch` (or `AGENT_SCRATCH_CWD` if unset) says so, else `cwd`
+fn agent_cwd(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    cwd: Option<String>,
+    use_scratch: Option<bool>,
+) -> CmdResult<Option<String>> {
+    if use_scratch.unwrap_or_else(scratch_cwd_default) {
+        let dir = scratch_dir(app, session_id)?;
+        return Ok(Some(dir.to_string_lossy().to_string()));
+    }
+    Ok(resolve_agent_cwd(cwd))
+}
+
 fn try_set_acp_model(
     session: &AgentRpcSession,
     session_id: &str,
     model: &str,
-) -> CmdResult<()> {
+) -> Result<(), String> {
     let params = serde_json::json!({
         "sessionId": session_id,
         "modelId": model,
@@ -1366,7 +1641,7 @@ fn try_set_acp_model(
     Ok(())
 }
 
-fn respond_elicitation(session: &AgentRpcSession, call_id: &str, decision: &str) -> CmdResult<()> {
+fn respond_elicitation(session: &AgentRpcSession, call_id: &str, decision: &str) -> Result<(), String> {
     let normalized = call_id
         .trim_start_matches("patch_")
         .trim_start_matches("elicitation_")
@@ -1398,35 +1673,52 @@ fn respond_elicitation(session: &AgentRpcSession, call_id: &str, decision: &str)
     Ok(())
 }
 
+/// Trim, drop empty entries and remove case-insensitive duplicates while
+/// keeping the first-seen spelling and order.
+fn dedupe_models<I>(items: I) -> Vec<String>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut seen = HashSet::new();
+    items
+        .into_iter()
+        .map(|item| item.as_ref().trim().to_string())
+        .filter(|item| !item.is_empty() && seen.insert(item.to_lowercase()))
+        .collect()
+}
+
 fn parse_model_list(value: Option<&serde_json::Value>) -> Vec<String> {
     match value {
-        Some(serde_json::Value::Array(items)) => items
-            .iter()
-            .filter_map(|item| item.as_str())
-            .map(|item| item.trim().to_string())
-            .filter(|item| !item.is_empty())
-            .collect(),
-        Some(serde_json::Value::String(text)) => text
-            .lines()
-            .flat_map(|line| line.split(','))
-            .map(|item| item.trim().to_string())
-            .filter(|item| !item.is_empty())
-            .collect(),
+        Some(serde_json::Value::Array(items)) => {
+            dedupe_models(items.iter().filter_map(|item| item.as_str()))
+        }
+        Some(serde_json::Value::String(text)) => {
+            dedupe_models(text.lines().flat_map(|line| line.split(',')))
+        }
         _ => Vec::new(),
     }
 }
 
+/// Extract model ids from an ACP `session/new` `models` object
+/// (`{ availableModels: [{ modelId, name }], currentModelId }`).
+fn parse_acp_model_ids(models: &serde_json::Value) -> Vec<String> {
+    let Some(available) = models.get("availableModels").and_then(|v| v.as_array()) else {
+        return Vec::new();
+    };
+    dedupe_models(available.iter().filter_map(|item| {
+        item.get("modelId")
+            .and_then(|v| v.as_str())
+            .or_else(|| item.as_str())
+    }))
+}
+
 fn set_model_list_key(
     config: &mut HashMap<String, serde_json::Value>,
     key: &str,
     models: Option<Vec<String>>,
 ) {
-    let models = models.unwrap_or_default();
-    let cleaned: Vec<String> = models
-        .into_iter()
-        .map(|item| item.trim().to_string())
-        .filter(|item| !item.is_empty())
-        .collect();
+    let cleaned = dedupe_models(models.unwrap_or_default());
     if cleaned.is_empty() {
         config.remove(key);
     } else {
@@ -1444,7 +1736,7 @@ fn get_or_create_rpc_session(
     kind: AgentRpcKind,
     cwd: Option<String>,
     model: Option<String>,
-) -> CmdResult<Arc<AgentRpcSession>> {
+) -> Result<Arc<AgentRpcSession>, String> {
     let existing = {
         let sessions = state.agent_rpc_sessions.lock().map_err(map_err)?;
         sessions.get(session_id).cloned()
@@ -1486,36 +1778,271 @@ fn get_or_create_rpc_session(
     Ok(session)
 }
 
-fn stop_rpc_stream(app: tauri::AppHandle, state: State<AppState>, session_id: &str) -> CmdResult<()> {
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StopMode {
+    /// Ask the agent to cancel, killing it only if it ignores the cancel
+    Soft,
+    /// Kill the agent process right away
+    Hard,
+}
+
+fn resolve_stop_mode(mode: Option<&str>) -> StopMode {
+    let mode = mode
+        .map(|m| m.to_string())
+        .or_else(|| get_config_value("AGENT_STOP_MODE"));
+    match mode.as_deref().map(str::trim) {
+        Some("hard") => StopMode::Hard,
+        _ => StopMode::Soft,
+    }
+}
+
+fn send_rpc_notification(
+    session: &AgentRpcSession,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<(), String> {
+    let payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+    });
+    let line = format!("{}\n", payload);
+    let mut stdin = session.stdin.lock().map_err(map_err)?;
+    stdin.write_all(line.as_bytes()).map_err(map_err)?;
+    stdin.flush().ok();
+    Ok(())
+}
+
+fn kill_rpc_session(app: &tauri::AppHandle, session_id: &str, session: &Arc<AgentRpcSession>) {
+    if let Ok(mut child) = session.child.lock() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    let state = app.state::<AppState>();
+    if let Ok(mut sessions) = state.agent_rpc_sessions.lock() {
+        // Only drop the entry if it still points at the process we killed
+        if sessions
+            .get(session_id)
+            .map_or(false, |current| Arc::ptr_eq(current, session))
+        {
+            sessions.remove(session_id);
+        }
+    };
+    // The ended session's scratch directory now ages like the others
+    let app = app.clone();
+    std::thread::spawn(move || prune_scratch_dirs(&app));
+}
+
+fn stop_rpc_stream(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    session_id: &str,
+    mode: Option<String>,
+) -> Result<(), String> {
     let session = {
         let sessions = state.agent_rpc_sessions.lock().map_err(map_err)?;
         sessions.get(session_id).cloned()
     };
 
-    if let Some(session) = session {
-        if let Ok(mut state) = session.state.lock() {
-            if let Some(request_id) = state.active_request.take() {
-                state.request_map.retain(|_, v| v != &request_id);
-                state.codex_received_delta = false;
-                state.acp_permission_map.clear();
-                emit_agent_event(
-                    &app,
-                    &request_id,
-                    AgentStreamEvent {
-                        done: Some(true),
-                        status: Some("stopped".to_string()),
-                        ..Default::default()
-                    },
+    let Some(session) = session else {
+        return Ok(());
+    };
+    let mode = resolve_stop_mode(mode.as_deref());
+
+    let stopped = {
+        let mut rpc_state = session.state.lock().map_err(map_err)?;
+        take_active_request(&mut rpc_state, mode == StopMode::Soft)
+    };
+
+    if mode == StopMode::Hard {
+        kill_rpc_session(&app, session_id, &session);
+    }
+
+    let Some((request_id, rpc_ids, acp_session_id)) = stopped else {
+        return Ok(());
+    };
+
+    emit_agent_event(
+        &app,
+        &request_id,
+        AgentStreamEvent {
+            done: Some(true),
+            ..status_event(AgentStatus::Stopped)
+        },
+    );
+
+    if mode == StopMode::Hard || rpc_ids.is_empty() {
+        return Ok(());
+    }
+    cancel_rpc_request(
+        app,
+        session_id.to_string(),
+        session,
+        rpc_ids,
+        acp_session_id,
+    );
+    Ok(())
+}
+
+/// Clear the active request of a locked session state, returning it with the
+/// RPC ids sent for it and the ACP session it ran in. With `cancel`, the
+/// responses to those ids are awaited as acknowledgements of a cancel.
+fn take_active_request(
+    rpc_state: &mut AgentRpcState,
+    cancel: bool,
+) -> Option<(String, Vec<u64>, Option<String>)> {
+    let request_id = rpc_state.active_request.take()?;
+    let rpc_ids: Vec<u64> = rpc_state
+        .request_map
+        .iter()
+        .filter(|(_, v)| **v == request_id)
+        .map(|(id, _)| *id)
+        .collect();
+    rpc_state.request_map.retain(|_, v| v != &request_id);
+    rpc_state.codex_received_delta = false;
+    rpc_state.acp_permission_map.clear();
+    if cancel {
+        rpc_state.cancelled_rpc_ids.extend(rpc_ids.iter().copied());
+    }
+    Some((request_id, rpc_ids, rpc_state.session_id.clone()))
+}
+
+/// Ask the agent to cancel `rpc_ids`, killing it if the cancel can't be sent
+/// or isn't acknowledged within `SOFT_STOP_TIMEOUT`
+fn cancel_rpc_request(
+    app: tauri::AppHandle,
+    session_id: String,
+    session: Arc<AgentRpcSession>,
+    rpc_ids: Vec<u64>,
+    acp_session_id: Option<String>,
+) {
+    let cancel_result = match session.kind {
+        AgentRpcKind::CodexMcp => rpc_ids.iter().try_for_each(|rpc_id| {
+            send_rpc_notification(
+                &session,
+                "notifications/cancelled",
+                serde_json::json!({ "requestId": rpc_id, "reason": "user_cancelled" }),
+            )
+        }),
+        AgentRpcKind::ClaudeAcp | AgentRpcKind::OpenCodeAcp => match acp_session_id {
+            Some(acp_session_id) => send_rpc_notification(
+                &session,
+                "session/cancel",
+                serde_json::json!({ "sessionId": acp_session_id }),
+            ),
+            None => Err("ACP session not started".to_string()),
+        },
+    };
+    if cancel_result.is_err() {
+        kill_rpc_session(&app, &session_id, &session);
+        return;
+    }
+
+    // Fall back to killing the agent if it does not acknowledge the cancel in time
+    std::thread::spawn(move || {
+        let deadline = Instant::now() + SOFT_STOP_TIMEOUT;
+        while Instant::now() < deadline {
+            let pending = session
+                .state
+                .lock()
+                .map(|state| rpc_ids.iter().any(|id| state.cancelled_rpc_ids.contains(id)))
+                .unwrap_or(false);
+            if !pending {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        kill_rpc_session(&app, &session_id, &session);
+    });
+}
+
+/// Watch `request_id` for going quiet until it ends, unless the watchdog is
+/// turned off; see `agent_watchdog`
+fn watch_request(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    session: &Arc<AgentRpcSession>,
+    request_id: &str,
+) {
+    let Some(limits) = StallLimits::from_config() else {
+        return;
+    };
+    app.state::<AppState>()
+        .agent_watchdogs
+        .start(request_id, Instant::now());
+
+    let app = app.clone();
+    let session_id = session_id.to_string();
+    let session = session.clone();
+    let request_id = request_id.to_string();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(WATCHDOG_POLL_INTERVAL);
+        let state = app.state::<AppState>();
+        let verdict = state
+            .agent_watchdogs
+            .check(&request_id, Instant::now(), limits);
+        match verdict {
+            Verdict::Quiet => {}
+            Verdict::Stalled => {
+                log::warn!(
+                    "[Agent] {} sent nothing for request {} in {} minutes",
+                    session.kind.label(),
+                    request_id,
+                    limits.stall.as_secs() / 60
                 );
+                emit_agent_status(&app, &request_id, AgentStatus::Stalled);
             }
+            Verdict::Expired => {
+                expire_request(&app, session_id, session, &request_id, limits);
+                return;
+            }
+            Verdict::Unwatched => return,
         }
-    }
+    });
+}
 
-    Ok(())
+/// End `request_id` after the agent went quiet past its grace period: fail
+/// it, clear it from the session if it is still the active one, and ask the
+/// agent to cancel it when `AGENT_STALL_INTERRUPT` allows
+fn expire_request(
+    app: &tauri::AppHandle,
+    session_id: String,
+    session: Arc<AgentRpcSession>,
+    request_id: &str,
+    limits: StallLimits,
+) {
+    let interrupt = agent_watchdog::interrupt_on_expiry();
+    let expired = session.state.lock().ok().and_then(|mut rpc_state| {
+        if rpc_state.active_request.as_deref() != Some(request_id) {
+            return None;
+        }
+        take_active_request(&mut rpc_state, interrupt)
+    });
+    let minutes = ((limits.stall + limits.grace).as_secs() / 60).to_string();
+    log::warn!(
+        "[Agent] Ending request {}: {} sent nothing in {} minutes",
+        request_id,
+        session.kind.label(),
+        minutes
+    );
+    emit_agent_error(
+        app,
+        request_id,
+        i18n::t(
+            "agent.error.stalled",
+            &[("label", session.kind.label()), ("minutes", &minutes)],
+        ),
+    );
+    if let Some((_, rpc_ids, acp_session_id)) = expired {
+        if interrupt && !rpc_ids.is_empty() {
+            cancel_rpc_request(app.clone(), session_id, session, rpc_ids, acp_session_id);
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub(crate) struct CodexExecOptions {
+    #[serde(default)]
     messages: Vec<crate::chat::ChatMessage>,
     #[serde(rename = "requestId")]
     request_id: Option<String>,
@@ -1523,6 +2050,12 @@ pub(crate) struct CodexExecOptions {
     session_id: String,
     model: Option<String>,
     cwd: Option<String>,
+    /// Run in the session's scratch directory instead of `cwd`; defaults to
+    /// `AGENT_SCRATCH_CWD`
+    #[serde(rename = "useScratchCwd")]
+    use_scratch_cwd: Option<bool>,
+    #[serde(flatten)]
+    template: TemplatedPrompt,
 }
 
 #[tauri::command]
@@ -1539,7 +2072,17 @@ pub(crate) fn codex_exec(
     });
 
     let session_id = options.session_id.clone();
-    let cwd = resolve_agent_cwd(options.cwd.clone());
+    let prompt = last_user_prompt(&options.messages);
+    let messages = options.template.apply(&state, options.messages)?;
+    state
+        .agent_transcripts
+        .begin(&app, &session_id, &request_id, prompt);
+    let cwd = agent_cwd(
+        &app,
+        &session_id,
+        options.cwd.clone(),
+        options.use_scratch_cwd,
+    )?;
     let model = options.model.clone();
 
     let session = get_or_create_rpc_session(
@@ -1550,6 +2093,7 @@ pub(crate) fn codex_exec(
         cwd.clone(),
         model,
     )?;
+    watch_request(&app, &session_id, &session, &request_id);
 
     let app_clone = app.clone();
     let request_id_clone = request_id.clone();
@@ -1559,7 +2103,11 @@ pub(crate) fn codex_exec(
             emit_agent_error(&app_clone, &request_id_clone, err);
             return;
         }
-        let prompt = build_cli_prompt(&options.messages);
+        let (messages, truncated) = fit_prompt_messages(&messages);
+        if truncated {
+            emit_agent_status(&app_clone, &request_id_clone, AgentStatus::ContextTruncated);
+        }
+        let prompt = build_cli_prompt(&messages);
         let (conversation_id, use_reply) = {
             let mut state = session.state.lock().unwrap_or_else(|err| err.into_inner());
             let conversation_id = state
@@ -1623,6 +2171,8 @@ pub(crate) fn codex_exec(
 pub(crate) struct CodexKillOptions {
     #[serde(rename = "sessionId")]
     session_id: String,
+    /// "soft" (default) asks the agent to cancel, "hard" kills the process
+    mode: Option<String>,
 }
 
 #[tauri::command]
@@ -1631,11 +2181,13 @@ pub(crate) fn codex_kill(
     state: State<AppState>,
     options: CodexKillOptions,
 ) -> CmdResult<()> {
-    stop_rpc_stream(app, state, &options.session_id)
+    stop_rpc_stream(app, state, &options.session_id, options.mode)?;
+    Ok(())
 }
 
 #[derive(Deserialize)]
 pub(crate) struct ClaudeExecOptions {
+    #[serde(default)]
     messages: Vec<crate::chat::ChatMessage>,
     #[serde(rename = "requestId")]
     request_id: Option<String>,
@@ -1643,6 +2195,12 @@ pub(crate) struct ClaudeExecOptions {
     session_id: String,
     model: Option<String>,
     cwd: Option<String>,
+    /// Run in the session's scratch directory instead of `cwd`; defaults to
+    /// `AGENT_SCRATCH_CWD`
+    #[serde(rename = "useScratchCwd")]
+    use_scratch_cwd: Option<bool>,
+    #[serde(flatten)]
+    template: TemplatedPrompt,
 }
 
 #[tauri::command]
@@ -1659,7 +2217,17 @@ pub(crate) fn claude_exec(
     });
 
     let session_id = options.session_id.clone();
-    let cwd = resolve_agent_cwd(options.cwd.clone());
+    let prompt = last_user_prompt(&options.messages);
+    let messages = options.template.apply(&state, options.messages)?;
+    state
+        .agent_transcripts
+        .begin(&app, &session_id, &request_id, prompt);
+    let cwd = agent_cwd(
+        &app,
+        &session_id,
+        options.cwd.clone(),
+        options.use_scratch_cwd,
+    )?;
     let model = options.model.clone();
 
     let session = get_or_create_rpc_session(
@@ -1670,6 +2238,7 @@ pub(crate) fn claude_exec(
         cwd.clone(),
         None,
     )?;
+    watch_request(&app, &session_id, &session, &request_id);
 
     let app_clone = app.clone();
     let request_id_clone = request_id.clone();
@@ -1690,7 +2259,11 @@ pub(crate) fn claude_exec(
             }
         }
 
-        let prompt = build_cli_prompt(&options.messages);
+        let (messages, truncated) = fit_prompt_messages(&messages);
+        if truncated {
+            emit_agent_status(&app_clone, &request_id_clone, AgentStatus::ContextTruncated);
+        }
+        let prompt = build_cli_prompt(&messages);
         let params = serde_json::json!({
             "sessionId": session_id,
             "prompt": [
@@ -1717,6 +2290,8 @@ pub(crate) fn claude_exec(
 pub(crate) struct ClaudeKillOptions {
     #[serde(rename = "sessionId")]
     session_id: String,
+    /// "soft" (default) asks the agent to cancel, "hard" kills the process
+    mode: Option<String>,
 }
 
 #[tauri::command]
@@ -1725,11 +2300,13 @@ pub(crate) fn claude_kill(
     state: State<AppState>,
     options: ClaudeKillOptions,
 ) -> CmdResult<()> {
-    stop_rpc_stream(app, state, &options.session_id)
+    stop_rpc_stream(app, state, &options.session_id, options.mode)?;
+    Ok(())
 }
 
 #[derive(Deserialize)]
 pub(crate) struct OpenCodeRunOptions {
+    #[serde(default)]
     messages: Vec<crate::chat::ChatMessage>,
     #[serde(rename = "requestId")]
     request_id: Option<String>,
@@ -1737,6 +2314,12 @@ pub(crate) struct OpenCodeRunOptions {
     session_id: String,
     model: Option<String>,
     cwd: Option<String>,
+    /// Run in the session's scratch directory instead of `cwd`; defaults to
+    /// `AGENT_SCRATCH_CWD`
+    #[serde(rename = "useScratchCwd")]
+    use_scratch_cwd: Option<bool>,
+    #[serde(flatten)]
+    template: TemplatedPrompt,
 }
 
 #[tauri::command]
@@ -1753,7 +2336,17 @@ pub(crate) fn opencode_run(
     });
 
     let session_id = options.session_id.clone();
-    let cwd = resolve_agent_cwd(options.cwd.clone());
+    let prompt = last_user_prompt(&options.messages);
+    let messages = options.template.apply(&state, options.messages)?;
+    state
+        .agent_transcripts
+        .begin(&app, &session_id, &request_id, prompt);
+    let cwd = agent_cwd(
+        &app,
+        &session_id,
+        options.cwd.clone(),
+        options.use_scratch_cwd,
+    )?;
     let model = options.model.clone();
 
     let session = get_or_create_rpc_session(
@@ -1764,6 +2357,7 @@ pub(crate) fn opencode_run(
         cwd.clone(),
         None,
     )?;
+    watch_request(&app, &session_id, &session, &request_id);
 
     let app_clone = app.clone();
     let request_id_clone = request_id.clone();
@@ -1784,7 +2378,11 @@ pub(crate) fn opencode_run(
             }
         }
 
-        let prompt = build_cli_prompt(&options.messages);
+        let (messages, truncated) = fit_prompt_messages(&messages);
+        if truncated {
+            emit_agent_status(&app_clone, &request_id_clone, AgentStatus::ContextTruncated);
+        }
+        let prompt = build_cli_prompt(&messages);
         let params = serde_json::json!({
             "sessionId": session_id,
             "prompt": [
@@ -1811,6 +2409,8 @@ pub(crate) fn opencode_run(
 pub(crate) struct OpenCodeKillOptions {
     #[serde(rename = "sessionId")]
     session_id: String,
+    /// "soft" (default) asks the agent to cancel, "hard" kills the process
+    mode: Option<String>,
 }
 
 #[tauri::command]
@@ -1819,7 +2419,8 @@ pub(crate) fn opencode_kill(
     state: State<AppState>,
     options: OpenCodeKillOptions,
 ) -> CmdResult<()> {
-    stop_rpc_stream(app, state, &options.session_id)
+    stop_rpc_stream(app, state, &options.session_id, options.mode)?;
+    Ok(())
 }
 
 #[derive(Deserialize)]
@@ -1829,6 +2430,7 @@ pub(crate) struct AgentPreflightOptions {
     agent_id: String,
     model: Option<String>,
     cwd: Option<String>,
+    use_scratch_cwd: Option<bool>,
 }
 
 #[tauri::command]
@@ -1841,10 +2443,21 @@ pub(crate) fn agent_preflight(
         "codex" => AgentRpcKind::CodexMcp,
         "claude" => AgentRpcKind::ClaudeAcp,
         "opencode" => AgentRpcKind::OpenCodeAcp,
-        other => return Err(format!("Unsupported agent: {}", other)),
+        other => {
+            return Err(CommandError::localized(
+                ErrorCode::InvalidInput,
+                "error.unsupported_agent",
+                &[("agent", other)],
+            ))
+        }
     };
 
-    let resolved_cwd = resolve_agent_cwd(options.cwd.clone());
+    let resolved_cwd = agent_cwd(
+        &app,
+        &options.session_id,
+        options.cwd.clone(),
+        options.use_scratch_cwd,
+    )?;
     let session = get_or_create_rpc_session(
         app.clone(),
         state,
@@ -1893,17 +2506,29 @@ pub(crate) fn agent_preflight(
     Ok(true)
 }
 
+/// Models discovered from running ACP sessions of the given kind.
+fn discovered_models(state: &AppState, kind: AgentRpcKind) -> Vec<String> {
+    let Ok(sessions) = state.agent_rpc_sessions.lock() else {
+        return Vec::new();
+    };
+    let models: Vec<String> = sessions
+        .values()
+        .filter(|session| session.kind == kind)
+        .filter_map(|session| session.state.lock().ok().map(|s| s.discovered_models.clone()))
+        .flatten()
+        .collect();
+    dedupe_models(models)
+}
+
 #[tauri::command]
-pub(crate) fn agent_models_get() -> CmdResult<serde_json::Value> {
+pub(crate) fn agent_models_get(state: State<AppState>) -> CmdResult<serde_json::Value> {
     let config_path = SearchConfig::json_config_path();
-    if !config_path.exists() {
-        return Ok(serde_json::json!({
-            "codex": DEFAULT_CODEX_MODELS,
-            "claude": []
-        }));
-    }
-    let content = std::fs::read_to_string(&config_path).map_err(map_err)?;
-    let config: serde_json::Value = serde_json::from_str(&content).map_err(map_err)?;
+    let config: serde_json::Value = if config_path.exists() {
+        let content = std::fs::read_to_string(&config_path)?;
+        serde_json::from_str(&content)?
+    } else {
+        serde_json::Value::Null
+    };
     let codex = {
         let parsed = parse_model_list(config.get("AGENT_MODELS_CODEX"));
         if parsed.is_empty() {
@@ -1912,8 +2537,13 @@ pub(crate) fn agent_models_get() -> CmdResult<serde_json::Value> {
             parsed
         }
     };
-    let claude = parse_model_list(config.get("AGENT_MODELS_CLAUDE"));
-    Ok(serde_json::json!({ "codex": codex, "claude": claude }))
+    let claude = dedupe_models(
+        parse_model_list(config.get("AGENT_MODELS_CLAUDE"))
+            .into_iter()
+            .chain(discovered_models(&state, AgentRpcKind::ClaudeAcp)),
+    );
+    let opencode = discovered_models(&state, AgentRpcKind::OpenCodeAcp);
+    Ok(serde_json::json!({ "codex": codex, "claude": claude, "opencode": opencode }))
 }
 
 #[derive(Deserialize)]
@@ -1929,8 +2559,8 @@ pub(crate) fn agent_models_save(
 ) -> CmdResult<serde_json::Value> {
     let config_path = SearchConfig::json_config_path();
     let mut config: HashMap<String, serde_json::Value> = if config_path.exists() {
-        let content = std::fs::read_to_string(&config_path).map_err(map_err)?;
-        serde_json::from_str(&content).map_err(map_err)?
+        let content = std::fs::read_to_string(&config_path)?;
+        serde_json::from_str(&content)?
     } else {
         HashMap::new()
     };
@@ -1939,10 +2569,10 @@ pub(crate) fn agent_models_save(
     set_model_list_key(&mut config, "AGENT_MODELS_CLAUDE", options.claude);
 
     if let Some(parent) = config_path.parent() {
-        std::fs::create_dir_all(parent).map_err(map_err)?;
+        std::fs::create_dir_all(parent)?;
     }
-    let content = serde_json::to_string_pretty(&config).map_err(map_err)?;
-    std::fs::write(&config_path, content).map_err(map_err)?;
+    let content = serde_json::to_string_pretty(&config)?;
+    std::fs::write(&config_path, content)?;
     Ok(serde_json::json!({ "config_path": config_path.to_string_lossy() }))
 }
 
@@ -1956,7 +2586,11 @@ pub(crate) struct OcExecOptions {
 #[tauri::command]
 pub(crate) fn oc_exec(options: OcExecOptions) -> CmdResult<serde_json::Value> {
     if options.args.is_empty() {
-        return Err("Missing oc command arguments".to_string());
+        return Err(CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.missing_oc_args",
+            &[],
+        ));
     }
     let mut cmd = Command::new("oc");
     cmd.args(&options.args);
@@ -1974,6 +2608,21 @@ pub(crate) fn oc_exec(options: OcExecOptions) -> CmdResult<serde_json::Value> {
     }))
 }
 
+/// Time `session`'s active request again once the user has answered its
+/// permission prompt; the agent is the one to wait on from here
+fn resume_watchdog(state: &AppState, session: &AgentRpcSession) {
+    let active_request = session
+        .state
+        .lock()
+        .ok()
+        .and_then(|state| state.active_request.clone());
+    if let Some(request_id) = active_request {
+        state
+            .agent_watchdogs
+            .record(&request_id, Activity::Event, Instant::now());
+    }
+}
+
 #[derive(Deserialize)]
 pub(crate) struct CodexPermissionResponseOptions {
     #[serde(rename = "sessionId")]
@@ -1996,7 +2645,11 @@ pub(crate) fn codex_permission_response(
     };
 
     let Some(session) = session else {
-        return Err("Codex session not found".to_string());
+        return Err(CommandError::localized(
+            ErrorCode::NotFound,
+            "error.codex_session_not_found",
+            &[],
+        ));
     };
 
     if options.permission_type == "apply_patch_approval_request" {
@@ -2028,6 +2681,7 @@ pub(crate) fn codex_permission_response(
         let decision = if options.approved { "approved" } else { "denied" };
         respond_elicitation(&session, &options.call_id, decision)?;
     }
+    resume_watchdog(&state, &session);
 
     Ok(())
 }
@@ -2053,7 +2707,11 @@ pub(crate) fn acp_permission_response(
     };
 
     let Some(session) = session else {
-        return Err("ACP session not found".to_string());
+        return Err(CommandError::localized(
+            ErrorCode::NotFound,
+            "error.acp_session_not_found",
+            &[],
+        ));
     };
 
     let request_id = {
@@ -2062,7 +2720,11 @@ pub(crate) fn acp_permission_response(
     };
 
     let Some(request_id) = request_id else {
-        return Err("ACP permission request not found".to_string());
+        return Err(CommandError::localized(
+            ErrorCode::NotFound,
+            "error.acp_permission_not_found",
+            &[],
+        ));
     };
 
     let result = if let Some(option_id) = options.option_id {
@@ -2081,6 +2743,7 @@ pub(crate) fn acp_permission_response(
     };
 
     send_rpc_response(&session.stdin, request_id, Ok(result))?;
+    resume_watchdog(&state, &session);
     Ok(())
 }
 
@@ -2090,8 +2753,8 @@ pub(crate) fn agent_sessions_load(app: tauri::AppHandle) -> CmdResult<Option<ser
     if !path.exists() {
         return Ok(None);
     }
-    let content = std::fs::read_to_string(&path).map_err(map_err)?;
-    let payload = serde_json::from_str(&content).map_err(map_err)?;
+    let content = std::fs::read_to_string(&path)?;
+    let payload = serde_json::from_str(&content)?;
     Ok(Some(payload))
 }
 
@@ -2102,17 +2765,151 @@ pub(crate) fn agent_sessions_save(
 ) -> CmdResult<bool> {
     let path = agent_sessions_path(&app)?;
     if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(map_err)?;
+        std::fs::create_dir_all(parent)?;
     }
-    let content = serde_json::to_string(&payload).map_err(map_err)?;
-    std::fs::write(&path, content).map_err(map_err)?;
+    let content = serde_json::to_string(&payload)?;
+    std::fs::write(&path, content)?;
     Ok(true)
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AgentTranscriptGetOptions {
+    session_id: String,
+    /// Only entries with this `seq` or later, to catch up after a reload
+    #[serde(default)]
+    from_seq: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AgentTranscriptDeleteOptions {
+    session_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AgentTranscriptExportOptions {
+    session_id: String,
+    folder_path: VaultPath,
+    /// File name of the new doc
+    name: String,
+    /// Heading of the doc; defaults to the file name
+    title: Option<String>,
+}
+
+/// The session's transcript as recorded by the backend, the source of
+/// truth for rebuilding a session after the webview reloads
+#[tauri::command]
+pub(crate) fn agent_transcript_get(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    options: AgentTranscriptGetOptions,
+) -> CmdResult<Vec<TranscriptEntry>> {
+    Ok(state
+        .agent_transcripts
+        .read(&app, &options.session_id, options.from_seq))
+}
+
+#[tauri::command]
+pub(crate) fn agent_transcript_delete(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    options: AgentTranscriptDeleteOptions,
+) -> CmdResult<bool> {
+    Ok(state.agent_transcripts.delete(&app, &options.session_id)?)
+}
+
+/// Save a session's recorded transcript as a new markdown doc
+#[tauri::command]
+pub(crate) fn agent_transcript_export(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    options: AgentTranscriptExportOptions,
+) -> CmdResult<DocCreated> {
+    let entries = state.agent_transcripts.read(&app, &options.session_id, 0);
+    if entries.is_empty() {
+        return Err(CommandError::localized(
+            ErrorCode::NotFound,
+            "error.transcript_not_found",
+            &[("session", &options.session_id)],
+        ));
+    }
+    let title = options
+        .title
+        .unwrap_or_else(|| options.name.trim_end_matches(".md").to_string());
+    let markdown = agent_transcript::to_markdown(&title, &entries);
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let created = ctx.create_doc(options.folder_path.as_str(), &options.name, None)?;
+    ctx.save_doc_content(&created.rel_path, &markdown, None)?;
+    Ok(created)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn resolve_fs_path_confines_paths_to_its_roots() {
+        let base = std::env::temp_dir().join("agent-session");
+        let contexts = std::env::temp_dir().join("contexts");
+        let roots = [base.clone(), contexts.clone()];
+        let inside = base.join("out").join("notes.md");
+        assert_eq!(
+            resolve_fs_path(&roots, &inside.to_string_lossy()),
+            Ok(inside.clone())
+        );
+        assert_eq!(resolve_fs_path(&roots, "out/notes.md"), Ok(inside.clone()));
+        let doc = contexts.join("plans").join("roadmap.md");
+        assert_eq!(
+            resolve_fs_path(&roots, &doc.to_string_lossy()),
+            Ok(doc.clone())
+        );
+        // A scratch session only has its own directory
+        assert!(resolve_fs_path(&roots[..1], &doc.to_string_lossy()).is_err());
+
+        let outside = std::env::temp_dir().join("elsewhere.md");
+        assert!(resolve_fs_path(&roots, &outside.to_string_lossy()).is_err());
+        let escape = base.join("..").join("elsewhere.md");
+        assert!(resolve_fs_path(&roots, &escape.to_string_lossy()).is_err());
+        assert!(resolve_fs_path(&roots, "../elsewhere.md").is_err());
+    }
+
+    #[test]
+    fn save_agent_doc_refuses_read_only_folders() {
+        let base = std::env::temp_dir().join(format!("agent-fs-write-{}", std::process::id()));
+        let ctx = OpenContext::initialize(opencontext_core::EnvOverrides {
+            base_root: Some(base.clone()),
+            contexts_root: Some(base.join("contexts")),
+            db_path: Some(base.join("test.db")),
+        })
+        .unwrap();
+        ctx.create_folder("archive", None).unwrap();
+        ctx.create_doc("archive", "notes.md", None).unwrap();
+        ctx.set_folder_settings(
+            "archive",
+            None,
+            opencontext_core::FolderSettings {
+                read_only: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for path in ["archive/notes.md", "archive/new.md", "archive/2024/new.md"] {
+            let path = VaultPath::parse(path).unwrap();
+            assert!(save_agent_doc(&ctx, &path, "overwritten").is_err());
+        }
+        assert_eq!(ctx.get_doc_content("archive/notes.md").unwrap(), "");
+        assert!(ctx.get_doc_meta("archive/new.md").is_err());
+
+        let drafts = VaultPath::parse("drafts/new.md").unwrap();
+        save_agent_doc(&ctx, &drafts, "draft").unwrap();
+        assert_eq!(ctx.get_doc_content("drafts/new.md").unwrap(), "draft");
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
     #[test]
     fn parse_codex_mcp_args_prefers_mcp_server_for_new_versions() {
         let args = parse_codex_mcp_args("codex v0.40.1");
@@ -2130,4 +2927,57 @@ mod tests {
         let args = parse_codex_mcp_args("");
         assert_eq!(args, vec!["mcp-server".to_string()]);
     }
+
+    #[test]
+    fn parse_model_list_dedupes_case_insensitively_in_order() {
+        let value = serde_json::json!(["gpt-5", " GPT-5 ", "o3", "gpt-5-codex", "O3"]);
+        let models = parse_model_list(Some(&value));
+        assert_eq!(models, vec!["gpt-5", "o3", "gpt-5-codex"]);
+    }
+
+    #[test]
+    fn parse_model_list_trims_whitespace_in_text_lists() {
+        let value = serde_json::json!("  sonnet , opus\n\n  ,Sonnet\n haiku  ");
+        let models = parse_model_list(Some(&value));
+        assert_eq!(models, vec!["sonnet", "opus", "haiku"]);
+    }
+
+    #[test]
+    fn set_model_list_key_removes_key_when_only_blank_entries() {
+        let mut config = HashMap::new();
+        config.insert("AGENT_MODELS_CODEX".to_string(), serde_json::json!(["old"]));
+        set_model_list_key(
+            &mut config,
+            "AGENT_MODELS_CODEX",
+            Some(vec!["  ".to_string(), String::new()]),
+        );
+        assert!(!config.contains_key("AGENT_MODELS_CODEX"));
+    }
+
+    #[test]
+    fn set_model_list_key_stores_deduped_models() {
+        let mut config = HashMap::new();
+        set_model_list_key(
+            &mut config,
+            "AGENT_MODELS_CLAUDE",
+            Some(vec![" opus ".to_string(), "OPUS".to_string(), "sonnet".to_string()]),
+        );
+        assert_eq!(
+            config.get("AGENT_MODELS_CLAUDE"),
+            Some(&serde_json::json!(["opus", "sonnet"]))
+        );
+    }
+
+    #[test]
+    fn parse_acp_model_ids_reads_available_models() {
+        let models = serde_json::json!({
+            "availableModels": [
+                { "modelId": "default", "name": "Default" },
+                { "modelId": "opus", "name": "Opus" },
+                { "modelId": "Default" }
+            ],
+            "currentModelId": "default"
+        });
+        assert_eq!(parse_acp_model_ids(&models), vec!["default", "opus"]);
+    }
 }
@@ -1,23 +1,192 @@
-use crate::chat::ChatMessage;
-use crate::utils::{map_err, CmdResult};
+use crate::app_events::EmitScoped;
+use crate::chat::{fit_prompt_messages, flatten_message_content, ChatMessage};
+use crate::commands::pricing::{record_chat_usage, ChatUsage, TokenCounts};
+use crate::commands::prompts::TemplatedPrompt;
+use crate::commands::search::{reload_search_config, run_search, truncate_snippet};
+use crate::stream_seq::StreamSeq;
+use crate::tasks::CancellationToken;
+use crate::utils::{
+    get_config_bool, get_config_value, map_err, mask_secret, read_config_for_update,
+    read_config_json, redact, CmdResult, CommandError, ErrorCode,
+};
+use crate::AppState;
 use futures::StreamExt;
-use opencontext_core::search::SearchConfig;
+use opencontext_core::retry::RetryPolicy;
+use opencontext_core::search::{SearchConfig, SearchOptions, SearchResults};
+use opencontext_core::VaultPath;
 use serde::{Deserialize, Serialize};
-use tauri::Emitter;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::State;
 
-const DEFAULT_AI_PROMPT: &str = "You are an AI within a journaling app. Your job is to help the user reflect on their thoughts in a thoughtful and kind manner. The user can never directly address you or directly respond to you. Try not to repeat what the user said, instead try to seed new ideas, encourage or debate. Keep your responses concise, but meaningful. Respond in the same language as the user.";
+/// Reply length cap; the Messages API requires one
+const ANTHROPIC_MAX_TOKENS: u32 = 4096;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
 
-fn get_config_value(key: &str) -> Option<String> {
-    let config_path = SearchConfig::json_config_path();
-    if !config_path.exists() {
-        return None;
+/// config.json keys of the sampling settings
+const TEMPERATURE_KEY: &str = "AI_TEMPERATURE";
+const MAX_TOKENS_KEY: &str = "AI_MAX_TOKENS";
+const TOP_P_KEY: &str = "AI_TOP_P";
+const RETRY_MAX_ATTEMPTS_KEY: &str = "AI_RETRY_MAX_ATTEMPTS";
+const RETRY_BASE_DELAY_MS_KEY: &str = "AI_RETRY_BASE_DELAY_MS";
+
+/// Time an `ai_chat_once` reply may take
+const CHAT_ONCE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+/// Longest error body quoted in an error message
+const MAX_ERROR_BODY_CHARS: usize = 500;
+
+pub(crate) const DEFAULT_AI_PROMPT: &str = "You are an AI within a journaling app. Your job is to help the user reflect on their thoughts in a thoughtful and kind manner. The user can never directly address you or directly respond to you. Try not to repeat what the user said, instead try to seed new ideas, encourage or debate. Keep your responses concise, but meaningful. Respond in the same language as the user.";
+
+/// Highest temperature `provider` accepts: Anthropic's range is 0-1,
+/// OpenAI's and Ollama's 0-2
+fn max_temperature(provider: &str) -> f64 {
+    if provider == "anthropic" {
+        1.0
+    } else {
+        2.0
+    }
+}
+
+/// Sampling settings for chat requests. Unset ones are left out of the
+/// request so the server's defaults apply.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct GenerationParams {
+    temperature: Option<f64>,
+    max_tokens: Option<u64>,
+    top_p: Option<f64>,
+}
+
+impl GenerationParams {
+    fn from_config(config: &serde_json::Value) -> Self {
+        let number = |key: &str| {
+            let value = config.get(key)?;
+            value
+                .as_f64()
+                .or_else(|| value.as_str()?.trim().parse().ok())
+        };
+        Self {
+            temperature: number(TEMPERATURE_KEY),
+            max_tokens: number(MAX_TOKENS_KEY)
+                .filter(|n| *n >= 1.0)
+                .map(|n| n as u64),
+            top_p: number(TOP_P_KEY),
+        }
+    }
+
+    /// The settings saved in config.json
+    pub(crate) fn load() -> Self {
+        read_config_json()
+            .map(|config| Self::from_config(&config))
+            .unwrap_or_default()
+    }
+
+    /// Check the settings against the ranges `provider` accepts
+    fn validate(&self, provider: &str) -> CmdResult<()> {
+        let invalid = |key: &str, args: &[(&str, &str)]| {
+            Err(CommandError::localized(ErrorCode::InvalidInput, key, args))
+        };
+        let max_temperature = max_temperature(provider);
+        if self
+            .temperature
+            .is_some_and(|t| !(0.0..=max_temperature).contains(&t))
+        {
+            return invalid(
+                "error.temperature_out_of_range",
+                &[("max", &max_temperature.to_string())],
+            );
+        }
+        if self.top_p.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+            return invalid("error.top_p_out_of_range", &[]);
+        }
+        if self.max_tokens == Some(0) {
+            return invalid("error.max_tokens_too_low", &[]);
+        }
+        Ok(())
+    }
+
+    /// Set the settings as top-level request fields, as the OpenAI and
+    /// Anthropic APIs take them
+    pub(crate) fn apply(&self, body: &mut serde_json::Value) {
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            body["max_tokens"] = serde_json::json!(max_tokens);
+        }
+        if let Some(top_p) = self.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+    }
+
+    /// Ollama's `options` object, `None` when nothing is set
+    fn ollama_options(&self) -> Option<serde_json::Value> {
+        let mut options = serde_json::Map::new();
+        if let Some(temperature) = self.temperature {
+            options.insert("temperature".to_string(), serde_json::json!(temperature));
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            options.insert("num_predict".to_string(), serde_json::json!(max_tokens));
+        }
+        if let Some(top_p) = self.top_p {
+            options.insert("top_p".to_string(), serde_json::json!(top_p));
+        }
+        (!options.is_empty()).then_some(serde_json::Value::Object(options))
     }
-    let content = std::fs::read_to_string(&config_path).ok()?;
-    let config: serde_json::Value = serde_json::from_str(&content).ok()?;
-    config
-        .get(key)
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string())
+}
+
+/// How chat requests are retried on 429/5xx or a failed connection, from
+/// `AI_RETRY_MAX_ATTEMPTS` and `AI_RETRY_BASE_DELAY_MS`. Streams are only
+/// retried before their first byte arrives.
+fn retry_policy_from(config: &serde_json::Value) -> RetryPolicy {
+    let number = |key: &str| {
+        let value = config.get(key)?;
+        value
+            .as_u64()
+            .or_else(|| value.as_str()?.trim().parse().ok())
+    };
+    let defaults = RetryPolicy::default();
+    RetryPolicy::new(
+        number(RETRY_MAX_ATTEMPTS_KEY)
+            .map_or(defaults.max_attempts, |n| n.min(u32::MAX as u64) as u32),
+        number(RETRY_BASE_DELAY_MS_KEY)
+            .map_or(defaults.base_delay, std::time::Duration::from_millis),
+    )
+}
+
+fn retry_policy() -> RetryPolicy {
+    read_config_json()
+        .map(|config| retry_policy_from(&config))
+        .unwrap_or_default()
+}
+
+/// For options where `null` clears a setting and leaving the field out
+/// keeps it
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Store `value` under `key`, remove the key for `Some(None)`, and leave it
+/// alone for `None`
+fn update_config_number<T: Serialize>(
+    config: &mut serde_json::Map<String, serde_json::Value>,
+    key: &str,
+    value: Option<Option<T>>,
+) -> CmdResult<()> {
+    match value {
+        Some(Some(value)) => {
+            config.insert(key.to_string(), serde_json::to_value(value)?);
+        }
+        Some(None) => {
+            config.remove(key);
+        }
+        None => {}
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -29,13 +198,8 @@ pub(crate) fn get_ai_config() -> CmdResult<serde_json::Value> {
     let model = get_config_value("AI_MODEL").unwrap_or_else(|| "gpt-4o".to_string());
     let prompt = get_config_value("AI_PROMPT").unwrap_or_else(|| DEFAULT_AI_PROMPT.to_string());
 
-    let api_key_masked = api_key.as_ref().map(|key| {
-        if key.len() > 7 {
-            format!("{}...{}", &key[..3], &key[key.len() - 4..])
-        } else {
-            "****".to_string()
-        }
-    });
+    let api_key_masked = api_key.as_deref().map(mask_secret);
+    let params = GenerationParams::load();
 
     Ok(serde_json::json!({
         "provider": provider,
@@ -44,7 +208,10 @@ pub(crate) fn get_ai_config() -> CmdResult<serde_json::Value> {
         "api_key_masked": api_key_masked,
         "has_api_key": api_key.is_some() && !api_key.as_ref().unwrap().is_empty(),
         "prompt": prompt,
-        "default_prompt": DEFAULT_AI_PROMPT
+        "default_prompt": DEFAULT_AI_PROMPT,
+        "temperature": params.temperature,
+        "max_tokens": params.max_tokens,
+        "top_p": params.top_p
     }))
 }
 
@@ -56,20 +223,37 @@ pub(crate) struct SaveAIConfigOptions {
     api_base: Option<String>,
     model: Option<String>,
     prompt: Option<String>,
+    /// `null` clears these, so the server default applies again
+    #[serde(default, deserialize_with = "nullable")]
+    temperature: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    max_tokens: Option<Option<u64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    top_p: Option<Option<f64>>,
 }
 
 #[tauri::command]
-pub(crate) fn save_ai_config(options: SaveAIConfigOptions) -> CmdResult<serde_json::Value> {
-    use std::collections::HashMap;
+pub(crate) async fn save_ai_config(
+    app: tauri::AppHandle,
+    options: SaveAIConfigOptions,
+) -> CmdResult<serde_json::Value> {
+    // Settings left out of the request keep their saved values, which
+    // must still suit the provider, e.g. when switching to Anthropic
+    let saved = GenerationParams::load();
+    let provider = options
+        .provider
+        .clone()
+        .or_else(|| get_config_value("AI_PROVIDER"))
+        .unwrap_or_else(|| "openai".to_string());
+    GenerationParams {
+        temperature: options.temperature.unwrap_or(saved.temperature),
+        max_tokens: options.max_tokens.unwrap_or(saved.max_tokens),
+        top_p: options.top_p.unwrap_or(saved.top_p),
+    }
+    .validate(&provider)?;
 
     let config_path = SearchConfig::json_config_path();
-
-    let mut config: HashMap<String, serde_json::Value> = if config_path.exists() {
-        let content = std::fs::read_to_string(&config_path).map_err(map_err)?;
-        serde_json::from_str(&content).unwrap_or_default()
-    } else {
-        HashMap::new()
-    };
+    let mut config = read_config_for_update()?;
 
     if let Some(provider) = options.provider {
         config.insert(
@@ -91,13 +275,17 @@ pub(crate) fn save_ai_config(options: SaveAIConfigOptions) -> CmdResult<serde_js
     if let Some(prompt) = options.prompt {
         config.insert("AI_PROMPT".to_string(), serde_json::Value::String(prompt));
     }
+    update_config_number(&mut config, TEMPERATURE_KEY, options.temperature)?;
+    update_config_number(&mut config, MAX_TOKENS_KEY, options.max_tokens)?;
+    update_config_number(&mut config, TOP_P_KEY, options.top_p)?;
 
     if let Some(parent) = config_path.parent() {
-        std::fs::create_dir_all(parent).map_err(map_err)?;
+        std::fs::create_dir_all(parent)?;
     }
 
-    let content = serde_json::to_string_pretty(&config).map_err(map_err)?;
-    std::fs::write(&config_path, content).map_err(map_err)?;
+    let content = serde_json::to_string_pretty(&config)?;
+    std::fs::write(&config_path, content)?;
+    reload_search_config(&app).await?;
 
     Ok(serde_json::json!({
         "success": true,
@@ -107,10 +295,162 @@ pub(crate) fn save_ai_config(options: SaveAIConfigOptions) -> CmdResult<serde_js
 
 #[derive(Deserialize)]
 pub(crate) struct AIChatOptions {
+    #[serde(default)]
     messages: Vec<ChatMessage>,
     #[serde(rename = "requestId")]
     request_id: Option<String>,
     model: Option<String>,
+    /// Search the vault for the last user message and inject the hits
+    #[serde(default, rename = "useContext")]
+    use_context: bool,
+    /// Ask the model to cite injected sources as `[n]`
+    #[serde(default, rename = "citeSources")]
+    cite_sources: bool,
+    /// Prompt template rendered and appended as the last user message
+    #[serde(flatten)]
+    template: TemplatedPrompt,
+    /// Doc the chat is about; its folder settings pick the system prompt
+    #[serde(rename = "docPath")]
+    doc_path: Option<VaultPath>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AIChatOnceOptions {
+    #[serde(default)]
+    messages: Vec<ChatMessage>,
+    model: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AIChatAbortOptions {
+    request_id: String,
+}
+
+/// Cancellation tokens of in-flight `ai_chat` streams, by request id
+#[derive(Default)]
+pub(crate) struct ChatStreams {
+    next_generation: AtomicU64,
+    active: Mutex<HashMap<String, (u64, CancellationToken)>>,
+}
+
+impl ChatStreams {
+    /// Track a stream until the returned guard drops. A stream without a
+    /// request id can't be aborted and isn't tracked.
+    fn register(&self, request_id: Option<&str>) -> ChatStreamGuard<'_> {
+        let token = CancellationToken::default();
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        if let Some(id) = request_id {
+            let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+            // A repeated id replaces the stream it was used for
+            if let Some((_, previous)) = active.insert(id.to_string(), (generation, token.clone()))
+            {
+                previous.cancel();
+            }
+        }
+        ChatStreamGuard {
+            streams: self,
+            request_id: request_id.map(str::to_string),
+            generation,
+            token,
+            seq: StreamSeq::default(),
+        }
+    }
+
+    /// Cancel a stream. Returns false if none is running under the id.
+    fn abort(&self, request_id: &str) -> bool {
+        let active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        match active.get(request_id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct ChatStreamGuard<'a> {
+    streams: &'a ChatStreams,
+    request_id: Option<String>,
+    generation: u64,
+    token: CancellationToken,
+    seq: StreamSeq,
+}
+
+impl ChatStreamGuard<'_> {
+    /// Emit `event` on the stream's channel with the next `seq`
+    fn emit<T: Serialize + Clone>(&self, window: &tauri::Window, event_name: &str, event: T) {
+        let _ = window.emit_scoped(event_name, self.seq.wrap(event));
+    }
+}
+
+impl Drop for ChatStreamGuard<'_> {
+    fn drop(&mut self) {
+        let Some(id) = &self.request_id else {
+            return;
+        };
+        let mut active = self
+            .streams
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if active
+            .get(id)
+            .is_some_and(|(generation, _)| *generation == self.generation)
+        {
+            active.remove(id);
+        }
+    }
+}
+
+/// The stream's next chunk, or `None` once it ends or `cancel` fires
+async fn next_chunk<S>(stream: &mut S, cancel: &CancellationToken) -> Option<S::Item>
+where
+    S: futures::Stream + Unpin,
+{
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => None,
+        chunk = stream.next() => chunk,
+    }
+}
+
+/// `retry.send(request)`, or `None` once `cancel` fires, including while
+/// waiting out a backoff between attempts
+async fn send_unless_cancelled(
+    retry: &RetryPolicy,
+    cancel: &CancellationToken,
+    request: impl FnMut() -> reqwest::RequestBuilder,
+) -> Option<reqwest::Result<reqwest::Response>> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => None,
+        response = retry.send(request) => Some(response),
+    }
+}
+
+/// System prompt for a chat about `doc_path`: the doc's folder settings,
+/// then `AI_PROMPT`, then the built-in default
+fn system_prompt_for(state: &AppState, doc_path: &str) -> CmdResult<String> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    Ok(ctx
+        .resolve_doc_settings(doc_path)?
+        .default_ai_prompt
+        .or_else(|| get_config_value("AI_PROMPT"))
+        .unwrap_or_else(|| DEFAULT_AI_PROMPT.to_string()))
+}
+
+/// Replace the leading system message, or insert one
+fn set_system_prompt(messages: &mut Vec<ChatMessage>, prompt: String) {
+    let message = ChatMessage {
+        role: "system".to_string(),
+        content: serde_json::Value::String(prompt),
+    };
+    match messages.first_mut() {
+        Some(first) if first.role == "system" => *first = message,
+        _ => messages.insert(0, message),
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -118,6 +458,152 @@ pub(crate) struct AIStreamEvent {
     content: Option<String>,
     done: Option<bool>,
     error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    /// On the `done` event, when the provider reported token counts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<ChatUsage>,
+}
+
+/// Search hits injected into a RAG prompt
+const RAG_CONTEXT_HITS: usize = 6;
+
+/// A doc whose chunks were sent to the model. `index` is the `[n]` label
+/// used in the injected context.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Citation {
+    index: usize,
+    doc_id: Option<String>,
+    title: String,
+    path: String,
+}
+
+/// Sent once, right before `done`, when context was injected
+#[derive(Serialize, Clone)]
+struct AICitationsEvent {
+    status: &'static str,
+    citations: Vec<Citation>,
+}
+
+/// Group hits by doc (in rank order) into numbered citations and the
+/// context block that labels each doc's excerpts with its number.
+fn build_rag_context(
+    results: SearchResults,
+    doc_id: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<Citation>) {
+    let mut citations: Vec<Citation> = Vec::new();
+    let mut excerpts: Vec<Vec<String>> = Vec::new();
+    for hit in results.results {
+        let index = match citations.iter().position(|c| c.path == hit.file_path) {
+            Some(pos) => pos,
+            None => {
+                citations.push(Citation {
+                    index: citations.len() + 1,
+                    doc_id: doc_id(&hit.file_path),
+                    title: hit.display_name.clone(),
+                    path: hit.file_path.clone(),
+                });
+                excerpts.push(Vec::new());
+                citations.len() - 1
+            }
+        };
+        excerpts[index].push(truncate_snippet(&hit.content));
+    }
+
+    let context = citations
+        .iter()
+        .zip(&excerpts)
+        .map(|(citation, parts)| {
+            format!(
+                "[{}] {} ({})\n{}",
+                citation.index,
+                citation.title,
+                citation.path,
+                parts.join("\n...\n")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    (context, citations)
+}
+
+/// Search for the last user message. `None` when there is nothing to
+/// search for, no hits, or the index is unavailable.
+async fn retrieve_context(
+    state: &AppState,
+    messages: &[ChatMessage],
+    cite_sources: bool,
+) -> Option<(ChatMessage, Vec<Citation>)> {
+    let query = messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| flatten_message_content(&m.content))?;
+    if query.trim().is_empty() {
+        return None;
+    }
+    let results = match run_search(
+        state,
+        SearchOptions {
+            query,
+            limit: Some(RAG_CONTEXT_HITS),
+            ..Default::default()
+        },
+    )
+    .await
+    {
+        Ok(results) if results.error.is_none() && !results.results.is_empty() => results,
+        Ok(_) => return None,
+        Err(e) => {
+            log::warn!("[AI] Context search failed: {}", e);
+            return None;
+        }
+    };
+
+    let (context, citations) = {
+        let ctx = state.ctx.read().ok()?;
+        build_rag_context(results, |path| {
+            ctx.get_doc_meta(path).ok().map(|d| d.stable_id)
+        })
+    };
+    let mut prompt = String::from(
+        "Excerpts from the user's notes that may be relevant. Use them only if they help answer.\n\n",
+    );
+    prompt.push_str(&context);
+    if cite_sources {
+        prompt.push_str(
+            "\n\nWhen you use an excerpt, cite it with its number in square brackets, e.g. [1].",
+        );
+    }
+    let message = ChatMessage {
+        role: "system".to_string(),
+        content: serde_json::Value::String(prompt),
+    };
+    Some((message, citations))
+}
+
+/// Splits a streamed response into lines. A chunk can end partway through
+/// a line, or a character, so the unterminated tail waits for the next one.
+#[derive(Default)]
+pub(crate) struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Lines completed by `chunk`, without their line endings
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+        let rest = self.pending.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
 }
 
 pub(crate) fn extract_stream_content(value: &serde_json::Value) -> Option<String> {
@@ -189,8 +675,388 @@ pub(crate) fn content_for_ollama(content: &serde_json::Value) -> (String, Vec<St
     (String::new(), Vec::new())
 }
 
+/// Ollama's API root: the configured base when it points at Ollama,
+/// otherwise the local default.
+fn ollama_base(api_base: &str) -> String {
+    if api_base.contains("ollama") || api_base.contains("11434") {
+        api_base.to_string()
+    } else {
+        "http://localhost:11434/api".to_string()
+    }
+}
+
+/// Anthropic's API root: the configured base unless it still points at
+/// OpenAI, which is the default when switching providers.
+fn anthropic_base(api_base: &str) -> String {
+    if api_base.contains("api.openai.com") {
+        "https://api.anthropic.com/v1".to_string()
+    } else {
+        api_base.trim_end_matches('/').to_string()
+    }
+}
+
+/// Convert message content to Anthropic content blocks; data-URL images
+/// become base64 image blocks.
+fn content_for_anthropic(content: &serde_json::Value) -> serde_json::Value {
+    let Some(arr) = content.as_array() else {
+        return serde_json::Value::String(flatten_message_content(content));
+    };
+    let blocks: Vec<serde_json::Value> = arr
+        .iter()
+        .filter_map(|item| {
+            if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
+                return Some(serde_json::json!({ "type": "text", "text": text }));
+            }
+            let url = item.get("image_url")?.get("url")?.as_str()?;
+            let (header, data) = url.strip_prefix("data:")?.split_once(";base64,")?;
+            Some(serde_json::json!({
+                "type": "image",
+                "source": { "type": "base64", "media_type": header, "data": data }
+            }))
+        })
+        .collect();
+    serde_json::Value::Array(blocks)
+}
+
+/// Build a Messages API request body. System messages move to the
+/// top-level `system` field, which Anthropic requires.
+pub(crate) fn anthropic_request(
+    model: &str,
+    messages: &[ChatMessage],
+    stream: bool,
+) -> serde_json::Value {
+    let system: Vec<String> = messages
+        .iter()
+        .filter(|m| m.role == "system")
+        .map(|m| flatten_message_content(&m.content))
+        .collect();
+    let messages: Vec<serde_json::Value> = messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| {
+            serde_json::json!({
+                "role": if m.role == "assistant" { "assistant" } else { "user" },
+                "content": content_for_anthropic(&m.content)
+            })
+        })
+        .collect();
+    let mut body = serde_json::json!({
+        "model": model,
+        "max_tokens": ANTHROPIC_MAX_TOKENS,
+        "messages": messages,
+        "stream": stream
+    });
+    if !system.is_empty() {
+        body["system"] = serde_json::Value::String(system.join("\n\n"));
+    }
+    body
+}
+
+/// What a Messages API stream event means for the chat stream
+#[derive(Debug, PartialEq)]
+pub(crate) enum AnthropicEvent {
+    Text(String),
+    /// Token counts so far; input arrives at the start, output at the end
+    Usage {
+        input: Option<u64>,
+        output: Option<u64>,
+    },
+    Stop,
+    Error(String),
+}
+
+fn anthropic_usage(usage: &serde_json::Value) -> AnthropicEvent {
+    let count = |key: &str| usage.get(key).and_then(|v| v.as_u64());
+    AnthropicEvent::Usage {
+        input: count("input_tokens"),
+        output: count("output_tokens"),
+    }
+}
+
+pub(crate) fn parse_anthropic_event(json: &serde_json::Value) -> Option<AnthropicEvent> {
+    match json.get("type").and_then(|t| t.as_str())? {
+        "content_block_delta" => json
+            .get("delta")
+            .and_then(|d| d.get("text"))
+            .and_then(|t| t.as_str())
+            .map(|text| AnthropicEvent::Text(text.to_string())),
+        "message_start" => json
+            .get("message")
+            .and_then(|m| m.get("usage"))
+            .map(anthropic_usage),
+        "message_delta" => json.get("usage").map(anthropic_usage),
+        "message_stop" => Some(AnthropicEvent::Stop),
+        "error" => Some(AnthropicEvent::Error(
+            json.get("error")
+                .and_then(|e| e.get("message"))
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error")
+                .to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// Token counts from the `usage` of an OpenAI stream chunk
+fn openai_token_counts(json: &serde_json::Value) -> Option<TokenCounts> {
+    let usage = json.get("usage")?;
+    Some(TokenCounts {
+        input: usage.get("prompt_tokens")?.as_u64()?,
+        output: usage
+            .get("completion_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+    })
+}
+
+/// Whether to ask an OpenAI-compatible server for a final usage chunk. Not
+/// every server accepts the option, so by default it is only sent to OpenAI
+/// itself; `AI_STREAM_USAGE` turns it on or off for any server.
+fn stream_usage_requested(api_base: &str) -> bool {
+    get_config_bool("AI_STREAM_USAGE").unwrap_or_else(|| api_base.contains("api.openai.com"))
+}
+
+/// Token counts from the final line of an Ollama stream
+fn ollama_token_counts(json: &serde_json::Value) -> Option<TokenCounts> {
+    let count = |key: &str| json.get(key).and_then(|v| v.as_u64());
+    if count("prompt_eval_count").is_none() && count("eval_count").is_none() {
+        return None;
+    }
+    Some(TokenCounts {
+        input: count("prompt_eval_count").unwrap_or(0),
+        output: count("eval_count").unwrap_or(0),
+    })
+}
+
+/// Whether a chat provider is usable: Ollama needs no key, the others do
+pub(crate) fn ai_configured() -> bool {
+    get_config_value("AI_PROVIDER").as_deref() == Some("ollama")
+        || get_config_value("AI_API_KEY").is_some_and(|key| !key.trim().is_empty())
+}
+
+/// The message in an error body: `{"error": {"message": ...}}` (OpenAI,
+/// Anthropic), `{"error": "..."}` (Ollama), or else the body itself,
+/// shortened
+fn error_detail(body: &str) -> String {
+    let json: Option<serde_json::Value> = serde_json::from_str(body).ok();
+    let message = json
+        .as_ref()
+        .and_then(|json| json.get("error"))
+        .and_then(|error| {
+            error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .or_else(|| error.as_str())
+        });
+    if let Some(message) = message {
+        return message.to_string();
+    }
+    let body = body.trim();
+    if body.chars().count() > MAX_ERROR_BODY_CHARS {
+        format!(
+            "{}…",
+            body.chars().take(MAX_ERROR_BODY_CHARS).collect::<String>()
+        )
+    } else {
+        body.to_string()
+    }
+}
+
+/// Pass a successful response through. Any other becomes an error quoting
+/// the provider's explanation from the body, not just the status.
+async fn ensure_success(
+    provider: &str,
+    response: reqwest::Response,
+) -> CmdResult<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let code = if status.as_u16() == 401 || status.as_u16() == 403 {
+        ErrorCode::Unauthorized
+    } else {
+        ErrorCode::Network
+    };
+    let detail = error_detail(&body);
+    let status_text = status.to_string();
+    let error = if detail.is_empty() {
+        CommandError::localized(
+            code,
+            "error.provider_status",
+            &[("provider", provider), ("status", &status_text)],
+        )
+    } else {
+        CommandError::localized(
+            code,
+            "error.provider_status_detail",
+            &[
+                ("provider", provider),
+                ("status", &status_text),
+                ("detail", &redact(&detail)),
+            ],
+        )
+    };
+    Err(error.with_details(serde_json::json!({ "status": status.as_u16() })))
+}
+
+fn api_key_missing(provider: &str) -> CommandError {
+    CommandError::localized(
+        ErrorCode::Unauthorized,
+        "error.api_key_missing",
+        &[("provider", provider)],
+    )
+}
+
+fn no_content(provider: &str) -> CommandError {
+    CommandError::localized(
+        ErrorCode::Network,
+        "error.no_content",
+        &[("provider", provider)],
+    )
+}
+
+/// Run a single non-streaming completion with the configured provider and
+/// model, returning the reply text.
+pub(crate) async fn complete(
+    messages: &[ChatMessage],
+    timeout: std::time::Duration,
+) -> CmdResult<String> {
+    complete_with_model(messages, None, timeout).await
+}
+
+/// [`complete`], with `model` in place of the configured one when given
+async fn complete_with_model(
+    messages: &[ChatMessage],
+    model: Option<&str>,
+    timeout: std::time::Duration,
+) -> CmdResult<String> {
+    let provider = get_config_value("AI_PROVIDER").unwrap_or_else(|| "openai".to_string());
+    let api_base =
+        get_config_value("AI_API_BASE").unwrap_or_else(|| "https://api.openai.com/v1".to_string());
+    let model = model
+        .filter(|value| !value.trim().is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| get_config_value("AI_MODEL").unwrap_or_else(|| "gpt-4o".to_string()));
+    let client = reqwest::Client::builder().timeout(timeout).build()?;
+    let retry = retry_policy();
+
+    if provider == "ollama" {
+        let ollama_url = ollama_base(&api_base);
+        let messages: Vec<serde_json::Value> = messages
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "role": m.role,
+                    "content": content_for_ollama(&m.content).0
+                })
+            })
+            .collect();
+        log::debug!(
+            "[AI] ollama completion: {}/chat, model {}",
+            redact(&ollama_url),
+            model
+        );
+        let body = serde_json::json!({
+            "model": model,
+            "messages": messages,
+            "stream": false
+        });
+        let response = retry
+            .send(|| client.post(format!("{}/chat", ollama_url)).json(&body))
+            .await?;
+        let response = ensure_success("Ollama", response).await?;
+        let json: serde_json::Value = response.json().await?;
+        return json
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| no_content("Ollama"));
+    }
+
+    if provider == "anthropic" {
+        let api_key = get_config_value("AI_API_KEY").ok_or_else(|| api_key_missing("Anthropic"))?;
+        let anthropic_url = anthropic_base(&api_base);
+        log::debug!(
+            "[AI] anthropic completion: {}/messages, model {}",
+            redact(&anthropic_url),
+            model
+        );
+        let body = anthropic_request(&model, messages, false);
+        let response = retry
+            .send(|| {
+                client
+                    .post(format!("{}/messages", anthropic_url))
+                    .header("x-api-key", &api_key)
+                    .header("anthropic-version", ANTHROPIC_VERSION)
+                    .json(&body)
+            })
+            .await?;
+        let response = ensure_success("Anthropic", response).await?;
+        let json: serde_json::Value = response.json().await?;
+        return json
+            .get("content")
+            .and_then(extract_stream_content)
+            .ok_or_else(|| no_content("Anthropic"));
+    }
+
+    let api_key = get_config_value("AI_API_KEY").ok_or_else(|| api_key_missing("OpenAI"))?;
+    log::debug!(
+        "[AI] {} completion: {}/chat/completions, model {}",
+        provider,
+        redact(&api_base),
+        model
+    );
+    let body = serde_json::json!({
+        "model": model,
+        "messages": messages,
+        "stream": false
+    });
+    let response = retry
+        .send(|| {
+            client
+                .post(format!("{}/chat/completions", api_base))
+                .header("Authorization", format!("Bearer {}", api_key))
+                .json(&body)
+        })
+        .await?;
+    let response = ensure_success("OpenAI", response).await?;
+    let json: serde_json::Value = response.json().await?;
+    json.get("choices")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("message"))
+        .and_then(|m| m.get("content"))
+        .and_then(extract_stream_content)
+        .ok_or_else(|| no_content("OpenAI"))
+}
+
+/// Chat without streaming: the whole reply as one string, for callers that
+/// don't want to listen to `ai-stream-*` events. Failed requests return the
+/// provider's error message.
 #[tauri::command]
-pub(crate) async fn ai_chat(window: tauri::Window, options: AIChatOptions) -> CmdResult<()> {
+pub(crate) async fn ai_chat_once(options: AIChatOnceOptions) -> CmdResult<String> {
+    if options.messages.is_empty() {
+        return Err(CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.no_messages",
+            &[],
+        ));
+    }
+    complete_with_model(
+        &options.messages,
+        options.model.as_deref(),
+        CHAT_ONCE_TIMEOUT,
+    )
+    .await
+}
+
+#[tauri::command]
+pub(crate) async fn ai_chat(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    options: AIChatOptions,
+) -> CmdResult<()> {
     let provider = get_config_value("AI_PROVIDER").unwrap_or_else(|| "openai".to_string());
     let api_key = get_config_value("AI_API_KEY");
     let api_base =
@@ -205,18 +1071,70 @@ pub(crate) async fn ai_chat(window: tauri::Window, options: AIChatOptions) -> Cm
         Some(id) => format!("ai-stream-{}", id),
         None => "ai-stream".to_string(),
     };
+    let stream_guard = state.chat_streams.register(options.request_id.as_deref());
+
+    let mut messages = options.template.apply(&state, options.messages)?;
+    if let Some(doc_path) = &options.doc_path {
+        set_system_prompt(&mut messages, system_prompt_for(&state, doc_path.as_str())?);
+    }
+    let (mut messages, truncated) = fit_prompt_messages(&messages);
+    if truncated {
+        stream_guard.emit(
+            &window,
+            &event_name,
+            AIStreamEvent {
+                content: None,
+                done: None,
+                error: None,
+                status: Some("context_truncated".to_string()),
+                usage: None,
+            },
+        );
+    }
+
+    // Injected after fitting so truncation never drops the retrieved context.
+    let mut citations = None;
+    if options.use_context {
+        if let Some((context, cited)) =
+            retrieve_context(&state, &messages, options.cite_sources).await
+        {
+            let at = messages.len().saturating_sub(1);
+            messages.insert(at, context);
+            citations = Some(cited);
+        }
+    }
+    let mut finish = |tokens: Option<TokenCounts>| {
+        if let Some(citations) = citations.take() {
+            stream_guard.emit(
+                &window,
+                &event_name,
+                AICitationsEvent {
+                    status: "citations",
+                    citations,
+                },
+            );
+        }
+        stream_guard.emit(
+            &window,
+            &event_name,
+            AIStreamEvent {
+                content: None,
+                done: Some(true),
+                error: None,
+                status: None,
+                usage: tokens.map(|tokens| record_chat_usage(&state, &model, tokens)),
+            },
+        );
+    };
 
     let client = reqwest::Client::new();
+    let params = GenerationParams::load();
+    let retry = retry_policy();
 
     if provider == "ollama" {
-        let ollama_url = if api_base.contains("ollama") || api_base.contains("11434") {
-            api_base.clone()
-        } else {
-            "http://localhost:11434/api".to_string()
-        };
+        let ollama_url = ollama_base(&api_base);
 
-        let messages: Vec<serde_json::Value> = options
-            .messages
+        let messages: Vec<serde_json::Value> = messages
             .iter()
             .map(|m| {
                 let (text, images) = content_for_ollama(&m.content);
@@ -233,74 +1151,219 @@ pub(crate) async fn ai_chat(window: tauri::Window, options: AIChatOptions) -> Cm
             })
             .collect();
 
-        let response = client
-            .post(format!("{}/chat", ollama_url))
-            .header("Content-Type", "application/json")
-            .json(&serde_json::json!({
-                "model": model,
-                "messages": messages,
-                "stream": true
-            }))
-            .send()
-            .await
-            .map_err(map_err)?;
+        log::debug!(
+            "[AI] ollama chat: {}/chat, model {}",
+            redact(&ollama_url),
+            model
+        );
+        let mut body = serde_json::json!({
+            "model": model,
+            "messages": messages,
+            "stream": true
+        });
+        if let Some(options) = params.ollama_options() {
+            body["options"] = options;
+        }
+        let response = match send_unless_cancelled(&retry, &stream_guard.token, || {
+            client
+                .post(format!("{}/chat", ollama_url))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })
+        .await
+        {
+            Some(response) => response?,
+            None => {
+                finish(None);
+                return Ok(());
+            }
+        };
 
         if !response.status().is_success() {
-            let _ = window.emit(
+            stream_guard.emit(
+                &window,
                 &event_name,
                 AIStreamEvent {
                     content: None,
                     done: None,
                     error: Some(format!("Ollama error: {}", response.status())),
+                    status: None,
+                    usage: None,
                 },
             );
             return Ok(());
         }
 
         let mut stream = response.bytes_stream();
-        while let Some(chunk_result) = stream.next().await {
+        let mut lines = LineBuffer::default();
+        while let Some(chunk_result) = next_chunk(&mut stream, &stream_guard.token).await {
             match chunk_result {
                 Ok(chunk) => {
-                    let text = String::from_utf8_lossy(&chunk);
-                    for line in text.lines() {
+                    for line in lines.push(&chunk) {
                         if line.trim().is_empty() {
                             continue;
                         }
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
+                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
                             if let Some(content) = json
                                 .get("message")
                                 .and_then(|m| m.get("content"))
                                 .and_then(|c| c.as_str())
                             {
-                                let _ = window.emit(
+                                stream_guard.emit(
+                                    &window,
                                     &event_name,
                                     AIStreamEvent {
                                         content: Some(content.to_string()),
                                         done: None,
                                         error: None,
+                                        status: None,
+                                        usage: None,
                                     },
                                 );
                             }
                             if json.get("done").and_then(|d| d.as_bool()).unwrap_or(false) {
-                                let _ = window.emit(
+                                finish(ollama_token_counts(&json));
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    stream_guard.emit(
+                        &window,
+                        &event_name,
+                        AIStreamEvent {
+                            content: None,
+                            done: None,
+                            error: Some(redact(&format!("Ollama error: {}", e))),
+                            status: None,
+                            usage: None,
+                        },
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        finish(None);
+        return Ok(());
+    }
+
+    if provider == "anthropic" {
+        let api_key = api_key.ok_or_else(|| api_key_missing("Anthropic"))?;
+        let anthropic_url = anthropic_base(&api_base);
+        let mut body = anthropic_request(&model, &messages, true);
+        params.apply(&mut body);
+
+        log::debug!(
+            "[AI] anthropic chat: {}/messages, model {}",
+            redact(&anthropic_url),
+            model
+        );
+        let response = match send_unless_cancelled(&retry, &stream_guard.token, || {
+            client
+                .post(format!("{}/messages", anthropic_url))
+                .header("Content-Type", "application/json")
+                .header("x-api-key", &api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .json(&body)
+        })
+        .await
+        {
+            Some(response) => response?,
+            None => {
+                finish(None);
+                return Ok(());
+            }
+        };
+
+        // The error body explains a rejected request, so it goes in the event
+        let response = match ensure_success("Anthropic", response).await {
+            Ok(response) => response,
+            Err(e) => {
+                stream_guard.emit(
+                    &window,
+                    &event_name,
+                    AIStreamEvent {
+                        content: None,
+                        done: None,
+                        error: Some(e.message),
+                        status: None,
+                        usage: None,
+                    },
+                );
+                return Ok(());
+            }
+        };
+
+        let mut stream = response.bytes_stream();
+        let mut lines = LineBuffer::default();
+        let mut tokens: Option<TokenCounts> = None;
+        while let Some(chunk_result) = next_chunk(&mut stream, &stream_guard.token).await {
+            match chunk_result {
+                Ok(chunk) => {
+                    for line in lines.push(&chunk) {
+                        let Some(data) = line.strip_prefix("data:") else {
+                            continue;
+                        };
+                        let Ok(json) = serde_json::from_str::<serde_json::Value>(data.trim())
+                        else {
+                            continue;
+                        };
+                        match parse_anthropic_event(&json) {
+                            Some(AnthropicEvent::Text(token)) => {
+                                stream_guard.emit(
+                                    &window,
                                     &event_name,
                                     AIStreamEvent {
-                                        content: None,
-                                        done: Some(true),
+                                        content: Some(token),
+                                        done: None,
                                         error: None,
+                                        status: None,
+                                        usage: None,
                                     },
                                 );
                             }
+                            Some(AnthropicEvent::Usage { input, output }) => {
+                                let counts = tokens.get_or_insert_with(TokenCounts::default);
+                                counts.input = input.unwrap_or(counts.input);
+                                counts.output = output.unwrap_or(counts.output);
+                            }
+                            Some(AnthropicEvent::Stop) => {
+                                finish(tokens);
+                                return Ok(());
+                            }
+                            Some(AnthropicEvent::Error(message)) => {
+                                stream_guard.emit(
+                                    &window,
+                                    &event_name,
+                                    AIStreamEvent {
+                                        content: None,
+                                        done: None,
+                                        error: Some(redact(&format!(
+                                            "Anthropic error: {}",
+                                            message
+                                        ))),
+                                        status: None,
+                                        usage: None,
+                                    },
+                                );
+                                return Ok(());
+                            }
+                            None => {}
                         }
                     }
                 }
                 Err(e) => {
-                    let _ = window.emit(
+                    stream_guard.emit(
+                        &window,
                         &event_name,
                         AIStreamEvent {
                             content: None,
                             done: None,
-                            error: Some(format!("Ollama error: {}", e)),
+                            error: Some(redact(&format!("Anthropic error: {}", e))),
+                            status: None,
+                            usage: None,
                         },
                     );
                     return Ok(());
@@ -308,66 +1371,78 @@ pub(crate) async fn ai_chat(window: tauri::Window, options: AIChatOptions) -> Cm
             }
         }
 
-        let _ = window.emit(
-            &event_name,
-            AIStreamEvent {
-                content: None,
-                done: Some(true),
-                error: None,
-            },
-        );
+        finish(tokens);
         return Ok(());
     }
 
-    let api_key = api_key.ok_or_else(|| "OpenAI API key not configured".to_string())?;
+    let api_key = api_key.ok_or_else(|| api_key_missing("OpenAI"))?;
 
-    let response = client
-        .post(format!("{}/chat/completions", api_base))
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&serde_json::json!({
-            "model": model,
-            "messages": options.messages,
-            "stream": true
-        }))
-        .send()
-        .await
-        .map_err(map_err)?;
+    let mut body = serde_json::json!({
+        "model": model,
+        "messages": messages,
+        "stream": true
+    });
+    params.apply(&mut body);
+    if stream_usage_requested(&api_base) {
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+    }
+
+    log::debug!(
+        "[AI] {} chat: {}/chat/completions, model {}",
+        provider,
+        redact(&api_base),
+        model
+    );
+    let response = match send_unless_cancelled(&retry, &stream_guard.token, || {
+        client
+            .post(format!("{}/chat/completions", api_base))
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .json(&body)
+    })
+    .await
+    {
+        Some(response) => response?,
+        None => {
+            finish(None);
+            return Ok(());
+        }
+    };
 
     if !response.status().is_success() {
-        let _ = window.emit(
+        stream_guard.emit(
+            &window,
             &event_name,
             AIStreamEvent {
                 content: None,
                 done: None,
                 error: Some(format!("OpenAI error: {}", response.status())),
+                status: None,
+                usage: None,
             },
         );
         return Ok(());
     }
 
     let mut stream = response.bytes_stream();
-    while let Some(chunk_result) = stream.next().await {
+    let mut lines = LineBuffer::default();
+    let mut tokens: Option<TokenCounts> = None;
+    while let Some(chunk_result) = next_chunk(&mut stream, &stream_guard.token).await {
         match chunk_result {
             Ok(chunk) => {
-                let text = String::from_utf8_lossy(&chunk);
-                for line in text.lines() {
+                for line in lines.push(&chunk) {
                     if !line.starts_with("data: ") {
                         continue;
                     }
                     let content = line.trim_start_matches("data: ").trim();
                     if content == "[DONE]" {
-                        let _ = window.emit(
-                            &event_name,
-                            AIStreamEvent {
-                                content: None,
-                                done: Some(true),
-                                error: None,
-                            },
-                        );
+                        finish(tokens);
                         return Ok(());
                     }
                     if let Ok(json) = serde_json::from_str::<serde_json::Value>(content) {
+                        if let Some(counts) = openai_token_counts(&json) {
+                            tokens = Some(counts);
+                        }
                         let text_chunk = json
                             .get("choices")
                             .and_then(|c| c.get(0))
@@ -375,12 +1450,15 @@ pub(crate) async fn ai_chat(window: tauri::Window, options: AIChatOptions) -> Cm
                             .and_then(|d| d.get("content"))
                             .and_then(|c| extract_stream_content(c));
                         if let Some(token) = text_chunk {
-                            let _ = window.emit(
+                            stream_guard.emit(
+                                &window,
                                 &event_name,
                                 AIStreamEvent {
                                     content: Some(token),
                                     done: None,
                                     error: None,
+                                    status: None,
+                                    usage: None,
                                 },
                             );
                         }
@@ -388,12 +1466,15 @@ pub(crate) async fn ai_chat(window: tauri::Window, options: AIChatOptions) -> Cm
                 }
             }
             Err(e) => {
-                let _ = window.emit(
+                stream_guard.emit(
+                    &window,
                     &event_name,
                     AIStreamEvent {
                         content: None,
                         done: None,
-                        error: Some(format!("OpenAI error: {}", e)),
+                        error: Some(redact(&format!("OpenAI error: {}", e))),
+                        status: None,
+                        usage: None,
                     },
                 );
                 return Ok(());
@@ -401,22 +1482,43 @@ pub(crate) async fn ai_chat(window: tauri::Window, options: AIChatOptions) -> Cm
         }
     }
 
-    let _ = window.emit(
-        &event_name,
-        AIStreamEvent {
-            content: None,
-            done: Some(true),
-            error: None,
-        },
-    );
+    finish(tokens);
     Ok(())
 }
 
+/// Stop an `ai_chat` stream. It ends with the usual `done` event, so the
+/// reply so far stays.
+#[tauri::command]
+pub(crate) fn ai_chat_abort(
+    state: State<'_, AppState>,
+    options: AIChatAbortOptions,
+) -> CmdResult<bool> {
+    Ok(state.chat_streams.abort(&options.request_id))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn error_detail_prefers_the_provider_message() {
+        assert_eq!(
+            error_detail(r#"{"error":{"message":"Incorrect API key","type":"invalid"}}"#),
+            "Incorrect API key"
+        );
+        assert_eq!(
+            error_detail(r#"{"error":"model 'llama9' not found"}"#),
+            "model 'llama9' not found"
+        );
+        assert_eq!(error_detail(" Bad Gateway \n"), "Bad Gateway");
+        let long = "x".repeat(MAX_ERROR_BODY_CHARS + 10);
+        assert_eq!(
+            error_detail(&long).chars().count(),
+            MAX_ERROR_BODY_CHARS + 1
+        );
+    }
+
     #[test]
     fn extract_stream_content_handles_text_shapes() {
         assert_eq!(extract_stream_content(&json!("ping")), Some("ping".to_string()));
@@ -426,6 +1528,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn generation_params_are_left_out_when_unset() {
+        let params = GenerationParams::from_config(&json!({
+            "AI_TEMPERATURE": 0.2,
+            "AI_MAX_TOKENS": "512",
+        }));
+        let mut body = json!({ "model": "gpt-4o" });
+        params.apply(&mut body);
+        assert_eq!(
+            body,
+            json!({ "model": "gpt-4o", "temperature": 0.2, "max_tokens": 512 })
+        );
+        assert_eq!(
+            params.ollama_options(),
+            Some(json!({ "temperature": 0.2, "num_predict": 512 }))
+        );
+        assert_eq!(GenerationParams::default().ollama_options(), None);
+    }
+
+    #[test]
+    fn temperature_range_depends_on_the_provider() {
+        let params = GenerationParams {
+            temperature: Some(1.5),
+            ..Default::default()
+        };
+        assert!(params.validate("openai").is_ok());
+        assert!(params.validate("ollama").is_ok());
+        let err = params.validate("anthropic").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        assert_eq!(err.details.unwrap()["messageArgs"]["max"], "1");
+
+        let params = GenerationParams {
+            temperature: Some(2.5),
+            ..Default::default()
+        };
+        assert!(params.validate("openai").is_err());
+        assert!(GenerationParams::default().validate("anthropic").is_ok());
+    }
+
+    #[test]
+    fn retry_policy_reads_numbers_and_strings() {
+        let policy = retry_policy_from(&serde_json::json!({
+            "AI_RETRY_MAX_ATTEMPTS": "5",
+            "AI_RETRY_BASE_DELAY_MS": 250,
+        }));
+        assert_eq!(
+            policy,
+            RetryPolicy::new(5, std::time::Duration::from_millis(250))
+        );
+        assert_eq!(
+            retry_policy_from(&serde_json::json!({ "AI_RETRY_MAX_ATTEMPTS": 0 })).max_attempts,
+            1
+        );
+        assert_eq!(
+            retry_policy_from(&serde_json::json!({})),
+            RetryPolicy::default()
+        );
+    }
+
+    #[test]
+    fn save_ai_config_options_tell_null_from_missing() {
+        let options: SaveAIConfigOptions =
+            serde_json::from_value(json!({ "temperature": null, "topP": 0.9 })).unwrap();
+        assert_eq!(options.temperature, Some(None));
+        assert_eq!(options.top_p, Some(Some(0.9)));
+        assert_eq!(options.max_tokens, None);
+    }
+
+    #[test]
+    fn token_counts_come_from_final_chunks_only() {
+        let delta = json!({ "choices": [{ "delta": { "content": "Hi" } }], "usage": null });
+        assert_eq!(openai_token_counts(&delta), None);
+        let last = json!({
+            "choices": [],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 5, "total_tokens": 17 }
+        });
+        assert_eq!(
+            openai_token_counts(&last),
+            Some(TokenCounts {
+                input: 12,
+                output: 5
+            })
+        );
+
+        let partial = json!({ "message": { "content": "Hi" }, "done": false });
+        assert_eq!(ollama_token_counts(&partial), None);
+        let done = json!({ "done": true, "prompt_eval_count": 30, "eval_count": 8 });
+        assert_eq!(
+            ollama_token_counts(&done),
+            Some(TokenCounts {
+                input: 30,
+                output: 8
+            })
+        );
+    }
+
+    #[test]
+    fn line_buffer_joins_lines_split_across_chunks() {
+        let mut lines = LineBuffer::default();
+        let payload = "data: {\"choices\":[{\"delta\":{\"content\":\"Grüße\"}}]}\n\n";
+        // Split inside the JSON and inside the two-byte "ü"
+        let split = payload.find('ü').unwrap() + 1;
+        assert!(lines.push(&payload.as_bytes()[..split]).is_empty());
+        let complete = lines.push(&payload.as_bytes()[split..]);
+        assert_eq!(complete.len(), 2);
+
+        let data = complete[0].strip_prefix("data: ").unwrap();
+        let json: serde_json::Value = serde_json::from_str(data).unwrap();
+        let token = extract_stream_content(&json["choices"][0]["delta"]["content"]);
+        assert_eq!(token.as_deref(), Some("Grüße"));
+    }
+
+    #[test]
+    fn chat_streams_forget_finished_requests() {
+        let streams = ChatStreams::default();
+        let first = streams.register(Some("req-1"));
+        let second = streams.register(Some("req-1"));
+        assert!(first.token.is_cancelled());
+        drop(first);
+        assert!(streams.abort("req-1"));
+        assert!(second.token.is_cancelled());
+        drop(second);
+        assert!(!streams.abort("req-1"));
+    }
+
     #[test]
     fn content_for_ollama_extracts_images() {
         let (text, images) = content_for_ollama(&json!([
@@ -435,4 +1662,59 @@ mod tests {
         assert_eq!(text, "Hello");
         assert_eq!(images, vec!["ABC123".to_string()]);
     }
+
+    #[test]
+    fn anthropic_request_lifts_system_prompt() {
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: json!("Be brief."),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: json!([
+                    { "type": "text", "text": "What is this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,ABC" } }
+                ]),
+            },
+        ];
+        let body = anthropic_request("claude-sonnet", &messages, true);
+        assert_eq!(body["system"], "Be brief.");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(body["messages"][0]["content"][0]["text"], "What is this?");
+        assert_eq!(
+            body["messages"][0]["content"][1]["source"]["media_type"],
+            "image/png"
+        );
+        assert_eq!(body["messages"][0]["content"][1]["source"]["data"], "ABC");
+    }
+
+    #[test]
+    fn parse_anthropic_event_maps_stream_events() {
+        let delta = json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": { "type": "text_delta", "text": "Hi" }
+        });
+        assert_eq!(
+            parse_anthropic_event(&delta),
+            Some(AnthropicEvent::Text("Hi".to_string()))
+        );
+        assert_eq!(
+            parse_anthropic_event(&json!({ "type": "message_stop" })),
+            Some(AnthropicEvent::Stop)
+        );
+        assert_eq!(
+            parse_anthropic_event(&json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "end_turn" },
+                "usage": { "output_tokens": 15 }
+            })),
+            Some(AnthropicEvent::Usage {
+                input: None,
+                output: Some(15)
+            })
+        );
+        assert_eq!(parse_anthropic_event(&json!({ "type": "ping" })), None);
+    }
 }
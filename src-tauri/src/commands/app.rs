@@ -0,0 +1,249 @@
+use crate::app_events::EmitScoped;
+use crate::i18n;
+use crate::logging;
+use crate::services::{ServiceInfo, ServiceKind};
+use crate::tasks::TaskInfo;
+use crate::utils::{
+    get_config_bool, map_err, set_config_value, CmdResult, CommandError, ErrorCode,
+};
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use tauri::Manager;
+
+// ===== Quit Confirmation =====
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RunningTask {
+    kind: &'static str,
+    id: String,
+    label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct ConfirmQuitPayload {
+    running: Vec<RunningTask>,
+}
+
+/// Agents with an in-flight request and terminals whose child is still alive.
+pub(crate) fn collect_running_tasks(state: &AppState) -> Vec<RunningTask> {
+    let mut running = Vec::new();
+
+    if let Ok(sessions) = state.agent_rpc_sessions.lock() {
+        for (session_id, session) in sessions.iter() {
+            let active_request = session
+                .state
+                .lock()
+                .ok()
+                .and_then(|state| state.active_request.clone());
+            if let Some(request_id) = active_request {
+                running.push(RunningTask {
+                    kind: "agent",
+                    id: session_id.clone(),
+                    label: session.kind.label().to_string(),
+                    request_id: Some(request_id),
+                });
+            }
+        }
+    }
+
+    if let Ok(sessions) = state.terminal_sessions.lock() {
+        for (id, session) in sessions.iter() {
+            let alive = session
+                .child
+                .lock()
+                .ok()
+                .map(|mut child| matches!(child.try_wait(), Ok(None)))
+                .unwrap_or(false);
+            if alive {
+                running.push(RunningTask {
+                    kind: "terminal",
+                    id: id.clone(),
+                    label: session.command.clone(),
+                    request_id: None,
+                });
+            }
+        }
+    }
+
+    running
+}
+
+/// Show the window and ask the frontend to confirm quitting while work is
+/// still running. Returns `true` when a prompt was raised and the quit should
+/// be held back. Disabled by setting `CONFIRM_QUIT` to false in config.json.
+pub(crate) fn prompt_quit_if_busy(app: &tauri::AppHandle) -> bool {
+    if !get_config_bool("CONFIRM_QUIT").unwrap_or(true) {
+        return false;
+    }
+    let state = app.state::<AppState>();
+    let running = collect_running_tasks(&state);
+    if running.is_empty() {
+        return false;
+    }
+    crate::show_main_window(app);
+    let _ = app.emit_scoped("confirm-quit", ConfirmQuitPayload { running });
+    true
+}
+
+pub(crate) fn request_quit(app: &tauri::AppHandle, force: bool) {
+    if !force && prompt_quit_if_busy(app) {
+        return;
+    }
+    app.state::<AppState>()
+        .allow_close
+        .store(true, Ordering::SeqCst);
+    app.exit(0);
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuitAppOptions {
+    force: Option<bool>,
+}
+
+#[tauri::command]
+pub(crate) fn quit_app(app: tauri::AppHandle, options: Option<QuitAppOptions>) -> CmdResult<()> {
+    let force = options.and_then(|o| o.force).unwrap_or(false);
+    request_quit(&app, force);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn confirm_quit(app: tauri::AppHandle) -> CmdResult<()> {
+    request_quit(&app, true);
+    Ok(())
+}
+
+// ===== Logs =====
+
+#[tauri::command]
+pub(crate) fn get_log_path() -> CmdResult<String> {
+    logging::log_path()
+        .map(|p| p.to_string_lossy().to_string())
+        .ok_or_else(|| CommandError::localized(ErrorCode::NotFound, "error.file_logging_off", &[]))
+}
+
+#[tauri::command]
+pub(crate) fn open_logs_folder() -> CmdResult<()> {
+    let log_path = logging::log_path().ok_or_else(|| {
+        CommandError::localized(ErrorCode::NotFound, "error.file_logging_off", &[])
+    })?;
+    let dir = log_path.parent().ok_or_else(|| {
+        CommandError::localized(ErrorCode::NotFound, "error.log_dir_not_found", &[])
+    })?;
+
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(target_os = "windows")]
+    let opener = "explorer";
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let opener = "xdg-open";
+
+    std::process::Command::new(opener)
+        .arg(dir)
+        .spawn()
+        .map_err(map_err)?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetLogLevelOptions {
+    level: String,
+}
+
+/// Change the log level immediately and persist it as `LOG_LEVEL` for the next launch.
+#[tauri::command]
+pub(crate) fn set_log_level(options: SetLogLevelOptions) -> CmdResult<String> {
+    let level = logging::parse_level(&options.level).ok_or_else(|| {
+        CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.invalid_log_level",
+            &[("level", &options.level)],
+        )
+    })?;
+    log::set_max_level(level);
+    let name = level.to_string().to_lowercase();
+    set_config_value("LOG_LEVEL", serde_json::Value::String(name.clone()))?;
+    log::info!("[Logging] Level set to {}", name);
+    Ok(name)
+}
+
+// ===== Background Tasks =====
+
+/// Long-running tasks currently registered, oldest first
+#[tauri::command]
+pub(crate) fn task_list(state: tauri::State<AppState>) -> CmdResult<Vec<TaskInfo>> {
+    Ok(state.tasks.list())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaskCancelOptions {
+    id: u64,
+}
+
+/// Ask a task to stop. Its final status arrives as a `task-progress` event.
+#[tauri::command]
+pub(crate) fn task_cancel(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    options: TaskCancelOptions,
+) -> CmdResult<bool> {
+    Ok(state.tasks.cancel(&app, options.id))
+}
+
+// ===== Background Services =====
+
+/// State and uptime of each background service
+#[tauri::command]
+pub(crate) fn services_status(state: tauri::State<AppState>) -> CmdResult<Vec<ServiceInfo>> {
+    Ok(state.services.status())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ServiceOptions {
+    service: ServiceKind,
+}
+
+/// Start a background service; a no-op if it is running
+#[tauri::command]
+pub(crate) fn service_start(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    options: ServiceOptions,
+) -> CmdResult<Vec<ServiceInfo>> {
+    state.services.start(&app, options.service)?;
+    Ok(state.services.status())
+}
+
+/// Stop a background service; a no-op if it is stopped
+#[tauri::command]
+pub(crate) fn service_stop(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    options: ServiceOptions,
+) -> CmdResult<Vec<ServiceInfo>> {
+    state.services.stop(&app, options.service)?;
+    Ok(state.services.status())
+}
+
+// ===== Backend Locale =====
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetBackendLocaleOptions {
+    locale: String,
+}
+
+/// Locale for messages produced in Rust (agent statuses and hints, some
+/// errors). Returns the locale in use: unsupported ones fall back to `en`.
+#[tauri::command]
+pub(crate) fn set_backend_locale(options: SetBackendLocaleOptions) -> CmdResult<String> {
+    Ok(i18n::set_locale(&options.locale).to_string())
+}
@@ -1,7 +1,14 @@
-use crate::utils::{map_err, CmdResult};
+use crate::app_events;
+use crate::commands::search::{queue_doc_index, reload_search_config};
+use crate::commands::summarize::queue_enrichment;
+use crate::utils::{
+    map_err, mask_secret, read_config_for_update, CmdResult, CommandError, ErrorCode,
+};
 use crate::AppState;
-use opencontext_core::search::SearchConfig;
+use opencontext_core::search::{ConfigIssue, EmbeddingProvider, SearchConfig};
+use opencontext_core::{FolderSettings, OpenContext, VaultPath};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use tauri::State;
 
 // ===== Folder Commands =====
@@ -17,17 +24,15 @@ pub(crate) fn list_folders(
     state: State<AppState>,
     options: Option<ListFoldersOptions>,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let folders = ctx
-        .list_folders(options.and_then(|o| o.all).unwrap_or(false))
-        .map_err(map_err)?;
-    serde_json::to_value(&folders).map_err(map_err)
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let folders = ctx.list_folders(options.and_then(|o| o.all).unwrap_or(false))?;
+    Ok(serde_json::to_value(&folders)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct CreateFolderOptions {
-    path: String,
+    path: VaultPath,
     description: Option<String>,
 }
 
@@ -36,17 +41,15 @@ pub(crate) fn create_folder(
     state: State<AppState>,
     options: CreateFolderOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let folder = ctx
-        .create_folder(&options.path, options.description.as_deref())
-        .map_err(map_err)?;
-    serde_json::to_value(&folder).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let folder = ctx.create_folder(options.path.as_str(), options.description.as_deref())?;
+    Ok(serde_json::to_value(&folder)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct RenameFolderOptions {
-    path: String,
+    path: VaultPath,
     new_name: String,
 }
 
@@ -55,18 +58,16 @@ pub(crate) fn rename_folder(
     state: State<AppState>,
     options: RenameFolderOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let folder = ctx
-        .rename_folder(&options.path, &options.new_name)
-        .map_err(map_err)?;
-    serde_json::to_value(&folder).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let folder = ctx.rename_folder(options.path.as_str(), &options.new_name)?;
+    Ok(serde_json::to_value(&folder)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct MoveFolderOptions {
-    path: String,
-    dest_folder_path: String,
+    path: VaultPath,
+    dest_folder_path: VaultPath,
 }
 
 #[tauri::command]
@@ -74,25 +75,22 @@ pub(crate) fn move_folder(
     state: State<AppState>,
     options: MoveFolderOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let folder = ctx
-        .move_folder(&options.path, &options.dest_folder_path)
-        .map_err(map_err)?;
-    serde_json::to_value(&folder).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let folder = ctx.move_folder(options.path.as_str(), options.dest_folder_path.as_str())?;
+    Ok(serde_json::to_value(&folder)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct RemoveFolderOptions {
-    path: String,
+    path: VaultPath,
     force: Option<bool>,
 }
 
 #[tauri::command]
 pub(crate) fn remove_folder(state: State<AppState>, options: RemoveFolderOptions) -> CmdResult<bool> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    ctx.remove_folder(&options.path, options.force.unwrap_or(false))
-        .map_err(map_err)?;
+    let ctx = state.ctx.write().map_err(map_err)?;
+    ctx.remove_folder(options.path.as_str(), options.force.unwrap_or(false))?;
     Ok(true)
 }
 
@@ -101,85 +99,84 @@ pub(crate) fn remove_folder(state: State<AppState>, options: RemoveFolderOptions
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ListDocsOptions {
-    folder_path: String,
+    folder_path: VaultPath,
     recursive: Option<bool>,
 }
 
 #[tauri::command]
 pub(crate) fn list_docs(state: State<AppState>, options: ListDocsOptions) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let docs = ctx
-        .list_docs(&options.folder_path, options.recursive.unwrap_or(false))
-        .map_err(map_err)?;
-    serde_json::to_value(&docs).map_err(map_err)
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let docs = ctx.list_docs(
+        options.folder_path.as_str(),
+        options.recursive.unwrap_or(false),
+    )?;
+    Ok(serde_json::to_value(&docs)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct CreateDocOptions {
-    folder_path: String,
+    folder_path: VaultPath,
     name: String,
     description: Option<String>,
 }
 
 #[tauri::command]
 pub(crate) fn create_doc(state: State<AppState>, options: CreateDocOptions) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let doc = ctx
-        .create_doc(&options.folder_path, &options.name, options.description.as_deref())
-        .map_err(map_err)?;
-    serde_json::to_value(&doc).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let doc = ctx.create_doc(
+        options.folder_path.as_str(),
+        &options.name,
+        options.description.as_deref(),
+    )?;
+    Ok(serde_json::to_value(&doc)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct MoveDocOptions {
-    doc_path: String,
-    dest_folder_path: String,
+    doc_path: VaultPath,
+    dest_folder_path: VaultPath,
 }
 
 #[tauri::command]
 pub(crate) fn move_doc(state: State<AppState>, options: MoveDocOptions) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let doc = ctx
-        .move_doc(&options.doc_path, &options.dest_folder_path)
-        .map_err(map_err)?;
-    serde_json::to_value(&doc).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let doc = ctx.move_doc(options.doc_path.as_str(), options.dest_folder_path.as_str())?;
+    Ok(serde_json::to_value(&doc)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct RenameDocOptions {
-    doc_path: String,
+    doc_path: VaultPath,
     new_name: String,
 }
 
 #[tauri::command]
 pub(crate) fn rename_doc(state: State<AppState>, options: RenameDocOptions) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let doc = ctx
-        .rename_doc(&options.doc_path, &options.new_name)
-        .map_err(map_err)?;
-    serde_json::to_value(&doc).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let doc = ctx.rename_doc(options.doc_path.as_str(), &options.new_name)?;
+    Ok(serde_json::to_value(&doc)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct RemoveDocOptions {
-    doc_path: String,
+    doc_path: VaultPath,
 }
 
 #[tauri::command]
 pub(crate) fn remove_doc(state: State<AppState>, options: RemoveDocOptions) -> CmdResult<bool> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    ctx.remove_doc(&options.doc_path).map_err(map_err)?;
+    let ctx = state.ctx.write().map_err(map_err)?;
+    ctx.remove_doc(options.doc_path.as_str())?;
     Ok(true)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct SetDescriptionOptions {
-    doc_path: String,
+    doc_path: VaultPath,
     description: String,
 }
 
@@ -188,22 +185,39 @@ pub(crate) fn set_doc_description(
     state: State<AppState>,
     options: SetDescriptionOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let doc = ctx
-        .set_doc_description(&options.doc_path, &options.description)
-        .map_err(map_err)?;
-    serde_json::to_value(&doc).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let doc = ctx.set_doc_description(options.doc_path.as_str(), &options.description)?;
+    Ok(serde_json::to_value(&doc)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct GetDocContentOptions {
-    path: String,
+    path: VaultPath,
+    /// Return only lines `startLine..=endLine` (1-based), e.g. the lines a
+    /// search hit cites. Either bound may be left out.
+    start_line: Option<usize>,
+    end_line: Option<usize>,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub(crate) struct DocContentResponse {
     content: String,
+    /// Line count of the whole doc, when a line range was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_lines: Option<usize>,
+}
+
+/// Lines `start..=end` (1-based) of `content`, with their line endings
+fn line_range(content: &str, start: Option<usize>, end: Option<usize>) -> String {
+    let start = start.unwrap_or(1).max(1);
+    let end = end.unwrap_or(usize::MAX);
+    content
+        .split_inclusive('\n')
+        .skip(start - 1)
+        .take(end.saturating_sub(start - 1))
+        .collect()
 }
 
 #[tauri::command]
@@ -211,33 +225,75 @@ pub(crate) fn get_doc_content(
     state: State<AppState>,
     options: GetDocContentOptions,
 ) -> CmdResult<DocContentResponse> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let content = ctx.get_doc_content(&options.path).map_err(map_err)?;
-    Ok(DocContentResponse { content })
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let content = ctx.get_doc_content(options.path.as_str())?;
+    if options.start_line.is_none() && options.end_line.is_none() {
+        state.open_docs.served(options.path.as_str(), &content);
+        return Ok(DocContentResponse {
+            content,
+            total_lines: None,
+        });
+    }
+    Ok(DocContentResponse {
+        content: line_range(&content, options.start_line, options.end_line),
+        total_lines: Some(content.lines().count()),
+    })
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct SaveDocOptions {
-    path: String,
+    path: VaultPath,
     content: String,
     description: Option<String>,
+    /// Ask for description and tag suggestions even when `AUTO_ENRICH_DOCS`
+    /// is off or the doc already has a description
+    #[serde(default)]
+    suggest: bool,
 }
 
 #[tauri::command]
 pub(crate) fn save_doc_content(
+    app: tauri::AppHandle,
     state: State<AppState>,
     options: SaveDocOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let doc = ctx
-        .save_doc_content(
-            &options.path,
-            &options.content,
-            options.description.as_deref(),
-        )
-        .map_err(map_err)?;
-    serde_json::to_value(&doc).map_err(map_err)
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let doc = ctx.save_doc_content(
+        options.path.as_str(),
+        &options.content,
+        options.description.as_deref(),
+    )?;
+    // Before the lock is released, so `doc-changed` doesn't report the
+    // editor's own save
+    state
+        .open_docs
+        .served(options.path.as_str(), &options.content);
+    queue_enrichment(&app, &ctx, options.path.as_str(), options.suggest);
+    queue_doc_index(&app, options.path.as_str());
+    Ok(serde_json::to_value(&doc)?)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OpenDocOptions {
+    path: VaultPath,
+}
+
+/// Start sending `doc-changed` when `path` is written outside the editor,
+/// with changes counted from its current content. Pair with `close_doc`.
+#[tauri::command]
+pub(crate) fn open_doc(state: State<AppState>, options: OpenDocOptions) -> CmdResult<()> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let content = ctx.get_doc_content(options.path.as_str())?;
+    state.open_docs.open(options.path.as_str(), content);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn close_doc(state: State<AppState>, options: OpenDocOptions) -> CmdResult<()> {
+    state.open_docs.close(options.path.as_str());
+    Ok(())
 }
 
 #[derive(Deserialize)]
@@ -251,17 +307,15 @@ pub(crate) fn get_doc_by_id(
     state: State<AppState>,
     options: GetDocByIdOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let doc = ctx
-        .get_doc_by_stable_id(&options.stable_id)
-        .map_err(map_err)?;
-    serde_json::to_value(&doc).map_err(map_err)
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let doc = ctx.get_doc_by_stable_id(&options.stable_id)?;
+    Ok(serde_json::to_value(&doc)?)
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct GetDocMetaOptions {
-    path: String,
+    path: VaultPath,
 }
 
 #[tauri::command]
@@ -269,9 +323,9 @@ pub(crate) fn get_doc_meta(
     state: State<AppState>,
     options: GetDocMetaOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let doc = ctx.get_doc_meta(&options.path).map_err(map_err)?;
-    serde_json::to_value(&doc).map_err(map_err)
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let doc = ctx.get_doc_meta(options.path.as_str())?;
+    Ok(serde_json::to_value(&doc)?)
 }
 
 // ===== Manifest Command =====
@@ -279,7 +333,7 @@ pub(crate) fn get_doc_meta(
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ManifestOptions {
-    folder_path: String,
+    folder_path: VaultPath,
     limit: Option<u32>,
 }
 
@@ -288,71 +342,157 @@ pub(crate) fn generate_manifest(
     state: State<AppState>,
     options: ManifestOptions,
 ) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
-    let manifest = ctx
-        .generate_manifest(&options.folder_path, options.limit.map(|v| v as usize))
-        .map_err(map_err)?;
-    serde_json::to_value(&manifest).map_err(map_err)
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let manifest = ctx.generate_manifest(
+        options.folder_path.as_str(),
+        options.limit.map(|v| v as usize),
+    )?;
+    Ok(serde_json::to_value(&manifest)?)
+}
+
+// ===== Folder Settings Commands =====
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FolderSettingsGetOptions {
+    /// "" for the contexts root
+    #[serde(default)]
+    folder_path: VaultPath,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FolderSettingsSetOptions {
+    #[serde(default)]
+    folder_path: VaultPath,
+    /// File name of a doc in the folder, to override settings for it alone
+    doc: Option<String>,
+    /// Replaces what was set; unset keys are inherited
+    settings: FolderSettings,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FolderSettingsResponse {
+    /// Keys set on the folder itself
+    settings: FolderSettings,
+    /// The folder's settings with inherited keys filled in
+    effective: FolderSettings,
+    /// Per-doc overrides, by file name
+    docs: BTreeMap<String, FolderSettings>,
+}
+
+fn folder_settings_response(
+    ctx: &OpenContext,
+    folder_path: &str,
+) -> CmdResult<FolderSettingsResponse> {
+    let file = ctx.get_folder_settings(folder_path)?;
+    Ok(FolderSettingsResponse {
+        settings: file.folder,
+        effective: ctx.resolve_folder_settings(folder_path)?,
+        docs: file.docs,
+    })
+}
+
+#[tauri::command]
+pub(crate) fn folder_settings_get(
+    state: State<AppState>,
+    options: FolderSettingsGetOptions,
+) -> CmdResult<FolderSettingsResponse> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    folder_settings_response(&ctx, options.folder_path.as_str())
+}
+
+/// Settings precedence is doc override, then the nearest folder, then the
+/// global config (e.g. `AI_PROMPT`).
+#[tauri::command]
+pub(crate) fn folder_settings_set(
+    state: State<AppState>,
+    options: FolderSettingsSetOptions,
+) -> CmdResult<FolderSettingsResponse> {
+    let ctx = state.ctx.write().map_err(map_err)?;
+    ctx.set_folder_settings(
+        options.folder_path.as_str(),
+        options.doc.as_deref(),
+        options.settings,
+    )?;
+    folder_settings_response(&ctx, options.folder_path.as_str())
 }
 
 // ===== Environment Info Command =====
 
 #[tauri::command]
 pub(crate) fn get_env_info(state: State<AppState>) -> CmdResult<serde_json::Value> {
-    let ctx = state.ctx.lock().map_err(map_err)?;
+    let ctx = state.ctx.read().map_err(map_err)?;
     let base_info = ctx.env_info();
-    let config = &state.search_config;
+    let config = state.search_config();
 
-    let masked_api_key = config.embedding.api_key.as_ref().map(|key| {
-        if key.len() > 4 {
-            format!("{}...{}", &key[..3], &key[key.len() - 4..])
-        } else {
-            "****".to_string()
-        }
-    });
+    let masked_api_key = config.embedding.api_key.as_deref().map(mask_secret);
 
     let info = serde_json::json!({
         "contexts_root": base_info.contexts_root,
         "db_path": base_info.db_path,
+        "embedding_provider": config.embedding.provider.as_str(),
+        // Whether notes stay on this machine to be embedded
+        "embedding_local": config.embedding.is_local(),
+        "local_embeddings_available": EmbeddingProvider::Local.is_available(),
         "embedding_model": config.embedding.model,
         "embedding_api_base": config.embedding.api_base,
         "api_key_masked": masked_api_key,
         "has_api_key": config.embedding.api_key.is_some() && !config.embedding.api_key.as_ref().unwrap().is_empty(),
         "config_path": SearchConfig::json_config_path().to_string_lossy(),
         "dimensions": config.embedding.dimensions,
+        // Prefix of every event this instance emits
+        "event_instance": app_events::instance_id(),
     });
 
     Ok(info)
 }
 
+/// Problems in the config files: invalid syntax, mistyped values, bad URLs.
+/// Each one names the default that was used instead.
+#[tauri::command]
+pub(crate) fn validate_config() -> CmdResult<Vec<ConfigIssue>> {
+    Ok(SearchConfig::validate())
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct SaveConfigOptions {
+    /// `openai`, `ollama` or `local`
+    provider: Option<String>,
     api_key: Option<String>,
     api_base: Option<String>,
     model: Option<String>,
 }
 
 #[tauri::command]
-pub(crate) fn save_config(options: SaveConfigOptions) -> CmdResult<serde_json::Value> {
-    use std::collections::HashMap;
-
+pub(crate) async fn save_config(
+    app: tauri::AppHandle,
+    options: SaveConfigOptions,
+) -> CmdResult<serde_json::Value> {
     let config_path = SearchConfig::json_config_path();
-
-    let mut config: HashMap<String, serde_json::Value> = if config_path.exists() {
-        let content = std::fs::read_to_string(&config_path).map_err(map_err)?;
-        serde_json::from_str(&content).unwrap_or_default()
-    } else {
-        HashMap::new()
-    };
-
+    let mut config = read_config_for_update()?;
+
+    if let Some(provider) = options.provider {
+        let provider = EmbeddingProvider::parse(&provider).ok_or_else(|| {
+            CommandError::localized(
+                ErrorCode::InvalidInput,
+                "error.unknown_embedding_provider",
+                &[("provider", &provider)],
+            )
+        })?;
+        config.insert(
+            "EMBEDDING_PROVIDER".to_string(),
+            serde_json::Value::String(provider.as_str().to_string()),
+        );
+    }
     if let Some(key) = options.api_key {
         if !key.is_empty() {
             config.insert(
                 "EMBEDDING_API_KEY".to_string(),
                 serde_json::Value::String(key),
             );
-            config.remove("OPENAI_API_KEY");
         }
     }
     if let Some(base) = options.api_base {
@@ -360,7 +500,6 @@ pub(crate) fn save_config(options: SaveConfigOptions) -> CmdResult<serde_json::V
             "EMBEDDING_API_BASE".to_string(),
             serde_json::Value::String(base),
         );
-        config.remove("OPENAI_BASE_URL");
     }
     if let Some(model) = options.model {
         config.insert(
@@ -370,11 +509,12 @@ pub(crate) fn save_config(options: SaveConfigOptions) -> CmdResult<serde_json::V
     }
 
     if let Some(parent) = config_path.parent() {
-        std::fs::create_dir_all(parent).map_err(map_err)?;
+        std::fs::create_dir_all(parent)?;
     }
 
-    let content = serde_json::to_string_pretty(&config).map_err(map_err)?;
-    std::fs::write(&config_path, content).map_err(map_err)?;
+    let content = serde_json::to_string_pretty(&config)?;
+    std::fs::write(&config_path, content)?;
+    reload_search_config(&app).await?;
 
     Ok(serde_json::json!({
         "success": true,
@@ -0,0 +1,403 @@
+use crate::utils::{map_err, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use opencontext_core::VaultPath;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, DiffOp, TextDiff};
+use tauri::State;
+
+/// Bytes of each side that get diffed. Anything past the cap is left out
+/// and the result is flagged `truncated`.
+const MAX_DIFF_BYTES: usize = 512 * 1024;
+const DEFAULT_CONTEXT_LINES: usize = 3;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DiffDocContentOptions {
+    old_content: Option<String>,
+    new_content: Option<String>,
+    /// Doc whose saved content is the new side when `new_content` is absent
+    path: Option<VaultPath>,
+    /// File in the vault holding the old side when `old_content` is
+    /// absent. Defaults to `<path>.bak`.
+    snapshot: Option<VaultPath>,
+    context_lines: Option<usize>,
+    /// Split changed lines into changed and unchanged words
+    #[serde(default)]
+    word_diff: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocDiff {
+    hunks: Vec<DiffHunk>,
+    pub(crate) added: usize,
+    pub(crate) removed: usize,
+    /// A side was over the size cap and only its start was diffed
+    pub(crate) truncated: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DiffHunk {
+    /// 1-based first line and line count of each side, as in a unified
+    /// diff header
+    old_start: usize,
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+    lines: Vec<DiffLine>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DiffLine {
+    kind: DiffLineKind,
+    /// 1-based line numbers, absent on the side the line is not in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_line: Option<usize>,
+    text: String,
+    /// Pieces of an added or removed line, with `wordDiff`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    segments: Option<Vec<DiffSegment>>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct DiffSegment {
+    changed: bool,
+    text: String,
+}
+
+fn line_kind(tag: ChangeTag) -> DiffLineKind {
+    match tag {
+        ChangeTag::Equal => DiffLineKind::Context,
+        ChangeTag::Insert => DiffLineKind::Added,
+        ChangeTag::Delete => DiffLineKind::Removed,
+    }
+}
+
+fn strip_newline(text: &str) -> &str {
+    text.trim_end_matches(['\n', '\r'])
+}
+
+/// `text` cut to at most `MAX_DIFF_BYTES`, at a line end when there is one
+fn cap(text: &str) -> (&str, bool) {
+    if text.len() <= MAX_DIFF_BYTES {
+        return (text, false);
+    }
+    let end = match text.as_bytes()[..MAX_DIFF_BYTES]
+        .iter()
+        .rposition(|&b| b == b'\n')
+    {
+        Some(newline) => newline + 1,
+        None => (0..=MAX_DIFF_BYTES)
+            .rev()
+            .find(|&i| text.is_char_boundary(i))
+            .unwrap_or(0),
+    };
+    (&text[..end], true)
+}
+
+/// First line (1-based) and line count covered by a hunk's ops on one side
+fn hunk_range(
+    group: &[DiffOp],
+    range: impl Fn(&DiffOp) -> std::ops::Range<usize>,
+) -> (usize, usize) {
+    let start = group.first().map(|op| range(op).start).unwrap_or(0);
+    let end = group.last().map(|op| range(op).end).unwrap_or(start);
+    (start + 1, end - start)
+}
+
+/// Line diff of `old` against `new`, grouped into hunks with
+/// `context_lines` unchanged lines around each change
+pub(crate) fn diff_texts(old: &str, new: &str, context_lines: usize, word_diff: bool) -> DocDiff {
+    let (old, old_truncated) = cap(old);
+    let (new, new_truncated) = cap(new);
+    let diff = TextDiff::from_lines(old, new);
+
+    let mut added = 0;
+    let mut removed = 0;
+    let mut hunks = Vec::new();
+    for group in diff.grouped_ops(context_lines) {
+        let mut lines = Vec::new();
+        for op in &group {
+            if word_diff {
+                for change in diff.iter_inline_changes(op) {
+                    let kind = line_kind(change.tag());
+                    let mut segments: Vec<DiffSegment> = change
+                        .iter_strings_lossy()
+                        .map(|(changed, text)| DiffSegment {
+                            changed,
+                            text: text.into_owned(),
+                        })
+                        .collect();
+                    if let Some(last) = segments.last_mut() {
+                        last.text = strip_newline(&last.text).to_string();
+                    }
+                    segments.retain(|segment| !segment.text.is_empty());
+                    let text = segments.iter().map(|s| s.text.as_str()).collect();
+                    lines.push(DiffLine {
+                        kind,
+                        old_line: change.old_index().map(|i| i + 1),
+                        new_line: change.new_index().map(|i| i + 1),
+                        text,
+                        segments: (kind != DiffLineKind::Context).then_some(segments),
+                    });
+                }
+            } else {
+                for change in diff.iter_changes(op) {
+                    lines.push(DiffLine {
+                        kind: line_kind(change.tag()),
+                        old_line: change.old_index().map(|i| i + 1),
+                        new_line: change.new_index().map(|i| i + 1),
+                        text: strip_newline(change.value()).to_string(),
+                        segments: None,
+                    });
+                }
+            }
+        }
+        added += lines
+            .iter()
+            .filter(|l| l.kind == DiffLineKind::Added)
+            .count();
+        removed += lines
+            .iter()
+            .filter(|l| l.kind == DiffLineKind::Removed)
+            .count();
+
+        let (old_start, old_lines) = hunk_range(&group, DiffOp::old_range);
+        let (new_start, new_lines) = hunk_range(&group, DiffOp::new_range);
+        hunks.push(DiffHunk {
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+            lines,
+        });
+    }
+
+    DocDiff {
+        hunks,
+        added,
+        removed,
+        truncated: old_truncated || new_truncated,
+    }
+}
+
+/// `diff` as unified diff text: a `@@` header per hunk, then its lines
+/// marked with ` `, `+` or `-`
+pub(crate) fn unified_text(diff: &DocDiff) -> String {
+    let mut text = String::new();
+    for hunk in &diff.hunks {
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+        ));
+        for line in &hunk.lines {
+            text.push(match line.kind {
+                DiffLineKind::Context => ' ',
+                DiffLineKind::Added => '+',
+                DiffLineKind::Removed => '-',
+            });
+            text.push_str(&line.text);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Where a change sits on each side, as in a unified diff header
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ChangedRange {
+    old_start: usize,
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+}
+
+/// Line counts and ranges of the changes between two texts, without the
+/// lines themselves
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ChangeSummary {
+    pub(crate) ranges: Vec<ChangedRange>,
+    pub(crate) added: usize,
+    pub(crate) removed: usize,
+    pub(crate) truncated: bool,
+}
+
+/// Summary of the changes from `old` to `new`, one range per run of
+/// changed lines
+pub(crate) fn summarize_changes(old: &str, new: &str) -> ChangeSummary {
+    let diff = diff_texts(old, new, 0, false);
+    ChangeSummary {
+        ranges: diff
+            .hunks
+            .iter()
+            .map(|hunk| ChangedRange {
+                old_start: hunk.old_start,
+                old_lines: hunk.old_lines,
+                new_start: hunk.new_start,
+                new_lines: hunk.new_lines,
+            })
+            .collect(),
+        added: diff.added,
+        removed: diff.removed,
+        truncated: diff.truncated,
+    }
+}
+
+/// Diff two versions of a doc. Each side is given as text, or read from
+/// the vault: the new side from the doc at `path`, the old side from
+/// `snapshot` (default `<path>.bak`).
+#[tauri::command]
+pub(crate) fn diff_doc_content(
+    state: State<AppState>,
+    options: DiffDocContentOptions,
+) -> CmdResult<DocDiff> {
+    let missing = |what: &str| {
+        CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.diff_side_required",
+            &[("field", what)],
+        )
+    };
+    let ctx = state.ctx.read().map_err(map_err)?;
+
+    let new = match options.new_content {
+        Some(content) => content,
+        None => {
+            let path = options.path.as_ref().ok_or_else(|| missing("newContent"))?;
+            ctx.get_doc_content(path.as_str())?
+        }
+    };
+    let old = match options.old_content {
+        Some(content) => content,
+        None => {
+            let snapshot = match (options.snapshot, &options.path) {
+                (Some(snapshot), _) => snapshot,
+                (None, Some(path)) => VaultPath::parse(&format!("{}.bak", path))?,
+                (None, None) => return Err(missing("oldContent, snapshot")),
+            };
+            let abs_path = snapshot.to_abs(&ctx.env_info().contexts_root);
+            std::fs::read_to_string(&abs_path).map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    CommandError::localized(
+                        ErrorCode::NotFound,
+                        "error.snapshot_not_found",
+                        &[("snapshot", snapshot.as_str())],
+                    )
+                } else {
+                    e.into()
+                }
+            })?
+        }
+    };
+    drop(ctx);
+
+    Ok(diff_texts(
+        &old,
+        &new,
+        options.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES),
+        options.word_diff,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_texts_numbers_lines_and_groups_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let diff = diff_texts(old, new, 1, false);
+
+        assert_eq!((diff.added, diff.removed, diff.truncated), (2, 1, false));
+        assert_eq!(diff.hunks.len(), 2);
+        let first = &diff.hunks[0];
+        assert_eq!(
+            (
+                first.old_start,
+                first.old_lines,
+                first.new_start,
+                first.new_lines
+            ),
+            (1, 3, 1, 3)
+        );
+        let removed = &first.lines[1];
+        assert_eq!(removed.kind, DiffLineKind::Removed);
+        assert_eq!((removed.old_line, removed.new_line), (Some(2), None));
+        assert_eq!(removed.text, "b");
+        let appended = diff.hunks[1].lines.last().unwrap();
+        assert_eq!(appended.kind, DiffLineKind::Added);
+        assert_eq!((appended.new_line, appended.text.as_str()), (Some(10), "j"));
+    }
+
+    #[test]
+    fn diff_texts_splits_changed_words() {
+        let diff = diff_texts("the quick fox\n", "the slow fox\n", 3, true);
+        let removed = &diff.hunks[0].lines[0];
+        assert_eq!(removed.text, "the quick fox");
+        let changed: Vec<&str> = removed
+            .segments
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|s| s.changed)
+            .map(|s| s.text.as_str())
+            .collect();
+        assert_eq!(changed, ["quick"]);
+    }
+
+    #[test]
+    fn summarize_changes_gives_one_range_per_change() {
+        let summary = summarize_changes("a\nb\nc\nd\n", "a\nB\nc\nd\ne\nf\n");
+        assert_eq!((summary.added, summary.removed), (3, 1));
+        assert_eq!(
+            summary.ranges,
+            [
+                ChangedRange {
+                    old_start: 2,
+                    old_lines: 1,
+                    new_start: 2,
+                    new_lines: 1,
+                },
+                ChangedRange {
+                    old_start: 5,
+                    old_lines: 0,
+                    new_start: 5,
+                    new_lines: 2,
+                },
+            ]
+        );
+        assert!(summarize_changes("same\n", "same\n").ranges.is_empty());
+    }
+
+    #[test]
+    fn unified_text_marks_each_line() {
+        let diff = diff_texts("a\nb\nc\n", "a\nB\nc\n", 1, false);
+        assert_eq!(unified_text(&diff), "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+    }
+
+    #[test]
+    fn cap_cuts_at_a_line_end() {
+        let line = "x".repeat(1023) + "\n";
+        let text = line.repeat(MAX_DIFF_BYTES / 1024 + 1);
+        let (capped, truncated) = cap(&text);
+        assert!(truncated);
+        assert_eq!(capped.len(), MAX_DIFF_BYTES);
+        assert!(capped.ends_with('\n'));
+        assert_eq!(cap("short"), ("short", false));
+    }
+}
@@ -0,0 +1,491 @@
+use crate::chat::ChatMessage;
+use crate::commands::ai::{ai_configured, complete};
+use crate::commands::diff::{diff_texts, unified_text};
+use crate::commands::snapshot::snapshots_dir;
+use crate::tasks::{TaskHandle, TaskKind};
+use crate::utils::{map_err, read_config_json, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use opencontext_core::{snapshot_text_as_of, Doc, OpenContext};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tauri::Manager;
+
+/// Folder digests are written to; docs in it are never part of a digest
+pub(crate) const DIGEST_FOLDER: &str = "digests";
+/// config.json key: days a digest looks back
+const DAYS_KEY: &str = "DIGEST_DAYS";
+pub(crate) const DEFAULT_DAYS: u64 = 7;
+/// Tokens of changes sent to the model, unless the options say otherwise
+const DEFAULT_TOKEN_BUDGET: usize = 6_000;
+/// Rough chars per token, for budgeting
+const CHARS_PER_TOKEN: usize = 4;
+/// Least of the budget a doc gets. Once the budget can't give a doc this
+/// much, it and the docs after it are listed without their changes.
+const MIN_DOC_CHARS: usize = 600;
+/// Docs a digest covers at most, most recently modified first
+const MAX_DIGEST_DOCS: usize = 200;
+const DIGEST_TIMEOUT: Duration = Duration::from_secs(120);
+const DIGEST_PROMPT: &str = "You write a digest of what changed in a notes vault over a period. For each folder below, summarize the changes to its documents in a few short bullet points, naming the documents. Diffs mark added lines with + and removed lines with -; documents without a diff are given by their beginning. Go only by what is given, and leave out folders where nothing meaningful changed. Reply in Markdown with one `### <folder>` heading per folder and nothing else, in the language most of the documents are in.";
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GenerateDigestOptions {
+    /// Days to look back (default `DIGEST_DAYS`, else 7)
+    days: Option<u64>,
+    /// Tokens of diffs and excerpts the model is sent at most
+    token_budget: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DigestResult {
+    /// The digest doc
+    pub(crate) path: String,
+    /// Docs modified in the period
+    doc_count: usize,
+    /// Whether the model summarized the changes; otherwise the digest only
+    /// lists them
+    summarized: bool,
+    /// Why there is no summary, when one was wanted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+}
+
+/// How a doc changed in the period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    /// Created in the period
+    Created,
+    /// Lines changed since the last snapshot before the period
+    Edited { added: usize, removed: usize },
+    /// Modified, with no earlier version to compare against
+    Modified,
+    /// Private, encrypted or not text, so none of it goes to the model
+    Withheld,
+}
+
+struct ChangedDoc {
+    doc: Doc,
+    change: Change,
+    /// Diff or excerpt the model is shown; empty if there is none or the
+    /// budget ran out
+    material: String,
+}
+
+/// config.json `DIGEST_DAYS`, as a number or a numeric string
+fn configured_days() -> u64 {
+    read_config_json()
+        .and_then(|config| {
+            let value = config.get(DAYS_KEY)?.clone();
+            value
+                .as_u64()
+                .or_else(|| value.as_str()?.trim().parse().ok())
+        })
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_DAYS)
+}
+
+/// Name of the digest doc written on `date`
+pub(crate) fn digest_name(date: NaiveDate) -> String {
+    format!("{}.md", date.format("%Y-%m-%d"))
+}
+
+/// Marks where `cut` cut a text
+const CUT_MARK: &str = "\n…";
+
+/// `text` cut to at most `max_chars` chars, marked where it was cut
+fn cut(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let keep = max_chars.saturating_sub(CUT_MARK.chars().count());
+    let end = text.char_indices().nth(keep).map_or(text.len(), |(i, _)| i);
+    format!("{}{}", &text[..end], CUT_MARK)
+}
+
+/// Fit the non-empty `materials` into `budget` chars between them, in
+/// order: each gets an even share of what is left, so room a short one
+/// doesn't use goes to the rest, but never less than `MIN_DOC_CHARS`.
+/// Those the budget can't cover are emptied.
+fn fit_to_budget(materials: &mut [String], budget: usize) {
+    let mut remaining = materials.iter().filter(|m| !m.is_empty()).count();
+    let mut left = budget;
+    for material in materials.iter_mut().filter(|m| !m.is_empty()) {
+        let share = (left / remaining).max(MIN_DOC_CHARS);
+        remaining -= 1;
+        if share > left {
+            material.clear();
+            continue;
+        }
+        *material = cut(material, share);
+        left -= material.chars().count().min(left);
+    }
+}
+
+/// How `doc` changed since `since`, and what of it to show the model
+fn describe_change(
+    ctx: &OpenContext,
+    snapshots: &std::path::Path,
+    doc: &Doc,
+    since: DateTime<Utc>,
+) -> (Change, String) {
+    let withheld = !doc.kind.is_text()
+        || ctx
+            .resolve_doc_settings(&doc.rel_path)
+            .map_or(true, |settings| {
+                settings.is_private() || settings.is_encrypted()
+            });
+    if withheld {
+        return (Change::Withheld, String::new());
+    }
+    let Ok(content) = ctx.get_doc_content(&doc.rel_path) else {
+        return (Change::Withheld, String::new());
+    };
+    let created = DateTime::parse_from_rfc3339(&doc.created_at)
+        .is_ok_and(|created| created.with_timezone(&Utc) >= since);
+    if created {
+        return (Change::Created, content);
+    }
+    match snapshot_text_as_of(snapshots, &doc.rel_path, since) {
+        Ok(Some(before)) => {
+            let diff = diff_texts(&before, &content, 1, false);
+            let change = Change::Edited {
+                added: diff.added,
+                removed: diff.removed,
+            };
+            (change, unified_text(&diff))
+        }
+        Ok(None) => (Change::Modified, content),
+        Err(e) => {
+            log::warn!("[Digest] Reading snapshots failed: {}", e);
+            (Change::Modified, content)
+        }
+    }
+}
+
+/// Docs modified since `since`, outside the digest folder, with their
+/// changes; reports progress on `task` and stops if it is cancelled
+async fn gather(
+    state: &AppState,
+    task: &TaskHandle,
+    since: DateTime<Utc>,
+) -> CmdResult<Vec<ChangedDoc>> {
+    let ctx = state.ctx.read().map_err(map_err)?.clone();
+    let since_rfc3339 = since.to_rfc3339();
+    let digest_prefix = format!("{}/", DIGEST_FOLDER);
+    let docs: Vec<Doc> = ctx
+        .list_recent_docs(Some(&since_rfc3339), None)?
+        .into_iter()
+        .filter(|doc| !doc.rel_path.starts_with(&digest_prefix))
+        .take(MAX_DIGEST_DOCS)
+        .collect();
+
+    let snapshots = snapshots_dir(&ctx);
+    let total = docs.len();
+    let mut changed = Vec::with_capacity(total);
+    for (index, doc) in docs.into_iter().enumerate() {
+        if task.token().is_cancelled() {
+            return Err(CommandError::localized(
+                ErrorCode::Cancelled,
+                "error.task_cancelled",
+                &[],
+            ));
+        }
+        task.progress(index, total, Some(doc.rel_path.clone()));
+        let (change, material) = describe_change(&ctx, &snapshots, &doc, since);
+        changed.push(ChangedDoc {
+            doc,
+            change,
+            material,
+        });
+        tokio::task::yield_now().await;
+    }
+    Ok(changed)
+}
+
+/// Folder of a doc, as digests name it
+fn folder_of(doc: &Doc) -> &str {
+    match doc.rel_path.rsplit_once('/') {
+        Some((folder, _)) => folder,
+        None => "(root)",
+    }
+}
+
+/// Docs grouped by folder, folders in name order, docs most recent first
+fn by_folder(docs: &[ChangedDoc]) -> BTreeMap<&str, Vec<&ChangedDoc>> {
+    let mut folders: BTreeMap<&str, Vec<&ChangedDoc>> = BTreeMap::new();
+    for doc in docs {
+        folders.entry(folder_of(&doc.doc)).or_default().push(doc);
+    }
+    folders
+}
+
+fn change_label(change: Change) -> String {
+    match change {
+        Change::Created => "new".to_string(),
+        Change::Edited { added, removed } => format!("edited, +{} −{} lines", added, removed),
+        Change::Modified => "modified".to_string(),
+        Change::Withheld => "modified, contents not shared".to_string(),
+    }
+}
+
+/// The user message asking for a summary of `docs`
+fn prompt_input(docs: &[ChangedDoc], since: NaiveDate, until: NaiveDate) -> String {
+    let mut input = format!("Changes from {} to {}.\n", since, until);
+    for (folder, docs) in by_folder(docs) {
+        input.push_str(&format!("\n# Folder: {}\n", folder));
+        for changed in docs {
+            input.push_str(&format!(
+                "\n## {} ({})\n",
+                changed.doc.rel_path,
+                change_label(changed.change)
+            ));
+            if changed.material.is_empty() {
+                continue;
+            }
+            let fence = if matches!(changed.change, Change::Edited { .. }) {
+                "```diff"
+            } else {
+                "```"
+            };
+            input.push_str(&format!(
+                "{}\n{}\n```\n",
+                fence,
+                changed.material.trim_end()
+            ));
+        }
+    }
+    input
+}
+
+/// `text` percent-encoded for a URL query value
+fn encode_query_value(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Markdown link to `doc`, the way the editor links docs
+fn doc_link(doc: &Doc) -> String {
+    let name = doc.name.trim_end_matches(".md");
+    let text = name.replace('[', "\\[").replace(']', "\\]");
+    format!(
+        "[{}](oc://doc/{}?path={})",
+        text,
+        doc.stable_id,
+        encode_query_value(&doc.rel_path)
+    )
+}
+
+/// The digest doc: the model's summary when there is one, then every doc
+/// that changed, linked, by folder
+fn render_digest(
+    docs: &[ChangedDoc],
+    since: NaiveDate,
+    until: NaiveDate,
+    summary: Option<&str>,
+    warning: Option<&str>,
+) -> String {
+    let mut text = format!("# Digest {} – {}\n\n", since, until);
+    text.push_str(&match docs.len() {
+        0 => "No docs changed in this period.\n".to_string(),
+        1 => "1 doc changed in this period.\n".to_string(),
+        n => format!("{} docs changed in this period.\n", n),
+    });
+    if let Some(summary) = summary {
+        text.push_str(&format!("\n## Summary\n\n{}\n", summary.trim()));
+    } else if let Some(warning) = warning {
+        text.push_str(&format!("\n_No summary: {}_\n", warning));
+    }
+    if docs.is_empty() {
+        return text;
+    }
+    text.push_str("\n## Changed docs\n");
+    for (folder, docs) in by_folder(docs) {
+        text.push_str(&format!("\n### {}\n\n", folder));
+        for changed in docs {
+            let modified = DateTime::parse_from_rfc3339(&changed.doc.updated_at)
+                .map(|at| at.with_timezone(&Local).format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            text.push_str(&format!(
+                "- {} — {}, {}\n",
+                doc_link(&changed.doc),
+                change_label(changed.change),
+                modified
+            ));
+        }
+    }
+    text
+}
+
+/// Write `content` as a new doc in the digest folder, named for `date`
+fn write_digest(ctx: &OpenContext, date: NaiveDate, content: &str) -> CmdResult<String> {
+    if ctx.list_docs(DIGEST_FOLDER, false).is_err() {
+        ctx.create_folder(DIGEST_FOLDER, Some("Digests of recent changes"))?;
+    }
+    let mut name = digest_name(date);
+    let mut copy = 1;
+    while ctx
+        .get_doc_meta(&format!("{}/{}", DIGEST_FOLDER, name))
+        .is_ok()
+    {
+        copy += 1;
+        name = format!("{}-{}.md", date.format("%Y-%m-%d"), copy);
+    }
+    let description = format!("What changed up to {}", date);
+    let created = ctx.create_doc(DIGEST_FOLDER, &name, Some(&description))?;
+    ctx.save_doc_content(&created.rel_path, content, None)?;
+    Ok(created.rel_path)
+}
+
+/// Ask the model to summarize `docs`, inside `task` so it can be cancelled
+async fn summarize(
+    task: &TaskHandle,
+    docs: &[ChangedDoc],
+    since: NaiveDate,
+    until: NaiveDate,
+) -> CmdResult<String> {
+    let messages = [
+        ChatMessage {
+            role: "system".to_string(),
+            content: serde_json::Value::String(DIGEST_PROMPT.to_string()),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: serde_json::Value::String(prompt_input(docs, since, until)),
+        },
+    ];
+    let summary = task
+        .run(complete(&messages, DIGEST_TIMEOUT))
+        .await?
+        .trim()
+        .to_string();
+    if summary.is_empty() {
+        return Err(CommandError::localized(
+            ErrorCode::Network,
+            "error.empty_summary",
+            &[],
+        ));
+    }
+    Ok(summary)
+}
+
+/// Write a digest of the docs modified in the last `days`, as a
+/// cancellable task. Used by the command and the digest schedule.
+pub(crate) async fn generate(
+    app: &tauri::AppHandle,
+    options: GenerateDigestOptions,
+) -> CmdResult<DigestResult> {
+    let state = app.state::<AppState>();
+    let days = options
+        .days
+        .filter(|d| *d > 0)
+        .unwrap_or_else(configured_days);
+    let budget = options
+        .token_budget
+        .unwrap_or(DEFAULT_TOKEN_BUDGET)
+        .saturating_mul(CHARS_PER_TOKEN);
+    let now = Utc::now();
+    let since = now - chrono::Duration::days(days as i64);
+    let until_date = now.with_timezone(&Local).date_naive();
+    let since_date = since.with_timezone(&Local).date_naive();
+
+    let task = state.tasks.start(app, TaskKind::Digest)?;
+    let result: CmdResult<DigestResult> = async {
+        let mut docs = gather(&state, &task, since).await?;
+        let mut materials: Vec<String> = docs
+            .iter_mut()
+            .map(|doc| std::mem::take(&mut doc.material))
+            .collect();
+        fit_to_budget(&mut materials, budget);
+        for (doc, material) in docs.iter_mut().zip(materials) {
+            doc.material = material;
+        }
+
+        let has_material = docs.iter().any(|doc| !doc.material.is_empty());
+        let (summary, warning) = if !has_material {
+            (None, None)
+        } else if !ai_configured() {
+            (None, Some("no AI provider is configured".to_string()))
+        } else {
+            task.progress(docs.len(), docs.len(), Some("Summarizing".to_string()));
+            match summarize(&task, &docs, since_date, until_date).await {
+                Ok(summary) => (Some(summary), None),
+                Err(e) if e.code == ErrorCode::Cancelled => return Err(e),
+                Err(e) => {
+                    log::warn!("[Digest] Summary failed, listing changes instead: {}", e);
+                    (None, Some(e.message))
+                }
+            }
+        };
+
+        let content = render_digest(
+            &docs,
+            since_date,
+            until_date,
+            summary.as_deref(),
+            warning.as_deref(),
+        );
+        let path = {
+            let ctx = state.ctx.write().map_err(map_err)?;
+            write_digest(&ctx, until_date, &content)?
+        };
+        Ok(DigestResult {
+            path,
+            doc_count: docs.len(),
+            summarized: summary.is_some(),
+            warning,
+        })
+    }
+    .await;
+    task.finish(&result);
+    result
+}
+
+/// Summarize the docs modified in the last few days into a new doc in
+/// `digests/`, with the configured AI provider. Lists the changes instead
+/// when the provider is missing or fails. Cancellable with `task_cancel`.
+#[tauri::command]
+pub(crate) async fn generate_digest(
+    app: tauri::AppHandle,
+    options: Option<GenerateDigestOptions>,
+) -> CmdResult<DigestResult> {
+    generate(&app, options.unwrap_or_default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_to_budget_shares_evenly_and_drops_what_does_not_fit() {
+        let mut materials = vec![
+            "a".repeat(100),
+            String::new(),
+            "b".repeat(5_000),
+            "c".repeat(5_000),
+            "d".repeat(5_000),
+        ];
+        fit_to_budget(&mut materials, 1_500);
+        let lengths: Vec<usize> = materials.iter().map(|m| m.chars().count()).collect();
+        // An even share would be 375, under the minimum of 600, so the last
+        // doc gets nothing
+        assert_eq!(lengths, [100, 0, 600, 600, 0]);
+        assert!(materials[2].ends_with(CUT_MARK));
+    }
+
+    #[test]
+    fn encode_query_value_escapes_reserved_and_non_ascii() {
+        assert_eq!(
+            encode_query_value("notes/Q3 plan&计划.md"),
+            "notes%2FQ3%20plan%26%E8%AE%A1%E5%88%92.md"
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use crate::utils::{get_config_value, map_err, CmdResult, CommandError};
+use crate::AppState;
+use opencontext_core::search::SearchConfig;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DoctorCheck {
+    id: &'static str,
+    label: &'static str,
+    status: CheckStatus,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DoctorReport {
+    status: CheckStatus,
+    checks: Vec<DoctorCheck>,
+}
+
+struct CliSpec {
+    id: &'static str,
+    label: &'static str,
+    binary: &'static str,
+    /// Missing binaries fail the check instead of warning.
+    required: bool,
+    install_hint: &'static str,
+}
+
+const CLI_SPECS: &[CliSpec] = &[
+    CliSpec {
+        id: "codex",
+        label: "Codex CLI",
+        binary: "codex",
+        required: false,
+        install_hint: "Install with `npm install -g @openai/codex` to use the Codex agent.",
+    },
+    CliSpec {
+        id: "claude",
+        label: "Claude Code CLI",
+        binary: "claude",
+        required: false,
+        install_hint:
+            "Install with `npm install -g @anthropic-ai/claude-code` and run `claude login`.",
+    },
+    CliSpec {
+        id: "npx",
+        label: "npx (Node.js)",
+        binary: "npx",
+        required: false,
+        install_hint: "Install Node.js (https://nodejs.org) to run the Claude ACP adapter.",
+    },
+    CliSpec {
+        id: "opencode",
+        label: "OpenCode CLI",
+        binary: "opencode",
+        required: false,
+        install_hint: "Install with `npm install -g opencode-ai` to use the OpenCode agent.",
+    },
+    CliSpec {
+        id: "oc",
+        label: "OpenContext CLI (oc)",
+        binary: "oc",
+        required: false,
+        install_hint:
+            "Install with `npm install -g @aicontextlab/cli` so agents can query your contexts.",
+    },
+    CliSpec {
+        id: "git",
+        label: "Git",
+        binary: "git",
+        required: false,
+        install_hint: "Install Git (https://git-scm.com) for agents that work on repositories.",
+    },
+];
+
+fn executable_names(binary: &str) -> Vec<String> {
+    if cfg!(windows) {
+        ["exe", "cmd", "bat"]
+            .iter()
+            .map(|ext| format!("{}.{}", binary, ext))
+            .collect()
+    } else {
+        vec![binary.to_string()]
+    }
+}
+
+/// Install locations that are usually missing from the PATH a GUI app
+/// inherits (launchd on macOS, desktop sessions on Linux).
+fn common_install_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+    if let Some(home) = home.as_ref() {
+        for rel in [
+            ".local/bin",
+            ".cargo/bin",
+            ".npm-global/bin",
+            ".bun/bin",
+            ".volta/bin",
+            ".opencode/bin",
+            "AppData/Roaming/npm",
+        ] {
+            dirs.push(home.join(rel));
+        }
+        // Node version managers install global CLIs under the active version.
+        let nvm_versions = home.join(".nvm/versions/node");
+        if let Ok(entries) = std::fs::read_dir(nvm_versions) {
+            for entry in entries.flatten() {
+                dirs.push(entry.path().join("bin"));
+            }
+        }
+    }
+    for dir in [
+        "/opt/homebrew/bin",
+        "/usr/local/bin",
+        "/usr/bin",
+        "/snap/bin",
+    ] {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+fn find_in_dirs<I: IntoIterator<Item = PathBuf>>(binary: &str, dirs: I) -> Option<PathBuf> {
+    let names = executable_names(binary);
+    dirs.into_iter().find_map(|dir| {
+        names
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+fn find_on_path(binary: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    find_in_dirs(binary, std::env::split_paths(&path))
+}
+
+/// Run `<path> --version` and return the first non-empty output line.
+fn probe_version(path: &Path) -> Option<String> {
+    let mut child = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if started.elapsed() < VERSION_TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+    let output = child.wait_with_output().ok()?;
+    let text = if output.stdout.is_empty() {
+        String::from_utf8_lossy(&output.stderr).to_string()
+    } else {
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string())
+}
+
+fn check_cli(spec: &CliSpec) -> DoctorCheck {
+    let missing_status = if spec.required {
+        CheckStatus::Fail
+    } else {
+        CheckStatus::Warn
+    };
+
+    if let Some(path) = find_on_path(spec.binary) {
+        let version = probe_version(&path);
+        let (status, detail, hint) = match version.as_ref() {
+            Some(version) => (CheckStatus::Pass, format!("Found {}", version), None),
+            None => (
+                CheckStatus::Warn,
+                format!("Found at {} but `--version` failed", path.display()),
+                Some(format!(
+                    "Run `{} --version` in a terminal to check the installation.",
+                    spec.binary
+                )),
+            ),
+        };
+        return DoctorCheck {
+            id: spec.id,
+            label: spec.label,
+            status,
+            detail,
+            path: Some(path.to_string_lossy().to_string()),
+            version,
+            hint,
+        };
+    }
+
+    if let Some(path) = find_in_dirs(spec.binary, common_install_dirs()) {
+        let dir = path
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        return DoctorCheck {
+            id: spec.id,
+            label: spec.label,
+            status: missing_status,
+            detail: format!("Installed at {} but not on the app's PATH", path.display()),
+            version: probe_version(&path),
+            path: Some(path.to_string_lossy().to_string()),
+            hint: Some(format!(
+                "Apps launched from the desktop do not see your shell PATH. Add {} to the system PATH (e.g. `launchctl config user path` on macOS) or symlink `{}` into /usr/local/bin, then restart OpenContext.",
+                dir, spec.binary
+            )),
+        };
+    }
+
+    DoctorCheck {
+        id: spec.id,
+        label: spec.label,
+        status: missing_status,
+        detail: format!("`{}` not found", spec.binary),
+        path: None,
+        version: None,
+        hint: Some(spec.install_hint.to_string()),
+    }
+}
+
+/// CLIs that run as agents, as opposed to their helpers
+const AGENT_CLI_IDS: &[&str] = &["codex", "claude", "opencode"];
+
+/// Ids of the agent CLIs whose doctor check passes. Probes versions, so
+/// call it off the async runtime.
+pub(crate) fn detected_agent_clis() -> Vec<&'static str> {
+    CLI_SPECS
+        .iter()
+        .filter(|spec| AGENT_CLI_IDS.contains(&spec.id))
+        .map(check_cli)
+        .filter(|check| check.status == CheckStatus::Pass)
+        .map(|check| check.id)
+        .collect()
+}
+
+fn check_embedding_config() -> DoctorCheck {
+    let config = SearchConfig::load().unwrap_or_default();
+    match config.embedding.get_api_key() {
+        Ok(_) => DoctorCheck {
+            id: "embedding_config",
+            label: "Embedding config",
+            status: CheckStatus::Pass,
+            detail: format!(
+                "{} via {}",
+                config.embedding.model, config.embedding.api_base
+            ),
+            path: None,
+            version: None,
+            hint: None,
+        },
+        Err(e) => DoctorCheck {
+            id: "embedding_config",
+            label: "Embedding config",
+            status: CheckStatus::Fail,
+            detail: e.to_string(),
+            path: None,
+            version: None,
+            hint: Some(
+                "Set an embedding API key in Settings to enable semantic search.".to_string(),
+            ),
+        },
+    }
+}
+
+fn check_ai_config() -> DoctorCheck {
+    let has_key = get_config_value("AI_API_KEY").is_some_and(|key| !key.trim().is_empty());
+    let model = get_config_value("AI_MODEL").unwrap_or_else(|| "gpt-4o".to_string());
+    DoctorCheck {
+        id: "ai_config",
+        label: "AI chat config",
+        status: if has_key {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Warn
+        },
+        detail: if has_key {
+            format!("API key configured for {}", model)
+        } else {
+            "No AI API key configured".to_string()
+        },
+        path: None,
+        version: None,
+        hint: (!has_key).then(|| "Set an AI API key in Settings to use AI chat.".to_string()),
+    }
+}
+
+fn check_contexts_root(contexts_root: &Path) -> DoctorCheck {
+    let probe = contexts_root.join(format!(".opencontext-doctor-{}", std::process::id()));
+    let result = std::fs::create_dir_all(contexts_root)
+        .and_then(|_| std::fs::write(&probe, b"ok"))
+        .and_then(|_| std::fs::remove_file(&probe));
+    let (status, detail, hint) = match result {
+        Ok(()) => (CheckStatus::Pass, "Writable".to_string(), None),
+        Err(e) => (
+            CheckStatus::Fail,
+            format!("Not writable: {}", e),
+            Some("Check the folder permissions or set OPENCONTEXT_CONTEXTS_ROOT to a writable folder.".to_string()),
+        ),
+    };
+    DoctorCheck {
+        id: "contexts_root",
+        label: "Contexts folder",
+        status,
+        detail,
+        path: Some(contexts_root.to_string_lossy().to_string()),
+        version: None,
+        hint,
+    }
+}
+
+/// Diagnose the local environment: agent CLIs, configs and the contexts folder.
+#[tauri::command]
+pub(crate) async fn env_doctor(state: State<'_, AppState>) -> CmdResult<DoctorReport> {
+    let contexts_root = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.env_info().contexts_root.clone()
+    };
+
+    // Version probes spawn processes; keep them off the async runtime.
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut checks: Vec<DoctorCheck> = CLI_SPECS.iter().map(check_cli).collect();
+        checks.push(check_embedding_config());
+        checks.push(check_ai_config());
+        checks.push(check_contexts_root(&contexts_root));
+        let status = checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Pass);
+        DoctorReport { status, checks }
+    })
+    .await
+    .map_err(CommandError::internal)
+}
@@ -0,0 +1,282 @@
+use crate::utils::CmdResult;
+use serde::{Deserialize, Serialize};
+use similar::{Algorithm, DiffOp};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Time each side's diff against the base may take before it settles for
+/// coarser edits. The merge stays correct, just more conflict-prone.
+const DIFF_DEADLINE: Duration = Duration::from_secs(2);
+const MINE_MARKER: &str = "<<<<<<< mine\n";
+const SEPARATOR_MARKER: &str = "=======\n";
+const THEIRS_MARKER: &str = ">>>>>>> theirs\n";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeDocContentOptions {
+    /// Content both sides started from, usually the last saved version
+    base: String,
+    /// The editor's content
+    mine: String,
+    /// Content written meanwhile by someone else, such as an agent
+    theirs: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocMerge {
+    /// Merged content, with conflict markers around each conflict
+    merged: String,
+    conflicts: Vec<MergeConflict>,
+}
+
+/// Lines both sides changed differently
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeConflict {
+    /// 1-based first and last line of the conflict in `merged`, markers
+    /// included
+    start_line: usize,
+    end_line: usize,
+    base: String,
+    mine: String,
+    theirs: String,
+}
+
+/// One side's change to the base: base lines `start..end` become the
+/// side's lines `new`
+struct Edit {
+    start: usize,
+    end: usize,
+    new: Range<usize>,
+}
+
+/// Merged text and the number of lines in it so far
+#[derive(Default)]
+struct Output {
+    text: String,
+    lines: usize,
+}
+
+impl Output {
+    fn push(&mut self, lines: &[&str]) {
+        for line in lines {
+            self.text.push_str(line);
+            self.lines += line.ends_with('\n') as usize;
+        }
+    }
+
+    /// Like `push`, ending on a line break so a marker can follow
+    fn push_block(&mut self, lines: &[&str]) {
+        self.push(lines);
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.push(&["\n"]);
+        }
+    }
+}
+
+/// Line ending `text` mostly uses, if it has any line breaks
+fn line_ending(text: &str) -> Option<&'static str> {
+    let breaks = text.matches('\n').count();
+    let crlf = text.matches("\r\n").count();
+    (breaks > 0).then_some(if crlf * 2 > breaks { "\r\n" } else { "\n" })
+}
+
+fn edits(base: &[&str], side: &[&str]) -> Vec<Edit> {
+    let deadline = Instant::now() + DIFF_DEADLINE;
+    let mut edits: Vec<Edit> = Vec::new();
+    for op in similar::capture_diff_slices_deadline(Algorithm::Myers, base, side, Some(deadline)) {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let (old, new) = (op.old_range(), op.new_range());
+        match edits.last_mut() {
+            // A deletion and insertion at the same spot are one edit
+            Some(last) if last.end == old.start => {
+                last.end = old.end;
+                last.new.end = new.end;
+            }
+            _ => edits.push(Edit {
+                start: old.start,
+                end: old.end,
+                new,
+            }),
+        }
+    }
+    edits
+}
+
+/// Base lines `start..end` with a side's `edits` inside that range applied
+fn apply<'a>(
+    base: &[&'a str],
+    side: &[&'a str],
+    start: usize,
+    end: usize,
+    edits: &[Edit],
+) -> Vec<&'a str> {
+    let mut lines = Vec::new();
+    let mut pos = start;
+    for edit in edits {
+        lines.extend_from_slice(&base[pos..edit.start]);
+        lines.extend_from_slice(&side[edit.new.clone()]);
+        pos = edit.end;
+    }
+    lines.extend_from_slice(&base[pos..end]);
+    lines
+}
+
+/// Line-based three-way merge of two edits of `base`
+///
+/// Changes from one side only are taken as they are, and both sides making
+/// the same change is no conflict. Where the sides change the same or
+/// adjacent lines differently, both versions are kept between conflict
+/// markers. Line endings are compared ignoring CR, and the result uses the
+/// line ending `mine` mostly does.
+pub(crate) fn merge_texts(base: &str, mine: &str, theirs: &str) -> DocMerge {
+    let ending = line_ending(mine)
+        .or_else(|| line_ending(theirs))
+        .or_else(|| line_ending(base))
+        .unwrap_or("\n");
+    let normalize = |text: &str| text.replace("\r\n", "\n");
+    let (base, mine, theirs) = (normalize(base), normalize(mine), normalize(theirs));
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let mine: Vec<&str> = mine.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let mine_edits = edits(&base, &mine);
+    let theirs_edits = edits(&base, &theirs);
+
+    let mut out = Output::default();
+    let mut conflicts = Vec::new();
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+    loop {
+        let start = match (mine_edits.get(i), theirs_edits.get(j)) {
+            (None, None) => break,
+            (Some(m), Some(t)) => m.start.min(t.start),
+            (Some(m), None) => m.start,
+            (None, Some(t)) => t.start,
+        };
+        out.push(&base[pos..start]);
+
+        // Take in every edit that overlaps or touches the region so far
+        let (first_mine, first_theirs) = (i, j);
+        let mut end = start;
+        loop {
+            if let Some(edit) = mine_edits.get(i).filter(|edit| edit.start <= end) {
+                end = end.max(edit.end);
+                i += 1;
+            } else if let Some(edit) = theirs_edits.get(j).filter(|edit| edit.start <= end) {
+                end = end.max(edit.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+        let ours = apply(&base, &mine, start, end, &mine_edits[first_mine..i]);
+        let others = apply(&base, &theirs, start, end, &theirs_edits[first_theirs..j]);
+        if first_theirs == j || ours == others {
+            out.push(&ours);
+        } else if first_mine == i {
+            out.push(&others);
+        } else {
+            out.push_block(&[]);
+            let start_line = out.lines + 1;
+            out.push(&[MINE_MARKER]);
+            out.push_block(&ours);
+            out.push(&[SEPARATOR_MARKER]);
+            out.push_block(&others);
+            out.push(&[THEIRS_MARKER]);
+            conflicts.push(MergeConflict {
+                start_line,
+                end_line: out.lines,
+                base: base[start..end].concat(),
+                mine: ours.concat(),
+                theirs: others.concat(),
+            });
+        }
+        pos = end;
+    }
+    out.push(&base[pos..]);
+
+    let restore = |text: String| {
+        if ending == "\n" {
+            text
+        } else {
+            text.replace('\n', ending)
+        }
+    };
+    DocMerge {
+        merged: restore(out.text),
+        conflicts: conflicts
+            .into_iter()
+            .map(|conflict| MergeConflict {
+                base: restore(conflict.base),
+                mine: restore(conflict.mine),
+                theirs: restore(conflict.theirs),
+                ..conflict
+            })
+            .collect(),
+    }
+}
+
+/// Three-way merge of a doc edited in two places at once, such as the
+/// editor and an agent. Conflicts come back marked, for the user to settle.
+#[tauri::command]
+pub(crate) fn merge_doc_content(options: MergeDocContentOptions) -> CmdResult<DocMerge> {
+    Ok(merge_texts(&options.base, &options.mine, &options.theirs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_texts_combines_separate_edits() {
+        let base = "title\n\none\ntwo\nthree\nfour\n";
+        let mine = "Title\n\none\ntwo\nthree\nfour\n";
+        let theirs = "title\n\none\ntwo\nthree\nfour\nfive\n";
+        let merge = merge_texts(base, mine, theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, "Title\n\none\ntwo\nthree\nfour\nfive\n");
+
+        let same = merge_texts(base, mine, mine);
+        assert!(same.conflicts.is_empty());
+        assert_eq!(same.merged, mine);
+    }
+
+    #[test]
+    fn merge_texts_marks_overlapping_edits() {
+        let base = "a\nb\nc\nd\n";
+        let mine = "a\nB (mine)\nc\nd\n";
+        let theirs = "a\nB (theirs)\nc\nD\n";
+        let merge = merge_texts(base, mine, theirs);
+        assert_eq!(
+            merge.merged,
+            "a\n<<<<<<< mine\nB (mine)\n=======\nB (theirs)\n>>>>>>> theirs\nc\nD\n"
+        );
+        assert_eq!(merge.conflicts.len(), 1);
+        let conflict = &merge.conflicts[0];
+        assert_eq!((conflict.start_line, conflict.end_line), (2, 6));
+        assert_eq!(conflict.base, "b\n");
+        assert_eq!(conflict.mine, "B (mine)\n");
+        assert_eq!(conflict.theirs, "B (theirs)\n");
+    }
+
+    #[test]
+    fn merge_texts_keeps_markers_on_their_own_lines() {
+        let merge = merge_texts("end", "mine", "theirs");
+        assert_eq!(
+            merge.merged,
+            "<<<<<<< mine\nmine\n=======\ntheirs\n>>>>>>> theirs\n"
+        );
+    }
+
+    #[test]
+    fn merge_texts_ignores_line_ending_differences() {
+        let base = "a\nb\nc\n";
+        let mine = "A\r\nb\r\nc\r\n";
+        let theirs = "a\nb\nC\n";
+        let merge = merge_texts(base, mine, theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, "A\r\nb\r\nC\r\n");
+    }
+}
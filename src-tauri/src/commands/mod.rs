@@ -1,5 +1,21 @@
 pub(crate) mod agent;
 pub(crate) mod ai;
+pub(crate) mod app;
 pub(crate) mod context;
+pub(crate) mod diff;
+pub(crate) mod digest;
+pub(crate) mod doctor;
+pub(crate) mod merge;
+pub(crate) mod onboarding;
+pub(crate) mod patch;
+pub(crate) mod pricing;
+pub(crate) mod prompts;
+pub(crate) mod scratch;
 pub(crate) mod search;
+pub(crate) mod settings;
+pub(crate) mod share;
+pub(crate) mod snapshot;
+pub(crate) mod stats;
+pub(crate) mod summarize;
 pub(crate) mod terminal;
+pub(crate) mod vault;
@@ -0,0 +1,141 @@
+use crate::commands::ai::ai_configured;
+use crate::commands::doctor::detected_agent_clis;
+use crate::commands::search::{index_has_chunks, queue_doc_index};
+use crate::utils::{
+    get_config_bool, map_err, set_config_value, CmdResult, CommandError, ErrorCode,
+};
+use crate::AppState;
+use serde::Serialize;
+use tauri::State;
+
+/// config.json key set once the user closes the welcome checklist
+const DISMISSED_KEY: &str = "ONBOARDING_DISMISSED";
+
+/// Folder `create_sample_content` writes the starter docs into
+const SAMPLE_FOLDER: &str = "Getting Started";
+
+/// Starter docs as (file name, description, content)
+const SAMPLE_DOCS: &[(&str, &str, &str)] = &[
+    (
+        "welcome.md",
+        "What OpenContext is and how the vault is laid out",
+        "# Welcome to OpenContext\n\nOpenContext keeps the context you and your agents need as plain Markdown docs in folders.\n\n- Folders group related docs, such as one per project.\n- Every doc has a description, used by search and by agents deciding what to read.\n- The files live in your contexts folder, so any editor or git can work with them too.\n\nFeel free to edit or delete this folder once you have your own docs.\n",
+    ),
+    (
+        "writing-docs.md",
+        "Tips for writing docs that search and agents use well",
+        "# Writing useful docs\n\nShort, focused docs work best:\n\n1. One topic per doc, with a heading that names it.\n2. A one-line description saying when the doc is relevant.\n3. Decisions and their reasons, not just the outcome.\n\nLink related docs with their paths, like `Getting Started/welcome.md`.\n",
+    ),
+    (
+        "search-and-agents.md",
+        "How semantic search and agent CLIs use the vault",
+        "# Search and agents\n\nWith an embedding API key set in Settings, OpenContext builds a search index of your docs and keeps it current as you save.\n\nAgent CLIs such as Codex, Claude Code and OpenCode can then query the vault through the `oc` CLI, so they start each task with your project's context.\n\nTry a search for \"writing docs\" once the index is built.\n",
+    ),
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OnboardingStep {
+    id: &'static str,
+    done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OnboardingStatus {
+    /// The user closed the checklist; the UI stops offering it
+    dismissed: bool,
+    /// Every step is done
+    complete: bool,
+    steps: Vec<OnboardingStep>,
+}
+
+/// Whether the contexts folder exists and holds at least one doc
+fn vault_has_docs(state: &AppState) -> CmdResult<bool> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    if !ctx.env_info().contexts_root.is_dir() {
+        return Ok(false);
+    }
+    for folder in ctx.list_folders(false)? {
+        if !ctx.list_docs(&folder.rel_path, true)?.is_empty() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn step(id: &'static str, done: bool, detail: Option<String>) -> OnboardingStep {
+    OnboardingStep { id, done, detail }
+}
+
+/// First-run checklist computed from the app's actual state: docs in the
+/// vault, an embedding key and a built index, an AI provider, and at least
+/// one agent CLI found by the doctor checks.
+#[tauri::command]
+pub(crate) async fn onboarding_status(state: State<'_, AppState>) -> CmdResult<OnboardingStatus> {
+    let has_docs = vault_has_docs(&state)?;
+    let config = state.search_config();
+    let embedding_error = config.embedding.get_api_key().err().map(|e| e.to_string());
+    let index_built = index_has_chunks(&config);
+    // Version probes spawn processes; keep them off the async runtime.
+    let agents = tauri::async_runtime::spawn_blocking(detected_agent_clis)
+        .await
+        .map_err(CommandError::internal)?;
+
+    let steps = vec![
+        step("contexts", has_docs, None),
+        step("embedding", embedding_error.is_none(), embedding_error),
+        step("index", index_built, None),
+        step("ai", ai_configured(), None),
+        step(
+            "agent",
+            !agents.is_empty(),
+            (!agents.is_empty()).then(|| agents.join(", ")),
+        ),
+    ];
+    Ok(OnboardingStatus {
+        dismissed: get_config_bool(DISMISSED_KEY).unwrap_or(false),
+        complete: steps.iter().all(|step| step.done),
+        steps,
+    })
+}
+
+/// Hide the first-run checklist for good
+#[tauri::command]
+pub(crate) fn onboarding_dismiss() -> CmdResult<()> {
+    set_config_value(DISMISSED_KEY, serde_json::Value::Bool(true))
+}
+
+/// Write a small "Getting Started" folder of example docs through the
+/// normal create and save paths, so they get indexed like any other doc.
+/// Returns the created doc paths.
+#[tauri::command]
+pub(crate) fn create_sample_content(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> CmdResult<Vec<String>> {
+    let ctx = state.ctx.write().map_err(map_err)?;
+    if ctx
+        .list_folders(false)?
+        .iter()
+        .any(|folder| folder.rel_path == SAMPLE_FOLDER)
+    {
+        return Err(CommandError::localized(
+            ErrorCode::Conflict,
+            "error.folder_exists",
+            &[("folder", SAMPLE_FOLDER)],
+        ));
+    }
+    ctx.create_folder(SAMPLE_FOLDER, Some("Example docs to explore OpenContext"))?;
+
+    let mut created = Vec::with_capacity(SAMPLE_DOCS.len());
+    for (name, description, content) in SAMPLE_DOCS {
+        let doc = ctx.create_doc(SAMPLE_FOLDER, name, Some(description))?;
+        ctx.save_doc_content(&doc.rel_path, content, None)?;
+        queue_doc_index(&app, &doc.rel_path);
+        created.push(doc.rel_path);
+    }
+    Ok(created)
+}
@@ -0,0 +1,356 @@
+use crate::utils::{map_err, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::State;
+
+/// Lines of an added or deleted file shown in its snippet
+const MAX_SNIPPET_LINES: usize = 40;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AgentPatchPreviewOptions {
+    session_id: String,
+    call_id: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PatchPreview {
+    /// Every file's changes apply to its current contents
+    clean: bool,
+    files: Vec<FilePatchPreview>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PatchFileKind {
+    Add,
+    Delete,
+    Update,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FilePatchPreview {
+    path: String,
+    kind: PatchFileKind,
+    /// Where an update moves the file to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    move_path: Option<String>,
+    clean: bool,
+    /// Why the file's changes don't apply, beyond a failed hunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    snippets: Vec<PatchSnippet>,
+}
+
+/// One change: the text it replaces and the text it writes
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PatchSnippet {
+    /// 1-based line of the current file the change starts at, or would
+    /// have started at when it doesn't apply
+    line: usize,
+    before: String,
+    after: String,
+    /// The lines the change expects were found in the current file
+    applies: bool,
+}
+
+/// A unified diff hunk: the lines it expects and the lines it writes
+struct Hunk {
+    /// 1-based first old line from the `@@` header, if it has one
+    old_start: Option<usize>,
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+fn parse_hunks(diff: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("@@") {
+            let old_start = header
+                .trim()
+                .strip_prefix('-')
+                .and_then(|range| range.split([',', ' ']).next())
+                .and_then(|start| start.parse().ok());
+            hunks.push(Hunk {
+                old_start,
+                old: Vec::new(),
+                new: Vec::new(),
+            });
+            continue;
+        }
+        // File headers and anything else before the first hunk
+        let Some(hunk) = hunks.last_mut() else {
+            continue;
+        };
+        if let Some(text) = line.strip_prefix('-') {
+            hunk.old.push(text.to_string());
+        } else if let Some(text) = line.strip_prefix('+') {
+            hunk.new.push(text.to_string());
+        } else if !line.starts_with('\\') {
+            // Context; some tools drop the leading space of blank lines
+            let text = line.strip_prefix(' ').unwrap_or(line);
+            hunk.old.push(text.to_string());
+            hunk.new.push(text.to_string());
+        }
+    }
+    hunks
+}
+
+/// Where `block` occurs in `lines` at or after `from`, nearest `expected`.
+/// Line endings are compared ignoring CR.
+fn find_block(lines: &[&str], block: &[String], from: usize, expected: usize) -> Option<usize> {
+    if block.is_empty() {
+        return Some(expected.clamp(from, lines.len()));
+    }
+    let last = lines.len().checked_sub(block.len())?;
+    (from..=last)
+        .filter(|&at| {
+            lines[at..at + block.len()]
+                .iter()
+                .zip(block)
+                .all(|(line, want)| line.trim_end_matches('\r') == want.trim_end_matches('\r'))
+        })
+        .min_by_key(|&at| at.abs_diff(expected))
+}
+
+/// Apply `diff` to `content` in memory. Hunks that don't apply are skipped
+/// and reported with `applies: false`.
+fn apply_unified_diff(content: &str, diff: &str) -> (String, Vec<PatchSnippet>) {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut patched: Vec<String> = Vec::new();
+    let mut snippets = Vec::new();
+    let mut cursor = 0;
+    for hunk in parse_hunks(diff) {
+        let expected = match hunk.old_start {
+            // A pure insertion's start is the line it goes after
+            Some(start) if hunk.old.is_empty() => start,
+            Some(start) => start.saturating_sub(1),
+            None => cursor,
+        };
+        let found = find_block(&lines, &hunk.old, cursor, expected);
+        if let Some(at) = found {
+            patched.extend(lines[cursor..at].iter().map(|line| line.to_string()));
+            patched.extend(hunk.new.iter().cloned());
+            cursor = at + hunk.old.len();
+        }
+        snippets.push(PatchSnippet {
+            line: found.unwrap_or(expected) + 1,
+            before: hunk.old.join("\n"),
+            after: hunk.new.join("\n"),
+            applies: found.is_some(),
+        });
+    }
+    patched.extend(lines[cursor..].iter().map(|line| line.to_string()));
+
+    let ending = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut text = patched.join(ending);
+    if content.ends_with('\n') && !text.is_empty() {
+        text.push_str(ending);
+    }
+    (text, snippets)
+}
+
+/// The first `MAX_SNIPPET_LINES` lines of `content`
+fn head(content: &str) -> String {
+    content
+        .lines()
+        .take(MAX_SNIPPET_LINES)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Change kind and body of a Codex `FileChange`, which is either tagged by a
+/// `type` field or keyed by kind
+fn file_change(change: &serde_json::Value) -> Option<(PatchFileKind, &serde_json::Value)> {
+    let kind_of = |name: &str| match name {
+        "add" => Some(PatchFileKind::Add),
+        "delete" => Some(PatchFileKind::Delete),
+        "update" => Some(PatchFileKind::Update),
+        _ => None,
+    };
+    if let Some(name) = change.get("type").and_then(|t| t.as_str()) {
+        return Some((kind_of(name)?, change));
+    }
+    let (name, body) = change.as_object()?.iter().next()?;
+    Some((kind_of(name)?, body))
+}
+
+/// Preview one file's change against its `current` contents, `None` if it
+/// doesn't exist
+fn preview_file(
+    path: &str,
+    change: &serde_json::Value,
+    current: Option<String>,
+) -> FilePatchPreview {
+    let text = |key: &str, body: &serde_json::Value| {
+        body.get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let mut preview = FilePatchPreview {
+        path: path.to_string(),
+        kind: PatchFileKind::Update,
+        move_path: None,
+        clean: false,
+        error: None,
+        snippets: Vec::new(),
+    };
+    let Some((kind, body)) = file_change(change) else {
+        preview.error = Some("Unrecognized change".to_string());
+        return preview;
+    };
+    preview.kind = kind;
+
+    match kind {
+        PatchFileKind::Add => {
+            let content = text("content", body);
+            // Re-adding a file with the same content clobbers nothing
+            if current.as_ref().is_some_and(|current| *current != content) {
+                preview.error = Some("File already exists".to_string());
+            }
+            preview.snippets.push(PatchSnippet {
+                line: 1,
+                before: current.as_deref().map(head).unwrap_or_default(),
+                after: head(&content),
+                applies: preview.error.is_none(),
+            });
+        }
+        PatchFileKind::Delete => {
+            if current.is_none() {
+                preview.error = Some("File not found".to_string());
+            }
+            preview.snippets.push(PatchSnippet {
+                line: 1,
+                before: current.as_deref().map(head).unwrap_or_default(),
+                after: String::new(),
+                applies: current.is_some(),
+            });
+        }
+        PatchFileKind::Update => {
+            preview.move_path = body
+                .get("move_path")
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+            match current {
+                Some(current) => {
+                    let (_, snippets) = apply_unified_diff(&current, &text("unified_diff", body));
+                    preview.snippets = snippets;
+                }
+                None => preview.error = Some("File not found".to_string()),
+            }
+        }
+    }
+    preview.clean = preview.error.is_none() && preview.snippets.iter().all(|s| s.applies);
+    preview
+}
+
+/// Dry-run a pending Codex patch: apply its changes to the current file
+/// contents in memory, without writing, and show what each would replace
+#[tauri::command]
+pub(crate) fn agent_patch_preview(
+    state: State<AppState>,
+    options: AgentPatchPreviewOptions,
+) -> CmdResult<PatchPreview> {
+    let session = {
+        let sessions = state.agent_rpc_sessions.lock().map_err(map_err)?;
+        sessions.get(&options.session_id).cloned()
+    };
+    let Some(session) = session else {
+        return Err(CommandError::localized(
+            ErrorCode::NotFound,
+            "error.codex_session_not_found",
+            &[],
+        ));
+    };
+
+    let normalized = options
+        .call_id
+        .trim_start_matches("patch_")
+        .trim_start_matches("elicitation_");
+    let (changes, cwd) = {
+        let state = session.state.lock().map_err(map_err)?;
+        let changes = state
+            .codex_patch_changes
+            .get(&options.call_id)
+            .or_else(|| state.codex_patch_changes.get(normalized))
+            .cloned();
+        (changes, state.cwd.clone().map(PathBuf::from))
+    };
+    let changes = changes.ok_or_else(|| {
+        CommandError::localized(
+            ErrorCode::NotFound,
+            "error.patch_not_found",
+            &[("callId", &options.call_id)],
+        )
+    })?;
+
+    let files: Vec<FilePatchPreview> = changes
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(path, change)| {
+            let abs_path = match &cwd {
+                Some(cwd) => cwd.join(path),
+                None => PathBuf::from(path),
+            };
+            preview_file(path, change, std::fs::read_to_string(abs_path).ok())
+        })
+        .collect();
+    Ok(PatchPreview {
+        clean: files.iter().all(|file| file.clean),
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn apply_unified_diff_finds_shifted_hunks() {
+        let content = "intro\nnew line\none\ntwo\nthree\n";
+        let diff = "--- a/doc.md\n+++ b/doc.md\n@@ -2,2 +2,2 @@\n one\n-two\n+TWO\n";
+        let (patched, snippets) = apply_unified_diff(content, diff);
+        assert_eq!(patched, "intro\nnew line\none\nTWO\nthree\n");
+        assert_eq!(snippets.len(), 1);
+        assert!(snippets[0].applies);
+        assert_eq!(snippets[0].line, 3);
+        assert_eq!(snippets[0].before, "one\ntwo");
+        assert_eq!(snippets[0].after, "one\nTWO");
+    }
+
+    #[test]
+    fn apply_unified_diff_reports_conflicting_hunks() {
+        let content = "one\nchanged by hand\nthree\n";
+        let diff = "@@ -1,2 +1,2 @@\n one\n-two\n+TWO\n@@ -3,0 +4 @@\n+four\n";
+        let (patched, snippets) = apply_unified_diff(content, diff);
+        assert!(!snippets[0].applies);
+        assert!(snippets[1].applies);
+        assert_eq!(patched, "one\nchanged by hand\nthree\nfour\n");
+    }
+
+    #[test]
+    fn preview_file_reads_both_change_shapes() {
+        let current = || Some("a\nb\n".to_string());
+        let update = json!({ "update": { "unified_diff": "@@ -2 +2 @@\n-b\n+c\n" } });
+        let preview = preview_file("notes.md", &update, current());
+        assert_eq!(preview.kind, PatchFileKind::Update);
+        assert!(preview.clean);
+
+        let add = json!({ "type": "add", "content": "new\n" });
+        let preview = preview_file("notes.md", &add, current());
+        assert_eq!(preview.kind, PatchFileKind::Add);
+        assert!(!preview.clean);
+        assert_eq!(preview.error.as_deref(), Some("File already exists"));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::commands::search::reload_search_config;
+use crate::utils::{read_config_for_update, read_config_json, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use opencontext_core::search::{EmbeddingUsage, SearchConfig, UsageLedger};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::State;
+
+/// config.json key of the chat model price table
+const PRICING_KEY: &str = "AI_PRICING";
+/// Chat usage ledger, kept beside the embedding one
+const CHAT_USAGE_FILE: &str = "chat-usage.json";
+
+/// Bundled prices as (model pattern, input, output) in USD per million
+/// tokens. Entries in `AI_PRICING` replace or add to them.
+const DEFAULT_PRICING: &[(&str, f64, f64)] = &[
+    ("gpt-4o*", 2.5, 10.0),
+    ("gpt-4o-mini*", 0.15, 0.6),
+    ("gpt-4.1*", 2.0, 8.0),
+    ("gpt-4.1-mini*", 0.4, 1.6),
+    ("gpt-4.1-nano*", 0.1, 0.4),
+    ("gpt-5*", 1.25, 10.0),
+    ("gpt-5-mini*", 0.25, 2.0),
+    ("gpt-5-nano*", 0.05, 0.4),
+    ("o3*", 2.0, 8.0),
+    ("o3-mini*", 1.1, 4.4),
+    ("o4-mini*", 1.1, 4.4),
+    ("claude-opus-4*", 15.0, 75.0),
+    ("claude-opus-4-5*", 5.0, 25.0),
+    ("claude-sonnet-4*", 3.0, 15.0),
+    ("claude-3-7-sonnet*", 3.0, 15.0),
+    ("claude-3-5-sonnet*", 3.0, 15.0),
+    ("claude-haiku-4*", 1.0, 5.0),
+    ("claude-3-5-haiku*", 0.8, 4.0),
+    ("deepseek-chat*", 0.27, 1.1),
+    ("deepseek-reasoner*", 0.55, 2.19),
+];
+
+/// USD per million tokens
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ModelPrice {
+    input: f64,
+    output: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PricingTable {
+    defaults: BTreeMap<String, ModelPrice>,
+    /// Entries from `AI_PRICING`
+    custom: BTreeMap<String, ModelPrice>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PricingSaveOptions {
+    /// Replaces all custom entries
+    pricing: BTreeMap<String, ModelPrice>,
+}
+
+/// Tokens a chat turn used
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TokenCounts {
+    pub(crate) input: u64,
+    pub(crate) output: u64,
+}
+
+/// Tokens a chat turn used and what they cost, sent with its `done` event
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ChatUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+    /// USD; null when the model has no known price, so unknown never
+    /// reads as free
+    estimated_cost: Option<f64>,
+}
+
+fn default_pricing() -> BTreeMap<String, ModelPrice> {
+    DEFAULT_PRICING
+        .iter()
+        .map(|&(pattern, input, output)| (pattern.to_string(), ModelPrice { input, output }))
+        .collect()
+}
+
+/// `AI_PRICING` from config.json. Malformed entries are skipped.
+fn custom_pricing() -> BTreeMap<String, ModelPrice> {
+    let Some(serde_json::Value::Object(entries)) =
+        read_config_json().and_then(|config| config.get(PRICING_KEY).cloned())
+    else {
+        return BTreeMap::new();
+    };
+    entries
+        .into_iter()
+        .filter_map(|(pattern, price)| {
+            let price: ModelPrice = serde_json::from_value(price).ok()?;
+            valid_price(&price).then_some((pattern, price))
+        })
+        .collect()
+}
+
+fn valid_price(price: &ModelPrice) -> bool {
+    [price.input, price.output]
+        .iter()
+        .all(|value| value.is_finite() && *value >= 0.0)
+}
+
+/// Whether `model` matches `pattern`, where `*` stands for any run of
+/// characters. Case is ignored.
+fn pattern_matches(pattern: &str, model: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let model = model.to_lowercase();
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = model.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Price of the most specific pattern matching `model`, the one with the
+/// most characters besides `*`. A `provider/` prefix on the model is
+/// ignored.
+fn price_for(table: &BTreeMap<String, ModelPrice>, model: &str) -> Option<ModelPrice> {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    table
+        .iter()
+        .filter(|(pattern, _)| pattern_matches(pattern, model))
+        .max_by_key(|(pattern, _)| pattern.chars().filter(|c| *c != '*').count())
+        .map(|(_, price)| *price)
+}
+
+fn estimate_cost(
+    table: &BTreeMap<String, ModelPrice>,
+    model: &str,
+    tokens: TokenCounts,
+) -> Option<f64> {
+    let price = price_for(table, model)?;
+    Some((tokens.input as f64 * price.input + tokens.output as f64 * price.output) / 1_000_000.0)
+}
+
+fn chat_ledger(config: &SearchConfig) -> UsageLedger {
+    UsageLedger::new(
+        config
+            .paths
+            .get_usage_ledger_path()
+            .with_file_name(CHAT_USAGE_FILE),
+    )
+}
+
+/// Price a finished chat turn and add it to the chat usage ledger
+pub(crate) fn record_chat_usage(state: &AppState, model: &str, tokens: TokenCounts) -> ChatUsage {
+    let mut table = default_pricing();
+    table.extend(custom_pricing());
+    let estimated_cost = estimate_cost(&table, model, tokens);
+    chat_ledger(&state.search_config()).record_with_cost(
+        model,
+        tokens.input + tokens.output,
+        estimated_cost,
+    );
+    ChatUsage {
+        input_tokens: tokens.input,
+        output_tokens: tokens.output,
+        estimated_cost,
+    }
+}
+
+#[tauri::command]
+pub(crate) fn pricing_get() -> CmdResult<PricingTable> {
+    Ok(PricingTable {
+        defaults: default_pricing(),
+        custom: custom_pricing(),
+    })
+}
+
+#[tauri::command]
+pub(crate) async fn pricing_save(
+    app: tauri::AppHandle,
+    options: PricingSaveOptions,
+) -> CmdResult<()> {
+    if let Some((pattern, _)) = options
+        .pricing
+        .iter()
+        .find(|(pattern, price)| pattern.trim().is_empty() || !valid_price(price))
+    {
+        return Err(CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.invalid_price",
+            &[("pattern", pattern)],
+        ));
+    }
+
+    let config_path = SearchConfig::json_config_path();
+    let mut config = read_config_for_update()?;
+    if options.pricing.is_empty() {
+        config.remove(PRICING_KEY);
+    } else {
+        config.insert(
+            PRICING_KEY.to_string(),
+            serde_json::to_value(&options.pricing)?,
+        );
+    }
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&config_path, serde_json::to_string_pretty(&config)?)?;
+    reload_search_config(&app).await
+}
+
+/// Tokens spent on chat and agent turns per day and month, with the costs
+/// estimated from the price table when each turn finished
+#[tauri::command]
+pub(crate) fn chat_usage(state: State<AppState>) -> CmdResult<EmbeddingUsage> {
+    Ok(chat_ledger(&state.search_config()).summary(&BTreeMap::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_matches_wildcards_ignoring_case() {
+        assert!(pattern_matches("gpt-4o*", "GPT-4o-2024-08-06"));
+        assert!(pattern_matches(
+            "claude-*-sonnet*",
+            "claude-3-5-sonnet-latest"
+        ));
+        assert!(pattern_matches("llama3", "llama3"));
+        assert!(!pattern_matches("llama3", "llama3.1"));
+        assert!(!pattern_matches("o3*", "gpt-4o3"));
+    }
+
+    #[test]
+    fn estimate_cost_prefers_the_most_specific_pattern() {
+        let table = default_pricing();
+        let tokens = TokenCounts {
+            input: 1_000_000,
+            output: 1_000_000,
+        };
+        let mini = estimate_cost(&table, "gpt-4o-mini", tokens).unwrap();
+        assert!((mini - 0.75).abs() < 1e-9);
+        let routed = estimate_cost(&table, "openai/gpt-4o", tokens).unwrap();
+        assert!((routed - 12.5).abs() < 1e-9);
+        assert_eq!(estimate_cost(&table, "llama3.1:8b", tokens), None);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::chat::ChatMessage;
+use crate::utils::{map_err, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use opencontext_core::search::SearchConfig;
+use opencontext_core::VaultPath;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::State;
+
+const PROMPT_TEMPLATES_FILE: &str = "prompt-templates.json";
+
+/// Where a template is meant to be used; only a hint for the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PromptTarget {
+    Chat,
+    Agent,
+}
+
+/// A reusable prompt. `body` may contain `{{variable}}` placeholders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PromptTemplate {
+    id: String,
+    name: String,
+    body: String,
+    target: PromptTarget,
+    /// ms since epoch
+    updated_at: u64,
+}
+
+/// Value for a template variable. Docs and manifests are read here, so
+/// large bodies never pass through the webview.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum PromptVariable {
+    /// Literal text, such as the editor selection
+    Text(String),
+    /// Content of the doc at this path
+    Doc(VaultPath),
+    /// List of the docs in this folder, with their descriptions
+    Manifest(VaultPath),
+}
+
+/// Template fields accepted by `ai_chat` and the agent exec commands as an
+/// alternative to (or on top of) raw messages
+#[derive(Deserialize, Default)]
+pub(crate) struct TemplatedPrompt {
+    #[serde(rename = "templateId")]
+    template_id: Option<String>,
+    #[serde(default)]
+    variables: HashMap<String, PromptVariable>,
+}
+
+impl TemplatedPrompt {
+    /// `messages` with the rendered template appended as a user message
+    pub(crate) fn apply(
+        self,
+        state: &AppState,
+        mut messages: Vec<ChatMessage>,
+    ) -> CmdResult<Vec<ChatMessage>> {
+        if let Some(id) = self.template_id {
+            let text = render_template(state, &find_template(&id)?, &self.variables)?;
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: serde_json::Value::String(text),
+            });
+        }
+        if messages.is_empty() {
+            return Err(CommandError::localized(
+                ErrorCode::InvalidInput,
+                "error.prompt_input_required",
+                &[],
+            ));
+        }
+        Ok(messages)
+    }
+}
+
+fn templates_path() -> PathBuf {
+    SearchConfig::json_config_path()
+        .parent()
+        .map(|dir| dir.join(PROMPT_TEMPLATES_FILE))
+        .unwrap_or_else(|| PathBuf::from(PROMPT_TEMPLATES_FILE))
+}
+
+fn load_templates() -> CmdResult<Vec<PromptTemplate>> {
+    let path = templates_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn store_templates(templates: &[PromptTemplate]) -> CmdResult<()> {
+    let path = templates_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(templates)?)?;
+    Ok(())
+}
+
+fn find_template(id: &str) -> CmdResult<PromptTemplate> {
+    load_templates()?
+        .into_iter()
+        .find(|template| template.id == id)
+        .ok_or_else(|| {
+            CommandError::localized(
+                ErrorCode::NotFound,
+                "error.prompt_template_not_found",
+                &[("id", id)],
+            )
+        })
+}
+
+/// Names of the `{{variable}}` placeholders in `body`, in order, with the
+/// byte range each one covers
+fn placeholders(body: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = body[offset..].find("{{") {
+        let start = offset + start;
+        let Some(len) = body[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + len + 2;
+        let name = body[start + 2..end - 2].trim();
+        if !name.is_empty() && !name.contains(['{', '}']) {
+            found.push((start..end, name));
+        }
+        offset = end;
+    }
+    found
+}
+
+/// Fill every placeholder in one pass, so values are never expanded
+/// themselves. Fails listing the variables without a value.
+fn fill_placeholders(body: &str, values: &HashMap<&str, String>) -> CmdResult<String> {
+    let found = placeholders(body);
+    let mut missing: Vec<&str> = found
+        .iter()
+        .map(|(_, name)| *name)
+        .filter(|name| !values.contains_key(name))
+        .collect();
+    if !missing.is_empty() {
+        missing.sort_unstable();
+        missing.dedup();
+        return Err(CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.prompt_variables_missing",
+            &[("names", &missing.join(", "))],
+        ));
+    }
+    let mut out = String::with_capacity(body.len());
+    let mut last = 0;
+    for (range, name) in found {
+        out.push_str(&body[last..range.start]);
+        out.push_str(&values[name]);
+        last = range.end;
+    }
+    out.push_str(&body[last..]);
+    Ok(out)
+}
+
+fn render_template(
+    state: &AppState,
+    template: &PromptTemplate,
+    variables: &HashMap<String, PromptVariable>,
+) -> CmdResult<String> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let mut values = HashMap::new();
+    for (name, variable) in variables {
+        let value = match variable {
+            PromptVariable::Text(text) => text.clone(),
+            PromptVariable::Doc(path) => ctx.get_doc_content(path.as_str())?,
+            PromptVariable::Manifest(folder) => ctx
+                .generate_manifest(folder.as_str(), None)?
+                .iter()
+                .map(|entry| {
+                    if entry.description.is_empty() {
+                        format!("- {}", entry.rel_path)
+                    } else {
+                        format!("- {}: {}", entry.rel_path, entry.description)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        values.insert(name.as_str(), value);
+    }
+    fill_placeholders(&template.body, &values)
+}
+
+#[tauri::command]
+pub(crate) fn prompt_templates_list() -> CmdResult<Vec<PromptTemplate>> {
+    load_templates()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SavePromptTemplateOptions {
+    /// Existing template to replace; a new one is created when absent
+    id: Option<String>,
+    name: String,
+    body: String,
+    target: PromptTarget,
+}
+
+#[tauri::command]
+pub(crate) fn prompt_templates_save(
+    options: SavePromptTemplateOptions,
+) -> CmdResult<PromptTemplate> {
+    let name = options.name.trim().to_string();
+    if name.is_empty() {
+        return Err(CommandError::localized(
+            ErrorCode::InvalidName,
+            "error.prompt_template_name_empty",
+            &[],
+        ));
+    }
+    let mut templates = load_templates()?;
+    let updated_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let id = options.id.unwrap_or_else(|| {
+        let mut n = updated_at;
+        while templates.iter().any(|t| t.id == format!("tpl-{}", n)) {
+            n += 1;
+        }
+        format!("tpl-{}", n)
+    });
+    let template = PromptTemplate {
+        id,
+        name,
+        body: options.body,
+        target: options.target,
+        updated_at,
+    };
+    match templates.iter_mut().find(|t| t.id == template.id) {
+        Some(existing) => *existing = template.clone(),
+        None => templates.push(template.clone()),
+    }
+    store_templates(&templates)?;
+    Ok(template)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeletePromptTemplateOptions {
+    id: String,
+}
+
+/// Returns false if no template had this id
+#[tauri::command]
+pub(crate) fn prompt_templates_delete(options: DeletePromptTemplateOptions) -> CmdResult<bool> {
+    let mut templates = load_templates()?;
+    let before = templates.len();
+    templates.retain(|t| t.id != options.id);
+    if templates.len() == before {
+        return Ok(false);
+    }
+    store_templates(&templates)?;
+    Ok(true)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RenderPromptTemplateOptions {
+    template_id: String,
+    #[serde(default)]
+    variables: HashMap<String, PromptVariable>,
+}
+
+#[tauri::command]
+pub(crate) fn render_prompt_template(
+    state: State<AppState>,
+    options: RenderPromptTemplateOptions,
+) -> CmdResult<String> {
+    let template = find_template(&options.template_id)?;
+    render_template(&state, &template, &options.variables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_placeholders_substitutes_in_one_pass() {
+        let values = HashMap::from([
+            ("doc", "{{selection}}".to_string()),
+            ("selection", "x".to_string()),
+        ]);
+        assert_eq!(
+            fill_placeholders("Summarize {{ doc }} then {{selection}}.", &values).unwrap(),
+            "Summarize {{selection}} then x."
+        );
+    }
+
+    #[test]
+    fn fill_placeholders_reports_missing_variables() {
+        let err = fill_placeholders("{{a}} {{b}} {{b}} {{}}", &HashMap::new()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        assert!(err.message.ends_with("a, b"));
+    }
+}
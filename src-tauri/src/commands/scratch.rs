@@ -0,0 +1,329 @@
+use crate::commands::search::queue_doc_index;
+use crate::utils::{
+    get_config_bool, get_config_value, map_err, CmdResult, CommandError, ErrorCode,
+};
+use crate::AppState;
+use opencontext_core::VaultPath;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{Manager, State};
+
+/// Under the app data dir, one subdirectory per agent session
+const SCRATCH_DIR: &str = "agent-scratch";
+/// config.json key: run agents in their scratch directory when an exec call
+/// doesn't say
+const SCRATCH_CWD_KEY: &str = "AGENT_SCRATCH_CWD";
+/// config.json key: days a scratch directory is kept after its last change
+const RETENTION_KEY: &str = "AGENT_SCRATCH_RETENTION_DAYS";
+const DEFAULT_RETENTION_DAYS: u64 = 7;
+/// Files listed per scratch directory
+const MAX_LISTED_FILES: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AgentScratchListOptions {
+    session_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScratchListing {
+    /// `None` when the session has no scratch directory
+    path: Option<String>,
+    files: Vec<ScratchFile>,
+    /// More files exist than were listed
+    truncated: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScratchFile {
+    /// Relative to the scratch directory, with `/` separators
+    path: String,
+    bytes: u64,
+    /// ms since epoch
+    modified_at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AgentScratchImportOptions {
+    session_id: String,
+    /// Files to import, as listed by `agent_scratch_list`
+    paths: Vec<String>,
+    folder_path: VaultPath,
+}
+
+/// Outcome of importing one scratch file
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScratchImport {
+    path: String,
+    /// Vault path of the created doc
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doc_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn scratch_root(app: &tauri::AppHandle) -> CmdResult<PathBuf> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(map_err)?
+        .join(SCRATCH_DIR))
+}
+
+/// Directory name for a session; `None` if nothing of the id is usable
+fn session_dir_name(session_id: &str) -> Option<String> {
+    let safe: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    (!safe.is_empty()).then_some(safe)
+}
+
+fn session_dir(app: &tauri::AppHandle, session_id: &str) -> CmdResult<PathBuf> {
+    let name = session_dir_name(session_id).ok_or_else(|| {
+        CommandError::localized(ErrorCode::InvalidInput, "error.session_id_empty", &[])
+    })?;
+    Ok(scratch_root(app)?.join(name))
+}
+
+/// Whether exec calls that don't set `useScratchCwd` run in scratch
+pub(crate) fn scratch_cwd_default() -> bool {
+    get_config_bool(SCRATCH_CWD_KEY).unwrap_or(false)
+}
+
+/// The session's scratch directory, created if needed
+pub(crate) fn scratch_dir(app: &tauri::AppHandle, session_id: &str) -> CmdResult<PathBuf> {
+    let dir = session_dir(app, session_id)?;
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Whether `dir` is inside a scratch directory, where agent file access is
+/// confined to it
+pub(crate) fn is_scratch_dir(app: &tauri::AppHandle, dir: &str) -> bool {
+    scratch_root(app).is_ok_and(|root| Path::new(dir).starts_with(root))
+}
+
+fn modified_ms(metadata: &std::fs::Metadata) -> Option<u64> {
+    let since_epoch = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some(since_epoch.as_millis() as u64)
+}
+
+/// Files under `dir`, sorted by path, at most `limit` of them. Returns
+/// whether any were left out. Symlinks are not followed.
+fn list_files(dir: &Path, limit: usize) -> (Vec<ScratchFile>, bool) {
+    let mut files = Vec::new();
+    let mut truncated = false;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+            if !metadata.is_file() {
+                continue;
+            }
+            if files.len() >= limit {
+                truncated = true;
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(dir).map(Path::to_path_buf) else {
+                continue;
+            };
+            files.push(ScratchFile {
+                path: relative.to_string_lossy().replace('\\', "/"),
+                bytes: metadata.len(),
+                modified_at: modified_ms(&metadata),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    (files, truncated)
+}
+
+/// Whether `relative`, or a directory on the way to it, is a symlink
+/// inside `dir`
+fn is_symlinked(dir: &Path, relative: &VaultPath) -> bool {
+    let mut path = dir.to_path_buf();
+    relative.as_str().split('/').any(|part| {
+        path.push(part);
+        path.symlink_metadata()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink())
+    })
+}
+
+/// Newest modified time of `dir` or anything in it
+fn last_change(dir: &Path) -> Option<SystemTime> {
+    let own = std::fs::metadata(dir).and_then(|m| m.modified()).ok();
+    let (files, _) = list_files(dir, usize::MAX);
+    files
+        .iter()
+        .filter_map(|file| file.modified_at)
+        .map(|ms| std::time::UNIX_EPOCH + Duration::from_millis(ms))
+        .chain(own)
+        .max()
+}
+
+/// Remove scratch directories unchanged for longer than the retention
+/// period, except those of sessions still running. Returns how many were
+/// removed.
+pub(crate) fn prune_scratch_dirs(app: &tauri::AppHandle) -> usize {
+    let days = get_config_value(RETENTION_KEY)
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    let retention = Duration::from_secs(days * 24 * 60 * 60);
+    let Ok(root) = scratch_root(app) else {
+        return 0;
+    };
+    let Ok(entries) = std::fs::read_dir(&root) else {
+        return 0;
+    };
+    let live: Vec<String> = app
+        .state::<AppState>()
+        .agent_rpc_sessions
+        .lock()
+        .map(|sessions| {
+            sessions
+                .keys()
+                .filter_map(|id| session_dir_name(id))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !path.is_dir() || live.contains(&name) {
+            continue;
+        }
+        let expired = last_change(&path)
+            .and_then(|changed| changed.elapsed().ok())
+            .is_some_and(|age| age > retention);
+        if !expired {
+            continue;
+        }
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => removed += 1,
+            Err(e) => log::warn!("[Scratch] Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    if removed > 0 {
+        log::info!("[Scratch] Removed {} expired scratch directories", removed);
+    }
+    removed
+}
+
+/// Files an agent produced in its session's scratch directory
+#[tauri::command]
+pub(crate) fn agent_scratch_list(
+    app: tauri::AppHandle,
+    options: AgentScratchListOptions,
+) -> CmdResult<ScratchListing> {
+    let dir = session_dir(&app, &options.session_id)?;
+    if !dir.is_dir() {
+        return Ok(ScratchListing {
+            path: None,
+            files: Vec::new(),
+            truncated: false,
+        });
+    }
+    let (files, truncated) = list_files(&dir, MAX_LISTED_FILES);
+    Ok(ScratchListing {
+        path: Some(dir.to_string_lossy().to_string()),
+        files,
+        truncated,
+    })
+}
+
+/// Copy scratch files into a vault folder as new docs, named after the
+/// files. Each file is imported on its own; one that fails, such as a name
+/// already taken, is reported and the rest still go in. Symlinks are not
+/// imported, so an agent can't pull in files from outside its directory.
+#[tauri::command]
+pub(crate) fn agent_scratch_import(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    options: AgentScratchImportOptions,
+) -> CmdResult<Vec<ScratchImport>> {
+    let dir = session_dir(&app, &options.session_id)?;
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let mut imports = Vec::with_capacity(options.paths.len());
+    for path in options.paths {
+        let result = VaultPath::parse(&path)
+            .map_err(CommandError::from)
+            .and_then(|relative| {
+                let abs_path = relative.to_abs(&dir);
+                let name = abs_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .ok_or_else(|| {
+                        CommandError::localized(ErrorCode::InvalidInput, "error.not_a_file", &[])
+                    })?;
+                if is_symlinked(&dir, &relative) {
+                    return Err(CommandError::localized(
+                        ErrorCode::InvalidInput,
+                        "error.symlink_not_imported",
+                        &[],
+                    ));
+                }
+                let content = std::fs::read_to_string(&abs_path)?;
+                let created = ctx.create_doc(options.folder_path.as_str(), &name, None)?;
+                ctx.save_doc_content(&created.rel_path, &content, None)?;
+                Ok(created.rel_path)
+            });
+        imports.push(match result {
+            Ok(doc_path) => {
+                queue_doc_index(&app, &doc_path);
+                ScratchImport {
+                    path,
+                    doc_path: Some(doc_path),
+                    error: None,
+                }
+            }
+            Err(e) => ScratchImport {
+                path,
+                doc_path: None,
+                error: Some(e.message),
+            },
+        });
+    }
+    Ok(imports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_dir_name_keeps_ids_inside_the_root() {
+        assert_eq!(
+            session_dir_name("codex-1700000000"),
+            Some("codex-1700000000".to_string())
+        );
+        assert_eq!(session_dir_name("../etc"), Some("___etc".to_string()));
+        assert_eq!(session_dir_name(""), None);
+    }
+}
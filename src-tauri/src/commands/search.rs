@@ -1,84 +1,567 @@
-use crate::utils::{map_err, CmdResult};
+use crate::app_events::EmitScoped;
+use crate::chat::ChatMessage;
+use crate::commands::ai::{ai_configured, complete};
+use crate::i18n;
+use crate::index_schedule;
+use crate::services::ServiceKind;
+use crate::tasks::{ProgressThrottle, TaskKind};
+use crate::utils::{map_err, set_config_value, CmdResult, CommandError, ErrorCode};
 use crate::AppState;
-use opencontext_core::search::{IndexStats, Indexer, SearchOptions, SearchResults, Searcher};
+use opencontext_core::events::Event;
+use opencontext_core::search::{
+    ConfigIssue, DocGroup, DocIndexInspection, DocIndexSummary, EmbeddingUsage, IndexProgress,
+    IndexStats, Indexer, SearchConfig, SearchError, SearchHit, SearchMode, SearchOptions,
+    SearchResults, Searcher, UsageLedger, VectorStore,
+};
+use opencontext_core::{Doc, VaultPath};
 use serde::{Deserialize, Serialize};
-use tauri::{Emitter, State};
+use std::path::{Path, PathBuf};
+use tauri::{Emitter, Manager, State};
+use tokio::sync::broadcast::error::RecvError;
 
 #[tauri::command]
 pub(crate) async fn semantic_search(
     state: State<'_, AppState>,
     options: SearchOptions,
 ) -> CmdResult<SearchResults> {
-    let mut searcher_guard = state.searcher.lock().await;
+    run_search(&state, options).await
+}
+
+// ===== Query Expansion =====
+
+/// Paraphrases requested from the chat model for `expand_query`
+const EXPANSION_VARIANTS: usize = 3;
+const EXPANSION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Paraphrases in a reply holding a JSON array of strings; empty if the
+/// reply has none
+fn parse_query_variants(reply: &str) -> Vec<String> {
+    let (Some(start), Some(end)) = (reply.find('['), reply.rfind(']')) else {
+        return Vec::new();
+    };
+    reply
+        .get(start..=end)
+        .and_then(|json| serde_json::from_str::<Vec<String>>(json).ok())
+        .unwrap_or_default()
+}
+
+/// Ask the chat model for paraphrases of `query`. Expansion is best effort:
+/// any failure just means searching the query alone.
+async fn expand_query(query: &str) -> Vec<String> {
+    let prompt = format!(
+        "Write {} alternative phrasings of this search query that someone might use in \
+         their notes, using different words for the same meaning. Reply with only a JSON \
+         array of strings.\n\nQuery: {}",
+        EXPANSION_VARIANTS, query
+    );
+    let messages = [ChatMessage {
+        role: "user".to_string(),
+        content: serde_json::Value::String(prompt),
+    }];
+    match complete(&messages, EXPANSION_TIMEOUT).await {
+        Ok(reply) => parse_query_variants(&reply),
+        Err(e) => {
+            log::warn!("[Search] Query expansion failed: {}", e.message);
+            Vec::new()
+        }
+    }
+}
+
+// ===== Rerank =====
+
+/// Top results sent to the chat model when `rerank` is set
+const RERANK_CANDIDATES: usize = 20;
+/// Characters of each result sent for reranking, which bounds the prompt
+const RERANK_CHUNK_CHARS: usize = 500;
+const RERANK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+
+fn build_rerank_prompt(query: &str, hits: &[SearchHit]) -> String {
+    let mut prompt = format!(
+        "Rate how relevant each passage is to the search query, from 0 (unrelated) to 10 \
+         (answers it directly). Reply with only a JSON array of {} numbers, one per passage, \
+         in order.\n\nQuery: {}\n",
+        hits.len(),
+        query
+    );
+    for (i, hit) in hits.iter().enumerate() {
+        let text: String = hit
+            .content
+            .trim()
+            .chars()
+            .take(RERANK_CHUNK_CHARS)
+            .collect();
+        prompt.push_str(&format!("\n[{}] {}\n", i + 1, text));
+    }
+    prompt
+}
+
+/// The model's scores, one per passage. `None` unless the reply contains a
+/// JSON array of exactly `count` numbers.
+fn parse_rerank_scores(reply: &str, count: usize) -> Option<Vec<f32>> {
+    let start = reply.find('[')?;
+    let end = reply.rfind(']')?;
+    let scores: Vec<f32> = serde_json::from_str(reply.get(start..=end)?).ok()?;
+    (scores.len() == count).then_some(scores)
+}
+
+/// Sort the scored head of `hits` by rerank score. Ties keep retrieval
+/// order, and hits past the head stay where they are.
+fn apply_rerank_scores(hits: &mut [SearchHit], scores: &[f32]) {
+    for (hit, score) in hits.iter_mut().zip(scores) {
+        hit.rerank_score = Some((score / 10.0).clamp(0.0, 1.0));
+    }
+    hits[..scores.len()].sort_by(|a, b| {
+        b.rerank_score
+            .partial_cmp(&a.rerank_score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
 
-    if searcher_guard.is_none() {
-        let searcher = Searcher::new(state.search_config.clone())
-            .await
-            .map_err(map_err)?;
-        *searcher_guard = Some(searcher);
+/// Sort the scored head of `groups` by the rerank score of each group's
+/// best hit, and list the hits again in the new group order
+fn apply_group_rerank_scores(groups: &mut [DocGroup], scores: &[f32]) -> Vec<SearchHit> {
+    for (group, score) in groups.iter_mut().zip(scores) {
+        if let Some(best) = group.hits.first_mut() {
+            best.rerank_score = Some((score / 10.0).clamp(0.0, 1.0));
+        }
     }
+    let rerank_score = |group: &DocGroup| group.hits.first().and_then(|hit| hit.rerank_score);
+    groups[..scores.len()].sort_by(|a, b| {
+        rerank_score(b)
+            .partial_cmp(&rerank_score(a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    groups
+        .iter()
+        .flat_map(|group| group.hits.iter().cloned())
+        .collect()
+}
 
-    let searcher = searcher_guard.as_ref().unwrap();
-    searcher.search(options).await.map_err(map_err)
+/// Re-order the top results with the configured chat model. Grouped
+/// results are re-ordered by group, rating each doc's best hit. A failed or
+/// unparseable rerank keeps the retrieval order and sets `rerank_warning`.
+async fn rerank_results(results: &mut SearchResults) {
+    let candidates: Vec<SearchHit> = match &results.groups {
+        Some(groups) => groups
+            .iter()
+            .filter_map(|group| group.hits.first().cloned())
+            .take(RERANK_CANDIDATES)
+            .collect(),
+        None => results
+            .results
+            .iter()
+            .take(RERANK_CANDIDATES)
+            .cloned()
+            .collect(),
+    };
+    let count = candidates.len();
+    if count < 2 {
+        return;
+    }
+    let prompt = build_rerank_prompt(&results.query, &candidates);
+    let messages = [ChatMessage {
+        role: "user".to_string(),
+        content: serde_json::Value::String(prompt),
+    }];
+    let scores = match complete(&messages, RERANK_TIMEOUT).await {
+        Ok(reply) => parse_rerank_scores(&reply, count)
+            .ok_or_else(|| "Rerank reply could not be parsed".to_string()),
+        Err(e) => Err(e.message),
+    };
+    match (scores, &mut results.groups) {
+        (Ok(scores), Some(groups)) => {
+            results.results = apply_group_rerank_scores(groups, &scores);
+        }
+        (Ok(scores), None) => apply_rerank_scores(&mut results.results, &scores),
+        (Err(reason), _) => {
+            log::warn!(
+                "[Search] Rerank failed, keeping retrieval order: {}",
+                reason
+            );
+            results.rerank_warning = Some(reason);
+        }
+    }
+}
+
+// ===== Agent Search Bridge =====
+
+/// Maximum snippet length (in characters) returned to agents.
+const OC_SEARCH_SNIPPET_CHARS: usize = 400;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OcSearchOptions {
+    query: String,
+    limit: Option<usize>,
+    mode: Option<SearchMode>,
+    doc_type: Option<String>,
+    #[serde(default)]
+    rerank: bool,
+    #[serde(default)]
+    expand_query: bool,
+    /// Drop vector hits less similar than this (0-1)
+    min_score: Option<f32>,
+}
+
+/// One hit in the agent search contract.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OcSearchHit {
+    /// Stable document id (`oc doc get --id`), `null` if the doc is not tracked
+    doc_id: Option<String>,
+    title: String,
+    /// Path relative to the contexts root
+    path: String,
+    score: f32,
+    /// Vector similarity to the query (0-1), `null` for keyword-only hits
+    similarity: Option<f32>,
+    snippet: String,
+    heading_path: Option<String>,
+    /// Headings the matched chunk sits under, outermost first
+    headings: Vec<String>,
+    line_start: Option<usize>,
+    line_end: Option<usize>,
+    /// Byte range of the matched chunk in the doc (end exclusive)
+    byte_start: Option<usize>,
+    byte_end: Option<usize>,
+    /// Fence language of the first code block in the matched chunk
+    code_language: Option<String>,
+    /// Doc modified time when it was indexed (ms since epoch)
+    doc_modified_at: Option<u64>,
+}
+
+/// Agent search contract. The shape is stable; new fields may be added but
+/// existing ones are never renamed or removed:
+///
+/// ```json
+/// {
+///   "query": "string",
+///   "count": 0,
+///   "indexMissing": false,
+///   "noConfidentMatch": false,
+///   "results": [{
+///     "docId": "string | null",
+///     "title": "string",
+///     "path": "string",
+///     "score": 0.0,
+///     "similarity": "number | null",
+///     "snippet": "string",
+///     "headingPath": "string | null",
+///     "headings": ["string"],
+///     "lineStart": "number | null",
+///     "lineEnd": "number | null",
+///     "byteStart": "number | null",
+///     "byteEnd": "number | null",
+///     "codeLanguage": "string | null",
+///     "docModifiedAt": "number | null"
+///   }]
+/// }
+/// ```
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OcSearchResponse {
+    query: String,
+    count: usize,
+    index_missing: bool,
+    /// Every hit fell below `minScore`
+    no_confident_match: bool,
+    results: Vec<OcSearchHit>,
+}
+
+pub(crate) fn truncate_snippet(content: &str) -> String {
+    let trimmed = content.trim();
+    match trimmed.char_indices().nth(OC_SEARCH_SNIPPET_CHARS) {
+        Some((idx, _)) => format!("{}…", &trimmed[..idx]),
+        None => trimmed.to_string(),
+    }
+}
+
+/// Search with the shared `Searcher`, creating it on first use. Expands
+/// the query and reranks the results if the options ask for it.
+pub(crate) async fn run_search(
+    state: &AppState,
+    mut options: SearchOptions,
+) -> CmdResult<SearchResults> {
+    if options.expand_query && options.query_variants.is_empty() && ai_configured() {
+        options.query_variants = expand_query(options.query.trim()).await;
+    }
+    let rerank = options.rerank;
+    let mut results = {
+        let mut searcher_guard = state.searcher.lock().await;
+        if searcher_guard.is_none() {
+            let contexts_root = {
+                let ctx = state.ctx.read().map_err(map_err)?;
+                ctx.env_info().contexts_root
+            };
+            let searcher = Searcher::new(state.search_config())
+                .await?
+                .with_contexts_root(contexts_root);
+            *searcher_guard = Some(searcher);
+        }
+        let searcher = searcher_guard.as_ref().unwrap();
+        searcher.search(options).await?
+    };
+    if rerank && results.error.is_none() {
+        rerank_results(&mut results).await;
+    }
+    Ok(results)
+}
+
+/// Search through the in-process `Searcher` and return the agent contract
+/// shape, so agents don't depend on the `oc` binary or its text output.
+#[tauri::command]
+pub(crate) async fn oc_search(
+    state: State<'_, AppState>,
+    options: OcSearchOptions,
+) -> CmdResult<OcSearchResponse> {
+    let results = run_search(
+        &state,
+        SearchOptions {
+            query: options.query,
+            limit: options.limit,
+            mode: options.mode,
+            doc_type: options.doc_type,
+            rerank: options.rerank,
+            expand_query: options.expand_query,
+            min_score: options.min_score,
+            ..Default::default()
+        },
+    )
+    .await?;
+    if let Some(error) = results.error {
+        return Err(error.into());
+    }
+
+    let hits = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        results
+            .results
+            .into_iter()
+            .map(|hit| {
+                let doc = ctx.get_doc_meta(&hit.file_path).ok();
+                OcSearchHit {
+                    doc_id: doc.as_ref().map(|d| d.stable_id.clone()),
+                    title: doc.map(|d| d.name).unwrap_or(hit.display_name),
+                    snippet: truncate_snippet(&hit.content),
+                    path: hit.file_path,
+                    score: hit.score,
+                    similarity: hit.similarity,
+                    heading_path: hit.heading_path,
+                    headings: hit.headings,
+                    line_start: hit.line_start,
+                    line_end: hit.line_end,
+                    byte_start: hit.byte_start,
+                    byte_end: hit.byte_end,
+                    code_language: hit.code_language,
+                    doc_modified_at: hit.doc_modified_at,
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    Ok(OcSearchResponse {
+        query: results.query,
+        count: hits.len(),
+        index_missing: results.index_missing.unwrap_or(false),
+        no_confident_match: results.no_confident_match.unwrap_or(false),
+        results: hits,
+    })
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[allow(dead_code)]
 pub(crate) struct BuildIndexOptions {
+    #[allow(dead_code)]
     folder_path: Option<String>,
+    /// Rebuild every doc, not just the ones changed since the last build,
+    /// embedding every chunk again instead of taking vectors from the
+    /// embedding cache
+    #[serde(default)]
+    force: bool,
+    /// Only re-chunk and re-embed docs changed since they were indexed,
+    /// which is what a build does by default. `false` is the same as `force`.
+    incremental: Option<bool>,
 }
 
 #[tauri::command]
 pub(crate) async fn build_search_index(
     window: tauri::Window,
-    state: State<'_, AppState>,
-    _options: Option<BuildIndexOptions>,
+    options: Option<BuildIndexOptions>,
 ) -> CmdResult<IndexStats> {
-    let contexts_root = {
-        let ctx = state.ctx.lock().map_err(map_err)?;
-        ctx.env_info().contexts_root
-    };
+    let force = options.is_some_and(|options| options.force || options.incremental == Some(false));
+    run_index_build(window.app_handle(), force).await
+}
 
-    let docs = {
-        let ctx = state.ctx.lock().map_err(map_err)?;
-        let folders = ctx.list_folders(true).map_err(map_err)?;
-        let mut all_docs = Vec::new();
-        for folder in folders {
-            if let Ok(docs) = ctx.list_docs(&folder.rel_path, false) {
-                all_docs.extend(docs);
-            }
+/// Ask the running index build to stop once its current embedding batch is
+/// stored. Returns false if no build is running.
+#[tauri::command]
+pub(crate) fn cancel_search_index(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> CmdResult<bool> {
+    let build = state
+        .tasks
+        .list()
+        .into_iter()
+        .find(|task| task.kind == TaskKind::IndexBuild);
+    Ok(build.is_some_and(|task| state.tasks.cancel(&app, task.id)))
+}
+
+/// Send `index-progress` at most this often during a build
+const INDEX_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+/// ...or after this many more docs or chunks, whichever comes first
+const INDEX_PROGRESS_EVERY: usize = 50;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexProgressEvent<'a> {
+    #[serde(flatten)]
+    progress: &'a IndexProgress,
+    /// The progress message in the current locale
+    message: String,
+    /// Estimated time left, from the chunks embedded (or docs chunked) so far
+    eta_ms: Option<u64>,
+}
+
+/// `progress`'s message in the current locale, with its counts filled in
+fn index_progress_message(progress: &IndexProgress) -> String {
+    let count = |value: usize| value.to_string();
+    i18n::t(
+        progress.message_key,
+        &[
+            ("docsProcessed", &count(progress.docs_processed)),
+            ("docsTotal", &count(progress.docs_total)),
+            ("chunksEmbedded", &count(progress.chunks_embedded)),
+            ("chunksTotal", &count(progress.chunks_total)),
+            ("chunksStored", &count(progress.chunks_stored)),
+        ],
+    )
+}
+
+/// Progress callback for a build that emits throttled `index-progress`
+/// events through `emitter` and passes each emitted update to `on_sent`
+fn index_progress_emitter<R: tauri::Runtime>(
+    emitter: impl Emitter<R>,
+    mut on_sent: impl FnMut(IndexProgress, String),
+) -> impl FnMut(IndexProgress) {
+    let mut throttle = ProgressThrottle::new(INDEX_PROGRESS_INTERVAL, INDEX_PROGRESS_EVERY);
+    move |progress| {
+        // Builds are paced by embedding, so once the chunk count is known the
+        // throttle and ETA follow chunks rather than docs
+        let (done, total) = if progress.chunks_total > 0 {
+            (progress.chunks_embedded, progress.chunks_total)
+        } else if progress.phase == "done" {
+            (progress.docs_total, progress.docs_total)
+        } else {
+            (progress.docs_processed, progress.docs_total)
+        };
+        // A cancelled build sends nothing after this, so it always goes out
+        if progress.phase != "cancelled" && !throttle.should_send(done, total) {
+            return;
         }
-        all_docs
-    };
+        let event = IndexProgressEvent {
+            progress: &progress,
+            message: index_progress_message(&progress),
+            eta_ms: throttle.eta_ms(done, total),
+        };
+        let _ = emitter.emit_scoped("index-progress", &event);
+        on_sent(progress, event.message);
+    }
+}
 
-    let mut indexer_guard = state.indexer.lock().await;
+/// Rebuild the whole index as a tracked task, emitting `index-progress`.
+/// Only new and changed docs are embedded unless `force` is set. Fails with
+/// `conflict` while another build is running.
+pub(crate) async fn run_index_build(app: &tauri::AppHandle, force: bool) -> CmdResult<IndexStats> {
+    let state = app.state::<AppState>();
+    let task = state.tasks.start(app, TaskKind::IndexBuild)?;
+    // Not `task.run`: the build polls the task's cancel flag itself, so a
+    // cancel stops it between batches with what it stored kept
+    let result: CmdResult<IndexStats> = async {
+        let contexts_root = {
+            let ctx = state.ctx.read().map_err(map_err)?;
+            ctx.env_info().contexts_root
+        };
 
-    if indexer_guard.is_none() {
-        let indexer = Indexer::new(state.search_config.clone(), contexts_root)
-            .await
-            .map_err(map_err)?;
-        *indexer_guard = Some(indexer);
+        let docs = list_all_docs(&state)?;
+
+        let mut indexer_guard = state.indexer.lock().await;
+
+        if indexer_guard.is_none() {
+            let indexer = Indexer::new(state.search_config(), contexts_root).await?;
+            *indexer_guard = Some(indexer);
+        }
+
+        let indexer = indexer_guard.as_mut().unwrap();
+
+        let result = indexer
+            .build_all_with_progress(
+                docs,
+                index_progress_emitter(app.clone(), |progress, message| {
+                    task.progress(progress.current, progress.total, Some(message));
+                }),
+                task.token().flag(),
+                force,
+            )
+            .await?;
+        Ok(result)
     }
+    .await;
 
-    let indexer = indexer_guard.as_mut().unwrap();
+    match &result {
+        // A partial index is not a full build; drop the cached searcher so
+        // it reloads what was stored
+        Ok(stats) if stats.cancelled => *state.searcher.lock().await = None,
+        Ok(stats) => write_full_build_metadata(&state.search_config(), stats),
+        Err(_) => {}
+    }
+    match &result {
+        Ok(stats) if stats.cancelled => task.finish(&Err::<(), _>(CommandError::localized(
+            ErrorCode::Cancelled,
+            "error.index_build_cancelled",
+            &[],
+        ))),
+        _ => task.finish(&result),
+    }
+    result
+}
 
-    let result = indexer
-        .build_all_with_progress(docs, |progress| {
-            let _ = window.emit("index-progress", &progress);
-        })
-        .await
-        .map_err(map_err)?;
+pub(crate) fn list_all_docs(state: &AppState) -> CmdResult<Vec<Doc>> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let folders = ctx.list_folders(true)?;
+    let mut all_docs = Vec::new();
+    for folder in folders {
+        if let Ok(docs) = ctx.list_docs(&folder.rel_path, false) {
+            all_docs.extend(docs);
+        }
+    }
+    Ok(all_docs)
+}
 
-    let metadata_path = state.search_config.paths.get_index_metadata_path();
-    let metadata = serde_json::json!({
-        "lastFullBuild": std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64,
-        "totalChunks": result.total_chunks,
-        "totalDocs": result.total_docs,
-    });
+fn read_index_metadata(config: &SearchConfig) -> serde_json::Map<String, serde_json::Value> {
+    std::fs::read_to_string(config.paths.get_index_metadata_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Whether the index metadata records any chunks; cheap enough for status
+/// checks that shouldn't open the index
+pub(crate) fn index_has_chunks(config: &SearchConfig) -> bool {
+    read_index_metadata(config)
+        .get("totalChunks")
+        .and_then(|x| x.as_u64())
+        .is_some_and(|chunks| chunks > 0)
+}
+
+/// Set keys in the index metadata file, keeping the others
+pub(crate) fn update_index_metadata(
+    config: &SearchConfig,
+    values: serde_json::Map<String, serde_json::Value>,
+) {
+    let metadata_path = config.paths.get_index_metadata_path();
+    let mut metadata = read_index_metadata(config);
+    metadata.extend(values);
     if let Some(parent) = metadata_path.parent() {
         let _ = std::fs::create_dir_all(parent);
     }
@@ -86,8 +569,32 @@ pub(crate) async fn build_search_index(
         &metadata_path,
         serde_json::to_string_pretty(&metadata).unwrap_or_default(),
     );
+}
 
-    Ok(result)
+/// Last full build and last scheduled build attempt (ms since epoch)
+pub(crate) fn last_index_builds(config: &SearchConfig) -> (Option<u64>, Option<u64>) {
+    let metadata = read_index_metadata(config);
+    let read = |key: &str| metadata.get(key).and_then(|v| v.as_u64());
+    (read("lastFullBuild"), read("lastScheduledBuild"))
+}
+
+fn write_full_build_metadata(config: &SearchConfig, stats: &IndexStats) {
+    let metadata = serde_json::json!({
+        "lastFullBuild": std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+        "totalChunks": stats.total_chunks,
+        "totalDocs": stats.total_docs,
+        "skipped": stats.skipped,
+        "indexedDocs": stats.indexed_docs,
+        "skippedDocs": stats.skipped_docs,
+        "cacheHits": stats.cache_hits,
+        "cacheMisses": stats.cache_misses,
+    });
+    if let serde_json::Value::Object(values) = metadata {
+        update_index_metadata(config, values);
+    }
 }
 
 #[derive(Serialize)]
@@ -96,69 +603,797 @@ pub(crate) struct IndexStatus {
     exists: bool,
     chunk_count: usize,
     last_updated: Option<u64>,
+    /// Oldest and newest doc modified times the index was built from (ms
+    /// since epoch). An old newest value means the index is likely stale.
+    oldest_doc_modified_at: Option<u64>,
+    newest_doc_modified_at: Option<u64>,
+    /// When the scheduled rebuild runs next (ms since epoch), if one is
+    /// configured and the scheduler is running
+    next_scheduled_build: Option<u64>,
+    /// The index files can't be opened; `reset_corrupt_index` moves them
+    /// aside so the index can be rebuilt
+    corrupt: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    corrupt_reason: Option<String>,
+    /// The index was built with other chunking or embedding settings; a
+    /// full rebuild is needed for them to take effect
+    rebuild_needed: bool,
 }
 
 #[tauri::command]
 pub(crate) async fn get_index_status(state: State<'_, AppState>) -> CmdResult<IndexStatus> {
     let contexts_root = {
-        let ctx = state.ctx.lock().map_err(map_err)?;
+        let ctx = state.ctx.read().map_err(map_err)?;
         ctx.env_info().contexts_root
     };
 
     let mut indexer_guard = state.indexer.lock().await;
 
     if indexer_guard.is_none() {
-        let indexer = Indexer::new(state.search_config.clone(), contexts_root)
-            .await
-            .map_err(map_err)?;
-        *indexer_guard = Some(indexer);
+        match Indexer::new(state.search_config(), contexts_root).await {
+            Ok(indexer) => *indexer_guard = Some(indexer),
+            Err(SearchError::CorruptIndex { reason, .. }) => {
+                return Ok(IndexStatus {
+                    exists: true,
+                    chunk_count: 0,
+                    last_updated: None,
+                    oldest_doc_modified_at: None,
+                    newest_doc_modified_at: None,
+                    next_scheduled_build: None,
+                    corrupt: true,
+                    corrupt_reason: Some(reason),
+                    rebuild_needed: false,
+                });
+            }
+            Err(e) => return Err(e.into()),
+        }
     }
 
     let indexer = indexer_guard.as_ref().unwrap();
     let exists = indexer.index_exists().await;
-    let stats = indexer.get_stats().await.map_err(map_err)?;
-
-    let last_updated = {
-        let metadata_path = state.search_config.paths.get_index_metadata_path();
-        if metadata_path.exists() {
-            std::fs::read_to_string(&metadata_path)
-                .ok()
-                .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
-                .and_then(|v| {
-                    v.get("lastUpdated")
-                        .and_then(|x| x.as_u64())
-                        .or_else(|| v.get("lastFullBuild").and_then(|x| x.as_u64()))
-                })
-        } else {
-            None
-        }
+    let stats = indexer.get_stats().await?;
+    let doc_modified_range = indexer.doc_modified_range().await?;
+    let rebuild_needed = indexer.needs_rebuild().await;
+
+    let config = state.search_config();
+    let metadata = read_index_metadata(&config);
+    let last_updated = metadata
+        .get("lastUpdated")
+        .and_then(|x| x.as_u64())
+        .or_else(|| metadata.get("lastFullBuild").and_then(|x| x.as_u64()));
+    let next_scheduled_build = if state.services.is_running(ServiceKind::IndexSchedule) {
+        index_schedule::next_scheduled_build(&config)
+            .map(|next| next.max(chrono::Local::now()).timestamp_millis() as u64)
+    } else {
+        None
     };
 
     Ok(IndexStatus {
         exists,
         chunk_count: stats.total_chunks,
         last_updated,
+        oldest_doc_modified_at: doc_modified_range.map(|(oldest, _)| oldest),
+        newest_doc_modified_at: doc_modified_range.map(|(_, newest)| newest),
+        next_scheduled_build,
+        corrupt: false,
+        corrupt_reason: None,
+        rebuild_needed,
     })
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ResetCorruptIndexResult {
+    /// Where the damaged index was moved, if there was one
+    archived_to: Option<PathBuf>,
+}
+
+/// Move a damaged index aside and start an empty one in its place, so the
+/// index can be rebuilt. The old files are kept next to the new index.
+#[tauri::command]
+pub(crate) async fn reset_corrupt_index(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CmdResult<ResetCorruptIndexResult> {
+    let config = state.search_config();
+    let contexts_root = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.env_info().contexts_root
+    };
+
+    *state.searcher.lock().await = None;
+    let mut indexer_guard = state.indexer.lock().await;
+    *indexer_guard = None;
+    let archived_to = VectorStore::archive(&config.paths.get_lancedb_path())?;
+    *indexer_guard = Some(Indexer::new(config.clone(), contexts_root).await?);
+    drop(indexer_guard);
+
+    // The sync service holds its own indexer, and stops if it can't open one
+    if let Err(e) = state.index_sync.set_config(config).await {
+        log::warn!("[Search] Failed to reopen the index for sync: {}", e);
+    }
+    if state.services.failed(ServiceKind::IndexSync) {
+        state.services.start(&app, ServiceKind::IndexSync)?;
+    }
+    Ok(ResetCorruptIndexResult { archived_to })
+}
+
 #[tauri::command]
 pub(crate) async fn clean_search_index(state: State<'_, AppState>) -> CmdResult<bool> {
     let contexts_root = {
-        let ctx = state.ctx.lock().map_err(map_err)?;
+        let ctx = state.ctx.read().map_err(map_err)?;
         ctx.env_info().contexts_root
     };
 
     let mut indexer_guard = state.indexer.lock().await;
 
     if indexer_guard.is_none() {
-        let indexer = Indexer::new(state.search_config.clone(), contexts_root)
-            .await
-            .map_err(map_err)?;
+        let indexer = Indexer::new(state.search_config(), contexts_root).await?;
         *indexer_guard = Some(indexer);
     }
 
     let indexer = indexer_guard.as_mut().unwrap();
-    indexer.clean().await.map_err(map_err)?;
+    indexer.clean().await?;
 
     Ok(true)
 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InspectDocIndexOptions {
+    path: VaultPath,
+}
+
+/// How a doc was chunked into the index, and why it has no chunks if so
+#[tauri::command]
+pub(crate) async fn inspect_doc_index(
+    state: State<'_, AppState>,
+    options: InspectDocIndexOptions,
+) -> CmdResult<DocIndexInspection> {
+    let contexts_root = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.env_info().contexts_root
+    };
+
+    let mut indexer_guard = state.indexer.lock().await;
+
+    if indexer_guard.is_none() {
+        let indexer = Indexer::new(state.search_config(), contexts_root).await?;
+        *indexer_guard = Some(indexer);
+    }
+
+    let indexer = indexer_guard.as_mut().unwrap();
+    Ok(indexer.inspect_doc(options.path.as_str()).await?)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocIndexStatusOptions {
+    paths: Vec<VaultPath>,
+}
+
+/// Whether each doc is indexed, its chunk count, and whether it changed
+/// since, for marking stale docs in the tree. Reads each index once.
+#[tauri::command]
+pub(crate) async fn get_doc_index_status(
+    state: State<'_, AppState>,
+    options: DocIndexStatusOptions,
+) -> CmdResult<Vec<DocIndexSummary>> {
+    let contexts_root = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.env_info().contexts_root
+    };
+
+    let mut indexer_guard = state.indexer.lock().await;
+
+    if indexer_guard.is_none() {
+        let indexer = Indexer::new(state.search_config(), contexts_root).await?;
+        *indexer_guard = Some(indexer);
+    }
+
+    let indexer = indexer_guard.as_mut().unwrap();
+    let paths: Vec<String> = options
+        .paths
+        .iter()
+        .map(|path| path.as_str().to_string())
+        .collect();
+    Ok(indexer.doc_summaries(&paths).await?)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct IndexDocOptions {
+    doc_path: VaultPath,
+}
+
+/// Re-index one doc without rebuilding the index. Returns its chunk count;
+/// a doc deleted since it was listed counts 0.
+#[tauri::command]
+pub(crate) async fn index_doc(
+    state: State<'_, AppState>,
+    options: IndexDocOptions,
+) -> CmdResult<usize> {
+    let doc = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        match ctx
+            .get_doc_meta(options.doc_path.as_str())
+            .map_err(CommandError::from)
+        {
+            Ok(doc) => doc,
+            // Its deletion takes it out of the index
+            Err(e) if e.code == ErrorCode::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        }
+    };
+    Ok(state.index_sync.index_doc(&doc).await?)
+}
+
+/// Have the sync service index `path` as soon as its change comes in,
+/// rather than with its next batch, so an edit shows up in search right
+/// away. Does nothing until an index has been built.
+pub(crate) fn queue_doc_index(app: &tauri::AppHandle, path: &str) {
+    let app = app.clone();
+    let path = path.to_string();
+    tauri::async_runtime::spawn(async move {
+        app.state::<AppState>().index_sync.prioritize(&path).await;
+    });
+}
+
+// ===== Embedding Usage =====
+
+fn usage_ledger(config: &SearchConfig) -> UsageLedger {
+    UsageLedger::new(config.paths.get_usage_ledger_path())
+}
+
+/// Tokens spent on embeddings per day and month, with a cost estimate from
+/// the `EMBEDDING_PRICES` price table
+#[tauri::command]
+pub(crate) fn embedding_usage(state: State<AppState>) -> CmdResult<EmbeddingUsage> {
+    let config = state.search_config();
+    Ok(usage_ledger(&config).summary(&config.prices))
+}
+
+#[tauri::command]
+pub(crate) fn reset_usage_ledger(state: State<AppState>) -> CmdResult<()> {
+    usage_ledger(&state.search_config()).reset()?;
+    Ok(())
+}
+
+// ===== Embedding Migration =====
+
+/// Embedding settings a migration switches to
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MigrateEmbeddingsOptions {
+    model: String,
+    api_base: Option<String>,
+    api_key: Option<String>,
+    /// Wait for `apply_embedding_migration` instead of swapping on completion
+    #[serde(default)]
+    confirm: bool,
+}
+
+pub(crate) struct StagedMigration {
+    options: MigrateEmbeddingsOptions,
+    lancedb_path: PathBuf,
+    stats: IndexStats,
+}
+
+/// Progress of a background re-embed. At most one runs at a time.
+#[derive(Default)]
+pub(crate) enum EmbeddingMigration {
+    #[default]
+    Idle,
+    Building,
+    /// Built and waiting for the user to apply or discard it
+    Ready(StagedMigration),
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MigrationStatusEvent {
+    /// `building` | `ready` | `completed` | `failed` | `cancelled` | `discarded`
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<IndexStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn emit_migration_status(
+    app: &tauri::AppHandle,
+    status: &'static str,
+    stats: Option<IndexStats>,
+    error: Option<String>,
+) {
+    let _ = app.emit_scoped(
+        "embedding-migration",
+        MigrationStatusEvent {
+            status,
+            stats,
+            error,
+        },
+    );
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "lancedb".to_string());
+    path.with_file_name(format!("{}-{}", name, suffix))
+}
+
+/// Re-embed every document with a new model into a staging index. The live
+/// index keeps serving searches until the staging one is swapped in; if the
+/// build fails the staging index is deleted and nothing changes. Progress is
+/// reported through `index-progress`, status through `embedding-migration`.
+/// The build runs as a task, so cancelling it drops the staging index.
+///
+/// Docs in folders assigned to an embedding profile keep their profile index.
+#[tauri::command]
+pub(crate) async fn migrate_embeddings(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    options: MigrateEmbeddingsOptions,
+) -> CmdResult<()> {
+    if options.model.trim().is_empty() {
+        return Err(CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.embedding_model_required",
+            &[],
+        ));
+    }
+    let live = state.search_config();
+    let contexts_root = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.env_info().contexts_root
+    };
+    let docs: Vec<Doc> = list_all_docs(&state)?
+        .into_iter()
+        .filter(|doc| live.profile_for_path(&doc.rel_path).is_none())
+        .collect();
+    // Nothing after this returns early: the spawned build moves the state
+    // on, or back to idle when it fails
+    {
+        let mut migration = state.embedding_migration.lock().map_err(map_err)?;
+        if !matches!(*migration, EmbeddingMigration::Idle) {
+            return Err(CommandError::localized(
+                ErrorCode::Conflict,
+                "error.embedding_migration_running",
+                &[],
+            ));
+        }
+        *migration = EmbeddingMigration::Building;
+    }
+    let app = window.app_handle().clone();
+    let task = match state.tasks.start(&app, TaskKind::EmbeddingMigration) {
+        Ok(task) => task,
+        Err(e) => {
+            *state.embedding_migration.lock().map_err(map_err)? = EmbeddingMigration::Idle;
+            return Err(e);
+        }
+    };
+
+    let lancedb_path = sibling_path(&live.paths.get_lancedb_path(), "migrating");
+    let mut staging = live.clone();
+    staging.embedding.model = options.model.clone();
+    if let Some(base) = options.api_base.clone().filter(|b| !b.is_empty()) {
+        staging.embedding.api_base = base;
+    }
+    if let Some(key) = options.api_key.clone().filter(|k| !k.is_empty()) {
+        staging.embedding.api_key = Some(key);
+    }
+    staging.paths.lancedb_path = Some(lancedb_path.clone());
+    staging.paths.index_metadata_path = Some(lancedb_path.join("index-metadata.json"));
+    staging.folder_profiles.clear();
+
+    emit_migration_status(&app, "building", None, None);
+    tauri::async_runtime::spawn(async move {
+        let _ = std::fs::remove_dir_all(&lancedb_path);
+        let built: CmdResult<IndexStats> = async {
+            let mut indexer = Indexer::new(staging, contexts_root).await?;
+            let stats = indexer
+                .build_all_with_progress(
+                    docs,
+                    index_progress_emitter(window.clone(), |progress, message| {
+                        task.progress(progress.current, progress.total, Some(message));
+                    }),
+                    task.token().flag(),
+                    true,
+                )
+                .await?;
+            // A partial staging index must not replace the live one
+            if stats.cancelled {
+                return Err(CommandError::localized(
+                    ErrorCode::Cancelled,
+                    "error.task_cancelled",
+                    &[],
+                ));
+            }
+            Ok(stats)
+        }
+        .await;
+        task.finish(&built);
+
+        let state = app.state::<AppState>();
+        let stats = match built {
+            Ok(stats) => stats,
+            Err(e) => {
+                let status = if e.code == ErrorCode::Cancelled {
+                    log::info!("[Migration] Re-embedding cancelled");
+                    "cancelled"
+                } else {
+                    log::error!("[Migration] Re-embedding failed: {}", e);
+                    "failed"
+                };
+                let _ = std::fs::remove_dir_all(&lancedb_path);
+                if let Ok(mut migration) = state.embedding_migration.lock() {
+                    *migration = EmbeddingMigration::Idle;
+                }
+                let error = (status == "failed").then_some(e.message);
+                emit_migration_status(&app, status, None, error);
+                return;
+            }
+        };
+
+        let staged = StagedMigration {
+            options,
+            lancedb_path,
+            stats,
+        };
+        if staged.options.confirm {
+            let stats = staged.stats.clone();
+            if let Ok(mut migration) = state.embedding_migration.lock() {
+                *migration = EmbeddingMigration::Ready(staged);
+            }
+            emit_migration_status(&app, "ready", Some(stats), None);
+            return;
+        }
+        finish_migration(&app, staged).await;
+    });
+
+    Ok(())
+}
+
+/// Swap in a migration that was built with `confirm: true`
+#[tauri::command]
+pub(crate) async fn apply_embedding_migration(app: tauri::AppHandle) -> CmdResult<()> {
+    let staged = {
+        let state = app.state::<AppState>();
+        let mut migration = state.embedding_migration.lock().map_err(map_err)?;
+        match std::mem::take(&mut *migration) {
+            EmbeddingMigration::Ready(staged) => {
+                *migration = EmbeddingMigration::Building;
+                staged
+            }
+            other => {
+                *migration = other;
+                return Err(CommandError::localized(
+                    ErrorCode::Conflict,
+                    "error.embedding_migration_unfinished",
+                    &[],
+                ));
+            }
+        }
+    };
+    finish_migration(&app, staged).await;
+    Ok(())
+}
+
+/// Delete a migration that was built with `confirm: true`
+#[tauri::command]
+pub(crate) fn discard_embedding_migration(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CmdResult<()> {
+    let mut migration = state.embedding_migration.lock().map_err(map_err)?;
+    if let EmbeddingMigration::Ready(staged) = std::mem::take(&mut *migration) {
+        std::fs::remove_dir_all(&staged.lancedb_path)?;
+        emit_migration_status(&app, "discarded", None, None);
+    }
+    Ok(())
+}
+
+async fn finish_migration(app: &tauri::AppHandle, staged: StagedMigration) {
+    let stats = staged.stats.clone();
+    let result = swap_in_migration(app, staged).await;
+    if let Ok(mut migration) = app.state::<AppState>().embedding_migration.lock() {
+        *migration = EmbeddingMigration::Idle;
+    }
+    match result {
+        Ok(()) => {
+            log::info!("[Migration] Switched to the re-embedded index");
+            emit_migration_status(app, "completed", Some(stats), None);
+        }
+        Err(e) => {
+            log::error!("[Migration] Failed to swap indexes: {}", e);
+            emit_migration_status(app, "failed", None, Some(e.message));
+        }
+    }
+}
+
+/// Replace the live index with the staged one and persist the new settings.
+/// The old index is restored if the swap fails. Background indexing is
+/// paused meanwhile, queueing changes for after the swap.
+async fn swap_in_migration(app: &tauri::AppHandle, staged: StagedMigration) -> CmdResult<()> {
+    let state = app.state::<AppState>();
+    let was_paused = state.index_sync.is_paused();
+    state.index_sync.set_paused(true);
+    let result = swap_index_files(app, staged).await;
+    if let Err(e) = state.index_sync.reopen_index().await {
+        log::warn!("[Migration] Reopening the index for sync failed: {}", e);
+    }
+    state.index_sync.set_paused(was_paused);
+    result
+}
+
+async fn swap_index_files(app: &tauri::AppHandle, staged: StagedMigration) -> CmdResult<()> {
+    let state = app.state::<AppState>();
+    let live_path = state.search_config().paths.get_lancedb_path();
+    let previous_path = sibling_path(&live_path, "previous");
+    {
+        // Hold both locks so nothing reads or writes the index mid-swap.
+        let mut searcher = state.searcher.lock().await;
+        let mut indexer = state.indexer.lock().await;
+        *searcher = None;
+        *indexer = None;
+        state.index_sync.close_index().await;
+
+        if previous_path.exists() {
+            std::fs::remove_dir_all(&previous_path)?;
+        }
+        if live_path.exists() {
+            std::fs::rename(&live_path, &previous_path)?;
+        }
+        if let Err(e) = std::fs::rename(&staged.lancedb_path, &live_path) {
+            if previous_path.exists() {
+                let _ = std::fs::rename(&previous_path, &live_path);
+            }
+            let _ = std::fs::remove_dir_all(&staged.lancedb_path);
+            return Err(e.into());
+        }
+    }
+
+    let options = staged.options;
+    set_config_value("EMBEDDING_MODEL", serde_json::Value::String(options.model))?;
+    if let Some(base) = options.api_base.filter(|b| !b.is_empty()) {
+        set_config_value("EMBEDDING_API_BASE", serde_json::Value::String(base))?;
+    }
+    if let Some(key) = options.api_key.filter(|k| !k.is_empty()) {
+        set_config_value("EMBEDDING_API_KEY", serde_json::Value::String(key))?;
+    }
+    reload_search_config(app).await?;
+    write_full_build_metadata(&state.search_config(), &staged.stats);
+
+    let _ = std::fs::remove_dir_all(&previous_path);
+    Ok(())
+}
+
+// ===== Index Sync =====
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct IndexSyncStatus {
+    paused: bool,
+    pending_count: usize,
+}
+
+/// Pause or resume background indexing, persisting `INDEX_SYNC_PAUSED` and
+/// keeping the tray item, tooltip and frontend in sync.
+pub(crate) fn apply_index_sync_paused(app: &tauri::AppHandle, paused: bool) -> CmdResult<()> {
+    app.state::<AppState>().index_sync.set_paused(paused);
+    set_config_value("INDEX_SYNC_PAUSED", serde_json::Value::Bool(paused))?;
+    crate::sync_tray_indexing_state(app, paused);
+    let _ = app.emit_scoped("index-sync-paused", paused);
+    log::info!("[IndexSync] {}", if paused { "Paused" } else { "Resumed" });
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn get_index_sync_status(
+    state: State<'_, AppState>,
+) -> CmdResult<IndexSyncStatus> {
+    Ok(IndexSyncStatus {
+        paused: state.index_sync.is_paused(),
+        pending_count: state.index_sync.pending_count().await,
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetIndexSyncPausedOptions {
+    paused: bool,
+}
+
+#[tauri::command]
+pub(crate) fn set_index_sync_paused(
+    app: tauri::AppHandle,
+    options: SetIndexSyncPausedOptions,
+) -> CmdResult<bool> {
+    apply_index_sync_paused(&app, options.paused)?;
+    Ok(options.paused)
+}
+
+// ===== Config Reload =====
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ConfigReloaded {
+    /// Whether search settings differ from the previous config
+    changed: bool,
+    issues: Vec<ConfigIssue>,
+}
+
+/// Re-read the config files and apply them without a restart. When search
+/// settings changed, the cached searcher and indexers are dropped so the next
+/// search or index build uses the new embedding settings.
+pub(crate) async fn reload_search_config(app: &tauri::AppHandle) -> CmdResult<()> {
+    let state = app.state::<AppState>();
+    let (config, issues) = SearchConfig::load_with_issues();
+    let changed = {
+        let mut current = state.search_config.write().map_err(map_err)?;
+        let changed = *current != config;
+        *current = config.clone();
+        changed
+    };
+
+    if changed {
+        *state.searcher.lock().await = None;
+        *state.indexer.lock().await = None;
+        state.index_sync.set_config(config).await?;
+        log::info!("[Config] Search config reloaded");
+    }
+    let _ = app.emit_scoped("config-reloaded", ConfigReloaded { changed, issues });
+    Ok(())
+}
+
+/// Keep the cached searcher's keyword snapshot in step with renamed and
+/// moved folders, whose chunks the sync service repoints in the index.
+/// Runs until the event bus closes.
+pub(crate) async fn follow_folder_renames(app: tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let mut receiver = state.event_bus.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(Event::Folder(event)) => {
+                let Some((old_prefix, new_prefix)) = event.renamed_prefix() else {
+                    continue;
+                };
+                if let Some(searcher) = state.searcher.lock().await.as_mut() {
+                    searcher.rename_path_prefix(old_prefix, new_prefix);
+                }
+            }
+            Ok(Event::Doc(_)) => {}
+            // Renames may have been missed; reload the snapshot on next search
+            Err(RecvError::Lagged(_)) => *state.searcher.lock().await = None,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DocIndexed {
+    /// `None` when docs may have been re-indexed unreported
+    path: Option<String>,
+}
+
+/// Send a `doc-indexed` event for each doc the sync service re-indexes, so
+/// index status shown for it can be refreshed. Runs until the service is
+/// dropped.
+pub(crate) async fn follow_doc_indexing(app: tauri::AppHandle) {
+    let mut receiver = app.state::<AppState>().index_sync.subscribe_indexed();
+    loop {
+        let path = match receiver.recv().await {
+            Ok(path) => Some(path),
+            Err(RecvError::Lagged(_)) => None,
+            Err(RecvError::Closed) => break,
+        };
+        let _ = app.emit_scoped("doc-indexed", DocIndexed { path });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencontext_core::search::MatchType;
+
+    fn hit(path: &str) -> SearchHit {
+        SearchHit {
+            file_path: path.to_string(),
+            display_name: path.to_string(),
+            content: String::new(),
+            heading_path: None,
+            headings: Vec::new(),
+            section_title: None,
+            line_start: None,
+            line_end: None,
+            byte_start: None,
+            byte_end: None,
+            code_language: None,
+            score: 0.5,
+            similarity: Some(0.5),
+            matched_by: MatchType::Vector,
+            vector_score: None,
+            keyword_score: None,
+            hit_count: None,
+            doc_count: None,
+            folder_path: None,
+            aggregate_type: None,
+            doc_type: None,
+            entry_id: None,
+            entry_date: None,
+            entry_created_at: None,
+            idea_box: None,
+            doc_modified_at: None,
+            rerank_score: None,
+            matched_queries: Vec::new(),
+            highlights: Vec::new(),
+            snippet: None,
+        }
+    }
+
+    #[test]
+    fn index_progress_message_fills_in_the_counts() {
+        let progress = IndexProgress {
+            phase: "embedding".to_string(),
+            current: 3,
+            total: 10,
+            percent: 30,
+            message_key: "index.progress.embedding",
+            docs_processed: 2,
+            docs_total: 2,
+            chunks_stored: 0,
+            chunks_embedded: 3,
+            chunks_total: 10,
+        };
+        assert_eq!(index_progress_message(&progress), "Embedding (3/10 chunks)");
+    }
+
+    #[test]
+    fn parse_rerank_scores_requires_one_score_per_passage() {
+        assert_eq!(
+            parse_rerank_scores("Scores: [2, 9.5, 0]", 3),
+            Some(vec![2.0, 9.5, 0.0])
+        );
+        assert_eq!(parse_rerank_scores("[2, 9]", 3), None);
+        assert_eq!(parse_rerank_scores("no scores", 1), None);
+    }
+
+    #[test]
+    fn parse_query_variants_reads_json_array() {
+        assert_eq!(
+            parse_query_variants("Sure:\n[\"login failure\", \"sign-in error\"]"),
+            vec!["login failure".to_string(), "sign-in error".to_string()]
+        );
+        assert!(parse_query_variants("login failure").is_empty());
+    }
+
+    #[test]
+    fn apply_rerank_scores_sorts_only_the_scored_head() {
+        let mut hits = vec![hit("a"), hit("b"), hit("c"), hit("d")];
+        apply_rerank_scores(&mut hits, &[3.0, 8.0, 3.0]);
+        let order: Vec<&str> = hits.iter().map(|h| h.file_path.as_str()).collect();
+        assert_eq!(order, ["b", "a", "c", "d"]);
+        assert_eq!(hits[0].rerank_score, Some(0.8));
+        assert_eq!(hits[3].rerank_score, None);
+    }
+
+    #[test]
+    fn apply_group_rerank_scores_reorders_whole_groups() {
+        let group = |path: &str, chunks: usize| DocGroup {
+            file_path: path.to_string(),
+            display_name: path.to_string(),
+            score: 0.5,
+            hit_count: chunks,
+            hits: vec![hit(path); chunks],
+        };
+        let mut groups = vec![group("a", 2), group("b", 1), group("c", 1)];
+        let results = apply_group_rerank_scores(&mut groups, &[2.0, 9.0]);
+        let order: Vec<&str> = results.iter().map(|h| h.file_path.as_str()).collect();
+        assert_eq!(order, ["b", "a", "a", "c"]);
+        assert_eq!(groups[0].hits[0].rerank_score, Some(0.9));
+        assert_eq!(groups[1].hits[1].rerank_score, None);
+    }
+}
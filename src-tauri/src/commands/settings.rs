@@ -0,0 +1,369 @@
+use crate::utils::{map_err, mask_secret, read_config_json, redact, CmdResult};
+use crate::AppState;
+use opencontext_core::search::SearchConfig;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tauri::State;
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SettingSource {
+    Env,
+    File,
+    Default,
+}
+
+/// One resolved setting. Secrets are masked.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EffectiveSetting {
+    key: &'static str,
+    value: Value,
+    source: SettingSource,
+    /// File the value came from, for `file` sources
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    secret: bool,
+}
+
+/// Resolves each setting the same way the code that reads it does:
+/// environment variable, then config.json, then config.toml, then default.
+struct Resolver {
+    json: Value,
+    json_path: PathBuf,
+    toml_path: PathBuf,
+    settings: Vec<EffectiveSetting>,
+}
+
+impl Resolver {
+    fn push(
+        &mut self,
+        key: &'static str,
+        value: Value,
+        source: SettingSource,
+        path: Option<PathBuf>,
+    ) {
+        self.settings.push(EffectiveSetting {
+            key,
+            value,
+            source,
+            path,
+            secret: false,
+        });
+    }
+
+    fn json_value(&self, keys: &[&str]) -> Option<&Value> {
+        keys.iter()
+            .filter_map(|key| self.json.get(*key))
+            .find(|value| !value.is_null() && value.as_str() != Some(""))
+    }
+
+    fn env_value(keys: &[&str]) -> Option<String> {
+        keys.iter().find_map(|key| std::env::var(key).ok())
+    }
+
+    /// A key only read from config.json
+    fn file_setting(&mut self, key: &'static str, default: Value) {
+        match self.json_value(&[key]).cloned() {
+            Some(value) => {
+                let path = Some(self.json_path.clone());
+                self.push(key, value, SettingSource::File, path)
+            }
+            None => self.push(key, default, SettingSource::Default, None),
+        }
+    }
+
+    /// A search setting, reported with the value `SearchConfig` resolved.
+    /// Values not from env or config.json but differing from the built-in
+    /// default came from config.toml.
+    fn search_setting(
+        &mut self,
+        key: &'static str,
+        env_keys: &[&str],
+        json_keys: &[&str],
+        value: Value,
+        default: Value,
+    ) {
+        let (source, path) = if Self::env_value(env_keys).is_some() {
+            (SettingSource::Env, None)
+        } else if self.json_value(json_keys).is_some() {
+            (SettingSource::File, Some(self.json_path.clone()))
+        } else if value != default {
+            (SettingSource::File, Some(self.toml_path.clone()))
+        } else {
+            (SettingSource::Default, None)
+        };
+        self.push(key, value, source, path);
+    }
+
+    /// A path setting only overridable through the environment
+    fn env_path_setting(&mut self, key: &'static str, value: PathBuf) {
+        let source = if std::env::var_os(key).is_some() {
+            SettingSource::Env
+        } else {
+            SettingSource::Default
+        };
+        self.push(key, json!(value), source, None);
+    }
+
+    /// Mask the value of the most recently added setting
+    fn mark_secret(&mut self) {
+        if let Some(setting) = self.settings.last_mut() {
+            setting.secret = true;
+            setting.value = match setting.value.as_str() {
+                Some(value) if !value.is_empty() => json!(mask_secret(value)),
+                _ => Value::Null,
+            };
+        }
+    }
+
+    /// Mask the credentials (`user:pass@`) in the URL value of the most
+    /// recently added setting
+    fn mask_url_credentials(&mut self) {
+        let Some(setting) = self.settings.last_mut() else {
+            return;
+        };
+        let Some(mut url) = setting
+            .value
+            .as_str()
+            .and_then(|value| reqwest::Url::parse(value).ok())
+        else {
+            return;
+        };
+        if url.username().is_empty() && url.password().is_none() {
+            return;
+        }
+        let _ = url.set_username("****");
+        let _ = url.set_password(None);
+        setting.value = json!(url.as_str());
+    }
+}
+
+/// Every known setting with its resolved value and where it came from
+#[tauri::command]
+pub(crate) fn get_effective_config(state: State<AppState>) -> CmdResult<Vec<EffectiveSetting>> {
+    let env_info = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.env_info()
+    };
+    let config = state.search_config();
+    let defaults = SearchConfig::default();
+
+    let mut resolver = Resolver {
+        json: read_config_json().unwrap_or(Value::Null),
+        json_path: SearchConfig::json_config_path(),
+        toml_path: SearchConfig::toml_config_path(),
+        settings: Vec::new(),
+    };
+
+    // Storage
+    let base_root = SearchConfig::json_config_path()
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+    resolver.env_path_setting("OPENCONTEXT_ROOT", base_root);
+    resolver.env_path_setting("OPENCONTEXT_CONTEXTS_ROOT", env_info.contexts_root);
+    resolver.env_path_setting("OPENCONTEXT_DB_PATH", env_info.db_path);
+
+    // Embedding / search
+    resolver.search_setting(
+        "EMBEDDING_PROVIDER",
+        &["EMBEDDING_PROVIDER"],
+        &["EMBEDDING_PROVIDER"],
+        json!(config.embedding.provider),
+        json!(defaults.embedding.provider),
+    );
+    resolver.search_setting(
+        "EMBEDDING_API_KEY",
+        &["EMBEDDING_API_KEY", "OPENAI_API_KEY"],
+        &["EMBEDDING_API_KEY", "OPENAI_API_KEY"],
+        json!(config.embedding.api_key),
+        json!(defaults.embedding.api_key),
+    );
+    resolver.mark_secret();
+    resolver.search_setting(
+        "EMBEDDING_API_BASE",
+        &["EMBEDDING_API_BASE", "OPENAI_API_BASE"],
+        &["EMBEDDING_API_BASE", "OPENAI_BASE_URL"],
+        json!(config.embedding.api_base),
+        json!(defaults.embedding.api_base),
+    );
+    resolver.search_setting(
+        "EMBEDDING_MODEL",
+        &["EMBEDDING_MODEL"],
+        &["EMBEDDING_MODEL"],
+        json!(config.embedding.model),
+        json!(defaults.embedding.model),
+    );
+    resolver.search_setting(
+        "EMBEDDING_DIMENSIONS",
+        &[],
+        &[],
+        json!(config.embedding.dimensions),
+        json!(defaults.embedding.dimensions),
+    );
+    resolver.search_setting(
+        "EMBEDDING_BATCH_SIZE",
+        &[],
+        &["EMBEDDING_BATCH_SIZE"],
+        json!(config.embedding.batch_size),
+        json!(defaults.embedding.batch_size),
+    );
+    resolver.search_setting(
+        "EMBEDDING_MAX_CONCURRENT_REQUESTS",
+        &[],
+        &["EMBEDDING_MAX_CONCURRENT_REQUESTS"],
+        json!(config.embedding.max_concurrent_requests),
+        json!(defaults.embedding.max_concurrent_requests),
+    );
+    resolver.search_setting(
+        "EMBEDDING_RETRY_MAX_ATTEMPTS",
+        &[],
+        &["EMBEDDING_RETRY_MAX_ATTEMPTS"],
+        json!(config.embedding.retry_max_attempts),
+        json!(defaults.embedding.retry_max_attempts),
+    );
+    resolver.search_setting(
+        "EMBEDDING_RETRY_BASE_DELAY_MS",
+        &[],
+        &["EMBEDDING_RETRY_BASE_DELAY_MS"],
+        json!(config.embedding.retry_base_delay_ms),
+        json!(defaults.embedding.retry_base_delay_ms),
+    );
+    resolver.search_setting(
+        "CHUNKING_MAX_TOKENS",
+        &[],
+        &["CHUNKING_MAX_TOKENS", "EMBEDDING_MAX_CHUNK_TOKENS"],
+        json!(config.chunking.max_tokens),
+        json!(defaults.chunking.max_tokens),
+    );
+    resolver.search_setting(
+        "CHUNKING_OVERLAP_TOKENS",
+        &[],
+        &["CHUNKING_OVERLAP_TOKENS", "EMBEDDING_CHUNK_OVERLAP"],
+        json!(config.chunking.overlap_tokens),
+        json!(defaults.chunking.overlap_tokens),
+    );
+    resolver.search_setting(
+        "CHUNKING_STRATEGY",
+        &[],
+        &["CHUNKING_STRATEGY"],
+        json!(config.chunking.strategy.as_str()),
+        json!(defaults.chunking.strategy.as_str()),
+    );
+    resolver.search_setting(
+        "SEARCH_HYBRID_ALPHA",
+        &[],
+        &["SEARCH_HYBRID_ALPHA"],
+        json!(config.search.hybrid_alpha),
+        json!(defaults.search.hybrid_alpha),
+    );
+    resolver.search_setting(
+        "EMBEDDING_INCLUDE_HEADINGS",
+        &[],
+        &["EMBEDDING_INCLUDE_HEADINGS"],
+        json!(config.embedding.include_headings),
+        json!(defaults.embedding.include_headings),
+    );
+    resolver.search_setting(
+        "EMBEDDING_PROXY_URL",
+        &[],
+        &["EMBEDDING_PROXY_URL"],
+        json!(config.embedding.proxy_url),
+        json!(defaults.embedding.proxy_url),
+    );
+    resolver.mask_url_credentials();
+    // Header values often carry credentials, so only their names are shown
+    resolver.search_setting(
+        "EMBEDDING_EXTRA_HEADERS",
+        &[],
+        &["EMBEDDING_EXTRA_HEADERS"],
+        json!(config.embedding.extra_headers.keys().collect::<Vec<_>>()),
+        json!([]),
+    );
+    resolver.search_setting(
+        "EMBEDDING_PROFILES",
+        &[],
+        &["EMBEDDING_PROFILES"],
+        json!(config.profiles.keys().collect::<Vec<_>>()),
+        json!([]),
+    );
+    resolver.search_setting(
+        "EMBEDDING_FOLDER_PROFILES",
+        &[],
+        &["EMBEDDING_FOLDER_PROFILES"],
+        json!(config.folder_profiles),
+        json!({}),
+    );
+    resolver.search_setting(
+        "LANCEDB_PATH",
+        &[],
+        &[],
+        json!(config.paths.get_lancedb_path()),
+        json!(defaults.paths.get_lancedb_path()),
+    );
+
+    // AI chat
+    resolver.file_setting("AI_PROVIDER", json!("openai"));
+    resolver.file_setting("AI_API_KEY", Value::Null);
+    resolver.mark_secret();
+    resolver.file_setting("AI_API_BASE", json!("https://api.openai.com/v1"));
+    resolver.file_setting("AI_MODEL", json!("gpt-4o"));
+    resolver.file_setting("AI_PROMPT", json!(crate::commands::ai::DEFAULT_AI_PROMPT));
+    resolver.file_setting("AI_MAX_PROMPT_CHARS", Value::Null);
+    resolver.file_setting("AI_MAX_PROMPT_TOKENS", Value::Null);
+    resolver.file_setting("AI_PROMPT_TRUNCATION", json!("drop-oldest"));
+    resolver.file_setting("AI_PROMPT_KEEP_LAST", json!(crate::chat::DEFAULT_KEEP_LAST));
+    let retry = opencontext_core::retry::RetryPolicy::default();
+    resolver.file_setting("AI_RETRY_MAX_ATTEMPTS", json!(retry.max_attempts));
+    resolver.file_setting(
+        "AI_RETRY_BASE_DELAY_MS",
+        json!(retry.base_delay.as_millis() as u64),
+    );
+    resolver.file_setting("AUTO_ENRICH_DOCS", json!(false));
+    resolver.file_setting("DIGEST_DAYS", json!(crate::commands::digest::DEFAULT_DAYS));
+    resolver.file_setting("DIGEST_SCHEDULE_TIME", Value::Null);
+
+    // Agents
+    resolver.file_setting("AGENT_STOP_MODE", json!("soft"));
+    resolver.file_setting(
+        "AGENT_STALL_MINUTES",
+        json!(crate::agent_watchdog::DEFAULT_STALL_MINUTES),
+    );
+    resolver.file_setting(
+        "AGENT_STALL_GRACE_MINUTES",
+        json!(crate::agent_watchdog::DEFAULT_GRACE_MINUTES),
+    );
+    resolver.file_setting("AGENT_STALL_INTERRUPT", json!(true));
+    resolver.file_setting("AGENT_MODELS_CODEX", Value::Null);
+    resolver.file_setting("AGENT_MODELS_CLAUDE", Value::Null);
+
+    // App
+    resolver.file_setting("INDEX_SYNC_PAUSED", json!(false));
+    resolver.file_setting("INDEX_BUILD_SCHEDULE_HOURS", Value::Null);
+    resolver.file_setting("INDEX_BUILD_SCHEDULE_TIME", Value::Null);
+    resolver.file_setting("CONFIRM_QUIT", json!(true));
+    resolver.file_setting(
+        "LOG_LEVEL",
+        json!(crate::logging::DEFAULT_LEVEL.as_str().to_lowercase()),
+    );
+    resolver.file_setting("TRAY_ICON_VARIANT", json!("auto"));
+    resolver.file_setting(
+        "SNAPSHOT_KEEP",
+        json!(crate::commands::snapshot::DEFAULT_KEEP),
+    );
+    resolver.file_setting(
+        "SNAPSHOT_MAX_AGE_DAYS",
+        json!(crate::commands::snapshot::DEFAULT_MAX_AGE_DAYS),
+    );
+
+    // Secrets can also hide in other values, e.g. a key in an API base URL.
+    for setting in &mut resolver.settings {
+        if let Some(value) = setting.value.as_str().filter(|_| !setting.secret) {
+            setting.value = json!(redact(value));
+        }
+    }
+
+    Ok(resolver.settings)
+}
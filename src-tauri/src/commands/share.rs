@@ -0,0 +1,325 @@
+use crate::utils::{map_err, CmdResult, CommandError};
+use crate::AppState;
+use base64::Engine;
+use opencontext_core::{OpenContext, VaultPath};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// Images larger than this are left out rather than embedded
+const MAX_EMBEDDED_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+const SHARE_DIR_NAME: &str = "opencontext-share";
+const INTERNAL_LINK_SCHEMES: [&str; 2] = ["oc://", "opencontext://"];
+
+const STYLESHEET: &str = "
+body { max-width: 46rem; margin: 2.5rem auto; padding: 0 1.25rem; font: 16px/1.65 -apple-system, BlinkMacSystemFont, 'Segoe UI', Helvetica, Arial, sans-serif; color: #1f2328; }
+h1, h2, h3, h4 { line-height: 1.25; margin: 1.6em 0 0.6em; }
+img { max-width: 100%; }
+pre { background: #f6f8fa; padding: 0.9rem 1rem; border-radius: 6px; overflow-x: auto; }
+code { font: 0.9em ui-monospace, SFMono-Regular, Menlo, monospace; }
+blockquote { margin: 0; padding-left: 1rem; border-left: 3px solid #d0d7de; color: #59636e; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: 0.35rem 0.7rem; }
+@media (prefers-color-scheme: dark) {
+  body { background: #0d1117; color: #e6edf3; }
+  pre { background: #161b22; }
+}
+";
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn is_internal_link(url: &str) -> bool {
+    INTERNAL_LINK_SCHEMES
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+fn image_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "bmp" => "image/bmp",
+        "ico" => "image/x-icon",
+        "avif" => "image/avif",
+        _ => return None,
+    })
+}
+
+/// Render markdown to an HTML fragment. Internal links become their text
+/// (or `link_title` when they have none) and images become whatever
+/// `embed_image` returns for their URL, usually a data URL.
+fn render_markdown(
+    markdown: &str,
+    link_title: impl Fn(&str) -> String,
+    embed_image: impl Fn(&str) -> Option<String>,
+) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut events = Vec::new();
+    let mut in_metadata = false;
+    // For each open link: its URL when internal, and whether it had text
+    let mut links: Vec<(Option<String>, bool)> = Vec::new();
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+            _ if in_metadata => {}
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                if is_internal_link(&dest_url) {
+                    links.push((Some(dest_url.to_string()), false));
+                } else {
+                    links.push((None, false));
+                    events.push(Event::Start(Tag::Link {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    }));
+                }
+            }
+            Event::End(TagEnd::Link) => match links.pop() {
+                Some((Some(url), had_text)) => {
+                    if !had_text {
+                        events.push(Event::Text(link_title(&url).into()));
+                    }
+                }
+                _ => events.push(event),
+            },
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let dest_url = match embed_image(&dest_url) {
+                    Some(data_url) => CowStr::from(data_url),
+                    None => dest_url,
+                };
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            Event::Text(_) | Event::Code(_) => {
+                if let Some(link) = links.last_mut() {
+                    link.1 = true;
+                }
+                events.push(event);
+            }
+            event => events.push(event),
+        }
+    }
+    let mut body = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut body, events.into_iter());
+    body
+}
+
+fn standalone_html(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        STYLESHEET,
+        body
+    )
+}
+
+/// Title shown for an internal link without text: the linked doc's name
+fn internal_link_title(ctx: &OpenContext, url: &str) -> String {
+    let target = INTERNAL_LINK_SCHEMES
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+        .unwrap_or(url);
+    match target.split_once('/') {
+        Some(("doc", stable_id)) => ctx
+            .get_doc_by_stable_id(stable_id)
+            .map(|doc| doc.name.trim_end_matches(".md").to_string())
+            .unwrap_or_else(|_| "linked document".to_string()),
+        Some(("idea", _)) => "linked idea".to_string(),
+        _ => target.to_string(),
+    }
+}
+
+/// Data URL for an image referenced from a doc, by a path relative to the
+/// doc's folder or the contexts root. Images outside the vault, remote
+/// images and oversized files are left alone.
+fn embed_vault_image(contexts_root: &Path, doc_dir: &Path, url: &str) -> Option<String> {
+    if url.contains("://") || url.starts_with("data:") {
+        return None;
+    }
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    let decoded = percent_decode(url);
+    let relative = decoded.trim_start_matches('/');
+    let root = contexts_root.canonicalize().ok()?;
+    let path = [doc_dir.join(&decoded), contexts_root.join(relative)]
+        .into_iter()
+        .filter_map(|candidate| candidate.canonicalize().ok())
+        .find(|candidate| candidate.starts_with(&root) && candidate.is_file())?;
+    let mime = image_mime(&path)?;
+    if std::fs::metadata(&path).ok()?.len() > MAX_EMBEDDED_IMAGE_BYTES {
+        log::warn!("[Share] Not embedding {}: too large", path.display());
+        return None;
+    }
+    let bytes = std::fs::read(&path).ok()?;
+    Some(format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// Decode `%XX` escapes, as editors write spaces in image paths as `%20`
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(out).unwrap_or_else(|_| text.to_string())
+}
+
+/// File name for the snapshot, from the doc name
+fn snapshot_file_name(doc_name: &str) -> String {
+    let stem = Path::new(doc_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("document");
+    let safe: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.html", safe.trim())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ShareDocSnapshotOptions {
+    path: VaultPath,
+    /// File or existing directory to write to; defaults to a folder in the
+    /// system temp dir
+    output_path: Option<PathBuf>,
+    /// Also put the rendered doc on the clipboard as rich text
+    #[serde(default)]
+    copy_to_clipboard: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SharedSnapshot {
+    path: PathBuf,
+    bytes: usize,
+    copied: bool,
+}
+
+/// Render a doc to a self-contained HTML file for sharing outside the vault
+#[tauri::command]
+pub(crate) fn share_doc_snapshot(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    options: ShareDocSnapshotOptions,
+) -> CmdResult<SharedSnapshot> {
+    let (doc, content, body) = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        let doc = ctx.get_doc_meta(options.path.as_str())?;
+        let content = ctx.get_doc_content(options.path.as_str())?;
+        let contexts_root = ctx.env_info().contexts_root;
+        let doc_dir = doc
+            .abs_path
+            .parent()
+            .unwrap_or(contexts_root.as_path())
+            .to_path_buf();
+        let body = render_markdown(
+            &content,
+            |url| internal_link_title(&ctx, url),
+            |url| embed_vault_image(&contexts_root, &doc_dir, url),
+        );
+        (doc, content, body)
+    };
+    let title = doc.name.trim_end_matches(".md");
+    let page = standalone_html(title, &body);
+
+    let target = match options.output_path {
+        Some(path) if path.is_dir() => path.join(snapshot_file_name(&doc.name)),
+        Some(path) => path,
+        None => {
+            let dir = std::env::temp_dir().join(SHARE_DIR_NAME);
+            std::fs::create_dir_all(&dir)?;
+            dir.join(snapshot_file_name(&doc.name))
+        }
+    };
+    std::fs::write(&target, &page)?;
+
+    let copied = options.copy_to_clipboard;
+    if copied {
+        app.clipboard()
+            .write_html(body, Some(content))
+            .map_err(CommandError::internal)?;
+    }
+    Ok(SharedSnapshot {
+        path: target,
+        bytes: page.len(),
+        copied,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_markdown_flattens_internal_links_and_embeds_images() {
+        let markdown = "---\ntitle: x\n---\nSee [plan](oc://doc/abc), [](opencontext://doc/def) and [site](https://example.com).\n\n![chart](chart.png)";
+        let body = render_markdown(
+            markdown,
+            |url| format!("title of {}", url),
+            |url| (url == "chart.png").then(|| "data:image/png;base64,AA==".to_string()),
+        );
+        assert!(!body.contains("title: x"));
+        assert!(body.contains("See plan, title of opencontext://doc/def and"));
+        assert!(body.contains("<a href=\"https://example.com\">site</a>"));
+        assert!(body.contains("<img src=\"data:image/png;base64,AA==\" alt=\"chart\""));
+    }
+
+    #[test]
+    fn snapshot_file_name_is_safe() {
+        assert_eq!(snapshot_file_name("Q3: plan?.md"), "Q3_ plan_.html");
+    }
+}
@@ -0,0 +1,150 @@
+use crate::tasks::{ProgressThrottle, TaskKind};
+use crate::utils::{map_err, read_config_json, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use opencontext_core::{
+    list_snapshots, prune_snapshots, CoreResult, OpenContext, SnapshotInfo, SnapshotRestore,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{Manager, State};
+
+/// config.json key: snapshots kept, newest first (0 keeps all)
+const KEEP_KEY: &str = "SNAPSHOT_KEEP";
+pub(crate) const DEFAULT_KEEP: u64 = 20;
+/// config.json key: days a snapshot is kept (0 keeps them for good)
+const MAX_AGE_DAYS_KEY: &str = "SNAPSHOT_MAX_AGE_DAYS";
+pub(crate) const DEFAULT_MAX_AGE_DAYS: u64 = 30;
+
+/// Send `task-progress` for a snapshot at most this often...
+const SNAPSHOT_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// ...or after this many more files, whichever comes first
+const SNAPSHOT_PROGRESS_EVERY: usize = 200;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VaultSnapshotCreateOptions {
+    /// Shown next to the snapshot's time, e.g. "before agent run"
+    label: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VaultSnapshotRestoreOptions {
+    id: String,
+    /// Must be true: a restore replaces every file in the vault
+    #[serde(default)]
+    confirm: bool,
+}
+
+/// Snapshots live next to the database, outside the contexts root
+pub(crate) fn snapshots_dir(ctx: &OpenContext) -> PathBuf {
+    let db_path = ctx.env_info().db_path;
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("snapshots")
+}
+
+/// A count setting from config.json, as a number or a numeric string
+fn config_count(key: &str, default: u64) -> u64 {
+    read_config_json()
+        .and_then(|config| {
+            let value = config.get(key)?.clone();
+            value
+                .as_u64()
+                .or_else(|| value.as_str()?.trim().parse().ok())
+        })
+        .unwrap_or(default)
+}
+
+/// Delete the snapshots `SNAPSHOT_KEEP` and `SNAPSHOT_MAX_AGE_DAYS` no
+/// longer keep
+fn prune(dir: &Path) {
+    let keep = config_count(KEEP_KEY, DEFAULT_KEEP);
+    let max_age_days = config_count(MAX_AGE_DAYS_KEY, DEFAULT_MAX_AGE_DAYS);
+    let pruned = prune_snapshots(
+        dir,
+        (keep > 0).then_some(keep as usize),
+        (max_age_days > 0).then(|| chrono::Duration::days(max_age_days as i64)),
+    );
+    match pruned {
+        Ok(ids) if !ids.is_empty() => log::info!("[Snapshot] Pruned {}", ids.join(", ")),
+        Ok(_) => {}
+        Err(e) => log::warn!("[Snapshot] Pruning failed: {}", e),
+    }
+}
+
+/// Run snapshot work off the async runtime as a tracked task, reporting the
+/// files done through `task-progress`
+async fn run_snapshot_task<T: Send + 'static>(
+    app: &tauri::AppHandle,
+    work: impl FnOnce(&OpenContext, &Path, &mut dyn FnMut(usize, usize)) -> CoreResult<T>
+        + Send
+        + 'static,
+) -> CmdResult<T> {
+    let state = app.state::<AppState>();
+    let ctx = state.ctx.read().map_err(map_err)?.clone();
+    let task = state.tasks.start(app, TaskKind::Snapshot)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let dir = snapshots_dir(&ctx);
+        let mut throttle =
+            ProgressThrottle::new(SNAPSHOT_PROGRESS_INTERVAL, SNAPSHOT_PROGRESS_EVERY);
+        let result = work(&ctx, &dir, &mut |done, total| {
+            if throttle.should_send(done, total) {
+                task.progress(done, total, None);
+            }
+        })
+        .map_err(CommandError::from);
+        if result.is_ok() {
+            prune(&dir);
+        }
+        task.finish(&result);
+        result
+    })
+    .await
+    .map_err(CommandError::internal)?
+}
+
+/// Copy the whole vault (docs and their metadata, not the search index) into
+/// a new timestamped snapshot, then prune old snapshots
+#[tauri::command]
+pub(crate) async fn vault_snapshot_create(
+    app: tauri::AppHandle,
+    options: VaultSnapshotCreateOptions,
+) -> CmdResult<SnapshotInfo> {
+    let label = options.label.filter(|label| !label.trim().is_empty());
+    run_snapshot_task(&app, move |ctx, dir, on_progress| {
+        ctx.create_snapshot(dir, label.as_deref(), on_progress)
+    })
+    .await
+}
+
+/// Snapshots, newest first
+#[tauri::command]
+pub(crate) fn vault_snapshot_list(state: State<AppState>) -> CmdResult<Vec<SnapshotInfo>> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    Ok(list_snapshots(&snapshots_dir(&ctx))?)
+}
+
+/// Roll the vault back to a snapshot. Requires `confirm`; the current state
+/// is snapshotted first so the restore can itself be undone. Changed docs
+/// are re-indexed by the sync service and refreshed in open editors.
+#[tauri::command]
+pub(crate) async fn vault_snapshot_restore(
+    app: tauri::AppHandle,
+    options: VaultSnapshotRestoreOptions,
+) -> CmdResult<SnapshotRestore> {
+    if !options.confirm {
+        return Err(CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.snapshot_restore_unconfirmed",
+            &[],
+        ));
+    }
+    let id = options.id;
+    run_snapshot_task(&app, move |ctx, dir, on_progress| {
+        ctx.restore_snapshot(dir, &id, on_progress)
+    })
+    .await
+}
@@ -0,0 +1,85 @@
+use crate::commands::search::list_all_docs;
+use crate::utils::{map_err, CmdResult, CommandError};
+use crate::AppState;
+use opencontext_core::search::{IndexCoverage, Indexer, SearchError};
+use opencontext_core::{VaultStats, VaultStatsOptions};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+const MAX_WEEKS: usize = 520;
+const MAX_TOP: usize = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VaultStatisticsOptions {
+    /// Weekly buckets, ending with the current week (default 26)
+    weeks: Option<usize>,
+    /// Length of the top-N lists (default 10)
+    top: Option<usize>,
+    /// Count words in every text doc; reads each changed doc
+    #[serde(default)]
+    word_counts: bool,
+    /// Collect front matter and `#hashtag` tags; reads each changed doc
+    #[serde(default)]
+    tags: bool,
+    /// Compare the vault with the search index (default true)
+    index_coverage: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VaultStatisticsResponse {
+    #[serde(flatten)]
+    vault: VaultStats,
+    /// `None` when not requested or no index is built
+    index_coverage: Option<IndexCoverage>,
+}
+
+/// Stats for the stats page, computed in one pass over the vault. Word
+/// counts and tags are cached per doc until it changes.
+#[tauri::command]
+pub(crate) async fn vault_statistics(
+    state: State<'_, AppState>,
+    options: VaultStatisticsOptions,
+) -> CmdResult<VaultStatisticsResponse> {
+    let stats_options = VaultStatsOptions {
+        weeks: options.weeks.unwrap_or(26).clamp(1, MAX_WEEKS),
+        top: options.top.unwrap_or(10).clamp(1, MAX_TOP),
+        word_counts: options.word_counts,
+        tags: options.tags,
+    };
+    let ctx = state.ctx.read().map_err(map_err)?.clone();
+    // Reading docs for words and tags blocks; keep it off the async runtime.
+    let vault = tauri::async_runtime::spawn_blocking({
+        let ctx = ctx.clone();
+        move || ctx.vault_statistics(&stats_options)
+    })
+    .await
+    .map_err(CommandError::internal)??;
+
+    let index_coverage = if options.index_coverage.unwrap_or(true) {
+        let docs = list_all_docs(&state)?;
+        let mut indexer_guard = state.indexer.lock().await;
+        if indexer_guard.is_none() {
+            match Indexer::new(state.search_config(), ctx.env_info().contexts_root).await {
+                Ok(indexer) => *indexer_guard = Some(indexer),
+                // A broken index shouldn't hide the rest of the stats
+                Err(e @ SearchError::CorruptIndex { .. }) => {
+                    log::warn!("[Stats] Skipping index coverage: {}", e)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match indexer_guard.as_mut() {
+            Some(indexer) if indexer.index_exists().await => Some(indexer.coverage(&docs).await?),
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(VaultStatisticsResponse {
+        vault,
+        index_coverage,
+    })
+}
@@ -0,0 +1,503 @@
+use crate::app_events::EmitScoped;
+use crate::chat::ChatMessage;
+use crate::commands::ai::{ai_configured, complete};
+use crate::tasks::TaskKind;
+use crate::utils::{get_config_bool, map_err, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use futures::StreamExt;
+use opencontext_core::{Doc, OpenContext, VaultPath};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+
+const SUMMARY_PROMPT: &str = "Write a description of the document below for a file index that people and agents use to decide what to open. One or two sentences, at most 200 characters, saying what the document covers. Reply with the description only, in the document's language.";
+/// Leading chars of a doc sent to the model
+const SUMMARY_INPUT_CHARS: usize = 12_000;
+const MAX_DESCRIPTION_CHARS: usize = 300;
+const SUMMARY_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_CONCURRENCY: usize = 3;
+const MAX_CONCURRENCY: usize = 8;
+const ENRICH_PROMPT: &str = "Suggest a description and tags for the document below, for a file index that people and agents use to decide what to open. Reply with JSON only, in the form {\"description\": \"...\", \"tags\": [\"...\"]}. The description is one sentence of at most 200 characters saying what the document covers, in the document's language. Give 3 to 5 short tags.";
+const MAX_SUGGESTED_TAGS: usize = 5;
+/// Least time between two background enrichment calls
+const ENRICH_MIN_INTERVAL: Duration = Duration::from_secs(30);
+/// Saves queued for enrichment at most; later ones are dropped
+const MAX_PENDING_ENRICHMENTS: usize = 20;
+
+/// One line of plain text from a model reply
+fn clean_summary(reply: &str) -> String {
+    let text = reply.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.trim_matches(['"', '\'', '“', '”', '`']).trim();
+    text.chars().take(MAX_DESCRIPTION_CHARS).collect()
+}
+
+async fn generate_description(path: &str, content: &str) -> CmdResult<String> {
+    let excerpt: String = content.chars().take(SUMMARY_INPUT_CHARS).collect();
+    let messages = [
+        ChatMessage {
+            role: "system".to_string(),
+            content: serde_json::Value::String(SUMMARY_PROMPT.to_string()),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: serde_json::Value::String(format!("Document: {}\n\n{}", path, excerpt)),
+        },
+    ];
+    let description = clean_summary(&complete(&messages, SUMMARY_TIMEOUT).await?);
+    if description.is_empty() {
+        return Err(CommandError::localized(
+            ErrorCode::Network,
+            "error.empty_summary",
+            &[],
+        ));
+    }
+    Ok(description)
+}
+
+/// Description and tags from a model reply to `ENRICH_PROMPT`, which may
+/// wrap the JSON in prose or a code fence
+fn parse_enrichment(reply: &str) -> Option<(String, Vec<String>)> {
+    #[derive(Deserialize)]
+    struct Reply {
+        description: String,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+    let (start, end) = (reply.find('{')?, reply.rfind('}')?);
+    let parsed: Reply = serde_json::from_str(reply.get(start..=end)?).ok()?;
+    let description = clean_summary(&parsed.description);
+    let mut tags: Vec<String> = Vec::new();
+    for tag in parsed.tags {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.truncate(MAX_SUGGESTED_TAGS);
+    (!description.is_empty()).then_some((description, tags))
+}
+
+/// Summarize a doc and, unless `dry_run`, save it as the description
+async fn summarize_and_save(state: &AppState, path: &str, dry_run: bool) -> CmdResult<String> {
+    let content = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.get_doc_content(path)?
+    };
+    if content.trim().is_empty() {
+        return Err(CommandError::localized(
+            ErrorCode::InvalidInput,
+            "error.doc_empty",
+            &[],
+        ));
+    }
+    let description = generate_description(path, &content).await?;
+    if !dry_run {
+        let ctx = state.ctx.write().map_err(map_err)?;
+        ctx.set_doc_description(path, &description)?;
+    }
+    Ok(description)
+}
+
+/// Whether the description was set after the doc content last changed
+fn description_is_current(ctx: &OpenContext, doc: &Doc) -> bool {
+    if doc.description.trim().is_empty() {
+        return false;
+    }
+    let Ok(Some(set_at)) = ctx.get_doc_description_updated_at(&doc.rel_path) else {
+        return false;
+    };
+    let Ok(set_at) = chrono::DateTime::parse_from_rfc3339(&set_at) else {
+        return false;
+    };
+    let Ok(modified) = std::fs::metadata(&doc.abs_path).and_then(|m| m.modified()) else {
+        return false;
+    };
+    // Timestamps are stored with ms precision
+    set_at.timestamp_millis() >= chrono::DateTime::<chrono::Utc>::from(modified).timestamp_millis()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SummarizeDocOptions {
+    path: VaultPath,
+    /// Generate the description without saving it
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocSummaryResult {
+    path: String,
+    description: String,
+    saved: bool,
+}
+
+/// Generate a doc's description with the configured AI provider and save it
+#[tauri::command]
+pub(crate) async fn summarize_doc(
+    state: State<'_, AppState>,
+    options: SummarizeDocOptions,
+) -> CmdResult<DocSummaryResult> {
+    let description = summarize_and_save(&state, options.path.as_str(), options.dry_run).await?;
+    Ok(DocSummaryResult {
+        path: options.path.into_string(),
+        description,
+        saved: !options.dry_run,
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SummarizeFolderOptions {
+    folder_path: VaultPath,
+    /// Include subfolders (default true)
+    recursive: Option<bool>,
+    /// Docs summarized at once (default 3, at most 8)
+    concurrency: Option<usize>,
+    #[serde(default)]
+    dry_run: bool,
+    /// Also redo docs whose description is newer than their content
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SummaryStatus {
+    Summarized,
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocSummaryOutcome {
+    path: String,
+    status: SummaryStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Sent with `summarize-progress` as each doc finishes
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SummarizeProgressEvent<'a> {
+    current: usize,
+    total: usize,
+    doc: &'a DocSummaryOutcome,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SummarizeFolderReport {
+    summarized: usize,
+    skipped: usize,
+    failed: usize,
+    dry_run: bool,
+    /// Every doc considered, by path
+    docs: Vec<DocSummaryOutcome>,
+}
+
+async fn summarize_one(
+    state: &AppState,
+    doc: Doc,
+    dry_run: bool,
+    force: bool,
+) -> DocSummaryOutcome {
+    let outcome = |status, description, error| DocSummaryOutcome {
+        path: doc.rel_path.clone(),
+        status,
+        description,
+        error,
+    };
+    // Encrypted docs are never sent to the model
+    let withheld = !doc.kind.is_text()
+        || state.ctx.read().map_or(true, |ctx| {
+            ctx.resolve_doc_settings(&doc.rel_path)
+                .map_or(true, |settings| settings.is_encrypted())
+        });
+    if withheld {
+        return outcome(SummaryStatus::Skipped, None, None);
+    }
+    let current = !force
+        && state
+            .ctx
+            .read()
+            .is_ok_and(|ctx| description_is_current(&ctx, &doc));
+    if current {
+        return outcome(SummaryStatus::Skipped, Some(doc.description.clone()), None);
+    }
+    match summarize_and_save(state, &doc.rel_path, dry_run).await {
+        Ok(description) => outcome(SummaryStatus::Summarized, Some(description), None),
+        Err(e) => {
+            log::warn!("[Summarize] {} failed: {}", doc.rel_path, e);
+            outcome(SummaryStatus::Failed, None, Some(e.message))
+        }
+    }
+}
+
+/// Summarize every doc in a folder as a cancellable task, emitting
+/// `summarize-progress` per doc. A failed doc is reported and skipped.
+#[tauri::command]
+pub(crate) async fn summarize_folder(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    options: SummarizeFolderOptions,
+) -> CmdResult<SummarizeFolderReport> {
+    if !ai_configured() {
+        return Err(CommandError::localized(
+            ErrorCode::Config,
+            "error.ai_not_configured",
+            &[],
+        ));
+    }
+    let docs = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.list_docs(
+            options.folder_path.as_str(),
+            options.recursive.unwrap_or(true),
+        )?
+    };
+    let total = docs.len();
+    let concurrency = options
+        .concurrency
+        .unwrap_or(DEFAULT_CONCURRENCY)
+        .clamp(1, MAX_CONCURRENCY);
+
+    let task = state.tasks.start(&app, TaskKind::Summarize)?;
+    let result = task
+        .run(async {
+            let mut outcomes = Vec::with_capacity(total);
+            let mut pending = futures::stream::iter(docs)
+                .map(|doc| summarize_one(&state, doc, options.dry_run, options.force))
+                .buffer_unordered(concurrency);
+            while let Some(outcome) = pending.next().await {
+                task.progress(outcomes.len() + 1, total, Some(outcome.path.clone()));
+                let _ = app.emit_scoped(
+                    "summarize-progress",
+                    SummarizeProgressEvent {
+                        current: outcomes.len() + 1,
+                        total,
+                        doc: &outcome,
+                    },
+                );
+                outcomes.push(outcome);
+            }
+            Ok(outcomes)
+        })
+        .await;
+    task.finish(&result);
+
+    let mut docs = result?;
+    docs.sort_by(|a, b| a.path.cmp(&b.path));
+    let count = |status| docs.iter().filter(|d| d.status == status).count();
+    Ok(SummarizeFolderReport {
+        summarized: count(SummaryStatus::Summarized),
+        skipped: count(SummaryStatus::Skipped),
+        failed: count(SummaryStatus::Failed),
+        dry_run: options.dry_run,
+        docs,
+    })
+}
+
+/// Rate limit for background enrichment: one call at a time, spaced by
+/// `ENRICH_MIN_INTERVAL`, with each doc queued at most once
+#[derive(Default)]
+pub(crate) struct EnrichQueue {
+    pending: Mutex<HashSet<String>>,
+    last_call: tokio::sync::Mutex<Option<Instant>>,
+}
+
+/// Sent with `doc-enrichment-suggestion`; nothing is written until the
+/// user accepts it with `accept_doc_enrichment`
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct EnrichmentSuggestion {
+    path: String,
+    description: String,
+    tags: Vec<String>,
+}
+
+/// Queue description and tag suggestions for a just-saved doc, when asked
+/// for with `suggest` or when `AUTO_ENRICH_DOCS` is on and the doc has no
+/// description. Never for docs in private or encrypted folders.
+pub(crate) fn queue_enrichment(
+    app: &tauri::AppHandle,
+    ctx: &OpenContext,
+    path: &str,
+    suggest: bool,
+) {
+    let Ok(doc) = ctx.get_doc_meta(path) else {
+        return;
+    };
+    let wanted = suggest
+        || (get_config_bool("AUTO_ENRICH_DOCS").unwrap_or(false)
+            && doc.description.trim().is_empty());
+    if !wanted || !doc.kind.is_text() || !ai_configured() {
+        return;
+    }
+    if ctx.resolve_doc_settings(path).map_or(true, |settings| {
+        settings.is_private() || settings.is_encrypted()
+    }) {
+        return;
+    }
+    let queue = &app.state::<AppState>().inner().enrich_queue;
+    let Ok(mut pending) = queue.pending.lock() else {
+        return;
+    };
+    if pending.len() >= MAX_PENDING_ENRICHMENTS || !pending.insert(doc.rel_path.clone()) {
+        return;
+    }
+    drop(pending);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        if let Err(e) = enrich(&app, &state, &doc.rel_path).await {
+            log::warn!("[Enrich] {} failed: {}", doc.rel_path, e);
+        }
+        if let Ok(mut pending) = state.enrich_queue.pending.lock() {
+            pending.remove(&doc.rel_path);
+        }
+    });
+}
+
+/// Wait for the rate limit, then ask the model for suggestions as a
+/// cancellable task and emit them
+async fn enrich(app: &tauri::AppHandle, state: &AppState, path: &str) -> CmdResult<()> {
+    let mut last_call = state.enrich_queue.last_call.lock().await;
+    if let Some(wait) = last_call.and_then(|at| ENRICH_MIN_INTERVAL.checked_sub(at.elapsed())) {
+        tokio::time::sleep(wait).await;
+    }
+    // Read after the wait, so the latest save is what gets described
+    let content = {
+        let ctx = state.ctx.read().map_err(map_err)?;
+        ctx.get_doc_content(path)?
+    };
+    if content.trim().is_empty() {
+        return Ok(());
+    }
+    *last_call = Some(Instant::now());
+
+    let task = state.tasks.start(app, TaskKind::Enrich)?;
+    task.progress(0, 1, Some(path.to_string()));
+    let result = task
+        .run(async {
+            let excerpt: String = content.chars().take(SUMMARY_INPUT_CHARS).collect();
+            let messages = [
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: serde_json::Value::String(ENRICH_PROMPT.to_string()),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: serde_json::Value::String(format!(
+                        "Document: {}\n\n{}",
+                        path, excerpt
+                    )),
+                },
+            ];
+            let reply = complete(&messages, SUMMARY_TIMEOUT).await?;
+            parse_enrichment(&reply).ok_or_else(|| {
+                CommandError::localized(ErrorCode::Network, "error.no_usable_suggestions", &[])
+            })
+        })
+        .await;
+    task.finish(&result);
+
+    let (description, tags) = result?;
+    let _ = app.emit_scoped(
+        "doc-enrichment-suggestion",
+        EnrichmentSuggestion {
+            path: path.to_string(),
+            description,
+            tags,
+        },
+    );
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AcceptEnrichmentOptions {
+    path: VaultPath,
+    /// Description to set, as suggested or edited by the user
+    description: Option<String>,
+    /// Tags to add to the doc's front matter
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AcceptedEnrichment {
+    path: String,
+    description: Option<String>,
+    /// All of the doc's tags afterwards
+    tags: Vec<String>,
+    /// Whether the doc's content changed (tags are written to its front
+    /// matter), so an open editor should reload it
+    content_changed: bool,
+}
+
+/// Apply suggestions from `doc-enrichment-suggestion`
+#[tauri::command]
+pub(crate) fn accept_doc_enrichment(
+    state: State<AppState>,
+    options: AcceptEnrichmentOptions,
+) -> CmdResult<AcceptedEnrichment> {
+    let path = options.path.as_str();
+    let ctx = state.ctx.write().map_err(map_err)?;
+    let description = options
+        .description
+        .map(|d| clean_summary(&d))
+        .filter(|d| !d.is_empty());
+    if let Some(description) = &description {
+        ctx.set_doc_description(path, description)?;
+    }
+    let before = ctx.get_doc_tags(path)?;
+    let tags = if options.tags.is_empty() {
+        before.clone()
+    } else {
+        ctx.add_doc_tags(path, &options.tags)?
+    };
+    Ok(AcceptedEnrichment {
+        path: options.path.into_string(),
+        description,
+        content_changed: tags != before,
+        tags,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_summary_flattens_and_unquotes() {
+        assert_eq!(
+            clean_summary("  \"Meeting notes for\n the Q3 roadmap.\"\n"),
+            "Meeting notes for the Q3 roadmap."
+        );
+        assert_eq!(clean_summary(&"x".repeat(500)).len(), MAX_DESCRIPTION_CHARS);
+    }
+
+    #[test]
+    fn parse_enrichment_reads_fenced_json() {
+        let reply = "```json\n{\"description\": \"Q3 plan.\", \"tags\": [\"#Plan\", \"plan\", \"q3\"]}\n```";
+        assert_eq!(
+            parse_enrichment(reply),
+            Some((
+                "Q3 plan.".to_string(),
+                vec!["plan".to_string(), "q3".to_string()]
+            ))
+        );
+        assert_eq!(parse_enrichment("{\"description\": \" \"}"), None);
+        assert_eq!(parse_enrichment("no json"), None);
+    }
+}
@@ -1,5 +1,6 @@
-use crate::terminal_session::TerminalSession;
-use crate::utils::{map_err, CmdResult};
+use crate::app_events::EmitScoped;
+use crate::terminal_session::{TerminalOutput, TerminalSession};
+use crate::utils::{map_err, CmdResult, CommandError, ErrorCode};
 use crate::AppState;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use serde::{Deserialize, Serialize};
@@ -7,7 +8,7 @@ use std::collections::HashMap;
 use std::io::Read;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use tauri::{Emitter, State};
+use tauri::State;
 
 static TERMINAL_COUNTER: AtomicU64 = AtomicU64::new(1);
 
@@ -44,6 +45,12 @@ pub(crate) struct TerminalKillOptions {
     id: String,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TerminalOutputOptions {
+    id: String,
+}
+
 #[derive(Serialize, Clone)]
 pub(crate) struct TerminalOutputPayload {
     id: String,
@@ -71,6 +78,7 @@ pub(crate) fn terminal_spawn(
     };
     let pair = pty_system.openpty(size).map_err(map_err)?;
 
+    let command_label = options.command.clone();
     let mut cmd = CommandBuilder::new(options.command);
     if let Some(args) = options.args {
         cmd.args(args);
@@ -99,6 +107,8 @@ pub(crate) fn terminal_spawn(
     let output_app = app.clone();
     let child_handle = Arc::new(Mutex::new(child));
     let child_for_thread = child_handle.clone();
+    let output = Arc::new(Mutex::new(TerminalOutput::default()));
+    let output_for_thread = output.clone();
 
     std::thread::spawn(move || {
         let mut buffer = [0u8; 4096];
@@ -106,11 +116,19 @@ pub(crate) fn terminal_spawn(
             match reader.read(&mut buffer) {
                 Ok(0) => break,
                 Ok(size) => {
+                    // Emit under the gate lock so resume can't reorder output.
+                    let Ok(mut gate) = output_for_thread.lock() else {
+                        break;
+                    };
+                    if gate.paused {
+                        gate.buffer(&buffer[..size]);
+                        continue;
+                    }
                     let payload = TerminalOutputPayload {
                         id: output_id.clone(),
                         data: String::from_utf8_lossy(&buffer[..size]).to_string(),
                     };
-                    let _ = output_app.emit("terminal-output", payload);
+                    let _ = output_app.emit_scoped("terminal-output", payload);
                 }
                 Err(_) => break,
             }
@@ -122,7 +140,7 @@ pub(crate) fn terminal_spawn(
             .and_then(|mut child| child.wait().ok())
             .map(|status| status.exit_code() as i32);
 
-        let _ = output_app.emit(
+        let _ = output_app.emit_scoped(
             "terminal-exit",
             TerminalExitPayload {
                 id: output_id.clone(),
@@ -132,9 +150,11 @@ pub(crate) fn terminal_spawn(
     });
 
     let session = TerminalSession {
+        command: command_label,
         master,
         writer: Mutex::new(writer),
         child: child_handle,
+        output,
     };
     state
         .terminal_sessions
@@ -148,11 +168,11 @@ pub(crate) fn terminal_spawn(
 #[tauri::command]
 pub(crate) fn terminal_write(state: State<AppState>, options: TerminalWriteOptions) -> CmdResult<()> {
     let sessions = state.terminal_sessions.lock().map_err(map_err)?;
-    let session = sessions
-        .get(&options.id)
-        .ok_or_else(|| "Terminal session not found".to_string())?;
+    let session = sessions.get(&options.id).ok_or_else(|| {
+        CommandError::localized(ErrorCode::NotFound, "error.terminal_not_found", &[])
+    })?;
     let mut writer = session.writer.lock().map_err(map_err)?;
-    writer.write_all(options.data.as_bytes()).map_err(map_err)?;
+    writer.write_all(options.data.as_bytes())?;
     writer.flush().ok();
     Ok(())
 }
@@ -160,9 +180,9 @@ pub(crate) fn terminal_write(state: State<AppState>, options: TerminalWriteOptio
 #[tauri::command]
 pub(crate) fn terminal_resize(state: State<AppState>, options: TerminalResizeOptions) -> CmdResult<()> {
     let sessions = state.terminal_sessions.lock().map_err(map_err)?;
-    let session = sessions
-        .get(&options.id)
-        .ok_or_else(|| "Terminal session not found".to_string())?;
+    let session = sessions.get(&options.id).ok_or_else(|| {
+        CommandError::localized(ErrorCode::NotFound, "error.terminal_not_found", &[])
+    })?;
     session
         .master
         .resize(PtySize {
@@ -185,3 +205,59 @@ pub(crate) fn terminal_kill(state: State<AppState>, options: TerminalKillOptions
     }
     Ok(())
 }
+
+fn terminal_output_gate(state: &AppState, id: &str) -> CmdResult<Arc<Mutex<TerminalOutput>>> {
+    let sessions = state.terminal_sessions.lock().map_err(map_err)?;
+    sessions
+        .get(id)
+        .map(|session| session.output.clone())
+        .ok_or_else(|| {
+            CommandError::localized(ErrorCode::NotFound, "error.terminal_not_found", &[])
+        })
+}
+
+/// Stop emitting `terminal-output` events for a terminal. The process is not
+/// paused; its output is buffered (bounded) until the stream is resumed.
+#[tauri::command]
+pub(crate) fn terminal_pause_output(
+    state: State<AppState>,
+    options: TerminalOutputOptions,
+) -> CmdResult<()> {
+    let output = terminal_output_gate(&state, &options.id)?;
+    output.lock().map_err(map_err)?.paused = true;
+    Ok(())
+}
+
+/// Resume the output stream, first emitting whatever was buffered while paused.
+#[tauri::command]
+pub(crate) fn terminal_resume_output(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    options: TerminalOutputOptions,
+) -> CmdResult<()> {
+    let output = terminal_output_gate(&state, &options.id)?;
+    let mut gate = output.lock().map_err(map_err)?;
+    let backlog = gate.take_backlog();
+    if !backlog.is_empty() {
+        let _ = app.emit_scoped(
+            "terminal-output",
+            TerminalOutputPayload {
+                id: options.id.clone(),
+                data: String::from_utf8_lossy(&backlog).to_string(),
+            },
+        );
+    }
+    gate.paused = false;
+    Ok(())
+}
+
+/// Drop output buffered while paused. Returns the number of bytes discarded.
+#[tauri::command]
+pub(crate) fn terminal_clear(
+    state: State<AppState>,
+    options: TerminalOutputOptions,
+) -> CmdResult<usize> {
+    let output = terminal_output_gate(&state, &options.id)?;
+    let dropped = output.lock().map_err(map_err)?.clear();
+    Ok(dropped)
+}
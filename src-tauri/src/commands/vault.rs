@@ -0,0 +1,132 @@
+use crate::utils::{map_err, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use opencontext_core::{CoreError, FolderEncryption, VaultPath, VaultStatus};
+use serde::Deserialize;
+use std::path::Path;
+use tauri::State;
+
+const KEYRING_SERVICE: &str = "OpenContext vault";
+
+/// Keyring entry for a vault, one per contexts root
+fn keyring_entry(contexts_root: &Path) -> CmdResult<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, &contexts_root.to_string_lossy())
+        .map_err(CommandError::internal)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnlockVaultOptions {
+    /// Omit to use the passphrase remembered in the OS keyring
+    passphrase: Option<String>,
+    /// Remember the passphrase in the OS keyring for later unlocks
+    #[serde(default)]
+    remember: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LockVaultOptions {
+    /// Also remove the passphrase from the OS keyring
+    #[serde(default)]
+    forget: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetFolderEncryptedOptions {
+    folder_path: VaultPath,
+    encrypted: bool,
+}
+
+#[tauri::command]
+pub(crate) fn vault_status(state: State<AppState>) -> CmdResult<VaultStatus> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    Ok(ctx.vault_status())
+}
+
+/// Unlock encrypted folders until the app quits or `lock_vault`. The first
+/// unlock sets the passphrase, which is never stored in the vault: if it is
+/// lost, the encrypted docs are lost with it.
+#[tauri::command]
+pub(crate) async fn unlock_vault(
+    state: State<'_, AppState>,
+    options: UnlockVaultOptions,
+) -> CmdResult<VaultStatus> {
+    let ctx = state.ctx.read().map_err(map_err)?.clone();
+    let entry = keyring_entry(&ctx.env_info().contexts_root)?;
+    let passphrase = match options.passphrase {
+        Some(passphrase) => passphrase,
+        None => entry.get_password().map_err(|e| match e {
+            keyring::Error::NoEntry => CommandError::localized(
+                ErrorCode::InvalidInput,
+                "error.passphrase_not_remembered",
+                &[],
+            ),
+            e => CommandError::internal(e),
+        })?,
+    };
+    // Key derivation is deliberately slow; keep it off the async runtime.
+    let status = tauri::async_runtime::spawn_blocking({
+        let passphrase = passphrase.clone();
+        move || ctx.unlock_vault(&passphrase)
+    })
+    .await
+    .map_err(CommandError::internal)??;
+    if options.remember {
+        entry
+            .set_password(&passphrase)
+            .map_err(CommandError::internal)?;
+    }
+    Ok(status)
+}
+
+#[tauri::command]
+pub(crate) fn lock_vault(
+    state: State<AppState>,
+    options: LockVaultOptions,
+) -> CmdResult<VaultStatus> {
+    let ctx = state.ctx.read().map_err(map_err)?;
+    let status = ctx.lock_vault();
+    // Docs indexed while unlocked leave the index now, old versions of the
+    // index files included
+    let purge = ctx.unsearchable_docs("")?;
+    let index_sync = state.index_sync.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = index_sync.purge_files(&purge).await {
+            log::warn!("[Vault] Purging locked docs from the index failed: {}", e);
+        }
+    });
+    if options.forget {
+        match keyring_entry(&ctx.env_info().contexts_root)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(CommandError::internal(e)),
+        }
+    }
+    Ok(status)
+}
+
+/// Encrypt or decrypt every text doc in a folder. Needs the vault
+/// unlocked; a lost passphrase makes encrypted docs unrecoverable.
+#[tauri::command]
+pub(crate) async fn set_folder_encrypted(
+    state: State<'_, AppState>,
+    options: SetFolderEncryptedOptions,
+) -> CmdResult<FolderEncryption> {
+    let ctx = state.ctx.read().map_err(map_err)?.clone();
+    let (result, purge) = tauri::async_runtime::spawn_blocking(move || {
+        let folder_path = options.folder_path.as_str();
+        let result = ctx.set_folder_encrypted(folder_path, options.encrypted)?;
+        let purge = if options.encrypted {
+            ctx.unsearchable_docs(folder_path)?
+        } else {
+            Vec::new()
+        };
+        Ok::<_, CoreError>((result, purge))
+    })
+    .await
+    .map_err(CommandError::internal)??;
+    // Deleting chunks leaves their text in older versions of the index
+    // files; purge those too rather than wait for the next rebuild
+    state.index_sync.purge_files(&purge).await?;
+    Ok(result)
+}
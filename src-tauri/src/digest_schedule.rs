@@ -0,0 +1,50 @@
+use crate::commands::digest::{digest_name, generate, DIGEST_FOLDER};
+use crate::utils::{get_config_value, ErrorCode};
+use crate::AppState;
+use chrono::{Local, NaiveDate, NaiveTime};
+use std::time::Duration;
+use tauri::Manager;
+
+/// How often the scheduler checks whether a digest is due
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// config.json `DIGEST_SCHEDULE_TIME`: local "HH:MM" to write the day's
+/// digest at. No scheduled digests without it.
+fn schedule_time() -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(get_config_value("DIGEST_SCHEDULE_TIME")?.trim(), "%H:%M").ok()
+}
+
+/// Whether `date`'s digest has been written, by the schedule or by hand
+fn digest_written(app: &tauri::AppHandle, date: NaiveDate) -> bool {
+    let path = format!("{}/{}", DIGEST_FOLDER, digest_name(date));
+    app.state::<AppState>()
+        .ctx
+        .read()
+        .is_ok_and(|ctx| ctx.get_doc_meta(&path).is_ok())
+}
+
+/// Body of the scheduled digest service: once a day, after the scheduled
+/// time, writes a digest unless that day's is already there. A day whose
+/// attempt failed is not retried.
+pub(crate) async fn run(app: &tauri::AppHandle) {
+    let mut last_attempt: Option<NaiveDate> = None;
+    loop {
+        let now = Local::now();
+        let today = now.date_naive();
+        let due = schedule_time().is_some_and(|time| now.time() >= time)
+            && last_attempt != Some(today)
+            && !digest_written(app, today);
+        if due {
+            last_attempt = Some(today);
+            log::info!("[DigestSchedule] Writing scheduled digest");
+            match generate(app, Default::default()).await {
+                Ok(digest) => log::info!("[DigestSchedule] Wrote {}", digest.path),
+                Err(e) if e.code == ErrorCode::Conflict => {
+                    log::info!("[DigestSchedule] Skipped: a digest is already being written");
+                }
+                Err(e) => log::warn!("[DigestSchedule] Scheduled digest failed: {}", e),
+            }
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
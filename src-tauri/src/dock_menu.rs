@@ -0,0 +1,224 @@
+//! macOS dock menu (right-click on the dock icon).
+//!
+//! Tauri has no dock menu API, so `applicationDockMenu:` is added to tao's app
+//! delegate class at runtime. The menu itself is built on demand from a cached
+//! recent-docs list, which is refreshed (throttled) on document events.
+
+use crate::app_events::EmitScoped;
+use crate::AppState;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, Imp, Sel};
+use objc2::{define_class, msg_send, sel, MainThreadMarker, MainThreadOnly};
+use objc2_app_kit::{NSApplication, NSMenu, NSMenuItem};
+use objc2_foundation::{NSObject, NSString};
+use opencontext_core::events::SharedEventBus;
+use serde::Serialize;
+use std::cell::OnceCell;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::Manager;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+const RECENT_DOCS_LIMIT: usize = 5;
+/// Minimum delay between recent-docs refreshes while events keep arriving.
+const REFRESH_THROTTLE: Duration = Duration::from_secs(2);
+
+const TAG_NEW_DOCUMENT: isize = 1;
+const TAG_QUICK_CAPTURE: isize = 2;
+/// Recent doc items use `TAG_RECENT_BASE + index`.
+const TAG_RECENT_BASE: isize = 100;
+
+static APP_HANDLE: OnceLock<tauri::AppHandle> = OnceLock::new();
+static RECENT_DOCS: Mutex<Vec<RecentDoc>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static MENU_TARGET: OnceCell<Retained<DockMenuTarget>> = const { OnceCell::new() };
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecentDoc {
+    rel_path: String,
+    name: String,
+}
+
+define_class!(
+    // SAFETY: NSObject has no subclassing requirements and the class has no Drop impl.
+    #[unsafe(super(NSObject))]
+    #[thread_kind = MainThreadOnly]
+    #[name = "OpenContextDockMenuTarget"]
+    struct DockMenuTarget;
+
+    impl DockMenuTarget {
+        #[unsafe(method(dockItemClicked:))]
+        fn dock_item_clicked(&self, sender: &NSMenuItem) {
+            handle_dock_item(unsafe { sender.tag() });
+        }
+    }
+);
+
+impl DockMenuTarget {
+    fn new(mtm: MainThreadMarker) -> Retained<Self> {
+        let this = Self::alloc(mtm).set_ivars(());
+        unsafe { msg_send![super(this), init] }
+    }
+}
+
+fn handle_dock_item(tag: isize) {
+    let Some(app) = APP_HANDLE.get() else {
+        return;
+    };
+    crate::show_main_window(app);
+    match tag {
+        TAG_NEW_DOCUMENT => {
+            let _ = app.emit_scoped("menu-new-document", ());
+        }
+        TAG_QUICK_CAPTURE => {
+            let _ = app.emit_scoped("menu-quick-capture", ());
+        }
+        tag if tag >= TAG_RECENT_BASE => {
+            let index = (tag - TAG_RECENT_BASE) as usize;
+            let doc = RECENT_DOCS
+                .lock()
+                .ok()
+                .and_then(|docs| docs.get(index).cloned());
+            if let Some(doc) = doc {
+                let _ = app.emit_scoped("menu-open-doc", doc);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn menu_item(
+    mtm: MainThreadMarker,
+    target: &DockMenuTarget,
+    title: &str,
+    tag: isize,
+) -> Retained<NSMenuItem> {
+    unsafe {
+        let item = NSMenuItem::initWithTitle_action_keyEquivalent(
+            NSMenuItem::alloc(mtm),
+            &NSString::from_str(title),
+            Some(sel!(dockItemClicked:)),
+            &NSString::from_str(""),
+        );
+        let target: &AnyObject = target.as_ref();
+        item.setTarget(Some(target));
+        item.setTag(tag);
+        item
+    }
+}
+
+fn build_dock_menu(mtm: MainThreadMarker) -> Retained<NSMenu> {
+    let menu = NSMenu::new(mtm);
+    MENU_TARGET.with(|cell| {
+        let target = cell.get_or_init(|| DockMenuTarget::new(mtm));
+        menu.addItem(&menu_item(mtm, target, "New Document", TAG_NEW_DOCUMENT));
+        menu.addItem(&menu_item(mtm, target, "Quick Capture", TAG_QUICK_CAPTURE));
+
+        let recent = RECENT_DOCS
+            .lock()
+            .map(|docs| docs.clone())
+            .unwrap_or_default();
+        if !recent.is_empty() {
+            menu.addItem(&NSMenuItem::separatorItem(mtm));
+            for (index, doc) in recent.iter().enumerate() {
+                menu.addItem(&menu_item(
+                    mtm,
+                    target,
+                    &doc.name,
+                    TAG_RECENT_BASE + index as isize,
+                ));
+            }
+        }
+    });
+    menu
+}
+
+extern "C-unwind" fn application_dock_menu(
+    _this: &AnyObject,
+    _cmd: Sel,
+    _sender: &AnyObject,
+) -> *mut NSMenu {
+    match MainThreadMarker::new() {
+        Some(mtm) => Retained::autorelease_return(build_dock_menu(mtm)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+fn refresh_recent_docs(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let Ok(ctx) = state.ctx.read() else {
+        return;
+    };
+    let Ok(docs) = ctx.list_recent_docs(None, Some(RECENT_DOCS_LIMIT)) else {
+        return;
+    };
+    drop(ctx);
+
+    let recent = docs
+        .into_iter()
+        .map(|doc| RecentDoc {
+            rel_path: doc.rel_path,
+            name: doc.name,
+        })
+        .collect();
+    if let Ok(mut cached) = RECENT_DOCS.lock() {
+        *cached = recent;
+    }
+}
+
+/// Install the dock menu and keep its recent-docs list current.
+/// Must be called from the setup hook (main thread).
+pub(crate) fn install(app: &tauri::AppHandle, event_bus: SharedEventBus) {
+    let Some(mtm) = MainThreadMarker::new() else {
+        log::warn!("[DockMenu] Not on the main thread, skipping dock menu");
+        return;
+    };
+    let _ = APP_HANDLE.set(app.clone());
+    refresh_recent_docs(app);
+
+    let ns_app = NSApplication::sharedApplication(mtm);
+    let Some(delegate) = (unsafe { ns_app.delegate() }) else {
+        log::warn!("[DockMenu] No application delegate, skipping dock menu");
+        return;
+    };
+    let delegate: &AnyObject = (*delegate).as_ref();
+    let class = delegate.class();
+    let imp: Imp = unsafe {
+        std::mem::transmute::<extern "C-unwind" fn(&AnyObject, Sel, &AnyObject) -> *mut NSMenu, Imp>(
+            application_dock_menu,
+        )
+    };
+    // "@@:@" = returns id; takes self, _cmd and the NSApplication sender.
+    let added = unsafe {
+        objc2::ffi::class_addMethod(
+            class as *const _ as *mut _,
+            sel!(applicationDockMenu:),
+            imp,
+            c"@@:@".as_ptr(),
+        )
+    };
+    if !added.as_bool() {
+        log::warn!("[DockMenu] applicationDockMenu: already defined, skipping");
+        return;
+    }
+
+    let app = app.clone();
+    let mut receiver = event_bus.subscribe();
+    std::thread::spawn(move || loop {
+        match receiver.blocking_recv() {
+            Ok(_) | Err(RecvError::Lagged(_)) => {
+                // Trailing-edge throttle: wait, then fold any burst into one refresh.
+                std::thread::sleep(REFRESH_THROTTLE);
+                while !matches!(
+                    receiver.try_recv(),
+                    Err(TryRecvError::Empty) | Err(TryRecvError::Closed)
+                ) {}
+                refresh_recent_docs(&app);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    });
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+/// Catalogs compiled into the binary, keyed by language. Names match the
+/// frontend's i18n resources.
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("zh", include_str!("../locales/zh.json")),
+];
+
+const FALLBACK_LOCALE: &str = "en";
+
+static CURRENT_LOCALE: RwLock<&str> = RwLock::new(FALLBACK_LOCALE);
+
+type Catalog = HashMap<String, String>;
+
+fn catalogs() -> &'static HashMap<&'static str, Catalog> {
+    static CATALOGS: OnceLock<HashMap<&'static str, Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        LOCALES
+            .iter()
+            .map(|(locale, source)| {
+                let catalog = serde_json::from_str(source).unwrap_or_else(|e| {
+                    log::error!("[I18n] Invalid {} catalog: {}", locale, e);
+                    Catalog::new()
+                });
+                (*locale, catalog)
+            })
+            .collect()
+    })
+}
+
+/// Switch the locale for backend messages. Region tags (`zh-CN`) map to
+/// their language; unknown languages fall back to English. Returns the
+/// locale now in use.
+pub(crate) fn set_locale(tag: &str) -> &'static str {
+    let language = tag
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let locale = LOCALES
+        .iter()
+        .map(|(locale, _)| *locale)
+        .find(|locale| *locale == language)
+        .unwrap_or(FALLBACK_LOCALE);
+    *CURRENT_LOCALE.write().unwrap_or_else(|e| e.into_inner()) = locale;
+    locale
+}
+
+pub(crate) fn current_locale() -> &'static str {
+    *CURRENT_LOCALE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn resolve(locale: &str, key: &str, args: &[(&str, &str)]) -> Option<String> {
+    let catalogs = catalogs();
+    let template = catalogs
+        .get(locale)
+        .and_then(|catalog| catalog.get(key))
+        .or_else(|| catalogs.get(FALLBACK_LOCALE)?.get(key))?;
+    Some(interpolate(template, args))
+}
+
+/// Replace `{name}` placeholders in one pass, so argument values are never
+/// themselves expanded. Unknown placeholders are left as is.
+fn interpolate(template: &str, args: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| (*value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The message for `key` in the current locale (English if untranslated),
+/// or `None` if no catalog has it.
+pub(crate) fn lookup(key: &str, args: &[(&str, &str)]) -> Option<String> {
+    resolve(current_locale(), key, args)
+}
+
+/// Like `lookup`, but returns the key itself when the message is missing.
+pub(crate) fn t(key: &str, args: &[(&str, &str)]) -> String {
+    lookup(key, args).unwrap_or_else(|| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalogs_only_translate_known_keys() {
+        let catalogs = catalogs();
+        let english = &catalogs[FALLBACK_LOCALE];
+        assert!(!english.is_empty());
+        for (locale, catalog) in catalogs {
+            for key in catalog.keys() {
+                assert!(english.contains_key(key), "{locale} has unknown key {key}");
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_interpolates_and_falls_back_to_english() {
+        assert_eq!(
+            resolve("zh", "agent.error.auth_required", &[("label", "Claude")]).unwrap(),
+            "需要 Claude 认证。"
+        );
+        assert_eq!(
+            resolve("fr", "agent.error.auth_required", &[("label", "Claude")]).unwrap(),
+            "Claude authentication required."
+        );
+        assert_eq!(resolve("en", "missing.key", &[]), None);
+    }
+
+    #[test]
+    fn interpolate_does_not_expand_argument_values() {
+        assert_eq!(
+            interpolate(
+                "{label}: {detail} {other}",
+                &[("label", "{detail}"), ("detail", "x")]
+            ),
+            "{detail}: x {other}"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use crate::commands::search::{last_index_builds, run_index_build, update_index_metadata};
+use crate::utils::{read_config_json, ErrorCode};
+use crate::AppState;
+use chrono::{DateTime, Days, Local, NaiveTime, TimeDelta, TimeZone};
+use opencontext_core::search::SearchConfig;
+use serde_json::Value;
+use std::time::Duration;
+use tauri::Manager;
+
+/// How often the scheduler checks whether a build is due, so config edits
+/// and wake-ups from sleep are picked up without a restart
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When to rebuild the whole index in the background, from config.json:
+/// `INDEX_BUILD_SCHEDULE_TIME` (local "HH:MM", daily) or
+/// `INDEX_BUILD_SCHEDULE_HOURS` (interval). The time wins if both are set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BuildSchedule {
+    Every(TimeDelta),
+    DailyAt(NaiveTime),
+}
+
+impl BuildSchedule {
+    pub(crate) fn from_config() -> Option<Self> {
+        let config = read_config_json()?;
+        Self::parse(
+            config.get("INDEX_BUILD_SCHEDULE_HOURS"),
+            config.get("INDEX_BUILD_SCHEDULE_TIME"),
+        )
+    }
+
+    fn parse(hours: Option<&Value>, time: Option<&Value>) -> Option<Self> {
+        if let Some(time) = time
+            .and_then(Value::as_str)
+            .and_then(|t| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok())
+        {
+            return Some(Self::DailyAt(time));
+        }
+        let hours = match hours? {
+            Value::Number(n) => n.as_f64()?,
+            Value::String(text) => text.trim().parse().ok()?,
+            _ => return None,
+        };
+        if !(hours.is_finite() && hours > 0.0) {
+            return None;
+        }
+        TimeDelta::try_seconds((hours * 3600.0) as i64).map(Self::Every)
+    }
+
+    /// First run after `last_run`. A time in the past means the run is due.
+    pub(crate) fn next_run(&self, last_run: DateTime<Local>) -> DateTime<Local> {
+        match *self {
+            Self::Every(interval) => last_run + interval,
+            Self::DailyAt(time) => (0..=2)
+                .filter_map(|days| last_run.date_naive().checked_add_days(Days::new(days)))
+                // `None` when a DST change skips the time that day
+                .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+                .find(|candidate| *candidate > last_run)
+                .unwrap_or(last_run + TimeDelta::days(1)),
+        }
+    }
+}
+
+/// When the scheduled build is next due, counted from the last full or
+/// scheduled build. `None` without a schedule, or before the first full
+/// build: that one is left to the user, since it needs embeddings set up.
+pub(crate) fn next_scheduled_build(config: &SearchConfig) -> Option<DateTime<Local>> {
+    let schedule = BuildSchedule::from_config()?;
+    let (last_full, last_scheduled) = last_index_builds(config);
+    let last_run = last_full?.max(last_scheduled.unwrap_or(0));
+    let last_run = Local.timestamp_millis_opt(last_run as i64).single()?;
+    Some(schedule.next_run(last_run))
+}
+
+/// Body of the scheduled rebuild service: wakes up periodically and runs a
+/// full build once the schedule is due.
+pub(crate) async fn run(app: &tauri::AppHandle) {
+    loop {
+        let config = app.state::<AppState>().search_config();
+        if next_scheduled_build(&config).is_some_and(|next| next <= Local::now()) {
+            log::info!("[IndexSchedule] Starting scheduled index build");
+            match run_index_build(app, false).await {
+                Ok(stats) => log::info!(
+                    "[IndexSchedule] Scheduled build indexed {} docs",
+                    stats.total_docs
+                ),
+                // Another build is running; it counts as this run once done.
+                Err(e) if e.code == ErrorCode::Conflict => {
+                    log::info!("[IndexSchedule] Skipped: a build is already running");
+                }
+                Err(e) => log::warn!("[IndexSchedule] Scheduled build failed: {}", e),
+            }
+            // Recorded even on failure so a broken setup is retried on the
+            // next slot rather than every poll.
+            let mut values = serde_json::Map::new();
+            values.insert(
+                "lastScheduledBuild".to_string(),
+                Value::from(Local::now().timestamp_millis() as u64),
+            );
+            update_index_metadata(&config, values);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_prefers_daily_time_over_interval() {
+        let time = NaiveTime::from_hms_opt(3, 30, 0).unwrap();
+        assert_eq!(
+            BuildSchedule::parse(Some(&json!(6)), Some(&json!("03:30"))),
+            Some(BuildSchedule::DailyAt(time))
+        );
+        assert_eq!(
+            BuildSchedule::parse(Some(&json!("1.5")), Some(&json!("late"))),
+            Some(BuildSchedule::Every(TimeDelta::minutes(90)))
+        );
+        assert_eq!(BuildSchedule::parse(Some(&json!(0)), None), None);
+        assert_eq!(BuildSchedule::parse(None, None), None);
+    }
+
+    #[test]
+    fn next_run_is_the_first_slot_after_the_last_run() {
+        let every = BuildSchedule::Every(TimeDelta::hours(24));
+        assert_eq!(
+            every.next_run(local(2024, 5, 1, 10, 0)),
+            local(2024, 5, 2, 10, 0)
+        );
+
+        let daily = BuildSchedule::DailyAt(NaiveTime::from_hms_opt(3, 0, 0).unwrap());
+        assert_eq!(
+            daily.next_run(local(2024, 5, 1, 2, 0)),
+            local(2024, 5, 1, 3, 0)
+        );
+        assert_eq!(
+            daily.next_run(local(2024, 5, 1, 3, 0)),
+            local(2024, 5, 2, 3, 0)
+        );
+    }
+}
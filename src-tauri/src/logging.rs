@@ -0,0 +1,154 @@
+use crate::utils::get_config_value;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+const LOG_FILE_NAME: &str = "opencontext.log";
+/// Rotate once the active file grows past this size.
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Number of rotated files kept next to the active one (`opencontext.log.1` ...).
+const MAX_ROTATED_FILES: usize = 3;
+pub(crate) const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+static LOGGER: OnceLock<FileLogger> = OnceLock::new();
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+struct FileLogger {
+    path: PathBuf,
+    output: Mutex<Option<LogFile>>,
+}
+
+impl FileLogger {
+    fn open(path: &Path) -> std::io::Result<LogFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(LogFile { file, size })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        self.path
+            .with_file_name(format!("{}.{}", LOG_FILE_NAME, index))
+    }
+
+    fn rotate(&self, output: &mut Option<LogFile>) {
+        // Close the active file before renaming it (required on Windows).
+        *output = None;
+        let _ = std::fs::remove_file(self.rotated_path(MAX_ROTATED_FILES));
+        for index in (1..MAX_ROTATED_FILES).rev() {
+            let _ = std::fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+        }
+        let _ = std::fs::rename(&self.path, self.rotated_path(1));
+        *output = Self::open(&self.path).ok();
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies (lancedb, reqwest, tao, ...) are very chatty below info.
+        let own_target = metadata.target().starts_with("opencontext");
+        metadata.level() <= log::max_level() && (own_target || metadata.level() <= log::Level::Info)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            format_timestamp(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        if cfg!(debug_assertions) {
+            eprint!("{}", line);
+        }
+
+        let Ok(mut output) = self.output.lock() else {
+            return;
+        };
+        if output
+            .as_ref()
+            .is_some_and(|o| o.size + line.len() as u64 > MAX_LOG_BYTES)
+        {
+            self.rotate(&mut output);
+        }
+        if let Some(out) = output.as_mut() {
+            if out.file.write_all(line.as_bytes()).is_ok() {
+                out.size += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut output) = self.output.lock() {
+            if let Some(out) = output.as_mut() {
+                let _ = out.file.flush();
+            }
+        }
+    }
+}
+
+/// UTC timestamp without pulling in a date crate, e.g. `2024-05-01T12:34:56.789Z`.
+fn format_timestamp() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (hour, min, sec) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+
+    // Civil-from-days (Howard Hinnant)
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        hour,
+        min,
+        sec,
+        now.subsec_millis()
+    )
+}
+
+pub(crate) fn parse_level(value: &str) -> Option<LevelFilter> {
+    value.trim().parse().ok()
+}
+
+/// Install the global file logger writing to `log_dir/opencontext.log`.
+/// The initial level comes from `LOG_LEVEL` in config.json (default: info).
+pub(crate) fn init(log_dir: PathBuf) -> std::io::Result<()> {
+    std::fs::create_dir_all(&log_dir)?;
+    let path = log_dir.join(LOG_FILE_NAME);
+    let output = FileLogger::open(&path)?;
+    let logger = LOGGER.get_or_init(|| FileLogger {
+        path,
+        output: Mutex::new(Some(output)),
+    });
+    log::set_logger(logger).map_err(|e| std::io::Error::other(e.to_string()))?;
+    let level = get_config_value("LOG_LEVEL")
+        .and_then(|v| parse_level(&v))
+        .unwrap_or(DEFAULT_LEVEL);
+    log::set_max_level(level);
+    Ok(())
+}
+
+pub(crate) fn log_path() -> Option<PathBuf> {
+    LOGGER.get().map(|logger| logger.path.clone())
+}
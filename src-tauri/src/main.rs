@@ -2,37 +2,108 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod agent_rpc;
+mod agent_transcript;
+mod agent_watchdog;
+mod app_events;
 mod chat;
+mod cli;
 mod commands;
+mod digest_schedule;
+#[cfg(target_os = "macos")]
+mod dock_menu;
+mod i18n;
+mod index_schedule;
+mod logging;
+mod open_docs;
+mod services;
+mod stream_seq;
+mod tasks;
 mod terminal_session;
+mod tool_bridge;
 mod utils;
 
 use crate::agent_rpc::AgentRpcSession;
+use crate::app_events::EmitScoped;
 use crate::terminal_session::TerminalSession;
-use commands::{agent::*, ai::*, context::*, search::*, terminal::*};
+use commands::{
+    agent::*, ai::*, app::*, context::*, diff::*, digest::*, doctor::*, merge::*, onboarding::*,
+    patch::*, pricing::*, prompts::*, scratch::*, search::*, settings::*, share::*, snapshot::*,
+    stats::*, summarize::*, terminal::*, vault::*,
+};
 use opencontext_core::events::{create_event_bus, SharedEventBus};
 use opencontext_core::search::{IndexSyncService, Indexer, SearchConfig, Searcher};
 use opencontext_core::{EnvOverrides, OpenContext};
 use std::collections::HashMap;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+    Arc, Mutex, RwLock,
 };
 use tauri::image::Image;
-use tauri::menu::{Menu, MenuItem};
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, Submenu};
 use tauri::tray::{MouseButton, TrayIconBuilder, TrayIconEvent};
+use tauri::webview::PageLoadEvent;
 use tauri::{Manager, RunEvent, WindowEvent};
 use tokio::sync::Mutex as AsyncMutex;
 
+const TRAY_ID: &str = "main";
+
 struct AppState {
-    ctx: Mutex<OpenContext>,
+    /// Read commands share the lock; commands that change the tree take it
+    /// exclusively so they don't interleave with each other.
+    ctx: RwLock<OpenContext>,
     searcher: AsyncMutex<Option<Searcher>>,
     indexer: AsyncMutex<Option<Indexer>>,
-    search_config: SearchConfig,
-    #[allow(dead_code)]
+    /// Reloaded when settings are saved; see `reload_search_config`
+    search_config: RwLock<SearchConfig>,
     event_bus: SharedEventBus,
     terminal_sessions: Mutex<HashMap<String, TerminalSession>>,
     agent_rpc_sessions: Mutex<HashMap<String, Arc<AgentRpcSession>>>,
+    agent_transcripts: agent_transcript::TranscriptStore,
+    /// `seq` counters of running `agent-stream-*` requests
+    agent_stream_seqs: stream_seq::StreamSeqs,
+    /// Stall timers of running agent requests
+    agent_watchdogs: agent_watchdog::Watchdogs,
+    /// Docs open in the editor, for `doc-changed`
+    open_docs: open_docs::OpenDocs,
+    /// Set once quitting has been confirmed so window close is no longer intercepted
+    allow_close: Arc<AtomicBool>,
+    index_sync: Arc<IndexSyncService>,
+    embedding_migration: Mutex<EmbeddingMigration>,
+    tasks: tasks::TaskManager,
+    services: services::ServiceManager,
+    enrich_queue: commands::summarize::EnrichQueue,
+    tool_bridge: tool_bridge::ToolBridge,
+    chat_streams: commands::ai::ChatStreams,
+}
+
+impl AppState {
+    /// Snapshot of the current search config
+    fn search_config(&self) -> SearchConfig {
+        self.search_config
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+/// Tray "Pause Indexing" item, kept so its check state follows pauses made from the UI
+struct TrayPauseIndexingItem(CheckMenuItem<tauri::Wry>);
+
+fn tray_tooltip(indexing_paused: bool) -> &'static str {
+    if indexing_paused {
+        "OpenContext (indexing paused)"
+    } else {
+        "OpenContext"
+    }
+}
+
+fn sync_tray_indexing_state(app: &tauri::AppHandle, paused: bool) {
+    if let Some(item) = app.try_state::<TrayPauseIndexingItem>() {
+        let _ = item.0.set_checked(paused);
+    }
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some(tray_tooltip(paused)));
+    }
 }
 
 fn hide_main_window<R: tauri::Runtime>(window: &tauri::WebviewWindow<R>) {
@@ -56,7 +127,42 @@ fn show_main_window<R: tauri::Runtime>(app: &tauri::AppHandle<R>) {
     }
 }
 
+/// Pick the tray icon for the given system theme. `TRAY_ICON_VARIANT` in
+/// config.json (`auto` | `light` | `dark`) forces the variant for the named
+/// theme when detection is unreliable.
+#[cfg(not(target_os = "macos"))]
+fn tray_icon_for_theme(theme: tauri::Theme) -> Option<Image<'static>> {
+    let variant = utils::get_config_value("TRAY_ICON_VARIANT").map(|v| v.trim().to_lowercase());
+    let dark = match variant.as_deref() {
+        Some("light") => false,
+        Some("dark") => true,
+        _ => theme == tauri::Theme::Dark,
+    };
+    let bytes: &[u8] = if dark {
+        include_bytes!("../icons/tray-icon-dark-64.png")
+    } else {
+        include_bytes!("../icons/tray-icon-light-64.png")
+    };
+    Image::from_bytes(bytes).ok()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn apply_tray_theme<R: tauri::Runtime>(app: &tauri::AppHandle<R>, theme: tauri::Theme) {
+    if let (Some(tray), Some(icon)) = (app.tray_by_id(TRAY_ID), tray_icon_for_theme(theme)) {
+        let _ = tray.set_icon(Some(icon));
+    }
+}
+
 fn main() {
+    // Headless subcommands (`opencontext search ...`) exit before any window is created.
+    if let Some(code) = cli::run_from_env() {
+        std::process::exit(code);
+    }
+    // Agents start the binary as their stdio MCP server; see `tool_bridge`.
+    if let Some(code) = tool_bridge::run_relay_from_env() {
+        std::process::exit(code);
+    }
+
     // Create event bus for document lifecycle events
     let event_bus = create_event_bus();
 
@@ -65,13 +171,14 @@ fn main() {
         .expect("failed to initialize OpenContext core")
         .with_event_bus(event_bus.clone());
 
-    let search_config = SearchConfig::load().unwrap_or_default();
+    let (search_config, config_issues) = SearchConfig::load_with_issues();
+    let config_issues_for_setup = config_issues.clone();
     let contexts_root = ctx.env_info().contexts_root.clone();
 
-    // Clone for setup hook
-    let sync_event_bus = event_bus.clone();
-    let sync_config = search_config.clone();
-    let sync_contexts_root = contexts_root.clone();
+    let contexts_root_for_setup = contexts_root.clone();
+    let index_sync = Arc::new(IndexSyncService::new(search_config.clone(), contexts_root));
+    let indexing_paused = utils::get_config_bool("INDEX_SYNC_PAUSED").unwrap_or(false);
+    index_sync.set_paused(indexing_paused);
 
     let allow_close = Arc::new(AtomicBool::new(false));
     let allow_close_for_setup = allow_close.clone();
@@ -81,15 +188,41 @@ fn main() {
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .manage(AppState {
-            ctx: Mutex::new(ctx),
+            ctx: RwLock::new(ctx),
             searcher: AsyncMutex::new(None),
             indexer: AsyncMutex::new(None),
-            search_config,
+            search_config: RwLock::new(search_config),
             event_bus,
             terminal_sessions: Mutex::new(HashMap::new()),
             agent_rpc_sessions: Mutex::new(HashMap::new()),
+            agent_transcripts: Default::default(),
+            agent_stream_seqs: Default::default(),
+            agent_watchdogs: Default::default(),
+            open_docs: Default::default(),
+            allow_close: allow_close.clone(),
+            index_sync,
+            embedding_migration: Mutex::new(EmbeddingMigration::default()),
+            tasks: tasks::TaskManager::default(),
+            services: services::ServiceManager::default(),
+            enrich_queue: Default::default(),
+            tool_bridge: Default::default(),
+            chat_streams: Default::default(),
         })
         .setup(move |app| {
+            match app.path().app_log_dir() {
+                Ok(log_dir) => {
+                    if let Err(e) = logging::init(log_dir) {
+                        eprintln!("[Logging] Failed to initialize file logger: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("[Logging] Failed to resolve log directory: {}", e),
+            }
+            log::info!("[App] Starting OpenContext {}", app.package_info().version);
+            app_events::init(&app.config().identifier, &contexts_root_for_setup);
+            for issue in &config_issues_for_setup {
+                log::warn!("[Config] {}", issue);
+            }
+
             let minimize_to_tray_id: Option<tauri::menu::MenuId>;
 
             // Create Edit menu with predefined items for macOS
@@ -174,22 +307,45 @@ fn main() {
                 true,
                 None::<&str>,
             )?;
-            let tray_menu = Menu::with_items(app_handle, &[&tray_show, &tray_quit])?;
+            let tray_pause_indexing = CheckMenuItem::with_id(
+                app_handle,
+                "tray_pause_indexing",
+                "Pause Indexing",
+                true,
+                indexing_paused,
+                None::<&str>,
+            )?;
+            let tray_services = Submenu::with_id(app_handle, "tray_services", "Services", true)?;
+            let tray_menu = Menu::with_items(
+                app_handle,
+                &[&tray_show, &tray_pause_indexing, &tray_services, &tray_quit],
+            )?;
             let tray_show_id = tray_show.id().clone();
             let tray_quit_id = tray_quit.id().clone();
+            let tray_pause_indexing_id = tray_pause_indexing.id().clone();
+            app.manage(TrayPauseIndexingItem(tray_pause_indexing));
+            app.manage(services::TrayServicesMenu(tray_services));
+            services::rebuild_tray_menu(app_handle);
             let tray_app_handle = app_handle.clone();
-            let allow_close_for_menu = allow_close_for_setup.clone();
             let minimize_to_tray_id = minimize_to_tray_id.clone();
-            let mut tray_builder = TrayIconBuilder::new()
+            let mut tray_builder = TrayIconBuilder::with_id(TRAY_ID)
                 .menu(&tray_menu)
-                .tooltip("OpenContext")
+                .tooltip(tray_tooltip(indexing_paused))
                 .show_menu_on_left_click(false)
                 .on_menu_event(move |app, event| {
                     if event.id == tray_show_id {
                         show_main_window(app);
+                    } else if event.id == tray_pause_indexing_id {
+                        let paused = !app.state::<AppState>().index_sync.is_paused();
+                        if let Err(e) = apply_index_sync_paused(app, paused) {
+                            log::warn!("[IndexSync] Failed to toggle pause: {}", e);
+                        }
+                    } else if let Some(kind) = services::tray_item_service(event.id.as_ref()) {
+                        if let Err(e) = services::toggle(app, kind) {
+                            log::warn!("[Services] Failed to toggle {:?}: {}", kind, e);
+                        }
                     } else if event.id == tray_quit_id {
-                        allow_close_for_menu.store(true, Ordering::SeqCst);
-                        app.exit(0);
+                        request_quit(app, false);
                     } else if minimize_to_tray_id
                         .as_ref()
                         .map_or(false, |id| event.id == *id)
@@ -217,7 +373,13 @@ fn main() {
             }
             #[cfg(not(target_os = "macos"))]
             {
-                if let Some(icon) = app.default_window_icon().cloned() {
+                let theme = app_handle
+                    .get_webview_window("main")
+                    .and_then(|window| window.theme().ok())
+                    .unwrap_or(tauri::Theme::Light);
+                if let Some(icon) = tray_icon_for_theme(theme) {
+                    tray_builder = tray_builder.icon(icon);
+                } else if let Some(icon) = app.default_window_icon().cloned() {
                     tray_builder = tray_builder.icon(icon);
                 } else if let Ok(icon) = Image::from_bytes(include_bytes!("../icons/64x64.png")) {
                     tray_builder = tray_builder.icon(icon);
@@ -241,6 +403,10 @@ fn main() {
                 let window_for_event = window.clone();
                 let allow_close_for_window = allow_close_for_setup.clone();
                 window.on_window_event(move |event| {
+                    #[cfg(not(target_os = "macos"))]
+                    if let WindowEvent::ThemeChanged(theme) = event {
+                        apply_tray_theme(window_for_event.app_handle(), *theme);
+                    }
                     if let WindowEvent::CloseRequested { api, .. } = event {
                         if allow_close_for_window.load(Ordering::SeqCst) {
                             return;
@@ -251,17 +417,39 @@ fn main() {
                 });
             }
 
-            // Start index sync service in background
-            // Use tauri::async_runtime::spawn which works with Tauri's runtime management
-            tauri::async_runtime::spawn(async move {
-                let sync_service = IndexSyncService::new(sync_config, sync_contexts_root);
-                if let Err(e) = sync_service.start(sync_event_bus).await {
-                    log::error!("[IndexSync] Service error: {}", e);
+            #[cfg(target_os = "macos")]
+            dock_menu::install(app.handle(), app.state::<AppState>().event_bus.clone());
+
+            let follower = commands::search::follow_folder_renames(app_handle.clone());
+            tauri::async_runtime::spawn(follower);
+            tauri::async_runtime::spawn(open_docs::follow_doc_changes(app_handle.clone()));
+            let indexing = commands::search::follow_doc_indexing(app_handle.clone());
+            tauri::async_runtime::spawn(indexing);
+
+            let scratch_app = app_handle.clone();
+            std::thread::spawn(move || commands::scratch::prune_scratch_dirs(&scratch_app));
+
+            // Start background services; they can be stopped and restarted
+            // from the tray or the `service_*` commands.
+            let state = app.state::<AppState>();
+            for kind in [
+                services::ServiceKind::IndexSync,
+                services::ServiceKind::IndexSchedule,
+                services::ServiceKind::DigestSchedule,
+            ] {
+                if let Err(e) = state.services.start(app_handle, kind) {
+                    log::error!("[Services] Failed to start {:?}: {}", kind, e);
                 }
-            });
+            }
 
             Ok(())
         })
+        .on_page_load(move |webview, payload| {
+            // Settings fell back to defaults; tell the user why once the UI can listen.
+            if payload.event() == PageLoadEvent::Finished && !config_issues.is_empty() {
+                let _ = webview.emit_scoped("config-warnings", &config_issues);
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             // Folder commands
             list_folders,
@@ -280,28 +468,87 @@ fn main() {
             set_doc_description,
             get_doc_content,
             save_doc_content,
+            open_doc,
+            close_doc,
+            diff_doc_content,
+            merge_doc_content,
+            share_doc_snapshot,
             // Utility commands
             generate_manifest,
+            folder_settings_get,
+            folder_settings_set,
+            // Vault encryption
+            vault_status,
+            unlock_vault,
+            lock_vault,
+            set_folder_encrypted,
+            vault_statistics,
+            vault_snapshot_create,
+            vault_snapshot_list,
+            vault_snapshot_restore,
             get_env_info,
             save_config,
+            validate_config,
+            get_effective_config,
             terminal_spawn,
             terminal_write,
             terminal_resize,
             terminal_kill,
+            terminal_pause_output,
+            terminal_resume_output,
+            terminal_clear,
             // Search commands
             semantic_search,
+            oc_search,
             build_search_index,
+            cancel_search_index,
             get_index_status,
             clean_search_index,
+            inspect_doc_index,
+            get_doc_index_status,
+            index_doc,
+            embedding_usage,
+            reset_usage_ledger,
+            reset_corrupt_index,
+            migrate_embeddings,
+            task_list,
+            set_backend_locale,
+            task_cancel,
+            services_status,
+            service_start,
+            service_stop,
+            apply_embedding_migration,
+            discard_embedding_migration,
+            get_index_sync_status,
+            set_index_sync_paused,
             // AI commands
             get_ai_config,
             save_ai_config,
             ai_chat,
+            ai_chat_once,
+            ai_chat_abort,
+            pricing_get,
+            pricing_save,
+            chat_usage,
+            prompt_templates_list,
+            prompt_templates_save,
+            prompt_templates_delete,
+            render_prompt_template,
+            summarize_doc,
+            summarize_folder,
+            generate_digest,
+            accept_doc_enrichment,
             agent_sessions_load,
             agent_sessions_save,
+            agent_transcript_get,
+            agent_transcript_delete,
+            agent_transcript_export,
+            agent_scratch_list,
+            agent_scratch_import,
             codex_exec,
             codex_kill,
             codex_permission_response,
+            agent_patch_preview,
             acp_permission_response,
             claude_exec,
             claude_kill,
@@ -311,13 +558,29 @@ fn main() {
             agent_models_get,
             agent_models_save,
             oc_exec,
+            // App commands
+            quit_app,
+            confirm_quit,
+            get_log_path,
+            open_logs_folder,
+            set_log_level,
+            env_doctor,
+            onboarding_status,
+            onboarding_dismiss,
+            create_sample_content,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
 
     app.run(move |app_handle, event| {
-        if let RunEvent::ExitRequested { .. } = event {
-            allow_close_for_run.store(true, Ordering::SeqCst);
+        if let RunEvent::ExitRequested { api, .. } = &event {
+            if !allow_close_for_run.load(Ordering::SeqCst) {
+                if prompt_quit_if_busy(app_handle) {
+                    api.prevent_exit();
+                } else {
+                    allow_close_for_run.store(true, Ordering::SeqCst);
+                }
+            }
         }
         #[cfg(target_os = "macos")]
         if let RunEvent::Reopen {
@@ -0,0 +1,225 @@
+//! Docs open in the editor, and `doc-changed` events when one of them is
+//! written from elsewhere
+//!
+//! `open_doc` starts tracking a doc with its current content as the
+//! baseline, and `get_doc_content` and `save_doc_content` keep that
+//! baseline at what the editor last loaded or saved. When the event bus
+//! reports a write the editor didn't make, such as an agent's tool call, the
+//! new content is diffed against the baseline and `doc-changed` says which
+//! lines changed, so the editor can offer to reload or merge instead of a
+//! blind prompt. Writes that bypass the event bus, like a snapshot restore,
+//! call `check` and `deleted` themselves.
+
+use crate::app_events::EmitScoped;
+use crate::commands::diff::{summarize_changes, ChangeSummary};
+use crate::AppState;
+use opencontext_core::events::{DocEvent, Event, FolderEvent};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::Manager;
+use tokio::sync::broadcast::error::RecvError;
+
+/// A doc open in one or more editors
+struct OpenDoc {
+    /// Content the editor was last served or saved
+    baseline: String,
+    /// `open_doc` calls not yet matched by `close_doc`
+    opens: usize,
+}
+
+/// Payload of `doc-changed`
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocChanged {
+    path: String,
+    /// The doc is gone, with the folder it was in or on its own
+    deleted: bool,
+    /// Changes from the editor's baseline, absent for a deleted doc
+    #[serde(flatten)]
+    changes: Option<ChangeSummary>,
+}
+
+/// Baselines of open docs by vault path
+#[derive(Default)]
+pub(crate) struct OpenDocs {
+    docs: Mutex<HashMap<String, OpenDoc>>,
+}
+
+impl OpenDocs {
+    fn docs(&self) -> std::sync::MutexGuard<'_, HashMap<String, OpenDoc>> {
+        self.docs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Track `path` with `content` as its baseline
+    pub(crate) fn open(&self, path: &str, content: String) {
+        let mut docs = self.docs();
+        let doc = docs.entry(path.to_string()).or_insert(OpenDoc {
+            baseline: String::new(),
+            opens: 0,
+        });
+        doc.baseline = content;
+        doc.opens += 1;
+    }
+
+    /// Stop tracking `path` once every editor that opened it has closed it
+    pub(crate) fn close(&self, path: &str) {
+        let mut docs = self.docs();
+        if let Some(doc) = docs.get_mut(path) {
+            doc.opens -= 1;
+            if doc.opens == 0 {
+                docs.remove(path);
+            }
+        }
+    }
+
+    /// The editor now has `content` of `path`, if that doc is open
+    pub(crate) fn served(&self, path: &str, content: &str) {
+        if let Some(doc) = self.docs().get_mut(path) {
+            doc.baseline = content.to_string();
+        }
+    }
+
+    /// Changes from the baseline of `path` to `content`, or `None` when the
+    /// doc isn't open or is unchanged
+    fn changes(&self, path: &str, content: &str) -> Option<ChangeSummary> {
+        let docs = self.docs();
+        let baseline = &docs.get(path)?.baseline;
+        (baseline != content).then(|| summarize_changes(baseline, content))
+    }
+
+    /// Follow a doc to its new path
+    fn rename(&self, old_path: &str, new_path: &str) {
+        let mut docs = self.docs();
+        if let Some(doc) = docs.remove(old_path) {
+            docs.insert(new_path.to_string(), doc);
+        }
+    }
+
+    /// Stop tracking a deleted doc; true if it was open
+    fn remove(&self, path: &str) -> bool {
+        self.docs().remove(path).is_some()
+    }
+
+    fn paths(&self) -> Vec<String> {
+        self.docs().keys().cloned().collect()
+    }
+}
+
+/// Emit `doc-changed` if the saved content of `path` differs from what the
+/// editor has
+pub(crate) fn check(app: &tauri::AppHandle, path: &str) {
+    let state = app.state::<AppState>();
+    let content = match state.ctx.read() {
+        Ok(ctx) => ctx.get_doc_content(path),
+        Err(_) => return,
+    };
+    let content = match content {
+        Ok(content) => content,
+        Err(e) => {
+            log::warn!("[OpenDocs] Could not read {}: {}", path, e);
+            return;
+        }
+    };
+    if let Some(changes) = state.open_docs.changes(path, &content) {
+        let _ = app.emit_scoped(
+            "doc-changed",
+            DocChanged {
+                path: path.to_string(),
+                deleted: false,
+                changes: Some(changes),
+            },
+        );
+    }
+}
+
+/// Emit `doc-changed` for `path` if it was open, and stop tracking it
+pub(crate) fn deleted(app: &tauri::AppHandle, path: &str) {
+    if app.state::<AppState>().open_docs.remove(path) {
+        let _ = app.emit_scoped(
+            "doc-changed",
+            DocChanged {
+                path: path.to_string(),
+                deleted: true,
+                changes: None,
+            },
+        );
+    }
+}
+
+/// Turn writes to open docs into `doc-changed` events. Runs until the event
+/// bus closes.
+pub(crate) async fn follow_doc_changes(app: tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let mut receiver = state.event_bus.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(Event::Doc(DocEvent::Updated { rel_path })) => check(&app, &rel_path),
+            Ok(Event::Doc(DocEvent::Deleted { rel_path })) => deleted(&app, &rel_path),
+            Ok(Event::Doc(
+                DocEvent::Renamed { old_path, new_path } | DocEvent::Moved { old_path, new_path },
+            )) => state.open_docs.rename(&old_path, &new_path),
+            Ok(Event::Doc(DocEvent::Created { .. })) => {}
+            Ok(Event::Folder(
+                FolderEvent::Renamed { affected_docs, .. }
+                | FolderEvent::Moved { affected_docs, .. },
+            )) => {
+                for (old_path, new_path) in &affected_docs {
+                    state.open_docs.rename(old_path, new_path);
+                }
+            }
+            Ok(Event::Folder(FolderEvent::Deleted { removed_docs, .. })) => {
+                for path in &removed_docs {
+                    deleted(&app, path);
+                }
+            }
+            Ok(Event::Folder(FolderEvent::Created { .. })) => {}
+            // Writes may have been missed; compare every open doc
+            Err(RecvError::Lagged(_)) => {
+                for path in state.open_docs.paths() {
+                    check(&app, &path);
+                }
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baseline_follows_what_the_editor_loaded() {
+        let docs = OpenDocs::default();
+        docs.served("notes/a.md", "ignored\n");
+        assert!(docs.changes("notes/a.md", "x\n").is_none());
+
+        docs.open("notes/a.md", "one\ntwo\n".to_string());
+        assert!(docs.changes("notes/a.md", "one\ntwo\n").is_none());
+        let changes = docs.changes("notes/a.md", "one\n2\n").unwrap();
+        assert_eq!((changes.added, changes.removed), (1, 1));
+
+        // The editor reloaded, so the same content is no longer a change
+        docs.served("notes/a.md", "one\n2\n");
+        assert!(docs.changes("notes/a.md", "one\n2\n").is_none());
+
+        docs.rename("notes/a.md", "archive/a.md");
+        assert!(docs.changes("archive/a.md", "one\n").is_some());
+        assert!(docs.changes("notes/a.md", "one\n").is_none());
+    }
+
+    #[test]
+    fn doc_stays_open_until_every_editor_closes_it() {
+        let docs = OpenDocs::default();
+        docs.open("a.md", String::new());
+        docs.open("a.md", String::new());
+        docs.close("a.md");
+        assert_eq!(docs.paths(), ["a.md"]);
+        docs.close("a.md");
+        assert!(docs.paths().is_empty());
+        // Closing a doc that isn't open is a no-op
+        docs.close("a.md");
+        assert!(!docs.remove("a.md"));
+    }
+}
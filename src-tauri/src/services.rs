@@ -0,0 +1,277 @@
+use crate::app_events::EmitScoped;
+use crate::utils::CmdResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::async_runtime::JoinHandle;
+use tauri::menu::{CheckMenuItem, Submenu};
+use tauri::Manager;
+
+/// Background services the app runs alongside the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ServiceKind {
+    IndexSync,
+    IndexSchedule,
+    DigestSchedule,
+}
+
+impl ServiceKind {
+    const ALL: [ServiceKind; 3] = [
+        ServiceKind::IndexSync,
+        ServiceKind::IndexSchedule,
+        ServiceKind::DigestSchedule,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ServiceKind::IndexSync => "index-sync",
+            ServiceKind::IndexSchedule => "index-schedule",
+            ServiceKind::DigestSchedule => "digest-schedule",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ServiceKind::IndexSync => "Index Sync",
+            ServiceKind::IndexSchedule => "Scheduled Rebuild",
+            ServiceKind::DigestSchedule => "Scheduled Digest",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ServiceState {
+    Running,
+    Stopped,
+    Error,
+}
+
+/// Snapshot of a service, as listed by `services_status` and sent with
+/// `services-changed`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ServiceInfo {
+    kind: ServiceKind,
+    state: ServiceState,
+    /// Seconds since the service started, while running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uptime_secs: Option<u64>,
+    /// Why the service last stopped on its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct ServiceEntry {
+    handle: Option<JoinHandle<()>>,
+    started_at: Option<Instant>,
+    error: Option<String>,
+}
+
+/// Handles of the running background services, so they can be listed,
+/// stopped and restarted at runtime.
+#[derive(Default)]
+pub(crate) struct ServiceManager {
+    entries: Mutex<HashMap<ServiceKind, ServiceEntry>>,
+}
+
+impl ServiceManager {
+    pub(crate) fn status(&self) -> Vec<ServiceInfo> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        ServiceKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let entry = entries.get(&kind);
+                let started_at = entry.and_then(|e| e.started_at);
+                let error = entry.and_then(|e| e.error.clone());
+                let state = match (started_at, &error) {
+                    (Some(_), _) => ServiceState::Running,
+                    (None, Some(_)) => ServiceState::Error,
+                    (None, None) => ServiceState::Stopped,
+                };
+                ServiceInfo {
+                    kind,
+                    state,
+                    uptime_secs: started_at.map(|t| t.elapsed().as_secs()),
+                    error,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn is_running(&self, kind: ServiceKind) -> bool {
+        self.entries
+            .lock()
+            .map(|entries| entries.get(&kind).is_some_and(|e| e.started_at.is_some()))
+            .unwrap_or(false)
+    }
+
+    /// Whether a service stopped on its own with an error
+    pub(crate) fn failed(&self, kind: ServiceKind) -> bool {
+        self.entries
+            .lock()
+            .map(|entries| {
+                entries
+                    .get(&kind)
+                    .is_some_and(|e| e.started_at.is_none() && e.error.is_some())
+            })
+            .unwrap_or(false)
+    }
+
+    /// Start a service. A no-op if it is already running.
+    pub(crate) fn start(&self, app: &tauri::AppHandle, kind: ServiceKind) -> CmdResult<()> {
+        {
+            let mut entries = self.entries.lock().map_err(|e| e.to_string())?;
+            let entry = entries.entry(kind).or_default();
+            if entry.started_at.is_some() {
+                return Ok(());
+            }
+            let app_for_task = app.clone();
+            let handle = match kind {
+                ServiceKind::IndexSync => {
+                    let state = app.state::<crate::AppState>();
+                    let index_sync = state.index_sync.clone();
+                    let event_bus = state.event_bus.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let error = index_sync.start(event_bus).await.err();
+                        if let Some(e) = &error {
+                            log::error!("[IndexSync] Service error: {}", e);
+                        }
+                        services(&app_for_task).exited(
+                            &app_for_task,
+                            kind,
+                            error.map(|e| e.to_string()),
+                        );
+                    })
+                }
+                ServiceKind::IndexSchedule => tauri::async_runtime::spawn(async move {
+                    crate::index_schedule::run(&app_for_task).await;
+                    services(&app_for_task).exited(&app_for_task, kind, None);
+                }),
+                ServiceKind::DigestSchedule => tauri::async_runtime::spawn(async move {
+                    crate::digest_schedule::run(&app_for_task).await;
+                    services(&app_for_task).exited(&app_for_task, kind, None);
+                }),
+            };
+            *entry = ServiceEntry {
+                handle: Some(handle),
+                started_at: Some(Instant::now()),
+                error: None,
+            };
+        }
+        log::info!("[Services] Started {}", kind.as_str());
+        notify_changed(app);
+        Ok(())
+    }
+
+    /// Stop a service. A no-op if it is not running.
+    pub(crate) fn stop(&self, app: &tauri::AppHandle, kind: ServiceKind) -> CmdResult<()> {
+        let handle = {
+            let mut entries = self.entries.lock().map_err(|e| e.to_string())?;
+            match entries.get_mut(&kind) {
+                Some(entry) if entry.started_at.is_some() => {
+                    let handle = entry.handle.take();
+                    *entry = ServiceEntry::default();
+                    handle
+                }
+                _ => return Ok(()),
+            }
+        };
+        if let Some(handle) = handle {
+            handle.abort();
+        }
+        log::info!("[Services] Stopped {}", kind.as_str());
+        notify_changed(app);
+        Ok(())
+    }
+
+    /// Record that a service's task ended without being stopped
+    fn exited(&self, app: &tauri::AppHandle, kind: ServiceKind, error: Option<String>) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(
+                kind,
+                ServiceEntry {
+                    error: Some(error.unwrap_or_else(|| "Service exited".to_string())),
+                    ..Default::default()
+                },
+            );
+        }
+        notify_changed(app);
+    }
+}
+
+fn services(app: &tauri::AppHandle) -> &ServiceManager {
+    &app.state::<crate::AppState>().inner().services
+}
+
+/// Start the service if it is stopped, stop it otherwise
+pub(crate) fn toggle(app: &tauri::AppHandle, kind: ServiceKind) -> CmdResult<()> {
+    let manager = services(app);
+    if manager.is_running(kind) {
+        manager.stop(app, kind)
+    } else {
+        manager.start(app, kind)
+    }
+}
+
+// ===== Tray =====
+
+/// Prefix of the tray item ids in the "Services" submenu
+const TRAY_SERVICE_PREFIX: &str = "tray_service:";
+
+/// Tray "Services" submenu, kept so it can be rebuilt when a service changes
+pub(crate) struct TrayServicesMenu(pub(crate) Submenu<tauri::Wry>);
+
+/// The service a "Services" submenu item id refers to
+pub(crate) fn tray_item_service(id: &str) -> Option<ServiceKind> {
+    let name = id.strip_prefix(TRAY_SERVICE_PREFIX)?;
+    ServiceKind::ALL
+        .into_iter()
+        .find(|kind| kind.as_str() == name)
+}
+
+fn tray_item_text(info: &ServiceInfo) -> String {
+    match info.state {
+        ServiceState::Running => info.kind.label().to_string(),
+        ServiceState::Stopped => format!("{} (stopped)", info.kind.label()),
+        ServiceState::Error => format!("{} (error)", info.kind.label()),
+    }
+}
+
+/// Replace the submenu items with one checkable item per service
+pub(crate) fn rebuild_tray_menu(app: &tauri::AppHandle) {
+    let Some(menu) = app.try_state::<TrayServicesMenu>() else {
+        return;
+    };
+    if let Ok(items) = menu.0.items() {
+        for item in items {
+            let _ = menu.0.remove(&item);
+        }
+    }
+    for info in services(app).status() {
+        let item = CheckMenuItem::with_id(
+            app,
+            format!("{}{}", TRAY_SERVICE_PREFIX, info.kind.as_str()),
+            tray_item_text(&info),
+            true,
+            info.state == ServiceState::Running,
+            None::<&str>,
+        );
+        match item {
+            Ok(item) => {
+                let _ = menu.0.append(&item);
+            }
+            Err(e) => log::warn!("[Services] Failed to build tray item: {}", e),
+        }
+    }
+}
+
+fn notify_changed(app: &tauri::AppHandle) {
+    let _ = app.emit_scoped("services-changed", services(app).status());
+    rebuild_tray_menu(app);
+}
@@ -0,0 +1,97 @@
+//! Sequence numbers for streamed events
+//!
+//! Tauri doesn't guarantee the order events arrive in under load, so every
+//! `ai-stream-*` and `agent-stream-*` event carries a `seq` counting up from
+//! 0 per request. The `done` event's `seq` is the last one: a frontend that
+//! received fewer events has missed chunks and can resync from the agent
+//! transcript.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// An event with its `seq` alongside its own fields
+#[derive(Serialize, Clone)]
+pub(crate) struct Sequenced<T> {
+    seq: u64,
+    #[serde(flatten)]
+    event: T,
+}
+
+/// Counter of a single stream
+#[derive(Default)]
+pub(crate) struct StreamSeq(AtomicU64);
+
+impl StreamSeq {
+    /// Number `event` with the stream's next seq
+    pub(crate) fn wrap<T>(&self, event: T) -> Sequenced<T> {
+        Sequenced {
+            seq: self.0.fetch_add(1, Ordering::Relaxed),
+            event,
+        }
+    }
+}
+
+/// Counters of streams by request id, for streams emitted from many places
+#[derive(Default)]
+pub(crate) struct StreamSeqs {
+    next: Mutex<HashMap<String, u64>>,
+}
+
+impl StreamSeqs {
+    /// Number `event` with the next seq of `request_id`. The `last` event
+    /// ends the stream and drops its counter.
+    pub(crate) fn wrap<T>(&self, request_id: &str, event: T, last: bool) -> Sequenced<T> {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let seq = if last {
+            next.remove(request_id).unwrap_or(0)
+        } else {
+            let counter = next.entry(request_id.to_string()).or_insert(0);
+            *counter += 1;
+            *counter - 1
+        };
+        Sequenced { seq, event }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn seq_counts_up_across_interleaved_event_kinds() {
+        let seqs = StreamSeqs::default();
+        let events = [
+            json!({ "status": "thinking" }),
+            json!({ "reasoning": "Look at the doc" }),
+            json!({ "content": "The doc " }),
+            json!({ "tool": { "type": "tool_call", "callId": "1" } }),
+            json!({ "reasoning": "Summarize" }),
+            json!({ "content": "says hi" }),
+            json!({ "tool": { "type": "tool_call_update", "callId": "1" } }),
+        ];
+        for (expected, event) in events.into_iter().enumerate() {
+            // Another request's events don't take this one's numbers
+            seqs.wrap("other", json!({ "content": "x" }), false);
+            let sequenced = serde_json::to_value(seqs.wrap("req", event, false)).unwrap();
+            assert_eq!(sequenced["seq"], json!(expected));
+        }
+
+        let done = serde_json::to_value(seqs.wrap("req", json!({ "done": true }), true)).unwrap();
+        assert_eq!(done, json!({ "seq": 7, "done": true }));
+        // The stream ended; a reused id starts over
+        assert_eq!(
+            serde_json::to_value(seqs.wrap("req", json!({}), false)).unwrap()["seq"],
+            json!(0)
+        );
+    }
+
+    #[test]
+    fn single_stream_counter_numbers_in_emit_order() {
+        let seq = StreamSeq::default();
+        let numbers: Vec<u64> = (0..3).map(|i| seq.wrap(i).seq).collect();
+        assert_eq!(numbers, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::app_events::EmitScoped;
+use crate::utils::{CmdResult, CommandError, ErrorCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Shared flag a task checks (or awaits) to learn it should stop.
+#[derive(Clone, Default)]
+pub(crate) struct CancellationToken {
+    inner: Arc<(AtomicBool, Notify)>,
+}
+
+impl CancellationToken {
+    pub(crate) fn cancel(&self) {
+        self.inner.0.store(true, Ordering::SeqCst);
+        self.inner.1.notify_waiters();
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.inner.0.load(Ordering::SeqCst)
+    }
+
+    /// The flag itself, for work that polls an `AtomicBool` between steps
+    pub(crate) fn flag(&self) -> &AtomicBool {
+        &self.inner.0
+    }
+
+    /// Resolves once `cancel` has been called
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.1.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// What a task is doing. Kinds marked exclusive run one at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum TaskKind {
+    IndexBuild,
+    Summarize,
+    /// Description and tag suggestions for a saved doc
+    Enrich,
+    /// Creating or restoring a vault snapshot
+    Snapshot,
+    /// Writing a digest of recent changes
+    Digest,
+    /// Re-embedding every doc into a staging index with a new model
+    EmbeddingMigration,
+}
+
+impl TaskKind {
+    fn exclusive(self) -> bool {
+        match self {
+            TaskKind::IndexBuild
+            | TaskKind::Summarize
+            | TaskKind::Snapshot
+            | TaskKind::Digest
+            | TaskKind::EmbeddingMigration => true,
+            TaskKind::Enrich => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TaskStatus {
+    Running,
+    Cancelling,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// Snapshot of a task, as listed by `task_list` and sent with `task-progress`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaskInfo {
+    pub(crate) id: u64,
+    pub(crate) kind: TaskKind,
+    pub(crate) status: TaskStatus,
+    pub(crate) current: usize,
+    pub(crate) total: usize,
+    pub(crate) percent: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) message: Option<String>,
+    /// ms since epoch
+    pub(crate) started_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+struct TaskEntry {
+    info: TaskInfo,
+    token: CancellationToken,
+}
+
+/// Registry of long-running background work, so the UI can list and cancel
+/// any of it from one place.
+#[derive(Default)]
+pub(crate) struct TaskManager {
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, TaskEntry>>,
+}
+
+impl TaskManager {
+    /// Register a task. Fails with `conflict` if an exclusive task of the
+    /// same kind is already running.
+    pub(crate) fn start(&self, app: &tauri::AppHandle, kind: TaskKind) -> CmdResult<TaskHandle> {
+        let mut tasks = self.tasks.lock().map_err(|e| e.to_string())?;
+        if kind.exclusive() && tasks.values().any(|task| task.info.kind == kind) {
+            return Err(CommandError::localized(
+                ErrorCode::Conflict,
+                "error.task_already_running",
+                &[],
+            ));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let token = CancellationToken::default();
+        let info = TaskInfo {
+            id,
+            kind,
+            status: TaskStatus::Running,
+            current: 0,
+            total: 0,
+            percent: 0,
+            message: None,
+            started_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            error: None,
+        };
+        let _ = app.emit_scoped("task-progress", &info);
+        tasks.insert(
+            id,
+            TaskEntry {
+                info,
+                token: token.clone(),
+            },
+        );
+        Ok(TaskHandle {
+            id,
+            token,
+            app: app.clone(),
+        })
+    }
+
+    pub(crate) fn list(&self) -> Vec<TaskInfo> {
+        let Ok(tasks) = self.tasks.lock() else {
+            return Vec::new();
+        };
+        let mut list: Vec<TaskInfo> = tasks.values().map(|task| task.info.clone()).collect();
+        list.sort_by_key(|info| info.id);
+        list
+    }
+
+    /// Request cancellation. Returns false if no such task is running.
+    pub(crate) fn cancel(&self, app: &tauri::AppHandle, id: u64) -> bool {
+        let Ok(mut tasks) = self.tasks.lock() else {
+            return false;
+        };
+        let Some(task) = tasks.get_mut(&id) else {
+            return false;
+        };
+        task.token.cancel();
+        task.info.status = TaskStatus::Cancelling;
+        let _ = app.emit_scoped("task-progress", &task.info);
+        true
+    }
+
+    fn update(&self, app: &tauri::AppHandle, id: u64, apply: impl FnOnce(&mut TaskInfo)) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            if let Some(task) = tasks.get_mut(&id) {
+                apply(&mut task.info);
+                let _ = app.emit_scoped("task-progress", &task.info);
+            }
+        }
+    }
+
+    fn remove(&self, app: &tauri::AppHandle, id: u64, status: TaskStatus, error: Option<String>) {
+        let Ok(mut tasks) = self.tasks.lock() else {
+            return;
+        };
+        if let Some(mut task) = tasks.remove(&id) {
+            task.info.status = status;
+            task.info.error = error;
+            let _ = app.emit_scoped("task-progress", &task.info);
+        }
+    }
+}
+
+/// A registered task. Dropping it unregisters the task; call `finish` to
+/// report how it ended.
+pub(crate) struct TaskHandle {
+    id: u64,
+    token: CancellationToken,
+    app: tauri::AppHandle,
+}
+
+impl TaskHandle {
+    pub(crate) fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub(crate) fn progress(&self, current: usize, total: usize, message: Option<String>) {
+        let percent = (current * 100 / total.max(1)).min(100) as u8;
+        self.manager().update(&self.app, self.id, |info| {
+            info.current = current;
+            info.total = total;
+            info.percent = percent;
+            info.message = message;
+        });
+    }
+
+    /// Run `work`, abandoning it if the task is cancelled first
+    pub(crate) async fn run<T>(&self, work: impl Future<Output = CmdResult<T>>) -> CmdResult<T> {
+        tokio::select! {
+            result = work => result,
+            _ = self.token.cancelled() => Err(CommandError::localized(ErrorCode::Cancelled, "error.task_cancelled", &[])),
+        }
+    }
+
+    pub(crate) fn finish<T>(self, result: &CmdResult<T>) {
+        let (status, error) = match result {
+            Ok(_) => (TaskStatus::Completed, None),
+            Err(e) if e.code == ErrorCode::Cancelled => (TaskStatus::Cancelled, None),
+            Err(e) => (TaskStatus::Failed, Some(e.message.clone())),
+        };
+        self.manager().remove(&self.app, self.id, status, error);
+    }
+
+    fn manager(&self) -> &TaskManager {
+        use tauri::Manager;
+        &self.app.state::<crate::AppState>().inner().tasks
+    }
+}
+
+impl Drop for TaskHandle {
+    fn drop(&mut self) {
+        let status = if self.token.is_cancelled() {
+            TaskStatus::Cancelled
+        } else {
+            TaskStatus::Failed
+        };
+        // No-op when `finish` already removed the task.
+        self.manager().remove(&self.app, self.id, status, None);
+    }
+}
+
+/// Limits how often a long job reports progress to the webview: at most once
+/// per `interval` or every `every` items, always including the first and
+/// last update. Usable by any task that counts items towards a known total.
+pub(crate) struct ProgressThrottle {
+    started: Instant,
+    interval: Duration,
+    every: usize,
+    /// When the last update was let through, and its item count
+    last_sent: Option<(Instant, usize)>,
+}
+
+impl ProgressThrottle {
+    pub(crate) fn new(interval: Duration, every: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            interval,
+            every: every.max(1),
+            last_sent: None,
+        }
+    }
+
+    /// Whether an update at `done` of `total` items should be sent
+    pub(crate) fn should_send(&mut self, done: usize, total: usize) -> bool {
+        let now = Instant::now();
+        let send = match self.last_sent {
+            None => true,
+            Some(_) if done >= total => true,
+            Some((at, last_done)) => {
+                now.duration_since(at) >= self.interval
+                    || done.saturating_sub(last_done) >= self.every
+            }
+        };
+        if send {
+            self.last_sent = Some((now, done));
+        }
+        send
+    }
+
+    /// Time left at the average rate so far; `None` until an item is done
+    pub(crate) fn eta_ms(&self, done: usize, total: usize) -> Option<u64> {
+        if done == 0 {
+            return None;
+        }
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        Some(elapsed * total.saturating_sub(done) as u64 / done as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_throttle_sends_first_last_and_every_n() {
+        let mut throttle = ProgressThrottle::new(Duration::from_secs(60), 10);
+        let sent: Vec<usize> = (0..=25)
+            .filter(|done| throttle.should_send(*done, 25))
+            .collect();
+        assert_eq!(sent, [0, 10, 20, 25]);
+        assert_eq!(throttle.eta_ms(0, 25), None);
+        assert_eq!(throttle.eta_ms(25, 25), Some(0));
+    }
+}
@@ -1,9 +1,72 @@
 use portable_pty::MasterPty;
+use std::collections::VecDeque;
 use std::io::Write;
 use std::sync::{Arc, Mutex};
 
+/// Upper bound for output held back while a terminal's stream is paused.
+const MAX_BACKLOG_BYTES: usize = 256 * 1024;
+
+/// Gate between the PTY reader thread and the `terminal-output` event stream.
+///
+/// Pausing only stops emitting to the UI; the process keeps running and its
+/// output is kept in a bounded backlog (oldest bytes dropped) until resumed.
+#[derive(Default)]
+pub(crate) struct TerminalOutput {
+    pub(crate) paused: bool,
+    backlog: VecDeque<u8>,
+}
+
+impl TerminalOutput {
+    pub(crate) fn buffer(&mut self, data: &[u8]) {
+        if data.len() >= MAX_BACKLOG_BYTES {
+            self.backlog.clear();
+            self.backlog.extend(&data[data.len() - MAX_BACKLOG_BYTES..]);
+            return;
+        }
+        let overflow = (self.backlog.len() + data.len()).saturating_sub(MAX_BACKLOG_BYTES);
+        self.backlog.drain(..overflow);
+        self.backlog.extend(data);
+    }
+
+    pub(crate) fn take_backlog(&mut self) -> Vec<u8> {
+        self.backlog.drain(..).collect()
+    }
+
+    pub(crate) fn clear(&mut self) -> usize {
+        let dropped = self.backlog.len();
+        self.backlog.clear();
+        dropped
+    }
+}
+
 pub(crate) struct TerminalSession {
+    pub(crate) command: String,
     pub(crate) master: Box<dyn MasterPty + Send>,
     pub(crate) writer: Mutex<Box<dyn Write + Send>>,
     pub(crate) child: Arc<Mutex<Box<dyn portable_pty::Child + Send + Sync>>>,
+    pub(crate) output: Arc<Mutex<TerminalOutput>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backlog_drops_oldest_bytes_past_limit() {
+        let mut output = TerminalOutput::default();
+        output.buffer(&vec![b'a'; MAX_BACKLOG_BYTES - 2]);
+        output.buffer(b"bcde");
+        let backlog = output.take_backlog();
+        assert_eq!(backlog.len(), MAX_BACKLOG_BYTES);
+        assert!(backlog.ends_with(b"bcde"));
+        assert!(output.take_backlog().is_empty());
+    }
+
+    #[test]
+    fn clear_reports_dropped_bytes() {
+        let mut output = TerminalOutput::default();
+        output.buffer(b"hello");
+        assert_eq!(output.clear(), 5);
+        assert_eq!(output.clear(), 0);
+    }
 }
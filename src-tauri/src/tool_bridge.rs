@@ -0,0 +1,456 @@
+//! OpenContext tools for agents, served by the app over MCP
+//!
+//! ACP sessions are given an `opencontext` MCP server in `session/new`. The
+//! agent starts it as `<this binary> mcp-bridge <port>`, a relay between its
+//! stdio and a loopback socket the running app serves, so every tool call
+//! runs in the app against `AppState`. Changes go through the event bus like
+//! edits made in the UI, and folder settings are honored: read-only folders
+//! refuse writes, and encrypted ones need the vault unlocked.
+
+use crate::app_events::EmitScoped;
+use crate::commands::search::run_search;
+use crate::commands::summarize::queue_enrichment;
+use crate::utils::{map_err, CmdResult, CommandError, ErrorCode};
+use crate::AppState;
+use opencontext_core::search::SearchOptions;
+use opencontext_core::VaultPath;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::Mutex;
+use tauri::Manager;
+
+const RELAY_COMMAND: &str = "mcp-bridge";
+/// Passed in the environment rather than argv, which other users can see
+const TOKEN_ENV: &str = "OPENCONTEXT_BRIDGE_TOKEN";
+const SERVER_NAME: &str = "opencontext";
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Loopback address and token of the running bridge
+#[derive(Clone)]
+struct Endpoint {
+    port: u16,
+    token: String,
+}
+
+/// The bridge server, started by the first ACP session that needs it
+#[derive(Default)]
+pub(crate) struct ToolBridge {
+    endpoint: Mutex<Option<Endpoint>>,
+}
+
+/// Sent as `agent-doc-changed` after a tool creates or saves a doc, so the
+/// UI can refresh the tree and open editors
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentDocChanged {
+    /// "created" or "saved"
+    action: &'static str,
+    rel_path: String,
+}
+
+impl ToolBridge {
+    /// Entry for the `mcpServers` list of `session/new`
+    pub(crate) fn server_entry(&self, app: &tauri::AppHandle) -> Result<Value, String> {
+        let endpoint = self.endpoint(app)?;
+        let exe = std::env::current_exe().map_err(map_err)?;
+        Ok(json!({
+            "name": SERVER_NAME,
+            "command": exe.to_string_lossy(),
+            "args": [RELAY_COMMAND, endpoint.port.to_string()],
+            "env": [{ "name": TOKEN_ENV, "value": endpoint.token }],
+        }))
+    }
+
+    fn endpoint(&self, app: &tauri::AppHandle) -> Result<Endpoint, String> {
+        let mut endpoint = self.endpoint.lock().map_err(map_err)?;
+        if let Some(endpoint) = endpoint.as_ref() {
+            return Ok(endpoint.clone());
+        }
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(map_err)?;
+        let started = Endpoint {
+            port: listener.local_addr().map_err(map_err)?.port(),
+            token: new_token(),
+        };
+        let app = app.clone();
+        let token = started.token.clone();
+        std::thread::Builder::new()
+            .name("tool-bridge".to_string())
+            .spawn(move || serve(app, listener, token))
+            .map_err(map_err)?;
+        log::info!("[ToolBridge] Listening on port {}", started.port);
+        *endpoint = Some(started.clone());
+        Ok(started)
+    }
+}
+
+/// 128 random bits, as hex, that guard the loopback port for the app's
+/// lifetime
+fn new_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn serve(app: tauri::AppHandle, listener: TcpListener, token: String) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("[ToolBridge] Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let app = app.clone();
+        let token = token.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = handle_connection(&app, stream, &token) {
+                log::warn!("[ToolBridge] Connection closed: {}", e);
+            }
+        });
+    }
+}
+
+/// Longest token line read before the token is checked, so a client that
+/// never sends a newline can't make the bridge buffer without bound
+const TOKEN_LINE_LIMIT: u64 = 64;
+
+/// One relay: a token line, then newline-delimited JSON-RPC
+fn handle_connection(
+    app: &tauri::AppHandle,
+    stream: TcpStream,
+    token: &str,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut first = String::new();
+    reader
+        .by_ref()
+        .take(TOKEN_LINE_LIMIT)
+        .read_line(&mut first)?;
+    if !tokens_match(first.trim_end(), token) {
+        log::warn!("[ToolBridge] Rejected a connection with a wrong token");
+        return Ok(());
+    }
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle_message(app, message),
+            Err(e) => Some(rpc_error(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        if let Some(response) = response {
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Compare in time that depends only on the lengths, not on where the
+/// tokens first differ
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn rpc_error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Response to a request; notifications and responses get none
+fn handle_message(app: &tauri::AppHandle, message: Value) -> Option<Value> {
+    let id = message.get("id").cloned()?;
+    let method = message.get("method").and_then(Value::as_str)?;
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    let result = match method {
+        "initialize" => json!({
+            "protocolVersion": params
+                .get("protocolVersion")
+                .and_then(Value::as_str)
+                .unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": SERVER_NAME, "version": env!("CARGO_PKG_VERSION") },
+        }),
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": tool_list() }),
+        "tools/call" => call_tool(app, params),
+        _ => {
+            return Some(rpc_error(
+                id,
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            ))
+        }
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn tool_list() -> Value {
+    json!([
+        {
+            "name": "create_doc",
+            "description": "Create an empty OpenContext doc in a folder. Fails in read-only folders.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "folderPath": { "type": "string", "description": "Folder relative to the contexts root" },
+                    "name": { "type": "string", "description": "Doc name; .md is added if missing" },
+                    "description": { "type": "string" },
+                },
+                "required": ["folderPath", "name"],
+            },
+        },
+        {
+            "name": "save_doc_content",
+            "description": "Replace the content of an existing OpenContext doc. Fails in read-only folders, and in encrypted ones while the vault is locked.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Doc path relative to the contexts root" },
+                    "content": { "type": "string" },
+                    "description": { "type": "string" },
+                },
+                "required": ["path", "content"],
+            },
+        },
+        {
+            "name": "semantic_search",
+            "description": "Search OpenContext docs by meaning and keywords.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 },
+                    "mode": { "type": "string", "enum": ["hybrid", "vector", "keyword"] },
+                    "folderPrefix": { "type": "string", "description": "Only search docs under this folder" },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "generate_manifest",
+            "description": "List the docs of an OpenContext folder with their descriptions.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "folderPath": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 },
+                },
+                "required": ["folderPath"],
+            },
+        },
+    ])
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateDocArgs {
+    folder_path: VaultPath,
+    name: String,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SaveDocArgs {
+    path: VaultPath,
+    content: String,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestArgs {
+    folder_path: VaultPath,
+    limit: Option<usize>,
+}
+
+/// A tool's result as MCP content. Tool failures are results the agent can
+/// read, not protocol errors.
+fn call_tool(app: &tauri::AppHandle, params: Value) -> Value {
+    let result = serde_json::from_value::<ToolCall>(params)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e.to_string()))
+        .and_then(|call| run_tool(app, &call.name, call.arguments));
+    let (text, is_error) = match result {
+        Ok(value) => (
+            serde_json::to_string_pretty(&value).unwrap_or_default(),
+            false,
+        ),
+        Err(e) => (e.message, true),
+    };
+    json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+}
+
+fn arguments<T: serde::de::DeserializeOwned>(arguments: Value) -> CmdResult<T> {
+    serde_json::from_value(arguments)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e.to_string()))
+}
+
+/// `name` with `.md` added when it has no extension
+fn doc_name(name: &str) -> String {
+    if std::path::Path::new(name).extension().is_some() {
+        name.to_string()
+    } else {
+        format!("{}.md", name)
+    }
+}
+
+fn run_tool(app: &tauri::AppHandle, name: &str, args: Value) -> CmdResult<Value> {
+    let state = app.state::<AppState>();
+    match name {
+        "create_doc" => {
+            let args: CreateDocArgs = arguments(args)?;
+            let ctx = state.ctx.write().map_err(map_err)?;
+            let folder = args.folder_path.as_str();
+            let name = doc_name(&args.name);
+            let doc_path = if folder.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", folder, name)
+            };
+            ctx.ensure_agent_writable(&doc_path)?;
+            let doc = ctx.create_doc(folder, &name, args.description.as_deref())?;
+            notify(app, "created", &doc.rel_path);
+            Ok(serde_json::to_value(&doc)?)
+        }
+        "save_doc_content" => {
+            let args: SaveDocArgs = arguments(args)?;
+            let ctx = state.ctx.write().map_err(map_err)?;
+            let path = args.path.as_str();
+            ctx.ensure_agent_writable(path)?;
+            let doc = ctx.save_doc_content(path, &args.content, args.description.as_deref())?;
+            queue_enrichment(app, &ctx, path, false);
+            notify(app, "saved", path);
+            Ok(serde_json::to_value(&doc)?)
+        }
+        "semantic_search" => {
+            let options: SearchOptions = arguments(args)?;
+            let results = tauri::async_runtime::block_on(run_search(&state, options))?;
+            Ok(serde_json::to_value(&results)?)
+        }
+        "generate_manifest" => {
+            let args: ManifestArgs = arguments(args)?;
+            let ctx = state.ctx.read().map_err(map_err)?;
+            let manifest = ctx.generate_manifest(args.folder_path.as_str(), args.limit)?;
+            Ok(serde_json::to_value(&manifest)?)
+        }
+        _ => Err(CommandError::localized(
+            ErrorCode::NotFound,
+            "error.unknown_tool",
+            &[("name", name)],
+        )),
+    }
+}
+
+fn notify(app: &tauri::AppHandle, action: &'static str, rel_path: &str) {
+    let _ = app.emit_scoped(
+        "agent-doc-changed",
+        AgentDocChanged {
+            action,
+            rel_path: rel_path.to_string(),
+        },
+    );
+}
+
+/// Run the stdio relay if argv asks for it. Returns the exit code, or
+/// `None` when the app should start as usual.
+pub(crate) fn run_relay_from_env() -> Option<i32> {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(RELAY_COMMAND) {
+        return None;
+    }
+    let result = args
+        .next()
+        .and_then(|port| port.parse::<u16>().ok())
+        .ok_or_else(|| format!("usage: {} <port>", RELAY_COMMAND))
+        .and_then(relay);
+    Some(match result {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            1
+        }
+    })
+}
+
+/// Pipe stdin to the bridge and the bridge to stdout until either closes
+fn relay(port: u16) -> Result<(), String> {
+    let token = std::env::var(TOKEN_ENV).map_err(|_| format!("{} is not set", TOKEN_ENV))?;
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+        .map_err(|e| format!("OpenContext is not reachable on port {}: {}", port, e))?;
+    writeln!(stream, "{}", token).map_err(map_err)?;
+
+    let mut upstream = stream.try_clone().map_err(map_err)?;
+    std::thread::spawn(move || {
+        let _ = std::io::copy(&mut std::io::stdin().lock(), &mut upstream);
+        let _ = upstream.shutdown(std::net::Shutdown::Write);
+    });
+    std::io::copy(&mut stream, &mut std::io::stdout().lock()).map_err(map_err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_list_names_every_tool() {
+        let tools = tool_list();
+        let names: Vec<_> = tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "create_doc",
+                "save_doc_content",
+                "semantic_search",
+                "generate_manifest"
+            ]
+        );
+    }
+
+    #[test]
+    fn doc_name_adds_md_without_an_extension() {
+        assert_eq!(doc_name("plan"), "plan.md");
+        assert_eq!(doc_name("plan.md"), "plan.md");
+        assert_eq!(doc_name("notes.txt"), "notes.txt");
+    }
+
+    #[test]
+    fn tokens_match_only_the_same_token() {
+        let token = new_token();
+        assert!(tokens_match(&token, &token));
+        assert!(!tokens_match(&token[..31], &token));
+        assert!(!tokens_match(&new_token(), &token));
+        assert!(!tokens_match("", &token));
+    }
+
+    #[test]
+    fn new_token_is_random_hex() {
+        let token = new_token();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, new_token());
+    }
+}
@@ -1,7 +1,685 @@
+use crate::i18n;
+use opencontext_core::search::{SearchConfig, SearchError};
+use opencontext_core::CoreError;
+use serde::Serialize;
 use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::SystemTime;
 
-pub type CmdResult<T> = Result<T, String>;
+pub type CmdResult<T> = Result<T, CommandError>;
 
+/// Stringify an error. `?` in a command turns the string into an
+/// `internal` `CommandError`; use `?` on core/search errors directly to keep
+/// their codes.
 pub fn map_err<E: Display>(e: E) -> String {
-    e.to_string()
+    redact(&e.to_string())
+}
+
+/// Error kinds the frontend can branch on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Internal,
+    InvalidInput,
+    InvalidName,
+    InvalidPath,
+    NotFound,
+    Conflict,
+    Unauthorized,
+    PermissionDenied,
+    Config,
+    Io,
+    Database,
+    Network,
+    Embedding,
+    Index,
+    IndexNotBuilt,
+    Cancelled,
+    /// The file is not text (an image, PDF, archive, ...)
+    UnsupportedFileType,
+    /// The doc is in an encrypted folder and the vault is locked
+    Locked,
+    /// The search index files are damaged; `reset_corrupt_index` recovers
+    CorruptIndex,
+}
+
+/// Error returned by every Tauri command
+///
+/// Serializes as `{ code, message, details? }`. `Display` yields the bare
+/// message for code that still treats errors as strings.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// Message from the backend catalog in the current locale. The key and
+    /// arguments go in `details` so the frontend can localize it instead.
+    pub fn localized(code: ErrorCode, key: &str, args: &[(&str, &str)]) -> Self {
+        let params: serde_json::Map<String, serde_json::Value> = args
+            .iter()
+            .map(|(name, value)| (name.to_string(), serde_json::json!(value)))
+            .collect();
+        Self::new(code, i18n::t(key, args))
+            .with_details(serde_json::json!({ "messageKey": key, "messageArgs": params }))
+    }
+
+    /// Set `details`. Object fields are merged into details already set,
+    /// such as a localized message's key.
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        match (self.details.as_mut(), details) {
+            (Some(serde_json::Value::Object(existing)), serde_json::Value::Object(fields)) => {
+                existing.extend(fields)
+            }
+            (_, details) => self.details = Some(details),
+        }
+        self
+    }
+
+    pub fn internal<E: Display>(e: E) -> Self {
+        Self::new(ErrorCode::Internal, e.to_string())
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        let code = match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            std::io::ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+            std::io::ErrorKind::AlreadyExists => ErrorCode::Conflict,
+            _ => ErrorCode::Io,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::internal(e)
+    }
+}
+
+impl From<reqwest::Error> for CommandError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => {
+                let code = if status.as_u16() == 401 || status.as_u16() == 403 {
+                    ErrorCode::Unauthorized
+                } else {
+                    ErrorCode::Network
+                };
+                // The message includes the request URL, query string and all.
+                Self::new(code, redact(&e.to_string()))
+                    .with_details(serde_json::json!({ "status": status.as_u16() }))
+            }
+            None => Self::new(ErrorCode::Network, redact(&e.to_string())),
+        }
+    }
+}
+
+impl From<CoreError> for CommandError {
+    fn from(e: CoreError) -> Self {
+        match e {
+            // Core reports validation and lookup failures as plain messages.
+            CoreError::Message(message) => {
+                let lower = message.to_lowercase();
+                let code = if lower.contains("not found") || lower.contains("does not exist") {
+                    ErrorCode::NotFound
+                } else if lower.contains("already exists") || lower.contains("is not empty") {
+                    ErrorCode::Conflict
+                } else if lower.starts_with("failed") || lower.starts_with("unable") {
+                    ErrorCode::Internal
+                } else {
+                    ErrorCode::InvalidInput
+                };
+                Self::new(code, message)
+            }
+            CoreError::InvalidName {
+                ref name,
+                ref reason,
+            } => {
+                let details = serde_json::json!({ "name": name, "reason": reason });
+                Self::new(ErrorCode::InvalidName, e.to_string()).with_details(details)
+            }
+            CoreError::InvalidPath {
+                ref path,
+                ref reason,
+            } => {
+                let details = serde_json::json!({ "path": path, "reason": reason });
+                Self::new(ErrorCode::InvalidPath, e.to_string()).with_details(details)
+            }
+            CoreError::NotText { ref path, kind } => {
+                let details = serde_json::json!({ "path": path, "kind": kind });
+                Self::new(ErrorCode::UnsupportedFileType, e.to_string()).with_details(details)
+            }
+            CoreError::Locked { ref path } => {
+                let details = serde_json::json!({ "path": path });
+                Self::new(ErrorCode::Locked, e.to_string()).with_details(details)
+            }
+            CoreError::ReadOnly { ref path } => Self::localized(
+                ErrorCode::PermissionDenied,
+                "error.read_only_folder",
+                &[("path", path)],
+            ),
+            CoreError::WrongPassphrase => Self::new(ErrorCode::Unauthorized, e.to_string()),
+            CoreError::Db(e) => Self::new(ErrorCode::Database, e.to_string()),
+            CoreError::Io(e) => e.into(),
+        }
+    }
+}
+
+impl From<SearchError> for CommandError {
+    fn from(e: SearchError) -> Self {
+        let message = e.to_string();
+        match e {
+            SearchError::Config(_) => Self::new(ErrorCode::Config, message),
+            SearchError::ApiKeyMissing => Self::new(ErrorCode::Unauthorized, message),
+            SearchError::IndexNotBuilt => Self::new(ErrorCode::IndexNotBuilt, message),
+            SearchError::CorruptIndex { ref path, .. } => {
+                let details = serde_json::json!({ "path": path });
+                Self::new(ErrorCode::CorruptIndex, message).with_details(details)
+            }
+            SearchError::Embedding(_) => Self::new(ErrorCode::Embedding, message),
+            SearchError::Index(_)
+            | SearchError::VectorStore(_)
+            | SearchError::Lance(_)
+            | SearchError::DimensionMismatch { .. }
+            | SearchError::IndexModelMismatch { .. } => Self::new(ErrorCode::Index, message),
+            SearchError::Http(e) => Self::from(e),
+            SearchError::Io(e) => Self::from(e),
+            SearchError::Json(_) | SearchError::Search(_) => {
+                Self::new(ErrorCode::Internal, message)
+            }
+        }
+    }
+}
+
+pub fn read_config_json() -> Option<serde_json::Value> {
+    let config_path = SearchConfig::json_config_path();
+    if !config_path.exists() {
+        return None;
+    }
+    let content = std::fs::read_to_string(&config_path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn get_config_value(key: &str) -> Option<String> {
+    read_config_json()?
+        .get(key)
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+/// Read a boolean flag from config.json, accepting both JSON booleans and
+/// string values such as "true"/"false" written by older UIs.
+pub fn get_config_bool(key: &str) -> Option<bool> {
+    let config = read_config_json()?;
+    match config.get(key)? {
+        serde_json::Value::Bool(value) => Some(*value),
+        serde_json::Value::String(text) => match text.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Some(true),
+            "false" | "0" | "no" | "off" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Read config.json for rewriting. Unlike the getters this fails on an
+/// invalid file, so saving a setting never wipes the user's other settings.
+pub fn read_config_for_update() -> CmdResult<serde_json::Map<String, serde_json::Value>> {
+    let config_path = SearchConfig::json_config_path();
+    if !config_path.exists() {
+        return Ok(serde_json::Map::new());
+    }
+    let content = std::fs::read_to_string(&config_path)?;
+    match serde_json::from_str(&content) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err(CommandError::localized(
+            ErrorCode::Config,
+            "error.config_not_object",
+            &[("path", &config_path.display().to_string())],
+        )),
+        Err(e) => Err(CommandError::localized(
+            ErrorCode::Config,
+            "error.config_invalid_json",
+            &[
+                ("path", &config_path.display().to_string()),
+                ("detail", &e.to_string()),
+            ],
+        )
+        .with_details(serde_json::json!({ "line": e.line(), "column": e.column() }))),
+    }
+}
+
+/// Insert or replace a single key in config.json, keeping all other keys.
+pub fn set_config_value(key: &str, value: serde_json::Value) -> CmdResult<()> {
+    let config_path = SearchConfig::json_config_path();
+    let mut config = read_config_for_update()?;
+    config.insert(key.to_string(), value);
+
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(&config)?;
+    std::fs::write(&config_path, content)?;
+    Ok(())
+}
+
+/// Shortest secret shown partially by `mask_secret`
+const MASK_MIN_CHARS: usize = 8;
+
+/// Trailing chars `mask_secret` shows; never more than half the secret
+/// given `MASK_MIN_CHARS`
+const MASK_SHOWN_CHARS: usize = 4;
+
+/// Token prefixes of well-known API keys (OpenAI, Anthropic, Google,
+/// GitHub, Slack, Hugging Face)
+const SECRET_PREFIXES: &[&str] = &[
+    "sk-", "sk_", "AIza", "ghp_", "gho_", "xoxb-", "xoxp-", "hf_",
+];
+
+/// Shortest token taken for a key by prefix alone
+const SECRET_PREFIX_MIN_CHARS: usize = 20;
+
+/// Field and parameter names whose values are secrets (`AI_API_KEY`,
+/// `access_token`, ...)
+const SECRET_NAME_SUFFIXES: &[&str] = &[
+    "api_key", "apikey", "api-key", "token", "secret", "password",
+];
+
+/// Environment variables holding secrets, on top of config.json's keys
+const SECRET_ENV_VARS: &[&str] = &[
+    "EMBEDDING_API_KEY",
+    "OPENAI_API_KEY",
+    "OPENAI_KEY",
+    "AI_API_KEY",
+];
+
+/// The one display format for secrets: `...wxyz`, or `****` when the
+/// secret is too short to show any of it.
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() < MASK_MIN_CHARS {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - MASK_SHOWN_CHARS..].iter().collect();
+    format!("...{}", tail)
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_NAME_SUFFIXES
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+/// Secrets of config.json and the environment, with the config.json mtime
+/// they were read at
+static CONFIGURED_SECRETS: Mutex<Option<(Option<SystemTime>, Vec<String>)>> = Mutex::new(None);
+
+/// Secrets currently configured in config.json or the environment. They are
+/// read again only once config.json has changed.
+fn configured_secrets() -> Vec<String> {
+    let modified = std::fs::metadata(SearchConfig::json_config_path())
+        .and_then(|meta| meta.modified())
+        .ok();
+    let mut cached = CONFIGURED_SECRETS.lock().unwrap_or_else(|e| e.into_inner());
+    match cached.as_ref() {
+        Some((read_at, secrets)) if *read_at == modified => secrets.clone(),
+        _ => {
+            let secrets = read_configured_secrets();
+            *cached = Some((modified, secrets.clone()));
+            secrets
+        }
+    }
+}
+
+fn read_configured_secrets() -> Vec<String> {
+    let mut secrets: Vec<String> = SECRET_ENV_VARS
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .collect();
+    if let Some(config) = read_config_json() {
+        collect_config_secrets(&config, false, &mut secrets);
+    }
+    secrets
+}
+
+/// Values of secret-named keys at any depth of config.json, such as an
+/// `EMBEDDING_PROFILES` entry's `api_key`, and every value of an extra
+/// headers map, where `Authorization` and custom headers carry keys.
+fn collect_config_secrets(value: &serde_json::Value, headers: bool, secrets: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                match value.as_str() {
+                    Some(secret) if headers || is_secret_name(key) => {
+                        secrets.push(secret.to_string())
+                    }
+                    _ => collect_config_secrets(
+                        value,
+                        headers || key.ends_with("EXTRA_HEADERS"),
+                        secrets,
+                    ),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_config_secrets(item, headers, secrets);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Mask API keys in text bound for logs, errors or exports: the configured
+/// secrets, tokens with a well-known key prefix, `Bearer` tokens, and values
+/// of secret-looking fields (`api_key=...`, `"token": "..."`).
+pub fn redact(text: &str) -> String {
+    redact_with(text, &configured_secrets())
+}
+
+fn redact_with(text: &str, secrets: &[String]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets {
+        // Too short to replace without mangling unrelated text.
+        if secret.chars().count() >= 6 && text.contains(secret.as_str()) {
+            text = text.replace(secret.as_str(), &mask_secret(secret));
+        }
+    }
+    redact_patterns(&text)
+}
+
+fn redact_patterns(text: &str) -> String {
+    let is_token_char =
+        |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '~' | '+' | '/' | '%');
+    let mut out = String::with_capacity(text.len());
+    let mut previous_token = "";
+    let mut separator = String::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let token_len = rest.find(|c: char| !is_token_char(c)).unwrap_or(rest.len());
+        if token_len == 0 {
+            let c = rest.chars().next().unwrap_or_default();
+            out.push(c);
+            separator.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let token = &rest[..token_len];
+        let assigned_with =
+            separator.trim_matches(|c: char| c.is_whitespace() || c == '"' || c == '\'');
+        // A bare `key` only counts as a query parameter (`?key=...`).
+        let is_secret = match assigned_with {
+            "=" => is_secret_name(previous_token) || previous_token.eq_ignore_ascii_case("key"),
+            ":" => is_secret_name(previous_token),
+            _ => false,
+        } || (previous_token.eq_ignore_ascii_case("bearer")
+            && !separator.is_empty()
+            && separator.trim().is_empty())
+            || (token.chars().count() >= SECRET_PREFIX_MIN_CHARS
+                && SECRET_PREFIXES
+                    .iter()
+                    .any(|prefix| token.starts_with(prefix)));
+        if is_secret {
+            out.push_str(&mask_secret(token));
+        } else {
+            out.push_str(token);
+        }
+        previous_token = token;
+        separator.clear();
+        rest = &rest[token_len..];
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencontext_core::DocKind;
+
+    #[test]
+    fn mask_secret_hides_short_keys_entirely() {
+        assert_eq!(mask_secret(""), "****");
+        assert_eq!(mask_secret("abc"), "****");
+        assert_eq!(mask_secret("abcdefg"), "****");
+        assert_eq!(mask_secret("abcdefgh"), "...efgh");
+        assert_eq!(mask_secret("sk-proj-1234567890"), "...7890");
+    }
+
+    #[test]
+    fn mask_secret_counts_chars_not_bytes() {
+        // Five to eight chars straddle the partial-display threshold.
+        assert_eq!(mask_secret("abcde"), "****");
+        assert_eq!(mask_secret("密钥密钥密钥密"), "****");
+        assert_eq!(mask_secret("sk-12345"), "...2345");
+        // Multi-byte chars at the cut point
+        assert_eq!(mask_secret("🔑🔑🔑-key-🔑🔑"), "...y-🔑🔑");
+        assert_eq!(mask_secret("密钥密钥密钥密钥"), "...密钥密钥");
+        // Pasted with an en dash and a zero-width space
+        assert_eq!(mask_secret("sk–abc\u{200b}defgh"), "...efgh");
+        assert_eq!(
+            mask_secret("a\u{200b}b\u{200b}c\u{200b}de"),
+            "...c\u{200b}de"
+        );
+    }
+
+    #[test]
+    fn redact_masks_known_patterns() {
+        let key = "sk-proj-abcdefghijklmnopqrstuvwxyz";
+        assert_eq!(
+            redact_with(&format!("Authorization: Bearer {}", key), &[]),
+            "Authorization: Bearer ...wxyz"
+        );
+        assert_eq!(
+            redact_with(
+                "error sending request for url (https://host/v1/models?api_key=secret12345&x=1)",
+                &[]
+            ),
+            "error sending request for url (https://host/v1/models?api_key=...2345&x=1)"
+        );
+        assert_eq!(
+            redact_with(r#"{"AI_API_KEY": "short"}"#, &[]),
+            r#"{"AI_API_KEY": "****"}"#
+        );
+        assert_eq!(redact_with(&format!("using {}", key), &[]), "using ...wxyz");
+        assert_eq!(
+            redact_with("unknown key: EMBEDDING_MODLE, token count 12", &[]),
+            "unknown key: EMBEDDING_MODLE, token count 12"
+        );
+        assert_eq!(
+            redact_with("https://host/models?key=AIzaSyExample", &[]),
+            "https://host/models?key=...mple"
+        );
+    }
+
+    #[test]
+    fn config_secrets_include_profile_keys_and_header_values() {
+        let config = serde_json::json!({
+            "EMBEDDING_MODEL": "text-embedding-3-small",
+            "AI_API_KEY": "top-level-key",
+            "EMBEDDING_PROFILES": {
+                "work": { "model": "m", "api_key": "profile-key" },
+                "home": { "EMBEDDING_API_KEY": "aliased-profile-key" },
+            },
+            "EMBEDDING_EXTRA_HEADERS": {
+                "Authorization": "Bearer header-key",
+                "X-Custom-Auth": "custom-header-key",
+            },
+        });
+        let mut secrets = Vec::new();
+        collect_config_secrets(&config, false, &mut secrets);
+        secrets.sort();
+        assert_eq!(
+            secrets,
+            [
+                "Bearer header-key",
+                "aliased-profile-key",
+                "custom-header-key",
+                "profile-key",
+                "top-level-key",
+            ]
+        );
+    }
+
+    #[test]
+    fn openai_key_env_var_is_a_secret() {
+        std::env::set_var("OPENAI_KEY", "legacy-openai-key-value");
+        let secrets = read_configured_secrets();
+        std::env::remove_var("OPENAI_KEY");
+        assert!(secrets.contains(&"legacy-openai-key-value".to_string()));
+    }
+
+    #[test]
+    fn redact_masks_configured_secrets() {
+        let secrets = vec!["custom-secret-value".to_string(), "abc".to_string()];
+        assert_eq!(
+            redact_with("failed with custom-secret-value (abc)", &secrets),
+            "failed with ...alue (abc)"
+        );
+    }
+
+    #[test]
+    fn core_errors_map_to_codes() {
+        let not_found: CommandError =
+            CoreError::Message("Document \"a.md\" not found.".to_string()).into();
+        assert_eq!(not_found.code, ErrorCode::NotFound);
+        assert_eq!(not_found.to_string(), "Document \"a.md\" not found.");
+
+        let conflict: CommandError =
+            CoreError::Message("Folder \"notes\" already exists.".to_string()).into();
+        assert_eq!(conflict.code, ErrorCode::Conflict);
+
+        let invalid: CommandError =
+            CoreError::Message("Document name is required.".to_string()).into();
+        assert_eq!(invalid.code, ErrorCode::InvalidInput);
+
+        let invalid_name: CommandError = CoreError::InvalidName {
+            name: "CON".to_string(),
+            reason: "name is reserved on Windows".to_string(),
+        }
+        .into();
+        assert_eq!(invalid_name.code, ErrorCode::InvalidName);
+        assert_eq!(
+            invalid_name.details.unwrap()["reason"],
+            "name is reserved on Windows"
+        );
+
+        let io: CommandError = CoreError::Io(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "denied",
+        ))
+        .into();
+        assert_eq!(io.code, ErrorCode::PermissionDenied);
+
+        let binary: CommandError = CoreError::NotText {
+            path: "a.png".to_string(),
+            kind: DocKind::Image,
+        }
+        .into();
+        assert_eq!(binary.code, ErrorCode::UnsupportedFileType);
+        assert_eq!(binary.details.unwrap()["kind"], "image");
+
+        let locked: CommandError = CoreError::Locked {
+            path: "private/a.md".to_string(),
+        }
+        .into();
+        assert_eq!(locked.code, ErrorCode::Locked);
+        assert_eq!(locked.details.unwrap()["path"], "private/a.md");
+
+        let read_only: CommandError = CoreError::ReadOnly {
+            path: "archive/a.md".to_string(),
+        }
+        .into();
+        assert_eq!(read_only.code, ErrorCode::PermissionDenied);
+        assert_eq!(
+            read_only.details.unwrap()["messageKey"],
+            "error.read_only_folder"
+        );
+    }
+
+    #[test]
+    fn search_errors_map_to_codes() {
+        let auth: CommandError = SearchError::ApiKeyMissing.into();
+        assert_eq!(auth.code, ErrorCode::Unauthorized);
+
+        let config: CommandError = SearchError::Config("bad".to_string()).into();
+        assert_eq!(config.code, ErrorCode::Config);
+        assert_eq!(config.message, "Configuration error: bad");
+
+        let missing: CommandError = SearchError::IndexNotBuilt.into();
+        assert_eq!(missing.code, ErrorCode::IndexNotBuilt);
+
+        let index: CommandError = SearchError::VectorStore("broken".to_string()).into();
+        assert_eq!(index.code, ErrorCode::Index);
+
+        let corrupt: CommandError = SearchError::CorruptIndex {
+            path: "/tmp/lancedb".into(),
+            reason: "bad manifest".to_string(),
+        }
+        .into();
+        assert_eq!(corrupt.code, ErrorCode::CorruptIndex);
+        assert_eq!(corrupt.details.unwrap()["path"], "/tmp/lancedb");
+    }
+
+    #[test]
+    fn strings_become_internal_errors_and_serialize_flat() {
+        let err: CommandError = "boom".into();
+        assert_eq!(err.code, ErrorCode::Internal);
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({ "code": "internal", "message": "boom" })
+        );
+
+        let detailed = CommandError::new(ErrorCode::Network, "offline")
+            .with_details(serde_json::json!({ "status": 502 }));
+        assert_eq!(
+            serde_json::to_value(&detailed).unwrap()["details"]["status"],
+            502
+        );
+
+        let localized = CommandError::localized(
+            ErrorCode::Network,
+            "error.no_content",
+            &[("provider", "OpenAI")],
+        )
+        .with_details(serde_json::json!({ "status": 502 }));
+        assert_eq!(localized.message, "OpenAI returned no content");
+        let details = serde_json::to_value(&localized).unwrap()["details"].clone();
+        assert_eq!(details["messageKey"], "error.no_content");
+        assert_eq!(details["status"], 502);
+    }
 }
@@ -527,7 +527,8 @@ async function createUiServer({ host = '127.0.0.1', port = 3222 }) {
   app.post('/api/index/build', async (req, res) => {
     try {
       const indexer = await getIndexer();
-      const result = await indexer.buildIndex({ force: req.body?.force });
+      const force = Boolean(req.body?.force) || req.body?.incremental === false;
+      const result = await indexer.buildIndex({ force });
       res.json(result);
     } catch (error) {
       res.status(500).json({ error: error.message });
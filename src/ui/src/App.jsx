@@ -433,6 +433,46 @@ export default function App() {
     });
   };
 
+  // macOS dock menu items (desktop only)
+  const dockMenuActionsRef = useRef(null);
+  dockMenuActionsRef.current = {
+    newDocument: () => {
+      navigate(ROUTES.HOME);
+      handleCreatePageAction();
+    },
+    quickCapture: () => navigate(ROUTES.IDEA),
+    openDoc: (doc) => {
+      if (!doc?.relPath) return;
+      navigate(ROUTES.HOME);
+      loadDoc({ rel_path: doc.relPath, name: doc.name, description: '', updated_at: new Date().toISOString() });
+    },
+  };
+  useEffect(() => {
+    const unlisteners = [];
+    const events = [
+      ['menu-new-document', () => dockMenuActionsRef.current.newDocument()],
+      ['menu-quick-capture', () => dockMenuActionsRef.current.quickCapture()],
+      ['menu-open-doc', (event) => dockMenuActionsRef.current.openDoc(event.payload)],
+    ];
+    let disposed = false;
+    (async () => {
+      for (const [name, handler] of events) {
+        try {
+          const unlisten = await api.listenAppEvent(name, handler);
+          if (disposed) unlisten();
+          else unlisteners.push(unlisten);
+        } catch {
+          // Not in Tauri environment, ignore
+          return;
+        }
+      }
+    })();
+    return () => {
+      disposed = true;
+      unlisteners.forEach((unlisten) => unlisten());
+    };
+  }, []);
+
   const handleCreateFolderAction = (folderPath = '') => {
     setDialog({
       isOpen: true,
@@ -29,11 +29,32 @@ const waitForTauriRuntime = async (timeoutMs = 1500) => {
 let tauriInvoke = null;
 let loadInvokePromise = null;
 
+/**
+ * Commands reject with `{ code, message, details }`. Rethrow as an Error so
+ * callers reading `err.message` or `String(err)` keep working, and expose
+ * `err.code` / `err.details` for branching.
+ */
+function withCommandErrors(invoke) {
+  return async (...args) => {
+    try {
+      return await invoke(...args);
+    } catch (e) {
+      if (e && typeof e === 'object' && !(e instanceof Error) && 'message' in e) {
+        const err = new Error(e.message);
+        err.code = e.code;
+        err.details = e.details;
+        throw err;
+      }
+      throw e;
+    }
+  };
+}
+
 async function loadInvoke() {
   if (typeof window !== 'undefined') {
     const directInvoke = window.__TAURI_INTERNALS__?.invoke;
     if (directInvoke) {
-      tauriInvoke = directInvoke;
+      tauriInvoke = withCommandErrors(directInvoke);
       return tauriInvoke;
     }
   }
@@ -41,7 +62,7 @@ async function loadInvoke() {
   if (!runtimeReady) return null;
   try {
     const tauri = await import('@tauri-apps/api/core');
-    tauriInvoke = tauri.invoke;
+    tauriInvoke = withCommandErrors(tauri.invoke);
     return tauriInvoke;
   } catch (e) {
     console.warn('Failed to load @tauri-apps/api, falling back to HTTP:', e);
@@ -69,6 +90,28 @@ async function fetchJSON(url, options) {
   return res.json();
 }
 
+let eventInstancePromise = null;
+
+/**
+ * Subscribe to an event of the desktop app. Events are named
+ * `<instance>:<event>` so that two running instances, e.g. a dev and a release
+ * build, don't receive each other's; the instance id comes from `get_env_info`.
+ */
+export async function listenAppEvent(name, handler) {
+  const { listen } = await import('@tauri-apps/api/event');
+  if (!eventInstancePromise) {
+    eventInstancePromise = getInvoke()
+      .then((invoke) => invoke('get_env_info'))
+      .then((info) => info.event_instance)
+      .catch((e) => {
+        eventInstancePromise = null;
+        throw e;
+      });
+  }
+  const instance = await eventInstancePromise;
+  return listen(`${instance}:${name}`, handler);
+}
+
 // ===== Folder API =====
 
 export async function listFolders(options = {}) {
@@ -200,14 +243,73 @@ export async function setDocDescription(docPath, description) {
   });
 }
 
-export async function getDocContent(path) {
+/**
+ * Doc content. `range` ({ startLine, endLine }, 1-based and inclusive)
+ * limits it to those lines, e.g. a search hit's `line_start`/`line_end`;
+ * the response then also has `totalLines`. Ranges are desktop only.
+ */
+export async function getDocContent(path, range = {}) {
   const invoke = await getInvoke();
   if (invoke) {
-    return invoke('get_doc_content', { options: { path } });
+    const { startLine, endLine } = range;
+    return invoke('get_doc_content', { options: { path, startLine, endLine } });
   }
   return fetchJSON(`${API_BASE}/api/docs/content?path=${encodeURIComponent(path)}`);
 }
 
+/**
+ * Tell the app a doc is open in the editor, so writes from elsewhere arrive
+ * via `listenDocChanged`. Pair with `closeDoc`. No-op outside the desktop app.
+ */
+export async function openDoc(path) {
+  const invoke = await getInvoke();
+  if (!invoke) return;
+  await invoke('open_doc', { options: { path } });
+}
+
+export async function closeDoc(path) {
+  const invoke = await getInvoke();
+  if (!invoke) return;
+  await invoke('close_doc', { options: { path } });
+}
+
+/**
+ * Open docs written outside the editor: `{ path, deleted, ranges, added,
+ * removed, truncated }`, counted from the content the editor last loaded or
+ * saved. `ranges` holds `{ oldStart, oldLines, newStart, newLines }` per change.
+ */
+export async function listenDocChanged(onChange) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('doc-changed', (event) => {
+    onChange?.(event.payload);
+  });
+}
+
+/**
+ * Line diff between two versions of a doc. Pass `oldContent`/`newContent`
+ * directly, or a `path` (new side) and an optional `snapshot` file (old
+ * side, defaults to `<path>.bak`). Desktop only.
+ */
+export async function diffDocContent({ oldContent, newContent, path, snapshot, contextLines, wordDiff = false } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Doc diff is only available in the desktop app');
+  return invoke('diff_doc_content', {
+    options: { oldContent, newContent, path, snapshot, contextLines, wordDiff },
+  });
+}
+
+/**
+ * Three-way merge of a doc changed both in the editor (`mine`) and elsewhere,
+ * e.g. by an agent (`theirs`), since `base`. Returns `{ merged, conflicts }`;
+ * `merged` carries conflict markers wherever `conflicts` is non-empty.
+ */
+export async function mergeDocContent({ base, mine, theirs }) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Doc merge is only available in the desktop app');
+  return invoke('merge_doc_content', { options: { base, mine, theirs } });
+}
+
 export async function getDocMeta(path) {
   if (!path) throw new Error('Missing doc path');
   const invoke = await getInvoke();
@@ -247,10 +349,14 @@ export async function searchDocs(query, limit = 50) {
   return fetchJSON(`${API_BASE}/api/docs/search?${params}`);
 }
 
-export async function saveDocContent(path, content, description) {
+/**
+ * `suggest` asks for description and tag suggestions after the save
+ * (desktop only); they arrive via `listenDocEnrichmentSuggestions`.
+ */
+export async function saveDocContent(path, content, description, { suggest = false } = {}) {
   const invoke = await getInvoke();
   if (invoke) {
-    return invoke('save_doc_content', { options: { path, content, description } });
+    return invoke('save_doc_content', { options: { path, content, description, suggest } });
   }
   return fetchJSON(`${API_BASE}/api/docs/save`, {
     method: 'POST',
@@ -259,14 +365,41 @@ export async function saveDocContent(path, content, description) {
   });
 }
 
+/**
+ * Render a doc to a self-contained HTML file for sharing: internal links
+ * become plain text and vault images are embedded. Written to `outputPath`
+ * (a file or folder) or the temp dir; returns `{ path, bytes, copied }`.
+ * Desktop only.
+ */
+export async function shareDocSnapshot(path, { outputPath, copyToClipboard = false } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Sharing is only available in the desktop app');
+  return invoke('share_doc_snapshot', { options: { path, outputPath, copyToClipboard } });
+}
+
 // ===== Index API =====
 
-export async function buildSearchIndex() {
+export async function buildSearchIndex({ force = false, incremental } = {}) {
   const invoke = await getInvoke();
   if (invoke) {
-    return invoke('build_search_index');
+    return invoke('build_search_index', { options: { force, incremental } });
   }
-  return fetchJSON(`${API_BASE}/api/index/build`, { method: 'POST' });
+  return fetchJSON(`${API_BASE}/api/index/build`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ force, incremental }),
+  });
+}
+
+/**
+ * Stop the running index build after its current embedding batch. The build
+ * then resolves with `cancelled: true` and keeps what it stored. Resolves to
+ * false if no build is running. Desktop only.
+ */
+export async function cancelSearchIndex() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Cancelling an index build is only available in the desktop app');
+  return invoke('cancel_search_index');
 }
 
 export async function getIndexStatus() {
@@ -285,6 +418,302 @@ export async function cleanSearchIndex() {
   return fetchJSON(`${API_BASE}/api/index/clean`, { method: 'POST' });
 }
 
+/**
+ * Chunks stored for a doc, with token counts and content hashes, and the
+ * outcome of the last attempt to index it (`status` is null if never tried).
+ */
+export async function inspectDocIndex(path) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Inspecting the index is only available in the desktop app');
+  return invoke('inspect_doc_index', { options: { path } });
+}
+
+/**
+ * For each doc path: `{ path, indexed, chunks, stale, indexedModifiedAt }`,
+ * where `stale` means the doc changed since it was indexed. Resolves to an
+ * empty list outside the desktop app.
+ */
+export async function getDocIndexStatus(paths) {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('get_doc_index_status', { options: { paths } });
+}
+
+/**
+ * Docs re-indexed in the background, as their index status changes.
+ * Payload: `{ path }`, `null` when docs may have been missed. Desktop only.
+ */
+export async function listenDocIndexed(onIndexed) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('doc-indexed', (event) => {
+    onIndexed?.(event.payload);
+  });
+}
+
+/**
+ * Re-index one doc without rebuilding the whole index. Resolves to its chunk
+ * count; a doc that no longer exists is dropped from the index and counts 0.
+ */
+export async function indexDoc(docPath) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Indexing a single doc is only available in the desktop app');
+  return invoke('index_doc', { options: { docPath } });
+}
+
+/**
+ * Embedding tokens used per day and month, each with an estimated cost in
+ * USD. Models without a known price are listed in `unpricedModels`; set
+ * their price per million tokens in `EMBEDDING_PRICES`. Desktop only.
+ */
+export async function getEmbeddingUsage() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Embedding usage is only available in the desktop app');
+  return invoke('embedding_usage');
+}
+
+/** Clear the embedding usage ledger. Desktop only. */
+export async function resetUsageLedger() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Embedding usage is only available in the desktop app');
+  return invoke('reset_usage_ledger');
+}
+
+/**
+ * Chat model prices in USD per million tokens, as `{ defaults, custom }`
+ * maps from model pattern (`*` matches anything) to `{ input, output }`.
+ * Desktop only.
+ */
+export async function getPricing() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Pricing is only available in the desktop app');
+  return invoke('pricing_get');
+}
+
+/** Replace the custom chat model prices. Desktop only. */
+export async function savePricing(pricing) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Pricing is only available in the desktop app');
+  return invoke('pricing_save', { options: { pricing } });
+}
+
+/**
+ * Tokens used by chat and agent turns per day and month, in the same shape
+ * as `getEmbeddingUsage`. Desktop only.
+ */
+export async function getChatUsage() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Chat usage is only available in the desktop app');
+  return invoke('chat_usage');
+}
+
+/**
+ * Move a damaged index aside and start an empty one, when
+ * `getIndexStatus()` reports `corrupt`. Desktop only.
+ */
+export async function resetCorruptIndex() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Resetting the index is only available in the desktop app');
+  return invoke('reset_corrupt_index');
+}
+
+/**
+ * Re-embed all docs with a new model in the background, then swap indexes.
+ * With `confirm: true` the swap waits for applyEmbeddingMigration().
+ * @param {{model: string, apiBase?: string, apiKey?: string, confirm?: boolean}} options
+ */
+export async function migrateEmbeddings(options) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Embedding migration requires the desktop app');
+  return invoke('migrate_embeddings', { options });
+}
+
+export async function applyEmbeddingMigration() {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return invoke('apply_embedding_migration');
+}
+
+export async function discardEmbeddingMigration() {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return invoke('discard_embedding_migration');
+}
+
+/**
+ * Locale for messages produced by the backend (agent statuses, hints).
+ * Resolves to the locale actually used; unsupported ones fall back to 'en'.
+ */
+export async function setBackendLocale(locale) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return invoke('set_backend_locale', { options: { locale } });
+}
+
+// ===== Background Tasks =====
+
+/**
+ * Registered long-running tasks
+ * @returns {Promise<Array<{id: number, kind: string, status: string, current: number, total: number, percent: number, message?: string, startedAt: number, error?: string}>>}
+ */
+export async function listTasks() {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('task_list');
+}
+
+export async function cancelTask(id) {
+  const invoke = await getInvoke();
+  if (!invoke) return false;
+  return invoke('task_cancel', { options: { id } });
+}
+
+export async function listenTaskProgress(onProgress) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('task-progress', (event) => {
+    onProgress?.(event.payload);
+  });
+}
+
+/**
+ * Generate a doc's description with the configured AI provider and save it.
+ * With `dryRun` the description is returned but not saved. Desktop only.
+ */
+export async function summarizeDoc(path, { dryRun = false } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Summarization is only available in the desktop app');
+  return invoke('summarize_doc', { options: { path, dryRun } });
+}
+
+/**
+ * Summarize every doc in a folder. Docs whose description is newer than
+ * their content are skipped unless `force` is set.
+ */
+export async function summarizeFolder(folderPath, { recursive = true, concurrency, dryRun = false, force = false } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Summarization is only available in the desktop app');
+  return invoke('summarize_folder', {
+    options: { folderPath, recursive, concurrency, dryRun, force },
+  });
+}
+
+export async function listenSummarizeProgress(onProgress) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('summarize-progress', (event) => {
+    onProgress?.(event.payload);
+  });
+}
+
+/**
+ * Write a digest of the docs modified in the last `days` (default: the
+ * `DIGEST_DAYS` setting) into `digests/`. The AI provider summarizes them
+ * from diffs and excerpts capped at `tokenBudget`; without it the digest
+ * only lists the changes. Cancellable as a task. Desktop only.
+ */
+export async function generateDigest({ days, tokenBudget } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Digests are only available in the desktop app');
+  return invoke('generate_digest', { options: { days, tokenBudget } });
+}
+
+/**
+ * Description and tag suggestions for saved docs: `{ path, description, tags }`.
+ * Nothing is written until `acceptDocEnrichment` is called.
+ */
+export async function listenDocEnrichmentSuggestions(onSuggestion) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('doc-enrichment-suggestion', (event) => {
+    onSuggestion?.(event.payload);
+  });
+}
+
+/**
+ * Apply (possibly edited) suggestions. Tags are added to the doc's front
+ * matter; reload the editor when `contentChanged` is true.
+ */
+export async function acceptDocEnrichment(path, { description, tags = [] } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Doc enrichment is only available in the desktop app');
+  return invoke('accept_doc_enrichment', { options: { path, description, tags } });
+}
+
+/**
+ * Prompt templates. Bodies use `{{variable}}` placeholders; variables are
+ * `{ text }`, `{ doc: path }` or `{ manifest: folderPath }`. Desktop only.
+ */
+export async function listPromptTemplates() {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('prompt_templates_list');
+}
+
+export async function savePromptTemplate({ id, name, body, target = 'chat' }) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Prompt templates are only available in the desktop app');
+  return invoke('prompt_templates_save', { options: { id, name, body, target } });
+}
+
+export async function deletePromptTemplate(id) {
+  const invoke = await getInvoke();
+  if (!invoke) return false;
+  return invoke('prompt_templates_delete', { options: { id } });
+}
+
+export async function renderPromptTemplate(templateId, variables = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Prompt templates are only available in the desktop app');
+  return invoke('render_prompt_template', { options: { templateId, variables } });
+}
+
+export async function getServicesStatus() {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('services_status');
+}
+
+export async function startService(service) {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('service_start', { options: { service } });
+}
+
+export async function stopService(service) {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('service_stop', { options: { service } });
+}
+
+export async function listenServicesChanged(onChange) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('services-changed', (event) => {
+    onChange?.(event.payload);
+  });
+}
+
+/**
+ * Docs created or saved by an agent through the OpenContext tools.
+ * Payload: `{ action: 'created' | 'saved', relPath }`. Desktop only.
+ */
+export async function listenAgentDocChanged(onChange) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('agent-doc-changed', (event) => {
+    onChange?.(event.payload);
+  });
+}
+
+export async function listenEmbeddingMigration(onStatus) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('embedding-migration', (event) => {
+    onStatus?.(event.payload);
+  });
+}
+
 // ===== Utility API =====
 
 export async function generateManifest(folderPath, limit) {
@@ -297,6 +726,118 @@ export async function generateManifest(folderPath, limit) {
   return fetchJSON(`${API_BASE}/api/manifest?${params}`);
 }
 
+/**
+ * Settings of a folder ("" for the root): `{ settings, effective, docs }`,
+ * where `effective` includes keys inherited from parent folders. Desktop only.
+ */
+export async function getFolderSettings(folderPath = '') {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Folder settings are only available in the desktop app');
+  return invoke('folder_settings_get', { options: { folderPath } });
+}
+
+/**
+ * Replace a folder's settings, or a single doc's override when `doc` (a file
+ * name in the folder) is given. Unset keys are inherited. Desktop only.
+ */
+export async function setFolderSettings(folderPath, settings, doc) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Folder settings are only available in the desktop app');
+  return invoke('folder_settings_set', { options: { folderPath, settings, doc } });
+}
+
+/** `{ hasPassphrase, unlocked }` for encrypted folders. Desktop only. */
+export async function getVaultStatus() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault encryption is only available in the desktop app');
+  return invoke('vault_status');
+}
+
+/**
+ * Unlock encrypted folders. Without a passphrase, the one remembered in the
+ * OS keyring is used. The first unlock sets the passphrase; it cannot be
+ * recovered if lost.
+ */
+export async function unlockVault({ passphrase, remember = false } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault encryption is only available in the desktop app');
+  return invoke('unlock_vault', { options: { passphrase, remember } });
+}
+
+export async function lockVault({ forget = false } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault encryption is only available in the desktop app');
+  return invoke('lock_vault', { options: { forget } });
+}
+
+/** Encrypt or decrypt a folder's docs; needs the vault unlocked. */
+export async function setFolderEncrypted(folderPath, encrypted) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault encryption is only available in the desktop app');
+  return invoke('set_folder_encrypted', { options: { folderPath, encrypted } });
+}
+
+/**
+ * Vault-wide stats: weekly series, most edited docs and, optionally, word
+ * counts (`wordCounts`), tags (`tags`) and search index coverage
+ * (`indexCoverage`, default true). Desktop only.
+ */
+export async function getVaultStatistics({ weeks, top, wordCounts = false, tags = false, indexCoverage } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault statistics are only available in the desktop app');
+  return invoke('vault_statistics', { options: { weeks, top, wordCounts, tags, indexCoverage } });
+}
+
+/**
+ * Snapshot the whole vault as a background task. Resolves to
+ * `{ id, createdAt, label, fileCount, totalBytes }`. Desktop only.
+ */
+export async function createVaultSnapshot(label) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault snapshots are only available in the desktop app');
+  return invoke('vault_snapshot_create', { options: { label } });
+}
+
+/** Vault snapshots, newest first. Desktop only. */
+export async function listVaultSnapshots() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault snapshots are only available in the desktop app');
+  return invoke('vault_snapshot_list');
+}
+
+/**
+ * Roll the vault back to snapshot `id`; fails unless `confirm` is true.
+ * Resolves to `{ snapshot, preRestore, restored, removed }`.
+ */
+export async function restoreVaultSnapshot(id, { confirm = false } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Vault snapshots are only available in the desktop app');
+  return invoke('vault_snapshot_restore', { options: { id, confirm } });
+}
+
+/**
+ * First-run checklist: `{ dismissed, complete, steps: [{ id, done, detail? }] }`
+ * with steps `contexts`, `embedding`, `index`, `ai` and `agent`. Desktop only.
+ */
+export async function getOnboardingStatus() {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return invoke('onboarding_status');
+}
+
+export async function dismissOnboarding() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Onboarding is only available in the desktop app');
+  return invoke('onboarding_dismiss');
+}
+
+/** Create the "Getting Started" example folder; resolves to the new doc paths. */
+export async function createSampleContent() {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Sample content is only available in the desktop app');
+  return invoke('create_sample_content');
+}
+
 export async function getEnvInfo() {
   const invoke = await getInvoke();
   if (invoke) {
@@ -352,6 +893,49 @@ export async function saveConfig(options) {
   });
 }
 
+/**
+ * Validate config.json / config.toml
+ * @returns {Promise<Array<{severity: 'error'|'warning', file: string, key: string|null, message: string, line: number|null, column: number|null}>>}
+ */
+export async function validateConfig() {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('validate_config');
+}
+
+/**
+ * Every known setting with its resolved value and source (secrets masked)
+ * @returns {Promise<Array<{key: string, value: any, source: 'env'|'file'|'default', path?: string, secret: boolean}>>}
+ */
+export async function getEffectiveConfig() {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('get_effective_config');
+}
+
+/**
+ * Listen for config problems found at startup (same shape as validateConfig)
+ */
+export async function listenConfigWarnings(onWarnings) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('config-warnings', (event) => {
+    onWarnings?.(event.payload);
+  });
+}
+
+/**
+ * Listen for config reloads after settings are saved
+ * @param {(payload: {changed: boolean, issues: Array}) => void} onReload
+ */
+export async function listenConfigReloaded(onReload) {
+  const invoke = await getInvoke();
+  if (!invoke) return null;
+  return listenAppEvent('config-reloaded', (event) => {
+    onReload?.(event.payload);
+  });
+}
+
 export async function loadAgentSessions() {
   if (!hasTauriRuntime()) return null;
   const invoke = await getInvoke();
@@ -378,6 +962,49 @@ export async function saveAgentSessions(payload) {
   return null;
 }
 
+/**
+ * Transcript of an agent session as recorded by the backend, from `fromSeq`
+ * on. Concatenate the `content` of a request's entries for its full reply.
+ */
+export async function getAgentTranscript(sessionId, fromSeq = 0) {
+  const invoke = await getInvoke();
+  if (!invoke) return [];
+  return invoke('agent_transcript_get', { options: { sessionId, fromSeq } });
+}
+
+export async function deleteAgentTranscript(sessionId) {
+  const invoke = await getInvoke();
+  if (!invoke) return false;
+  return invoke('agent_transcript_delete', { options: { sessionId } });
+}
+
+/** Save a session's recorded transcript as a new doc. Desktop only. */
+export async function exportAgentTranscript(sessionId, folderPath, name, title) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Exporting agent sessions is only available in the desktop app');
+  return invoke('agent_transcript_export', { options: { sessionId, folderPath, name, title } });
+}
+
+/**
+ * Files an agent produced in its session's scratch directory:
+ * `{ path, files: [{ path, bytes, modifiedAt }], truncated }`. Desktop only.
+ */
+export async function listAgentScratch(sessionId) {
+  const invoke = await getInvoke();
+  if (!invoke) return { path: null, files: [], truncated: false };
+  return invoke('agent_scratch_list', { options: { sessionId } });
+}
+
+/**
+ * Copy scratch files into a vault folder as new docs. Resolves to one
+ * `{ path, docPath?, error? }` per file. Desktop only.
+ */
+export async function importAgentScratch(sessionId, paths, folderPath) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Importing agent files is only available in the desktop app');
+  return invoke('agent_scratch_import', { options: { sessionId, paths, folderPath } });
+}
+
 export async function preflightAgentSession(options) {
   const invoke = await getInvoke();
   if (!invoke) return null;
@@ -402,12 +1029,28 @@ export async function execOcCommand(options) {
   return invoke('oc_exec', { options });
 }
 
+/**
+ * Counts a stream's events. Each carries a `seq` from 0 and the `done`
+ * event's is the last, so `missed(seq)` on done is how many never arrived;
+ * agent replies can then be rebuilt with `getAgentTranscript`.
+ */
+function createSeqTracker() {
+  let received = 0;
+  return {
+    see() {
+      received += 1;
+    },
+    missed(lastSeq) {
+      return typeof lastSeq === 'number' ? Math.max(0, lastSeq + 1 - received) : 0;
+    },
+  };
+}
+
 export async function listenAgentStream(requestId, onEvent) {
   const invoke = await getInvoke();
   if (!invoke) return null;
-  const { listen } = await import('@tauri-apps/api/event');
   const eventName = `agent-stream-${requestId}`;
-  return listen(eventName, (event) => {
+  return listenAppEvent(eventName, (event) => {
     onEvent?.(event.payload);
   });
 }
@@ -433,7 +1076,7 @@ export async function getAIConfig() {
 /**
  * Save AI configuration
  * @param {Object} options - AI config options
- * @param {string} options.provider - AI provider (openai | ollama)
+ * @param {string} options.provider - AI provider (openai | anthropic | ollama)
  * @param {string} options.apiKey - AI API key
  * @param {string} options.apiBase - AI API base URL
  * @param {string} options.model - AI model name
@@ -455,11 +1098,30 @@ export async function saveAIConfig(options) {
   });
 }
 
+/**
+ * AI chat without streaming: resolves to the whole reply text. Rejects with
+ * the provider's error message when the request fails. Desktop only.
+ * @param {Array<{role: string, content: string}>} messages
+ * @param {{model?: string}} [options]
+ * @returns {Promise<string>}
+ */
+export async function aiChatOnce(messages, { model } = {}) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('AI chat without streaming is only available in the desktop app');
+  return invoke('ai_chat_once', { options: { messages, model } });
+}
+
 /**
  * Stream AI chat completion
  * @param {Array<{role: string, content: string}>} messages - Chat messages
  * @param {function(string): void} onToken - Callback for each token
  * @param {function(Error): void} onError - Error callback
+ * @param {{model?: string, requestId?: string, useContext?: boolean, citeSources?: boolean, docPath?: string, onCitations?: function(Array<{index: number, docId: string|null, title: string, path: string}>): void}} [options]
+ *   `requestId` lets the stream be stopped with `abortAIChat` (desktop only)
+ *   `onUsage` receives `{ inputTokens, outputTokens, estimatedCost }` when the provider reports usage;
+ *   `estimatedCost` is null for models without a known price
+ *   `useContext` injects vault search hits (desktop only); `onCitations` receives the docs they came from
+ *   `docPath` uses the system prompt from that doc's folder settings (desktop only)
  * @returns {Promise<void>}
  */
 export async function streamAIChat(messages, onToken, onError, options = {}) {
@@ -469,10 +1131,8 @@ export async function streamAIChat(messages, onToken, onError, options = {}) {
   // Use Tauri events for streaming if available
   if (invoke) {
     try {
-      const { listen } = await import('@tauri-apps/api/event');
-      
       // 为每个请求生成唯一 ID，避免并行请求冲突
-      const requestId = `ai-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
+      const requestId = options.requestId || `ai-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
       const eventName = `ai-stream-${requestId}`;
       
       return new Promise((resolve, reject) => {
@@ -480,8 +1140,10 @@ export async function streamAIChat(messages, onToken, onError, options = {}) {
         let resolved = false;
         
         // Set up event listener for streaming
-        listen(eventName, (event) => {
-          const { content, done, error } = event.payload;
+        const seqs = createSeqTracker();
+        listenAppEvent(eventName, (event) => {
+          const { content, done, error, citations, usage, seq } = event.payload;
+          seqs.see();
           
           if (error) {
             if (!resolved) {
@@ -500,8 +1162,18 @@ export async function streamAIChat(messages, onToken, onError, options = {}) {
           if (content) {
             onToken?.(content);
           }
+
+          if (citations) {
+            options.onCitations?.(citations);
+          }
+
+          if (usage) {
+            options.onUsage?.(usage);
+          }
           
           if (done) {
+            const missed = seqs.missed(seq);
+            if (missed) options.onMissedEvents?.(missed);
             if (!resolved) {
               resolved = true;
               if (unlisten) unlisten();
@@ -516,6 +1188,17 @@ export async function streamAIChat(messages, onToken, onError, options = {}) {
           if (modelOverride) {
             requestOptions.model = modelOverride;
           }
+          if (options.useContext) {
+            requestOptions.useContext = true;
+            requestOptions.citeSources = Boolean(options.citeSources);
+          }
+          if (options.templateId) {
+            requestOptions.templateId = options.templateId;
+            requestOptions.variables = options.variables;
+          }
+          if (options.docPath) {
+            requestOptions.docPath = options.docPath;
+          }
           invoke('ai_chat', { options: requestOptions }).catch((e) => {
             if (!resolved) {
               resolved = true;
@@ -587,6 +1270,18 @@ export async function streamAIChat(messages, onToken, onError, options = {}) {
   }
 }
 
+/**
+ * Stop a desktop AI chat stream started with `requestId`. The stream still
+ * ends with its `done` event, keeping the reply so far.
+ * @param {string} requestId
+ * @returns {Promise<boolean>} false if no such stream is running
+ */
+export async function abortAIChat(requestId) {
+  const invoke = await getInvoke();
+  if (!invoke) return false;
+  return invoke('ai_chat_abort', { options: { requestId } });
+}
+
 /**
  * Stream Codex CLI execution (desktop only)
  * @param {Array<{role: string, content: string}>} messages - Chat messages
@@ -597,10 +1292,14 @@ export async function streamAIChat(messages, onToken, onError, options = {}) {
  * @param {string} options.model - Optional model override
  * @param {string} options.requestId - Optional request id
  * @param {string} options.cwd - Optional working directory
+ * @param {boolean} options.useScratchCwd - Run in the session's scratch directory instead of `cwd`
+ * @param {string} options.templateId - Optional prompt template appended as the last user message
+ * @param {Object} options.variables - Template variables, e.g. `{ doc: { doc: 'notes/a.md' } }`
  * @param {function(string): void} options.onStatus - Callback for status updates
  * @param {function(string): void} options.onReasoning - Callback for reasoning deltas
  * @param {function(Object): void} options.onPermission - Callback for permission requests
  * @param {function(Object): void} options.onTool - Callback for tool events
+ * @param {function(number): void} options.onMissedEvents - Called on done with the number of events that never arrived
  * @returns {Promise<void>}
  */
 export async function streamCodexExec(messages, onToken, onError, options = {}) {
@@ -611,7 +1310,6 @@ export async function streamCodexExec(messages, onToken, onError, options = {})
     throw error;
   }
 
-  const { listen } = await import('@tauri-apps/api/event');
   const requestId = options.requestId || `codex-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
   const sessionId = options.sessionId;
   if (!sessionId) {
@@ -626,18 +1324,24 @@ export async function streamCodexExec(messages, onToken, onError, options = {})
     sessionId,
     model: options.model,
     cwd: options.cwd,
+    useScratchCwd: options.useScratchCwd,
+    templateId: options.templateId,
+    variables: options.variables,
   };
 
   return new Promise((resolve, reject) => {
     let unlisten = null;
     let resolved = false;
 
-    listen(eventName, (event) => {
-      const { content, done, error, status, reasoning, permission, tool } = event.payload;
+    const seqs = createSeqTracker();
+    listenAppEvent(eventName, (event) => {
+      const { content, done, error, status, reasoning, permission, tool, usage, seq } = event.payload;
+      seqs.see();
       if (status) options.onStatus?.(status);
       if (reasoning) options.onReasoning?.(reasoning);
       if (permission) options.onPermission?.(permission);
       if (tool) options.onTool?.(tool);
+      if (usage) options.onUsage?.(usage);
       if (error) {
         if (!resolved) {
           resolved = true;
@@ -657,6 +1361,8 @@ export async function streamCodexExec(messages, onToken, onError, options = {})
       }
 
       if (done) {
+        const missed = seqs.missed(seq);
+        if (missed) options.onMissedEvents?.(missed);
         if (!resolved) {
           resolved = true;
           if (unlisten) unlisten();
@@ -706,6 +1412,7 @@ export async function stopCodexExec(sessionId) {
  * @param {function(string): void} options.onReasoning - Callback for reasoning deltas
  * @param {function(Object): void} options.onPermission - Callback for permission requests
  * @param {function(Object): void} options.onTool - Callback for tool events
+ * @param {function(number): void} options.onMissedEvents - Called on done with the number of events that never arrived
  * @returns {Promise<void>}
  */
 export async function streamClaudeExec(messages, onToken, onError, options = {}) {
@@ -716,7 +1423,6 @@ export async function streamClaudeExec(messages, onToken, onError, options = {})
     throw error;
   }
 
-  const { listen } = await import('@tauri-apps/api/event');
   const requestId = options.requestId || `claude-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
   const sessionId = options.sessionId;
   if (!sessionId) {
@@ -731,14 +1437,19 @@ export async function streamClaudeExec(messages, onToken, onError, options = {})
     sessionId,
     model: options.model,
     cwd: options.cwd,
+    useScratchCwd: options.useScratchCwd,
+    templateId: options.templateId,
+    variables: options.variables,
   };
 
   return new Promise((resolve, reject) => {
     let unlisten = null;
     let resolved = false;
 
-    listen(eventName, (event) => {
-      const { content, done, error, status, reasoning, permission, tool } = event.payload;
+    const seqs = createSeqTracker();
+    listenAppEvent(eventName, (event) => {
+      const { content, done, error, status, reasoning, permission, tool, seq } = event.payload;
+      seqs.see();
       if (status) options.onStatus?.(status);
       if (reasoning) options.onReasoning?.(reasoning);
       if (permission) options.onPermission?.(permission);
@@ -762,6 +1473,8 @@ export async function streamClaudeExec(messages, onToken, onError, options = {})
       }
 
       if (done) {
+        const missed = seqs.missed(seq);
+        if (missed) options.onMissedEvents?.(missed);
         if (!resolved) {
           resolved = true;
           if (unlisten) unlisten();
@@ -811,6 +1524,7 @@ export async function stopClaudeExec(sessionId) {
  * @param {function(string): void} options.onReasoning - Callback for reasoning deltas
  * @param {function(Object): void} options.onPermission - Callback for permission requests
  * @param {function(Object): void} options.onTool - Callback for tool events
+ * @param {function(number): void} options.onMissedEvents - Called on done with the number of events that never arrived
  * @returns {Promise<void>}
  */
 export async function streamOpenCodeRun(messages, onToken, onError, options = {}) {
@@ -821,7 +1535,6 @@ export async function streamOpenCodeRun(messages, onToken, onError, options = {}
     throw error;
   }
 
-  const { listen } = await import('@tauri-apps/api/event');
   const requestId = options.requestId || `opencode-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
   const sessionId = options.sessionId;
   if (!sessionId) {
@@ -836,14 +1549,19 @@ export async function streamOpenCodeRun(messages, onToken, onError, options = {}
     sessionId,
     model: options.model,
     cwd: options.cwd,
+    useScratchCwd: options.useScratchCwd,
+    templateId: options.templateId,
+    variables: options.variables,
   };
 
   return new Promise((resolve, reject) => {
     let unlisten = null;
     let resolved = false;
 
-    listen(eventName, (event) => {
-      const { content, done, error, status, reasoning, permission, tool } = event.payload;
+    const seqs = createSeqTracker();
+    listenAppEvent(eventName, (event) => {
+      const { content, done, error, status, reasoning, permission, tool, seq } = event.payload;
+      seqs.see();
       if (status) options.onStatus?.(status);
       if (reasoning) options.onReasoning?.(reasoning);
       if (permission) options.onPermission?.(permission);
@@ -867,6 +1585,8 @@ export async function streamOpenCodeRun(messages, onToken, onError, options = {}
       }
 
       if (done) {
+        const missed = seqs.missed(seq);
+        if (missed) options.onMissedEvents?.(missed);
         if (!resolved) {
           resolved = true;
           if (unlisten) unlisten();
@@ -918,6 +1638,20 @@ export async function respondCodexPermission(options) {
   await invoke('codex_permission_response', { options });
 }
 
+/**
+ * Dry-run a pending Codex patch against the current files without writing.
+ * Offered when a permission event has `previewAvailable`.
+ * @param {Object} options
+ * @param {string} options.sessionId
+ * @param {string} options.callId
+ * @returns {Promise<{clean: boolean, files: Array<{path: string, kind: string, clean: boolean, snippets: Array<{line: number, before: string, after: string, applies: boolean}>}>}>}
+ */
+export async function previewAgentPatch({ sessionId, callId }) {
+  const invoke = await getInvoke();
+  if (!invoke) throw new Error('Patch preview is only available in the desktop app');
+  return invoke('agent_patch_preview', { options: { sessionId, callId } });
+}
+
 /**
  * Respond to an ACP permission request (Claude/OpenCode)
  * @param {Object} options
@@ -941,16 +1675,21 @@ export async function respondAcpPermission(options) {
  * @param {number} options.limit - Max results (default 10)
  * @param {string} options.mode - Search mode: 'hybrid' | 'vector' | 'keyword' (default 'hybrid')
  * @param {string} options.aggregateBy - Aggregation: 'content' | 'doc' | 'folder' (default 'doc')
- * @returns {Promise<{query: string, results: Array, count: number, error?: string, indexMissing?: boolean}>}
+ * @param {boolean} options.rerank - Re-order top results with the configured chat model (default false)
+ * @param {boolean} options.expandQuery - Also search AI-generated paraphrases of the query (default false)
+ * @param {string} options.cursor - `next_cursor` of the previous page, to fetch the page after it (desktop only)
+ * @param {boolean} options.groupByDoc - Also return hits nested by doc in `groups`, limit counting docs (desktop only)
+ * @param {number} options.maxChunksPerDoc - Most hits per doc when grouping (default 3)
+ * @returns {Promise<{query: string, results: Array, count: number, error?: string, indexMissing?: boolean, rerank_warning?: string, next_cursor?: string, total_estimate?: number}>}
  */
 export async function semanticSearch(query, options = {}) {
-  const { limit = 10, mode = 'hybrid', aggregateBy = 'doc', docType } = options;
+  const { limit = 10, mode = 'hybrid', aggregateBy = 'doc', docType, rerank = false, expandQuery = false, cursor, groupByDoc, maxChunksPerDoc } = options;
   
   const invoke = await getInvoke();
   if (invoke) {
     try {
       return await invoke('semantic_search', { 
-        options: { query, limit, mode, aggregateBy, docType } 
+        options: { query, limit, mode, aggregateBy, docType, rerank, expandQuery, cursor, groupByDoc, maxChunksPerDoc } 
       });
     } catch (e) {
       console.warn('semantic_search not available in Tauri, falling back to HTTP:', e);
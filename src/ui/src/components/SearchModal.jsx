@@ -48,6 +48,25 @@ function HighlightText({ text, query, maxLength = 200 }) {
   );
 }
 
+// Snippet from the search index, query terms wrapped in <mark></mark>
+function MarkedSnippet({ snippet }) {
+  const parts = snippet.split(/<mark>(.*?)<\/mark>/g);
+
+  return (
+    <span className="text-gray-500 dark:text-zinc-500">
+      {parts.map((part, i) =>
+        i % 2 === 1 ? (
+          <span key={i} className="text-gray-900 dark:text-zinc-100 font-medium border-b border-gray-300/60 dark:border-zinc-700/60 bg-yellow-100/50 dark:bg-yellow-500/20 px-0.5 rounded-[1px]">
+            {part}
+          </span>
+        ) : (
+          <span key={i}>{part}</span>
+        )
+      )}
+    </span>
+  );
+}
+
 function normalizeIdeaTitleFromContent(content, maxLength = 48) {
   const raw = String(content || '').trim();
   if (!raw) return '';
@@ -135,9 +154,13 @@ function SearchResultItem({ result, query, isSelected, onClick }) {
           {!isIdea && headingDisplay}
         </div>
         
-        {result.content && (
+        {(result.snippet || result.content) && (
           <div className="text-[12px] leading-relaxed line-clamp-2 mt-1 font-normal">
-            <HighlightText text={result.content} query={query} maxLength={120} />
+            {result.snippet ? (
+              <MarkedSnippet snippet={result.snippet} />
+            ) : (
+              <HighlightText text={result.content} query={query} maxLength={120} />
+            )}
           </div>
         )}
 
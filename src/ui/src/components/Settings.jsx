@@ -27,13 +27,15 @@ export function Settings() {
   const [indexStatus, setIndexStatus] = useState(null);
   const [loading, setLoading] = useState(false);
   const [indexBuilding, setIndexBuilding] = useState(false);
-  const [indexProgress, setIndexProgress] = useState(null); // { phase, current, total, percent, message }
+  const [indexCancelling, setIndexCancelling] = useState(false);
+  const [indexProgress, setIndexProgress] = useState(null); // { phase, current, total, percent, message, docsProcessed, docsTotal, chunksStored, chunksEmbedded, chunksTotal, etaMs }
   const [showApiKey, setShowApiKey] = useState(false);
   const [showAIApiKey, setShowAIApiKey] = useState(false);
   
   // Edit mode states
   const [isEditing, setIsEditing] = useState(false);
   const [editForm, setEditForm] = useState({
+    provider: 'openai',
     apiKey: '',
     apiBase: '',
     model: '',
@@ -66,8 +68,7 @@ export function Settings() {
     let unlisten = null;
     (async () => {
       try {
-        const { listen } = await import('@tauri-apps/api/event');
-        unlisten = await listen('index-progress', (event) => {
+        unlisten = await api.listenAppEvent('index-progress', (event) => {
           setIndexProgress(event.payload);
         });
       } catch (e) {
@@ -111,6 +112,7 @@ export function Settings() {
       // Initialize edit form with current values
       if (env) {
         setEditForm({
+          provider: env.embedding_provider || 'openai',
           apiKey: '', // Don't show actual key, user must re-enter
           apiBase: env.embedding_api_base || 'https://api.openai.com/v1',
           model: env.embedding_model || 'text-embedding-3-small',
@@ -132,6 +134,9 @@ export function Settings() {
         apiBase: aiConfig.apiBase || 'https://api.openai.com/v1',
         model: aiConfig.model || 'gpt-4o',
         prompt: aiConfig.prompt || aiConfig.defaultPrompt || '',
+        temperature: aiConfig.temperature ?? '',
+        maxTokens: aiConfig.maxTokens ?? '',
+        topP: aiConfig.topP ?? '',
       });
     }
   }, [aiConfig]);
@@ -183,6 +188,7 @@ export function Settings() {
     // Reset form to current values
     if (envInfo) {
       setEditForm({
+        provider: envInfo.embedding_provider || 'openai',
         apiKey: '',
         apiBase: envInfo.embedding_api_base || 'https://api.openai.com/v1',
         model: envInfo.embedding_model || 'text-embedding-3-small',
@@ -204,10 +210,18 @@ export function Settings() {
         apiBase: aiConfig.apiBase || 'https://api.openai.com/v1',
         model: aiConfig.model || 'gpt-4o',
         prompt: aiConfig.prompt || aiConfig.defaultPrompt || '',
+        temperature: aiConfig.temperature ?? '',
+        maxTokens: aiConfig.maxTokens ?? '',
+        topP: aiConfig.topP ?? '',
       });
     }
   }, [aiConfig]);
 
+  const optionalNumber = (value) => {
+    const trimmed = String(value ?? '').trim();
+    return trimmed === '' ? null : Number(trimmed);
+  };
+
   const handleSaveAIConfig = async () => {
     setSavingAI(true);
     try {
@@ -217,6 +231,10 @@ export function Settings() {
         apiBase: aiEditForm.apiBase || undefined,
         model: aiEditForm.model || undefined,
         prompt: aiEditForm.prompt || undefined,
+        // Empty clears the value so the provider default applies
+        temperature: optionalNumber(aiEditForm.temperature),
+        maxTokens: optionalNumber(aiEditForm.maxTokens),
+        topP: optionalNumber(aiEditForm.topP),
       });
       setIsEditingAI(false);
     } catch (err) {
@@ -235,6 +253,7 @@ export function Settings() {
     setSaving(true);
     try {
       await api.saveConfig({
+        provider: editForm.provider || undefined,
         apiKey: editForm.apiKey || undefined,
         apiBase: editForm.apiBase || undefined,
         model: editForm.model || undefined,
@@ -253,6 +272,7 @@ export function Settings() {
   const handleBuildIndex = async () => {
     if (indexBuilding) return;
     setIndexBuilding(true);
+    setIndexCancelling(false);
     setIndexProgress(null);
     try {
       await api.buildSearchIndex();
@@ -262,11 +282,22 @@ export function Settings() {
       alert(t('error.operationFailed') + ': ' + err.message);
     } finally {
       setIndexBuilding(false);
+      setIndexCancelling(false);
       // Clear progress after a short delay to show completion message
       setTimeout(() => setIndexProgress(null), 2000);
     }
   };
 
+  const handleCancelIndex = async () => {
+    setIndexCancelling(true);
+    try {
+      await api.cancelSearchIndex();
+    } catch (err) {
+      console.error('Failed to cancel index build:', err);
+      setIndexCancelling(false);
+    }
+  };
+
   const handleCleanIndex = async () => {
     if (!confirm(t('settings.confirmCleanIndex'))) return;
     try {
@@ -278,6 +309,17 @@ export function Settings() {
     }
   };
 
+  const handleResetCorruptIndex = async () => {
+    if (!confirm(t('settings.confirmResetCorruptIndex'))) return;
+    try {
+      await api.resetCorruptIndex();
+      await loadData();
+    } catch (err) {
+      console.error('Failed to reset index:', err);
+      alert(t('error.operationFailed') + ': ' + err.message);
+    }
+  };
+
   if (loading && !envInfo) {
     return (
       <div className="flex items-center justify-center h-full text-gray-400 dark:text-zinc-500">
@@ -332,6 +374,35 @@ export function Settings() {
         </div>
         
         <div className="bg-gray-50 dark:bg-zinc-900/50 rounded-lg overflow-hidden border border-gray-100 dark:border-zinc-800">
+          {/* Embedding Provider */}
+          <div className="px-6 py-4 border-b border-gray-200/60 dark:border-zinc-800 grid grid-cols-3 gap-4 items-center">
+            <div className="text-sm font-medium text-gray-500 dark:text-zinc-400">{t('settings.embeddingProvider')}</div>
+            <div className="col-span-2">
+              {isEditing ? (
+                <select
+                  value={editForm.provider}
+                  onChange={(e) => setEditForm(f => ({ ...f, provider: e.target.value }))}
+                  className="px-3 py-1.5 text-sm bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
+                >
+                  <option value="openai">OpenAI / Compatible</option>
+                  <option value="ollama">Ollama (Local)</option>
+                  {envInfo?.local_embeddings_available && (
+                    <option value="local">Built-in model (Offline)</option>
+                  )}
+                </select>
+              ) : (
+                <span className="text-sm text-gray-900 dark:text-zinc-200">
+                  {envInfo?.embedding_provider === 'ollama'
+                    ? 'Ollama (Local)'
+                    : envInfo?.embedding_provider === 'local' ? 'Built-in model (Offline)' : 'OpenAI / Compatible'}
+                  {envInfo?.embedding_provider !== 'local' && envInfo?.embedding_local && (
+                    <span className="ml-2 text-xs text-gray-400 dark:text-zinc-500">localhost</span>
+                  )}
+                </span>
+              )}
+            </div>
+          </div>
+
           {/* Embedding Model */}
           <div className="px-6 py-4 border-b border-gray-200/60 dark:border-zinc-800 grid grid-cols-3 gap-4 items-center">
             <div className="text-sm font-medium text-gray-500 dark:text-zinc-400">{t('settings.embeddingModel')}</div>
@@ -342,7 +413,9 @@ export function Settings() {
                   value={editForm.model}
                   onChange={(e) => setEditForm(f => ({ ...f, model: e.target.value }))}
                   className="w-full px-3 py-1.5 text-sm font-mono bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
-                  placeholder="text-embedding-3-small"
+                  placeholder={editForm.provider === 'ollama'
+                    ? 'nomic-embed-text'
+                    : editForm.provider === 'local' ? 'BAAI/bge-small-en-v1.5' : 'text-embedding-3-small'}
                 />
               ) : (
                 <span className="text-sm text-gray-900 dark:text-zinc-200 font-mono">
@@ -362,7 +435,7 @@ export function Settings() {
                   value={editForm.apiBase}
                   onChange={(e) => setEditForm(f => ({ ...f, apiBase: e.target.value }))}
                   className="w-full px-3 py-1.5 text-sm font-mono bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
-                  placeholder="https://api.openai.com/v1"
+                  placeholder={editForm.provider === 'ollama' ? 'http://localhost:11434' : 'https://api.openai.com/v1'}
                 />
               ) : (
                 <span className="text-sm text-gray-900 dark:text-zinc-200 font-mono break-all">
@@ -477,11 +550,14 @@ export function Settings() {
                   className="px-3 py-1.5 text-sm bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
                 >
                   <option value="openai">OpenAI / Compatible</option>
+                  <option value="anthropic">Anthropic</option>
                   <option value="ollama">Ollama (Local)</option>
                 </select>
               ) : (
                 <span className="text-sm text-gray-900 dark:text-zinc-200">
-                  {aiConfig?.provider === 'ollama' ? 'Ollama (Local)' : 'OpenAI / Compatible'}
+                  {aiConfig?.provider === 'ollama'
+                    ? 'Ollama (Local)'
+                    : aiConfig?.provider === 'anthropic' ? 'Anthropic' : 'OpenAI / Compatible'}
                 </span>
               )}
             </div>
@@ -517,7 +593,9 @@ export function Settings() {
                   value={aiEditForm.apiBase}
                   onChange={(e) => setAIEditForm(f => ({ ...f, apiBase: e.target.value }))}
                   className="w-full px-3 py-1.5 text-sm font-mono bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
-                  placeholder={aiEditForm.provider === 'ollama' ? 'http://localhost:11434/api' : 'https://api.openai.com/v1'}
+                  placeholder={aiEditForm.provider === 'ollama'
+                    ? 'http://localhost:11434/api'
+                    : aiEditForm.provider === 'anthropic' ? 'https://api.anthropic.com/v1' : 'https://api.openai.com/v1'}
                 />
               ) : (
                 <span className="text-sm text-gray-900 dark:text-zinc-200 font-mono break-all">
@@ -568,6 +646,74 @@ export function Settings() {
             </div>
           )}
           
+          {/* Temperature */}
+          <div className="px-6 py-4 border-b border-gray-200/60 dark:border-zinc-800 grid grid-cols-3 gap-4 items-center">
+            <div className="text-sm font-medium text-gray-500 dark:text-zinc-400">{t('settings.aiTemperature')}</div>
+            <div className="col-span-2">
+              {isEditingAI ? (
+                <input
+                  type="number"
+                  step="0.1"
+                  min="0"
+                  max={aiEditForm.provider === 'anthropic' ? '1' : '2'}
+                  value={aiEditForm.temperature}
+                  onChange={(e) => setAIEditForm(f => ({ ...f, temperature: e.target.value }))}
+                  className="w-40 px-3 py-1.5 text-sm font-mono bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
+                  placeholder={t('settings.aiParamDefault')}
+                />
+              ) : (
+                <span className="text-sm text-gray-900 dark:text-zinc-200 font-mono">
+                  {aiConfig?.temperature ?? <span className="text-gray-400 dark:text-zinc-500 italic">{t('settings.aiParamDefault')}</span>}
+                </span>
+              )}
+            </div>
+          </div>
+
+          {/* Max Tokens */}
+          <div className="px-6 py-4 border-b border-gray-200/60 dark:border-zinc-800 grid grid-cols-3 gap-4 items-center">
+            <div className="text-sm font-medium text-gray-500 dark:text-zinc-400">{t('settings.aiMaxTokens')}</div>
+            <div className="col-span-2">
+              {isEditingAI ? (
+                <input
+                  type="number"
+                  step="1"
+                  min="1"
+                  value={aiEditForm.maxTokens}
+                  onChange={(e) => setAIEditForm(f => ({ ...f, maxTokens: e.target.value }))}
+                  className="w-40 px-3 py-1.5 text-sm font-mono bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
+                  placeholder={t('settings.aiParamDefault')}
+                />
+              ) : (
+                <span className="text-sm text-gray-900 dark:text-zinc-200 font-mono">
+                  {aiConfig?.maxTokens ?? <span className="text-gray-400 dark:text-zinc-500 italic">{t('settings.aiParamDefault')}</span>}
+                </span>
+              )}
+            </div>
+          </div>
+
+          {/* Top P */}
+          <div className="px-6 py-4 border-b border-gray-200/60 dark:border-zinc-800 grid grid-cols-3 gap-4 items-center">
+            <div className="text-sm font-medium text-gray-500 dark:text-zinc-400">{t('settings.aiTopP')}</div>
+            <div className="col-span-2">
+              {isEditingAI ? (
+                <input
+                  type="number"
+                  step="0.05"
+                  min="0"
+                  max="1"
+                  value={aiEditForm.topP}
+                  onChange={(e) => setAIEditForm(f => ({ ...f, topP: e.target.value }))}
+                  className="w-40 px-3 py-1.5 text-sm font-mono bg-white dark:bg-zinc-950 border border-gray-200 dark:border-zinc-700 rounded-md focus:outline-none focus:ring-2 focus:ring-gray-200 dark:focus:ring-zinc-700 focus:border-gray-400 dark:focus:border-zinc-600 transition-all dark:text-zinc-200"
+                  placeholder={t('settings.aiParamDefault')}
+                />
+              ) : (
+                <span className="text-sm text-gray-900 dark:text-zinc-200 font-mono">
+                  {aiConfig?.topP ?? <span className="text-gray-400 dark:text-zinc-500 italic">{t('settings.aiParamDefault')}</span>}
+                </span>
+              )}
+            </div>
+          </div>
+
           {/* System Prompt */}
           <div className="px-6 py-4 grid grid-cols-3 gap-4">
             <div className="text-sm font-medium text-gray-500 dark:text-zinc-400 pt-1.5">{t('settings.aiPrompt')}</div>
@@ -780,9 +926,14 @@ export function Settings() {
             <div>
               <div className="text-sm text-gray-500 dark:text-zinc-400 mb-1">{t('settings.indexState')}</div>
               <div className="flex items-center gap-2">
-                <div className={`w-2.5 h-2.5 rounded-full ${indexStatus?.exists ? 'bg-green-500' : 'bg-red-400'}`} />
-                <span className="text-sm font-medium text-gray-700 dark:text-zinc-300">
-                  {indexStatus?.exists ? t('settings.ready') : t('settings.notBuilt')}
+                <div className={`w-2.5 h-2.5 rounded-full ${indexStatus?.corrupt ? 'bg-amber-500' : indexStatus?.exists ? 'bg-green-500' : 'bg-red-400'}`} />
+                <span
+                  className="text-sm font-medium text-gray-700 dark:text-zinc-300"
+                  title={indexStatus?.corruptReason || undefined}
+                >
+                  {indexStatus?.corrupt
+                    ? t('settings.indexCorrupt')
+                    : indexStatus?.exists ? t('settings.ready') : t('settings.notBuilt')}
                 </span>
               </div>
             </div>
@@ -798,6 +949,12 @@ export function Settings() {
             </div>
           </div>
 
+          {indexStatus?.rebuildNeeded && !indexBuilding && (
+            <div className="mb-4 p-3 rounded-lg border border-amber-200 dark:border-amber-900/60 bg-amber-50 dark:bg-amber-950/30 text-sm text-amber-800 dark:text-amber-300">
+              {t('settings.rebuildNeeded')}
+            </div>
+          )}
+
           {/* Progress bar */}
           {indexBuilding && indexProgress && (
             <div className="mb-4 p-4 bg-white dark:bg-zinc-950 rounded-lg border border-gray-200 dark:border-zinc-800">
@@ -820,7 +977,13 @@ export function Settings() {
                 {indexProgress.phase === 'embedding' && '🧠 生成向量中...'}
                 {indexProgress.phase === 'storing' && '💾 写入索引中...'}
                 {indexProgress.phase === 'done' && '✅ 完成！'}
-                {' '}({indexProgress.current}/{indexProgress.total})
+                {indexProgress.phase === 'cancelled' && '⏹ 已取消'}
+                {' '}({indexProgress.chunksTotal > 0
+                  ? `${indexProgress.chunksEmbedded}/${indexProgress.chunksTotal}`
+                  : indexProgress.docsTotal != null
+                    ? `${indexProgress.docsProcessed}/${indexProgress.docsTotal}`
+                    : `${indexProgress.current}/${indexProgress.total}`})
+                {indexProgress.etaMs > 0 && ` · ~${Math.ceil(indexProgress.etaMs / 1000)}s`}
               </div>
             </div>
           )}
@@ -841,13 +1004,23 @@ export function Settings() {
               {indexBuilding ? t('settings.building') : t('settings.rebuildIndex')}
             </button>
 
+            {indexBuilding && (
+              <button
+                onClick={handleCancelIndex}
+                disabled={indexCancelling}
+                className="px-4 py-2 rounded-md text-sm font-medium text-gray-700 dark:text-zinc-300 border border-gray-200 dark:border-zinc-700 hover:bg-gray-50 dark:hover:bg-zinc-800 transition-all disabled:opacity-50 disabled:cursor-wait"
+              >
+                {indexCancelling ? t('settings.cancellingBuild') : t('settings.cancelBuild')}
+              </button>
+            )}
+
             <button
-              onClick={handleCleanIndex}
+              onClick={indexStatus?.corrupt ? handleResetCorruptIndex : handleCleanIndex}
               disabled={indexBuilding}
               className="px-4 py-2 rounded-md text-sm font-medium text-red-600 dark:text-red-400 hover:bg-red-50 dark:hover:bg-red-900/20 border border-transparent hover:border-red-100 dark:hover:border-red-900/50 transition-all flex items-center gap-2 ml-auto disabled:opacity-50 disabled:cursor-not-allowed"
             >
               <TrashIcon className="w-4 h-4" />
-              {t('settings.cleanIndex')}
+              {indexStatus?.corrupt ? t('settings.resetCorruptIndex') : t('settings.cleanIndex')}
             </button>
           </div>
         </div>
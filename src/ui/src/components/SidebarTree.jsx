@@ -30,6 +30,7 @@ import {
 import { useTauriDrag } from '../hooks/useTauriDrag.jsx';
 import { Logo } from './Logo';
 import IdeaSidebar from './IdeaSidebar';
+import { getDocIndexStatus, listenAppEvent, listenDocIndexed } from '../api';
 
 // Simple dropdown menu for sidebar actions
 function SidebarDropdown({ items, children }) {
@@ -125,6 +126,7 @@ function SidebarItem({
   depth = 0,
   children,
   rightActions,
+  badge,
   isDragOver = false,
 }) {
   const itemRef = useRef(null);
@@ -177,7 +179,10 @@ function SidebarItem({
         )}
 
         <div className="flex-1 min-w-0 overflow-hidden">
-          <div className="truncate leading-5">{label}</div>
+          <div className="flex items-center gap-1.5 leading-5">
+            <span className="truncate">{label}</span>
+            {badge}
+          </div>
           {description && (
             <div className={`text-xs truncate font-normal ${isActive ? 'text-gray-500 dark:text-zinc-400' : 'text-gray-400 dark:text-zinc-500'}`}>
               {description}
@@ -257,6 +262,7 @@ function FolderTreeRenderer({
   onEditDocDescription,
   onRenameDoc,
   onDeleteDoc,
+  staleDocs,
 }) {
   const { t } = useTranslation();
   const isExpanded = expandedFolders.has(node.path);
@@ -341,6 +347,7 @@ function FolderTreeRenderer({
                     onEditDocDescription={onEditDocDescription}
                     onRenameDoc={onRenameDoc}
                     onDeleteDoc={onDeleteDoc}
+                    staleDocs={staleDocs}
                   />
                 ))}
                 {docs.length === 0 && node.children.length === 0 && (
@@ -360,6 +367,14 @@ function FolderTreeRenderer({
                       depth={depth + 1}
                       hasChildren={false}
                       isActive={selectedDoc?.rel_path === doc.rel_path}
+                      badge={staleDocs?.has(doc.rel_path) && (
+                        <span
+                          title={t('sidebar.staleTitle')}
+                          className="shrink-0 px-1 rounded-sm text-[10px] font-normal leading-4 bg-amber-100 text-amber-700 dark:bg-amber-900/40 dark:text-amber-300"
+                        >
+                          {t('sidebar.stale')}
+                        </span>
+                      )}
                       onClick={() => loadDoc(doc, { urlMode: 'push' })}
                       onContextMenu={(e) => onContextMenu(e, { type: 'doc', ...doc })}
                       rightActions={
@@ -426,6 +441,45 @@ export function SidebarTree({
   const folderTree = useMemo(() => buildFolderTree(folders), [folders]);
   const [activeDragItem, setActiveDragItem] = useState(null);
   const [isIdeasExpanded, setIsIdeasExpanded] = useState(true);
+  const [staleDocs, setStaleDocs] = useState(() => new Set());
+  const [indexVersion, setIndexVersion] = useState(0);
+
+  // Re-read the badges once indexing settles: after a build, or a burst of
+  // background re-indexing
+  useEffect(() => {
+    let timer = null;
+    const refresh = () => {
+      clearTimeout(timer);
+      timer = setTimeout(() => setIndexVersion((v) => v + 1), 1000);
+    };
+    const unlisteners = [
+      listenDocIndexed(refresh),
+      listenAppEvent('index-progress', (event) => {
+        if (event.payload?.phase === 'done' || event.payload?.phase === 'cancelled') refresh();
+      }),
+    ].map((promise) => promise.catch(() => null));
+    return () => {
+      clearTimeout(timer);
+      unlisteners.forEach((promise) => promise.then((unlisten) => unlisten?.()));
+    };
+  }, []);
+
+  // Docs changed since they were indexed, among those listed, in one call
+  useEffect(() => {
+    const paths = Object.values(folderDocs || {}).flat().map((doc) => doc.rel_path);
+    if (paths.length === 0) return undefined;
+    let cancelled = false;
+    getDocIndexStatus(paths)
+      .then((statuses) => {
+        if (!cancelled) {
+          setStaleDocs(new Set(statuses.filter((s) => s.stale).map((s) => s.path)));
+        }
+      })
+      .catch((err) => console.warn('[SidebarTree] Failed to read doc index status:', err));
+    return () => {
+      cancelled = true;
+    };
+  }, [folderDocs, indexVersion]);
 
   const buildIdeaRoute = useCallback((box, date) => {
     const safeBox = box ? encodeURIComponent(box) : '';
@@ -579,6 +633,7 @@ export function SidebarTree({
               onEditDocDescription={onEditDocDescription}
               onRenameDoc={onRenameDoc}
               onDeleteDoc={onDeleteDoc}
+              staleDocs={staleDocs}
             />
           ))}
           
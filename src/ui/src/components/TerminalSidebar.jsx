@@ -122,8 +122,7 @@ const ensureTauriListeners = async () => {
   if (tauriListenersPromise) return tauriListenersPromise;
   tauriListenersPromise = (async () => {
     try {
-      const { listen } = await import('@tauri-apps/api/event');
-      await listen('terminal-output', (event) => {
+      await api.listenAppEvent('terminal-output', (event) => {
         const payload = event.payload || {};
         const entry = termEntries.get(payload.id);
         if (!entry) return;
@@ -136,7 +135,7 @@ const ensureTauriListeners = async () => {
         outputCache.set(payload.id, { last: raw, ts: now });
         entry.term.write(raw);
       });
-      await listen('terminal-exit', (event) => {
+      await api.listenAppEvent('terminal-exit', (event) => {
         const payload = event.payload || {};
         if (!payload.id) return;
         updateTerminals((prev) => prev.map((terminal) => (
@@ -82,6 +82,16 @@ export function AgentInputBar({
           )}
         </div>
         <div className="flex items-center gap-2 flex-shrink-0">
+          {activeSession?.usage && (
+            <span
+              className="text-[11px] tabular-nums text-zinc-400 dark:text-zinc-500"
+              title={`${activeSession.usage.inputTokens} in / ${activeSession.usage.outputTokens} out`}
+            >
+              {activeSession.usage.estimatedCost == null
+                ? '$?'
+                : `~$${activeSession.usage.estimatedCost.toFixed(activeSession.usage.estimatedCost < 0.01 ? 4 : 2)}`}
+            </span>
+          )}
           <button
             type="button"
             className={`h-7 w-7 rounded-lg flex-shrink-0 flex items-center justify-center transition-all duration-200 ${
@@ -254,9 +254,10 @@ export const useAgentStreaming = ({
               requestId,
               ...(modelOverride ? { model: modelOverride } : {}),
             }
-          : modelOverride
-            ? { model: modelOverride }
-            : undefined;
+          : {
+              requestId,
+              ...(modelOverride ? { model: modelOverride } : {}),
+            };
 
       await streamFn(
         messagesForModel,
@@ -287,6 +288,20 @@ export const useAgentStreaming = ({
               setReasoningText('');
             }
           },
+          onUsage: (usage) => {
+            updateSession(sessionId, (session) => {
+              const previous = session.usage;
+              // One unpriced turn makes the session total unknown
+              const unknown = usage.estimatedCost == null || (previous && previous.estimatedCost == null);
+              return {
+                usage: {
+                  inputTokens: (previous?.inputTokens || 0) + usage.inputTokens,
+                  outputTokens: (previous?.outputTokens || 0) + usage.outputTokens,
+                  estimatedCost: unknown ? null : (previous?.estimatedCost || 0) + usage.estimatedCost,
+                },
+              };
+            });
+          },
           onReasoning: (delta) => {
             if (activeRequestIdRef.current !== requestId) return;
             setReasoningText((prev) => `${prev || ''}${delta}`);
@@ -609,6 +624,8 @@ export const useAgentStreaming = ({
         api.stopClaudeExec(sessionId).catch(() => {});
       } else if (activeSession?.agentId === 'opencode') {
         api.stopOpenCodeRun(sessionId).catch(() => {});
+      } else {
+        api.abortAIChat(activeRequestIdRef.current).catch(() => {});
       }
     }
     setIsGenerating(false);
@@ -54,6 +54,9 @@ export function AIContextProvider({ children }) {
         apiKeyMasked: cfg.api_key_masked,
         prompt: cfg.prompt || DEFAULT_PROMPT,
         defaultPrompt: cfg.default_prompt || DEFAULT_PROMPT,
+        temperature: cfg.temperature ?? null,
+        maxTokens: cfg.max_tokens ?? null,
+        topP: cfg.top_p ?? null,
       });
     } catch (err) {
       console.error('Failed to load AI config:', err);
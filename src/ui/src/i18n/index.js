@@ -32,5 +32,16 @@ i18n
     },
   });
 
+// Keep backend-generated messages (agent statuses, hints) in the same language
+const syncBackendLocale = (lng) => {
+  import('../api')
+    .then(({ setBackendLocale }) => setBackendLocale(lng))
+    .catch(() => {});
+};
+i18n.on('languageChanged', syncBackendLocale);
+if (i18n.language) {
+  syncBackendLocale(i18n.language);
+}
+
 export default i18n;
 